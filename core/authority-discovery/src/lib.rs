@@ -24,9 +24,10 @@
 //!
 //! 1. **Makes itself discoverable**
 //!
-//!    1. Retrieves its external addresses.
+//!    1. Retrieves its external addresses, or the addresses of its sentry nodes if it is only
+//!       reachable through them.
 //!
-//!    2. Adds its network peer id to the addresses.
+//!    2. Adds its network peer id to its external addresses.
 //!
 //!    3. Signs the above.
 //!
@@ -79,6 +80,9 @@ where
 	network: Arc<Network>,
 	/// Channel we receive Dht events on.
 	dht_event_rx: Receiver<DhtEvent>,
+	/// Addresses of the sentry nodes the authority is reachable through, published in place of its
+	/// own external addresses.
+	sentry_nodes: Option<Vec<libp2p::Multiaddr>>,
 
 	/// Interval to be proactive, publishing own addresses.
 	publish_interval: tokio_timer::Interval,
@@ -102,9 +106,13 @@ where
 	<Client as ProvideRuntimeApi>::Api: AuthorityDiscoveryApi<Block>,
 {
 	/// Return a new authority discovery.
+	///
+	/// If `sentry_nodes` is `Some`, the authority only connects to these nodes, and their addresses
+	/// are published instead of its own. They must contain the peer id of the sentry nodes.
 	pub fn new(
 		client: Arc<Client>,
 		network: Arc<Network>,
+		sentry_nodes: Option<Vec<libp2p::Multiaddr>>,
 		dht_event_rx: futures::sync::mpsc::Receiver<DhtEvent>,
 	) -> AuthorityDiscovery<Client, Network, Block> {
 		// Kademlia's default time-to-live for Dht records is 36h, republishing records every 24h. Given that a node
@@ -124,6 +132,7 @@ where
 			client,
 			network,
 			dht_event_rx,
+			sentry_nodes,
 			publish_interval,
 			query_interval,
 			address_cache,
@@ -134,17 +143,20 @@ where
 	fn publish_own_ext_addresses(&mut self) -> Result<()> {
 		let id = BlockId::hash(self.client.info().best_hash);
 
-		let addresses = self
-			.network
-			.external_addresses()
-			.into_iter()
-			.map(|a| {
-				a.with(libp2p::core::multiaddr::Protocol::P2p(
-					self.network.local_peer_id().into(),
-				))
-			})
-			.map(|a| a.to_vec())
-			.collect();
+		let addresses = match &self.sentry_nodes {
+			Some(sentry_nodes) => sentry_nodes.iter().map(|a| a.to_vec()).collect(),
+			None => self
+				.network
+				.external_addresses()
+				.into_iter()
+				.map(|a| {
+					a.with(libp2p::core::multiaddr::Protocol::P2p(
+						self.network.local_peer_id().into(),
+					))
+				})
+				.map(|a| a.to_vec())
+				.collect(),
+		};
 
 		let mut serialized_addresses = vec![];
 		schema::AuthorityAddresses { addresses }
@@ -616,7 +628,7 @@ mod tests {
 		let network: Arc<TestNetwork> = Arc::new(Default::default());
 
 		let mut authority_discovery =
-			AuthorityDiscovery::new(test_api, network.clone(), dht_event_rx);
+			AuthorityDiscovery::new(test_api, network.clone(), None, dht_event_rx);
 
 		authority_discovery.publish_own_ext_addresses().unwrap();
 
@@ -624,6 +636,30 @@ mod tests {
 		assert_eq!(network.put_value_call.lock().unwrap().len(), 1);
 	}
 
+	#[test]
+	fn publish_own_ext_addresses_publishes_sentry_nodes() {
+		let (_dht_event_tx, dht_event_rx) = futures::sync::mpsc::channel(1000);
+		let test_api = Arc::new(TestApi {});
+		let network: Arc<TestNetwork> = Arc::new(Default::default());
+		let sentry_node: libp2p::Multiaddr =
+			"/ip4/127.0.0.1/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV".parse().unwrap();
+
+		let mut authority_discovery = AuthorityDiscovery::new(
+			test_api,
+			network.clone(),
+			Some(vec![sentry_node.clone()]),
+			dht_event_rx,
+		);
+
+		authority_discovery.publish_own_ext_addresses().unwrap();
+
+		// Expect authority discovery to publish the address of the sentry node.
+		let (_, value) = network.put_value_call.lock().unwrap().pop().unwrap();
+		let signed = schema::SignedAuthorityAddresses::decode(value).unwrap();
+		let addresses = schema::AuthorityAddresses::decode(signed.addresses).unwrap().addresses;
+		assert_eq!(addresses, vec![sentry_node.to_vec()]);
+	}
+
 	#[test]
 	fn request_addresses_of_others_triggers_dht_get_query() {
 		let (_dht_event_tx, dht_event_rx) = futures::sync::mpsc::channel(1000);
@@ -631,7 +667,7 @@ mod tests {
 		let network: Arc<TestNetwork> = Arc::new(Default::default());
 
 		let mut authority_discovery =
-			AuthorityDiscovery::new(test_api, network.clone(), dht_event_rx);
+			AuthorityDiscovery::new(test_api, network.clone(), None, dht_event_rx);
 
 		authority_discovery.request_addresses_of_others().unwrap();
 
@@ -648,7 +684,7 @@ mod tests {
 		let network: Arc<TestNetwork> = Arc::new(Default::default());
 
 		let mut authority_discovery =
-			AuthorityDiscovery::new(test_api, network.clone(), dht_event_rx);
+			AuthorityDiscovery::new(test_api, network.clone(), None, dht_event_rx);
 
 		// Create sample dht event.
 
@@ -30,13 +30,14 @@ use client::ExecutionStrategies;
 use service::{
 	config::Configuration,
 	ServiceBuilderExport, ServiceBuilderImport, ServiceBuilderRevert,
-	RuntimeGenesis, ChainSpecExtension, PruningMode, ChainSpec,
+	RuntimeGenesis, ChainSpecExtension, PruningMode, DatabaseConfig, ChainSpec,
 };
 use network::{
 	self,
 	multiaddr::Protocol,
 	config::{
-		NetworkConfiguration, TransportConfig, NonReservedPeerMode, NodeKeyConfig, build_multiaddr
+		NetworkConfiguration, TransportConfig, NonReservedPeerMode, NodeKeyConfig, SentryMode, SyncMode,
+		build_multiaddr
 	},
 };
 use primitives::H256;
@@ -52,7 +53,8 @@ use structopt::{StructOpt, clap::AppSettings};
 #[doc(hidden)]
 pub use structopt::clap::App;
 use params::{
-	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, BuildSpecCmd,
+	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, ImportStateCmd,
+	ExportStateCmd, BuildSpecCmd,
 	NetworkConfigurationParams, MergeParameters, TransactionPoolParams,
 	NodeKeyParams, NodeKeyType, Cors,
 };
@@ -231,6 +233,12 @@ where
 		params::CoreParams::ImportBlocks(params) => ParseAndPrepare::ImportBlocks(
 			ParseAndPrepareImport { params, version }
 		),
+		params::CoreParams::ExportState(params) => ParseAndPrepare::ExportState(
+			ParseAndPrepareExportState { params, version }
+		),
+		params::CoreParams::ImportState(params) => ParseAndPrepare::ImportState(
+			ParseAndPrepareImportState { params, version }
+		),
 		params::CoreParams::PurgeChain(params) => ParseAndPrepare::PurgeChain(
 			ParseAndPreparePurge { params, version }
 		),
@@ -252,6 +260,10 @@ pub enum ParseAndPrepare<'a, CC, RP> {
 	ExportBlocks(ParseAndPrepareExport<'a>),
 	/// Command ready to import the chain.
 	ImportBlocks(ParseAndPrepareImport<'a>),
+	/// Command ready to export the state of a block.
+	ExportState(ParseAndPrepareExportState<'a>),
+	/// Command ready to import the state of a block.
+	ImportState(ParseAndPrepareImportState<'a>),
 	/// Command ready to purge the chain.
 	PurgeChain(ParseAndPreparePurge<'a>),
 	/// Command ready to revert the chain.
@@ -343,7 +355,9 @@ impl<'a> ParseAndPrepareExport<'a> {
 	{
 		let config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
 
-		info!("DB path: {}", config.database_path.display());
+		if let Some(path) = config.database.path() {
+			info!("DB path: {}", path.display());
+		}
 		let from = self.params.from.unwrap_or(1);
 		let to = self.params.to;
 		let json = self.params.json;
@@ -403,6 +417,80 @@ impl<'a> ParseAndPrepareImport<'a> {
 	}
 }
 
+/// Command ready to export the state of a block.
+pub struct ParseAndPrepareExportState<'a> {
+	params: ExportStateCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareExportState<'a> {
+	/// Runs the command and exports the state.
+	pub fn run_with_builder<C, G, E, F, B, S, Exit>(
+		self,
+		builder: F,
+		spec_factory: S,
+		exit: Exit,
+	) -> error::Result<()>
+	where S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+		F: FnOnce(Configuration<C, G, E>) -> Result<B, error::Error>,
+		B: ServiceBuilderExport,
+		C: Default,
+		G: RuntimeGenesis,
+		E: ChainSpecExtension,
+		Exit: IntoExit
+	{
+		let config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
+
+		if let Some(path) = config.database.path() {
+			info!("DB path: {}", path.display());
+		}
+		let file: Box<dyn Write> = match self.params.output {
+			Some(filename) => Box::new(File::create(filename)?),
+			None => Box::new(stdout()),
+		};
+
+		builder(config)?.export_state(exit.into_exit(), file, self.params.block.map(Into::into))?;
+		Ok(())
+	}
+}
+
+/// Command ready to import the state of a block.
+pub struct ParseAndPrepareImportState<'a> {
+	params: ImportStateCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareImportState<'a> {
+	/// Runs the command and imports the state.
+	pub fn run_with_builder<C, G, E, F, B, S, Exit>(
+		self,
+		builder: F,
+		spec_factory: S,
+		exit: Exit,
+	) -> error::Result<()>
+	where S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+		F: FnOnce(Configuration<C, G, E>) -> Result<B, error::Error>,
+		B: ServiceBuilderImport,
+		C: Default,
+		G: RuntimeGenesis,
+		E: ChainSpecExtension,
+		Exit: IntoExit
+	{
+		let config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
+
+		if let Some(path) = config.database.path() {
+			info!("DB path: {}", path.display());
+		}
+		let file: Box<dyn Read> = match self.params.input {
+			Some(filename) => Box::new(File::open(filename)?),
+			None => Box::new(stdin()),
+		};
+
+		builder(config)?.import_state(exit.into_exit(), file)?;
+		Ok(())
+	}
+}
+
 /// Command ready to purge the chain.
 pub struct ParseAndPreparePurge<'a> {
 	params: PurgeChainCmd,
@@ -422,7 +510,9 @@ impl<'a> ParseAndPreparePurge<'a> {
 		let config = create_config_with_db_path::<(), _, _, _>(
 			spec_factory, &self.params.shared_params, self.version
 		)?;
-		let db_path = config.database_path;
+		let db_path = config.database.path()
+			.expect("`create_config_with_db_path` always stores the database at a path; qed")
+			.to_path_buf();
 
 		if !self.params.yes {
 			print!("Are you sure to remove {:?}? [y/N]: ", &db_path);
@@ -575,6 +665,22 @@ fn fill_network_configuration(
 		config.non_reserved_mode = NonReservedPeerMode::Deny;
 	}
 
+	for addr in cli.sentry_nodes.iter().chain(cli.sentry.iter()) {
+		network::config::parse_str_addr(addr).map_err(|err| error::Error::Input(
+			format!("Invalid sentry-mode address {}, expected `<multiaddr>/p2p/<PeerId>`: {}", addr, err)
+		))?;
+	}
+
+	if !cli.sentry_nodes.is_empty() {
+		config.sentry_mode = SentryMode::Validator { sentry_nodes: cli.sentry_nodes };
+	} else if !cli.sentry.is_empty() {
+		config.sentry_mode = SentryMode::Sentry { validators: cli.sentry };
+	}
+
+	if cli.warp_sync {
+		config.sync_mode = SyncMode::Warp { backfill: cli.warp_sync_backfill };
+	}
+
 	for addr in cli.listen_addr.iter() {
 		let addr = addr.parse().ok().ok_or(error::Error::InvalidListenMultiaddress)?;
 		config.listen_addresses.push(addr);
@@ -672,16 +778,19 @@ where
 		|| keystore_path(&base_path, config.chain_spec.id())
 	);
 
-	config.database_path = db_path(&base_path, config.chain_spec.id());
-	config.database_cache_size = cli.database_cache_size;
+	config.database = match cli.database {
+		params::Database::RocksDb => DatabaseConfig::Path {
+			path: db_path(&base_path, config.chain_spec.id()),
+			cache_size: cli.database_cache_size.map(|size| size as usize),
+		},
+		params::Database::Memory => DatabaseConfig::InMemory,
+	};
 	config.state_cache_size = cli.state_cache_size;
 	config.pruning = match cli.pruning {
-		Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
+		Some(ref s) => parse_pruning_mode(s)?,
 		None => PruningMode::default(),
-		Some(s) => PruningMode::keep_blocks(s.parse()
-			.map_err(|_| error::Error::Input("Invalid pruning mode specified".to_string()))?
-		),
 	};
+	config.keep_block_bodies = cli.keep_block_bodies;
 
 	let is_dev = cli.shared_params.dev;
 
@@ -695,6 +804,8 @@ where
 		};
 
 	config.wasm_method = cli.wasm_method.into();
+	config.max_runtime_instances = cli.max_runtime_instances;
+	config.wasm_runtime_overrides = cli.wasm_runtime_overrides;
 
 	let exec = cli.execution_strategies;
 	let exec_all_or = |strat: params::ExecutionStrategy| exec.execution.unwrap_or(strat).into();
@@ -744,6 +855,7 @@ where
 	config.rpc_ws = Some(parse_address(&format!("{}:{}", ws_interface, 9944), cli.ws_port)?);
 
 	config.rpc_ws_max_connections = cli.ws_max_connections;
+	config.rpc_methods = cli.rpc_methods.into();
 	config.rpc_cors = cli.rpc_cors.unwrap_or_else(|| if is_dev {
 		log::warn!("Running in --dev mode, RPC CORS has been disabled.");
 		Cors::All
@@ -818,7 +930,10 @@ where
 	let base_path = base_path(cli, version);
 
 	let mut config = service::Configuration::default_with_spec(spec.clone());
-	config.database_path = db_path(&base_path, spec.id());
+	config.database = DatabaseConfig::Path {
+		path: db_path(&base_path, spec.id()),
+		cache_size: None,
+	};
 
 	Ok(config)
 }
@@ -842,6 +957,34 @@ fn parse_address(
 	Ok(address)
 }
 
+/// Parse a pruning mode: `archive`, a number of blocks, a duration (`12h`, `7d`) or a state
+/// size (`500MB`, `100GB`).
+fn parse_pruning_mode(mode: &str) -> error::Result<PruningMode> {
+	let invalid = || error::Error::Input(format!("Invalid pruning mode specified: {}", mode));
+	if mode == "archive" {
+		return Ok(PruningMode::ArchiveAll);
+	}
+	if let Ok(blocks) = mode.parse() {
+		return Ok(PruningMode::keep_blocks(blocks));
+	}
+
+	let split = mode.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+	let value: u64 = mode[..split].parse().map_err(|_| invalid())?;
+	let (multiplier, by_age) = match &mode[split..] {
+		"s" => (1, true),
+		"m" => (60, true),
+		"h" => (60 * 60, true),
+		"d" => (24 * 60 * 60, true),
+		"KB" => (1 << 10, false),
+		"MB" => (1 << 20, false),
+		"GB" => (1 << 30, false),
+		"TB" => (1 << 40, false),
+		_ => return Err(invalid()),
+	};
+	let value = value.checked_mul(multiplier).ok_or_else(invalid)?;
+	Ok(if by_age { PruningMode::keep_for(value) } else { PruningMode::keep_state_size(value) })
+}
+
 fn keystore_path(base_path: &Path, chain_id: &str) -> PathBuf {
 	let mut path = base_path.to_owned();
 	path.push("chains");
@@ -1058,4 +1201,17 @@ mod tests {
 		assert!(no_config_dir().is_ok());
 		assert!(some_config_dir("x".to_string()).is_ok());
 	}
+
+	#[test]
+	fn parses_pruning_modes() {
+		assert_eq!(parse_pruning_mode("archive").unwrap(), PruningMode::ArchiveAll);
+		assert_eq!(parse_pruning_mode("1000").unwrap(), PruningMode::keep_blocks(1000));
+		assert_eq!(parse_pruning_mode("12h").unwrap(), PruningMode::keep_for(12 * 60 * 60));
+		assert_eq!(parse_pruning_mode("7d").unwrap(), PruningMode::keep_for(7 * 24 * 60 * 60));
+		assert_eq!(parse_pruning_mode("500MB").unwrap(), PruningMode::keep_state_size(500 << 20));
+		assert!(parse_pruning_mode("").is_err());
+		assert!(parse_pruning_mode("d").is_err());
+		assert!(parse_pruning_mode("7w").is_err());
+		assert!(parse_pruning_mode("100000000000TB").is_err());
+	}
 }
@@ -50,6 +50,19 @@ arg_enum! {
 	pub enum WasmExecutionMethod {
 		// Uses an interpreter.
 		Interpreted,
+		// Uses a compiled runtime.
+		Compiled,
+	}
+}
+
+impl WasmExecutionMethod {
+	/// Returns list of variants that are not disabled by feature flags.
+	fn enabled_variants() -> Vec<&'static str> {
+		Self::variants()
+			.iter()
+			.cloned()
+			.filter(|&name| cfg!(feature = "wasmtime") || name != "Compiled")
+			.collect()
 	}
 }
 
@@ -57,6 +70,48 @@ impl Into<service::config::WasmExecutionMethod> for WasmExecutionMethod {
 	fn into(self) -> service::config::WasmExecutionMethod {
 		match self {
 			WasmExecutionMethod::Interpreted => service::config::WasmExecutionMethod::Interpreted,
+			#[cfg(feature = "wasmtime")]
+			WasmExecutionMethod::Compiled => service::config::WasmExecutionMethod::Compiled,
+			#[cfg(not(feature = "wasmtime"))]
+			WasmExecutionMethod::Compiled => panic!(
+				"Substrate must be compiled with \"wasmtime\" feature for compiled Wasm execution"
+			),
+		}
+	}
+}
+
+arg_enum! {
+	/// Database backend.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy)]
+	pub enum Database {
+		// RocksDB database stored in the chain's data directory.
+		RocksDb,
+		// In-memory database, lost when the node stops.
+		Memory,
+	}
+}
+
+arg_enum! {
+	/// Which RPC methods to expose.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy)]
+	pub enum RpcMethods {
+		// Expose unsafe methods only when listening on a local interface.
+		Auto,
+		// Only expose safe methods.
+		Safe,
+		// Expose every method.
+		Unsafe,
+	}
+}
+
+impl Into<service::config::RpcMethods> for RpcMethods {
+	fn into(self) -> service::config::RpcMethods {
+		match self {
+			RpcMethods::Auto => service::config::RpcMethods::Auto,
+			RpcMethods::Safe => service::config::RpcMethods::Safe,
+			RpcMethods::Unsafe => service::config::RpcMethods::Unsafe,
 		}
 	}
 }
@@ -116,6 +171,20 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "reserved-only")]
 	pub reserved_only: bool,
 
+	/// Specify a list of sentry node addresses. The node only connects to these nodes.
+	///
+	/// This is meant for validators that don't want to expose their address. The addresses of
+	/// the sentry nodes are published in place of the validator's own.
+	#[structopt(long = "sentry-nodes", value_name = "URL", conflicts_with = "sentry")]
+	pub sentry_nodes: Vec<String>,
+
+	/// Run as a sentry node of the validators with the given addresses.
+	///
+	/// A slot is always kept for these validators, and their addresses are never shared with the
+	/// rest of the network.
+	#[structopt(long = "sentry", value_name = "URL")]
+	pub sentry: Vec<String>,
+
 	/// Listen on this multiaddress.
 	#[structopt(long = "listen-addr", value_name = "LISTEN_ADDR")]
 	pub listen_addr: Vec<String>,
@@ -141,6 +210,17 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "no-mdns")]
 	pub no_mdns: bool,
 
+	/// Warp sync the chain instead of importing every block since genesis.
+	///
+	/// The proofs of the changes of the finality authorities are downloaded up to a recent
+	/// finalized block, then the state of that block. Only possible when the database is empty.
+	#[structopt(long = "warp-sync")]
+	pub warp_sync: bool,
+
+	/// After a warp sync, also download the blocks below the block it synced to.
+	#[structopt(long = "warp-sync-backfill", requires = "warp-sync")]
+	pub warp_sync_backfill: bool,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams
@@ -328,6 +408,18 @@ pub struct RunCmd {
 	#[structopt(long = "db-cache", value_name = "MiB")]
 	pub database_cache_size: Option<u32>,
 
+	/// Select the database backend.
+	///
+	/// An in-memory database is lost when the node stops, which suits ephemeral test networks.
+	#[structopt(
+		long = "database",
+		value_name = "DB",
+		possible_values = &Database::variants(),
+		case_insensitive = true,
+		default_value = "RocksDb"
+	)]
+	pub database: Database,
+
 	/// Specify the state cache size.
 	#[structopt(long = "state-cache-size", value_name = "Bytes", default_value = "67108864")]
 	pub state_cache_size: usize,
@@ -344,6 +436,19 @@ pub struct RunCmd {
 	#[structopt(long = "ws-external")]
 	pub ws_external: bool,
 
+	/// RPC methods to expose.
+	///
+	/// `Unsafe` methods, such as `state_traceBlock`, are expensive or expose node internals.
+	/// `Auto` exposes them only when the RPC servers listen on a local interface.
+	#[structopt(
+		long = "rpc-methods",
+		value_name = "METHOD SET",
+		possible_values = &RpcMethods::variants(),
+		case_insensitive = true,
+		default_value = "Auto"
+	)]
+	pub rpc_methods: RpcMethods,
+
 	/// Specify HTTP RPC server TCP port.
 	#[structopt(long = "rpc-port", value_name = "PORT")]
 	pub rpc_port: Option<u16>,
@@ -368,10 +473,20 @@ pub struct RunCmd {
 
 	/// Specify the pruning mode, a number of blocks to keep or 'archive'.
 	///
+	/// The state can also be kept for a duration of block time, e.g. '12h' or '7d', or up to a
+	/// size of the outdated state kept on disk, e.g. '500MB' or '100GB'. A database can't be
+	/// reopened as an archive once it was pruned, and vice versa.
+	///
 	/// Default is 256.
 	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
 	pub pruning: Option<String>,
 
+	/// Specify the number of finalized blocks to keep the bodies of.
+	///
+	/// Headers and justifications of older blocks are kept. Default is to keep all block bodies.
+	#[structopt(long = "keep-block-bodies", value_name = "COUNT")]
+	pub keep_block_bodies: Option<u32>,
+
 	/// The human-readable name for this node.
 	///
 	/// The node name will be reported to the telemetry server, if enabled.
@@ -409,12 +524,23 @@ pub struct RunCmd {
 	#[structopt(
 		long = "wasm-execution",
 		value_name = "METHOD",
-		possible_values = &WasmExecutionMethod::variants(),
+		possible_values = &WasmExecutionMethod::enabled_variants(),
 		case_insensitive = true,
 		default_value = "Interpreted"
 	)]
 	pub wasm_method: WasmExecutionMethod,
 
+	/// The maximum number of runtimes with different code to keep cached.
+	#[structopt(long = "max-runtime-instances", value_name = "COUNT")]
+	pub max_runtime_instances: Option<usize>,
+
+	/// Specify the path where local Wasm runtimes are stored.
+	///
+	/// These runtimes override on-chain runtimes when their `spec_name` and `spec_version`
+	/// match the version of the on-chain runtime.
+	#[structopt(long = "wasm-runtime-overrides", value_name = "PATH", parse(from_os_str))]
+	pub wasm_runtime_overrides: Option<PathBuf>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub execution_strategies: ExecutionStrategies,
@@ -666,7 +792,7 @@ pub struct ImportBlocksCmd {
 	#[structopt(
 		long = "wasm-execution",
 		value_name = "METHOD",
-		possible_values = &WasmExecutionMethod::variants(),
+		possible_values = &WasmExecutionMethod::enabled_variants(),
 		case_insensitive = true,
 		default_value = "Interpreted"
 	)]
@@ -685,6 +811,40 @@ pub struct ImportBlocksCmd {
 
 impl_get_log_filter!(ImportBlocksCmd);
 
+/// The `export-state` command used to export the state of a block.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ExportStateCmd {
+	/// Output file name or stdout if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Specify the block number of the state to export.
+	///
+	/// Default is finalized block.
+	#[structopt(long = "block", value_name = "BLOCK")]
+	pub block: Option<u32>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ExportStateCmd);
+
+/// The `import-state` command used to import the state of a block.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ImportStateCmd {
+	/// Input file or stdin if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub input: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ImportStateCmd);
+
 /// The `revert` command used revert the chain to a previous state.
 #[derive(Debug, StructOpt, Clone)]
 pub struct RevertCmd {
@@ -732,6 +892,12 @@ pub enum CoreParams<CC, RP> {
 	/// Import blocks from file.
 	ImportBlocks(ImportBlocksCmd),
 
+	/// Export the state of a block to a file.
+	ExportState(ExportStateCmd),
+
+	/// Import the state of a block from file.
+	ImportState(ImportStateCmd),
+
 	/// Revert chain to the previous state.
 	Revert(RevertCmd),
 
@@ -765,6 +931,16 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			ImportBlocksCmd::augment_clap(SubCommand::with_name("import-blocks"))
 				.about("Import blocks from file.")
 		)
+		.subcommand(
+			ExportStateCmd::augment_clap(SubCommand::with_name("export-state"))
+				.about("Export the state of a block to a file.")
+		)
+		.subcommand(
+			ImportStateCmd::augment_clap(SubCommand::with_name("import-state"))
+				.about("Import the state of a block from file into an empty database. \
+						The block becomes the best and finalized block, without its ancestry."
+					)
+		)
 		.subcommand(
 			RevertCmd::augment_clap(SubCommand::with_name("revert"))
 				.about("Revert chain to the previous state.")
@@ -783,6 +959,10 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 				CoreParams::ExportBlocks(ExportBlocksCmd::from_clap(matches)),
 			("import-blocks", Some(matches)) =>
 				CoreParams::ImportBlocks(ImportBlocksCmd::from_clap(matches)),
+			("export-state", Some(matches)) =>
+				CoreParams::ExportState(ExportStateCmd::from_clap(matches)),
+			("import-state", Some(matches)) =>
+				CoreParams::ImportState(ImportStateCmd::from_clap(matches)),
 			("revert", Some(matches)) => CoreParams::Revert(RevertCmd::from_clap(matches)),
 			("purge-chain", Some(matches)) =>
 				CoreParams::PurgeChain(PurgeChainCmd::from_clap(matches)),
@@ -799,6 +979,8 @@ impl<CC, RP> GetLogFilter for CoreParams<CC, RP> where CC: GetLogFilter {
 			CoreParams::BuildSpec(c) => c.get_log_filter(),
 			CoreParams::ExportBlocks(c) => c.get_log_filter(),
 			CoreParams::ImportBlocks(c) => c.get_log_filter(),
+			CoreParams::ExportState(c) => c.get_log_filter(),
+			CoreParams::ImportState(c) => c.get_log_filter(),
 			CoreParams::PurgeChain(c) => c.get_log_filter(),
 			CoreParams::Revert(c) => c.get_log_filter(),
 			CoreParams::Custom(c) => c.get_log_filter(),
@@ -14,7 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Client backend that uses RocksDB database as storage.
+//! Client backend that uses a key-value database as storage. The database is RocksDB by default,
+//! see `DatabaseSettingsSrc` for the alternatives.
 //!
 //! # Canonicality vs. Finality
 //!
@@ -34,9 +35,9 @@ mod storage_cache;
 mod utils;
 
 use std::sync::Arc;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use client::backend::NewBlockState;
 use client::blockchain::{well_known_cache_keys, HeaderBackend};
@@ -44,11 +45,13 @@ use client::{ForkBlocks, ExecutionStrategies};
 use client::backend::{StorageCollection, ChildStorageCollection};
 use client::error::{Result as ClientResult, Error as ClientError};
 use codec::{Decode, Encode};
-use hash_db::{Hasher, Prefix};
+use hash_db::{Hasher, HashDB, Prefix, EMPTY_PREFIX};
 use kvdb::{KeyValueDB, DBTransaction};
-use trie::{MemoryDB, PrefixedMemoryDB, prefixed_key};
+use trie::{MemoryDB, PrefixedMemoryDB, TrieMut, Layout, prefixed_key, trie_types::TrieDBMut};
 use parking_lot::{Mutex, RwLock};
-use primitives::{H256, Blake2Hasher, ChangesTrieConfiguration, convert_hash, traits::CodeExecutor};
+use primitives::{
+	H256, Blake2Hasher, ChangesTrieConfiguration, convert_hash, hexdisplay::HexDisplay, traits::CodeExecutor,
+};
 use primitives::storage::well_known_keys;
 use sr_primitives::{
 	generic::{BlockId, DigestItem}, Justification, StorageOverlay, ChildrenStorageOverlay,
@@ -60,7 +63,7 @@ use sr_primitives::traits::{
 use executor::RuntimeInfo;
 use state_machine::{
 	DBValue, ChangesTrieTransaction, ChangesTrieCacheAction, ChangesTrieBuildCache,
-	backend::Backend as StateBackend,
+	backend::{Backend as StateBackend, is_child_trie_killed},
 };
 use crate::utils::{Meta, db_err, meta_keys, read_db, read_meta};
 use client::leaves::{LeafSet, FinalizationDisplaced};
@@ -68,7 +71,7 @@ use client::children;
 use state_db::StateDb;
 use header_metadata::{CachedHeaderMetadata, HeaderMetadata, HeaderMetadataCache};
 use crate::storage_cache::{CachingState, SharedCache, new_shared_cache};
-use log::{trace, debug, warn};
+use log::{trace, debug, info, warn};
 pub use state_db::PruningMode;
 
 #[cfg(feature = "test-helpers")]
@@ -79,6 +82,11 @@ const MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR: u32 = 32768;
 
 /// Default value for storage cache child ratio.
 const DEFAULT_CHILD_RATIO: (usize, usize) = (1, 10);
+/// Maximal number of nodes of killed child tries removed from the state column per commit.
+const CHILD_TRIE_GC_BATCH: usize = 1024;
+/// Maximal number of block bodies deleted per commit when pruning the bodies left by a node that
+/// kept more of them.
+const BODY_PRUNING_BATCH: u32 = 10_000;
 
 /// DB-backed patricia trie state, transaction type is an overlay of changes to commit.
 pub type DbState = state_machine::TrieBackend<Arc<dyn state_machine::Storage<Blake2Hasher>>, Blake2Hasher>;
@@ -142,6 +150,18 @@ impl<B: BlockT> StateBackend<Blake2Hasher> for RefTrackingState<B> {
 		self.state.exists_child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.state.for_keys_with_prefix(prefix, f)
 	}
@@ -165,11 +185,16 @@ impl<B: BlockT> StateBackend<Blake2Hasher> for RefTrackingState<B> {
 		self.state.storage_root(delta)
 	}
 
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 		where
 			I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 	{
-		self.state.child_storage_root(storage_key, delta)
+		self.state.child_storage_root(storage_key, delta, killed)
 	}
 
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
@@ -191,16 +216,45 @@ impl<B: BlockT> StateBackend<Blake2Hasher> for RefTrackingState<B> {
 
 /// Database settings.
 pub struct DatabaseSettings {
-	/// Cache size in bytes. If `None` default is used.
-	pub cache_size: Option<usize>,
 	/// State cache size.
 	pub state_cache_size: usize,
 	/// Ratio of cache size dedicated to child tries.
 	pub state_cache_child_ratio: Option<(usize, usize)>,
-	/// Path to the database.
-	pub path: PathBuf,
 	/// Pruning mode.
 	pub pruning: PruningMode,
+	/// Number of finalized blocks to keep the bodies of. Bodies of older blocks are deleted, while
+	/// their headers and justifications are kept. `None` keeps all bodies.
+	pub keep_block_bodies: Option<u32>,
+	/// Where to find the database.
+	pub source: DatabaseSettingsSrc,
+}
+
+/// Where to find the database.
+#[derive(Clone)]
+pub enum DatabaseSettingsSrc {
+	/// RocksDB database at the given path. Requires the `kvdb-rocksdb` feature.
+	Path {
+		/// Path to the database.
+		path: PathBuf,
+		/// Cache size in MiB. If `None` default is used.
+		cache_size: Option<usize>,
+	},
+	/// Database kept in memory. Everything is lost when the node stops, which suits ephemeral
+	/// test networks.
+	InMemory,
+	/// An already open database, e.g. an alternative embedded store. It must have
+	/// `NUM_COLUMNS` columns.
+	Custom(Arc<dyn KeyValueDB>),
+}
+
+impl DatabaseSettingsSrc {
+	/// Path of the database, if it is stored on disk by RocksDB.
+	pub fn path(&self) -> Option<&Path> {
+		match self {
+			DatabaseSettingsSrc::Path { path, .. } => Some(path),
+			DatabaseSettingsSrc::InMemory | DatabaseSettingsSrc::Custom(_) => None,
+		}
+	}
 }
 
 /// Create an instance of db-backed client.
@@ -211,6 +265,7 @@ pub fn new_client<E, S, Block, RA>(
 	fork_blocks: ForkBlocks<Block>,
 	execution_strategies: ExecutionStrategies,
 	keystore: Option<primitives::traits::BareCryptoStorePtr>,
+	wasm_override: Option<client::WasmOverride>,
 ) -> Result<(
 		client::Client<
 			Backend<Block>,
@@ -228,7 +283,7 @@ pub fn new_client<E, S, Block, RA>(
 		S: BuildStorage,
 {
 	let backend = Arc::new(Backend::new(settings, CANONICALIZATION_DELAY)?);
-	let executor = client::LocalCallExecutor::new(backend.clone(), executor, keystore);
+	let executor = client::LocalCallExecutor::new(backend.clone(), executor, keystore, wasm_override);
 	Ok((
 		client::Client::new(backend.clone(), executor, genesis_storage, fork_blocks, execution_strategies)?,
 		backend,
@@ -442,6 +497,10 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	aux_ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
 	set_head: Option<BlockId<Block>>,
+	state_import: bool,
+	state_batches: Option<StateImport>,
+	state_nodes: Option<PrefixedMemoryDB<H>>,
+	historic_block: bool,
 }
 
 impl<Block: BlockT, H: Hasher> BlockImportOperation<Block, H> {
@@ -519,6 +578,46 @@ impl<Block> client::backend::BlockImportOperation<Block, Blake2Hasher>
 		Ok(root)
 	}
 
+	fn import_state_batch(
+		&mut self,
+		top: StorageOverlay,
+		children: ChildrenStorageOverlay
+	) -> ClientResult<()> {
+		self.state_batches.get_or_insert_with(StateImport::new).add(top, children)
+	}
+
+	fn import_state(
+		&mut self,
+		top: StorageOverlay,
+		children: ChildrenStorageOverlay
+	) -> ClientResult<H256> {
+		self.import_state_batch(top, children)?;
+		let (root, transaction) = self.state_batches.take()
+			.expect("batch was imported above; qed")
+			.finish()?;
+		self.db_updates = transaction;
+		self.state_import = true;
+		Ok(root)
+	}
+
+	fn import_state_nodes(&mut self, nodes: PrefixedMemoryDB<Blake2Hasher>) -> ClientResult<()> {
+		match self.state_nodes {
+			Some(ref mut state_nodes) => state_nodes.consolidate(nodes),
+			None => self.state_nodes = Some(nodes),
+		}
+		Ok(())
+	}
+
+	fn mark_state_imported(&mut self) -> ClientResult<()> {
+		self.state_import = true;
+		Ok(())
+	}
+
+	fn mark_historic(&mut self) -> ClientResult<()> {
+		self.historic_block = true;
+		Ok(())
+	}
+
 	fn update_changes_trie(
 		&mut self,
 		update: ChangesTrieTransaction<Blake2Hasher, NumberFor<Block>>,
@@ -560,6 +659,70 @@ impl<Block> client::backend::BlockImportOperation<Block, Blake2Hasher>
 struct StorageDb<Block: BlockT> {
 	pub db: Arc<dyn KeyValueDB>,
 	pub state_db: StateDb<Block::Hash, Vec<u8>>,
+	pub child_trie_gc: ChildTrieGc,
+}
+
+/// Key spaces of killed child tries whose nodes are still in the state column.
+///
+/// Removing a large child trie at once would stall the commit that kills it, so every state
+/// commit removes at most `CHILD_TRIE_GC_BATCH` nodes. The pending key spaces are persisted
+/// in the meta column to resume the collection after a restart.
+struct ChildTrieGc {
+	pending: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl ChildTrieGc {
+	fn load(db: &dyn KeyValueDB) -> ClientResult<Self> {
+		let pending = match db.get(columns::META, meta_keys::CHILD_TRIE_GC).map_err(db_err)? {
+			Some(encoded) => Vec::<Vec<u8>>::decode(&mut &encoded[..])
+				.map_err(|_| ClientError::Backend("Error decoding child trie gc queue".into()))?,
+			None => Vec::new(),
+		};
+		Ok(ChildTrieGc { pending: Mutex::new(pending.into_iter().collect()) })
+	}
+
+	/// Queue the given key spaces and remove the next batch of nodes in the transaction.
+	fn collect(
+		&self,
+		db: &dyn KeyValueDB,
+		transaction: &mut DBTransaction,
+		deleted_prefixes: Vec<Vec<u8>>,
+	) {
+		let mut pending = self.pending.lock();
+		if pending.is_empty() && deleted_prefixes.is_empty() {
+			return;
+		}
+		pending.extend(deleted_prefixes);
+
+		let mut budget = CHILD_TRIE_GC_BATCH;
+		while budget > 0 {
+			let exhausted = match pending.front() {
+				Some(prefix) => {
+					let mut keys = db.iter_from_prefix(columns::STATE, prefix).take(budget + 1);
+					let mut removed = 0;
+					while removed < budget {
+						match keys.next() {
+							Some((key, _)) => {
+								transaction.delete(columns::STATE, &key[..]);
+								removed += 1;
+							},
+							None => break,
+						}
+					}
+					budget -= removed;
+					keys.next().is_none()
+				},
+				None => break,
+			};
+			if !exhausted {
+				break;
+			}
+			pending.pop_front();
+		}
+
+		trace!(target: "db", "{} child trie key spaces left to collect", pending.len());
+		transaction.put(columns::META, meta_keys::CHILD_TRIE_GC, &pending.iter().collect::<Vec<_>>().encode());
+	}
 }
 
 impl<Block: BlockT> state_machine::Storage<Blake2Hasher> for StorageDb<Block> {
@@ -766,6 +929,7 @@ pub struct Backend<Block: BlockT> {
 	changes_trie_config: Mutex<Option<Option<ChangesTrieConfiguration>>>,
 	blockchain: BlockchainDb<Block>,
 	canonicalization_delay: u64,
+	keep_block_bodies: Option<u32>,
 	shared_cache: SharedCache<Block, Blake2Hasher>,
 	import_lock: Mutex<()>,
 }
@@ -779,39 +943,34 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 	}
 
 	fn new_inner(config: DatabaseSettings, canonicalization_delay: u64) -> ClientResult<Self> {
-		#[cfg(feature = "kvdb-rocksdb")]
-		let db = crate::utils::open_database(&config, columns::META, "full")?;
-		#[cfg(not(feature = "kvdb-rocksdb"))]
-		let db = {
-			log::warn!("Running without the RocksDB feature. The database will NOT be saved.");
-			Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS))
-		};
-		Self::from_kvdb(db as Arc<_>, canonicalization_delay, &config)
+		let db = crate::utils::open_database(&config.source, columns::META, "full")?;
+		Self::from_kvdb(db, canonicalization_delay, &config)
 	}
 
-	/// Create new memory-backed client backend for tests.
+	/// Create new client backend for tests. The database is selected by the `SUBSTRATE_TEST_DB`
+	/// environment variable and kept in memory by default.
 	#[cfg(any(test, feature = "test-helpers"))]
 	pub fn new_test(keep_blocks: u32, canonicalization_delay: u64) -> Self {
-		let db = Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
-		Self::new_test_db(keep_blocks, canonicalization_delay, db as Arc<_>)
+		let db_setting = Self::test_settings(keep_blocks, crate::utils::test_database_source());
+		Self::new_inner(db_setting, canonicalization_delay).expect("failed to create test-db")
 	}
 
 	/// Creates a client backend with test settings.
 	#[cfg(any(test, feature = "test-helpers"))]
 	pub fn new_test_db(keep_blocks: u32, canonicalization_delay: u64, db: Arc<dyn KeyValueDB>) -> Self {
+		let db_setting = Self::test_settings(keep_blocks, DatabaseSettingsSrc::Custom(db));
+		Self::new_inner(db_setting, canonicalization_delay).expect("failed to create test-db")
+	}
 
-		let db_setting = DatabaseSettings {
-			cache_size: None,
+	#[cfg(any(test, feature = "test-helpers"))]
+	fn test_settings(keep_blocks: u32, source: DatabaseSettingsSrc) -> DatabaseSettings {
+		DatabaseSettings {
 			state_cache_size: 16777216,
 			state_cache_child_ratio: Some((50, 100)),
-			path: Default::default(),
 			pruning: PruningMode::keep_blocks(keep_blocks),
-		};
-		Self::from_kvdb(
-			db,
-			canonicalization_delay,
-			&db_setting,
-		).expect("failed to create test-db")
+			keep_block_bodies: None,
+			source,
+		}
 	}
 
 	fn from_kvdb(
@@ -823,10 +982,16 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		let blockchain = BlockchainDb::new(db.clone())?;
 		let meta = blockchain.meta.clone();
 		let map_e = |e: state_db::Error<io::Error>| ::client::error::Error::from(format!("State database error: {:?}", e));
-		let state_db: StateDb<_, _> = StateDb::new(config.pruning.clone(), &StateMetaDb(&*db)).map_err(map_e)?;
+		let (state_db_init_commit, state_db): (_, StateDb<_, _>) =
+			StateDb::new(config.pruning.clone(), &StateMetaDb(&*db)).map_err(map_e)?;
+		let child_trie_gc = ChildTrieGc::load(&*db)?;
+		let mut transaction = DBTransaction::new();
+		apply_state_commit(&*db, &child_trie_gc, &mut transaction, state_db_init_commit);
+		db.write(transaction).map_err(db_err)?;
 		let storage_db = StorageDb {
 			db: db.clone(),
 			state_db,
+			child_trie_gc,
 		};
 		let offchain_storage = offchain::LocalStorage::new(db.clone());
 		let changes_tries_storage = DbChangesTrieStorage {
@@ -837,19 +1002,22 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			_phantom: Default::default(),
 		};
 
-		Ok(Backend {
+		let backend = Backend {
 			storage: Arc::new(storage_db),
 			offchain_storage,
 			changes_tries_storage,
 			changes_trie_config: Mutex::new(None),
 			blockchain,
 			canonicalization_delay,
+			keep_block_bodies: config.keep_block_bodies,
 			shared_cache: new_shared_cache(
 				config.state_cache_size,
 				config.state_cache_child_ratio.unwrap_or(DEFAULT_CHILD_RATIO),
 			),
 			import_lock: Default::default(),
-		})
+		};
+		backend.prune_block_body_backlog()?;
+		Ok(backend)
 	}
 
 	/// Returns in-memory blockchain that contains the same set of blocks that the self.
@@ -990,6 +1158,51 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		Ok((enacted, retracted))
 	}
 
+	// write the data of a block below the last finalized block whose ancestry is unknown,
+	// because the state of the finalized block was imported without it.
+	fn write_historic_block(
+		&self,
+		transaction: &mut DBTransaction,
+		pending_block: PendingBlock<Block>,
+	) -> ClientResult<()> {
+		let hash = pending_block.header.hash();
+		let number = *pending_block.header.number();
+		let finalized_number = self.blockchain.meta.read().finalized_number;
+		if number.is_zero() || number >= finalized_number {
+			return Err(ClientError::Backend(format!(
+				"Historic block #{} must be above genesis and below the finalized block #{}",
+				number,
+				finalized_number,
+			)));
+		}
+
+		let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
+		utils::insert_number_to_key_mapping(transaction, columns::KEY_LOOKUP, number, hash)?;
+		utils::insert_hash_to_key_mapping(transaction, columns::KEY_LOOKUP, number, hash)?;
+
+		let header_metadata = CachedHeaderMetadata::from(&pending_block.header);
+		self.blockchain.insert_header_metadata(header_metadata.hash, header_metadata);
+
+		transaction.put(columns::HEADER, &lookup_key, &pending_block.header.encode());
+		if let Some(body) = pending_block.body {
+			transaction.put(columns::BODY, &lookup_key, &body.encode());
+		}
+		if let Some(justification) = pending_block.justification {
+			transaction.put(columns::JUSTIFICATION, &lookup_key, &justification.encode());
+		}
+		Ok(())
+	}
+
+	fn ensure_only_genesis(&self) -> ClientResult<()> {
+		let meta = self.blockchain.meta.read();
+		if !meta.best_number.is_zero() || !meta.finalized_number.is_zero() {
+			return Err(ClientError::Backend(
+				"State can only be imported into a database that only contains the genesis block".into()
+			));
+		}
+		Ok(())
+	}
+
 	fn ensure_sequential_finalization(
 		&self,
 		header: &Block::Header,
@@ -1033,16 +1246,30 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		Ok((*hash, number, false, true))
 	}
 
+	/// Time of a block in seconds since the UNIX epoch, read from its state. The block may be
+	/// pending in the state db. Archive databases don't keep pending states in the state db, but
+	/// don't prune either.
+	fn block_timestamp(&self, header: &Block::Header) -> u64 {
+		let state = DbState::new(self.storage.clone(), *header.state_root());
+		match state.storage(&block_timestamp_key()) {
+			Ok(value) => decode_block_timestamp(value.as_ref().map(|v| &v[..])),
+			Err(e) => {
+				debug!(target: "db", "Error reading timestamp of block {}: {}", header.hash(), e);
+				0
+			},
+		}
+	}
+
 	// performs forced canonicaliziation with a delay after importing a non-finalized block.
 	fn force_delayed_canonicalize(
 		&self,
 		transaction: &mut DBTransaction,
 		hash: Block::Hash,
-		number: NumberFor<Block>,
+		header: &Block::Header,
 	)
 		-> ClientResult<()>
 	{
-		let number_u64 = number.saturated_into::<u64>();
+		let number_u64 = header.number().saturated_into::<u64>();
 		if number_u64 > self.canonicalization_delay {
 			let new_canonical = number_u64 - self.canonicalization_delay;
 
@@ -1050,18 +1277,21 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				return Ok(())
 			}
 
-			let hash = if new_canonical == number_u64 {
-				hash
+			let (hash, timestamp) = if new_canonical == number_u64 {
+				(hash, self.block_timestamp(header))
 			} else {
-				::client::blockchain::HeaderBackend::hash(&self.blockchain, new_canonical.saturated_into())?
-					.expect("existence of block with number `new_canonical` \
-						implies existence of blocks with all numbers before it; qed")
+				let header = ::client::blockchain::HeaderBackend::header(
+					&self.blockchain,
+					BlockId::Number(new_canonical.saturated_into()),
+				)?.expect("existence of block with number `new_canonical` \
+					implies existence of blocks with all numbers before it; qed");
+				(header.hash(), self.block_timestamp(&header))
 			};
 
 			trace!(target: "db", "Canonicalize block #{} ({:?})", new_canonical, hash);
-			let commit = self.storage.state_db.canonicalize_block(&hash)
+			let commit = self.storage.state_db.canonicalize_block(&hash, timestamp, &*self.storage)
 				.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
-			apply_state_commit(transaction, commit);
+			apply_state_commit(&*self.storage.db, &self.storage.child_trie_gc, transaction, commit);
 		};
 
 		Ok(())
@@ -1093,6 +1323,25 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			last_finalized_hash = block_hash;
 		}
 
+		// nodes of an imported state are stored at once, the block follows once they are all stored.
+		if let Some(mut nodes) = operation.state_nodes.take() {
+			self.ensure_only_genesis()?;
+			for (key, (value, rc)) in nodes.drain() {
+				if rc > 0 {
+					transaction.put_vec(columns::STATE, &key, value.to_vec());
+				}
+			}
+		}
+
+		let historic_block = if operation.historic_block {
+			operation.pending_block.take()
+		} else {
+			None
+		};
+		if let Some(pending_block) = historic_block {
+			self.write_historic_block(&mut transaction, pending_block)?;
+		}
+
 		let imported = if let Some(pending_block) = operation.pending_block {
 			let hash = pending_block.header.hash();
 			let parent_hash = *pending_block.header.parent_hash();
@@ -1101,7 +1350,26 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			// blocks are keyed by number + hash.
 			let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
 
-			let (enacted, retracted) = if pending_block.leaf_state.is_best() {
+			if operation.state_import {
+				self.ensure_only_genesis()?;
+				let state_root = pending_block.header.state_root();
+				let stored = HashDB::contains(&operation.db_updates, state_root, EMPTY_PREFIX) ||
+					state_machine::Storage::get(&*self.storage, state_root, EMPTY_PREFIX)
+						.map_err(ClientError::Backend)?
+						.is_some();
+				if !stored {
+					return Err(ClientError::InvalidStateRoot);
+				}
+			}
+
+			let (enacted, retracted) = if operation.state_import {
+				// the ancestry of the block is unknown, so there is no route to it.
+				let lookup_key = utils::number_and_hash_to_lookup_key(number, &hash)?;
+				transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+				transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
+				utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+				(Default::default(), Default::default())
+			} else if pending_block.leaf_state.is_best() {
 				self.set_head_with_transaction(&mut transaction, parent_hash, (number, hash))?
 			} else {
 				(Default::default(), Default::default())
@@ -1133,6 +1401,26 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				transaction.put(columns::META, meta_keys::GENESIS_HASH, hash.as_ref());
 			}
 
+			// the state of an imported block is either in the operation until it is committed, or
+			// was stored in earlier operations with `import_state_nodes`.
+			let state_timestamp = if operation.state_import {
+				let state_root = pending_block.header.state_root();
+				let value = if HashDB::contains(&operation.db_updates, state_root, EMPTY_PREFIX) {
+					trie::read_trie_value::<Layout<Blake2Hasher>, _>(
+						&operation.db_updates,
+						state_root,
+						&block_timestamp_key(),
+					).map_err(|e| format!("{}", e))
+				} else {
+					DbState::new(self.storage.clone(), *state_root).storage(&block_timestamp_key())
+				};
+				let value = value
+					.map_err(|e| ClientError::Backend(format!("Error reading block timestamp: {}", e)))?;
+				decode_block_timestamp(value.as_ref().map(|v| &v[..]))
+			} else {
+				0
+			};
+
 			let mut changeset: state_db::ChangeSet<Vec<u8>> = state_db::ChangeSet::default();
 			for (key, (val, rc)) in operation.db_updates.drain() {
 				if rc > 0 {
@@ -1141,13 +1429,20 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 					changeset.deleted.push(key);
 				}
 			}
+			changeset.deleted_prefixes = removed_child_trie_keyspaces(
+				&operation.old_state,
+				&operation.storage_updates,
+			)?;
 			let number_u64 = number.saturated_into::<u64>();
-			let commit = self.storage.state_db.insert_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
-				.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
-			apply_state_commit(&mut transaction, commit);
+			let commit = if operation.state_import {
+				self.storage.state_db.import_state(&hash, number_u64, state_timestamp, changeset)
+			} else {
+				self.storage.state_db.insert_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
+			}.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
+			apply_state_commit(&*self.storage.db, &self.storage.child_trie_gc, &mut transaction, commit);
 
-			// Check if need to finalize. Genesis is always finalized instantly.
-			let finalized = number_u64 == 0 || pending_block.leaf_state.is_final();
+			// Check if need to finalize. Genesis and imported states are always finalized instantly.
+			let finalized = number_u64 == 0 || operation.state_import || pending_block.leaf_state.is_final();
 
 			let header = &pending_block.header;
 			let is_best = pending_block.leaf_state.is_best();
@@ -1156,7 +1451,14 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			self.changes_tries_storage.commit(&mut transaction, changes_trie_updates);
 			let cache = operation.old_state.release(); // release state reference so that it can be finalized
 
-			if finalized {
+			if finalized && operation.state_import {
+				// there is no ancestry to finalize, but the leaves below the block are displaced.
+				let new_displaced = self.blockchain.leaves.write().finalize_height(number);
+				match finalization_displaced_leaves {
+					None => finalization_displaced_leaves = Some(new_displaced),
+					Some(ref mut displaced) => displaced.merge(new_displaced),
+				}
+			} else if finalized {
 				// TODO: ensure best chain contains this block.
 				self.ensure_sequential_finalization(header, Some(last_finalized_hash))?;
 				self.note_finalized(
@@ -1167,7 +1469,7 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				)?;
 			} else {
 				// canonicalize blocks which are old enough, regardless of finality.
-				self.force_delayed_canonicalize(&mut transaction, hash, *header.number())?
+				self.force_delayed_canonicalize(&mut transaction, hash, header)?
 			}
 
 			debug!(target: "db", "DB Commit {:?} ({}), best = {}", hash, number, is_best);
@@ -1186,7 +1488,7 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 
 			meta_updates.push((hash, number, pending_block.leaf_state.is_best(), finalized));
 
-			Some((number, hash, enacted, retracted, displaced_leaf, is_best, cache))
+			Some((number, hash, enacted, retracted, displaced_leaf, is_best, cache, operation.state_import))
 		} else {
 			None
 		};
@@ -1216,7 +1518,7 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			self.changes_tries_storage.commit_cache(changes_trie_cache_update);
 		}
 
-		if let Some((number, hash, enacted, retracted, displaced_leaf, is_best, mut cache)) = imported {
+		if let Some((number, hash, enacted, retracted, displaced_leaf, is_best, mut cache, state_import)) = imported {
 			if let Err(e) = write_result {
 				let mut leaves = self.blockchain.leaves.write();
 				let mut undo = leaves.undo();
@@ -1240,6 +1542,11 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				Some(number),
 				|| is_best,
 			);
+
+			if state_import {
+				// the cached state belongs to blocks that are not ancestors of the imported block.
+				self.shared_cache.lock().clear();
+			}
 		}
 
 		if let Some((enacted, retracted)) = cache_update {
@@ -1274,9 +1581,10 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			let lookup_key = utils::number_and_hash_to_lookup_key(f_num, f_hash.clone())?;
 			transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
 
-			let commit = self.storage.state_db.canonicalize_block(&f_hash)
+			let timestamp = self.block_timestamp(f_header);
+			let commit = self.storage.state_db.canonicalize_block(&f_hash, timestamp, &*self.storage)
 				.map_err(|e: state_db::Error<io::Error>| client::error::Error::from(format!("State database error: {:?}", e)))?;
-			apply_state_commit(transaction, commit);
+			apply_state_commit(&*self.storage.db, &self.storage.child_trie_gc, transaction, commit);
 
 			let changes_trie_config = self.changes_trie_config(parent_hash)?;
 			if let Some(changes_trie_config) = changes_trie_config {
@@ -1284,6 +1592,8 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			}
 		}
 
+		self.prune_block_body(transaction, f_hash, f_num)?;
+
 		let new_displaced = self.blockchain.leaves.write().finalize_height(f_num);
 		match displaced {
 			x @ &mut None => *x = Some(new_displaced),
@@ -1292,15 +1602,200 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 
 		Ok(())
 	}
+
+	// delete the body of the finalized block that leaves the window of kept bodies.
+	fn prune_block_body(
+		&self,
+		transaction: &mut DBTransaction,
+		f_hash: Block::Hash,
+		f_num: NumberFor<Block>,
+	) -> ClientResult<()> {
+		let keep_block_bodies: NumberFor<Block> = match self.keep_block_bodies {
+			Some(keep_block_bodies) => keep_block_bodies.into(),
+			None => return Ok(()),
+		};
+		if f_num < keep_block_bodies {
+			return Ok(());
+		}
+
+		let number = f_num - keep_block_bodies;
+		let hash = if number == f_num {
+			Some(f_hash)
+		} else {
+			self.blockchain.hash(number)?
+		};
+		if let Some(hash) = hash {
+			trace!(target: "db", "Pruning body of block #{} ({:?})", number, hash);
+			transaction.delete(columns::BODY, &utils::number_and_hash_to_lookup_key(number, hash)?);
+		}
+		transaction.put(columns::META, meta_keys::BODIES_PRUNED, &(number + One::one()).encode());
+		Ok(())
+	}
+
+	// delete the bodies of all the finalized blocks outside of the window of kept bodies, that
+	// were left by a node keeping more bodies, e.g. before `keep_block_bodies` was enabled.
+	fn prune_block_body_backlog(&self) -> ClientResult<()> {
+		let keep_block_bodies: NumberFor<Block> = match self.keep_block_bodies {
+			Some(keep_block_bodies) => keep_block_bodies.into(),
+			None => return Ok(()),
+		};
+		let finalized_number = self.blockchain.meta.read().finalized_number;
+		if finalized_number < keep_block_bodies {
+			return Ok(());
+		}
+
+		let last = finalized_number - keep_block_bodies;
+		let mut number: NumberFor<Block> = match self.storage.db.get(columns::META, meta_keys::BODIES_PRUNED)
+			.map_err(db_err)?
+		{
+			Some(raw) => Decode::decode(&mut &raw[..])
+				.map_err(|err| ClientError::Backend(format!("Error decoding pruned bodies: {}", err)))?,
+			None => Zero::zero(),
+		};
+		if number > last {
+			return Ok(());
+		}
+
+		info!(target: "db", "Pruning the bodies of blocks #{}..#{}", number, last);
+		while number <= last {
+			let mut transaction = DBTransaction::new();
+			for _ in 0..BODY_PRUNING_BATCH {
+				if number > last {
+					break;
+				}
+				if let Some(hash) = self.blockchain.hash(number)? {
+					transaction.delete(columns::BODY, &utils::number_and_hash_to_lookup_key(number, hash)?);
+				}
+				number = number + One::one();
+			}
+			transaction.put(columns::META, meta_keys::BODIES_PRUNED, &number.encode());
+			self.storage.db.write(transaction).map_err(db_err)?;
+		}
+		Ok(())
+	}
+}
+
+/// Returns the key spaces of the child tries of `state` that are removed, or replaced with a new
+/// child trie, by the top level storage `changes`.
+///
+/// Child tries without a unique id store their nodes in the key space of the top trie, so they are
+/// never returned.
+fn removed_child_trie_keyspaces<S: StateBackend<Blake2Hasher>>(
+	state: &S,
+	changes: &StorageCollection,
+) -> ClientResult<Vec<Vec<u8>>> {
+	let mut keyspaces = Vec::new();
+	for (key, change) in changes.iter().filter(|(key, _)| well_known_keys::is_child_storage_key(key)) {
+		let current = state.storage(key)
+			.map_err(|e| ClientError::Backend(format!("Error reading child trie value: {:?}", e)))?;
+		if let Some(current) = current {
+			if is_child_trie_killed::<Blake2Hasher>(Some(&current), change.as_ref().map(|v| &v[..])) {
+				let (_, unique_id) = trie::decode_child_trie_value::<Layout<Blake2Hasher>>(&current);
+				if !unique_id.is_empty() {
+					keyspaces.push(unique_id.to_vec());
+				}
+			}
+		}
+	}
+	Ok(keyspaces)
+}
+
+/// Trie nodes of a state built from its full contents, e.g. from a state snapshot, batch by
+/// batch.
+///
+/// The values of the child tries in the main trie are checked against the contents of the
+/// child tries once all batches are imported.
+struct StateImport {
+	nodes: PrefixedMemoryDB<Blake2Hasher>,
+	root: H256,
+	/// Values of the child tries in the main trie and roots built from their contents.
+	children: HashMap<Vec<u8>, (Option<Vec<u8>>, Option<Vec<u8>>)>,
 }
 
-fn apply_state_commit(transaction: &mut DBTransaction, commit: state_db::CommitSet<Vec<u8>>) {
+impl StateImport {
+	fn new() -> Self {
+		let mut nodes = PrefixedMemoryDB::default();
+		let mut root = H256::default();
+		TrieDBMut::<Blake2Hasher>::new(&mut nodes, &mut root);
+		StateImport { nodes, root, children: HashMap::new() }
+	}
+
+	/// Add a batch of key/value pairs. The value of a child trie must be added in the same or an
+	/// earlier batch than its contents.
+	fn add(&mut self, top: StorageOverlay, children: ChildrenStorageOverlay) -> ClientResult<()> {
+		for (key, value) in top.iter() {
+			if well_known_keys::is_child_storage_key(key) {
+				self.children.entry(key.clone()).or_default().0 = Some(value.clone());
+			}
+		}
+		self.root = trie::delta_trie_root::<Layout<Blake2Hasher>, _, _, _, _>(
+			&mut self.nodes,
+			self.root,
+			top.into_iter().map(|(k, v)| (k, Some(v))),
+		).map_err(|e| ClientError::Backend(format!("Error building state trie: {}", e)))?;
+
+		for (storage_key, child) in children {
+			let (value, child_root) = self.children.entry(storage_key.clone()).or_default();
+			let value = value.as_ref().ok_or_else(|| ClientError::Backend(
+				format!("Missing value of child trie {}", HexDisplay::from(&storage_key))
+			))?;
+			let (_, unique_id) = trie::decode_child_trie_value::<Layout<Blake2Hasher>>(value);
+			let root = child_root.take()
+				.unwrap_or_else(|| trie::default_child_trie_root::<Layout<Blake2Hasher>>(&storage_key));
+			*child_root = Some(trie::child_delta_trie_root::<Layout<Blake2Hasher>, _, _, _, _>(
+				&storage_key,
+				unique_id,
+				&mut self.nodes,
+				root,
+				child.into_iter().map(|(k, v)| (k, Some(v))),
+			).map_err(|e| ClientError::Backend(format!("Error building child trie: {}", e)))?);
+		}
+
+		// drop the nodes replaced by this batch.
+		self.nodes.purge();
+		Ok(())
+	}
+
+	/// Check the child tries and return the state root and the trie nodes.
+	fn finish(self) -> ClientResult<(H256, PrefixedMemoryDB<Blake2Hasher>)> {
+		for (value, child_root) in self.children.values() {
+			if let (Some(value), Some(child_root)) = (value, child_root) {
+				let (root, _) = trie::decode_child_trie_value::<Layout<Blake2Hasher>>(value);
+				if &child_root[..] != root {
+					return Err(ClientError::InvalidStateRoot);
+				}
+			}
+		}
+		Ok((self.root, self.nodes))
+	}
+}
+
+/// Storage key of the time of a block in its state: the `Now` value of the timestamp module, in
+/// milliseconds since the UNIX epoch.
+fn block_timestamp_key() -> [u8; 16] {
+	primitives::twox_128(b"Timestamp Now")
+}
+
+/// Decode the time of a block from its value at `block_timestamp_key`, in seconds since the UNIX
+/// epoch. Blocks without a time, e.g. of chains without the timestamp module, are treated as the
+/// oldest possible blocks by the state db when pruning by age.
+fn decode_block_timestamp(value: Option<&[u8]>) -> u64 {
+	value.and_then(|value| u64::decode(&mut &value[..]).ok()).map_or(0, |millis| millis / 1000)
+}
+
+fn apply_state_commit(
+	db: &dyn KeyValueDB,
+	child_trie_gc: &ChildTrieGc,
+	transaction: &mut DBTransaction,
+	commit: state_db::CommitSet<Vec<u8>>,
+) {
 	for (key, val) in commit.data.inserted.into_iter() {
 		transaction.put(columns::STATE, &key[..], &val);
 	}
 	for key in commit.data.deleted.into_iter() {
 		transaction.delete(columns::STATE, &key[..]);
 	}
+	child_trie_gc.collect(db, transaction, commit.data.deleted_prefixes);
 	for (key, val) in commit.meta.inserted.into_iter() {
 		transaction.put(columns::STATE_META, &key[..], &val);
 	}
@@ -1353,6 +1848,10 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			aux_ops: Vec::new(),
 			finalized_blocks: Vec::new(),
 			set_head: None,
+			state_import: false,
+			state_batches: None,
+			state_nodes: None,
+			historic_block: false,
 		})
 	}
 
@@ -1434,7 +1933,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			let mut transaction = DBTransaction::new();
 			match self.storage.state_db.revert_one() {
 				Some(commit) => {
-					apply_state_commit(&mut transaction, commit);
+					apply_state_commit(&*self.storage.db, &self.storage.child_trie_gc, &mut transaction, commit);
 					let removed = self.blockchain.header(BlockId::Number(best))?.ok_or_else(
 						|| client::error::Error::UnknownBlock(
 							format!("Error reverting to {}. Block hash not found.", best)))?;
@@ -1725,6 +2224,143 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn import_state_without_ancestry() {
+		let db = Backend::<Block>::new_test(2, 0);
+		insert_header(&db, 0, Default::default(), Vec::new(), Default::default());
+
+		let storage_key = b":child_storage:default:child".to_vec();
+		let child_root = BlakeTwo256::trie_root(vec![(vec![1], vec![2])]);
+		let top: StorageOverlay = vec![
+			(vec![1, 3, 5], vec![2, 4, 6]),
+			(storage_key.clone(), trie::encode_child_trie_value(child_root.as_ref(), &[7; 32])),
+		].into_iter().collect();
+		let children: ChildrenStorageOverlay = vec![
+			(storage_key.clone(), vec![(vec![1], vec![2])].into_iter().collect()),
+		].into_iter().collect();
+
+		let header = Header {
+			number: 10,
+			parent_hash: H256::from_low_u64_be(9),
+			state_root: BlakeTwo256::trie_root(top.clone().into_iter().collect()),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+
+		let mut op = db.begin_operation().unwrap();
+		assert_eq!(op.import_state(top, children).unwrap(), header.state_root);
+		op.set_block_data(header, None, None, NewBlockState::Final).unwrap();
+		db.commit_operation(op).unwrap();
+
+		let info = db.blockchain().info();
+		assert_eq!(info.best_hash, hash);
+		assert_eq!(info.finalized_hash, hash);
+		assert_eq!(db.blockchain().leaves().unwrap(), vec![hash]);
+
+		let state = db.state_at(BlockId::Number(10)).unwrap();
+		assert_eq!(state.storage(&[1, 3, 5]).unwrap(), Some(vec![2, 4, 6]));
+		assert_eq!(state.child_storage(&storage_key, &[1]).unwrap(), Some(vec![2]));
+	}
+
+	#[test]
+	fn import_state_from_stored_nodes() {
+		let db = Backend::<Block>::new_test(2, 0);
+		insert_header(&db, 0, Default::default(), Vec::new(), Default::default());
+
+		let mut nodes = PrefixedMemoryDB::<Blake2Hasher>::default();
+		let mut state_root = H256::default();
+		{
+			let mut trie = TrieDBMut::<Blake2Hasher>::new(&mut nodes, &mut state_root);
+			trie.insert(&[1, 3, 5], &[2, 4, 6]).unwrap();
+			trie.insert(&[1, 2, 3], &[9, 9, 9]).unwrap();
+		}
+		let header = Header {
+			number: 10,
+			parent_hash: H256::from_low_u64_be(9),
+			state_root,
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+
+		// the block can't be imported before its state.
+		let mut op = db.begin_operation().unwrap();
+		op.mark_state_imported().unwrap();
+		op.set_block_data(header.clone(), None, None, NewBlockState::Final).unwrap();
+		assert!(db.commit_operation(op).is_err());
+
+		let mut op = db.begin_operation().unwrap();
+		op.import_state_nodes(nodes).unwrap();
+		db.commit_operation(op).unwrap();
+		assert_eq!(db.blockchain().info().best_number, 0);
+
+		let mut op = db.begin_operation().unwrap();
+		op.mark_state_imported().unwrap();
+		op.set_block_data(header, None, None, NewBlockState::Final).unwrap();
+		db.commit_operation(op).unwrap();
+
+		let info = db.blockchain().info();
+		assert_eq!(info.best_hash, hash);
+		assert_eq!(info.finalized_hash, hash);
+		let state = db.state_at(BlockId::Number(10)).unwrap();
+		assert_eq!(state.storage(&[1, 3, 5]).unwrap(), Some(vec![2, 4, 6]));
+		assert_eq!(state.storage(&[1, 2, 3]).unwrap(), Some(vec![9, 9, 9]));
+	}
+
+	#[test]
+	fn import_historic_block_below_imported_state() {
+		let db = Backend::<Block>::new_test(2, 0);
+		insert_header(&db, 0, Default::default(), Vec::new(), Default::default());
+
+		let top: StorageOverlay = vec![(vec![1], vec![2])].into_iter().collect();
+		let historic = Header {
+			number: 9,
+			parent_hash: H256::from_low_u64_be(8),
+			state_root: Default::default(),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let historic_hash = historic.hash();
+		let header = Header {
+			number: 10,
+			parent_hash: historic_hash,
+			state_root: BlakeTwo256::trie_root(top.clone().into_iter().collect()),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+
+		let mut op = db.begin_operation().unwrap();
+		op.import_state(top, Default::default()).unwrap();
+		op.set_block_data(header, None, None, NewBlockState::Final).unwrap();
+		db.commit_operation(op).unwrap();
+
+		let mut op = db.begin_operation().unwrap();
+		op.set_block_data(historic.clone(), Some(Vec::new()), None, NewBlockState::Normal).unwrap();
+		op.mark_historic().unwrap();
+		db.commit_operation(op).unwrap();
+
+		// the block is stored, but the leaves and the best and finalized blocks are unchanged.
+		assert_eq!(db.blockchain().header(BlockId::Number(9)).unwrap(), Some(historic.clone()));
+		assert_eq!(db.blockchain().body(BlockId::Hash(historic_hash)).unwrap(), Some(Vec::new()));
+		let info = db.blockchain().info();
+		assert_eq!(info.best_hash, hash);
+		assert_eq!(info.finalized_hash, hash);
+		assert_eq!(db.blockchain().leaves().unwrap(), vec![hash]);
+
+		// blocks above the finalized block can't be historic.
+		let mut op = db.begin_operation().unwrap();
+		op.set_block_data(
+			Header { number: 11, parent_hash: hash, ..historic },
+			None,
+			None,
+			NewBlockState::Normal,
+		).unwrap();
+		op.mark_historic().unwrap();
+		assert!(db.commit_operation(op).is_err());
+	}
+
 	#[test]
 	fn delete_only_when_negative_rc() {
 		let _ = ::env_logger::try_init();
@@ -2361,4 +2997,132 @@ mod tests {
 			backend.commit_operation(op).unwrap_err();
 		}
 	}
+
+	#[test]
+	fn block_bodies_are_pruned_on_finalization() {
+		let mut settings = Backend::<Block>::test_settings(10, crate::utils::test_database_source());
+		settings.keep_block_bodies = Some(2);
+		let backend = Backend::<Block>::new(settings, 0).unwrap();
+
+		let mut parent_hash = Default::default();
+		let mut hashes = Vec::new();
+		for number in 0..5 {
+			let header = Header {
+				number,
+				parent_hash,
+				state_root: BlakeTwo256::trie_root(Vec::new()),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			parent_hash = header.hash();
+			hashes.push(parent_hash);
+
+			let block_id = if number == 0 {
+				BlockId::Hash(Default::default())
+			} else {
+				BlockId::Number(number - 1)
+			};
+			let mut op = backend.begin_operation().unwrap();
+			backend.begin_state_operation(&mut op, block_id).unwrap();
+			op.set_block_data(header, Some(vec![number.into()]), None, NewBlockState::Best).unwrap();
+			backend.commit_operation(op).unwrap();
+		}
+
+		for number in 1..5 {
+			backend.finalize_block(BlockId::Hash(hashes[number]), Some(vec![number as u8])).unwrap();
+		}
+
+		let bc = backend.blockchain();
+		for number in 0..5 {
+			let id = BlockId::Hash(hashes[number]);
+			assert_eq!(bc.body(id).unwrap().is_some(), number >= 3);
+			assert!(bc.header(id).unwrap().is_some());
+		}
+		assert_eq!(bc.justification(BlockId::Hash(hashes[1])).unwrap(), Some(vec![1]));
+	}
+
+	#[test]
+	fn block_bodies_backlog_is_pruned_on_open() {
+		let db = crate::utils::test_database();
+		let settings = |keep_block_bodies| {
+			let mut settings = Backend::<Block>::test_settings(10, DatabaseSettingsSrc::Custom(db.clone()));
+			settings.keep_block_bodies = keep_block_bodies;
+			settings
+		};
+
+		let mut hashes = Vec::new();
+		{
+			let backend = Backend::<Block>::new(settings(None), 0).unwrap();
+			let mut parent_hash = Default::default();
+			for number in 0..6 {
+				let header = Header {
+					number,
+					parent_hash,
+					state_root: BlakeTwo256::trie_root(Vec::new()),
+					digest: Default::default(),
+					extrinsics_root: Default::default(),
+				};
+				parent_hash = header.hash();
+				hashes.push(parent_hash);
+
+				let block_id = if number == 0 {
+					BlockId::Hash(Default::default())
+				} else {
+					BlockId::Number(number - 1)
+				};
+				let mut op = backend.begin_operation().unwrap();
+				backend.begin_state_operation(&mut op, block_id).unwrap();
+				op.set_block_data(header, Some(vec![number.into()]), None, NewBlockState::Best).unwrap();
+				backend.commit_operation(op).unwrap();
+			}
+			for number in 1..5 {
+				backend.finalize_block(BlockId::Hash(hashes[number]), None).unwrap();
+			}
+		}
+
+		// Block #4 is finalized, so the bodies of blocks #0 to #2 are deleted when reopened.
+		let backend = Backend::<Block>::new(settings(Some(2)), 0).unwrap();
+		let bc = backend.blockchain();
+		for number in 0..6 {
+			assert_eq!(bc.body(BlockId::Hash(hashes[number])).unwrap().is_some(), number >= 3);
+		}
+
+		// The pruning continues from there on finalization.
+		backend.finalize_block(BlockId::Hash(hashes[5]), None).unwrap();
+		assert!(bc.body(BlockId::Hash(hashes[3])).unwrap().is_none());
+		assert!(bc.body(BlockId::Hash(hashes[4])).unwrap().is_some());
+	}
+
+	#[test]
+	fn killed_child_tries_are_collected_incrementally() {
+		let db = crate::utils::test_database();
+		let mut transaction = DBTransaction::new();
+		for i in 0..(CHILD_TRIE_GC_BATCH as u32 * 2 + 10) {
+			let mut key = b"killed".to_vec();
+			key.extend(i.encode());
+			transaction.put(columns::STATE, &key, &[1]);
+		}
+		transaction.put(columns::STATE, b"kept", &[1]);
+		db.write(transaction).unwrap();
+		let remaining = |db: &Arc<dyn KeyValueDB>| db.iter_from_prefix(columns::STATE, b"killed").count();
+
+		let gc = ChildTrieGc::load(&*db).unwrap();
+		let mut transaction = DBTransaction::new();
+		gc.collect(&*db, &mut transaction, vec![b"killed".to_vec()]);
+		db.write(transaction).unwrap();
+		assert_eq!(remaining(&db), CHILD_TRIE_GC_BATCH + 10);
+
+		// the queue survives a restart
+		let gc = ChildTrieGc::load(&*db).unwrap();
+		assert_eq!(gc.pending.lock().len(), 1);
+		for _ in 0..2 {
+			let mut transaction = DBTransaction::new();
+			gc.collect(&*db, &mut transaction, Vec::new());
+			db.write(transaction).unwrap();
+		}
+		assert_eq!(remaining(&db), 0);
+		assert!(gc.pending.lock().is_empty());
+		assert!(ChildTrieGc::load(&*db).unwrap().pending.lock().is_empty());
+		assert!(db.get(columns::STATE, b"kept").unwrap().is_some());
+	}
 }
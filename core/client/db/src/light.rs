@@ -73,27 +73,19 @@ impl<Block> LightStorage<Block>
 		Self::new_inner(config)
 	}
 
-	#[cfg(feature = "kvdb-rocksdb")]
 	fn new_inner(config: DatabaseSettings) -> ClientResult<Self> {
-		let db = crate::utils::open_database(&config, columns::META, "light")?;
-		Self::from_kvdb(db as Arc<_>)
+		let db = crate::utils::open_database(&config.source, columns::META, "light")?;
+		Self::from_kvdb(db)
 	}
 
-	#[cfg(not(feature = "kvdb-rocksdb"))]
-	fn new_inner(_config: DatabaseSettings) -> ClientResult<Self> {
-		log::warn!("Running without the RocksDB feature. The database will NOT be saved.");
-		let db = Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
-		Self::from_kvdb(db as Arc<_>)
-	}
-
-	/// Create new memory-backed `LightStorage` for tests.
+	/// Create new `LightStorage` for tests. The database is selected by the `SUBSTRATE_TEST_DB`
+	/// environment variable and kept in memory by default.
 	#[cfg(any(test, feature = "test-helpers"))]
 	pub fn new_test() -> Self {
-		use utils::NUM_COLUMNS;
-
-		let db = Arc::new(::kvdb_memorydb::create(NUM_COLUMNS));
+		let db = crate::utils::open_database(&crate::utils::test_database_source(), columns::META, "light")
+			.expect("failed to open test-db");
 
-		Self::from_kvdb(db as Arc<_>).expect("failed to create test-db")
+		Self::from_kvdb(db).expect("failed to create test-db")
 	}
 
 	fn from_kvdb(db: Arc<dyn KeyValueDB>) -> ClientResult<Self> {
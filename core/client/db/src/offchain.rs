@@ -40,11 +40,11 @@ impl std::fmt::Debug for LocalStorage {
 }
 
 impl LocalStorage {
-	/// Create new offchain storage for tests (backed by memorydb)
+	/// Create new offchain storage for tests. The database is selected by the `SUBSTRATE_TEST_DB`
+	/// environment variable and kept in memory by default.
 	#[cfg(any(test, feature = "test-helpers"))]
 	pub fn new_test() -> Self {
-		let db = Arc::new(::kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
-		Self::new(db as _)
+		Self::new(crate::utils::test_database())
 	}
 
 	/// Create offchain local storage with given `KeyValueDB` backend.
@@ -22,7 +22,8 @@ use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard};
 use linked_hash_map::{LinkedHashMap, Entry};
 use hash_db::Hasher;
 use sr_primitives::traits::{Block as BlockT, Header};
-use state_machine::{backend::Backend as StateBackend, TrieBackend};
+use primitives::storage::well_known_keys;
+use state_machine::{backend::{Backend as StateBackend, is_child_trie_killed}, TrieBackend};
 use log::trace;
 use super::{StorageCollection, ChildStorageCollection};
 use std::hash::Hash as StdHash;
@@ -144,6 +145,19 @@ impl<K: EstimateSize + Eq + StdHash, V: EstimateSize> LRUMap<K, V> {
 
 }
 
+impl<V: EstimateSize> LRUMap<ChildStorageKey, V> {
+	/// Remove all entries of the child trie with the given storage key.
+	fn remove_child_trie(&mut self, storage_key: &[u8]) {
+		let keys: Vec<_> = self.0.keys()
+			.filter(|(sk, _)| &sk[..] == storage_key)
+			.cloned()
+			.collect();
+		for k in keys {
+			self.remove(&k);
+		}
+	}
+}
+
 impl<B: BlockT, H: Hasher> Cache<B, H> {
 	/// Returns the used memory size of the storage cache in bytes.
 	pub fn used_storage_cache_size(&self) -> usize {
@@ -170,6 +184,9 @@ impl<B: BlockT, H: Hasher> Cache<B, H> {
 					for a in &m.storage {
 						trace!("Reverting enacted key {:?}", a);
 						self.lru_storage.remove(a);
+						if well_known_keys::is_child_storage_key(a) {
+							self.lru_child_storage.remove_child_trie(a);
+						}
 					}
 					for a in &m.child_storage {
 						trace!("Reverting enacted child key {:?}", a);
@@ -190,6 +207,9 @@ impl<B: BlockT, H: Hasher> Cache<B, H> {
 					for a in &m.storage {
 						trace!("Retracted key {:?}", a);
 						self.lru_storage.remove(a);
+						if well_known_keys::is_child_storage_key(a) {
+							self.lru_child_storage.remove_child_trie(a);
+						}
 					}
 					for a in &m.child_storage {
 						trace!("Retracted child key {:?}", a);
@@ -203,13 +223,18 @@ impl<B: BlockT, H: Hasher> Cache<B, H> {
 		}
 		if clear {
 			// We don't know anything about the block; clear everything
-			trace!("Wiping cache");
-			self.lru_storage.clear();
-			self.lru_child_storage.clear();
-			self.lru_hashes.clear();
-			self.modifications.clear();
+			self.clear();
 		}
 	}
+
+	/// Remove all cached data.
+	pub fn clear(&mut self) {
+		trace!("Wiping cache");
+		self.lru_storage.clear();
+		self.lru_child_storage.clear();
+		self.lru_hashes.clear();
+		self.modifications.clear();
+	}
 }
 
 pub type SharedCache<B, H> = Arc<Mutex<Cache<B, H>>>;
@@ -352,6 +377,20 @@ impl<H: Hasher, B: BlockT> CacheChanges<H, B> {
 			}
 			let mut modifications = HashSet::new();
 			let mut child_modifications = HashSet::new();
+			if is_best {
+				// A child trie that is removed or replaced takes all its cached values with it.
+				// The previous value of the child trie is not always cached, in which case the
+				// child trie is treated as replaced.
+				for (k, v) in changes.iter().filter(|(k, _)| well_known_keys::is_child_storage_key(k)) {
+					let killed = cache.lru_storage.get(k).map_or(true, |current| is_child_trie_killed::<H>(
+						current.as_ref().map(|v| &v[..]),
+						v.as_ref().map(|v| &v[..]),
+					));
+					if killed {
+						cache.lru_child_storage.remove_child_trie(k);
+					}
+				}
+			}
 			child_changes.into_iter().for_each(|(sk, changes)|
 				for (k, v) in changes.into_iter() {
 					let k = (sk.clone(), k);
@@ -450,7 +489,7 @@ impl<H: Hasher, S: StateBackend<H>, B: BlockT> CachingState<H, S, B> {
 				}
 			}
 			if let Some(child_key) = child_key {
-				if m.child_storage.contains(child_key) {
+				if m.child_storage.contains(child_key) || m.storage.contains(&child_key.0) {
 					trace!("Cache lookup skipped for {:?}: modified in a later block", child_key);
 					return false;
 				}
@@ -538,6 +577,18 @@ impl<H: Hasher, S: StateBackend<H>, B: BlockT> StateBackend<H> for CachingState<
 		self.state.exists_child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.state.for_keys_with_prefix(prefix, f)
 	}
@@ -562,12 +613,17 @@ impl<H: Hasher, S: StateBackend<H>, B: BlockT> StateBackend<H> for CachingState<
 		self.state.storage_root(delta)
 	}
 
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 		where
 			I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 			H::Out: Ord
 	{
-		self.state.child_storage_root(storage_key, delta)
+		self.state.child_storage_root(storage_key, delta, killed)
 	}
 
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
@@ -658,6 +714,43 @@ mod tests {
 		assert!(s.storage(&key).unwrap().is_none());
 	}
 
+	#[test]
+	fn killed_child_trie_is_removed_from_cache() {
+		let root_parent = H256::random();
+		let storage_key = b":child_storage:default:child".to_vec();
+		let key = H256::random()[..].to_vec();
+		let h0 = H256::random();
+		let h1 = H256::random();
+
+		let shared = new_shared_cache::<Block, Blake2Hasher>(256*1024, (0,1));
+
+		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(root_parent));
+		s.cache.sync_cache(
+			&[],
+			&[],
+			vec![(storage_key.clone(), Some(vec![1; 64]))],
+			vec![(storage_key.clone(), vec![(key.clone(), Some(vec![2]))])],
+			Some(h0.clone()),
+			Some(0),
+			|| true,
+		);
+
+		let mut s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h0.clone()));
+		assert_eq!(s.child_storage(&storage_key, &key).unwrap(), Some(vec![2]));
+		s.cache.sync_cache(
+			&[],
+			&[],
+			vec![(storage_key.clone(), None)],
+			vec![],
+			Some(h1.clone()),
+			Some(1),
+			|| true,
+		);
+
+		let s = CachingState::new(InMemory::<Blake2Hasher>::default(), shared.clone(), Some(h1));
+		assert_eq!(s.child_storage(&storage_key, &key).unwrap(), None);
+	}
+
 	#[test]
 	fn should_track_used_size_correctly() {
 		let root_parent = H256::random();
@@ -17,7 +17,6 @@
 //! Db-based backend utility structures and functions, used by both
 //! full and light storages.
 
-#[cfg(feature = "kvdb-rocksdb")]
 use std::sync::Arc;
 use std::{io, convert::TryInto};
 
@@ -34,8 +33,7 @@ use sr_primitives::traits::{
 	Block as BlockT, Header as HeaderT, Zero,
 	UniqueSaturatedFrom, UniqueSaturatedInto,
 };
-#[cfg(feature = "kvdb-rocksdb")]
-use crate::DatabaseSettings;
+use crate::DatabaseSettingsSrc;
 
 /// Number of columns in the db. Must be the same for both full && light dbs.
 /// Otherwise RocksDb will fail to open database && check its type.
@@ -59,6 +57,10 @@ pub mod meta_keys {
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
 	/// Children prefix list key.
 	pub const CHILDREN_PREFIX: &[u8; 8] = b"children";
+	/// Key spaces of killed child tries left to remove from the state column.
+	pub const CHILD_TRIE_GC: &[u8; 8] = b"child_gc";
+	/// Number of the first finalized block whose body hasn't been pruned.
+	pub const BODIES_PRUNED: &[u8; 13] = b"bodies_pruned";
 }
 
 /// Database metadata.
@@ -205,17 +207,31 @@ pub fn db_err(err: io::Error) -> client::error::Error {
 	client::error::Error::Backend(format!("{}", err))
 }
 
-/// Open RocksDB database.
-#[cfg(feature = "kvdb-rocksdb")]
+/// Open the database from given source and check its type.
 pub fn open_database(
-	config: &DatabaseSettings,
+	source: &DatabaseSettingsSrc,
 	col_meta: Option<u32>,
 	db_type: &str
 ) -> client::error::Result<Arc<dyn KeyValueDB>> {
-	let mut db_config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
-	db_config.memory_budget = config.cache_size;
-	let path = config.path.to_str().ok_or_else(|| client::error::Error::Backend("Invalid database path".into()))?;
-	let db = Database::open(&db_config, &path).map_err(db_err)?;
+	let db: Arc<dyn KeyValueDB> = match source {
+		#[cfg(feature = "kvdb-rocksdb")]
+		DatabaseSettingsSrc::Path { path, cache_size } => {
+			let mut db_config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+			db_config.memory_budget = *cache_size;
+			let path = path.to_str()
+				.ok_or_else(|| client::error::Error::Backend("Invalid database path".into()))?;
+			Arc::new(Database::open(&db_config, &path).map_err(db_err)?)
+		},
+		#[cfg(not(feature = "kvdb-rocksdb"))]
+		DatabaseSettingsSrc::Path { .. } => return Err(client::error::Error::Backend(
+			"Substrate must be compiled with the \"kvdb-rocksdb\" feature to open a database at a path".into()
+		)),
+		DatabaseSettingsSrc::InMemory => {
+			log::warn!("Running with an in-memory database. The database will NOT be saved.");
+			Arc::new(kvdb_memorydb::create(NUM_COLUMNS))
+		},
+		DatabaseSettingsSrc::Custom(db) => db.clone(),
+	};
 
 	// check database type
 	match db.get(col_meta, meta_keys::TYPE).map_err(db_err)? {
@@ -232,7 +248,86 @@ pub fn open_database(
 		},
 	}
 
-	Ok(Arc::new(db))
+	Ok(db)
+}
+
+/// Source of the databases opened by the tests, selected by the `SUBSTRATE_TEST_DB` environment
+/// variable: `memory` (the default) or `rocksdb`. RocksDB databases are created in a temporary
+/// directory, which is removed when the database is dropped. The `test-client-db-rocksdb` CI job
+/// runs the tests of this crate on RocksDB.
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn test_database_source() -> DatabaseSettingsSrc {
+	match std::env::var("SUBSTRATE_TEST_DB").as_ref().map(|s| s.as_str()) {
+		Err(_) | Ok("memory") => DatabaseSettingsSrc::InMemory,
+		#[cfg(feature = "kvdb-rocksdb")]
+		Ok("rocksdb") => {
+			let dir = tempdir::TempDir::new("substrate-client-db-test")
+				.expect("failed to create test database directory");
+			let config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+			let path = dir.path().to_str().expect("temporary directory path is valid unicode; qed");
+			let db = Database::open(&config, path).expect("failed to open test database");
+			DatabaseSettingsSrc::Custom(Arc::new(TempDatabase { db, _dir: dir }))
+		},
+		#[cfg(not(feature = "kvdb-rocksdb"))]
+		Ok("rocksdb") => panic!("SUBSTRATE_TEST_DB=rocksdb requires the \"kvdb-rocksdb\" feature"),
+		Ok(other) => panic!("Unknown SUBSTRATE_TEST_DB database: {}", other),
+	}
+}
+
+/// Opens a new database of the kind selected by `test_database_source`.
+#[cfg(any(test, feature = "test-helpers"))]
+pub fn test_database() -> Arc<dyn KeyValueDB> {
+	match test_database_source() {
+		DatabaseSettingsSrc::Custom(db) => db,
+		_ => Arc::new(kvdb_memorydb::create(NUM_COLUMNS)),
+	}
+}
+
+/// RocksDB database removed from the disk when dropped.
+#[cfg(all(any(test, feature = "test-helpers"), feature = "kvdb-rocksdb"))]
+struct TempDatabase {
+	// Dropped before the directory it is stored in.
+	db: Database,
+	_dir: tempdir::TempDir,
+}
+
+#[cfg(all(any(test, feature = "test-helpers"), feature = "kvdb-rocksdb"))]
+impl KeyValueDB for TempDatabase {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
+		self.db.get(col, key)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+		self.db.get_by_prefix(col, prefix)
+	}
+
+	fn write_buffered(&self, transaction: DBTransaction) {
+		self.db.write_buffered(transaction)
+	}
+
+	fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+		self.db.write(transaction)
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		self.db.flush()
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		KeyValueDB::iter(&self.db, col)
+	}
+
+	fn iter_from_prefix<'a>(
+		&'a self,
+		col: Option<u32>,
+		prefix: &'a [u8],
+	) -> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		KeyValueDB::iter_from_prefix(&self.db, col, prefix)
+	}
+
+	fn restore(&self, new_db: &str) -> io::Result<()> {
+		self.db.restore(new_db)
+	}
 }
 
 /// Read database column entry for the given block.
@@ -345,4 +440,12 @@ mod tests {
 			_ => unreachable!(),
 		};
 	}
+
+	#[test]
+	fn open_database_checks_type() {
+		let source = DatabaseSettingsSrc::Custom(test_database());
+		assert!(open_database(&source, COLUMN_META, "full").is_ok());
+		assert!(open_database(&source, COLUMN_META, "full").is_ok());
+		assert!(open_database(&source, COLUMN_META, "light").is_err());
+	}
 }
@@ -28,6 +28,7 @@ use state_machine::{ChangesTrieStorage as StateChangesTrieStorage, ChangesTrieTr
 use crate::blockchain::well_known_cache_keys;
 use consensus::BlockOrigin;
 use hash_db::Hasher;
+use trie::PrefixedMemoryDB;
 use parking_lot::Mutex;
 
 /// In memory array of storage values.
@@ -110,6 +111,30 @@ pub trait BlockImportOperation<Block, H> where
 	fn update_db_storage(&mut self, update: <Self::State as StateBackend<H>>::Transaction) -> error::Result<()>;
 	/// Inject storage data into the database replacing any existing data.
 	fn reset_storage(&mut self, top: StorageOverlay, children: ChildrenStorageOverlay) -> error::Result<H::Out>;
+	/// Inject the full storage of the block, e.g. from a state snapshot, replacing any existing
+	/// data. Unlike `reset_storage`, `top` contains the values of the child tries, which are kept
+	/// as they are. Returns the state root.
+	///
+	/// The block is imported without its ancestry, as the new best and finalized block of a
+	/// database that only contains the genesis block. `top` and `children` complete the storage
+	/// added with `import_state_batch`.
+	fn import_state(&mut self, top: StorageOverlay, children: ChildrenStorageOverlay) -> error::Result<H::Out>;
+	/// Add a part of the full storage of the block imported with `import_state`, so that large
+	/// states can be imported without holding all of their entries at once. The value of a child
+	/// trie must be added in the same or an earlier batch than the contents of the child trie.
+	fn import_state_batch(&mut self, top: StorageOverlay, children: ChildrenStorageOverlay) -> error::Result<()>;
+	/// Inject checked trie nodes of the state of a block that is imported without its ancestry,
+	/// e.g. downloaded in parts during a warp sync. The nodes are stored as soon as the operation
+	/// is committed, even without a block, and stay in the database if the import is abandoned.
+	fn import_state_nodes(&mut self, nodes: PrefixedMemoryDB<H>) -> error::Result<()>;
+	/// Import the block set with `set_block_data` like with `import_state`, but with a state
+	/// whose nodes were all injected with `import_state_nodes` in earlier operations.
+	fn mark_state_imported(&mut self) -> error::Result<()>;
+	/// Mark the block set with `set_block_data` as historic: a block below the block whose state
+	/// was imported with `import_state`, e.g. downloaded after a warp sync. Only its header, body
+	/// and justification are stored, without its state and without changing the leaves or the
+	/// best and finalized blocks.
+	fn mark_historic(&mut self) -> error::Result<()>;
 	/// Set storage changes.
 	fn update_storage(
 		&mut self,
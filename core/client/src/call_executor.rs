@@ -15,7 +15,7 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{sync::Arc, cmp::Ord, panic::UnwindSafe, result, cell::RefCell, rc::Rc};
-use codec::{Encode, Decode};
+use codec::{Codec, Encode, Decode};
 use sr_primitives::{
 	generic::BlockId, traits::Block as BlockT, traits::NumberFor,
 };
@@ -25,14 +25,16 @@ use state_machine::{
 };
 use executor::{RuntimeVersion, RuntimeInfo, NativeVersion};
 use hash_db::Hasher;
+use log::trace;
 use primitives::{
 	offchain::OffchainExt, H256, Blake2Hasher, NativeOrEncoded, NeverNativeValue,
-	traits::{CodeExecutor, KeystoreExt},
+	traits::{CodeExecutor, KeystoreExt, Externalities},
 };
 
 use crate::runtime_api::{ProofRecorder, InitializeBlock};
 use crate::backend;
 use crate::error;
+use crate::wasm_override::{WasmOverride, OverrideExt};
 
 /// Method call executor.
 pub trait CallExecutor<B, H>
@@ -153,10 +155,15 @@ where
 
 /// Call executor that executes methods locally, querying all required
 /// data from local backend.
+///
+/// If runtime overrides are given, the on-chain runtime code is replaced by the override with the
+/// same `spec_name` and `spec_version` while executing calls. Execution proofs and runtime version
+/// queries always use the on-chain code.
 pub struct LocalCallExecutor<B, E> {
 	backend: Arc<B>,
 	executor: E,
 	keystore: Option<primitives::traits::BareCryptoStorePtr>,
+	wasm_override: Option<Arc<WasmOverride>>,
 }
 
 impl<B, E> LocalCallExecutor<B, E> {
@@ -165,11 +172,21 @@ impl<B, E> LocalCallExecutor<B, E> {
 		backend: Arc<B>,
 		executor: E,
 		keystore: Option<primitives::traits::BareCryptoStorePtr>,
+		wasm_override: Option<WasmOverride>,
 	) -> Self {
 		LocalCallExecutor {
 			backend,
 			executor,
 			keystore,
+			wasm_override: wasm_override.filter(|o| !o.is_empty()).map(Arc::new),
+		}
+	}
+
+	/// Returns the code executor that applies the runtime overrides.
+	fn override_executor(&self) -> OverrideExecutor<E> {
+		OverrideExecutor {
+			executor: &self.executor,
+			wasm_override: self.wasm_override.as_ref().map(|o| &**o),
 		}
 	}
 }
@@ -180,6 +197,54 @@ impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
 			backend: self.backend.clone(),
 			executor: self.executor.clone(),
 			keystore: self.keystore.clone(),
+			wasm_override: self.wasm_override.clone(),
+		}
+	}
+}
+
+/// A `CodeExecutor` that executes the runtime override matching the version of the on-chain
+/// runtime instead of the on-chain runtime.
+///
+/// Overridden runtimes are always executed as Wasm, as the native runtime would report the same
+/// version as the on-chain runtime.
+struct OverrideExecutor<'a, E> {
+	executor: &'a E,
+	wasm_override: Option<&'a WasmOverride>,
+}
+
+impl<'a, E: CodeExecutor + RuntimeInfo> CodeExecutor for OverrideExecutor<'a, E> {
+	type Error = E::Error;
+
+	fn call<
+		Ext: Externalities,
+		R: Codec + PartialEq,
+		NC: FnOnce() -> result::Result<R, String> + UnwindSafe,
+	>(
+		&self,
+		ext: &mut Ext,
+		method: &str,
+		data: &[u8],
+		use_native: bool,
+		native_call: Option<NC>,
+	) -> (Result<NativeOrEncoded<R>, Self::Error>, bool) {
+		let wasm_blob = match self.wasm_override {
+			Some(wasm_override) => self.executor.runtime_version(ext)
+				.and_then(|version| wasm_override.get(&version)),
+			None => None,
+		};
+
+		match wasm_blob {
+			Some(wasm_blob) => {
+				trace!(
+					target: "wasm_overrides",
+					"Executing `{}` with runtime override {}",
+					method,
+					wasm_blob.path().display(),
+				);
+				let mut ext = OverrideExt::new(ext, wasm_blob);
+				self.executor.call(&mut ext, method, data, false, native_call)
+			},
+			None => self.executor.call(ext, method, data, use_native, native_call),
 		}
 	}
 }
@@ -202,12 +267,13 @@ where
 	) -> error::Result<Vec<u8>> {
 		let mut changes = OverlayedChanges::default();
 		let state = self.backend.state_at(*id)?;
+		let executor = self.override_executor();
 		let return_data = StateMachine::new(
 			&state,
 			self.backend.changes_trie_storage(),
 			side_effects_handler,
 			&mut changes,
-			&self.executor,
+			&executor,
 			method,
 			call_data,
 			self.keystore.clone().map(KeystoreExt),
@@ -260,6 +326,7 @@ where
 		};
 
 		let mut state = self.backend.state_at(*at)?;
+		let executor = self.override_executor();
 
 		let result = match recorder {
 			Some(recorder) => {
@@ -279,7 +346,7 @@ where
 					self.backend.changes_trie_storage(),
 					side_effects_handler,
 					&mut *changes.borrow_mut(),
-					&self.executor,
+					&executor,
 					method,
 					call_data,
 					keystore,
@@ -297,7 +364,7 @@ where
 				self.backend.changes_trie_storage(),
 				side_effects_handler,
 				&mut *changes.borrow_mut(),
-				&self.executor,
+				&executor,
 				method,
 				call_data,
 				keystore,
@@ -354,7 +421,7 @@ where
 			self.backend.changes_trie_storage(),
 			side_effects_handler,
 			changes,
-			&self.executor,
+			&self.override_executor(),
 			method,
 			call_data,
 			self.keystore.clone().map(KeystoreExt),
@@ -17,7 +17,7 @@
 //! Substrate Client
 
 use std::{
-	marker::PhantomData, collections::{HashSet, BTreeMap, HashMap}, sync::Arc,
+	marker::PhantomData, collections::{HashSet, BTreeMap, BTreeSet, HashMap}, sync::Arc,
 	panic::UnwindSafe, result, cell::RefCell, rc::Rc,
 };
 use log::{info, trace, warn};
@@ -41,11 +41,13 @@ use sr_primitives::{
 };
 use state_machine::{
 	DBValue, Backend as StateBackend, ChangesTrieAnchorBlockId, ExecutionStrategy, ExecutionManager,
-	prove_read, prove_child_read, ChangesTrieRootsStorage, ChangesTrieStorage,
+	prove_read, prove_child_read, prove_read_range, read_range, prove_state_chunk,
+	state_chunk_proof_check, ChangesTrieRootsStorage, ChangesTrieStorage,
 	ChangesTrieTransaction, ChangesTrieConfigurationRange, key_changes, key_changes_proof,
-	OverlayedChanges, BackendTrustLevel,
+	block_changes_from_changes_trie, OverlayedChanges, BackendTrustLevel,
+	backend::is_child_trie_killed,
 };
-use executor::{RuntimeVersion, RuntimeInfo};
+use executor::{RuntimeVersion, RuntimeInfo, trace::{trace_host_calls, HostCall}};
 use consensus::{
 	Error as ConsensusError, BlockStatus, BlockImportParams, BlockCheckParams,
 	ImportResult, BlockOrigin, ForkChoiceStrategy,
@@ -202,6 +204,17 @@ pub struct FinalityNotification<Block: BlockT> {
 	pub header: Block::Header,
 }
 
+/// The host calls made by the runtime while executing a block.
+#[derive(Clone, Debug)]
+pub struct BlockTrace {
+	/// Host calls made while initializing the block.
+	pub initialize: Vec<HostCall>,
+	/// Host calls made while applying the extrinsics, one entry per extrinsic in block order.
+	pub extrinsics: Vec<Vec<HostCall>>,
+	/// Host calls made while finalizing the block.
+	pub finalize: Vec<HostCall>,
+}
+
 // used in importing a block, where additional changes are made after the runtime
 // executed.
 enum PrePostHeader<H> {
@@ -269,7 +282,7 @@ pub fn new_with_backend<B, E, Block, S, RA>(
 		Block: BlockT<Hash=H256>,
 		B: backend::LocalBackend<Block, Blake2Hasher>
 {
-	let call_executor = LocalCallExecutor::new(backend.clone(), executor, keystore);
+	let call_executor = LocalCallExecutor::new(backend.clone(), executor, keystore, None);
 	Client::new(backend, call_executor, build_genesis_storage, Default::default(), Default::default())
 }
 
@@ -357,6 +370,40 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(keys)
 	}
 
+	/// Given a `BlockId` and a key prefix, return at most `count` keys that start with the prefix
+	/// and their values, in lexicographic order. Only the keys after `start_key` are returned if
+	/// it is given.
+	pub fn storage_pairs_paged(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: u32,
+	) -> error::Result<Vec<(StorageKey, StorageData)>> {
+		let state = self.state_at(id)?;
+		let start_key = start_key.map(|key| &key.0[..]);
+		let pairs = read_range::<Blake2Hasher, _>(&state, &prefix.0, start_key, Some(count))
+			.map_err(error::Error::from_state)?;
+		Ok(pairs.into_iter().map(|(key, value)| (StorageKey(key), StorageData(value))).collect())
+	}
+
+	/// Read a part of the state of the block `id`, in order, e.g. to send it to a node that warp
+	/// syncs, as described in `state_machine::read_state_chunk`.
+	///
+	/// Returns the entries, with the storage key of their child trie if any, whether the end of
+	/// the state was reached and the proof of the entries, to be checked by `import_state_chunk`.
+	pub fn read_state_chunk(
+		&self,
+		id: &BlockId<Block>,
+		start_key: Option<&[u8]>,
+		start_child_key: Option<&[u8]>,
+		max_size: usize,
+	) -> error::Result<(Vec<(Option<Vec<u8>>, Vec<u8>, Vec<u8>)>, bool, Vec<Vec<u8>>)> {
+		self.state_at(id)
+			.and_then(|state| prove_state_chunk(state, start_key, start_child_key, max_size)
+				.map_err(Into::into))
+	}
+
 	/// Given a `BlockId` and a key, return the value under the key in that block.
 	pub fn storage(&self, id: &BlockId<Block>, key: &StorageKey) -> error::Result<Option<StorageData>> {
 		Ok(self.state_at(id)?
@@ -429,6 +476,164 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		&self.executor
 	}
 
+	/// Re-executes a block and records the host calls made by the runtime.
+	///
+	/// The block is executed as Wasm on top of the state of its parent, one extrinsic at a time, so
+	/// that every host call can be attributed to the extrinsic that made it. Nothing is written
+	/// to the database.
+	pub fn trace_block(&self, id: &BlockId<Block>) -> error::Result<BlockTrace> {
+		let header = self.header(id)?
+			.ok_or_else(|| error::Error::UnknownBlock(format!("{}", id)))?;
+		let extrinsics = self.body(id)?
+			.ok_or_else(|| error::Error::UnknownBlock(format!("Body of {} not found", id)))?;
+
+		let state = self.state_at(&BlockId::Hash(*header.parent_hash()))?;
+		// the state is destroyed whether the execution succeeds or not.
+		let trace = (|| -> error::Result<BlockTrace> {
+			let mut overlay = OverlayedChanges::default();
+			let mut execute = |method: &str, call_data: &[u8]| -> error::Result<Vec<HostCall>> {
+				let (result, calls) = trace_host_calls(|| self.executor
+					.call_at_state::<_, _, NeverNativeValue, fn() -> _>(
+						&state,
+						&mut overlay,
+						method,
+						call_data,
+						ExecutionStrategy::AlwaysWasm.get_manager(),
+						None,
+						None,
+					)
+				);
+				result.map(|_| calls)
+			};
+
+			Ok(BlockTrace {
+				initialize: execute("Core_initialize_block", &header.encode())?,
+				extrinsics: extrinsics.iter()
+					.map(|extrinsic| execute("BlockBuilder_apply_extrinsic", &extrinsic.encode()))
+					.collect::<error::Result<_>>()?,
+				finalize: execute("BlockBuilder_finalize_block", &[])?,
+			})
+		})();
+		let destroyed = self.backend.destroy_state(state);
+
+		let trace = trace?;
+		destroyed?;
+		Ok(trace)
+	}
+
+	/// Get the storage entries changed by a block, as `(child storage key, key, value before the
+	/// block, value after the block)`, ordered by child storage key and key. Entries that have
+	/// been written with their previous value are omitted.
+	///
+	/// Only the keys of the top trie that start with `prefix` and the keys of the child tries
+	/// whose storage key starts with `prefix` are returned. The changed keys are read from the
+	/// changes trie of the block if changes tries are supported and the changes trie of the
+	/// block hasn't been pruned, otherwise the block is re-executed on top of the state of its
+	/// parent.
+	pub fn storage_diff(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &StorageKey,
+	) -> error::Result<Vec<(Option<StorageKey>, StorageKey, Option<StorageData>, Option<StorageData>)>> {
+		let header = self.header(id)?
+			.ok_or_else(|| error::Error::UnknownBlock(format!("{}", id)))?;
+		let parent = BlockId::Hash(*header.parent_hash());
+
+		let changes_trie_changes = self.require_changes_trie()
+			.map_err(|e| e.to_string())
+			.and_then(|(_, storage)| block_changes_from_changes_trie::<Blake2Hasher, _>(
+				storage,
+				&ChangesTrieAnchorBlockId {
+					hash: convert_hash(&header.hash()),
+					number: *header.number(),
+				},
+				*header.number(),
+				&prefix.0,
+			));
+		let changes = match changes_trie_changes {
+			Ok(changes) => changes,
+			Err(e) => {
+				trace!("Re-executing block {} to get its storage changes: {}", id, e);
+				let extrinsics = self.body(id)?
+					.ok_or_else(|| error::Error::UnknownBlock(format!("Body of {} not found", id)))?;
+				let state = self.state_at(&parent)?;
+				let mut overlay = OverlayedChanges::default();
+				let result = self.executor.call_at_state::<_, _, NeverNativeValue, fn() -> _>(
+					&state,
+					&mut overlay,
+					"Core_execute_block",
+					&<Block as BlockT>::encode_from(&header, &extrinsics),
+					self.execution_strategies().other.get_manager(),
+					None,
+					None,
+				);
+				self.backend.destroy_state(state)?;
+				result?;
+
+				overlay.commit_prospective();
+				let (top, children) = overlay.into_committed();
+				top.map(|(key, _)| (None, key))
+					.filter(|(_, key)| key.starts_with(&prefix.0))
+					.chain(children
+						.filter(|(storage_key, _)| storage_key.starts_with(&prefix.0))
+						.flat_map(|(storage_key, changes)| changes
+							.map(move |(key, _)| (Some(storage_key.clone()), key))
+						)
+					)
+					.collect()
+			},
+		};
+
+		let before = self.state_at(&parent)?;
+		let after = self.state_at(id)?;
+		let mut changes = changes.into_iter().collect::<BTreeSet<_>>();
+		// killing a child trie only records the change of its value in the top trie, the keys it
+		// contained are enumerated here.
+		let child_storage_keys = changes.iter()
+			.filter(|(storage_key, key)| storage_key.is_none() && well_known_keys::is_child_storage_key(key))
+			.map(|(_, key)| key.clone())
+			.collect::<Vec<_>>();
+		for storage_key in child_storage_keys {
+			let (old, new) = (before.storage(&storage_key), after.storage(&storage_key));
+			let (old, new) = (
+				old.map_err(|e| error::Error::from_state(Box::new(e)))?,
+				new.map_err(|e| error::Error::from_state(Box::new(e)))?,
+			);
+			if is_child_trie_killed::<Blake2Hasher>(old.as_ref().map(AsRef::as_ref), new.as_ref().map(AsRef::as_ref)) {
+				before.for_keys_in_child_storage(&storage_key, |key| {
+					changes.insert((Some(storage_key.clone()), key.to_vec()));
+				});
+			}
+		}
+
+		let mut diff = Vec::new();
+		for (storage_key, key) in changes {
+			let (old, new) = match storage_key {
+				Some(ref storage_key) => (
+					before.child_storage(storage_key, &key),
+					after.child_storage(storage_key, &key),
+				),
+				None => (before.storage(&key), after.storage(&key)),
+			};
+			let (old, new) = (
+				old.map_err(|e| error::Error::from_state(Box::new(e)))?,
+				new.map_err(|e| error::Error::from_state(Box::new(e)))?,
+			);
+			if old != new {
+				diff.push((
+					storage_key.map(StorageKey),
+					StorageKey(key),
+					old.map(StorageData),
+					new.map(StorageData),
+				));
+			}
+		}
+		self.backend.destroy_state(before)?;
+		self.backend.destroy_state(after)?;
+
+		Ok(diff)
+	}
+
 	/// Reads storage value at a given block + key, returning read proof.
 	pub fn read_proof<I>(&self, id: &BlockId<Block>, keys: I) -> error::Result<Vec<Vec<u8>>> where
 		I: IntoIterator,
@@ -455,6 +660,23 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				.map_err(Into::into))
 	}
 
+	/// Reads the storage values of the keys that start with `prefix` at a given block, returning
+	/// read proof.
+	///
+	/// Only the keys after `start_key` are read if it is given, and at most `count` keys if it
+	/// is given.
+	pub fn read_range_proof(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: Option<u32>,
+	) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_read_range(state, prefix, start_key, count)
+				.map_err(Into::into))
+	}
+
 	/// Execute a call to a contract on top of state in a block of given hash
 	/// AND returning execution proof.
 	///
@@ -828,6 +1050,64 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		result
 	}
 
+	/// Check a part of the state of a block, read by another node with `read_state_chunk`, against
+	/// the state root of the block, and store its trie nodes. The nodes are stored at once, so
+	/// that the state doesn't have to be held in memory until the block is imported with
+	/// `import_state`.
+	pub fn import_state_chunk(
+		&self,
+		state_root: Block::Hash,
+		start_key: Option<&[u8]>,
+		start_child_key: Option<&[u8]>,
+		entries: &[(Option<Vec<u8>>, Vec<u8>, Vec<u8>)],
+		complete: bool,
+		proof: Vec<Vec<u8>>,
+	) -> error::Result<()> {
+		let nodes = state_chunk_proof_check::<Blake2Hasher>(
+			state_root,
+			proof,
+			start_key,
+			start_child_key,
+			entries,
+			complete,
+		).map_err(|e| Error::InvalidStateProof(e.to_string()))?;
+		self.lock_import_and_run(|operation| operation.op.import_state_nodes(nodes))
+	}
+
+	/// Import the finalized block `header`, whose whole state was stored with
+	/// `import_state_chunk`, e.g. during a warp sync, into a database that only contains the
+	/// genesis block. The block becomes the best and finalized block, without its ancestry.
+	pub fn import_state(
+		&self,
+		header: Block::Header,
+		justification: Option<Justification>,
+	) -> error::Result<()> {
+		let hash = header.hash();
+		self.lock_import_and_run(|operation| {
+			operation.op.mark_state_imported()?;
+			operation.op.set_block_data(header, None, justification, backend::NewBlockState::Final)?;
+			operation.notify_finalized.push(hash);
+			Ok(())
+		})
+	}
+
+	/// Import a block below the block whose state was imported with `import_state`. Only the
+	/// block itself is stored, the best and finalized blocks don't change.
+	///
+	/// The caller is responsible for checking that the block is an ancestor of the finalized
+	/// block.
+	pub fn import_historic_block(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> error::Result<()> {
+		self.lock_import_and_run(|operation| {
+			operation.op.set_block_data(header, body, justification, backend::NewBlockState::Normal)?;
+			operation.op.mark_historic()
+		})
+	}
+
 	/// Apply a checked and validated block to an operation. If a justification is provided
 	/// then `finalized` *must* be true.
 	fn apply_block(
@@ -1880,7 +2160,7 @@ pub(crate) mod tests {
 	use consensus::{BlockOrigin, SelectChain};
 	use test_client::{
 		prelude::*,
-		client_db::{Backend, DatabaseSettings, PruningMode},
+		client_db::{Backend, DatabaseSettings, DatabaseSettingsSrc, PruningMode},
 		runtime::{self, Block, Transfer, RuntimeApi, TestAPI},
 	};
 
@@ -2024,6 +2304,133 @@ pub(crate) mod tests {
 		);
 	}
 
+	#[test]
+	fn storage_diff_works() {
+		for client in vec![
+			test_client::new(),
+			TestClientBuilder::new().set_support_changes_trie(true).build(),
+		] {
+			let mut builder = client.new_block(Default::default()).unwrap();
+			builder.push_transfer(Transfer {
+				from: AccountKeyring::Alice.into(),
+				to: AccountKeyring::Ferdie.into(),
+				amount: 42,
+				nonce: 0,
+			}).unwrap();
+			client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+
+			let before: HashMap<_, _> = client.state_at(&BlockId::Number(0)).unwrap()
+				.pairs().into_iter().collect();
+			let after: HashMap<_, _> = client.state_at(&BlockId::Number(1)).unwrap()
+				.pairs().into_iter().collect();
+			let mut expected = before.keys().chain(after.keys())
+				.collect::<BTreeSet<_>>()
+				.into_iter()
+				.filter(|key| before.get(*key) != after.get(*key))
+				.map(|key| (
+					None,
+					StorageKey(key.clone()),
+					before.get(key).cloned().map(StorageData),
+					after.get(key).cloned().map(StorageData),
+				))
+				.collect::<Vec<_>>();
+			assert_eq!(
+				client.storage_diff(&BlockId::Number(1), &StorageKey(Vec::new())).unwrap(),
+				expected,
+			);
+
+			let alice = blake2_256(&runtime::system::balance_of_key(AccountKeyring::Alice.into())).to_vec();
+			expected.retain(|(_, key, _, _)| key.0 == alice);
+			assert_eq!(expected.len(), 1);
+			assert_eq!(client.storage_diff(&BlockId::Number(1), &StorageKey(alice)).unwrap(), expected);
+		}
+	}
+
+	#[test]
+	fn storage_pairs_paged_works() {
+		let client = TestClientBuilder::new()
+			.add_extra_storage(b":mock1".to_vec(), vec![1])
+			.add_extra_storage(b":mock2".to_vec(), vec![2])
+			.add_extra_storage(b":mock3".to_vec(), vec![3])
+			.build();
+		let prefix = StorageKey(b":mock".to_vec());
+		let pair = |key: &[u8], value| (StorageKey(key.to_vec()), StorageData(vec![value]));
+
+		assert_eq!(
+			client.storage_pairs_paged(&BlockId::Number(0), &prefix, None, 2).unwrap(),
+			vec![pair(b":mock1", 1), pair(b":mock2", 2)],
+		);
+		assert_eq!(
+			client.storage_pairs_paged(
+				&BlockId::Number(0),
+				&prefix,
+				Some(&StorageKey(b":mock2".to_vec())),
+				2,
+			).unwrap(),
+			vec![pair(b":mock3", 3)],
+		);
+		assert_eq!(
+			client.storage_pairs_paged(&BlockId::Number(0), &prefix, None, 0).unwrap(),
+			vec![],
+		);
+	}
+
+	#[test]
+	fn read_state_chunk_resumes_in_child_tries() {
+		let child = b":child_storage:default:child".to_vec();
+		let client = TestClientBuilder::new()
+			.add_extra_child_storage(child.clone(), b"a".to_vec(), vec![1])
+			.add_extra_child_storage(child.clone(), b"b".to_vec(), vec![2])
+			.build();
+		let id = BlockId::Number(0);
+		let state_root = *client.header(&id).unwrap().unwrap().state_root();
+
+		// read the state one entry at a time, except for the root of the child trie which is
+		// always followed by at least one entry of the child trie.
+		let mut entries = Vec::new();
+		let (mut start_key, mut start_child_key): (Option<Vec<u8>>, Option<Vec<u8>>) = (None, None);
+		loop {
+			let (chunk, complete, proof) = client.read_state_chunk(
+				&id,
+				start_key.as_ref().map(|key| &key[..]),
+				start_child_key.as_ref().map(|key| &key[..]),
+				1,
+			).unwrap();
+			assert!(!chunk.is_empty());
+			assert!(state_chunk_proof_check::<Blake2Hasher>(
+				state_root,
+				proof,
+				start_key.as_ref().map(|key| &key[..]),
+				start_child_key.as_ref().map(|key| &key[..]),
+				&chunk,
+				complete,
+			).is_ok());
+			match chunk.last() {
+				Some((Some(storage_key), key, _)) => {
+					start_key = Some(storage_key.clone());
+					start_child_key = Some(key.clone());
+				},
+				Some((None, key, _)) => {
+					start_key = Some(key.clone());
+					start_child_key = None;
+				},
+				None => unreachable!(),
+			}
+			entries.extend(chunk);
+			if complete {
+				break;
+			}
+		}
+
+		let top_keys = client.storage_keys(&id, &StorageKey(Vec::new())).unwrap();
+		assert_eq!(entries.len(), top_keys.len() + 2);
+		let position = entries.iter().position(|(c, key, _)| c.is_none() && *key == child).unwrap();
+		assert_eq!(
+			entries[position + 1..position + 3].to_vec(),
+			vec![(Some(child.clone()), b"a".to_vec(), vec![1]), (Some(child), b"b".to_vec(), vec![2])],
+		);
+	}
+
 	#[test]
 	fn block_builder_does_not_include_invalid() {
 		let client = test_client::new();
@@ -2758,17 +3165,16 @@ pub(crate) mod tests {
 	#[test]
 	fn doesnt_import_blocks_that_revert_finality() {
 		let _ = env_logger::try_init();
-		let tmp = tempfile::tempdir().unwrap();
 
 		// we need to run with archive pruning to avoid pruning non-canonical
 		// states
 		let backend = Arc::new(Backend::new(
 			DatabaseSettings {
-				cache_size: None,
 				state_cache_size: 1 << 20,
 				state_cache_child_ratio: None,
-				path: tmp.path().into(),
 				pruning: PruningMode::ArchiveAll,
+				keep_block_bodies: None,
+				source: DatabaseSettingsSrc::InMemory,
 			},
 			u64::max_value(),
 		).unwrap());
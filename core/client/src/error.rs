@@ -67,6 +67,9 @@ pub enum Error {
 	/// Invalid remote CHT-based proof.
 	#[display(fmt = "Remote node has responded with invalid header proof")]
 	InvalidCHTProof,
+	/// Invalid remote proof of a part of the state of a block.
+	#[display(fmt = "Remote node has responded with invalid state proof: {}", _0)]
+	InvalidStateProof(String),
 	/// Remote fetch has been cancelled.
 	#[display(fmt = "Remote data fetch has been cancelled")]
 	RemoteFetchCancelled,
@@ -97,6 +100,9 @@ pub enum Error {
 	/// Invalid calculated state root on block import.
 	#[display(fmt = "Calculated state root does not match.")]
 	InvalidStateRoot,
+	/// A runtime override could not be loaded.
+	#[display(fmt = "Invalid wasm override `{}`: {}", _0, _1)]
+	WasmOverride(String, String),
 	/// A convenience variant for String
 	#[display(fmt = "{}", _0)]
 	Msg(String),
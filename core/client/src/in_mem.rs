@@ -26,7 +26,7 @@ use sr_primitives::{Justification, StorageOverlay, ChildrenStorageOverlay};
 use state_machine::backend::{Backend as StateBackend, InMemory};
 use state_machine::{self, InMemoryChangesTrieStorage, ChangesTrieAnchorBlockId, ChangesTrieTransaction};
 use hash_db::{Hasher, Prefix};
-use trie::MemoryDB;
+use trie::{MemoryDB, PrefixedMemoryDB};
 use header_metadata::{CachedHeaderMetadata, HeaderMetadata};
 
 use crate::error;
@@ -518,6 +518,26 @@ where
 		Ok(root)
 	}
 
+	fn import_state_batch(&mut self, _top: StorageOverlay, _children: ChildrenStorageOverlay) -> error::Result<()> {
+		Err(error::Error::Backend("State import is not supported by the in-memory backend".into()))
+	}
+
+	fn import_state(&mut self, _top: StorageOverlay, _children: ChildrenStorageOverlay) -> error::Result<H::Out> {
+		Err(error::Error::Backend("State import is not supported by the in-memory backend".into()))
+	}
+
+	fn import_state_nodes(&mut self, _nodes: PrefixedMemoryDB<H>) -> error::Result<()> {
+		Err(error::Error::Backend("State import is not supported by the in-memory backend".into()))
+	}
+
+	fn mark_state_imported(&mut self) -> error::Result<()> {
+		Err(error::Error::Backend("State import is not supported by the in-memory backend".into()))
+	}
+
+	fn mark_historic(&mut self) -> error::Result<()> {
+		Err(error::Error::Backend("Historic blocks are not supported by the in-memory backend".into()))
+	}
+
 	fn insert_aux<I>(&mut self, ops: I) -> error::Result<()>
 		where I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>
 	{
@@ -62,7 +62,8 @@
 //! 	backend.clone(),
 //! 	LocalCallExecutor::new(
 //! 		backend.clone(),
-//! 		NativeExecutor::<LocalExecutor>::new(WasmExecutionMethod::Interpreted, None),
+//! 		NativeExecutor::<LocalExecutor>::new(WasmExecutionMethod::Interpreted, None, None),
+//! 		None,
 //! 		None,
 //!		),
 //! 	// This parameter provides the storage for the chain genesis.
@@ -104,6 +105,8 @@ mod call_executor;
 mod client;
 #[cfg(feature = "std")]
 mod notifications;
+#[cfg(feature = "std")]
+mod wasm_override;
 
 
 #[cfg(feature = "std")]
@@ -111,12 +114,14 @@ pub use crate::blockchain::Info as ChainInfo;
 #[cfg(feature = "std")]
 pub use crate::call_executor::{CallExecutor, LocalCallExecutor};
 #[cfg(feature = "std")]
+pub use crate::wasm_override::{WasmOverride, WasmBlob};
+#[cfg(feature = "std")]
 pub use crate::client::{
 	new_with_backend,
 	new_in_mem,
 	BlockBody, ImportNotifications, FinalityNotifications, BlockchainEvents,
 	BlockImportNotification, Client, ClientInfo, ExecutionStrategies, FinalityNotification,
-	LongestChain, BlockOf, ProvideUncles, ForkBlocks,
+	LongestChain, BlockOf, ProvideUncles, ForkBlocks, BlockTrace,
 	utils, apply_aux,
 };
 #[cfg(feature = "std")]
@@ -33,7 +33,7 @@ use crate::blockchain::{HeaderBackend as BlockchainHeaderBackend, well_known_cac
 use crate::error::{Error as ClientError, Result as ClientResult};
 use crate::light::blockchain::{Blockchain, Storage as BlockchainStorage};
 use hash_db::Hasher;
-use trie::MemoryDB;
+use trie::{MemoryDB, PrefixedMemoryDB};
 
 const IN_MEMORY_EXPECT_PROOF: &str = "InMemory state backend has Void error type and always succeeds; qed";
 
@@ -297,6 +297,26 @@ where
 		Ok(storage_root)
 	}
 
+	fn import_state_batch(&mut self, _top: StorageOverlay, _children: ChildrenStorageOverlay) -> ClientResult<()> {
+		Err(ClientError::NotAvailableOnLightClient)
+	}
+
+	fn import_state(&mut self, _top: StorageOverlay, _children: ChildrenStorageOverlay) -> ClientResult<H::Out> {
+		Err(ClientError::NotAvailableOnLightClient)
+	}
+
+	fn import_state_nodes(&mut self, _nodes: PrefixedMemoryDB<H>) -> ClientResult<()> {
+		Err(ClientError::NotAvailableOnLightClient)
+	}
+
+	fn mark_state_imported(&mut self) -> ClientResult<()> {
+		Err(ClientError::NotAvailableOnLightClient)
+	}
+
+	fn mark_historic(&mut self) -> ClientResult<()> {
+		Err(ClientError::NotAvailableOnLightClient)
+	}
+
 	fn insert_aux<I>(&mut self, ops: I) -> ClientResult<()>
 		where I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>
 	{
@@ -357,6 +377,26 @@ impl<H: Hasher> StateBackend<H> for GenesisOrUnavailableState<H>
 		}
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		match *self {
+			GenesisOrUnavailableState::Genesis(ref state) =>
+				Ok(state.next_storage_key(key).expect(IN_MEMORY_EXPECT_PROOF)),
+			GenesisOrUnavailableState::Unavailable => Err(ClientError::NotAvailableOnLightClient),
+		}
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		match *self {
+			GenesisOrUnavailableState::Genesis(ref state) =>
+				Ok(state.next_child_storage_key(storage_key, key).expect(IN_MEMORY_EXPECT_PROOF)),
+			GenesisOrUnavailableState::Unavailable => Err(ClientError::NotAvailableOnLightClient),
+		}
+	}
+
 	fn for_keys_with_prefix<A: FnMut(&[u8])>(&self, prefix: &[u8], action: A) {
 		match *self {
 			GenesisOrUnavailableState::Genesis(ref state) => state.for_keys_with_prefix(prefix, action),
@@ -403,13 +443,18 @@ impl<H: Hasher> StateBackend<H> for GenesisOrUnavailableState<H>
 		}
 	}
 
-	fn child_storage_root<I>(&self, key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 	where
 		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>
 	{
 		match *self {
 			GenesisOrUnavailableState::Genesis(ref state) => {
-				let (root, is_equal, _) = state.child_storage_root(key, delta);
+				let (root, is_equal, _) = state.child_storage_root(key, delta, killed);
 				(root, is_equal, ())
 			},
 			GenesisOrUnavailableState::Unavailable => (H::Out::default().as_ref().to_vec(), true, ()),
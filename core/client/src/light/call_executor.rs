@@ -392,7 +392,7 @@ mod tests {
 	}
 
 	fn local_executor() -> NativeExecutor<test_client::LocalExecutor> {
-		NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+		NativeExecutor::new(WasmExecutionMethod::Interpreted, None, None)
 	}
 
 	#[test]
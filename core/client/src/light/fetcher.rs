@@ -31,7 +31,7 @@ use sr_primitives::traits::{
 use state_machine::{
 	ChangesTrieRootsStorage, ChangesTrieAnchorBlockId, ChangesTrieConfigurationRange,
 	TrieBackend, read_proof_check, key_changes_proof_check, create_proof_check_backend_storage,
-	read_child_proof_check,
+	read_child_proof_check, read_range_proof_check,
 };
 
 use crate::cht;
@@ -93,6 +93,24 @@ pub struct RemoteReadChildRequest<Header: HeaderT> {
 	pub retry_count: Option<usize>,
 }
 
+/// Remote storage read request for a range of keys.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RemoteReadRangeRequest<Header: HeaderT> {
+	/// Read at state of given block.
+	pub block: Header::Hash,
+	/// Header of block at which read is performed.
+	pub header: Header,
+	/// Prefix of the storage keys to read.
+	pub prefix: Vec<u8>,
+	/// Only read the keys after this key. None means that the keys are read from the prefix.
+	pub start_key: Option<Vec<u8>>,
+	/// Maximal number of keys to read. None means that all keys with the prefix are read. The
+	/// remote never reads more keys than the limits of `state_machine::prove_read_range` allow.
+	pub count: Option<u32>,
+	/// Number of times to retry request. None means that default RETRY_COUNT is used.
+	pub retry_count: Option<usize>,
+}
+
 /// Remote key changes read request.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RemoteChangesRequest<Header: HeaderT> {
@@ -148,6 +166,8 @@ pub trait Fetcher<Block: BlockT>: Send + Sync {
 	type RemoteHeaderResult: Future<Output = Result<Block::Header, ClientError>> + Send + 'static;
 	/// Remote storage read future.
 	type RemoteReadResult: Future<Output = Result<HashMap<Vec<u8>, Option<Vec<u8>>>, ClientError>> + Send + 'static;
+	/// Remote storage range read future.
+	type RemoteReadRangeResult: Future<Output = Result<Vec<(Vec<u8>, Vec<u8>)>, ClientError>> + Send + 'static;
 	/// Remote call result future.
 	type RemoteCallResult: Future<Output = Result<Vec<u8>, ClientError>> + Send + 'static;
 	/// Remote changes result future.
@@ -167,6 +187,11 @@ pub trait Fetcher<Block: BlockT>: Send + Sync {
 		&self,
 		request: RemoteReadChildRequest<Block::Header>
 	) -> Self::RemoteReadResult;
+	/// Fetch remote storage keys and values of a range of keys.
+	fn remote_read_range(
+		&self,
+		request: RemoteReadRangeRequest<Block::Header>
+	) -> Self::RemoteReadRangeResult;
 	/// Fetch remote call result.
 	fn remote_call(&self, request: RemoteCallRequest<Block::Header>) -> Self::RemoteCallResult;
 	/// Fetch remote changes ((block number, extrinsic index)) where given key has been changed
@@ -200,6 +225,14 @@ pub trait FetchChecker<Block: BlockT>: Send + Sync {
 		request: &RemoteReadChildRequest<Block::Header>,
 		remote_proof: Vec<Vec<u8>>
 	) -> ClientResult<HashMap<Vec<u8>, Option<Vec<u8>>>>;
+	/// Check remote storage range read proof.
+	///
+	/// Fails if the proof doesn't prove that all keys of the range are returned.
+	fn check_read_range_proof(
+		&self,
+		request: &RemoteReadRangeRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>>;
 	/// Check remote method execution proof.
 	fn check_execution_proof(
 		&self,
@@ -416,6 +449,20 @@ impl<E, Block, H, S> FetchChecker<Block> for LightDataChecker<E, H, Block, S>
 		).map_err(Into::into)
 	}
 
+	fn check_read_range_proof(
+		&self,
+		request: &RemoteReadRangeRequest<Block::Header>,
+		remote_proof: Vec<Vec<u8>>
+	) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>> {
+		read_range_proof_check::<H>(
+			convert_hash(request.header.state_root()),
+			remote_proof,
+			&request.prefix,
+			request.start_key.as_ref().map(|key| &key[..]),
+			request.count,
+		).map_err(Into::into)
+	}
+
 	fn check_execution_proof(
 		&self,
 		request: &RemoteCallRequest<Block::Header>,
@@ -453,6 +500,25 @@ impl<E, Block, H, S> FetchChecker<Block> for LightDataChecker<E, H, Block, S>
 	}
 }
 
+/// Encode a storage proof of the state with the given root in the compact encoding.
+///
+/// Remote nodes that support it send read and execution proofs in this encoding.
+pub fn encode_compact_proof<H: Hasher>(
+	root: &H::Out,
+	proof: &[Vec<u8>],
+) -> ClientResult<Vec<Vec<u8>>> {
+	trie::encode_compact_proof::<H>(root, proof)
+		.map_err(|e| ClientError::Msg(format!("Failed to encode compact proof: {}", e)))
+}
+
+/// Decode a storage proof in the compact encoding into the trie nodes that are checked by the
+/// `FetchChecker`.
+pub fn decode_compact_proof<H: Hasher>(proof: &[Vec<u8>]) -> ClientResult<Vec<Vec<u8>>> {
+	trie::decode_compact_proof::<H>(proof)
+		.map(|(_, proof)| proof)
+		.map_err(|e| ClientError::Msg(format!("Failed to decode compact proof: {}", e)))
+}
+
 /// A view of BTreeMap<Number, Hash> as a changes trie roots storage.
 struct RootsStorage<'a, Number: SimpleArithmetic, Hash: 'a> {
 	roots: (Number, &'a [Hash]),
@@ -532,6 +598,7 @@ pub mod tests {
 	impl Fetcher<Block> for OkCallFetcher {
 		type RemoteHeaderResult = Ready<Result<Header, ClientError>>;
 		type RemoteReadResult = Ready<Result<HashMap<Vec<u8>, Option<Vec<u8>>>, ClientError>>;
+		type RemoteReadRangeResult = Ready<Result<Vec<(Vec<u8>, Vec<u8>)>, ClientError>>;
 		type RemoteCallResult = Ready<Result<Vec<u8>, ClientError>>;
 		type RemoteChangesResult = Ready<Result<Vec<(NumberFor<Block>, u32)>, ClientError>>;
 		type RemoteBodyResult = Ready<Result<Vec<Extrinsic>, ClientError>>;
@@ -548,6 +615,10 @@ pub mod tests {
 			not_implemented_in_tests()
 		}
 
+		fn remote_read_range(&self, _request: RemoteReadRangeRequest<Header>) -> Self::RemoteReadRangeResult {
+			not_implemented_in_tests()
+		}
+
 		fn remote_call(&self, _request: RemoteCallRequest<Header>) -> Self::RemoteCallResult {
 			futures03::future::ready(Ok((*self.lock()).clone()))
 		}
@@ -569,7 +640,7 @@ pub mod tests {
 	>;
 
 	fn local_executor() -> NativeExecutor<test_client::LocalExecutor> {
-		NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+		NativeExecutor::new(WasmExecutionMethod::Interpreted, None, None)
 	}
 
 	fn prepare_for_read_proof_check() -> (TestChecker, Header, Vec<Vec<u8>>, u32) {
@@ -696,6 +767,25 @@ pub mod tests {
 		}, remote_read_proof).unwrap().remove(well_known_keys::HEAP_PAGES).unwrap().unwrap()[0], heap_pages as u8);
 	}
 
+	#[test]
+	fn compact_storage_read_proof_is_checked() {
+		let (local_checker, remote_block_header, remote_read_proof, heap_pages) = prepare_for_read_proof_check();
+		let compact_proof = encode_compact_proof::<Blake2Hasher>(
+			&remote_block_header.state_root,
+			&remote_read_proof,
+		).unwrap();
+		assert!(compact_proof.iter().map(Vec::len).sum::<usize>()
+			< remote_read_proof.iter().map(Vec::len).sum::<usize>());
+
+		let remote_read_proof = decode_compact_proof::<Blake2Hasher>(&compact_proof).unwrap();
+		assert_eq!((&local_checker as &dyn FetchChecker<Block>).check_read_proof(&RemoteReadRequest::<Header> {
+			block: remote_block_header.hash(),
+			header: remote_block_header,
+			keys: vec![well_known_keys::HEAP_PAGES.to_vec()],
+			retry_count: None,
+		}, remote_read_proof).unwrap().remove(well_known_keys::HEAP_PAGES).unwrap().unwrap()[0], heap_pages as u8);
+	}
+
 	#[test]
 	fn storage_child_read_proof_is_generated_and_checked() {
 		let (
@@ -716,6 +806,46 @@ pub mod tests {
 		).unwrap().remove(b"key1".as_ref()).unwrap().unwrap(), result);
 	}
 
+	#[test]
+	fn storage_range_read_proof_is_generated_and_checked() {
+		let remote_client = test_client::new();
+		let remote_block_id = BlockId::Number(0);
+		let mut remote_block_header = remote_client.header(&remote_block_id).unwrap().unwrap();
+		remote_block_header.state_root = remote_client.state_at(&remote_block_id).unwrap()
+			.storage_root(::std::iter::empty()).0.into();
+		let local_checker = LightDataChecker::new(
+			Arc::new(DummyBlockchain::new(DummyStorage::new())),
+			local_executor(),
+		);
+		let request = |count| RemoteReadRangeRequest::<Header> {
+			block: remote_block_header.hash(),
+			header: remote_block_header.clone(),
+			prefix: b":".to_vec(),
+			start_key: None,
+			count,
+			retry_count: None,
+		};
+
+		// 'fetch' all well known keys from remote node
+		let remote_read_proof = remote_client.read_range_proof(&remote_block_id, b":", None, None)
+			.unwrap();
+		let keys = (&local_checker as &dyn FetchChecker<Block>)
+			.check_read_range_proof(&request(None), remote_read_proof).unwrap()
+			.into_iter()
+			.map(|(key, _)| key)
+			.collect::<Vec<_>>();
+		assert!(keys.contains(&well_known_keys::CODE.to_vec()));
+		assert!(keys.contains(&well_known_keys::HEAP_PAGES.to_vec()));
+
+		// proof of the first key only can't be used to check the whole range
+		let remote_read_proof = remote_client.read_range_proof(&remote_block_id, b":", None, Some(1))
+			.unwrap();
+		assert_eq!((&local_checker as &dyn FetchChecker<Block>)
+			.check_read_range_proof(&request(Some(1)), remote_read_proof.clone()).unwrap().len(), 1);
+		assert!((&local_checker as &dyn FetchChecker<Block>)
+			.check_read_range_proof(&request(None), remote_read_proof).is_err());
+	}
+
 	#[test]
 	fn header_proof_is_generated_and_checked() {
 		let (local_checker, local_cht_root, remote_block_header, remote_header_proof) = prepare_for_header_proof_check(true);
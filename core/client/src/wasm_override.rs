@@ -0,0 +1,327 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Overrides for the on-chain runtime code.
+//!
+//! A node can be pointed at a directory that contains Wasm runtime blobs. Each blob is identified
+//! by the `spec_name` and `spec_version` it reports through `Core_version`. Whenever the on-chain
+//! runtime reports the same `spec_name` and `spec_version`, the blob from the directory is executed
+//! instead of the on-chain code. This allows re-executing historic blocks with a patched runtime,
+//! e.g. one that emits extra debug logging, without touching the on-chain `:code`.
+
+use std::{any::{Any, TypeId}, collections::HashMap, fs, path::{Path, PathBuf}};
+use codec::Decode;
+use executor::{RuntimeVersion, WasmExecutionMethod};
+use log::info;
+use primitives::{
+	H256, Blake2Hasher, Hasher,
+	storage::{well_known_keys, ChildStorageKey},
+	traits::{Externalities, ExtensionStore},
+};
+use state_machine::BasicExternalities;
+
+use crate::error::{Error, Result};
+
+/// The number of heap pages used to read the version of a runtime override.
+const VERSION_HEAP_PAGES: u64 = 1024;
+
+/// A runtime blob that replaces the on-chain runtime code.
+#[derive(Debug)]
+pub struct WasmBlob {
+	/// The Wasm code of the runtime.
+	code: Vec<u8>,
+	/// The hash of `code`.
+	hash: H256,
+	/// The file the code was read from.
+	path: PathBuf,
+}
+
+impl WasmBlob {
+	/// The file the code was read from.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+/// A set of runtime overrides, indexed by `spec_name` and `spec_version`.
+#[derive(Debug, Default)]
+pub struct WasmOverride {
+	overrides: HashMap<(String, u32), WasmBlob>,
+}
+
+impl WasmOverride {
+	/// Loads all `*.wasm` files from the given directory.
+	///
+	/// Returns an error if a file can not be read, does not report a version or if two files
+	/// report the same `spec_name` and `spec_version`.
+	pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref();
+		let entries = fs::read_dir(path).map_err(|e| Error::WasmOverride(
+			path.display().to_string(),
+			e.to_string(),
+		))?;
+
+		let mut overrides = HashMap::new();
+		for entry in entries {
+			let path = entry
+				.map_err(|e| Error::WasmOverride(path.display().to_string(), e.to_string()))?
+				.path();
+			if path.extension().map_or(true, |ext| ext != "wasm") {
+				continue
+			}
+
+			let code = fs::read(&path)
+				.map_err(|e| Error::WasmOverride(path.display().to_string(), e.to_string()))?;
+			let version = runtime_version(&code)
+				.map_err(|e| Error::WasmOverride(path.display().to_string(), e))?;
+
+			let key = (version.spec_name.to_string(), version.spec_version);
+			if let Some(existing) = overrides.get(&key).map(|blob: &WasmBlob| blob.path.clone()) {
+				return Err(Error::WasmOverride(
+					path.display().to_string(),
+					format!(
+						"runtime `{}` version {} is already overridden by `{}`",
+						key.0,
+						key.1,
+						existing.display(),
+					),
+				))
+			}
+
+			info!(
+				target: "wasm_overrides",
+				"Found wasm override for runtime `{}` version {}: {}",
+				key.0,
+				key.1,
+				path.display(),
+			);
+
+			let hash = Blake2Hasher::hash(&code);
+			overrides.insert(key, WasmBlob { code, hash, path });
+		}
+
+		Ok(WasmOverride { overrides })
+	}
+
+	/// Returns the override for the runtime with the given version.
+	///
+	/// Only an override that reports exactly the same `spec_name` and `spec_version` as the given
+	/// `version` is returned.
+	pub fn get(&self, version: &RuntimeVersion) -> Option<&WasmBlob> {
+		self.overrides.get(&(version.spec_name.to_string(), version.spec_version))
+	}
+
+	/// Returns `true` if there are no overrides.
+	pub fn is_empty(&self) -> bool {
+		self.overrides.is_empty()
+	}
+}
+
+/// Reads the version of the given runtime code by calling `Core_version`.
+fn runtime_version(code: &[u8]) -> std::result::Result<RuntimeVersion, String> {
+	let mut ext = BasicExternalities::default();
+	let version = executor::call_in_wasm::<_, ()>(
+		"Core_version",
+		&[],
+		WasmExecutionMethod::Interpreted,
+		&mut ext,
+		code,
+		VERSION_HEAP_PAGES,
+	).map_err(|e| format!("failed to call `Core_version`: {}", e))?;
+
+	RuntimeVersion::decode(&mut &version[..])
+		.map_err(|e| format!("failed to decode runtime version: {}", e.what()))
+}
+
+/// Externalities that expose the code of a `WasmBlob` as `:code`.
+///
+/// All other accesses are forwarded to the wrapped externalities.
+pub(crate) struct OverrideExt<'a, E> {
+	inner: &'a mut E,
+	blob: &'a WasmBlob,
+}
+
+impl<'a, E: Externalities> OverrideExt<'a, E> {
+	/// Wraps the given externalities.
+	pub(crate) fn new(inner: &'a mut E, blob: &'a WasmBlob) -> Self {
+		OverrideExt { inner, blob }
+	}
+
+	/// Returns `true` if `:code` was not changed by the current execution.
+	fn code_unchanged(&self) -> bool {
+		self.inner.storage_hash(well_known_keys::CODE)
+			== self.inner.original_storage_hash(well_known_keys::CODE)
+	}
+}
+
+impl<'a, E: Externalities> Externalities for OverrideExt<'a, E> {
+	fn storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		if key == well_known_keys::CODE && self.code_unchanged() {
+			Some(self.blob.code.clone())
+		} else {
+			self.inner.storage(key)
+		}
+	}
+
+	fn storage_hash(&self, key: &[u8]) -> Option<H256> {
+		if key == well_known_keys::CODE && self.code_unchanged() {
+			Some(self.blob.hash)
+		} else {
+			self.inner.storage_hash(key)
+		}
+	}
+
+	fn child_storage_hash(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<H256> {
+		self.inner.child_storage_hash(storage_key, key)
+	}
+
+	fn original_storage(&self, key: &[u8]) -> Option<Vec<u8>> {
+		if key == well_known_keys::CODE {
+			Some(self.blob.code.clone())
+		} else {
+			self.inner.original_storage(key)
+		}
+	}
+
+	fn original_child_storage(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<Vec<u8>> {
+		self.inner.original_child_storage(storage_key, key)
+	}
+
+	fn original_storage_hash(&self, key: &[u8]) -> Option<H256> {
+		if key == well_known_keys::CODE {
+			Some(self.blob.hash)
+		} else {
+			self.inner.original_storage_hash(key)
+		}
+	}
+
+	fn original_child_storage_hash(
+		&self,
+		storage_key: ChildStorageKey,
+		key: &[u8],
+	) -> Option<H256> {
+		self.inner.original_child_storage_hash(storage_key, key)
+	}
+
+	fn child_storage(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<Vec<u8>> {
+		self.inner.child_storage(storage_key, key)
+	}
+
+	fn next_storage_key(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.inner.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<Vec<u8>> {
+		self.inner.next_child_storage_key(storage_key, key)
+	}
+
+	fn kill_child_storage(&mut self, storage_key: ChildStorageKey) {
+		self.inner.kill_child_storage(storage_key)
+	}
+
+	fn clear_prefix(&mut self, prefix: &[u8]) {
+		self.inner.clear_prefix(prefix)
+	}
+
+	fn clear_child_prefix(&mut self, storage_key: ChildStorageKey, prefix: &[u8]) {
+		self.inner.clear_child_prefix(storage_key, prefix)
+	}
+
+	fn place_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.inner.place_storage(key, value)
+	}
+
+	fn place_child_storage(
+		&mut self,
+		storage_key: ChildStorageKey,
+		key: Vec<u8>,
+		value: Option<Vec<u8>>,
+	) {
+		self.inner.place_child_storage(storage_key, key, value)
+	}
+
+	fn chain_id(&self) -> u64 {
+		self.inner.chain_id()
+	}
+
+	fn storage_root(&mut self) -> H256 {
+		self.inner.storage_root()
+	}
+
+	fn child_storage_root(&mut self, storage_key: ChildStorageKey) -> Vec<u8> {
+		self.inner.child_storage_root(storage_key)
+	}
+
+	fn storage_changes_root(&mut self, parent: H256) -> std::result::Result<Option<H256>, ()> {
+		self.inner.storage_changes_root(parent)
+	}
+
+	fn storage_start_transaction(&mut self) {
+		self.inner.storage_start_transaction()
+	}
+
+	fn storage_rollback_transaction(&mut self) -> std::result::Result<(), ()> {
+		self.inner.storage_rollback_transaction()
+	}
+
+	fn storage_commit_transaction(&mut self) -> std::result::Result<(), ()> {
+		self.inner.storage_commit_transaction()
+	}
+}
+
+impl<'a, E: Externalities> ExtensionStore for OverrideExt<'a, E> {
+	fn extension_by_type_id(&mut self, type_id: TypeId) -> Option<&mut dyn Any> {
+		self.inner.extension_by_type_id(type_id)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_client::runtime::{WASM_BINARY, VERSION};
+
+	#[test]
+	fn overrides_are_indexed_by_version() {
+		let dir = tempfile::tempdir().unwrap();
+		fs::write(dir.path().join("test.wasm"), WASM_BINARY).unwrap();
+		fs::write(dir.path().join("README"), b"not a runtime").unwrap();
+
+		let wasm_override = WasmOverride::new(dir.path()).unwrap();
+		let blob = wasm_override.get(&VERSION).expect("Override for the test runtime exists");
+		assert_eq!(blob.path(), dir.path().join("test.wasm").as_path());
+
+		let mut other_version = VERSION;
+		other_version.spec_version += 1;
+		assert!(wasm_override.get(&other_version).is_none());
+	}
+
+	#[test]
+	fn overrides_with_the_same_version_are_refused() {
+		let dir = tempfile::tempdir().unwrap();
+		fs::write(dir.path().join("first.wasm"), WASM_BINARY).unwrap();
+		fs::write(dir.path().join("second.wasm"), WASM_BINARY).unwrap();
+
+		assert!(WasmOverride::new(dir.path()).is_err());
+	}
+
+	#[test]
+	fn overrides_without_version_are_refused() {
+		let dir = tempfile::tempdir().unwrap();
+		fs::write(dir.path().join("broken.wasm"), b"\0asm").unwrap();
+
+		assert!(WasmOverride::new(dir.path()).is_err());
+	}
+}
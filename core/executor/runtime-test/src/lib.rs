@@ -42,6 +42,8 @@ primitives::wasm_export_functions! {
 
 	fn test_exhaust_heap() -> Vec<u8> { Vec::with_capacity(16777216) }
 
+	fn test_recursion(depth: u32) -> Vec<u8> { recurse(depth).to_vec() }
+
 	fn test_panic() { panic!("test panic") }
 
 	fn test_conditional_panic(input: Vec<u8>) -> Vec<u8> {
@@ -185,6 +187,17 @@ primitives::wasm_export_functions! {
 	}
  }
 
+/// Recurses `depth` times, hashing the result of every level so the recursion can not be turned
+/// into a loop.
+#[cfg(not(feature = "std"))]
+fn recurse(depth: u32) -> [u8; 16] {
+	if depth == 0 {
+		[0; 16]
+	} else {
+		blake2_128(&recurse(depth - 1))
+	}
+}
+
 #[cfg(not(feature = "std"))]
 fn execute_sandboxed(
 	code: &[u8],
@@ -14,41 +14,41 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! This module implements a freeing-bump allocator.
+//! This module implements a size-class heap allocator.
 //!
-//! The algorithm is as follows:
-//! We store `N` linked list heads, where `N` is the total number of sizes
-//! of allocations to support. A simple set is powers of two from 8 bytes
-//! to 16,777,216 bytes (2^3 - 2^24 inclusive), resulting in `N = 22`:
+//! The heap is a sequence of blocks. Each block is prefixed with an 8 byte header that records
+//! the size of the block, the size of the block right before it and whether the block is
+//! allocated:
 //!
 //! ```ignore
-//!	let mut heads [u64; N] = [0; N];
-//! fn size(n: u64) -> u64 { 8 << n }
-//! let mut bumper = 0;
-//! fn bump(n: u64) -> u64 { let res = bumper; bumper += n; res }
+//! header = (previous_size as u64) << 32 | size as u64 | allocated as u64
 //! ```
 //!
-//! We assume there is a slab of heap to be allocated:
+//! Sizes are always multiples of 8, so the lowest bit of the size is free to store the
+//! allocated flag. Knowing the size of both neighbours of a block allows freed blocks to be
+//! merged with free neighbours in constant time.
 //!
-//! ```ignore
-//! let mut heap = [0u8; HEAP_SIZE];
-//! ```
+//! Free blocks are kept in `N` doubly linked lists, one per size class. The size class of a block
+//! is the largest power of two that is not bigger than its size. The pointers to the next and the
+//! previous entry of the list are stored in the first 8 bytes of the free block itself.
+//!
+//! Whenever we allocate, we round the requested size up to the next multiple of 8 and search the
+//! free lists, starting with the size class of the request, for the first block that is big
+//! enough. If the block found is bigger than needed, the remainder is split off and freed again.
+//! If no free block fits, the block is taken from the end of the heap by increasing the bumper.
 //!
-//! Whenever we allocate, we select the lowest linked list item size that
-//! will fit the allocation (i.e. the next highest power of two).
-//! We then check to see if the linked list is empty. If empty, we use
-//! the bump allocator to get the allocation with an extra 8 bytes
-//! preceding it. We initialise those preceding 8 bytes to identify the
-//! list to which it belongs. If it is not empty, we unlink the first item from
-//! the linked list and then reset the 8 preceding bytes so they now record
-//! the identity of the linked list.
+//! To deallocate we use the header of the allocation to merge it with its free neighbours. If the
+//! resulting block ends at the bumper, it is given back to the bump region, otherwise it is put
+//! into the free list of its size class.
 //!
-//! To deallocate we use the preceding 8 bytes of the allocation to knit
-//! back the allocation into the linked list from the head.
+//! Reallocation grows a block in place if it is followed by the bumper or by a free block that is
+//! big enough, and shrinks a block in place by freeing its tail. Only if neither is possible the
+//! content is moved to a new allocation.
 
 use crate::error::{Error, Result};
 use log::trace;
 use std::convert::{TryFrom, TryInto};
+use std::fmt;
 use std::ops::Range;
 use wasm_interface::{Pointer, WordSize};
 
@@ -59,21 +59,103 @@ const ALIGNMENT: u32 = 8;
 // The pointer returned by `allocate()` needs to fulfill the alignment
 // requirement. In our case a pointer will always be a multiple of
 // 8, as long as the first pointer is aligned to 8 bytes.
-// This is because all pointers will contain a 8 byte prefix (the list
-// index) and then a subsequent item of 2^x bytes, where x = [3..24].
+// This is because all blocks consist of a 8 byte header and a
+// subsequent item whose size is a multiple of 8.
+//
+// There is one free list per size class from 8 bytes to 16,777,216 bytes
+// (2^3 - 2^24 inclusive). Free blocks that are bigger than the maximum
+// allocation are kept in the last list.
 const N: usize = 22;
 const MAX_POSSIBLE_ALLOCATION: u32 = 16777216; // 2^24 bytes
 const MIN_POSSIBLE_ALLOCATION: u32 = 8;
 
-// Each pointer is prefixed with 8 bytes, which identify the list index
-// to which it belongs.
+// Each block is prefixed with 8 bytes, which store its header.
 const PREFIX_SIZE: u32 = 8;
 
-pub struct FreeingBumpHeapAllocator {
+// The bit of the header that is set if the block is allocated.
+const ALLOCATED: u64 = 1;
+
+// Marks the end of a free list.
+const NIL: u32 = u32::max_value();
+
+/// Statistics about the usage of the heap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocationStats {
+	/// The number of bytes that are currently allocated, including the block headers.
+	pub bytes_allocated: u32,
+	/// The highest value of `bytes_allocated` since the allocator was created.
+	pub bytes_allocated_peak: u32,
+	/// The number of bytes of the heap that are used by blocks, allocated or free.
+	pub address_space_used: u32,
+	/// The number of bytes in free blocks, including the block headers.
+	pub free_bytes: u32,
+	/// The number of free blocks.
+	pub free_blocks: u32,
+}
+
+impl AllocationStats {
+	/// The fraction of the used address space that is taken by free blocks.
+	///
+	/// Free blocks are holes between allocated blocks that can only be reused by allocations
+	/// that fit into them.
+	pub fn fragmentation(&self) -> f64 {
+		if self.address_space_used == 0 {
+			0.0
+		} else {
+			f64::from(self.free_bytes) / f64::from(self.address_space_used)
+		}
+	}
+}
+
+impl fmt::Display for AllocationStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{} bytes allocated (peak {} bytes), {} bytes used, {} bytes free in {} blocks \
+			({:.1}% fragmentation)",
+			self.bytes_allocated,
+			self.bytes_allocated_peak,
+			self.address_space_used,
+			self.free_bytes,
+			self.free_blocks,
+			self.fragmentation() * 100.0,
+		)
+	}
+}
+
+/// The decoded header of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+	/// The size of the block, without the header.
+	size: u32,
+	/// The size of the block right before this one, `0` for the first block.
+	prev_size: u32,
+	/// Whether the block is allocated.
+	allocated: bool,
+}
+
+impl Header {
+	fn encode(&self) -> u64 {
+		let allocated = if self.allocated { ALLOCATED } else { 0 };
+		u64::from(self.prev_size) << 32 | u64::from(self.size) | allocated
+	}
+
+	fn decode(header: u64) -> Self {
+		Header {
+			size: header as u32 & !(ALLOCATED as u32),
+			prev_size: (header >> 32) as u32,
+			allocated: header & ALLOCATED == ALLOCATED,
+		}
+	}
+}
+
+pub struct SizeClassHeapAllocator {
 	bumper: u32,
 	heads: [u32; N],
 	ptr_offset: u32,
-	total_size: u32,
+	/// The size of the block that ends at the bumper, `0` if there is none.
+	last_size: u32,
+	stats: AllocationStats,
 }
 
 /// Create an allocator error.
@@ -81,8 +163,8 @@ fn error(msg: &'static str) -> Error {
 	Error::Allocator(msg)
 }
 
-impl FreeingBumpHeapAllocator {
-	/// Creates a new allocation heap which follows a freeing-bump strategy.
+impl SizeClassHeapAllocator {
+	/// Creates a new allocation heap which follows a size-class strategy.
 	/// The maximum size which can be allocated at once is 16 MiB.
 	///
 	/// # Arguments
@@ -92,18 +174,27 @@ impl FreeingBumpHeapAllocator {
 		// ptr_offset is the next alignment boundary on or after heap_base.
 		let ptr_offset = (heap_base + ALIGNMENT - 1) / ALIGNMENT * ALIGNMENT;
 
-		FreeingBumpHeapAllocator {
+		SizeClassHeapAllocator {
 			bumper: 0,
-			heads: [0; N],
+			heads: [NIL; N],
 			ptr_offset,
-			total_size: 0,
+			last_size: 0,
+			stats: AllocationStats::default(),
+		}
+	}
+
+	/// Returns the statistics about the usage of the heap.
+	pub fn stats(&self) -> AllocationStats {
+		AllocationStats {
+			address_space_used: self.bumper,
+			..self.stats
 		}
 	}
 
 	/// Gets requested number of bytes to allocate and returns a pointer.
 	/// The maximum size which can be allocated at once is 16 MiB.
 	/// There is no minimum size, but whatever size is passed into
-	/// this function is rounded to the next power of two. If the requested
+	/// this function is rounded to the next multiple of 8. If the requested
 	/// size is below 8 bytes it will be rounded up to 8 bytes.
 	///
 	/// # Arguments
@@ -111,46 +202,37 @@ impl FreeingBumpHeapAllocator {
 	/// - `mem` - a slice representing the linear memory on which this allocator operates.
 	/// - `size` - size in bytes of the allocation request
 	pub fn allocate(&mut self, mem: &mut [u8], size: WordSize) -> Result<Pointer<u8>> {
-		let mem_size = u32::try_from(mem.len())
-			.expect("size of Wasm linear memory is <2^32");
-		let max_heap_size = mem_size - self.ptr_offset;
-
 		if size > MAX_POSSIBLE_ALLOCATION {
 			return Err(Error::RequestedAllocationTooLarge);
 		}
 
-		let size = size.max(MIN_POSSIBLE_ALLOCATION);
-		let item_size = size.next_power_of_two();
-		if item_size + PREFIX_SIZE + self.total_size > max_heap_size {
-			return Err(Error::AllocatorOutOfSpace);
-		}
-
-		let list_index = (item_size.trailing_zeros() - 3) as usize;
-		let ptr: u32 = if self.heads[list_index] != 0 {
-			// Something from the free list
-			let item = self.heads[list_index];
-			let ptr = item + PREFIX_SIZE;
-			assert!(
-				ptr + item_size <= max_heap_size,
-				"Pointer is looked up in list of free entries, into which
-				only valid values are inserted; qed"
-			);
-
-			self.heads[list_index] = self.get_heap_u64(mem, item)?
-				.try_into()
-				.map_err(|_| error("read invalid free list pointer"))?;
-			ptr
-		} else {
-			// Nothing to be freed. Bump.
-			self.bump(item_size, max_heap_size)? + PREFIX_SIZE
+		let size = Self::round_size(size);
+		let block = match self.find_free_block(mem, size)? {
+			Some((block, header)) => {
+				self.unlink_free_block(mem, block, header.size)?;
+
+				let header = Header { allocated: true, ..header };
+				self.set_header(mem, block, header)?;
+				self.add_allocated(header.size);
+				self.truncate(mem, block, header, size)?;
+				block
+			},
+			None => {
+				// Nothing to be reused. Bump.
+				let block = self.bump(mem, size)?;
+				self.add_allocated(size);
+				block
+			},
 		};
+		self.update_peak();
 
-		self.set_heap_u64(mem, ptr - PREFIX_SIZE, list_index as u64)?;
-
-		self.total_size = self.total_size + item_size + PREFIX_SIZE;
-		trace!(target: "wasm-heap", "Heap size is {} bytes after allocation", self.total_size);
+		trace!(
+			target: "wasm-heap",
+			"Heap size is {} bytes after allocation",
+			self.stats.bytes_allocated,
+		);
 
-		Ok(Pointer::new(self.ptr_offset + ptr))
+		Ok(Pointer::new(self.ptr_offset + block + PREFIX_SIZE))
 	}
 
 	/// Deallocates the space which was allocated for a pointer.
@@ -160,49 +242,304 @@ impl FreeingBumpHeapAllocator {
 	/// - `mem` - a slice representing the linear memory on which this allocator operates.
 	/// - `ptr` - pointer to the allocated chunk
 	pub fn deallocate(&mut self, mem: &mut [u8], ptr: Pointer<u8>) -> Result<()> {
-		let ptr = u32::from(ptr) - self.ptr_offset;
-		if ptr < PREFIX_SIZE {
-			return Err(error("Invalid pointer for deallocation"));
+		let (block, header) = self.allocated_block(mem, ptr, "Invalid pointer for deallocation")?;
+
+		self.remove_allocated(header.size)?;
+		self.free_block(mem, block, header.size, header.prev_size)?;
+
+		trace!(
+			target: "wasm-heap",
+			"Heap size is {} bytes after deallocation",
+			self.stats.bytes_allocated,
+		);
+
+		Ok(())
+	}
+
+	/// Changes the size of an allocation and returns the pointer to the resized allocation.
+	///
+	/// The allocation is resized in place if possible, in which case the returned pointer is
+	/// equal to `ptr`. Otherwise its content is moved to a new allocation and `ptr` is
+	/// deallocated. The same size restrictions as for `allocate` apply.
+	///
+	/// # Arguments
+	///
+	/// - `mem` - a slice representing the linear memory on which this allocator operates.
+	/// - `ptr` - pointer to the allocated chunk
+	/// - `size` - the new size in bytes of the allocation
+	pub fn reallocate(
+		&mut self,
+		mem: &mut [u8],
+		ptr: Pointer<u8>,
+		size: WordSize,
+	) -> Result<Pointer<u8>> {
+		if size > MAX_POSSIBLE_ALLOCATION {
+			return Err(Error::RequestedAllocationTooLarge);
 		}
 
-		let list_index: usize = self.get_heap_u64(mem, ptr - PREFIX_SIZE)?
-			.try_into()
-			.map_err(|_| error("read invalid list index"))?;
-		if list_index > self.heads.len() {
-			return Err(error("read invalid list index"));
+		let (block, header) = self.allocated_block(mem, ptr, "Invalid pointer for reallocation")?;
+		let new_size = Self::round_size(size);
+
+		if new_size <= header.size {
+			self.truncate(mem, block, header, new_size)?;
+			return Ok(ptr)
 		}
-		self.set_heap_u64(mem, ptr - PREFIX_SIZE, self.heads[list_index] as u64)?;
-		self.heads[list_index] = ptr - PREFIX_SIZE;
 
-		let item_size = Self::get_item_size_from_index(list_index);
-		self.total_size = self.total_size.checked_sub(item_size as u32 + PREFIX_SIZE)
-			.ok_or_else(|| error("Unable to subtract from total heap size without overflow"))?;
-		trace!(target: "wasm-heap", "Heap size is {} bytes after deallocation", self.total_size);
+		let end = block + PREFIX_SIZE + header.size;
+		if end == self.bumper {
+			// The block is the last one, grow it into the bump region.
+			if block + PREFIX_SIZE + new_size <= self.max_heap_size(mem) {
+				self.set_header(mem, block, Header { size: new_size, ..header })?;
+				self.bumper = block + PREFIX_SIZE + new_size;
+				self.last_size = new_size;
+				self.stats.bytes_allocated += new_size - header.size;
+				self.update_peak();
+				return Ok(ptr)
+			}
+		} else {
+			// Absorb the next block if it is free and big enough.
+			let next = self.header(mem, end)?;
+			let combined_size = header.size + PREFIX_SIZE + next.size;
+			if !next.allocated && combined_size >= new_size {
+				self.unlink_free_block(mem, end, next.size)?;
+
+				let header = Header { size: combined_size, ..header };
+				self.set_header(mem, block, header)?;
+				self.set_prev_size_of_next(mem, block, combined_size)?;
+				self.stats.bytes_allocated += PREFIX_SIZE + next.size;
+				self.truncate(mem, block, header, new_size)?;
+				self.update_peak();
+				return Ok(ptr)
+			}
+		}
 
+		// Move the content to a new allocation.
+		let new_ptr = self.allocate(mem, size)?;
+		let src = self.heap_range(block + PREFIX_SIZE, header.size, mem.len())
+			.ok_or_else(|| error("read out of heap bounds"))?;
+		let dest = u32::from(new_ptr) as usize;
+		mem.copy_within(src, dest);
+		self.deallocate(mem, ptr)?;
+
+		Ok(new_ptr)
+	}
+
+	/// Rounds the given allocation size up to the size of the item that is used for it.
+	fn round_size(size: WordSize) -> u32 {
+		let size = size.max(MIN_POSSIBLE_ALLOCATION);
+		(size + ALIGNMENT - 1) / ALIGNMENT * ALIGNMENT
+	}
+
+	/// Returns the index of the free list for blocks of the given size.
+	fn size_class(size: u32) -> usize {
+		let log2 = 31 - size.max(MIN_POSSIBLE_ALLOCATION).leading_zeros() as usize;
+		(log2 - 3).min(N - 1)
+	}
+
+	/// The size of the memory from `ptr_offset` to the end of the linear memory.
+	fn max_heap_size(&self, mem: &[u8]) -> u32 {
+		let mem_size = u32::try_from(mem.len())
+			.expect("size of Wasm linear memory is <2^32");
+		mem_size.saturating_sub(self.ptr_offset)
+	}
+
+	fn add_allocated(&mut self, size: u32) {
+		self.stats.bytes_allocated += size + PREFIX_SIZE;
+	}
+
+	// Must be called once an allocation is finished, after the tail of the block was freed.
+	fn update_peak(&mut self) {
+		self.stats.bytes_allocated_peak =
+			self.stats.bytes_allocated_peak.max(self.stats.bytes_allocated);
+	}
+
+	fn remove_allocated(&mut self, size: u32) -> Result<()> {
+		self.stats.bytes_allocated = self.stats.bytes_allocated.checked_sub(size + PREFIX_SIZE)
+			.ok_or_else(|| error("Unable to subtract from total heap size without overflow"))?;
 		Ok(())
 	}
 
-	/// Increases the `bumper` by `item_size + PREFIX_SIZE`.
+	/// Increases the `bumper` by `size + PREFIX_SIZE` and writes the header of the new block.
 	///
 	/// Returns the `bumper` from before the increase.
 	/// Returns an `Error::AllocatorOutOfSpace` if the operation
 	/// would exhaust the heap.
-	fn bump(&mut self, item_size: u32, max_heap_size: u32) -> Result<u32> {
-		if self.bumper + PREFIX_SIZE + item_size > max_heap_size {
-			return Err(Error::AllocatorOutOfSpace);
+	fn bump(&mut self, mem: &mut [u8], size: u32) -> Result<u32> {
+		let end = self.bumper
+			.checked_add(PREFIX_SIZE + size)
+			.filter(|end| *end <= self.max_heap_size(mem))
+			.ok_or(Error::AllocatorOutOfSpace)?;
+
+		let block = self.bumper;
+		self.bumper = end;
+		self.set_header(mem, block, Header { size, prev_size: self.last_size, allocated: true })?;
+		self.last_size = size;
+		Ok(block)
+	}
+
+	/// Returns the offset of the block of the given allocation and its header.
+	fn allocated_block(
+		&self,
+		mem: &[u8],
+		ptr: Pointer<u8>,
+		msg: &'static str,
+	) -> Result<(u32, Header)> {
+		let block = u32::from(ptr)
+			.checked_sub(self.ptr_offset)
+			.and_then(|ptr| ptr.checked_sub(PREFIX_SIZE))
+			.filter(|block| *block < self.bumper)
+			.ok_or_else(|| error(msg))?;
+		let header = self.header(mem, block)?;
+		if !header.allocated {
+			return Err(error(msg));
+		}
+		Ok((block, header))
+	}
+
+	/// Shrinks the allocated block to `size` by freeing its tail.
+	///
+	/// The tail is only split off if it is big enough to form a block on its own.
+	fn truncate(&mut self, mem: &mut [u8], block: u32, header: Header, size: u32) -> Result<()> {
+		if header.size < size + PREFIX_SIZE + MIN_POSSIBLE_ALLOCATION {
+			return Ok(())
+		}
+
+		let tail = block + PREFIX_SIZE + size;
+		let tail_size = header.size - size - PREFIX_SIZE;
+		self.set_header(mem, block, Header { size, ..header })?;
+		self.remove_allocated(tail_size)?;
+		self.free_block(mem, tail, tail_size, size)
+	}
+
+	/// Turns the given block into a free block.
+	///
+	/// The block is merged with its free neighbours and then either given back to the bump
+	/// region or put into the free list of its size class.
+	fn free_block(&mut self, mem: &mut [u8], block: u32, size: u32, prev_size: u32) -> Result<()> {
+		let (mut block, mut size, mut prev_size) = (block, size, prev_size);
+
+		let next = block + PREFIX_SIZE + size;
+		if next < self.bumper {
+			let header = self.header(mem, next)?;
+			if !header.allocated {
+				self.unlink_free_block(mem, next, header.size)?;
+				size += PREFIX_SIZE + header.size;
+			}
+		}
+
+		if block > 0 {
+			let prev = block.checked_sub(PREFIX_SIZE + prev_size)
+				.ok_or_else(|| error("read invalid block header"))?;
+			let header = self.header(mem, prev)?;
+			if header.size != prev_size {
+				return Err(error("read invalid block header"));
+			}
+			if !header.allocated {
+				self.unlink_free_block(mem, prev, header.size)?;
+				size += PREFIX_SIZE + header.size;
+				block = prev;
+				prev_size = header.prev_size;
+			}
+		}
+
+		if block + PREFIX_SIZE + size == self.bumper {
+			self.bumper = block;
+			self.last_size = prev_size;
+			Ok(())
+		} else {
+			self.set_header(mem, block, Header { size, prev_size, allocated: false })?;
+			self.set_prev_size_of_next(mem, block, size)?;
+			self.link_free_block(mem, block, size)
+		}
+	}
+
+	/// Returns the first free block of at least `size` bytes and its header.
+	fn find_free_block(&self, mem: &[u8], size: u32) -> Result<Option<(u32, Header)>> {
+		for class in Self::size_class(size)..N {
+			let mut block = self.heads[class];
+			while block != NIL {
+				let header = self.header(mem, block)?;
+				if header.size >= size {
+					return Ok(Some((block, header)))
+				}
+				block = self.free_list_pointer(mem, block + PREFIX_SIZE)?;
+			}
+		}
+		Ok(None)
+	}
+
+	/// Puts the free block at the head of the free list of its size class.
+	fn link_free_block(&mut self, mem: &mut [u8], block: u32, size: u32) -> Result<()> {
+		let class = Self::size_class(size);
+		let next = self.heads[class];
+		self.set_heap_u32(mem, block + PREFIX_SIZE, next)?;
+		self.set_heap_u32(mem, block + PREFIX_SIZE + 4, NIL)?;
+		if next != NIL {
+			self.set_heap_u32(mem, next + PREFIX_SIZE + 4, block)?;
+		}
+		self.heads[class] = block;
+
+		self.stats.free_bytes += PREFIX_SIZE + size;
+		self.stats.free_blocks += 1;
+		Ok(())
+	}
+
+	/// Removes the free block from the free list of its size class.
+	fn unlink_free_block(&mut self, mem: &mut [u8], block: u32, size: u32) -> Result<()> {
+		let next = self.free_list_pointer(mem, block + PREFIX_SIZE)?;
+		let prev = self.free_list_pointer(mem, block + PREFIX_SIZE + 4)?;
+		if prev == NIL {
+			self.heads[Self::size_class(size)] = next;
+		} else {
+			self.set_heap_u32(mem, prev + PREFIX_SIZE, next)?;
+		}
+		if next != NIL {
+			self.set_heap_u32(mem, next + PREFIX_SIZE + 4, prev)?;
+		}
+
+		self.stats.free_bytes = self.stats.free_bytes.checked_sub(PREFIX_SIZE + size)
+			.ok_or_else(|| error("Unable to subtract from free heap size without overflow"))?;
+		self.stats.free_blocks = self.stats.free_blocks.checked_sub(1)
+			.ok_or_else(|| error("Unable to subtract from free block count without overflow"))?;
+		Ok(())
+	}
+
+	/// Updates the header of the block after the given one, if there is any.
+	fn set_prev_size_of_next(&self, mem: &mut [u8], block: u32, size: u32) -> Result<()> {
+		let next = block + PREFIX_SIZE + size;
+		if next < self.bumper {
+			let header = self.header(mem, next)?;
+			self.set_header(mem, next, Header { prev_size: size, ..header })?;
+		}
+		Ok(())
+	}
+
+	// Read a pointer to the next or previous entry of a free list and check that it points into
+	// the used part of the heap.
+	fn free_list_pointer(&self, heap: &[u8], offset: u32) -> Result<u32> {
+		match self.get_heap_u32(heap, offset)? {
+			block if block == NIL || block < self.bumper => Ok(block),
+			_ => Err(error("read invalid free list pointer")),
 		}
+	}
 
-		let res = self.bumper;
-		self.bumper += item_size + PREFIX_SIZE;
-		Ok(res)
+	// Read the header of the block at the given offset and check that the block is within the
+	// used part of the heap.
+	fn header(&self, heap: &[u8], block: u32) -> Result<Header> {
+		let header = Header::decode(self.get_heap_u64(heap, block)?);
+		let end = block.checked_add(PREFIX_SIZE)
+			.and_then(|end| end.checked_add(header.size));
+		match end {
+			Some(end) if end <= self.bumper && header.size % ALIGNMENT == 0 => Ok(header),
+			_ => Err(error("read invalid block header")),
+		}
 	}
 
-	fn get_item_size_from_index(index: usize) -> usize {
-		// we shift 1 by three places, since the first possible item size is 8
-		1 << 3 << index
+	fn set_header(&self, heap: &mut [u8], block: u32, header: Header) -> Result<()> {
+		self.set_heap_u64(heap, block, header.encode())
 	}
 
-	// Read a u64 from the heap in LE form. Used to read heap allocation prefixes.
+	// Read a u64 from the heap in LE form. Used to read block headers.
 	fn get_heap_u64(&self, heap: &[u8], offset: u32) -> Result<u64> {
 		let range = self.heap_range(offset, 8, heap.len())
 			.ok_or_else(|| error("read out of heap bounds"))?;
@@ -211,12 +548,30 @@ impl FreeingBumpHeapAllocator {
 		Ok(u64::from_le_bytes(bytes))
 	}
 
-	// Write a u64 to the heap in LE form. Used to write heap allocation prefixes.
+	// Write a u64 to the heap in LE form. Used to write block headers.
 	fn set_heap_u64(&self, heap: &mut [u8], offset: u32, val: u64) -> Result<()> {
 		let range = self.heap_range(offset, 8, heap.len())
 			.ok_or_else(|| error("write out of heap bounds"))?;
 		let bytes = val.to_le_bytes();
-		&mut heap[range].copy_from_slice(&bytes[..]);
+		heap[range].copy_from_slice(&bytes[..]);
+		Ok(())
+	}
+
+	// Read a u32 from the heap in LE form. Used to read free list pointers.
+	fn get_heap_u32(&self, heap: &[u8], offset: u32) -> Result<u32> {
+		let range = self.heap_range(offset, 4, heap.len())
+			.ok_or_else(|| error("read out of heap bounds"))?;
+		let bytes = heap[range].try_into()
+			.expect("[u8] slice of length 4 must be convertible to [u8; 4]");
+		Ok(u32::from_le_bytes(bytes))
+	}
+
+	// Write a u32 to the heap in LE form. Used to write free list pointers.
+	fn set_heap_u32(&self, heap: &mut [u8], offset: u32, val: u32) -> Result<()> {
+		let range = self.heap_range(offset, 4, heap.len())
+			.ok_or_else(|| error("write out of heap bounds"))?;
+		let bytes = val.to_le_bytes();
+		heap[range].copy_from_slice(&bytes[..]);
 		Ok(())
 	}
 
@@ -251,7 +606,7 @@ mod tests {
 	fn should_allocate_properly() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 
 		// when
 		let ptr = heap.allocate(&mut mem[..], 1).unwrap();
@@ -265,7 +620,7 @@ mod tests {
 	fn should_always_align_pointers_to_multiples_of_8() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(13);
+		let mut heap = SizeClassHeapAllocator::new(13);
 
 		// when
 		let ptr = heap.allocate(&mut mem[..], 1).unwrap();
@@ -280,7 +635,7 @@ mod tests {
 	fn should_increment_pointers_properly() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 
 		// when
 		let ptr1 = heap.allocate(&mut mem[..], 1).unwrap();
@@ -303,7 +658,7 @@ mod tests {
 	fn should_free_properly() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 		let ptr1 = heap.allocate(&mut mem[..], 1).unwrap();
 		// the prefix of 8 bytes is prepended to the pointer
 		assert_eq!(ptr1, to_pointer(PREFIX_SIZE));
@@ -313,12 +668,12 @@ mod tests {
 		assert_eq!(ptr2, to_pointer(24));
 
 		// when
-		heap.deallocate(&mut mem[..], ptr2).unwrap();
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
 
 		// then
 		// then the heads table should contain a pointer to the
-		// prefix of ptr2 in the leftmost entry
-		assert_eq!(heap.heads[0], u32::from(ptr2) - PREFIX_SIZE);
+		// prefix of ptr1 in the leftmost entry
+		assert_eq!(heap.heads[0], u32::from(ptr1) - PREFIX_SIZE);
 	}
 
 	#[test]
@@ -326,7 +681,7 @@ mod tests {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
 		let padded_offset = 16;
-		let mut heap = FreeingBumpHeapAllocator::new(13);
+		let mut heap = SizeClassHeapAllocator::new(13);
 
 		let ptr1 = heap.allocate(&mut mem[..], 1).unwrap();
 		// the prefix of 8 bytes is prepended to the pointer
@@ -345,18 +700,22 @@ mod tests {
 		// then
 		// should have re-allocated
 		assert_eq!(ptr3, to_pointer(padded_offset + 16 + PREFIX_SIZE));
-		assert_eq!(heap.heads, [0; N]);
+		assert_eq!(heap.heads, [NIL; N]);
 	}
 
 	#[test]
 	fn should_build_linked_list_of_free_areas_properly() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 
+		// every allocation is followed by a guard, so that freed blocks are not merged
 		let ptr1 = heap.allocate(&mut mem[..], 8).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
 		let ptr2 = heap.allocate(&mut mem[..], 8).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
 		let ptr3 = heap.allocate(&mut mem[..], 8).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
 
 		// when
 		heap.deallocate(&mut mem[..], ptr1).unwrap();
@@ -376,7 +735,7 @@ mod tests {
 	fn should_not_allocate_if_too_large() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(13);
+		let mut heap = SizeClassHeapAllocator::new(13);
 
 		// when
 		let ptr = heap.allocate(&mut mem[..], PAGE_SIZE - 13);
@@ -392,7 +751,7 @@ mod tests {
 	fn should_not_allocate_if_full() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 		let ptr1 = heap.allocate(&mut mem[..], (PAGE_SIZE / 2) - PREFIX_SIZE).unwrap();
 		assert_eq!(ptr1, to_pointer(PREFIX_SIZE));
 
@@ -411,7 +770,7 @@ mod tests {
 	fn should_allocate_max_possible_allocation_size() {
 		// given
 		let mut mem = vec![0u8; (MAX_POSSIBLE_ALLOCATION + PAGE_SIZE) as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 
 		// when
 		let ptr = heap.allocate(&mut mem[..], MAX_POSSIBLE_ALLOCATION).unwrap();
@@ -424,7 +783,7 @@ mod tests {
 	fn should_not_allocate_if_requested_size_too_large() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 
 		// when
 		let ptr = heap.allocate(&mut mem[..], MAX_POSSIBLE_ALLOCATION + 1);
@@ -440,25 +799,24 @@ mod tests {
 	fn should_return_error_when_bumper_greater_than_heap_size() {
 		// given
 		let mut mem = [0u8; 64];
-		let mut heap = FreeingBumpHeapAllocator::new(0);
+		let mut heap = SizeClassHeapAllocator::new(0);
 
 		let ptr1 = heap.allocate(&mut mem[..], 32).unwrap();
 		assert_eq!(ptr1, to_pointer(PREFIX_SIZE));
 		heap.deallocate(&mut mem[..], ptr1).expect("failed freeing ptr1");
-		assert_eq!(heap.total_size, 0);
-		assert_eq!(heap.bumper, 40);
+		assert_eq!(heap.stats().bytes_allocated, 0);
+		// the last block is given back to the bump region
+		assert_eq!(heap.bumper, 0);
 
 		let ptr2 = heap.allocate(&mut mem[..], 16).unwrap();
-		assert_eq!(ptr2, to_pointer(48));
-		heap.deallocate(&mut mem[..], ptr2).expect("failed freeing ptr2");
-		assert_eq!(heap.total_size, 0);
+		assert_eq!(ptr2, to_pointer(PREFIX_SIZE));
+		let ptr3 = heap.allocate(&mut mem[..], 32).unwrap();
+		assert_eq!(ptr3, to_pointer(32));
 		assert_eq!(heap.bumper, 64);
 
 		// when
 		// the `bumper` value is equal to `max_heap_size` here and any
 		// further allocation which would increment the bumper must fail.
-		// we try to allocate 8 bytes here, which will increment the
-		// bumper since no 8 byte item has been allocated+freed before.
 		let ptr = heap.allocate(&mut mem[..], 8);
 
 		// then
@@ -472,21 +830,21 @@ mod tests {
 	fn should_include_prefixes_in_total_heap_size() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(1);
+		let mut heap = SizeClassHeapAllocator::new(1);
 
 		// when
 		// an item size of 16 must be used then
 		heap.allocate(&mut mem[..], 9).unwrap();
 
 		// then
-		assert_eq!(heap.total_size, PREFIX_SIZE + 16);
+		assert_eq!(heap.stats().bytes_allocated, PREFIX_SIZE + 16);
 	}
 
 	#[test]
 	fn should_calculate_total_heap_size_to_zero() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(13);
+		let mut heap = SizeClassHeapAllocator::new(13);
 
 		// when
 		let ptr = heap.allocate(&mut mem[..], 42).unwrap();
@@ -494,14 +852,14 @@ mod tests {
 		heap.deallocate(&mut mem[..], ptr).unwrap();
 
 		// then
-		assert_eq!(heap.total_size, 0);
+		assert_eq!(heap.stats().bytes_allocated, 0);
 	}
 
 	#[test]
 	fn should_calculate_total_size_of_zero() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let mut heap = FreeingBumpHeapAllocator::new(19);
+		let mut heap = SizeClassHeapAllocator::new(19);
 
 		// when
 		for _ in 1..10 {
@@ -510,14 +868,14 @@ mod tests {
 		}
 
 		// then
-		assert_eq!(heap.total_size, 0);
+		assert_eq!(heap.stats().bytes_allocated, 0);
 	}
 
 	#[test]
 	fn should_read_and_write_u64_correctly() {
 		// given
 		let mut mem = [0u8; PAGE_SIZE as usize];
-		let heap = FreeingBumpHeapAllocator::new(16);
+		let heap = SizeClassHeapAllocator::new(16);
 
 		// when
 		heap.set_heap_u64(&mut mem[..], 40, 4480113).unwrap();
@@ -528,27 +886,359 @@ mod tests {
 	}
 
 	#[test]
-	fn should_get_item_size_from_index() {
+	fn should_get_size_class() {
+		assert_eq!(SizeClassHeapAllocator::size_class(8), 0);
+		assert_eq!(SizeClassHeapAllocator::size_class(24), 1);
+		assert_eq!(SizeClassHeapAllocator::size_class(32), 2);
+	}
+
+	#[test]
+	fn should_get_max_size_class() {
+		assert_eq!(SizeClassHeapAllocator::size_class(MAX_POSSIBLE_ALLOCATION), N - 1);
+		assert_eq!(SizeClassHeapAllocator::size_class(MAX_POSSIBLE_ALLOCATION * 4), N - 1);
+	}
+
+	#[test]
+	fn should_not_deallocate_twice() {
 		// given
-		let index = 0;
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr = heap.allocate(&mut mem[..], 8).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+		heap.deallocate(&mut mem[..], ptr).unwrap();
 
 		// when
-		let item_size = FreeingBumpHeapAllocator::get_item_size_from_index(index);
+		let result = heap.deallocate(&mut mem[..], ptr);
 
 		// then
-		assert_eq!(item_size, 8);
+		match result.unwrap_err() {
+			Error::Allocator(_) => {},
+			e => panic!("Expected allocator error, got: {:?}", e),
+		}
 	}
 
 	#[test]
-	fn should_get_max_item_size_from_index() {
+	fn should_merge_freed_blocks_with_free_neighbours() {
 		// given
-		let index = 21;
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr1 = heap.allocate(&mut mem[..], 8).unwrap();
+		let ptr2 = heap.allocate(&mut mem[..], 8).unwrap();
+		let ptr3 = heap.allocate(&mut mem[..], 8).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+
+		// when
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
+		heap.deallocate(&mut mem[..], ptr3).unwrap();
+		heap.deallocate(&mut mem[..], ptr2).unwrap();
+
+		// then
+		// the three blocks form a single free block of 8 + 2 * (8 + 8) bytes
+		let class = SizeClassHeapAllocator::size_class(40);
+		assert_eq!(heap.heads[class], u32::from(ptr1) - PREFIX_SIZE);
+		assert_eq!(heap.heads[0], NIL);
+		assert_eq!(heap.stats().free_blocks, 1);
+		assert_eq!(heap.stats().free_bytes, PREFIX_SIZE + 40);
+
+		let ptr4 = heap.allocate(&mut mem[..], 40).unwrap();
+		assert_eq!(ptr4, ptr1);
+		assert_eq!(heap.stats().free_blocks, 0);
+	}
+
+	#[test]
+	fn should_split_free_blocks() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr1 = heap.allocate(&mut mem[..], 64).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
+
+		// when
+		let ptr2 = heap.allocate(&mut mem[..], 16).unwrap();
+		let ptr3 = heap.allocate(&mut mem[..], 16).unwrap();
+
+		// then
+		assert_eq!(ptr2, ptr1);
+		assert_eq!(ptr3, to_pointer(u32::from(ptr1) + 16 + PREFIX_SIZE));
+		// 64 - 2 * (16 + 8) bytes are left
+		assert_eq!(heap.stats().free_bytes, 16 + PREFIX_SIZE);
+	}
+
+	#[test]
+	fn should_give_trailing_free_blocks_back_to_the_bump_region() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr1 = heap.allocate(&mut mem[..], 8).unwrap();
+		let ptr2 = heap.allocate(&mut mem[..], 8).unwrap();
+		let ptr3 = heap.allocate(&mut mem[..], 8).unwrap();
+
+		// when
+		heap.deallocate(&mut mem[..], ptr2).unwrap();
+		heap.deallocate(&mut mem[..], ptr3).unwrap();
+
+		// then
+		assert_eq!(heap.bumper, u32::from(ptr2) - PREFIX_SIZE);
+		assert_eq!(heap.stats().free_blocks, 0);
+
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
+		assert_eq!(heap.bumper, 0);
+	}
+
+	#[test]
+	fn should_reuse_memory_for_growing_allocations() {
+		// given
+		let mut mem = vec![0u8; 4 * 1024 * 1024];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let guard = heap.allocate(&mut mem[..], 8).unwrap();
+
+		// when
+		// every allocation is a bit bigger than the previous one, which never fits into
+		// a power of two sized slot that was freed before
+		for i in 0..64 {
+			let ptr = heap.allocate(&mut mem[..], 1024 * 1024 + i * 4096).unwrap();
+			heap.deallocate(&mut mem[..], ptr).unwrap();
+		}
+
+		// then
+		heap.deallocate(&mut mem[..], guard).unwrap();
+		assert_eq!(heap.bumper, 0);
+	}
+
+	#[test]
+	fn should_shrink_in_place() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr1 = heap.allocate(&mut mem[..], 64).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+
+		// when
+		let ptr2 = heap.reallocate(&mut mem[..], ptr1, 16).unwrap();
+
+		// then
+		assert_eq!(ptr2, ptr1);
+		assert_eq!(heap.stats().free_blocks, 1);
+		assert_eq!(heap.stats().free_bytes, 64 - 16);
+
+		let ptr3 = heap.allocate(&mut mem[..], 40).unwrap();
+		assert_eq!(ptr3, to_pointer(u32::from(ptr1) + 16 + PREFIX_SIZE));
+	}
+
+	#[test]
+	fn should_grow_last_block_in_place() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		heap.allocate(&mut mem[..], 8).unwrap();
+		let ptr1 = heap.allocate(&mut mem[..], 8).unwrap();
+
+		// when
+		let ptr2 = heap.reallocate(&mut mem[..], ptr1, 1024).unwrap();
+
+		// then
+		assert_eq!(ptr2, ptr1);
+		assert_eq!(heap.bumper, u32::from(ptr1) + 1024);
+		assert_eq!(heap.stats().bytes_allocated, 2 * PREFIX_SIZE + 8 + 1024);
+	}
+
+	#[test]
+	fn should_grow_in_place_into_free_neighbour() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr1 = heap.allocate(&mut mem[..], 8).unwrap();
+		let ptr2 = heap.allocate(&mut mem[..], 64).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+		heap.deallocate(&mut mem[..], ptr2).unwrap();
+
+		// when
+		let ptr3 = heap.reallocate(&mut mem[..], ptr1, 32).unwrap();
+
+		// then
+		assert_eq!(ptr3, ptr1);
+		// the rest of the free neighbour is still available
+		let ptr4 = heap.allocate(&mut mem[..], 40).unwrap();
+		assert_eq!(ptr4, to_pointer(u32::from(ptr1) + 32 + PREFIX_SIZE));
+		assert_eq!(heap.stats().free_blocks, 0);
+	}
+
+	#[test]
+	fn should_move_allocation_if_it_can_not_grow_in_place() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		let ptr1 = heap.allocate(&mut mem[..], 16).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+		let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+		mem[u32::from(ptr1) as usize..][..16].copy_from_slice(&data);
+
+		// when
+		let ptr2 = heap.reallocate(&mut mem[..], ptr1, 64).unwrap();
+
+		// then
+		assert_ne!(ptr2, ptr1);
+		assert_eq!(&mem[u32::from(ptr2) as usize..][..16], &data[..]);
+		assert_eq!(heap.stats().bytes_allocated, 2 * PREFIX_SIZE + 8 + 64);
+		assert_eq!(heap.stats().free_blocks, 1);
+	}
+
+	#[test]
+	fn should_track_peak_usage() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+
+		// when
+		let ptr1 = heap.allocate(&mut mem[..], 1024).unwrap();
+		let ptr2 = heap.allocate(&mut mem[..], 1024).unwrap();
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
+		heap.deallocate(&mut mem[..], ptr2).unwrap();
+		heap.allocate(&mut mem[..], 8).unwrap();
+
+		// then
+		let stats = heap.stats();
+		assert_eq!(stats.bytes_allocated, PREFIX_SIZE + 8);
+		assert_eq!(stats.bytes_allocated_peak, 2 * (PREFIX_SIZE + 1024));
+		assert_eq!(stats.address_space_used, PREFIX_SIZE + 8);
+	}
+
+	#[test]
+	fn should_report_fragmentation() {
+		// given
+		let mut mem = [0u8; PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(0);
+		assert_eq!(heap.stats().fragmentation(), 0.0);
 
 		// when
-		let item_size = FreeingBumpHeapAllocator::get_item_size_from_index(index);
+		let ptr1 = heap.allocate(&mut mem[..], 24).unwrap();
+		heap.allocate(&mut mem[..], 24).unwrap();
+		heap.deallocate(&mut mem[..], ptr1).unwrap();
 
 		// then
-		assert_eq!(item_size as u32, MAX_POSSIBLE_ALLOCATION);
+		// half of the used address space is a hole
+		assert_eq!(heap.stats().fragmentation(), 0.5);
+	}
+
+	/// A minimal xorshift random number generator, so the fuzz tests are reproducible.
+	struct Rng(u64);
+
+	impl Rng {
+		fn next(&mut self) -> u64 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 7;
+			self.0 ^= self.0 << 17;
+			self.0
+		}
+
+		fn below(&mut self, max: u32) -> u32 {
+			(self.next() % u64::from(max)) as u32
+		}
+	}
+
+	/// A live allocation of the reference model.
+	struct Allocation {
+		ptr: Pointer<u8>,
+		len: u32,
+		fill: u8,
 	}
 
+	/// Checks the allocator against a reference model that keeps track of all live allocations.
+	///
+	/// Every allocation is filled with its own byte, so an allocation that overlaps with another
+	/// one or that is not moved correctly by `reallocate` shows up as changed content.
+	fn fuzz_against_reference_model(seed: u64, heap_base: u32, max_len: u32) {
+		let mut rng = Rng(seed);
+		let mut mem = vec![0u8; 4 * PAGE_SIZE as usize];
+		let mut heap = SizeClassHeapAllocator::new(heap_base);
+		let mut live: Vec<Allocation> = Vec::new();
+		let mut fill = 0u8;
+
+		let check = |mem: &[u8], live: &[Allocation], heap: &SizeClassHeapAllocator| {
+			for allocation in live {
+				let start = u32::from(allocation.ptr);
+				assert_eq!(start % ALIGNMENT, 0);
+				assert!(start >= heap.ptr_offset + PREFIX_SIZE);
+				let content = &mem[start as usize..][..allocation.len as usize];
+				assert!(content.iter().all(|byte| *byte == allocation.fill));
+			}
+
+			let stats = heap.stats();
+			let min_allocated = live.iter()
+				.map(|a| PREFIX_SIZE + SizeClassHeapAllocator::round_size(a.len))
+				.sum::<u32>();
+			assert!(stats.bytes_allocated >= min_allocated);
+			assert!(stats.bytes_allocated_peak >= stats.bytes_allocated);
+			assert_eq!(stats.bytes_allocated + stats.free_bytes, stats.address_space_used);
+		};
+
+		for _ in 0..2000 {
+			fill = fill.wrapping_add(1);
+			match rng.below(3) {
+				0 => {
+					let len = rng.below(max_len) + 1;
+					match heap.allocate(&mut mem[..], len) {
+						Ok(ptr) => {
+							mem[u32::from(ptr) as usize..][..len as usize]
+								.iter_mut()
+								.for_each(|byte| *byte = fill);
+							live.push(Allocation { ptr, len, fill });
+						},
+						Err(Error::AllocatorOutOfSpace) => {},
+						Err(e) => panic!("Unexpected allocation error: {:?}", e),
+					}
+				},
+				1 if !live.is_empty() => {
+					let index = rng.below(live.len() as u32) as usize;
+					let allocation = live.swap_remove(index);
+					heap.deallocate(&mut mem[..], allocation.ptr).unwrap();
+				},
+				2 if !live.is_empty() => {
+					let index = rng.below(live.len() as u32) as usize;
+					let len = rng.below(max_len) + 1;
+					match heap.reallocate(&mut mem[..], live[index].ptr, len) {
+						Ok(ptr) => {
+							let allocation = &mut live[index];
+							let kept = allocation.len.min(len) as usize;
+							let content = &mut mem[u32::from(ptr) as usize..][..len as usize];
+							assert!(content[..kept].iter().all(|byte| *byte == allocation.fill));
+							content.iter_mut().for_each(|byte| *byte = fill);
+							*allocation = Allocation { ptr, len, fill };
+						},
+						Err(Error::AllocatorOutOfSpace) => {},
+						Err(e) => panic!("Unexpected reallocation error: {:?}", e),
+					}
+				},
+				_ => {},
+			}
+
+			check(&mem, &live, &heap);
+		}
+
+		for allocation in live.drain(..) {
+			heap.deallocate(&mut mem[..], allocation.ptr).unwrap();
+		}
+
+		// everything is given back to the bump region
+		let stats = heap.stats();
+		assert_eq!(stats.bytes_allocated, 0);
+		assert_eq!(stats.address_space_used, 0);
+		assert_eq!(stats.free_blocks, 0);
+		assert_eq!(heap.heads, [NIL; N]);
+	}
+
+	#[test]
+	fn should_match_reference_model_with_small_allocations() {
+		for seed in 1..8 {
+			fuzz_against_reference_model(seed, seed as u32, 128);
+		}
+	}
+
+	#[test]
+	fn should_match_reference_model_with_large_allocations() {
+		for seed in 1..8 {
+			fuzz_against_reference_model(seed * 7919, 0, 16 * 1024);
+		}
+	}
 }
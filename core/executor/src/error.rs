@@ -61,6 +61,9 @@ pub enum Error {
 	/// allocator is allowed to place its data.
 	#[display(fmt="The runtime doesn't provide a global named `__heap_base`")]
 	HeapBaseNotFoundOrInvalid,
+	/// The runtime exceeded the maximum stack height and was stopped.
+	#[display(fmt="Runtime exceeded the maximum stack height")]
+	StackExhausted,
 	/// The runtime WebAssembly module is not allowed to have the `start` function.
 	#[display(fmt="The runtime has the `start` function")]
 	RuntimeHasStartFn,
@@ -78,6 +81,10 @@ pub enum Error {
 	/// Executing the given function failed with the given error.
 	#[display(fmt="Function execution failed with: {}", _0)]
 	FunctionExecution(String),
+	/// The runtime called a host function that is not provided by the executor. The parameters
+	/// are the name of the function and its version.
+	#[display(fmt="Host function `{}` version {} is not provided by this node", _0, _1)]
+	MissingHostFunction(String, u32),
 }
 
 impl std::error::Error for Error {
@@ -118,4 +125,8 @@ pub enum WasmError {
 	CantDeserializeWasm,
 	/// Instantiation error.
 	Instantiation(Error),
+	/// The runtime needs a version of a runtime interface that is not provided by the executor.
+	/// The parameters are the name of the interface and its version.
+	#[display(fmt="Runtime interface `{}` version {} is not provided by this node", _0, _1)]
+	UnsupportedHostInterface(String, u32),
 }
@@ -19,40 +19,32 @@
 //! These are the host functions callable from within the Substrate runtime.
 
 use crate::error::{Error, Result};
+use crate::trace::{record_detail, HostCallDetail};
 
 use codec::Encode;
 use std::{convert::TryFrom, str, panic};
-use primitives::{
-	blake2_128, blake2_256, twox_64, twox_128, twox_256, ed25519, sr25519, Blake2Hasher, Pair,
-	crypto::KeyTypeId, offchain,
-};
+use primitives::{ed25519, sr25519, Blake2Hasher, Pair, crypto::KeyTypeId, offchain};
 use trie::{TrieConfiguration, trie_types::Layout};
 use wasm_interface::{FunctionContext, Pointer, PointerType, Result as WResult, WordSize};
 
-#[cfg(feature="wasm-extern-trace")]
-macro_rules! debug_trace {
-	( $( $x:tt )* ) => ( trace!( $( $x )* ) )
-}
-
-#[cfg(not(feature="wasm-extern-trace"))]
-macro_rules! debug_trace {
-	( $( $x:tt )* ) => ()
-}
-
 pub struct SubstrateExternals;
 
+/// The host functions Substrate provides to every runtime: the `SubstrateExternals` and the host
+/// functions of the runtime interfaces declared in `sr-io`.
+pub type SubstrateHostFunctions = (SubstrateExternals, runtime_io::hashing::HostFunctions);
+
 impl_wasm_host_interface! {
 	impl SubstrateExternals where context {
 		ext_malloc(size: WordSize) -> Pointer<u8> {
-			let r = context.allocate_memory(size)?;
-			debug_trace!(target: "sr-io", "malloc {} bytes at {:?}", size, r);
-			Ok(r)
+			context.allocate_memory(size)
 		}
 
 		ext_free(addr: Pointer<u8>) {
-			context.deallocate_memory(addr)?;
-			debug_trace!(target: "sr-io", "free {:?}", addr);
-			Ok(())
+			context.deallocate_memory(addr)
+		}
+
+		ext_realloc(addr: Pointer<u8>, size: WordSize) -> Pointer<u8> {
+			context.reallocate_memory(addr, size)
 		}
 
 		ext_sandbox_instantiate(
@@ -159,6 +151,7 @@ impl_wasm_host_interface! {
 		) {
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_set_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 			let value = context.read_memory(value_data, value_len)
 				.map_err(|_| "Invalid attempt to determine value in ext_set_storage")?;
 			with_external_storage(move ||
@@ -177,8 +170,10 @@ impl_wasm_host_interface! {
 		) {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_set_child_storage")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_set_child_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 			let value = context.read_memory(value_data, value_len)
 				.map_err(|_| "Invalid attempt to determine value in ext_set_child_storage")?;
 
@@ -196,8 +191,10 @@ impl_wasm_host_interface! {
 		) {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_clear_child_storage")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_clear_child_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 
 			with_external_storage(move ||
 				Ok(runtime_io::clear_child_storage(&storage_key, &key))
@@ -208,6 +205,7 @@ impl_wasm_host_interface! {
 		ext_clear_storage(key_data: Pointer<u8>, key_len: WordSize) {
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_clear_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 			with_external_storage(move ||
 				Ok(runtime_io::clear_storage(&key))
 			)?;
@@ -217,6 +215,7 @@ impl_wasm_host_interface! {
 		ext_exists_storage(key_data: Pointer<u8>, key_len: WordSize) -> u32 {
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_exists_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 			with_external_storage(move ||
 				Ok(if runtime_io::exists_storage(&key) { 1 } else { 0 })
 			)
@@ -230,8 +229,10 @@ impl_wasm_host_interface! {
 		) -> u32 {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_exists_child_storage")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_exists_child_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 
 			with_external_storage(move ||
 				Ok(if runtime_io::exists_child_storage(&storage_key, &key) { 1 } else { 0 })
@@ -241,6 +242,7 @@ impl_wasm_host_interface! {
 		ext_clear_prefix(prefix_data: Pointer<u8>, prefix_len: WordSize) {
 			let prefix = context.read_memory(prefix_data, prefix_len)
 				.map_err(|_| "Invalid attempt to determine prefix in ext_clear_prefix")?;
+			record_detail(|| HostCallDetail::StoragePrefix(prefix.clone()));
 			with_external_storage(move ||
 				Ok(runtime_io::clear_prefix(&prefix))
 			)?;
@@ -255,8 +257,10 @@ impl_wasm_host_interface! {
 		) {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_clear_child_prefix")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let prefix = context.read_memory(prefix_data, prefix_len)
 				.map_err(|_| "Invalid attempt to determine prefix in ext_clear_child_prefix")?;
+			record_detail(|| HostCallDetail::StoragePrefix(prefix.clone()));
 			with_external_storage(move ||
 				Ok(runtime_io::clear_child_prefix(&storage_key, &prefix))
 			)?;
@@ -267,6 +271,7 @@ impl_wasm_host_interface! {
 		ext_kill_child_storage(storage_key_data: Pointer<u8>, storage_key_len: WordSize) {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_kill_child_storage")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			with_external_storage(move ||
 				Ok(runtime_io::kill_child_storage(&storage_key))
 			)?;
@@ -281,11 +286,13 @@ impl_wasm_host_interface! {
 		) -> Pointer<u8> {
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_get_allocated_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 			let maybe_value = with_external_storage(move ||
 				Ok(runtime_io::storage(&key))
 			)?;
 
 			if let Some(value) = maybe_value {
+				record_detail(|| HostCallDetail::ResultLen(value.len() as u32));
 				let offset = context.allocate_memory(value.len() as u32)?;
 				context.write_memory(offset, &value)
 					.map_err(|_| "Invalid attempt to set memory in ext_get_allocated_storage")?;
@@ -308,14 +315,17 @@ impl_wasm_host_interface! {
 		) -> Pointer<u8> {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_get_allocated_child_storage")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to determine key in ext_get_allocated_child_storage")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 
 			let maybe_value = with_external_storage(move ||
 				Ok(runtime_io::child_storage(&storage_key, &key))
 			)?;
 
 			if let Some(value) = maybe_value {
+				record_detail(|| HostCallDetail::ResultLen(value.len() as u32));
 				let offset = context.allocate_memory(value.len() as u32)?;
 				context.write_memory(offset, &value)
 					.map_err(|_| "Invalid attempt to set memory in ext_get_allocated_child_storage")?;
@@ -329,6 +339,66 @@ impl_wasm_host_interface! {
 			}
 		}
 
+		ext_next_storage_key(
+			key_data: Pointer<u8>,
+			key_len: WordSize,
+			written_out: Pointer<u32>,
+		) -> Pointer<u8> {
+			let key = context.read_memory(key_data, key_len)
+				.map_err(|_| "Invalid attempt to determine key in ext_next_storage_key")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
+			let maybe_next_key = with_external_storage(move ||
+				Ok(runtime_io::next_storage_key(&key))
+			)?;
+
+			if let Some(next_key) = maybe_next_key {
+				record_detail(|| HostCallDetail::ResultLen(next_key.len() as u32));
+				let offset = context.allocate_memory(next_key.len() as u32)?;
+				context.write_memory(offset, &next_key)
+					.map_err(|_| "Invalid attempt to set memory in ext_next_storage_key")?;
+				context.write_primitive(written_out, next_key.len() as u32)
+					.map_err(|_| "Invalid attempt to write written_out in ext_next_storage_key")?;
+				Ok(offset)
+			} else {
+				context.write_primitive(written_out, u32::max_value())
+					.map_err(|_| "Invalid attempt to write failed written_out in ext_next_storage_key")?;
+				Ok(Pointer::null())
+			}
+		}
+
+		ext_next_child_storage_key(
+			storage_key_data: Pointer<u8>,
+			storage_key_len: WordSize,
+			key_data: Pointer<u8>,
+			key_len: WordSize,
+			written_out: Pointer<u32>,
+		) -> Pointer<u8> {
+			let storage_key = context.read_memory(storage_key_data, storage_key_len)
+				.map_err(|_| "Invalid attempt to determine storage_key in ext_next_child_storage_key")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
+			let key = context.read_memory(key_data, key_len)
+				.map_err(|_| "Invalid attempt to determine key in ext_next_child_storage_key")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
+
+			let maybe_next_key = with_external_storage(move ||
+				Ok(runtime_io::next_child_storage_key(&storage_key, &key))
+			)?;
+
+			if let Some(next_key) = maybe_next_key {
+				record_detail(|| HostCallDetail::ResultLen(next_key.len() as u32));
+				let offset = context.allocate_memory(next_key.len() as u32)?;
+				context.write_memory(offset, &next_key)
+					.map_err(|_| "Invalid attempt to set memory in ext_next_child_storage_key")?;
+				context.write_primitive(written_out, next_key.len() as u32)
+					.map_err(|_| "Invalid attempt to write written_out in ext_next_child_storage_key")?;
+				Ok(offset)
+			} else {
+				context.write_primitive(written_out, u32::max_value())
+					.map_err(|_| "Invalid attempt to write failed written_out in ext_next_child_storage_key")?;
+				Ok(Pointer::null())
+			}
+		}
+
 		ext_get_storage_into(
 			key_data: Pointer<u8>,
 			key_len: WordSize,
@@ -338,11 +408,13 @@ impl_wasm_host_interface! {
 		) -> WordSize {
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to get key in ext_get_storage_into")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 			let maybe_value = with_external_storage(move ||
 				Ok(runtime_io::storage(&key))
 			)?;
 
 			if let Some(value) = maybe_value {
+				record_detail(|| HostCallDetail::ResultLen(value.len() as u32));
 				let data = &value[value.len().min(value_offset as usize)..];
 				let written = std::cmp::min(value_len as usize, data.len());
 				context.write_memory(value_data, &data[..written])
@@ -364,14 +436,17 @@ impl_wasm_host_interface! {
 		) -> WordSize {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_get_child_storage_into")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let key = context.read_memory(key_data, key_len)
 				.map_err(|_| "Invalid attempt to get key in ext_get_child_storage_into")?;
+			record_detail(|| HostCallDetail::StorageKey(key.clone()));
 
 			let maybe_value = with_external_storage(move ||
 				Ok(runtime_io::child_storage(&storage_key, &key))
 			)?;
 
 			if let Some(value) = maybe_value {
+				record_detail(|| HostCallDetail::ResultLen(value.len() as u32));
 				let data = &value[value.len().min(value_offset as usize)..];
 				let written = std::cmp::min(value_len as usize, data.len());
 				context.write_memory(value_data, &data[..written])
@@ -398,6 +473,7 @@ impl_wasm_host_interface! {
 		) -> Pointer<u8> {
 			let storage_key = context.read_memory(storage_key_data, storage_key_len)
 				.map_err(|_| "Invalid attempt to determine storage_key in ext_child_storage_root")?;
+			record_detail(|| HostCallDetail::ChildStorageKey(storage_key.clone()));
 			let value = with_external_storage(move ||
 				Ok(runtime_io::child_storage_root(&storage_key))
 			)?;
@@ -431,6 +507,27 @@ impl_wasm_host_interface! {
 			}
 		}
 
+		ext_storage_start_transaction() {
+			with_external_storage(move ||
+				Ok(runtime_io::storage_start_transaction())
+			)?;
+			Ok(())
+		}
+
+		ext_storage_rollback_transaction() {
+			with_external_storage(move ||
+				Ok(runtime_io::storage_rollback_transaction())
+			)?;
+			Ok(())
+		}
+
+		ext_storage_commit_transaction() {
+			with_external_storage(move ||
+				Ok(runtime_io::storage_commit_transaction())
+			)?;
+			Ok(())
+		}
+
 		ext_blake2_256_enumerated_trie_root(
 			values_data: Pointer<u8>,
 			lens_data: Pointer<u32>,
@@ -459,93 +556,6 @@ impl_wasm_host_interface! {
 			Ok(runtime_io::chain_id())
 		}
 
-		ext_twox_64(data: Pointer<u8>, len: WordSize, out: Pointer<u8>) {
-			let result: [u8; 8] = if len == 0 {
-				let hashed = twox_64(&[0u8; 0]);
-				hashed
-			} else {
-				let key = context.read_memory(data, len)
-					.map_err(|_| "Invalid attempt to get key in ext_twox_64")?;
-				let hashed_key = twox_64(&key);
-				hashed_key
-			};
-
-			context.write_memory(out, &result)
-				.map_err(|_| "Invalid attempt to set result in ext_twox_64")?;
-			Ok(())
-		}
-
-		ext_twox_128(data: Pointer<u8>, len: WordSize, out: Pointer<u8>) {
-			let result: [u8; 16] = if len == 0 {
-				let hashed = twox_128(&[0u8; 0]);
-				hashed
-			} else {
-				let key = context.read_memory(data, len)
-					.map_err(|_| "Invalid attempt to get key in ext_twox_128")?;
-				let hashed_key = twox_128(&key);
-				hashed_key
-			};
-
-			context.write_memory(out, &result)
-				.map_err(|_| "Invalid attempt to set result in ext_twox_128")?;
-			Ok(())
-		}
-
-		ext_twox_256(data: Pointer<u8>, len: WordSize, out: Pointer<u8>) {
-			let result: [u8; 32] = if len == 0 {
-				twox_256(&[0u8; 0])
-			} else {
-				let mem = context.read_memory(data, len)
-					.map_err(|_| "Invalid attempt to get data in ext_twox_256")?;
-				twox_256(&mem)
-			};
-			context.write_memory(out, &result)
-				.map_err(|_| "Invalid attempt to set result in ext_twox_256")?;
-			Ok(())
-		}
-
-		ext_blake2_128(data: Pointer<u8>, len: WordSize, out: Pointer<u8>) {
-			let result: [u8; 16] = if len == 0 {
-				let hashed = blake2_128(&[0u8; 0]);
-				hashed
-			} else {
-				let key = context.read_memory(data, len)
-					.map_err(|_| "Invalid attempt to get key in ext_blake2_128")?;
-				let hashed_key = blake2_128(&key);
-				hashed_key
-			};
-
-			context.write_memory(out, &result)
-				.map_err(|_| "Invalid attempt to set result in ext_blake2_128")?;
-			Ok(())
-		}
-
-		ext_blake2_256(data: Pointer<u8>, len: WordSize, out: Pointer<u8>) {
-			let result: [u8; 32] = if len == 0 {
-				blake2_256(&[0u8; 0])
-			} else {
-				let mem = context.read_memory(data, len)
-					.map_err(|_| "Invalid attempt to get data in ext_blake2_256")?;
-				blake2_256(&mem)
-			};
-			context.write_memory(out, &result)
-				.map_err(|_| "Invalid attempt to set result in ext_blake2_256")?;
-			Ok(())
-		}
-
-		ext_keccak_256(data: Pointer<u8>, len: WordSize, out: Pointer<u8>) {
-			let result: [u8; 32] = if len == 0 {
-				tiny_keccak::keccak256(&[0u8; 0])
-			} else {
-				let mem = context.read_memory(data, len)
-					.map_err(|_| "Invalid attempt to get data in ext_keccak_256")?;
-				tiny_keccak::keccak256(&mem)
-			};
-			context.write_memory(out, &result)
-				.map_err(|_| "Invalid attempt to set result in ext_keccak_256")?;
-			Ok(())
-		}
-
 		ext_ed25519_public_keys(id_data: Pointer<u8>, result_len: Pointer<u32>) -> Pointer<u8> {
 			let mut id = [0u8; 4];
 			context.read_memory_into(id_data, &mut id[..])
@@ -0,0 +1,507 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+mod sandbox;
+
+use state_machine::TestExternalities as CoreTestExternalities;
+use hex_literal::hex;
+use primitives::{
+	Blake2Hasher, blake2_128, blake2_256, ed25519, sr25519, map, Pair, offchain::OffchainExt,
+	traits::Externalities,
+};
+use runtime_test::WASM_BINARY;
+use substrate_offchain::testing;
+use trie::{TrieConfiguration, trie_types::Layout};
+use codec::{Encode, Decode};
+use test_case::test_case;
+
+use crate::WasmExecutionMethod;
+
+type TestExternalities = CoreTestExternalities<Blake2Hasher, u64>;
+
+fn call<E: Externalities>(
+	wasm_method: WasmExecutionMethod,
+	ext: &mut E,
+	heap_pages: u64,
+	code: &[u8],
+	method: &str,
+	data: &[u8],
+) -> crate::error::Result<Vec<u8>> {
+	crate::call_in_wasm::<_, ()>(
+		method,
+		data,
+		wasm_method,
+		ext,
+		code,
+		heap_pages,
+	)
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn returning_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+
+	let output = call(wasm_method, &mut ext, 8, &test_code[..], "test_empty_return", &[]).unwrap();
+	assert_eq!(output, vec![0u8; 0]);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn panicking_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+
+	let output = call(wasm_method, &mut ext, 8, &test_code[..], "test_panic", &[]);
+	assert!(output.is_err());
+
+	let output = call(wasm_method, &mut ext, 8, &test_code[..], "test_conditional_panic", &[0]);
+	assert_eq!(Decode::decode(&mut &output.unwrap()[..]), Ok(Vec::<u8>::new()));
+
+	let output = call(
+		wasm_method,
+		&mut ext,
+		8,
+		&test_code[..],
+		"test_conditional_panic",
+		&vec![2].encode(),
+	);
+	assert!(output.is_err());
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn exhausting_the_stack_should_trap(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+
+	let output = call(wasm_method, &mut ext, 8, &test_code[..], "test_recursion", &100u32.encode());
+	assert_eq!(output.unwrap().len(), 17);
+
+	let output = call(
+		wasm_method,
+		&mut ext,
+		8,
+		&test_code[..],
+		"test_recursion",
+		&1_000_000u32.encode(),
+	);
+	match output.unwrap_err() {
+		crate::error::Error::StackExhausted => {},
+		e => panic!("Expected stack exhausted error, got: {:?}", e),
+	}
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn storage_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+
+	{
+		let mut ext = ext.ext();
+		ext.set_storage(b"foo".to_vec(), b"bar".to_vec());
+		let test_code = WASM_BINARY;
+
+		let output = call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_data_in",
+			&b"Hello world".to_vec().encode(),
+		).unwrap();
+
+		assert_eq!(output, b"all ok!".to_vec().encode());
+	}
+
+	let expected = TestExternalities::new((map![
+		b"input".to_vec() => b"Hello world".to_vec(),
+		b"foo".to_vec() => b"bar".to_vec(),
+		b"baz".to_vec() => b"bar".to_vec()
+	], map![]));
+	assert_eq!(ext, expected);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn clear_prefix_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	{
+		let mut ext = ext.ext();
+		ext.set_storage(b"aaa".to_vec(), b"1".to_vec());
+		ext.set_storage(b"aab".to_vec(), b"2".to_vec());
+		ext.set_storage(b"aba".to_vec(), b"3".to_vec());
+		ext.set_storage(b"abb".to_vec(), b"4".to_vec());
+		ext.set_storage(b"bbb".to_vec(), b"5".to_vec());
+		let test_code = WASM_BINARY;
+
+		// This will clear all entries which prefix is "ab".
+		let output = call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_clear_prefix",
+			&b"ab".to_vec().encode(),
+		).unwrap();
+
+		assert_eq!(output, b"all ok!".to_vec().encode());
+	}
+
+	let expected = TestExternalities::new((map![
+		b"aaa".to_vec() => b"1".to_vec(),
+		b"aab".to_vec() => b"2".to_vec(),
+		b"bbb".to_vec() => b"5".to_vec()
+	], map![]));
+	assert_eq!(expected, ext);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn blake2_256_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &test_code[..], "test_blake2_256", &[0]).unwrap(),
+		blake2_256(&b""[..]).to_vec().encode(),
+	);
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_blake2_256",
+			&b"Hello world!".to_vec().encode(),
+		).unwrap(),
+		blake2_256(&b"Hello world!"[..]).to_vec().encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn blake2_128_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &test_code[..], "test_blake2_128", &[0]).unwrap(),
+		blake2_128(&b""[..]).to_vec().encode(),
+	);
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_blake2_128",
+			&b"Hello world!".to_vec().encode(),
+		).unwrap(),
+		blake2_128(&b"Hello world!"[..]).to_vec().encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn twox_256_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &test_code[..], "test_twox_256", &[0]).unwrap(),
+		hex!(
+			"99e9d85137db46ef4bbea33613baafd56f963c64b1f3685a4eb4abd67ff6203a"
+		).to_vec().encode(),
+	);
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_twox_256",
+			&b"Hello world!".to_vec().encode(),
+		).unwrap(),
+		hex!(
+			"b27dfd7f223f177f2a13647b533599af0c07f68bda23d96d059da2b451a35a74"
+		).to_vec().encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn twox_128_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &test_code[..], "test_twox_128", &[0]).unwrap(),
+		hex!("99e9d85137db46ef4bbea33613baafd5").to_vec().encode(),
+	);
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_twox_128",
+			&b"Hello world!".to_vec().encode(),
+		).unwrap(),
+		hex!("b27dfd7f223f177f2a13647b533599af").to_vec().encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn ed25519_verify_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+	let key = ed25519::Pair::from_seed(&blake2_256(b"test"));
+	let sig = key.sign(b"all ok!");
+	let mut calldata = vec![];
+	calldata.extend_from_slice(key.public().as_ref());
+	calldata.extend_from_slice(sig.as_ref());
+
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_ed25519_verify",
+			&calldata.encode(),
+		).unwrap(),
+		true.encode(),
+	);
+
+	let other_sig = key.sign(b"all is not ok!");
+	let mut calldata = vec![];
+	calldata.extend_from_slice(key.public().as_ref());
+	calldata.extend_from_slice(other_sig.as_ref());
+
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_ed25519_verify",
+			&calldata.encode(),
+		).unwrap(),
+		false.encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn sr25519_verify_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = WASM_BINARY;
+	let key = sr25519::Pair::from_seed(&blake2_256(b"test"));
+	let sig = key.sign(b"all ok!");
+	let mut calldata = vec![];
+	calldata.extend_from_slice(key.public().as_ref());
+	calldata.extend_from_slice(sig.as_ref());
+
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_sr25519_verify",
+			&calldata.encode(),
+		).unwrap(),
+		true.encode(),
+	);
+
+	let other_sig = key.sign(b"all is not ok!");
+	let mut calldata = vec![];
+	calldata.extend_from_slice(key.public().as_ref());
+	calldata.extend_from_slice(other_sig.as_ref());
+
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_sr25519_verify",
+			&calldata.encode(),
+		).unwrap(),
+		false.encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn ordered_trie_root_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let trie_input = vec![b"zero".to_vec(), b"one".to_vec(), b"two".to_vec()];
+	let test_code = WASM_BINARY;
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &test_code[..], "test_ordered_trie_root", &[0]).unwrap(),
+		Layout::<Blake2Hasher>::ordered_trie_root(trie_input.iter()).as_bytes().encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn offchain_local_storage_should_work(wasm_method: WasmExecutionMethod) {
+	use substrate_client::backend::OffchainStorage;
+
+	let mut ext = TestExternalities::default();
+	let (offchain, state) = testing::TestOffchainExt::new();
+	ext.register_extension(OffchainExt::new(offchain));
+	let test_code = WASM_BINARY;
+	let mut ext = ext.ext();
+	assert_eq!(
+		call(
+			wasm_method,
+			&mut ext,
+			8,
+			&test_code[..],
+			"test_offchain_local_storage",
+			&[0],
+		).unwrap(),
+		true.encode(),
+	);
+	assert_eq!(state.read().persistent_storage.get(b"", b"test"), Some(vec![]));
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn offchain_http_should_work(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let (offchain, state) = testing::TestOffchainExt::new();
+	ext.register_extension(OffchainExt::new(offchain));
+	state.write().expect_request(
+		0,
+		testing::PendingRequest {
+			method: "POST".into(),
+			uri: "http://localhost:12345".into(),
+			body: vec![1, 2, 3, 4],
+			headers: vec![("X-Auth".to_owned(), "test".to_owned())],
+			sent: true,
+			response: Some(vec![1, 2, 3]),
+			response_headers: vec![("X-Auth".to_owned(), "hello".to_owned())],
+			..Default::default()
+		},
+	);
+
+	let test_code = WASM_BINARY;
+	let mut ext = ext.ext();
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &test_code[..], "test_offchain_http", &[0]).unwrap(),
+		true.encode(),
+	);
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn missing_host_functions_only_fail_when_called(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+
+	let code = wabt::wat2wasm(r#"
+	(module
+		(import "env" "ext_missing_version_2" (func $missing (param i32) (result i32)))
+		(memory (export "memory") 1)
+		(global (export "__heap_base") i32 (i32.const 1024))
+		(func (export "test_not_calling_missing") (param i32 i32) (result i64)
+			i64.const 0
+		)
+		(func (export "test_calling_missing") (param i32 i32) (result i64)
+			(drop
+				(call $missing (i32.const 0))
+			)
+			i64.const 0
+		)
+	)
+	"#).unwrap();
+
+	assert_eq!(
+		call(wasm_method, &mut ext, 8, &code, "test_not_calling_missing", &[]).unwrap(),
+		Vec::<u8>::new(),
+	);
+
+	let err = call(wasm_method, &mut ext, 8, &code, "test_calling_missing", &[]).unwrap_err();
+	match wasm_method {
+		WasmExecutionMethod::Interpreted => assert_eq!(
+			format!("{}", err),
+			format!(
+				"{}",
+				wasmi::Error::Trap(
+					crate::error::Error::MissingHostFunction("ext_missing".into(), 2).into()
+				),
+			),
+		),
+		// Wasmtime wraps the error of the host function into its own trap type.
+		#[cfg(feature = "wasmtime")]
+		WasmExecutionMethod::Compiled => assert!(
+			format!("{}", err).contains("Host function `ext_missing` version 2 is not provided"),
+		),
+	}
+}
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn host_calls_are_traced(wasm_method: WasmExecutionMethod) {
+	use crate::trace::{trace_host_calls, HostCallDetail};
+
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	ext.set_storage(b"foo".to_vec(), b"bar".to_vec());
+
+	let (output, calls) = trace_host_calls(|| call(
+		wasm_method,
+		&mut ext,
+		8,
+		&WASM_BINARY[..],
+		"test_data_in",
+		&b"Hello world".to_vec().encode(),
+	));
+	assert_eq!(output.unwrap(), b"all ok!".to_vec().encode());
+
+	let storage_calls = calls.iter()
+		.filter(|call| call.function.contains("storage"))
+		.map(|call| (call.function.as_str(), call.details.clone()))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		storage_calls,
+		vec![
+			("ext_set_storage", vec![HostCallDetail::StorageKey(b"input".to_vec())]),
+			(
+				"ext_get_allocated_storage",
+				vec![HostCallDetail::StorageKey(b"foo".to_vec()), HostCallDetail::ResultLen(3)],
+			),
+			("ext_set_storage", vec![HostCallDetail::StorageKey(b"baz".to_vec())]),
+		],
+	);
+	assert!(calls.iter().all(|call| call.error.is_none()));
+
+	// Outside of the tracing scope, nothing is recorded.
+	let ((), calls) = trace_host_calls(|| ());
+	assert!(calls.is_empty());
+}
@@ -20,6 +20,8 @@
 //!
 //! - It provides an implementation of a common entrypoint for calling into the runtime, both
 //! wasm and compiled.
+//! - It provides two engines for executing the wasm runtime: the wasmi interpreter, which is
+//! always available, and the wasmtime JIT compiler, which is enabled by the `wasmtime` feature.
 //! - It defines the environment for the wasm execution, namely the host functions that are to be
 //! provided into the wasm runtime module.
 //! - It also provides the required infrastructure for executing the current wasm runtime (specified
@@ -32,14 +34,20 @@
 #[macro_use]
 mod wasm_utils;
 mod wasmi_execution;
+#[cfg(feature = "wasmtime")]
+mod wasmtime_execution;
 #[macro_use]
 mod native_executor;
 mod sandbox;
 mod allocator;
+mod stack_limit;
 mod host_interface;
 mod wasm_runtime;
+#[cfg(test)]
+mod integration_tests;
 
 pub mod error;
+pub mod trace;
 pub use wasmi;
 pub use native_executor::{with_native_environment, NativeExecutor, NativeExecutionDispatch};
 pub use runtime_version::{RuntimeVersion, NativeVersion};
@@ -48,7 +56,9 @@ pub use codec::Codec;
 pub use primitives::traits::Externalities;
 #[doc(hidden)]
 pub use wasm_interface;
-pub use wasm_runtime::WasmExecutionMethod;
+pub use wasm_runtime::{WasmExecutionMethod, RuntimeCacheStats};
+
+use wasm_interface::HostFunctions;
 
 /// Call the given `function` in the given wasm `code`.
 ///
@@ -59,8 +69,11 @@ pub use wasm_runtime::WasmExecutionMethod;
 /// - `ext`: The externalities that should be set while executing the wasm function.
 /// - `heap_pages`: The number of heap pages to allocate.
 ///
+/// Besides the default Substrate host functions, the host functions provided by `HF` are made
+/// available to the wasm code. `()` can be used if no additional host functions are required.
+///
 /// Returns the `Vec<u8>` that contains the return value of the function.
-pub fn call_in_wasm<E: Externalities>(
+pub fn call_in_wasm<E: Externalities, HF: HostFunctions>(
 	function: &str,
 	call_data: &[u8],
 	execution_method: WasmExecutionMethod,
@@ -73,6 +86,7 @@ pub fn call_in_wasm<E: Externalities>(
 		execution_method,
 		heap_pages,
 		code,
+		<(host_interface::SubstrateHostFunctions, HF)>::host_functions(),
 	)?;
 	instance.call(ext, function, call_data)
 }
@@ -99,7 +113,7 @@ mod tests {
 	fn call_in_interpreted_wasm_works() {
 		let mut ext = TestExternalities::default();
 		let mut ext = ext.ext();
-		let res = call_in_wasm(
+		let res = call_in_wasm::<_, ()>(
 			"test_empty_return",
 			&[],
 			WasmExecutionMethod::Interpreted,
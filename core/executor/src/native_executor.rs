@@ -14,17 +14,30 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{result, cell::RefCell, panic::UnwindSafe};
+use std::{result, cell::RefCell, panic::UnwindSafe, sync::Arc};
 use crate::error::{Error, Result};
-use crate::wasm_runtime::{RuntimesCache, WasmExecutionMethod, WasmRuntime};
+use crate::wasm_runtime::{
+	RuntimesCache, RuntimeCacheMetrics, RuntimeCacheStats, WasmExecutionMethod, WasmRuntime,
+	DEFAULT_MAX_RUNTIME_INSTANCES,
+};
 use crate::RuntimeInfo;
+use crate::host_interface::SubstrateHostFunctions;
 use runtime_version::{NativeVersion, RuntimeVersion};
 use codec::{Decode, Encode};
 use primitives::{NativeOrEncoded, traits::{CodeExecutor, Externalities}};
 use log::{trace, warn};
+use wasm_interface::HostFunctions;
+use lazy_static::lazy_static;
+
+lazy_static! {
+	/// Counters shared by the runtimes caches of all threads.
+	static ref RUNTIMES_CACHE_METRICS: Arc<RuntimeCacheMetrics> = Default::default();
+}
 
 thread_local! {
-	static RUNTIMES_CACHE: RefCell<RuntimesCache> = RefCell::new(RuntimesCache::new());
+	static RUNTIMES_CACHE: RefCell<RuntimesCache> = RefCell::new(
+		RuntimesCache::new(RUNTIMES_CACHE_METRICS.clone())
+	);
 }
 
 /// Default num of pages for the heap
@@ -51,6 +64,10 @@ pub fn with_native_environment<F, U>(ext: &mut dyn Externalities, f: F) -> Resul
 ///
 /// By dispatching we mean that we execute a runtime function specified by it's name.
 pub trait NativeExecutionDispatch: Send + Sync {
+	/// Host functions for custom runtime interfaces that should be callable from within the runtime
+	/// besides the default Substrate runtime interfaces.
+	type ExtendHostFunctions: HostFunctions + 'static;
+
 	/// Dispatch a method in the runtime.
 	///
 	/// If the method with the specified name doesn't exist then `Err` is returned.
@@ -72,6 +89,8 @@ pub struct NativeExecutor<D> {
 	native_version: NativeVersion,
 	/// The number of 64KB pages to allocate for Wasm execution.
 	default_heap_pages: u64,
+	/// The maximum number of runtimes to keep cached.
+	max_runtime_instances: usize,
 }
 
 impl<D: NativeExecutionDispatch> NativeExecutor<D> {
@@ -83,15 +102,30 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	///
 	/// `default_heap_pages` - Number of 64KB pages to allocate for Wasm execution.
 	/// 	Defaults to `DEFAULT_HEAP_PAGES` if `None` is provided.
-	pub fn new(fallback_method: WasmExecutionMethod, default_heap_pages: Option<u64>) -> Self {
+	///
+	/// `max_runtime_instances` - The maximum number of runtimes with different code to keep
+	/// 	cached. Defaults to `DEFAULT_MAX_RUNTIME_INSTANCES` if `None` is provided.
+	pub fn new(
+		fallback_method: WasmExecutionMethod,
+		default_heap_pages: Option<u64>,
+		max_runtime_instances: Option<usize>,
+	) -> Self {
 		NativeExecutor {
 			_dummy: Default::default(),
 			fallback_method,
 			native_version: D::native_version(),
 			default_heap_pages: default_heap_pages.unwrap_or(DEFAULT_HEAP_PAGES),
+			max_runtime_instances: max_runtime_instances.unwrap_or(DEFAULT_MAX_RUNTIME_INSTANCES),
 		}
 	}
 
+	/// Returns the counters of the runtimes cache.
+	///
+	/// The counters are shared by all executors of this process.
+	pub fn runtime_cache_stats(&self) -> RuntimeCacheStats {
+		RUNTIMES_CACHE_METRICS.stats()
+	}
+
 	fn with_runtime<E, R>(
 		&self,
 		ext: &mut E,
@@ -99,7 +133,12 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	) -> Result<R> where E: Externalities {
 		RUNTIMES_CACHE.with(|cache| {
 			let mut cache = cache.borrow_mut();
-			let runtime = cache.fetch_runtime(ext, self.fallback_method, self.default_heap_pages)?;
+			let runtime = cache.fetch_runtime::<_, (SubstrateHostFunctions, D::ExtendHostFunctions)>(
+				ext,
+				self.fallback_method,
+				self.default_heap_pages,
+				self.max_runtime_instances,
+			)?;
 			f(runtime, ext)
 		})
 	}
@@ -112,6 +151,7 @@ impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
 			fallback_method: self.fallback_method,
 			native_version: D::native_version(),
 			default_heap_pages: self.default_heap_pages,
+			max_runtime_instances: self.max_runtime_instances,
 		}
 	}
 }
@@ -208,15 +248,60 @@ impl<D: NativeExecutionDispatch> CodeExecutor for NativeExecutor<D> {
 }
 
 /// Implements a `NativeExecutionDispatch` for provided parameters.
+///
+/// # Example
+///
+/// ```
+/// substrate_executor::native_executor_instance!(
+///     pub MyExecutor,
+///     test_runtime::api::dispatch,
+///     test_runtime::native_version,
+/// );
+/// ```
+///
+/// # With custom host functions
+///
+/// When you want to use custom runtime interfaces from within your runtime, you need to make the
+/// executor aware of the host functions for these interfaces.
+///
+/// ```ignore
+/// use substrate_runtime_interface::runtime_interface;
+///
+/// #[runtime_interface]
+/// trait MyInterface {
+///     fn say_hello_world(data: &str) {
+///         println!("Hello world from: {}", data);
+///     }
+/// }
+///
+/// substrate_executor::native_executor_instance!(
+///     pub MyExecutor,
+///     test_runtime::api::dispatch,
+///     test_runtime::native_version,
+///     my_interface::HostFunctions,
+/// );
+/// ```
+///
+/// When you have multiple interfaces, you can give the host functions as a tuple e.g.:
+/// `(my_interface::HostFunctions, my_interface2::HostFunctions)`
 #[macro_export]
 macro_rules! native_executor_instance {
 	( $pub:vis $name:ident, $dispatcher:path, $version:path $(,)?) => {
 		/// A unit struct which implements `NativeExecutionDispatch` feeding in the hard-coded runtime.
 		$pub struct $name;
-		$crate::native_executor_instance!(IMPL $name, $dispatcher, $version);
+		$crate::native_executor_instance!(IMPL $name, $dispatcher, $version, ());
 	};
-	(IMPL $name:ident, $dispatcher:path, $version:path) => {
+	( $pub:vis $name:ident, $dispatcher:path, $version:path, $custom_host_functions:ty $(,)?) => {
+		/// A unit struct which implements `NativeExecutionDispatch` feeding in the hard-coded runtime.
+		$pub struct $name;
+		$crate::native_executor_instance!(
+			IMPL $name, $dispatcher, $version, $custom_host_functions
+		);
+	};
+	(IMPL $name:ident, $dispatcher:path, $version:path, $custom_host_functions:ty) => {
 		impl $crate::NativeExecutionDispatch for $name {
+			type ExtendHostFunctions = $custom_host_functions;
+
 			fn dispatch(
 				ext: &mut $crate::Externalities,
 				method: &str,
@@ -17,15 +17,26 @@
 #![warn(missing_docs)]
 
 //! This module implements sandboxing support in the runtime.
+//!
+//! Guest modules are executed by a [`SandboxEngine`]. Each supervisor executes its guests with the
+//! engine that matches its own execution method: the interpreted runtime uses [`WasmiEngine`]
+//! and the compiled runtime uses [`WasmtimeEngine`]. The environment definition, the mapping of
+//! guest imports to supervisor functions and the bookkeeping of memories and instances are shared
+//! between the engines.
+
+mod wasmi_backend;
+#[cfg(feature = "wasmtime")]
+mod wasmtime_backend;
+
+pub use self::wasmi_backend::WasmiEngine;
+#[cfg(feature = "wasmtime")]
+pub use self::wasmtime_backend::WasmtimeEngine;
 
 use crate::error::{Result, Error};
 use std::{collections::HashMap, rc::Rc};
 use codec::{Decode, Encode};
 use primitives::sandbox as sandbox_primitives;
-use wasmi::{
-	Externals, ImportResolver, MemoryInstance, MemoryRef, Module, ModuleInstance,
-	ModuleRef, RuntimeArgs, RuntimeValue, Trap, TrapKind, memory_units::Pages,
-};
+use sandbox_primitives::TypedValue;
 use wasm_interface::{Pointer, WordSize};
 
 /// Index of a function inside the supervisor.
@@ -43,9 +54,9 @@ impl From<SupervisorFuncIndex> for usize {
 
 /// Index of a function within guest index space.
 ///
-/// This index is supposed to be used with as index for `Externals`.
+/// Engines pass this index back to [`Dispatch`] when the guest calls an imported function.
 #[derive(Copy, Clone, Debug, PartialEq)]
-struct GuestFuncIndex(usize);
+pub struct GuestFuncIndex(usize);
 
 /// This struct holds a mapping from guest index space to supervisor.
 struct GuestToSupervisorFunctionMapping {
@@ -68,76 +79,81 @@ impl GuestToSupervisorFunctionMapping {
 	}
 }
 
-struct Imports {
+/// The entities a guest module may import, as given by its environment definition.
+///
+/// This is generic over the memory type of the engine.
+pub struct Imports<M> {
 	func_map: HashMap<(Vec<u8>, Vec<u8>), GuestFuncIndex>,
-	memories_map: HashMap<(Vec<u8>, Vec<u8>), MemoryRef>,
+	memories_map: HashMap<(Vec<u8>, Vec<u8>), M>,
 }
 
-impl ImportResolver for Imports {
-	fn resolve_func(
-		&self,
-		module_name: &str,
-		field_name: &str,
-		signature: &::wasmi::Signature,
-	) -> std::result::Result<wasmi::FuncRef, wasmi::Error> {
-		let key = (
-			module_name.as_bytes().to_owned(),
-			field_name.as_bytes().to_owned(),
-		);
-		let idx = *self.func_map.get(&key).ok_or_else(|| {
-			wasmi::Error::Instantiation(format!(
-				"Export {}:{} not found",
-				module_name, field_name
-			))
-		})?;
-		Ok(wasmi::FuncInstance::alloc_host(signature.clone(), idx.0))
+impl<M: Clone> Imports<M> {
+	/// Returns the guest index of the function imported as `module_name`:`field_name`.
+	fn func_by_name(&self, module_name: &str, field_name: &str) -> Option<GuestFuncIndex> {
+		let key = (module_name.as_bytes().to_vec(), field_name.as_bytes().to_vec());
+		self.func_map.get(&key).cloned()
 	}
 
-	fn resolve_memory(
-		&self,
-		module_name: &str,
-		field_name: &str,
-		_memory_type: &::wasmi::MemoryDescriptor,
-	) -> std::result::Result<MemoryRef, wasmi::Error> {
-		let key = (
-			module_name.as_bytes().to_vec(),
-			field_name.as_bytes().to_vec(),
-		);
-		let mem = self.memories_map
-			.get(&key)
-			.ok_or_else(|| {
-				wasmi::Error::Instantiation(format!(
-					"Export {}:{} not found",
-					module_name, field_name
-				))
-			})?
-			.clone();
-		Ok(mem)
+	/// Returns the memory imported as `module_name`:`field_name`.
+	fn memory_by_name(&self, module_name: &str, field_name: &str) -> Option<M> {
+		let key = (module_name.as_bytes().to_vec(), field_name.as_bytes().to_vec());
+		self.memories_map.get(&key).cloned()
 	}
+}
+
+/// Dispatches the calls of a guest module to the supervisor functions it imports.
+pub trait Dispatch {
+	/// Call the supervisor function that is imported by the guest under `index`.
+	fn dispatch(&mut self, index: GuestFuncIndex, args: &[TypedValue]) -> Result<Option<TypedValue>>;
+}
+
+/// A Wasm engine that executes sandboxed guest modules.
+///
+/// Memories and instances are handles, cloning them is cheap. A guest module can only access the
+/// memories it imports, the supervisor accesses them through [`memory_get`] and [`memory_set`].
+///
+/// [`memory_get`]: #tymethod.memory_get
+/// [`memory_set`]: #tymethod.memory_set
+pub trait SandboxEngine: Clone {
+	/// A linear memory that can be imported by guest modules.
+	type Memory: Clone;
+
+	/// An instantiated guest module.
+	type Instance;
 
-	fn resolve_global(
+	/// Create a new memory with `initial` pages that can grow up to `maximum` pages.
+	fn new_memory(&self, initial: u32, maximum: Option<u32>) -> Result<Self::Memory>;
+
+	/// Read `buf.len()` bytes at `offset` from the given memory into `buf`.
+	///
+	/// Returns `Err` if the range is out of bounds.
+	fn memory_get(&self, memory: &Self::Memory, offset: u32, buf: &mut [u8]) -> Result<()>;
+
+	/// Write `data` at `offset` into the given memory.
+	///
+	/// Returns `Err` if the range is out of bounds.
+	fn memory_set(&self, memory: &Self::Memory, offset: u32, data: &[u8]) -> Result<()>;
+
+	/// Instantiate the guest module `wasm` with the given imports and run its start function.
+	///
+	/// Calls of the start function to imported functions are dispatched to `dispatch`.
+	fn instantiate(
 		&self,
-		module_name: &str,
-		field_name: &str,
-		_global_type: &::wasmi::GlobalDescriptor,
-	) -> std::result::Result<wasmi::GlobalRef, wasmi::Error> {
-		Err(wasmi::Error::Instantiation(format!(
-			"Export {}:{} not found",
-			module_name, field_name
-		)))
-	}
+		wasm: &[u8],
+		imports: &Imports<Self::Memory>,
+		dispatch: &mut dyn Dispatch,
+	) -> std::result::Result<Self::Instance, InstantiationError>;
 
-	fn resolve_table(
+	/// Invoke the function `export_name` exported by the given instance.
+	///
+	/// Calls of the guest to imported functions are dispatched to `dispatch`.
+	fn invoke(
 		&self,
-		module_name: &str,
-		field_name: &str,
-		_table_type: &::wasmi::TableDescriptor,
-	) -> std::result::Result<wasmi::TableRef, wasmi::Error> {
-		Err(wasmi::Error::Instantiation(format!(
-			"Export {}:{} not found",
-			module_name, field_name
-		)))
-	}
+		instance: &Self::Instance,
+		export_name: &str,
+		args: &[TypedValue],
+		dispatch: &mut dyn Dispatch,
+	) -> Result<Option<TypedValue>>;
 }
 
 /// This trait encapsulates sandboxing capabilities.
@@ -147,11 +163,14 @@ pub trait SandboxCapabilities {
 	/// Represents a function reference into the supervisor environment.
 	type SupervisorFuncRef;
 
+	/// The engine that executes the guest modules of this supervisor.
+	type Engine: SandboxEngine;
+
 	/// Returns a reference to an associated sandbox `Store`.
-	fn store(&self) -> &Store<Self::SupervisorFuncRef>;
+	fn store(&self) -> &Store<Self::SupervisorFuncRef, Self::Engine>;
 
 	/// Returns a mutable reference to an associated sandbox `Store`.
-	fn store_mut(&mut self) -> &mut Store<Self::SupervisorFuncRef>;
+	fn store_mut(&mut self) -> &mut Store<Self::SupervisorFuncRef, Self::Engine>;
 
 	/// Allocate space of the specified length in the supervisor memory.
 	///
@@ -208,60 +227,42 @@ pub trait SandboxCapabilities {
 	) -> Result<i64>;
 }
 
-/// Implementation of [`Externals`] that allows execution of guest module with
-/// [externals][`Externals`] that might refer functions defined by supervisor.
-///
-/// [`Externals`]: ../../wasmi/trait.Externals.html
+/// Implementation of [`Dispatch`] that executes the calls of a guest module in the supervisor
+/// context.
 pub struct GuestExternals<'a, FE: SandboxCapabilities + 'a> {
 	supervisor_externals: &'a mut FE,
-	sandbox_instance: &'a SandboxInstance<FE::SupervisorFuncRef>,
+	dispatch_thunk: &'a FE::SupervisorFuncRef,
+	guest_to_supervisor_mapping: &'a GuestToSupervisorFunctionMapping,
 	state: u32,
 }
 
-fn trap(msg: &'static str) -> Trap {
-	TrapKind::Host(Box::new(Error::Other(msg.into()))).into()
-}
-
-fn deserialize_result(serialized_result: &[u8]) -> std::result::Result<Option<RuntimeValue>, Trap> {
+fn deserialize_result(serialized_result: &[u8]) -> Result<Option<TypedValue>> {
 	use self::sandbox_primitives::{HostError, ReturnValue};
 	let result_val = std::result::Result::<ReturnValue, HostError>::decode(&mut &serialized_result[..])
-		.map_err(|_| trap("Decoding Result<ReturnValue, HostError> failed!"))?;
+		.map_err(|_| Error::Other("Decoding Result<ReturnValue, HostError> failed!".into()))?;
 
 	match result_val {
 		Ok(return_value) => Ok(match return_value {
 			ReturnValue::Unit => None,
-			ReturnValue::Value(typed_value) => Some(RuntimeValue::from(typed_value)),
+			ReturnValue::Value(typed_value) => Some(typed_value),
 		}),
-		Err(HostError) => Err(trap("Supervisor function returned sandbox::HostError")),
+		Err(HostError) => Err(Error::Other("Supervisor function returned sandbox::HostError".into())),
 	}
 }
 
-impl<'a, FE: SandboxCapabilities + 'a> Externals for GuestExternals<'a, FE> {
-	fn invoke_index(
-		&mut self,
-		index: usize,
-		args: RuntimeArgs,
-	) -> std::result::Result<Option<RuntimeValue>, Trap> {
-		// Make `index` typesafe again.
-		let index = GuestFuncIndex(index);
-
-		let func_idx = self.sandbox_instance
-			.guest_to_supervisor_mapping
+impl<'a, FE: SandboxCapabilities + 'a> Dispatch for GuestExternals<'a, FE> {
+	fn dispatch(&mut self, index: GuestFuncIndex, args: &[TypedValue]) -> Result<Option<TypedValue>> {
+		let func_idx = self.guest_to_supervisor_mapping
 			.func_by_guest_index(index)
 			.expect(
-				"`invoke_index` is called with indexes registered via `FuncInstance::alloc_host`;
-					`FuncInstance::alloc_host` is called with indexes that was obtained from `guest_to_supervisor_mapping`;
+				"`dispatch` is called with indexes that were resolved from `Imports`;
+					`Imports` only contains indexes that were obtained from `guest_to_supervisor_mapping`;
 					`func_by_guest_index` called with `index` can't return `None`;
 					qed"
 			);
 
 		// Serialize arguments into a byte vector.
-		let invoke_args_data: Vec<u8> = args.as_ref()
-			.iter()
-			.cloned()
-			.map(sandbox_primitives::TypedValue::from)
-			.collect::<Vec<_>>()
-			.encode();
+		let invoke_args_data: Vec<u8> = args.encode();
 
 		let state = self.state;
 
@@ -271,7 +272,7 @@ impl<'a, FE: SandboxCapabilities + 'a> Externals for GuestExternals<'a, FE> {
 		let invoke_args_ptr = self.supervisor_externals.allocate(invoke_args_len)?;
 		self.supervisor_externals.write_memory(invoke_args_ptr, &invoke_args_data)?;
 		let result = self.supervisor_externals.invoke(
-			&self.sandbox_instance.dispatch_thunk,
+			self.dispatch_thunk,
 			invoke_args_ptr,
 			invoke_args_len,
 			state,
@@ -300,7 +301,8 @@ impl<'a, FE: SandboxCapabilities + 'a> Externals for GuestExternals<'a, FE> {
 
 fn with_guest_externals<FE, R, F>(
 	supervisor_externals: &mut FE,
-	sandbox_instance: &SandboxInstance<FE::SupervisorFuncRef>,
+	dispatch_thunk: &FE::SupervisorFuncRef,
+	guest_to_supervisor_mapping: &GuestToSupervisorFunctionMapping,
 	state: u32,
 	f: F,
 ) -> R
@@ -310,7 +312,8 @@ where
 {
 	let mut guest_externals = GuestExternals {
 		supervisor_externals,
-		sandbox_instance,
+		dispatch_thunk,
+		guest_to_supervisor_mapping,
 		state,
 	};
 	f(&mut guest_externals)
@@ -327,16 +330,17 @@ where
 /// it's required to provide supervisor externals: it will be used to execute
 /// code in the supervisor context.
 ///
-/// This is generic over a supervisor function reference type.
+/// This is generic over a supervisor function reference type and the instance type of the
+/// engine.
 ///
 /// [`invoke`]: #method.invoke
-pub struct SandboxInstance<FR> {
-	instance: ModuleRef,
+pub struct SandboxInstance<FR, I> {
+	instance: I,
 	dispatch_thunk: FR,
 	guest_to_supervisor_mapping: GuestToSupervisorFunctionMapping,
 }
 
-impl<FR> SandboxInstance<FR> {
+impl<FR, I> SandboxInstance<FR, I> {
 	/// Invoke an exported function by a name.
 	///
 	/// `supervisor_externals` is required to execute the implementations
@@ -344,21 +348,24 @@ impl<FR> SandboxInstance<FR> {
 	///
 	/// The `state` parameter can be used to provide custom data for
 	/// these syscall implementations.
-	pub fn invoke<FE: SandboxCapabilities<SupervisorFuncRef=FR>>(
+	pub fn invoke<FE>(
 		&self,
 		export_name: &str,
-		args: &[RuntimeValue],
+		args: &[TypedValue],
 		supervisor_externals: &mut FE,
 		state: u32,
-	) -> std::result::Result<Option<wasmi::RuntimeValue>, wasmi::Error> {
+	) -> Result<Option<TypedValue>>
+	where
+		FE: SandboxCapabilities<SupervisorFuncRef=FR>,
+		FE::Engine: SandboxEngine<Instance=I>,
+	{
+		let engine = supervisor_externals.store().engine().clone();
 		with_guest_externals(
 			supervisor_externals,
-			self,
+			&self.dispatch_thunk,
+			&self.guest_to_supervisor_mapping,
 			state,
-			|guest_externals| {
-				self.instance
-					.invoke_export(export_name, args, guest_externals)
-			},
+			|guest_externals| engine.invoke(&self.instance, export_name, args, guest_externals),
 		)
 	}
 }
@@ -378,10 +385,10 @@ pub enum InstantiationError {
 	StartTrapped,
 }
 
-fn decode_environment_definition(
+fn decode_environment_definition<M: Clone>(
 	raw_env_def: &[u8],
-	memories: &[Option<MemoryRef>],
-) -> std::result::Result<(Imports, GuestToSupervisorFunctionMapping), InstantiationError> {
+	memories: &[Option<M>],
+) -> std::result::Result<(Imports<M>, GuestToSupervisorFunctionMapping), InstantiationError> {
 	let env_def = sandbox_primitives::EnvironmentDefinition::decode(&mut &raw_env_def[..])
 		.map_err(|_| InstantiationError::EnvironmentDefinitionCorrupted)?;
 
@@ -443,29 +450,21 @@ pub fn instantiate<FE: SandboxCapabilities>(
 	let (imports, guest_to_supervisor_mapping) =
 		decode_environment_definition(raw_env_def, &supervisor_externals.store().memories)?;
 
-	let module = Module::from_buffer(wasm).map_err(|_| InstantiationError::ModuleDecoding)?;
-	let instance = ModuleInstance::new(&module, &imports).map_err(|_| InstantiationError::Instantiation)?;
+	let engine = supervisor_externals.store().engine().clone();
+	let instance = with_guest_externals(
+		supervisor_externals,
+		&dispatch_thunk,
+		&guest_to_supervisor_mapping,
+		state,
+		|guest_externals| engine.instantiate(wasm, &imports, guest_externals),
+	)?;
 
 	let sandbox_instance = Rc::new(SandboxInstance {
-		// In general, it's not a very good idea to use `.not_started_instance()` for anything
-		// but for extracting memory and tables. But in this particular case, we are extracting
-		// for the purpose of running `start` function which should be ok.
-		instance: instance.not_started_instance().clone(),
+		instance,
 		dispatch_thunk,
 		guest_to_supervisor_mapping,
 	});
 
-	with_guest_externals(
-		supervisor_externals,
-		&sandbox_instance,
-		state,
-		|guest_externals| {
-			instance
-				.run_start(guest_externals)
-				.map_err(|_| InstantiationError::StartTrapped)
-		},
-	)?;
-
 	// At last, register the instance.
 	let instance_idx = supervisor_externals
 		.store_mut()
@@ -475,22 +474,30 @@ pub fn instantiate<FE: SandboxCapabilities>(
 
 /// This struct keeps track of all sandboxed components.
 ///
-/// This is generic over a supervisor function reference type.
-pub struct Store<FR> {
+/// This is generic over a supervisor function reference type and the engine that executes the
+/// guest modules.
+pub struct Store<FR, E: SandboxEngine> {
+	engine: E,
 	// Memories and instances are `Some` untill torndown.
-	instances: Vec<Option<Rc<SandboxInstance<FR>>>>,
-	memories: Vec<Option<MemoryRef>>,
+	instances: Vec<Option<Rc<SandboxInstance<FR, E::Instance>>>>,
+	memories: Vec<Option<E::Memory>>,
 }
 
-impl<FR> Store<FR> {
-	/// Create a new empty sandbox store.
-	pub fn new() -> Self {
+impl<FR, E: SandboxEngine> Store<FR, E> {
+	/// Create a new empty sandbox store whose guest modules are executed by `engine`.
+	pub fn new(engine: E) -> Self {
 		Store {
+			engine,
 			instances: Vec::new(),
 			memories: Vec::new(),
 		}
 	}
 
+	/// Returns the engine that executes the guest modules of this store.
+	pub fn engine(&self) -> &E {
+		&self.engine
+	}
+
 	/// Create a new memory instance and return it's index.
 	///
 	/// # Errors
@@ -500,14 +507,10 @@ impl<FR> Store<FR> {
 	pub fn new_memory(&mut self, initial: u32, maximum: u32) -> Result<u32> {
 		let maximum = match maximum {
 			sandbox_primitives::MEM_UNLIMITED => None,
-			specified_limit => Some(Pages(specified_limit as usize)),
+			specified_limit => Some(specified_limit),
 		};
 
-		let mem =
-			MemoryInstance::alloc(
-				Pages(initial as usize),
-				maximum,
-			)?;
+		let mem = self.engine.new_memory(initial, maximum)?;
 
 		let mem_idx = self.memories.len();
 		self.memories.push(Some(mem));
@@ -520,7 +523,7 @@ impl<FR> Store<FR> {
 	///
 	/// Returns `Err` If `instance_idx` isn't a valid index of an instance or
 	/// instance is already torndown.
-	pub fn instance(&self, instance_idx: u32) -> Result<Rc<SandboxInstance<FR>>> {
+	pub fn instance(&self, instance_idx: u32) -> Result<Rc<SandboxInstance<FR, E::Instance>>> {
 		self.instances
 			.get(instance_idx as usize)
 			.cloned()
@@ -534,7 +537,7 @@ impl<FR> Store<FR> {
 	///
 	/// Returns `Err` If `memory_idx` isn't a valid index of an memory or
 	/// if memory has been torn down.
-	pub fn memory(&self, memory_idx: u32) -> Result<MemoryRef> {
+	pub fn memory(&self, memory_idx: u32) -> Result<E::Memory> {
 		self.memories
 			.get(memory_idx as usize)
 			.cloned()
@@ -576,300 +579,12 @@ impl<FR> Store<FR> {
 		}
 	}
 
-	fn register_sandbox_instance(&mut self, sandbox_instance: Rc<SandboxInstance<FR>>) -> u32 {
+	fn register_sandbox_instance(
+		&mut self,
+		sandbox_instance: Rc<SandboxInstance<FR, E::Instance>>,
+	) -> u32 {
 		let instance_idx = self.instances.len();
 		self.instances.push(Some(sandbox_instance));
 		instance_idx as u32
 	}
 }
-
-#[cfg(test)]
-mod tests {
-	use super::*;
-	use primitives::{Blake2Hasher, traits::Externalities};
-	use crate::wasm_runtime::WasmRuntime;
-	use crate::wasmi_execution;
-	use state_machine::TestExternalities as CoreTestExternalities;
-	use wabt;
-	use runtime_test::WASM_BINARY;
-
-	type TestExternalities = CoreTestExternalities<Blake2Hasher, u64>;
-
-	fn call_wasm<E: Externalities>(
-		ext: &mut E,
-		heap_pages: u64,
-		code: &[u8],
-		method: &str,
-		data: &[u8],
-	) -> Result<Vec<u8>> {
-		let mut instance = wasmi_execution::create_instance(ext, code, heap_pages)
-			.map_err(|err| err.to_string())?;
-		instance.call(ext, method, data)
-	}
-
-	#[test]
-	fn sandbox_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(import "env" "assert" (func $assert (param i32)))
-			(import "env" "inc_counter" (func $inc_counter (param i32) (result i32)))
-			(func (export "call")
-				(drop
-					(call $inc_counter (i32.const 5))
-				)
-
-				(call $inc_counter (i32.const 3))
-				;; current counter value is on the stack
-
-				;; check whether current == 8
-				i32.const 8
-				i32.eq
-
-				call $assert
-			)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox", &code).unwrap(),
-			true.encode(),
-		);
-	}
-
-	#[test]
-	fn sandbox_trap() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(import "env" "assert" (func $assert (param i32)))
-			(func (export "call")
-				i32.const 0
-				call $assert
-			)
-		)
-		"#).unwrap();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox", &code).unwrap(),
-			vec![0],
-		);
-	}
-
-	#[test]
-	fn sandbox_should_trap_when_heap_exhausted() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(import "env" "assert" (func $assert (param i32)))
-			(func (export "call")
-				i32.const 0
-				call $assert
-			)
-		)
-		"#).unwrap().encode();
-
-		let res = call_wasm(&mut ext, 8, &test_code[..], "test_exhaust_heap", &code);
-		assert_eq!(res.is_err(), true);
-		if let Err(err) = res {
-			assert_eq!(
-				format!("{}", err),
-				format!(
-					"{}",
-					wasmi::Error::Trap(Error::FunctionExecution("AllocatorOutOfSpace".into()).into()),
-				),
-			);
-		}
-	}
-
-	#[test]
-	fn start_called() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(import "env" "assert" (func $assert (param i32)))
-			(import "env" "inc_counter" (func $inc_counter (param i32) (result i32)))
-
-			;; Start function
-			(start $start)
-			(func $start
-				;; Increment counter by 1
-				(drop
-					(call $inc_counter (i32.const 1))
-				)
-			)
-
-			(func (export "call")
-				;; Increment counter by 1. The current value is placed on the stack.
-				(call $inc_counter (i32.const 1))
-
-				;; Counter is incremented twice by 1, once there and once in `start` func.
-				;; So check the returned value is equal to 2.
-				i32.const 2
-				i32.eq
-				call $assert
-			)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox", &code).unwrap(),
-			true.encode(),
-		);
-	}
-
-	#[test]
-	fn invoke_args() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(import "env" "assert" (func $assert (param i32)))
-
-			(func (export "call") (param $x i32) (param $y i64)
-				;; assert that $x = 0x12345678
-				(call $assert
-					(i32.eq
-						(get_local $x)
-						(i32.const 0x12345678)
-					)
-				)
-
-				(call $assert
-					(i64.eq
-						(get_local $y)
-						(i64.const 0x1234567887654321)
-					)
-				)
-			)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox_args", &code).unwrap(),
-			true.encode(),
-		);
-	}
-
-	#[test]
-	fn return_val() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(func (export "call") (param $x i32) (result i32)
-				(i32.add
-					(get_local $x)
-					(i32.const 1)
-				)
-			)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox_return_val", &code).unwrap(),
-			true.encode(),
-		);
-	}
-
-	#[test]
-	fn unlinkable_module() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(import "env" "non-existent" (func))
-
-			(func (export "call")
-			)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox_instantiate", &code).unwrap(),
-			1u8.encode(),
-		);
-	}
-
-	#[test]
-	fn corrupted_module() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		// Corrupted wasm file
-		let code = vec![0u8, 0, 0, 0, 1, 0, 0, 0].encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox_instantiate", &code).unwrap(),
-			1u8.encode(),
-		);
-	}
-
-	#[test]
-	fn start_fn_ok() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(func (export "call")
-			)
-
-			(func $start
-			)
-
-			(start $start)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox_instantiate", &code).unwrap(),
-			0u8.encode(),
-		);
-	}
-
-	#[test]
-	fn start_fn_traps() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let code = wabt::wat2wasm(r#"
-		(module
-			(func (export "call")
-			)
-
-			(func $start
-				unreachable
-			)
-
-			(start $start)
-		)
-		"#).unwrap().encode();
-
-		assert_eq!(
-			call_wasm(&mut ext, 8, &test_code[..], "test_sandbox_instantiate", &code).unwrap(),
-			2u8.encode(),
-		);
-	}
-}
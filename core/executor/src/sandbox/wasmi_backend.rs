@@ -0,0 +1,153 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sandboxing of guest modules with the wasmi interpreter.
+
+use super::{Dispatch, GuestFuncIndex, Imports, InstantiationError, SandboxEngine};
+use crate::error::Result;
+use primitives::sandbox::TypedValue;
+use wasmi::{
+	Externals, ImportResolver, MemoryInstance, MemoryRef, Module, ModuleInstance, ModuleRef,
+	RuntimeArgs, RuntimeValue, Trap, TrapKind, memory_units::Pages,
+};
+
+/// A [`SandboxEngine`] that interprets guest modules with wasmi.
+#[derive(Clone, Default)]
+pub struct WasmiEngine;
+
+impl SandboxEngine for WasmiEngine {
+	type Memory = MemoryRef;
+	type Instance = ModuleRef;
+
+	fn new_memory(&self, initial: u32, maximum: Option<u32>) -> Result<MemoryRef> {
+		MemoryInstance::alloc(
+			Pages(initial as usize),
+			maximum.map(|maximum| Pages(maximum as usize)),
+		).map_err(Into::into)
+	}
+
+	fn memory_get(&self, memory: &MemoryRef, offset: u32, buf: &mut [u8]) -> Result<()> {
+		memory.get_into(offset, buf).map_err(Into::into)
+	}
+
+	fn memory_set(&self, memory: &MemoryRef, offset: u32, data: &[u8]) -> Result<()> {
+		memory.set(offset, data).map_err(Into::into)
+	}
+
+	fn instantiate(
+		&self,
+		wasm: &[u8],
+		imports: &Imports<MemoryRef>,
+		dispatch: &mut dyn Dispatch,
+	) -> std::result::Result<ModuleRef, InstantiationError> {
+		let module = Module::from_buffer(wasm).map_err(|_| InstantiationError::ModuleDecoding)?;
+		let instance = ModuleInstance::new(&module, imports)
+			.map_err(|_| InstantiationError::Instantiation)?;
+
+		instance
+			.run_start(&mut GuestExternals(dispatch))
+			.map_err(|_| InstantiationError::StartTrapped)
+	}
+
+	fn invoke(
+		&self,
+		instance: &ModuleRef,
+		export_name: &str,
+		args: &[TypedValue],
+		dispatch: &mut dyn Dispatch,
+	) -> Result<Option<TypedValue>> {
+		let args = args.iter().cloned().map(Into::into).collect::<Vec<RuntimeValue>>();
+		instance
+			.invoke_export(export_name, &args, &mut GuestExternals(dispatch))
+			.map(|result| result.map(Into::into))
+			.map_err(Into::into)
+	}
+}
+
+/// Implementation of [`Externals`] that forwards the calls of a guest module to a [`Dispatch`].
+///
+/// [`Externals`]: ../../wasmi/trait.Externals.html
+struct GuestExternals<'a>(&'a mut dyn Dispatch);
+
+impl<'a> Externals for GuestExternals<'a> {
+	fn invoke_index(
+		&mut self,
+		index: usize,
+		args: RuntimeArgs,
+	) -> std::result::Result<Option<RuntimeValue>, Trap> {
+		let args = args.as_ref().iter().cloned().map(Into::into).collect::<Vec<TypedValue>>();
+
+		// Make `index` typesafe again.
+		self.0.dispatch(GuestFuncIndex(index), &args)
+			.map(|result| result.map(Into::into))
+			.map_err(|e| TrapKind::Host(Box::new(e)).into())
+	}
+}
+
+impl ImportResolver for Imports<MemoryRef> {
+	fn resolve_func(
+		&self,
+		module_name: &str,
+		field_name: &str,
+		signature: &::wasmi::Signature,
+	) -> std::result::Result<wasmi::FuncRef, wasmi::Error> {
+		let idx = self.func_by_name(module_name, field_name).ok_or_else(|| {
+			wasmi::Error::Instantiation(format!(
+				"Export {}:{} not found",
+				module_name, field_name
+			))
+		})?;
+		Ok(wasmi::FuncInstance::alloc_host(signature.clone(), idx.0))
+	}
+
+	fn resolve_memory(
+		&self,
+		module_name: &str,
+		field_name: &str,
+		_memory_type: &::wasmi::MemoryDescriptor,
+	) -> std::result::Result<MemoryRef, wasmi::Error> {
+		self.memory_by_name(module_name, field_name).ok_or_else(|| {
+			wasmi::Error::Instantiation(format!(
+				"Export {}:{} not found",
+				module_name, field_name
+			))
+		})
+	}
+
+	fn resolve_global(
+		&self,
+		module_name: &str,
+		field_name: &str,
+		_global_type: &::wasmi::GlobalDescriptor,
+	) -> std::result::Result<wasmi::GlobalRef, wasmi::Error> {
+		Err(wasmi::Error::Instantiation(format!(
+			"Export {}:{} not found",
+			module_name, field_name
+		)))
+	}
+
+	fn resolve_table(
+		&self,
+		module_name: &str,
+		field_name: &str,
+		_table_type: &::wasmi::TableDescriptor,
+	) -> std::result::Result<wasmi::TableRef, wasmi::Error> {
+		Err(wasmi::Error::Instantiation(format!(
+			"Export {}:{} not found",
+			module_name, field_name
+		)))
+	}
+}
@@ -0,0 +1,270 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sandboxing of guest modules with the Wasmtime JIT compiler.
+//!
+//! All memories and instances of a sandbox store live in a single wasmtime `Store` that is
+//! separate from the store of the supervisor, so a guest can never reach the memory of the
+//! supervisor. While a guest is executing, the guest store is borrowed by the call. Host functions
+//! called by the guest therefore lend their `Caller` to the engine, and all accesses to the guest
+//! store made by the supervisor in the meantime, e.g. reading a sandboxed memory or invoking
+//! another guest, go through this caller. Borrows are only lent through a [`Lend`] guard, which
+//! takes them back when the call that lent them returns or unwinds.
+
+use super::{Dispatch, GuestFuncIndex, Imports, InstantiationError, SandboxEngine};
+use crate::error::{Error, Result};
+use lazy_static::lazy_static;
+use primitives::sandbox::TypedValue;
+use std::{cell::{Cell, RefCell}, marker::PhantomData, ptr::NonNull, rc::Rc};
+use wasmtime::{
+	AsContextMut, Caller, Config, Engine, Extern, ExternType, Func, Instance, Memory, MemoryType,
+	Module, Store, StoreContextMut, Trap, Val,
+};
+
+lazy_static! {
+	/// The engine that compiles all guest modules.
+	///
+	/// Only the features that are supported by wasmi are enabled, so both engines accept the
+	/// same guest modules.
+	static ref ENGINE: Engine = {
+		let mut config = Config::new();
+		config
+			.wasm_threads(false)
+			.wasm_reference_types(false)
+			.wasm_simd(false)
+			.wasm_bulk_memory(false)
+			.wasm_multi_value(false);
+		Engine::new(&config).expect("the configuration only disables optional features; qed")
+	};
+}
+
+/// A slot in which a borrow can be lent, see [`Lend`].
+type Slot = Cell<Option<NonNull<()>>>;
+
+/// The borrows that are lent to the code running further down the stack during a call into a
+/// guest.
+#[derive(Default)]
+struct Lent {
+	/// The `&mut dyn Dispatch` of the call into the guest that is currently executing, if any.
+	dispatch: Slot,
+	/// The `Caller` of the host function that is currently dispatching to the supervisor, if any.
+	caller: Slot,
+}
+
+/// Lends a mutable borrow through a [`Slot`] for as long as the guard lives.
+///
+/// The previous content of the slot is restored when the guard is dropped. As the guard can't
+/// outlive the borrow and is also dropped when unwinding, the slot never holds a dangling pointer,
+/// and while the borrow is lent it can only be used through the slot.
+struct Lend<'a, T> {
+	slot: &'a Slot,
+	previous: Option<NonNull<()>>,
+	_borrow: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Lend<'a, T> {
+	fn new(slot: &'a Slot, borrow: &'a mut T) -> Self {
+		let previous = slot.replace(Some(NonNull::from(borrow).cast()));
+		Lend { slot, previous, _borrow: PhantomData }
+	}
+}
+
+impl<'a, T> Drop for Lend<'a, T> {
+	fn drop(&mut self) {
+		self.slot.set(self.previous);
+	}
+}
+
+/// The data stored in the guest store.
+struct GuestState {
+	lent: Rc<Lent>,
+}
+
+/// A [`SandboxEngine`] that compiles guest modules with wasmtime.
+#[derive(Clone)]
+pub struct WasmtimeEngine {
+	store: Rc<RefCell<Store<GuestState>>>,
+	lent: Rc<Lent>,
+}
+
+impl WasmtimeEngine {
+	/// Create a new engine with an empty guest store.
+	pub fn new() -> Self {
+		let lent = Rc::new(Lent::default());
+		let store = Store::new(&ENGINE, GuestState { lent: lent.clone() });
+		WasmtimeEngine {
+			store: Rc::new(RefCell::new(store)),
+			lent,
+		}
+	}
+
+	/// Run `f` with the context of the guest store.
+	fn with_context<R>(&self, f: impl FnOnce(StoreContextMut<GuestState>) -> R) -> R {
+		match self.lent.caller.get() {
+			// Safe, because only a `Caller<GuestState>` is lent through this slot, by the host
+			// function that owns it, and that host function doesn't use it while it is lent.
+			Some(caller) => f(unsafe { caller.cast::<Caller<GuestState>>().as_mut() }.as_context_mut()),
+			None => f(self.store.borrow_mut().as_context_mut()),
+		}
+	}
+
+	/// Run `f` with the context of the guest store, while `dispatch` is lent to the host functions
+	/// called by the guest.
+	fn with_dispatch<R>(
+		&self,
+		mut dispatch: &mut dyn Dispatch,
+		f: impl FnOnce(StoreContextMut<GuestState>) -> R,
+	) -> R {
+		let lent = self.lent.clone();
+		let _lend = Lend::new(&lent.dispatch, &mut dispatch);
+		self.with_context(f)
+	}
+}
+
+impl Default for WasmtimeEngine {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SandboxEngine for WasmtimeEngine {
+	type Memory = Memory;
+	type Instance = Instance;
+
+	fn new_memory(&self, initial: u32, maximum: Option<u32>) -> Result<Memory> {
+		self.with_context(|mut ctx| Memory::new(&mut ctx, MemoryType::new(initial, maximum)))
+			.map_err(|e| Error::Other(format!("Failed to create a sandboxed memory: {}", e)))
+	}
+
+	fn memory_get(&self, memory: &Memory, offset: u32, buf: &mut [u8]) -> Result<()> {
+		self.with_context(|ctx| memory.read(&ctx, offset as usize, buf))
+			.map_err(|_| Error::Other("sandboxed memory read is out of bounds".into()))
+	}
+
+	fn memory_set(&self, memory: &Memory, offset: u32, data: &[u8]) -> Result<()> {
+		self.with_context(|ctx| memory.write(ctx, offset as usize, data))
+			.map_err(|_| Error::Other("sandboxed memory write is out of bounds".into()))
+	}
+
+	fn instantiate(
+		&self,
+		wasm: &[u8],
+		imports: &Imports<Memory>,
+		dispatch: &mut dyn Dispatch,
+	) -> std::result::Result<Instance, InstantiationError> {
+		let module = Module::new(&ENGINE, wasm).map_err(|_| InstantiationError::ModuleDecoding)?;
+
+		self.with_dispatch(dispatch, |mut ctx| {
+			let mut externs = Vec::new();
+			for import in module.imports() {
+				let module_name = import.module();
+				let field_name = import.name();
+				let import: Extern = match import.ty() {
+					ExternType::Func(func_type) => {
+						let index = imports.func_by_name(module_name, field_name)
+							.ok_or(InstantiationError::Instantiation)?;
+						Func::new(
+							&mut ctx,
+							func_type,
+							move |caller, params, results| call_supervisor(caller, index, params, results),
+						).into()
+					}
+					ExternType::Memory(_) => imports.memory_by_name(module_name, field_name)
+						.ok_or(InstantiationError::Instantiation)?
+						.into(),
+					_ => return Err(InstantiationError::Instantiation),
+				};
+				externs.push(import);
+			}
+
+			Instance::new(&mut ctx, &module, &externs).map_err(|e| match e.downcast_ref::<Trap>() {
+				Some(_) => InstantiationError::StartTrapped,
+				None => InstantiationError::Instantiation,
+			})
+		})
+	}
+
+	fn invoke(
+		&self,
+		instance: &Instance,
+		export_name: &str,
+		args: &[TypedValue],
+		dispatch: &mut dyn Dispatch,
+	) -> Result<Option<TypedValue>> {
+		self.with_dispatch(dispatch, |mut ctx| {
+			let func = instance.get_func(&mut ctx, export_name)
+				.ok_or_else(|| Error::MethodNotFound(export_name.into()))?;
+			let params = args.iter().cloned().map(into_val).collect::<Vec<_>>();
+			let mut results = vec![Val::I32(0); func.ty(&ctx).results().len()];
+
+			func.call(&mut ctx, &params, &mut results)
+				.map_err(|e| Error::Other(format!("Sandboxed execution trapped: {}", e)))?;
+			match results.first() {
+				None => Ok(None),
+				Some(result) => from_val(result)
+					.map(Some)
+					.ok_or_else(|| "Sandboxed function returned an unsupported value".into()),
+			}
+		})
+	}
+}
+
+/// Forward the call of a guest to the imported function `index` to the supervisor.
+fn call_supervisor(
+	mut caller: Caller<GuestState>,
+	index: GuestFuncIndex,
+	params: &[Val],
+	results: &mut [Val],
+) -> std::result::Result<(), Trap> {
+	let args = params.iter().map(from_val).collect::<Option<Vec<_>>>()
+		.ok_or_else(|| Trap::new("Guest passed an unsupported value to the supervisor"))?;
+	let lent = caller.data().lent.clone();
+	let dispatch = lent.dispatch.get()
+		.ok_or_else(|| Trap::new("Guest called the supervisor outside of a call"))?;
+
+	let result = {
+		let _lend = Lend::new(&lent.caller, &mut caller);
+		// Safe, because only the `&mut dyn Dispatch` of the call into the guest that led to this
+		// function is lent through this slot, and that call doesn't use it while it is lent.
+		unsafe { dispatch.cast::<&mut dyn Dispatch>().as_mut() }.dispatch(index, &args)
+	};
+
+	match (result.map_err(|e| Trap::new(e.to_string()))?, results.first_mut()) {
+		(Some(value), Some(result)) => *result = into_val(value),
+		(None, None) => {},
+		_ => return Err(Trap::new("Supervisor function returned a value of the wrong arity")),
+	}
+	Ok(())
+}
+
+fn into_val(value: TypedValue) -> Val {
+	match value {
+		TypedValue::I32(v) => Val::I32(v),
+		TypedValue::I64(v) => Val::I64(v),
+		TypedValue::F32(v) => Val::F32(v as u32),
+		TypedValue::F64(v) => Val::F64(v as u64),
+	}
+}
+
+fn from_val(value: &Val) -> Option<TypedValue> {
+	match *value {
+		Val::I32(v) => Some(TypedValue::I32(v)),
+		Val::I64(v) => Some(TypedValue::I64(v)),
+		Val::F32(v) => Some(TypedValue::F32(v as i32)),
+		Val::F64(v) => Some(TypedValue::F64(v as i64)),
+		_ => None,
+	}
+}
@@ -0,0 +1,74 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Deterministic limiting of the stack height of runtimes.
+//!
+//! How deep a runtime can recurse would otherwise depend on the execution method, the code
+//! generated for the machine and the native stack of the executing thread. To make stack
+//! exhaustion deterministic, every function of the runtime is instrumented to account for the
+//! stack height it uses in a global, the same way contracts are instrumented. The runtime traps
+//! as soon as the stack height exceeds [`MAX_STACK_HEIGHT`], which is reported as
+//! `Error::StackExhausted`.
+
+use crate::error::WasmError;
+use parity_wasm::elements::Module as RawModule;
+
+/// The maximum stack height a runtime may use.
+///
+/// The stack height of a function is the number of its locals plus the maximum height of its
+/// operand stack. This value is part of consensus, changing it can change the outcome of a block.
+pub const MAX_STACK_HEIGHT: u32 = 32 * 1024;
+
+/// The name under which the stack height global is exported for executors that can not access
+/// globals which are not exported.
+#[cfg(feature = "wasmtime")]
+pub(crate) const STACK_HEIGHT_EXPORT: &str = "__substrate_stack_height";
+
+/// Instruments the given module to trap when it exceeds `MAX_STACK_HEIGHT`.
+///
+/// The stack height is tracked in a new mutable global, which is the last global of the
+/// instrumented module.
+pub(crate) fn inject_limiter(module: RawModule) -> Result<RawModule, WasmError> {
+	pwasm_utils::stack_height::inject_limiter(module, MAX_STACK_HEIGHT)
+		.map_err(|_| WasmError::InvalidModule)
+}
+
+/// Returns the index of the stack height global of a module instrumented by `inject_limiter`.
+#[cfg(feature = "wasmtime")]
+fn stack_height_global(module: &RawModule) -> u32 {
+	let globals = module.import_count(parity_wasm::elements::ImportCountType::Global)
+		+ module.global_section().map_or(0, |section| section.entries().len());
+	globals.saturating_sub(1) as u32
+}
+
+/// Exports the stack height global of a module instrumented by `inject_limiter` as
+/// `STACK_HEIGHT_EXPORT`.
+#[cfg(feature = "wasmtime")]
+pub(crate) fn export_stack_height(module: RawModule) -> RawModule {
+	let index = stack_height_global(&module);
+	parity_wasm::builder::from_module(module)
+		.export()
+			.field(STACK_HEIGHT_EXPORT)
+			.internal().global(index)
+			.build()
+		.build()
+}
+
+/// Returns `true` if the given value of the stack height global shows that the runtime trapped
+/// because it exceeded `MAX_STACK_HEIGHT`.
+pub(crate) fn is_exhausted(stack_height: i32) -> bool {
+	stack_height as u32 > MAX_STACK_HEIGHT
+}
@@ -0,0 +1,150 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracing of the host functions called by the Wasm runtime.
+//!
+//! Tracing is enabled for the current thread while running a closure with [`trace_host_calls`].
+//! Every host function that is called by a Wasm runtime in this scope is recorded as a
+//! [`HostCall`], together with its arguments, its result and the time it took to execute. Host
+//! functions can attach additional information, like the storage key they accessed, to their
+//! record.
+//!
+//! A runtime that is executed natively does not call any host functions and can not be traced.
+
+use std::{cell::RefCell, time::{Duration, Instant}};
+use wasm_interface::{Function, Result as WResult, Value};
+
+/// Additional information that a host function attaches to its record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostCallDetail {
+	/// The storage key that was accessed.
+	StorageKey(Vec<u8>),
+	/// The storage key of the child trie that was accessed.
+	ChildStorageKey(Vec<u8>),
+	/// The prefix of the storage keys that were accessed.
+	StoragePrefix(Vec<u8>),
+	/// The size of the data that was returned to the runtime.
+	ResultLen(u32),
+}
+
+/// A host function call made by the runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostCall {
+	/// The name of the host function.
+	pub function: String,
+	/// The arguments the host function was called with.
+	pub args: Vec<Value>,
+	/// The value returned by the host function.
+	pub result: Option<Value>,
+	/// The error returned by the host function, if it failed.
+	pub error: Option<String>,
+	/// Additional information attached by the host function.
+	pub details: Vec<HostCallDetail>,
+	/// The time it took to execute the host function.
+	pub duration: Duration,
+}
+
+/// The state of a tracing scope.
+#[derive(Default)]
+struct Trace {
+	/// The recorded host calls.
+	calls: Vec<HostCall>,
+	/// The indices of the host calls that are currently executing, innermost last.
+	executing: Vec<usize>,
+}
+
+thread_local! {
+	/// The trace of the current tracing scope, `None` if tracing is disabled.
+	static TRACE: RefCell<Option<Trace>> = RefCell::new(None);
+}
+
+/// Runs the given closure and records all host calls made by Wasm runtimes on the current
+/// thread while it runs.
+///
+/// Host calls are returned in the order they were made. Host calls made from within another
+/// host call, e.g. while a sandboxed module calls back into the runtime, appear after the host
+/// call they are made from.
+pub fn trace_host_calls<R>(f: impl FnOnce() -> R) -> (R, Vec<HostCall>) {
+	/// Restores the previous tracing scope, even if `f` panics.
+	struct Scope(Option<Trace>);
+
+	impl Drop for Scope {
+		fn drop(&mut self) {
+			let previous = self.0.take();
+			TRACE.with(|trace| *trace.borrow_mut() = previous);
+		}
+	}
+
+	let _scope = Scope(TRACE.with(|trace| trace.replace(Some(Trace::default()))));
+	let result = f();
+	let calls = TRACE.with(|trace| trace.borrow_mut().take())
+		.map(|trace| trace.calls)
+		.unwrap_or_default();
+
+	(result, calls)
+}
+
+/// Executes a host function and records the call if tracing is enabled.
+pub(crate) fn host_call(
+	function: &dyn Function,
+	args: &mut dyn Iterator<Item=Value>,
+	execute: impl FnOnce(&mut dyn Iterator<Item=Value>) -> WResult<Option<Value>>,
+) -> WResult<Option<Value>> {
+	if !TRACE.with(|trace| trace.borrow().is_some()) {
+		return execute(args)
+	}
+
+	let args = args.collect::<Vec<_>>();
+	TRACE.with(|trace| if let Some(trace) = trace.borrow_mut().as_mut() {
+		trace.executing.push(trace.calls.len());
+		trace.calls.push(HostCall {
+			function: function.name().into(),
+			args: args.clone(),
+			result: None,
+			error: None,
+			details: Vec::new(),
+			duration: Duration::default(),
+		});
+	});
+
+	let start = Instant::now();
+	let result = execute(&mut args.into_iter());
+	let duration = start.elapsed();
+
+	TRACE.with(|trace| if let Some(trace) = trace.borrow_mut().as_mut() {
+		let index = trace.executing.pop();
+		if let Some(call) = index.and_then(|index| trace.calls.get_mut(index)) {
+			call.duration = duration;
+			match &result {
+				Ok(value) => call.result = *value,
+				Err(error) => call.error = Some(error.clone()),
+			}
+		}
+	});
+
+	result
+}
+
+/// Attaches additional information to the record of the host call that is currently executing.
+///
+/// `detail` is only evaluated if tracing is enabled.
+pub(crate) fn record_detail(detail: impl FnOnce() -> HostCallDetail) {
+	TRACE.with(|trace| if let Some(trace) = trace.borrow_mut().as_mut() {
+		if let Some(&index) = trace.executing.last() {
+			trace.calls[index].details.push(detail());
+		}
+	})
+}
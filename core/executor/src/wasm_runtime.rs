@@ -21,11 +21,18 @@
 
 use crate::error::{Error, WasmError};
 use crate::wasmi_execution;
+#[cfg(feature = "wasmtime")]
+use crate::wasmtime_execution;
 use log::{trace, warn};
 use codec::Decode;
 use primitives::{storage::well_known_keys, traits::Externalities};
 use runtime_version::RuntimeVersion;
-use std::{collections::hash_map::{Entry, HashMap}};
+use std::{
+	any::TypeId,
+	sync::{Arc, atomic::{AtomicU64, Ordering}},
+	time::{Duration, Instant},
+};
+use wasm_interface::{Function, HostFunctions};
 
 /// The Substrate Wasm runtime.
 pub trait WasmRuntime {
@@ -51,6 +58,78 @@ pub trait WasmRuntime {
 pub enum WasmExecutionMethod {
 	/// Uses the Wasmi interpreter.
 	Interpreted,
+	/// Uses the Wasmtime compiled runtime.
+	#[cfg(feature = "wasmtime")]
+	Compiled,
+}
+
+/// The default maximum number of runtimes kept alive by a `RuntimesCache`.
+pub const DEFAULT_MAX_RUNTIME_INSTANCES: usize = 8;
+
+/// Counters collected by the runtimes cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeCacheStats {
+	/// Number of times a cached runtime could be reused.
+	pub hits: u64,
+	/// Number of times a runtime was requested whose creation failed before. The error is cached,
+	/// so that invalid code isn't compiled again on every call.
+	pub failures: u64,
+	/// Number of times a runtime needed to be created.
+	pub misses: u64,
+	/// Number of runtimes that were removed from the cache to stay within its bound.
+	pub evictions: u64,
+	/// Total time spent on creating runtimes.
+	pub compilation_time: Duration,
+}
+
+/// Shared counters of one or more `RuntimesCache`s.
+#[derive(Debug, Default)]
+pub struct RuntimeCacheMetrics {
+	hits: AtomicU64,
+	failures: AtomicU64,
+	misses: AtomicU64,
+	evictions: AtomicU64,
+	compilation_time_nanos: AtomicU64,
+}
+
+impl RuntimeCacheMetrics {
+	/// Returns a snapshot of the current counters.
+	pub fn stats(&self) -> RuntimeCacheStats {
+		RuntimeCacheStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			failures: self.failures.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+			evictions: self.evictions.load(Ordering::Relaxed),
+			compilation_time: Duration::from_nanos(
+				self.compilation_time_nanos.load(Ordering::Relaxed)
+			),
+		}
+	}
+
+	fn record_compilation(&self, elapsed: Duration) {
+		self.misses.fetch_add(1, Ordering::Relaxed);
+		let nanos = elapsed.as_secs()
+			.saturating_mul(1_000_000_000)
+			.saturating_add(u64::from(elapsed.subsec_nanos()));
+		self.compilation_time_nanos.fetch_add(nanos, Ordering::Relaxed);
+	}
+}
+
+/// The key under which a runtime is stored in the `RuntimesCache`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct CacheKey {
+	/// The method used to execute the runtime.
+	wasm_method: WasmExecutionMethod,
+	/// The type id of the host functions the runtime was instantiated with.
+	host_functions: TypeId,
+	/// The hash of the runtime code.
+	code_hash: [u8; 32],
+}
+
+/// A runtime, or the error that occurred while creating it, stored in the `RuntimesCache`.
+struct CachedRuntime {
+	key: CacheKey,
+	runtime: Result<Box<dyn WasmRuntime>, WasmError>,
 }
 
 /// Cache for the runtimes.
@@ -63,20 +142,32 @@ pub enum WasmExecutionMethod {
 /// the memory reset to the initial memory. So, one runtime instance is reused for every fetch
 /// request.
 ///
-/// For now the cache grows indefinitely, but that should be fine for now since runtimes can only be
-/// upgraded rarely and there are no other ways to make the node to execute some other runtime.
+/// When using the compiled execution method, the cache keeps the compiled module and every call
+/// instantiates it anew.
+///
+/// Runtimes are keyed by the hash of their code, so that multiple runtimes can be alive at the
+/// same time. This happens for example when following forks across a runtime upgrade or when
+/// calling into old blocks. A runtime is instantiated with a fixed set of host functions. As
+/// different executors can provide different host functions, instances are also keyed by the
+/// type that provides the host functions.
+///
+/// The number of cached runtimes is bounded. When the bound is exceeded, the least recently used
+/// runtime is dropped.
 pub struct RuntimesCache {
 	/// A cache of runtime instances along with metadata, ready to be reused.
 	///
-	/// Instances are keyed by the Wasm execution method and the hash of their code.
-	instances: HashMap<(WasmExecutionMethod, [u8; 32]), Result<Box<dyn WasmRuntime>, WasmError>>,
+	/// The entries are ordered by their last use, the most recently used entry comes first.
+	instances: Vec<CachedRuntime>,
+	/// Counters that are updated on every fetch.
+	metrics: Arc<RuntimeCacheMetrics>,
 }
 
 impl RuntimesCache {
-	/// Creates a new instance of a runtimes cache.
-	pub fn new() -> RuntimesCache {
+	/// Creates a new instance of a runtimes cache that reports to the given `metrics`.
+	pub fn new(metrics: Arc<RuntimeCacheMetrics>) -> RuntimesCache {
 		RuntimesCache {
-			instances: HashMap::new(),
+			instances: Vec::new(),
+			metrics,
 		}
 	}
 
@@ -85,9 +176,9 @@ impl RuntimesCache {
 	/// On first use we create a new runtime instance, save it to the cache
 	/// and persist its initial memory.
 	///
-	/// Each subsequent request will return this instance, with its memory restored
-	/// to the persisted initial memory. Thus, we reuse one single runtime instance
-	/// for every `fetch_runtime` invocation.
+	/// Each subsequent request for the same code will return this instance, with its memory
+	/// restored to the persisted initial memory. Thus, we reuse one single runtime instance
+	/// per code for every `fetch_runtime` invocation.
 	///
 	/// # Parameters
 	///
@@ -97,6 +188,11 @@ impl RuntimesCache {
 	///
 	/// `default_heap_pages` - Number of 64KB pages to allocate for Wasm execution.
 	///
+	/// `max_instances` - The maximum number of runtimes to keep in the cache. The least recently
+	/// used runtimes are dropped once this number is exceeded. At least one runtime is always kept.
+	///
+	/// `HF` - The host functions the runtime is instantiated with.
+	///
 	/// # Return value
 	///
 	/// If no error occurred a tuple `(wasmi::ModuleRef, Option<RuntimeVersion>)` is
@@ -109,11 +205,12 @@ impl RuntimesCache {
 	///
 	/// `Error::InvalidMemoryReference` is returned if no memory export with the
 	/// identifier `memory` can be found in the runtime.
-	pub fn fetch_runtime<E: Externalities>(
+	pub fn fetch_runtime<E: Externalities, HF: HostFunctions + 'static>(
 		&mut self,
 		ext: &mut E,
 		wasm_method: WasmExecutionMethod,
 		default_heap_pages: u64,
+		max_instances: usize,
 	) -> Result<&mut (dyn WasmRuntime + 'static), Error> {
 		let code_hash = ext
 			.original_storage_hash(well_known_keys::CODE)
@@ -124,49 +221,161 @@ impl RuntimesCache {
 			.and_then(|pages| u64::decode(&mut &pages[..]).ok())
 			.unwrap_or(default_heap_pages);
 
-		let result = match self.instances.entry((wasm_method, code_hash.into())) {
-			Entry::Occupied(o) => {
-				let result = o.into_mut();
-				if let Ok(ref mut cached_runtime) = result {
-					if !cached_runtime.update_heap_pages(heap_pages) {
-						trace!(
-							target: "runtimes_cache",
-							"heap_pages were changed. Reinstantiating the instance"
-						);
-						*result = create_wasm_runtime(ext, wasm_method, heap_pages);
-						if let Err(ref err) = result {
-							warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
-						}
+		let key = CacheKey {
+			wasm_method,
+			host_functions: TypeId::of::<HF>(),
+			code_hash: code_hash.into(),
+		};
+
+		let entry = match self.instances.iter().position(|entry| entry.key == key) {
+			Some(position) => {
+				let mut entry = self.instances.remove(position);
+				let reusable = match entry.runtime {
+					Ok(ref mut cached_runtime) => cached_runtime.update_heap_pages(heap_pages),
+					Err(_) => {
+						self.metrics.failures.fetch_add(1, Ordering::Relaxed);
+						true
+					},
+				};
+
+				if reusable {
+					if entry.runtime.is_ok() {
+						self.metrics.hits.fetch_add(1, Ordering::Relaxed);
 					}
+				} else {
+					trace!(
+						target: "runtimes_cache",
+						"heap_pages were changed. Reinstantiating the instance"
+					);
+					entry.runtime = self.create_runtime::<_, HF>(ext, wasm_method, heap_pages);
 				}
-				result
+				entry
 			},
-			Entry::Vacant(v) => {
+			None => {
 				trace!(target: "runtimes_cache", "no instance found in cache, creating now.");
-				let result = create_wasm_runtime(ext, wasm_method, heap_pages);
-				if let Err(ref err) = result {
-					warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
+				CachedRuntime {
+					key,
+					runtime: self.create_runtime::<_, HF>(ext, wasm_method, heap_pages),
 				}
-				v.insert(result)
 			}
 		};
 
-		result.as_mut()
+		self.instances.insert(0, entry);
+
+		let max_instances = max_instances.max(1);
+		if self.instances.len() > max_instances {
+			let evicted = self.instances.len() - max_instances;
+			trace!(target: "runtimes_cache", "evicting {} least recently used runtimes", evicted);
+			self.instances.truncate(max_instances);
+			self.metrics.evictions.fetch_add(evicted as u64, Ordering::Relaxed);
+		}
+
+		self.instances[0].runtime.as_mut()
 			.map(|runtime| runtime.as_mut())
 			.map_err(|ref e| Error::InvalidCode(format!("{:?}", e)))
 	}
+
+	/// Returns the number of runtimes currently held by the cache.
+	pub fn len(&self) -> usize {
+		self.instances.len()
+	}
+
+	/// Returns `true` if the cache does not hold any runtime.
+	pub fn is_empty(&self) -> bool {
+		self.instances.is_empty()
+	}
+
+	/// Creates a new runtime for the code in `ext` and records the time it took.
+	fn create_runtime<E: Externalities, HF: HostFunctions>(
+		&self,
+		ext: &mut E,
+		wasm_method: WasmExecutionMethod,
+		heap_pages: u64,
+	) -> Result<Box<dyn WasmRuntime>, WasmError> {
+		let start = Instant::now();
+		let result = create_wasm_runtime(ext, wasm_method, heap_pages, HF::host_functions())
+			.and_then(|runtime| {
+				check_host_interfaces(&*runtime, &HF::interfaces())?;
+				Ok(runtime)
+			});
+		self.metrics.record_compilation(start.elapsed());
+
+		if let Err(ref err) = result {
+			warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
+		}
+		result
+	}
+}
+
+/// Checks that the runtime interfaces declared in the version of `runtime` are provided.
+///
+/// Runtimes that don't declare their interfaces are accepted. They fail with
+/// `Error::MissingHostFunction` when calling a function that isn't provided.
+fn check_host_interfaces(
+	runtime: &dyn WasmRuntime,
+	provided: &[(&str, u32)],
+) -> Result<(), WasmError> {
+	let version = match runtime.version() {
+		Some(version) => version,
+		None => return Ok(()),
+	};
+
+	if let Some((name, interface_version)) = version.unsupported_host_interfaces(provided).next() {
+		return Err(WasmError::UnsupportedHostInterface(name.into(), interface_version))
+	}
+
+	Ok(())
+}
+
+/// Logs the functions that are imported by a runtime, but that are not provided by the executor.
+///
+/// The runtime can still be executed, but calling one of these functions fails with
+/// `Error::MissingHostFunction`.
+pub(crate) fn warn_missing_host_functions(version: Option<&RuntimeVersion>, missing: &[String]) {
+	if missing.is_empty() {
+		return
+	}
+
+	let missing = missing.iter()
+		.map(|name| {
+			let (name, version) = wasm_interface::host_function_name_and_version(name);
+			format!("`{}` version {}", name, version)
+		})
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	match version {
+		Some(version) => warn!(
+			target: "wasm-executor",
+			"Runtime {} imports host functions that are not provided by this node: {}",
+			version,
+			missing,
+		),
+		None => warn!(
+			target: "wasm-executor",
+			"Runtime imports host functions that are not provided by this node: {}",
+			missing,
+		),
+	}
 }
 
 /// Create a wasm runtime with the given `code`.
+///
+/// The runtime can only import functions that are part of the given `host_functions`.
 pub fn create_wasm_runtime_with_code<E: Externalities>(
 	ext: &mut E,
 	wasm_method: WasmExecutionMethod,
 	heap_pages: u64,
 	code: &[u8],
+	host_functions: Vec<&'static dyn Function>,
 ) -> Result<Box<dyn WasmRuntime>, WasmError> {
 	match wasm_method {
 		WasmExecutionMethod::Interpreted =>
-			wasmi_execution::create_instance(ext, code, heap_pages)
+			wasmi_execution::create_instance(ext, code, heap_pages, host_functions)
+				.map(|runtime| -> Box<dyn WasmRuntime> { Box::new(runtime) }),
+		#[cfg(feature = "wasmtime")]
+		WasmExecutionMethod::Compiled =>
+			wasmtime_execution::create_instance(ext, code, heap_pages, host_functions)
 				.map(|runtime| -> Box<dyn WasmRuntime> { Box::new(runtime) }),
 	}
 }
@@ -175,9 +384,124 @@ fn create_wasm_runtime<E: Externalities>(
 	ext: &mut E,
 	wasm_method: WasmExecutionMethod,
 	heap_pages: u64,
+	host_functions: Vec<&'static dyn Function>,
 ) -> Result<Box<dyn WasmRuntime>, WasmError> {
 	let code = ext
 		.original_storage(well_known_keys::CODE)
 		.ok_or(WasmError::CodeNotFound)?;
-	create_wasm_runtime_with_code(ext, wasm_method, heap_pages, &code)
+	create_wasm_runtime_with_code(ext, wasm_method, heap_pages, &code, host_functions)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::host_interface::SubstrateHostFunctions;
+	use runtime_io::TestExternalities;
+
+	fn fetch(cache: &mut RuntimesCache, code: &[u8], max_instances: usize) {
+		let mut ext = TestExternalities::new_with_code(code, Default::default());
+		let mut ext = ext.ext();
+		cache.fetch_runtime::<_, SubstrateHostFunctions>(
+			&mut ext,
+			WasmExecutionMethod::Interpreted,
+			8,
+			max_instances,
+		).expect("Creates runtime");
+	}
+
+	#[test]
+	fn runtimes_are_reused_per_code_hash() {
+		let metrics = Arc::new(RuntimeCacheMetrics::default());
+		let mut cache = RuntimesCache::new(metrics.clone());
+
+		fetch(&mut cache, &runtime_test::WASM_BINARY, 2);
+		fetch(&mut cache, &test_runtime::WASM_BINARY, 2);
+		fetch(&mut cache, &runtime_test::WASM_BINARY, 2);
+		fetch(&mut cache, &test_runtime::WASM_BINARY, 2);
+
+		let stats = metrics.stats();
+		assert_eq!(cache.len(), 2);
+		assert_eq!(stats.misses, 2);
+		assert_eq!(stats.hits, 2);
+		assert_eq!(stats.evictions, 0);
+	}
+
+	/// A runtime that only provides its version.
+	struct VersionOnly(RuntimeVersion);
+
+	impl WasmRuntime for VersionOnly {
+		fn update_heap_pages(&mut self, _: u64) -> bool {
+			true
+		}
+
+		fn call(&mut self, _: &mut dyn Externalities, _: &str, _: &[u8]) -> Result<Vec<u8>, Error> {
+			Err(Error::Other("Not callable".into()))
+		}
+
+		fn version(&self) -> Option<RuntimeVersion> {
+			Some(self.0.clone())
+		}
+	}
+
+	#[test]
+	fn runtimes_needing_newer_host_interfaces_are_refused() {
+		let runtime = VersionOnly(RuntimeVersion {
+			host_interfaces: vec![("storage".into(), 2)].into(),
+			..Default::default()
+		});
+
+		assert!(check_host_interfaces(&runtime, &[("crypto", 1), ("storage", 3)]).is_ok());
+		assert!(check_host_interfaces(&VersionOnly(Default::default()), &[]).is_ok());
+
+		for provided in &[&[("storage", 1)][..], &[("crypto", 2)][..]] {
+			match check_host_interfaces(&runtime, provided) {
+				Err(WasmError::UnsupportedHostInterface(ref name, 2)) if name == "storage" => {},
+				res => panic!("Unexpected result: {:?}", res),
+			}
+		}
+	}
+
+	#[test]
+	fn cached_failures_are_not_counted_as_hits() {
+		let metrics = Arc::new(RuntimeCacheMetrics::default());
+		let mut cache = RuntimesCache::new(metrics.clone());
+		let mut ext = TestExternalities::new_with_code(&[1, 2, 3], Default::default());
+		let mut ext = ext.ext();
+
+		for _ in 0..2 {
+			assert!(cache.fetch_runtime::<_, SubstrateHostFunctions>(
+				&mut ext,
+				WasmExecutionMethod::Interpreted,
+				8,
+				2,
+			).is_err());
+		}
+
+		let stats = metrics.stats();
+		assert_eq!(stats.misses, 1);
+		assert_eq!(stats.failures, 1);
+		assert_eq!(stats.hits, 0);
+	}
+
+	#[test]
+	fn least_recently_used_runtime_is_evicted() {
+		let metrics = Arc::new(RuntimeCacheMetrics::default());
+		let mut cache = RuntimesCache::new(metrics.clone());
+
+		fetch(&mut cache, &runtime_test::WASM_BINARY, 1);
+		fetch(&mut cache, &test_runtime::WASM_BINARY, 1);
+		assert_eq!(cache.len(), 1);
+		assert_eq!(metrics.stats().evictions, 1);
+
+		// The most recently used runtime is still cached.
+		fetch(&mut cache, &test_runtime::WASM_BINARY, 1);
+		assert_eq!(metrics.stats().hits, 1);
+
+		// The evicted runtime needs to be created again.
+		fetch(&mut cache, &runtime_test::WASM_BINARY, 1);
+		let stats = metrics.stats();
+		assert_eq!(stats.misses, 3);
+		assert_eq!(stats.evictions, 2);
+		assert!(stats.compilation_time > Duration::from_secs(0));
+	}
 }
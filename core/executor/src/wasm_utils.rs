@@ -162,11 +162,14 @@ macro_rules! impl_wasm_host_interface {
 	) => (
 		impl $crate::wasm_interface::HostFunctions for $interface_name {
 			#[allow(non_camel_case_types)]
-			fn functions() -> &'static [&'static dyn $crate::wasm_interface::Function] {
-				gen_functions!(
-					$context,
-					$( $name( $( $names: $params ),* ) $( -> $returns )? { $( $body )* } )*
-				)
+			fn host_functions() -> Vec<&'static dyn $crate::wasm_interface::Function> {
+				let functions: &'static [&'static dyn $crate::wasm_interface::Function] =
+					gen_functions!(
+						$context,
+						$( $name( $( $names: $params ),* ) $( -> $returns )? { $( $body )* } )*
+					);
+
+				functions.to_vec()
 			}
 		}
 	);
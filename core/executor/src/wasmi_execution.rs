@@ -16,50 +16,62 @@
 
 //! Implementation of a Wasm runtime using the Wasmi interpreter.
 
-use std::{str, mem};
+use std::{str, mem, cell::RefCell};
 use wasmi::{
-	Module, ModuleInstance, MemoryInstance, MemoryRef, TableRef, ImportsBuilder, ModuleRef,
+	Module, ModuleInstance, MemoryRef, TableRef, ImportsBuilder, ModuleRef,
 	memory_units::Pages, RuntimeValue::{I32, I64, self},
 };
 use crate::error::{Error, WasmError};
+use crate::stack_limit;
 use codec::{Encode, Decode};
 use primitives::{sandbox as sandbox_primitives, traits::Externalities};
-use crate::host_interface::SubstrateExternals;
 use crate::sandbox;
 use crate::allocator;
-use crate::wasm_runtime::WasmRuntime;
+use crate::wasm_runtime::{WasmRuntime, warn_missing_host_functions};
 use log::trace;
 use parity_wasm::elements::{deserialize_buffer, DataSegment, Instruction, Module as RawModule};
 use runtime_version::RuntimeVersion;
 use wasm_interface::{
-	FunctionContext, HostFunctions, Pointer, WordSize, Sandbox, MemoryId, Result as WResult,
+	FunctionContext, Pointer, WordSize, Sandbox, MemoryId, Result as WResult, Function,
+	host_function_name_and_version,
 };
 
-struct FunctionExecutor {
-	sandbox_store: sandbox::Store<wasmi::FuncRef>,
-	heap: allocator::FreeingBumpHeapAllocator,
+struct FunctionExecutor<'a> {
+	sandbox_store: sandbox::Store<wasmi::FuncRef, sandbox::WasmiEngine>,
+	heap: allocator::SizeClassHeapAllocator,
 	memory: MemoryRef,
 	table: Option<TableRef>,
+	host_functions: &'a [&'static dyn Function],
+	missing_functions: &'a [String],
 }
 
-impl FunctionExecutor {
-	fn new(m: MemoryRef, heap_base: u32, t: Option<TableRef>) -> Result<Self, Error> {
+impl<'a> FunctionExecutor<'a> {
+	fn new(
+		m: MemoryRef,
+		heap_base: u32,
+		t: Option<TableRef>,
+		host_functions: &'a [&'static dyn Function],
+		missing_functions: &'a [String],
+	) -> Result<Self, Error> {
 		Ok(FunctionExecutor {
-			sandbox_store: sandbox::Store::new(),
-			heap: allocator::FreeingBumpHeapAllocator::new(heap_base),
+			sandbox_store: sandbox::Store::new(sandbox::WasmiEngine),
+			heap: allocator::SizeClassHeapAllocator::new(heap_base),
 			memory: m,
 			table: t,
+			host_functions,
+			missing_functions,
 		})
 	}
 }
 
-impl sandbox::SandboxCapabilities for FunctionExecutor {
+impl<'a> sandbox::SandboxCapabilities for FunctionExecutor<'a> {
 	type SupervisorFuncRef = wasmi::FuncRef;
+	type Engine = sandbox::WasmiEngine;
 
-	fn store(&self) -> &sandbox::Store<Self::SupervisorFuncRef> {
+	fn store(&self) -> &sandbox::Store<Self::SupervisorFuncRef, Self::Engine> {
 		&self.sandbox_store
 	}
-	fn store_mut(&mut self) -> &mut sandbox::Store<Self::SupervisorFuncRef> {
+	fn store_mut(&mut self) -> &mut sandbox::Store<Self::SupervisorFuncRef, Self::Engine> {
 		&mut self.sandbox_store
 	}
 	fn allocate(&mut self, len: WordSize) -> Result<Pointer<u8>, Error> {
@@ -108,7 +120,7 @@ impl sandbox::SandboxCapabilities for FunctionExecutor {
 	}
 }
 
-impl FunctionContext for FunctionExecutor {
+impl<'a> FunctionContext for FunctionExecutor<'a> {
 	fn read_memory_into(&self, address: Pointer<u8>, dest: &mut [u8]) -> WResult<()> {
 		self.memory.get_into(address.into(), dest).map_err(|e| format!("{:?}", e))
 	}
@@ -131,14 +143,21 @@ impl FunctionContext for FunctionExecutor {
 		})
 	}
 
+	fn reallocate_memory(&mut self, ptr: Pointer<u8>, size: WordSize) -> WResult<Pointer<u8>> {
+		let heap = &mut self.heap;
+		self.memory.with_direct_access_mut(|mem| {
+			heap.reallocate(mem, ptr, size).map_err(|e| format!("{:?}", e))
+		})
+	}
+
 	fn sandbox(&mut self) -> &mut dyn Sandbox {
 		self
 	}
 }
 
-impl Sandbox for FunctionExecutor {
+impl<'a> Sandbox for FunctionExecutor<'a> {
 	fn memory_get(
-		&self,
+		&mut self,
 		memory_id: MemoryId,
 		offset: WordSize,
 		buf_ptr: Pointer<u8>,
@@ -146,13 +165,12 @@ impl Sandbox for FunctionExecutor {
 	) -> WResult<u32> {
 		let sandboxed_memory = self.sandbox_store.memory(memory_id).map_err(|e| format!("{:?}", e))?;
 
-		match MemoryInstance::transfer(
-			&sandboxed_memory,
-			offset as usize,
-			&self.memory,
-			buf_ptr.into(),
-			buf_len as usize,
-		) {
+		let mut data = vec![0; buf_len as usize];
+		if self.sandbox_store.engine().memory_get(&sandboxed_memory, offset, &mut data).is_err() {
+			return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS);
+		}
+
+		match self.memory.set(buf_ptr.into(), &data) {
 			Ok(()) => Ok(sandbox_primitives::ERR_OK),
 			Err(_) => Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
 		}
@@ -167,13 +185,12 @@ impl Sandbox for FunctionExecutor {
 	) -> WResult<u32> {
 		let sandboxed_memory = self.sandbox_store.memory(memory_id).map_err(|e| format!("{:?}", e))?;
 
-		match MemoryInstance::transfer(
-			&self.memory,
-			val_ptr.into(),
-			&sandboxed_memory,
-			offset as usize,
-			val_len as usize,
-		) {
+		let data = match self.memory.get(val_ptr.into(), val_len as usize) {
+			Ok(data) => data,
+			Err(_) => return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+		};
+
+		match self.sandbox_store.engine().memory_set(&sandboxed_memory, offset, &data) {
 			Ok(()) => Ok(sandbox_primitives::ERR_OK),
 			Err(_) => Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
 		}
@@ -202,12 +219,8 @@ impl Sandbox for FunctionExecutor {
 	) -> WResult<u32> {
 		trace!(target: "sr-sandbox", "invoke, instance_idx={}", instance_id);
 
-		// Deserialize arguments and convert them into wasmi types.
 		let args = Vec::<sandbox_primitives::TypedValue>::decode(&mut &args[..])
-			.map_err(|_| "Can't decode serialized arguments for the invocation")?
-			.into_iter()
-			.map(Into::into)
-			.collect::<Vec<_>>();
+			.map_err(|_| "Can't decode serialized arguments for the invocation")?;
 
 		let instance = self.sandbox_store.instance(instance_id).map_err(|e| format!("{:?}", e))?;
 		let result = instance.invoke(export_name, &args, self, state);
@@ -216,7 +229,7 @@ impl Sandbox for FunctionExecutor {
 			Ok(None) => Ok(sandbox_primitives::ERR_OK),
 			Ok(Some(val)) => {
 				// Serialize return value and write it back into the memory.
-				sandbox_primitives::ReturnValue::Value(val.into()).using_encoded(|val| {
+				sandbox_primitives::ReturnValue::Value(val).using_encoded(|val| {
 					if val.len() > return_val_len as usize {
 						Err("Return value buffer is too small")?;
 					}
@@ -261,54 +274,82 @@ impl Sandbox for FunctionExecutor {
 	}
 }
 
-impl FunctionExecutor {
-	fn resolver() -> &'static dyn wasmi::ModuleImportResolver {
-		struct Resolver;
-		impl wasmi::ModuleImportResolver for Resolver {
-			fn resolve_func(&self, name: &str, signature: &wasmi::Signature)
-				-> std::result::Result<wasmi::FuncRef, wasmi::Error>
-			{
-				let signature = wasm_interface::Signature::from(signature);
-
-				if let Some((index, func)) = SubstrateExternals::functions().iter()
-					.enumerate()
-					.find(|f| name == f.1.name())
-				{
-					if signature == func.signature() {
-						Ok(wasmi::FuncInstance::alloc_host(signature.into(), index))
-					} else {
-						Err(wasmi::Error::Instantiation(
-							format!(
-								"Invalid signature for function `{}` expected `{:?}`, got `{:?}`",
-								func.name(),
-								signature,
-								func.signature(),
-							)
-						))
-					}
-				} else {
-					Err(wasmi::Error::Instantiation(
-						format!("Export {} not found", name),
-					))
-				}
+/// Resolves the imports of a runtime against the given host functions.
+///
+/// Imports that are not provided by the host functions are resolved to stubs that fail with
+/// `Error::MissingHostFunction` when they are called. This way a runtime that imports a function
+/// unknown to this node, e.g. a newer version of a host function, can still be instantiated and
+/// executed as long as it doesn't call this function.
+struct Resolver<'a> {
+	host_functions: &'a [&'static dyn Function],
+	/// The names of the imported functions that are not provided by `host_functions`.
+	///
+	/// The stub for the function at position `i` has the index `host_functions.len() + i`.
+	missing_functions: RefCell<Vec<String>>,
+}
+
+impl<'a> Resolver<'a> {
+	fn new(host_functions: &'a [&'static dyn Function]) -> Self {
+		Resolver {
+			host_functions,
+			missing_functions: RefCell::new(Vec::new()),
+		}
+	}
+}
+
+impl<'a> wasmi::ModuleImportResolver for Resolver<'a> {
+	fn resolve_func(&self, name: &str, signature: &wasmi::Signature)
+		-> std::result::Result<wasmi::FuncRef, wasmi::Error>
+	{
+		let signature = wasm_interface::Signature::from(signature);
+
+		if let Some((index, func)) = self.host_functions.iter()
+			.enumerate()
+			.find(|f| name == f.1.name())
+		{
+			if signature == func.signature() {
+				Ok(wasmi::FuncInstance::alloc_host(signature.into(), index))
+			} else {
+				Err(wasmi::Error::Instantiation(
+					format!(
+						"Invalid signature for function `{}` expected `{:?}`, got `{:?}`",
+						func.name(),
+						signature,
+						func.signature(),
+					)
+				))
 			}
+		} else {
+			let mut missing_functions = self.missing_functions.borrow_mut();
+			let index = self.host_functions.len() + missing_functions.len();
+			missing_functions.push(name.into());
+
+			Ok(wasmi::FuncInstance::alloc_host(signature.into(), index))
 		}
-		&Resolver
 	}
 }
 
-impl wasmi::Externals for FunctionExecutor {
+impl<'a> wasmi::Externals for FunctionExecutor<'a> {
 	fn invoke_index(&mut self, index: usize, args: wasmi::RuntimeArgs)
 		-> Result<Option<wasmi::RuntimeValue>, wasmi::Trap>
 	{
 		let mut args = args.as_ref().iter().copied().map(Into::into);
-		let function = SubstrateExternals::functions().get(index).ok_or_else(||
-			Error::from(
-				format!("Could not find host function with index: {}", index),
-			)
-		)?;
+		let function = match self.host_functions.get(index) {
+			Some(function) => function,
+			None => {
+				let name = self.missing_functions.get(index - self.host_functions.len())
+					.ok_or_else(||
+						Error::from(
+							format!("Could not find host function with index: {}", index),
+						)
+					)?;
+				let (name, version) = host_function_name_and_version(name);
+
+				return Err(Error::MissingHostFunction(name.into(), version).into())
+			}
+		};
 
-		function.execute(self, &mut args)
+		crate::trace::host_call(*function, &mut args, |args| function.execute(self, args))
 			.map_err(Error::FunctionExecution)
 			.map_err(wasmi::Trap::from)
 			.map(|v| v.map(Into::into))
@@ -346,11 +387,15 @@ fn call_in_wasm_module(
 	module_instance: &ModuleRef,
 	method: &str,
 	data: &[u8],
+	host_functions: &[&'static dyn Function],
+	missing_functions: &[String],
 ) -> Result<Vec<u8>, Error> {
 	call_in_wasm_module_with_custom_signature(
 		ext,
 		module_instance,
 		method,
+		host_functions,
+		missing_functions,
 		|alloc| {
 			let offset = alloc(data)?;
 			Ok(vec![I32(offset as i32), I32(data.len() as i32)])
@@ -376,6 +421,8 @@ fn call_in_wasm_module_with_custom_signature<
 	ext: &mut dyn Externalities,
 	module_instance: &ModuleRef,
 	method: &str,
+	host_functions: &[&'static dyn Function],
+	missing_functions: &[String],
 	create_parameters: F,
 	filter_result: FR,
 ) -> Result<R, Error> {
@@ -391,6 +438,8 @@ fn call_in_wasm_module_with_custom_signature<
 		memory.clone(),
 		heap_base,
 		table,
+		host_functions,
+		missing_functions,
 	)?;
 
 	let parameters = create_parameters(&mut |data: &[u8]| {
@@ -402,6 +451,7 @@ fn call_in_wasm_module_with_custom_signature<
 		ext,
 		|| module_instance.invoke_export(method, &parameters, &mut fec),
 	);
+	trace!(target: "wasm-heap", "Heap usage of `{}`: {}", method, fec.heap.stats());
 
 	match result {
 		Ok(val) => match filter_result(val, &memory)? {
@@ -414,21 +464,42 @@ fn call_in_wasm_module_with_custom_signature<
 				"Failed to execute code with {} pages",
 				memory.current_size().0
 			);
-			Err(e.into())
+			if stack_exhausted(module_instance) {
+				Err(Error::StackExhausted)
+			} else {
+				Err(e.into())
+			}
 		},
 	}
 }
 
+/// Returns `true` if the runtime trapped because it exceeded `MAX_STACK_HEIGHT`.
+///
+/// Only the instrumented stack height is deterministic. An overflow of the interpreter's own
+/// stack is a failure of the host, so it is not reported as `Error::StackExhausted`.
+fn stack_exhausted(module_instance: &ModuleRef) -> bool {
+	// The stack height global is the last global of an instrumented module.
+	match module_instance.globals().last().map(|global| global.get()) {
+		Some(I32(stack_height)) => stack_limit::is_exhausted(stack_height),
+		_ => false,
+	}
+}
+
 /// Prepare module instance
+///
+/// Returns the instance and the names of the imported functions that are not provided by
+/// `host_functions`.
 fn instantiate_module(
 	heap_pages: usize,
 	module: &Module,
-) -> Result<ModuleRef, Error> {
+	host_functions: &[&'static dyn Function],
+) -> Result<(ModuleRef, Vec<String>), Error> {
+	let resolver = Resolver::new(host_functions);
 	// start module instantiation. Don't run 'start' function yet.
 	let intermediate_instance = ModuleInstance::new(
 		module,
 		&ImportsBuilder::new()
-			.with_resolver("env", FunctionExecutor::resolver())
+			.with_resolver("env", &resolver)
 	)?;
 
 	// Verify that the module has the heap base global variable.
@@ -442,7 +513,7 @@ fn instantiate_module(
 		// Runtime is not allowed to have the `start` function.
 		Err(Error::RuntimeHasStartFn)
 	} else {
-		Ok(intermediate_instance.assert_no_start())
+		Ok((intermediate_instance.assert_no_start(), resolver.missing_functions.into_inner()))
 	}
 }
 
@@ -564,6 +635,10 @@ pub struct WasmiRuntime {
 	version: Option<RuntimeVersion>,
 	/// The snapshot of the instance's state taken just after the instantiation.
 	state_snapshot: StateSnapshot,
+	/// The host functions registered for this instance.
+	host_functions: Vec<&'static dyn Function>,
+	/// The functions imported by the instance that are not provided by `host_functions`.
+	missing_functions: Vec<String>,
 }
 
 impl WasmiRuntime {
@@ -592,7 +667,14 @@ impl WasmRuntime for WasmiRuntime {
 			-> Result<Vec<u8>, Error>
 	{
 		self.with(|module| {
-			call_in_wasm_module(ext, module, method, data)
+			call_in_wasm_module(
+				ext,
+				module,
+				method,
+				data,
+				&self.host_functions,
+				&self.missing_functions,
+			)
 		})
 	}
 
@@ -601,20 +683,26 @@ impl WasmRuntime for WasmiRuntime {
 	}
 }
 
-pub fn create_instance<E: Externalities>(ext: &mut E, code: &[u8], heap_pages: u64)
-	-> Result<WasmiRuntime, WasmError>
-{
-	let module = Module::from_buffer(&code).map_err(|_| WasmError::InvalidModule)?;
+pub fn create_instance<E: Externalities>(
+	ext: &mut E,
+	code: &[u8],
+	heap_pages: u64,
+	host_functions: Vec<&'static dyn Function>,
+) -> Result<WasmiRuntime, WasmError> {
+	let raw_module: RawModule = deserialize_buffer(code)
+		.map_err(|_| WasmError::CantDeserializeWasm)?;
+	let raw_module = stack_limit::inject_limiter(raw_module)?;
 
 	// Extract the data segments from the wasm code.
-	//
-	// A return of this error actually indicates that there is a problem in logic, since
-	// we just loaded and validated the `module` above.
-	let data_segments = extract_data_segments(&code)?;
+	let data_segments = extract_data_segments(&raw_module);
+
+	let module = Module::from_parity_wasm_module(raw_module)
+		.map_err(|_| WasmError::InvalidModule)?;
 
 	// Instantiate this module.
-	let instance = instantiate_module(heap_pages as usize, &module)
-		.map_err(WasmError::Instantiation)?;
+	let (instance, missing_functions) =
+		instantiate_module(heap_pages as usize, &module, &host_functions)
+			.map_err(WasmError::Instantiation)?;
 
 	// Take state snapshot before executing anything.
 	let state_snapshot = StateSnapshot::take(&instance, data_segments, heap_pages)
@@ -625,339 +713,31 @@ pub fn create_instance<E: Externalities>(ext: &mut E, code: &[u8], heap_pages: u
 				",
 		);
 
-	let version = call_in_wasm_module(ext, &instance, "Core_version", &[])
-		.ok()
-		.and_then(|v| RuntimeVersion::decode(&mut v.as_slice()).ok());
+	let version = call_in_wasm_module(
+		ext,
+		&instance,
+		"Core_version",
+		&[],
+		&host_functions,
+		&missing_functions,
+	).ok().and_then(|v| RuntimeVersion::decode(&mut v.as_slice()).ok());
+
+	warn_missing_host_functions(version.as_ref(), &missing_functions);
+
 	Ok(WasmiRuntime {
 		instance,
 		version,
 		state_snapshot,
+		host_functions,
+		missing_functions,
 	})
 }
 
-/// Extract the data segments from the given wasm code.
-///
-/// Returns `Err` if the given wasm code cannot be deserialized.
-fn extract_data_segments(wasm_code: &[u8]) -> Result<Vec<DataSegment>, WasmError> {
-	let raw_module: RawModule = deserialize_buffer(wasm_code)
-		.map_err(|_| WasmError::CantDeserializeWasm)?;
-
-	let segments = raw_module
+/// Extract the data segments from the given wasm module.
+fn extract_data_segments(raw_module: &RawModule) -> Vec<DataSegment> {
+	raw_module
 		.data_section()
 		.map(|ds| ds.entries())
 		.unwrap_or(&[])
-		.to_vec();
-	Ok(segments)
-}
-
-#[cfg(test)]
-mod tests {
-	use super::*;
-
-	use state_machine::TestExternalities as CoreTestExternalities;
-	use hex_literal::hex;
-	use primitives::{
-		Blake2Hasher, blake2_128, blake2_256, ed25519, sr25519, map, Pair, offchain::OffchainExt,
-	};
-	use runtime_test::WASM_BINARY;
-	use substrate_offchain::testing;
-	use trie::{TrieConfiguration, trie_types::Layout};
-	use codec::{Encode, Decode};
-
-	type TestExternalities = CoreTestExternalities<Blake2Hasher, u64>;
-
-	fn call<E: Externalities>(
-		ext: &mut E,
-		heap_pages: u64,
-		code: &[u8],
-		method: &str,
-		data: &[u8],
-	) -> Result<Vec<u8>, Error> {
-		let mut instance = create_instance(ext, code, heap_pages)
-			.map_err(|err| err.to_string())?;
-		instance.call(ext, method, data)
-	}
-
-	#[test]
-	fn returning_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let output = call(&mut ext, 8, &test_code[..], "test_empty_return", &[]).unwrap();
-		assert_eq!(output, vec![0u8; 0]);
-	}
-
-	#[test]
-	fn panicking_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-
-		let output = call(&mut ext, 8, &test_code[..], "test_panic", &[]);
-		assert!(output.is_err());
-
-		let output = call(&mut ext, 8, &test_code[..], "test_conditional_panic", &[0]);
-		assert_eq!(Decode::decode(&mut &output.unwrap()[..]), Ok(Vec::<u8>::new()));
-
-		let output = call(&mut ext, 8, &test_code[..], "test_conditional_panic", &vec![2].encode());
-		assert!(output.is_err());
-	}
-
-	#[test]
-	fn storage_should_work() {
-		let mut ext = TestExternalities::default();
-
-		{
-			let mut ext = ext.ext();
-			ext.set_storage(b"foo".to_vec(), b"bar".to_vec());
-			let test_code = WASM_BINARY;
-
-			let output = call(
-				&mut ext,
-				8,
-				&test_code[..],
-				"test_data_in",
-				&b"Hello world".to_vec().encode(),
-			).unwrap();
-
-			assert_eq!(output, b"all ok!".to_vec().encode());
-		}
-
-		let expected = TestExternalities::new((map![
-			b"input".to_vec() => b"Hello world".to_vec(),
-			b"foo".to_vec() => b"bar".to_vec(),
-			b"baz".to_vec() => b"bar".to_vec()
-		], map![]));
-		assert_eq!(ext, expected);
-	}
-
-	#[test]
-	fn clear_prefix_should_work() {
-		let mut ext = TestExternalities::default();
-		{
-			let mut ext = ext.ext();
-			ext.set_storage(b"aaa".to_vec(), b"1".to_vec());
-			ext.set_storage(b"aab".to_vec(), b"2".to_vec());
-			ext.set_storage(b"aba".to_vec(), b"3".to_vec());
-			ext.set_storage(b"abb".to_vec(), b"4".to_vec());
-			ext.set_storage(b"bbb".to_vec(), b"5".to_vec());
-			let test_code = WASM_BINARY;
-
-			// This will clear all entries which prefix is "ab".
-			let output = call(
-				&mut ext,
-				8,
-				&test_code[..],
-				"test_clear_prefix",
-				&b"ab".to_vec().encode(),
-			).unwrap();
-
-			assert_eq!(output, b"all ok!".to_vec().encode());
-		}
-
-		let expected = TestExternalities::new((map![
-			b"aaa".to_vec() => b"1".to_vec(),
-			b"aab".to_vec() => b"2".to_vec(),
-			b"bbb".to_vec() => b"5".to_vec()
-		], map![]));
-		assert_eq!(expected, ext);
-	}
-
-	#[test]
-	fn blake2_256_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_blake2_256", &[0]).unwrap(),
-			blake2_256(&b""[..]).to_vec().encode(),
-		);
-		assert_eq!(
-			call(
-				&mut ext,
-				8,
-				&test_code[..],
-				"test_blake2_256",
-				&b"Hello world!".to_vec().encode(),
-			).unwrap(),
-			blake2_256(&b"Hello world!"[..]).to_vec().encode(),
-		);
-	}
-
-	#[test]
-	fn blake2_128_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_blake2_128", &[0]).unwrap(),
-			blake2_128(&b""[..]).to_vec().encode(),
-		);
-		assert_eq!(
-			call(
-				&mut ext,
-				8,
-				&test_code[..],
-				"test_blake2_128",
-				&b"Hello world!".to_vec().encode(),
-			).unwrap(),
-			blake2_128(&b"Hello world!"[..]).to_vec().encode(),
-		);
-	}
-
-	#[test]
-	fn twox_256_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_twox_256", &[0]).unwrap(),
-			hex!(
-				"99e9d85137db46ef4bbea33613baafd56f963c64b1f3685a4eb4abd67ff6203a"
-			).to_vec().encode(),
-		);
-		assert_eq!(
-			call(
-				&mut ext,
-				8,
-				&test_code[..],
-				"test_twox_256",
-				&b"Hello world!".to_vec().encode(),
-			).unwrap(),
-			hex!(
-				"b27dfd7f223f177f2a13647b533599af0c07f68bda23d96d059da2b451a35a74"
-			).to_vec().encode(),
-		);
-	}
-
-	#[test]
-	fn twox_128_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_twox_128", &[0]).unwrap(),
-			hex!("99e9d85137db46ef4bbea33613baafd5").to_vec().encode(),
-		);
-		assert_eq!(
-			call(
-				&mut ext,
-				8,
-				&test_code[..],
-				"test_twox_128",
-				&b"Hello world!".to_vec().encode(),
-			).unwrap(),
-			hex!("b27dfd7f223f177f2a13647b533599af").to_vec().encode(),
-		);
-	}
-
-	#[test]
-	fn ed25519_verify_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-		let key = ed25519::Pair::from_seed(&blake2_256(b"test"));
-		let sig = key.sign(b"all ok!");
-		let mut calldata = vec![];
-		calldata.extend_from_slice(key.public().as_ref());
-		calldata.extend_from_slice(sig.as_ref());
-
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_ed25519_verify", &calldata.encode()).unwrap(),
-			true.encode(),
-		);
-
-		let other_sig = key.sign(b"all is not ok!");
-		let mut calldata = vec![];
-		calldata.extend_from_slice(key.public().as_ref());
-		calldata.extend_from_slice(other_sig.as_ref());
-
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_ed25519_verify", &calldata.encode()).unwrap(),
-			false.encode(),
-		);
-	}
-
-	#[test]
-	fn sr25519_verify_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let test_code = WASM_BINARY;
-		let key = sr25519::Pair::from_seed(&blake2_256(b"test"));
-		let sig = key.sign(b"all ok!");
-		let mut calldata = vec![];
-		calldata.extend_from_slice(key.public().as_ref());
-		calldata.extend_from_slice(sig.as_ref());
-
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_sr25519_verify", &calldata.encode()).unwrap(),
-			true.encode(),
-		);
-
-		let other_sig = key.sign(b"all is not ok!");
-		let mut calldata = vec![];
-		calldata.extend_from_slice(key.public().as_ref());
-		calldata.extend_from_slice(other_sig.as_ref());
-
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_sr25519_verify", &calldata.encode()).unwrap(),
-			false.encode(),
-		);
-	}
-
-	#[test]
-	fn ordered_trie_root_should_work() {
-		let mut ext = TestExternalities::default();
-		let mut ext = ext.ext();
-		let trie_input = vec![b"zero".to_vec(), b"one".to_vec(), b"two".to_vec()];
-		let test_code = WASM_BINARY;
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_ordered_trie_root", &[0]).unwrap(),
-			Layout::<Blake2Hasher>::ordered_trie_root(trie_input.iter()).as_bytes().encode(),
-		);
-	}
-
-	#[test]
-	fn offchain_local_storage_should_work() {
-		use substrate_client::backend::OffchainStorage;
-
-		let mut ext = TestExternalities::default();
-		let (offchain, state) = testing::TestOffchainExt::new();
-		ext.register_extension(OffchainExt::new(offchain));
-		let test_code = WASM_BINARY;
-		let mut ext = ext.ext();
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_offchain_local_storage", &[0]).unwrap(),
-			true.encode(),
-		);
-		assert_eq!(state.read().persistent_storage.get(b"", b"test"), Some(vec![]));
-	}
-
-	#[test]
-	fn offchain_http_should_work() {
-		let mut ext = TestExternalities::default();
-		let (offchain, state) = testing::TestOffchainExt::new();
-		ext.register_extension(OffchainExt::new(offchain));
-		state.write().expect_request(
-			0,
-			testing::PendingRequest {
-				method: "POST".into(),
-				uri: "http://localhost:12345".into(),
-				body: vec![1, 2, 3, 4],
-				headers: vec![("X-Auth".to_owned(), "test".to_owned())],
-				sent: true,
-				response: Some(vec![1, 2, 3]),
-				response_headers: vec![("X-Auth".to_owned(), "hello".to_owned())],
-				..Default::default()
-			},
-		);
-
-		let test_code = WASM_BINARY;
-		let mut ext = ext.ext();
-		assert_eq!(
-			call(&mut ext, 8, &test_code[..], "test_offchain_http", &[0]).unwrap(),
-			true.encode(),
-		);
-	}
+		.to_vec()
 }
@@ -0,0 +1,653 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementation of a Wasm runtime using the Wasmtime JIT compiler.
+//!
+//! The module is compiled once when the runtime is created. Every call gets a fresh instance in
+//! a fresh store, which is cheap compared to compilation and saves us from having to restore
+//! memory and globals between calls.
+
+use std::rc::Rc;
+use crate::error::{Error, WasmError};
+use crate::sandbox;
+use crate::allocator;
+use crate::stack_limit;
+use crate::wasm_runtime::{WasmRuntime, warn_missing_host_functions};
+use codec::{Encode, Decode};
+use log::trace;
+use parity_wasm::elements::{deserialize_buffer, Module as RawModule};
+use primitives::{sandbox as sandbox_primitives, traits::Externalities};
+use runtime_version::RuntimeVersion;
+use wasm_interface::{
+	FunctionContext, Pointer, WordSize, Sandbox, MemoryId, Result as WResult, Value, ValueType,
+	Function, host_function_name_and_version,
+};
+use wasmtime::{
+	Caller, Engine, ExternType, Func, FuncType, Instance, Linker, Memory, Module, Store, Table,
+	Trap, Val, ValType,
+};
+
+/// The state that is made available to the host functions during a call.
+///
+/// It is created right after the instance is created and dropped together with the store at the
+/// end of the call.
+struct HostState {
+	sandbox_store: sandbox::Store<Func, sandbox::WasmtimeEngine>,
+	heap: allocator::SizeClassHeapAllocator,
+	memory: Memory,
+	table: Option<Table>,
+	host_functions: Rc<Vec<&'static dyn Function>>,
+}
+
+/// The data type stored in a wasmtime `Store`.
+///
+/// It is `None` only while the instance is being created, before any code of the runtime is run.
+type StoreData = Option<HostState>;
+
+/// Implements `FunctionContext` and `Sandbox` on top of the caller handle that wasmtime provides
+/// to host functions.
+struct FunctionExecutor<'a, 'b> {
+	caller: &'a mut Caller<'b, StoreData>,
+}
+
+impl<'a, 'b> FunctionExecutor<'a, 'b> {
+	fn state(&self) -> &HostState {
+		self.caller.data().as_ref().expect(
+			"host state is set before calling into the instance;
+			host functions can only be called from within the instance;
+			qed"
+		)
+	}
+
+	fn state_mut(&mut self) -> &mut HostState {
+		self.caller.data_mut().as_mut().expect(
+			"host state is set before calling into the instance;
+			host functions can only be called from within the instance;
+			qed"
+		)
+	}
+
+	fn with_heap<R>(
+		&mut self,
+		f: impl FnOnce(&mut allocator::SizeClassHeapAllocator, &mut [u8]) -> R,
+	) -> R {
+		let memory = self.state().memory;
+		let (mem, state) = memory.data_and_store_mut(&mut *self.caller);
+		let state = state.as_mut().expect(
+			"host state is set before calling into the instance;
+			host functions can only be called from within the instance;
+			qed"
+		);
+		f(&mut state.heap, mem)
+	}
+}
+
+impl<'a, 'b> sandbox::SandboxCapabilities for FunctionExecutor<'a, 'b> {
+	type SupervisorFuncRef = Func;
+	type Engine = sandbox::WasmtimeEngine;
+
+	fn store(&self) -> &sandbox::Store<Self::SupervisorFuncRef, Self::Engine> {
+		&self.state().sandbox_store
+	}
+	fn store_mut(&mut self) -> &mut sandbox::Store<Self::SupervisorFuncRef, Self::Engine> {
+		&mut self.state_mut().sandbox_store
+	}
+	fn allocate(&mut self, len: WordSize) -> Result<Pointer<u8>, Error> {
+		self.with_heap(|heap, mem| heap.allocate(mem, len))
+	}
+	fn deallocate(&mut self, ptr: Pointer<u8>) -> Result<(), Error> {
+		self.with_heap(|heap, mem| heap.deallocate(mem, ptr))
+	}
+	fn write_memory(&mut self, ptr: Pointer<u8>, data: &[u8]) -> Result<(), Error> {
+		let memory = self.state().memory;
+		write_memory_from(memory.data_mut(&mut *self.caller), ptr, data)
+	}
+	fn read_memory(&self, ptr: Pointer<u8>, len: WordSize) -> Result<Vec<u8>, Error> {
+		let mut output = vec![0; len as usize];
+		let memory = self.state().memory;
+		read_memory_into(memory.data(&*self.caller), ptr, &mut output)?;
+		Ok(output)
+	}
+
+	fn invoke(
+		&mut self,
+		dispatch_thunk: &Self::SupervisorFuncRef,
+		invoke_args_ptr: Pointer<u8>,
+		invoke_args_len: WordSize,
+		state: u32,
+		func_idx: sandbox::SupervisorFuncIndex,
+	) -> Result<i64, Error>
+	{
+		let mut results = [Val::I64(0)];
+		dispatch_thunk.call(
+			&mut *self.caller,
+			&[
+				Val::I32(u32::from(invoke_args_ptr) as i32),
+				Val::I32(invoke_args_len as i32),
+				Val::I32(state as i32),
+				Val::I32(usize::from(func_idx) as i32),
+			],
+			&mut results,
+		).map_err(|e| Error::Other(format!("Supervisor function trapped: {}", e)))?;
+
+		match results[0] {
+			Val::I64(val) => Ok(val),
+			_ => Err("Supervisor function returned unexpected result!".into()),
+		}
+	}
+}
+
+impl<'a, 'b> FunctionContext for FunctionExecutor<'a, 'b> {
+	fn read_memory_into(&self, address: Pointer<u8>, dest: &mut [u8]) -> WResult<()> {
+		let memory = self.state().memory;
+		read_memory_into(memory.data(&*self.caller), address, dest).map_err(|e| format!("{:?}", e))
+	}
+
+	fn write_memory(&mut self, address: Pointer<u8>, data: &[u8]) -> WResult<()> {
+		let memory = self.state().memory;
+		write_memory_from(memory.data_mut(&mut *self.caller), address, data)
+			.map_err(|e| format!("{:?}", e))
+	}
+
+	fn allocate_memory(&mut self, size: WordSize) -> WResult<Pointer<u8>> {
+		self.with_heap(|heap, mem| heap.allocate(mem, size).map_err(|e| format!("{:?}", e)))
+	}
+
+	fn deallocate_memory(&mut self, ptr: Pointer<u8>) -> WResult<()> {
+		self.with_heap(|heap, mem| heap.deallocate(mem, ptr).map_err(|e| format!("{:?}", e)))
+	}
+
+	fn reallocate_memory(&mut self, ptr: Pointer<u8>, size: WordSize) -> WResult<Pointer<u8>> {
+		self.with_heap(|heap, mem| {
+			heap.reallocate(mem, ptr, size).map_err(|e| format!("{:?}", e))
+		})
+	}
+
+	fn sandbox(&mut self) -> &mut dyn Sandbox {
+		self
+	}
+}
+
+impl<'a, 'b> Sandbox for FunctionExecutor<'a, 'b> {
+	fn memory_get(
+		&mut self,
+		memory_id: MemoryId,
+		offset: WordSize,
+		buf_ptr: Pointer<u8>,
+		buf_len: WordSize,
+	) -> WResult<u32> {
+		let sandboxed_memory = self.state().sandbox_store.memory(memory_id)
+			.map_err(|e| format!("{:?}", e))?;
+
+		let mut data = vec![0; buf_len as usize];
+		let engine = self.state().sandbox_store.engine();
+		if engine.memory_get(&sandboxed_memory, offset, &mut data).is_err() {
+			return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS);
+		}
+
+		let memory = self.state().memory;
+		match write_memory_from(memory.data_mut(&mut *self.caller), buf_ptr, &data) {
+			Ok(()) => Ok(sandbox_primitives::ERR_OK),
+			Err(_) => Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+		}
+	}
+
+	fn memory_set(
+		&mut self,
+		memory_id: MemoryId,
+		offset: WordSize,
+		val_ptr: Pointer<u8>,
+		val_len: WordSize,
+	) -> WResult<u32> {
+		let sandboxed_memory = self.state().sandbox_store.memory(memory_id)
+			.map_err(|e| format!("{:?}", e))?;
+
+		let memory = self.state().memory;
+		let source = memory.data(&*self.caller);
+		let range = match checked_range(val_ptr.into(), val_len as usize, source.len()) {
+			Some(range) => range,
+			None => return Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+		};
+
+		let engine = self.state().sandbox_store.engine();
+		match engine.memory_set(&sandboxed_memory, offset, &source[range]) {
+			Ok(()) => Ok(sandbox_primitives::ERR_OK),
+			Err(_) => Ok(sandbox_primitives::ERR_OUT_OF_BOUNDS),
+		}
+	}
+
+	fn memory_teardown(&mut self, memory_id: MemoryId) -> WResult<()> {
+		self.state_mut().sandbox_store.memory_teardown(memory_id).map_err(|e| format!("{:?}", e))
+	}
+
+	fn memory_new(
+		&mut self,
+		initial: u32,
+		maximum: u32,
+	) -> WResult<MemoryId> {
+		self.state_mut().sandbox_store.new_memory(initial, maximum).map_err(|e| format!("{:?}", e))
+	}
+
+	fn invoke(
+		&mut self,
+		instance_id: u32,
+		export_name: &str,
+		args: &[u8],
+		return_val: Pointer<u8>,
+		return_val_len: WordSize,
+		state: u32,
+	) -> WResult<u32> {
+		trace!(target: "sr-sandbox", "invoke, instance_idx={}", instance_id);
+
+		let args = Vec::<sandbox_primitives::TypedValue>::decode(&mut &args[..])
+			.map_err(|_| "Can't decode serialized arguments for the invocation")?;
+
+		let instance = self.state().sandbox_store.instance(instance_id)
+			.map_err(|e| format!("{:?}", e))?;
+		let result = instance.invoke(export_name, &args, self, state);
+
+		match result {
+			Ok(None) => Ok(sandbox_primitives::ERR_OK),
+			Ok(Some(val)) => {
+				// Serialize return value and write it back into the memory.
+				sandbox_primitives::ReturnValue::Value(val).using_encoded(|val| {
+					if val.len() > return_val_len as usize {
+						Err("Return value buffer is too small")?;
+					}
+					FunctionContext::write_memory(self, return_val, val)
+						.map_err(|_| "Return value buffer is OOB")?;
+					Ok(sandbox_primitives::ERR_OK)
+				})
+			}
+			Err(_) => Ok(sandbox_primitives::ERR_EXECUTION),
+		}
+	}
+
+	fn instance_teardown(&mut self, instance_id: u32) -> WResult<()> {
+		self.state_mut().sandbox_store
+			.instance_teardown(instance_id)
+			.map_err(|e| format!("{:?}", e))
+	}
+
+	fn instance_new(
+		&mut self,
+		dispatch_thunk_id: u32,
+		wasm: &[u8],
+		raw_env_def: &[u8],
+		state: u32,
+	) -> WResult<u32> {
+		// Extract a dispatch thunk from instance's table by the specified index.
+		let dispatch_thunk = {
+			let table = self.state().table
+				.ok_or_else(|| "Runtime doesn't have a table; sandbox is unavailable")?;
+			match table.get(&mut *self.caller, dispatch_thunk_id) {
+				Some(Val::FuncRef(Some(func))) => func,
+				Some(Val::FuncRef(None)) =>
+					return Err("dispatch_thunk_idx points on an empty table entry".into()),
+				Some(_) => return Err("dispatch_thunk_idx points on a non-function entry".into()),
+				None => return Err("dispatch_thunk_idx is out of the table bounds".into()),
+			}
+		};
+
+		let instance_idx_or_err_code =
+			match sandbox::instantiate(self, dispatch_thunk, wasm, raw_env_def, state) {
+				Ok(instance_idx) => instance_idx,
+				Err(sandbox::InstantiationError::StartTrapped) =>
+					sandbox_primitives::ERR_EXECUTION,
+				Err(_) => sandbox_primitives::ERR_MODULE,
+			};
+
+		Ok(instance_idx_or_err_code as u32)
+	}
+}
+
+/// Returns the range `offset..offset + len` if it fits into memory of `memory_len` bytes.
+fn checked_range(offset: usize, len: usize, memory_len: usize) -> Option<std::ops::Range<usize>> {
+	let end = offset.checked_add(len)?;
+	if end <= memory_len {
+		Some(offset..end)
+	} else {
+		None
+	}
+}
+
+fn read_memory_into(memory: &[u8], address: Pointer<u8>, dest: &mut [u8]) -> Result<(), Error> {
+	let range = checked_range(address.into(), dest.len(), memory.len())
+		.ok_or_else(|| Error::Other("memory read is out of bounds".into()))?;
+	dest.copy_from_slice(&memory[range]);
+	Ok(())
+}
+
+fn write_memory_from(memory: &mut [u8], address: Pointer<u8>, data: &[u8]) -> Result<(), Error> {
+	let range = checked_range(address.into(), data.len(), memory.len())
+		.ok_or_else(|| Error::Other("memory write is out of bounds".into()))?;
+	memory[range].copy_from_slice(data);
+	Ok(())
+}
+
+fn into_wasmtime_val_type(value_type: ValueType) -> ValType {
+	match value_type {
+		ValueType::I32 => ValType::I32,
+		ValueType::I64 => ValType::I64,
+		ValueType::F32 => ValType::F32,
+		ValueType::F64 => ValType::F64,
+	}
+}
+
+fn into_wasmtime_val(value: Value) -> Val {
+	match value {
+		Value::I32(v) => Val::I32(v),
+		Value::I64(v) => Val::I64(v),
+		Value::F32(v) => Val::F32(v),
+		Value::F64(v) => Val::F64(v),
+	}
+}
+
+fn from_wasmtime_val(value: &Val) -> Value {
+	match *value {
+		Val::I32(v) => Value::I32(v),
+		Val::I64(v) => Value::I64(v),
+		Val::F32(v) => Value::F32(v),
+		Val::F64(v) => Value::F64(v),
+		ref other => panic!(
+			"host functions are declared only with numeric value types;
+			wasmtime checks that the arguments match the declared signature;
+			thus {:?} can't be passed to a host function;
+			qed",
+			other,
+		),
+	}
+}
+
+/// Define the given host functions in the `env` module of the given linker.
+fn define_host_functions(
+	linker: &mut Linker<StoreData>,
+	host_functions: &[&'static dyn Function],
+) -> Result<(), WasmError> {
+	for (index, function) in host_functions.iter().enumerate() {
+		let signature = function.signature();
+		let func_type = FuncType::new(
+			signature.args.iter().cloned().map(into_wasmtime_val_type),
+			signature.return_value.map(into_wasmtime_val_type),
+		);
+
+		// `dyn Function` is not `Sync`, thus we can't move it into the closure and look it up
+		// by its index in the host state instead.
+		linker.func_new(
+			"env",
+			function.name(),
+			func_type,
+			move |mut caller: Caller<'_, StoreData>, params: &[Val], results: &mut [Val]| {
+				let mut executor = FunctionExecutor { caller: &mut caller };
+				let function = executor.state().host_functions[index];
+				let mut args = params.iter().map(from_wasmtime_val);
+
+				let result = crate::trace::host_call(
+					function,
+					&mut args,
+					|args| function.execute(&mut executor, args),
+				).map_err(|e| Trap::new(Error::FunctionExecution(e).to_string()))?;
+
+				if let (Some(value), Some(slot)) = (result, results.first_mut()) {
+					*slot = into_wasmtime_val(value);
+				}
+				Ok(())
+			},
+		).map_err(|e| WasmError::Instantiation(Error::Other(e.to_string())))?;
+	}
+	Ok(())
+}
+
+/// Define stubs for the functions imported by `module` from the `env` module that are not
+/// provided by `host_functions`.
+///
+/// Calling a stub fails with `Error::MissingHostFunction`. This way a runtime that imports a
+/// function unknown to this node can still be executed as long as it doesn't call the function.
+///
+/// Returns the names of the stubbed functions.
+fn define_missing_host_functions(
+	linker: &mut Linker<StoreData>,
+	module: &Module,
+	host_functions: &[&'static dyn Function],
+) -> Result<Vec<String>, WasmError> {
+	let mut missing_functions = Vec::new();
+
+	for import in module.imports() {
+		let func_type = match import.ty() {
+			ExternType::Func(func_type) if import.module() == "env" => func_type,
+			_ => continue,
+		};
+
+		if host_functions.iter().any(|f| f.name() == import.name()) {
+			continue
+		}
+
+		let (name, version) = host_function_name_and_version(import.name());
+		let error = Error::MissingHostFunction(name.into(), version).to_string();
+
+		linker.func_new(
+			"env",
+			import.name(),
+			func_type,
+			move |_: Caller<'_, StoreData>, _: &[Val], _: &mut [Val]| {
+				Err(Trap::new(error.clone()))
+			},
+		).map_err(|e| WasmError::Instantiation(Error::Other(e.to_string())))?;
+
+		missing_functions.push(import.name().into());
+	}
+
+	Ok(missing_functions)
+}
+
+/// Returns `true` if the runtime trapped because it exceeded `MAX_STACK_HEIGHT`.
+///
+/// Only the instrumented stack height is deterministic. An overflow of the native stack depends
+/// on the compiled code and the executing thread, so it is not reported as
+/// `Error::StackExhausted`.
+fn stack_exhausted(instance: &Instance, store: &mut Store<StoreData>) -> bool {
+	let stack_height = instance
+		.get_global(&mut *store, stack_limit::STACK_HEIGHT_EXPORT)
+		.map(|global| global.get(&mut *store));
+	match stack_height {
+		Some(Val::I32(stack_height)) => stack_limit::is_exhausted(stack_height),
+		_ => false,
+	}
+}
+
+/// Find the global named `__heap_base` in the given instance and tries to get its value.
+fn get_heap_base(instance: &Instance, store: &mut Store<StoreData>) -> Result<u32, Error> {
+	let heap_base_val = instance
+		.get_global(&mut *store, "__heap_base")
+		.ok_or_else(|| Error::HeapBaseNotFoundOrInvalid)?
+		.get(&mut *store);
+
+	match heap_base_val {
+		Val::I32(v) => Ok(v as u32),
+		_ => Err(Error::HeapBaseNotFoundOrInvalid),
+	}
+}
+
+/// A runtime compiled by wasmtime along with its version.
+pub struct WasmtimeRuntime {
+	/// The compiled module.
+	module: Module,
+	/// The linker that provides the host functions to the instances of `module`.
+	linker: Linker<StoreData>,
+	/// The number of 64KB pages the memory is grown by after instantiation.
+	heap_pages: u64,
+	/// The host functions that are defined in `linker`, in the order of their definition.
+	host_functions: Rc<Vec<&'static dyn Function>>,
+	/// Runtime version according to `Core_version`.
+	///
+	/// Can be `None` if the runtime doesn't expose this function.
+	version: Option<RuntimeVersion>,
+}
+
+impl WasmtimeRuntime {
+	/// Instantiate the module in a fresh store and call `method` with the given `data`.
+	fn call_method(
+		&self,
+		ext: &mut dyn Externalities,
+		method: &str,
+		data: &[u8],
+	) -> Result<Vec<u8>, Error> {
+		let mut store = Store::new(self.module.engine(), None);
+		let instance = self.linker.instantiate(&mut store, &self.module)
+			.map_err(|e| Error::Other(format!("Failed to instantiate the runtime: {}", e)))?;
+
+		let memory = instance
+			.get_memory(&mut store, "memory")
+			.ok_or_else(|| Error::InvalidMemoryReference)?;
+		let table = instance.get_table(&mut store, "__indirect_function_table");
+		let heap_base = get_heap_base(&instance, &mut store)?;
+		memory.grow(&mut store, self.heap_pages).map_err(|_| Error::Runtime)?;
+
+		*store.data_mut() = Some(HostState {
+			sandbox_store: sandbox::Store::new(sandbox::WasmtimeEngine::new()),
+			heap: allocator::SizeClassHeapAllocator::new(heap_base),
+			memory,
+			table,
+			host_functions: self.host_functions.clone(),
+		});
+
+		let func = instance
+			.get_func(&mut store, method)
+			.ok_or_else(|| Error::MethodNotFound(method.to_owned()))?;
+
+		let data_ptr = {
+			let (mem, state) = memory.data_and_store_mut(&mut store);
+			let state = state.as_mut().expect("host state was set above; qed");
+			let data_ptr = state.heap.allocate(mem, data.len() as u32)?;
+			write_memory_from(mem, data_ptr, data)?;
+			data_ptr
+		};
+
+		let mut results = [Val::I64(0)];
+		let result = externalities::set_and_run_with_externalities(
+			ext,
+			|| func.call(
+				&mut store,
+				&[Val::I32(u32::from(data_ptr) as i32), Val::I32(data.len() as i32)],
+				&mut results,
+			),
+		);
+		if let Some(state) = store.data() {
+			trace!(target: "wasm-heap", "Heap usage of `{}`: {}", method, state.heap.stats());
+		}
+
+		match result {
+			Ok(()) => match results[0] {
+				Val::I64(r) => {
+					let offset = r as u32;
+					let length = (r as u64 >> 32) as usize;
+					let mut output = vec![0; length];
+					read_memory_into(memory.data(&store), Pointer::new(offset), &mut output)
+						.map_err(|_| Error::Runtime)?;
+					Ok(output)
+				},
+				_ => Err(Error::InvalidReturn),
+			},
+			Err(e) => {
+				trace!(
+					target: "wasm-executor",
+					"Failed to execute code with {} pages",
+					memory.size(&store),
+				);
+				if stack_exhausted(&instance, &mut store) {
+					Err(Error::StackExhausted)
+				} else {
+					Err(Error::Other(format!("Wasm execution trapped: {}", e)))
+				}
+			},
+		}
+	}
+}
+
+impl WasmRuntime for WasmtimeRuntime {
+	fn update_heap_pages(&mut self, heap_pages: u64) -> bool {
+		// Every call creates a new instance, so the new value can always be applied.
+		self.heap_pages = heap_pages;
+		true
+	}
+
+	fn call(&mut self, ext: &mut dyn Externalities, method: &str, data: &[u8])
+		-> Result<Vec<u8>, Error>
+	{
+		self.call_method(ext, method, data)
+	}
+
+	fn version(&self) -> Option<RuntimeVersion> {
+		self.version.clone()
+	}
+}
+
+/// Compile the given `code` and create a runtime from it.
+pub fn create_instance<E: Externalities>(
+	ext: &mut E,
+	code: &[u8],
+	heap_pages: u64,
+	host_functions: Vec<&'static dyn Function>,
+) -> Result<WasmtimeRuntime, WasmError> {
+	// Runtime is not allowed to have the `start` function. Wasmtime would run it as part of the
+	// instantiation, so check for it before compiling.
+	let raw_module: RawModule = deserialize_buffer(code)
+		.map_err(|_| WasmError::CantDeserializeWasm)?;
+	if raw_module.start_section().is_some() {
+		return Err(WasmError::Instantiation(Error::RuntimeHasStartFn));
+	}
+
+	// Wasmtime can only read globals that are exported, so export the stack height global to
+	// detect when the runtime exceeded its stack.
+	let raw_module = stack_limit::inject_limiter(raw_module)?;
+	let raw_module = stack_limit::export_stack_height(raw_module);
+	let code = parity_wasm::serialize(raw_module).map_err(|_| WasmError::InvalidModule)?;
+
+	let engine = Engine::default();
+	let module = Module::new(&engine, &code).map_err(|_| WasmError::InvalidModule)?;
+
+	let mut linker = Linker::new(&engine);
+	define_host_functions(&mut linker, &host_functions)?;
+	let missing_functions = define_missing_host_functions(&mut linker, &module, &host_functions)?;
+
+	// Check that all imports can be resolved and the module exports what we need, so that we
+	// fail here and not on the first call.
+	{
+		let mut store = Store::new(&engine, None);
+		let instance = linker.instantiate(&mut store, &module)
+			.map_err(|e| WasmError::Instantiation(Error::Other(e.to_string())))?;
+		instance
+			.get_memory(&mut store, "memory")
+			.ok_or_else(|| WasmError::Instantiation(Error::InvalidMemoryReference))?;
+		get_heap_base(&instance, &mut store).map_err(WasmError::Instantiation)?;
+	}
+
+	let mut runtime = WasmtimeRuntime {
+		module,
+		linker,
+		heap_pages,
+		host_functions: Rc::new(host_functions),
+		version: None,
+	};
+
+	runtime.version = runtime.call_method(ext, "Core_version", &[])
+		.ok()
+		.and_then(|v| RuntimeVersion::decode(&mut v.as_slice()).ok());
+
+	warn_missing_host_functions(runtime.version.as_ref(), &missing_functions);
+
+	Ok(runtime)
+}
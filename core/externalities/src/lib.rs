@@ -94,7 +94,13 @@ pub trait Externalities: ExtensionStore {
 		self.child_storage(storage_key, key).is_some()
 	}
 
-	/// Clear an entire child storage.
+	/// Returns the key immediately following the given key, if it exists.
+	fn next_storage_key(&self, key: &[u8]) -> Option<Vec<u8>>;
+
+	/// Returns the key immediately following the given key, if it exists, in child storage.
+	fn next_child_storage_key(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<Vec<u8>>;
+
+	/// Clear an entire child storage, in constant time if the child trie has a unique id.
 	fn kill_child_storage(&mut self, storage_key: ChildStorageKey);
 
 	/// Clear storage entries which keys are start with the given prefix.
@@ -124,6 +130,26 @@ pub trait Externalities: ExtensionStore {
 
 	/// Get the change trie root of the current storage overlay at a block with given parent.
 	fn storage_changes_root(&mut self, parent: H256) -> Result<Option<H256>, ()>;
+
+	/// Start a new nested storage transaction.
+	///
+	/// All changes made to the storage from now on can be reverted by
+	/// [`storage_rollback_transaction`] or kept by [`storage_commit_transaction`].
+	///
+	/// [`storage_rollback_transaction`]: #tymethod.storage_rollback_transaction
+	/// [`storage_commit_transaction`]: #tymethod.storage_commit_transaction
+	fn storage_start_transaction(&mut self);
+
+	/// Revert all changes made to the storage since the innermost open transaction was started.
+	///
+	/// Returns `Err(())` if there is no open transaction.
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()>;
+
+	/// Keep all changes made to the storage since the innermost open transaction was started.
+	///
+	/// The changes become part of the enclosing transaction, if any. Returns `Err(())` if there is
+	/// no open transaction.
+	fn storage_commit_transaction(&mut self) -> Result<(), ()>;
 }
 
 /// Extension for the [`Externalities`] trait.
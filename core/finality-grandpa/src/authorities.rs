@@ -112,6 +112,16 @@ where H: PartialEq,
 		}
 	}
 
+	/// Get a set with given authorities and set id, without any pending change.
+	pub(crate) fn new(authorities: Vec<(AuthorityId, u64)>, set_id: u64) -> Self {
+		AuthoritySet {
+			current_authorities: authorities,
+			set_id,
+			pending_standard_changes: ForkTree::new(),
+			pending_forced_changes: Vec::new(),
+		}
+	}
+
 	/// Get the current set id and a reference to the current authority set.
 	pub(crate) fn current(&self) -> (u64, &[(AuthorityId, u64)]) {
 		(self.set_id, &self.current_authorities[..])
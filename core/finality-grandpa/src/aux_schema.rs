@@ -38,6 +38,7 @@ const VERSION_KEY: &[u8] = b"grandpa_schema_version";
 const SET_STATE_KEY: &[u8] = b"grandpa_completed_round";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 const CONSENSUS_CHANGES_KEY: &[u8] = b"grandpa_consensus_changes";
+const AUTHORITY_SET_CHANGES_KEY: &[u8] = b"grandpa_authority_set_changes";
 
 const CURRENT_VERSION: u32 = 2;
 
@@ -267,6 +268,22 @@ pub(crate) fn load_persistent<Block: BlockT, B, G>(
 	where
 		B: AuxStore,
 		G: FnOnce() -> ClientResult<Vec<(AuthorityId, AuthorityWeight)>>,
+{
+	let persistent_data = load_or_init_persistent(backend, genesis_hash, genesis_number, genesis_authorities)?;
+	init_authority_set_changes::<Block, _>(backend, persistent_data.authority_set.set_id())?;
+	Ok(persistent_data)
+}
+
+fn load_or_init_persistent<Block: BlockT, B, G>(
+	backend: &B,
+	genesis_hash: Block::Hash,
+	genesis_number: NumberFor<Block>,
+	genesis_authorities: G,
+)
+	-> ClientResult<PersistentData<Block>>
+	where
+		B: AuxStore,
+		G: FnOnce() -> ClientResult<Vec<(AuthorityId, AuthorityWeight)>>,
 {
 	let version: Option<u32> = load_decode(backend, VERSION_KEY)?;
 	let consensus_changes = load_decode(backend, CONSENSUS_CHANGES_KEY)?
@@ -417,6 +434,44 @@ pub(crate) fn update_consensus_changes<H, N, F, R>(
 	write_aux(&[(CONSENSUS_CHANGES_KEY, set.encode().as_slice())])
 }
 
+/// Standard authority set changes, as `(set_id, block_number)` pairs in ascending order: the set
+/// `set_id` finalized the blocks up to `block_number`, whose justification is stored.
+///
+/// Forced changes aren't recorded, so the set ids aren't contiguous if there has been any.
+pub(crate) type AuthoritySetChanges<N> = Vec<(SetId, N)>;
+
+/// Load the authority set changes.
+///
+/// Returns `None` if they haven't been tracked since genesis, e.g. in databases that were created
+/// before they were tracked, in which case the changes can't be proven.
+pub(crate) fn load_authority_set_changes<B: AuxStore, N: Decode>(backend: &B)
+	-> ClientResult<Option<AuthoritySetChanges<N>>>
+{
+	load_decode(backend, AUTHORITY_SET_CHANGES_KEY)
+}
+
+/// Start tracking the authority set changes if they aren't tracked yet and the current set is
+/// the genesis set, so that none has been missed. Otherwise the changes that already happened
+/// can't be recovered, and they stay untracked.
+fn init_authority_set_changes<Block: BlockT, B: AuxStore>(backend: &B, set_id: SetId) -> ClientResult<()> {
+	if set_id == 0 && backend.get_aux(AUTHORITY_SET_CHANGES_KEY)?.is_none() {
+		let changes = AuthoritySetChanges::<NumberFor<Block>>::new();
+		backend.insert_aux(&[(AUTHORITY_SET_CHANGES_KEY, changes.encode().as_slice())], &[])?;
+	}
+	Ok(())
+}
+
+/// Update the authority set changes.
+pub(crate) fn update_authority_set_changes<N, F, R>(
+	changes: &AuthoritySetChanges<N>,
+	write_aux: F
+) -> R where
+	N: Encode,
+	F: FnOnce(&[(&'static [u8], &[u8])]) -> R,
+{
+	write_aux(&[(AUTHORITY_SET_CHANGES_KEY, changes.encode().as_slice())])
+}
+
 #[cfg(test)]
 pub(crate) fn load_authorities<B: AuxStore, H: Decode, N: Decode>(backend: &B)
 	-> Option<AuthoritySet<H, N>> {
@@ -607,4 +662,40 @@ mod test {
 			},
 		);
 	}
+
+	#[test]
+	fn authority_set_changes_are_only_tracked_since_genesis() {
+		// a new database tracks the changes from genesis.
+		let client = test_client::new();
+		load_persistent::<test_client::runtime::Block, _, _>(
+			&client,
+			H256::random(),
+			0,
+			|| Ok(vec![(AuthorityId::default(), 100)]),
+		).unwrap();
+		assert_eq!(load_authority_set_changes::<_, u64>(&client).unwrap(), Some(Vec::new()));
+
+		// a database that already went through changes without tracking them can't prove them.
+		let client = test_client::new();
+		let authority_set = AuthoritySet::<H256, u64> {
+			current_authorities: vec![(AuthorityId::default(), 100)],
+			pending_standard_changes: ForkTree::new(),
+			pending_forced_changes: Vec::new(),
+			set_id: 3,
+		};
+		client.insert_aux(
+			&[
+				(VERSION_KEY, 2u32.encode().as_slice()),
+				(AUTHORITY_SET_KEY, authority_set.encode().as_slice()),
+			],
+			&[],
+		).unwrap();
+		load_persistent::<test_client::runtime::Block, _, _>(
+			&client,
+			H256::random(),
+			0,
+			|| unreachable!(),
+		).unwrap();
+		assert_eq!(load_authority_set_changes::<_, u64>(&client).unwrap(), None);
+	}
 }
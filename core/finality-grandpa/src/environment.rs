@@ -1025,6 +1025,26 @@ pub(crate) fn finalize_block<B, Block: BlockT<Hash=H256>, E, RA>(
 			None
 		};
 
+		if let Some(ref new_set) = new_authorities {
+			// remember the last block finalized by the previous set, so that the set changes can
+			// be proven to warp syncing nodes. this is only possible if they've been tracked since
+			// genesis.
+			if let Some(mut changes) = crate::aux_schema::load_authority_set_changes(client)? {
+				changes.push((new_set.set_id - 1, number));
+				let write_result = crate::aux_schema::update_authority_set_changes(
+					&changes,
+					|insert| apply_aux(import_op, insert, &[]),
+				);
+
+				if let Err(e) = write_result {
+					warn!(target: "finality", "Failed to write updated authority set changes to disk. Bailing.");
+					warn!(target: "finality", "Node is in a potentially inconsistent state.");
+
+					return Err(e.into());
+				}
+			}
+		}
+
 		if status.changed {
 			let write_result = crate::aux_schema::update_authority_set::<Block, _, _>(
 				&authority_set,
@@ -33,30 +33,45 @@
 //! Finality proof provider can choose how to provide finality proof on its own. The incomplete
 //! finality proof (that finalizes some block C that is ancestor of the B and descendant
 //! of the U) could be returned.
+//!
+//! Warp syncing nodes, which have no blocks at all, are instead given the last block of every
+//! authority set since genesis along with its justification. The header of each of these blocks
+//! signals the next authority set, so that the whole chain of sets can be verified without
+//! downloading any other block or state.
 
 use std::sync::Arc;
+use futures::sync::mpsc;
 use log::{trace, warn};
 
 use client::{
-	backend::Backend, blockchain::Backend as BlockchainBackend, CallExecutor, Client,
+	backend::{AuxStore, Backend}, blockchain::Backend as BlockchainBackend, CallExecutor, Client,
 	error::{Error as ClientError, Result as ClientResult},
 	light::fetcher::{FetchChecker, RemoteCallRequest}, ExecutionStrategy,
 };
 use codec::{Encode, Decode};
 use grandpa::BlockNumberOps;
+use network::config::{WarpSyncProgress, WarpSyncVerifier as NetworkWarpSyncVerifier};
 use sr_primitives::{
 	Justification, generic::BlockId,
-	traits::{NumberFor, Block as BlockT, Header as HeaderT, One},
+	traits::{NumberFor, Block as BlockT, Header as HeaderT, One, Zero},
 };
 use primitives::{H256, Blake2Hasher};
 use substrate_telemetry::{telemetry, CONSENSUS_INFO};
 use fg_primitives::AuthorityId;
 
+use crate::{NewAuthoritySet, VoterCommand};
+use crate::authorities::{AuthoritySet, SharedAuthoritySet};
+use crate::aux_schema::{self, AuthoritySetChanges};
+use crate::import::find_scheduled_change;
 use crate::justification::GrandpaJustification;
 
 /// Maximum number of fragments that we want to return in a single prove_finality call.
 const MAX_FRAGMENTS_IN_PROOF: usize = 8;
 
+/// Maximum number of authority set changes that we want to return in a single prove_warp_sync
+/// call.
+const MAX_CHANGES_IN_WARP_SYNC_PROOF: usize = 128;
+
 /// GRANDPA authority set related methods for the finality proof provider.
 pub trait AuthoritySetForFinalityProver<Block: BlockT>: Send + Sync {
 	/// Call GrandpaApi::grandpa_authorities at given block.
@@ -175,6 +190,14 @@ impl<B, Block> network::FinalityProofProvider<Block> for FinalityProofProvider<B
 			),
 		}
 	}
+
+	fn prove_warp_sync(&self, begin: Block::Hash) -> Result<Option<Vec<u8>>, ClientError> {
+		match aux_schema::load_authority_set_changes(&*self.backend)? {
+			Some(changes) => prove_warp_sync(&*self.backend.blockchain(), &changes, begin),
+			// the changes that happened before they were tracked can't be proven
+			None => Ok(None),
+		}
+	}
 }
 
 /// The effects of block finality.
@@ -233,6 +256,46 @@ struct OriginalFinalityProofRequest<H: Encode + Decode> {
 	pub last_finalized: H,
 }
 
+/// Block finalized by an authority set, with its justification.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpSyncFragment<Header: HeaderT> {
+	/// Header of the block. If the block ends the authority set, it signals the next set.
+	pub header: Header,
+	/// Justification of the block.
+	pub justification: Vec<u8>,
+}
+
+/// Proof of the authority set changes that follow a block, for warp sync.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpSyncProof<Header: HeaderT> {
+	/// The last block of each authority set, in order.
+	pub changes: Vec<WarpSyncFragment<Header>>,
+	/// The latest block finalized by the last set, if it follows the last change.
+	pub target: Option<WarpSyncFragment<Header>>,
+	/// False if the proof has been truncated, in which case another proof must be requested
+	/// starting at the last change.
+	pub is_finished: bool,
+}
+
+/// The effects of a warp sync proof.
+#[derive(Debug, PartialEq)]
+pub(crate) struct WarpSyncEffects<Header: HeaderT> {
+	/// The proven authority set changes.
+	pub changes: AuthoritySetChanges<Header::Number>,
+	/// The authorities of the set ending with each change.
+	pub change_authorities: Vec<Vec<(AuthorityId, u64)>>,
+	/// The id of the set that finalizes the blocks after `header`.
+	pub set_id: u64,
+	/// The authorities of the set that finalizes the blocks after `header`.
+	pub authorities: Vec<(AuthorityId, u64)>,
+	/// The latest block proven to be final.
+	pub header: Header,
+	/// The justification of `header`.
+	pub justification: Vec<u8>,
+	/// False if another proof must be requested starting at `header`.
+	pub is_finished: bool,
+}
+
 /// Prepare data blob associated with finality proof request.
 pub(crate) fn make_finality_proof_request<H: Encode + Decode>(last_finalized: H, authorities_set_id: u64) -> Vec<u8> {
 	FinalityProofRequest::Original(OriginalFinalityProofRequest {
@@ -401,6 +464,292 @@ pub(crate) fn prove_finality<Block: BlockT<Hash=H256>, B: BlockchainBackend<Bloc
 	}
 }
 
+/// Prepare the proof of the authority set changes that follow the block `begin`, for warp sync.
+///
+/// `begin` must be the genesis block or the last block of an authority set. Up to
+/// `MAX_CHANGES_IN_WARP_SYNC_PROOF` changes are proven, followed by the latest finalized block
+/// with a justification.
+///
+/// Returns None if there are no finalized blocks to prove, or if the next change is a forced
+/// change, whose justifications can't be verified with the previous set.
+pub(crate) fn prove_warp_sync<Block: BlockT, B: BlockchainBackend<Block>>(
+	blockchain: &B,
+	changes: &AuthoritySetChanges<NumberFor<Block>>,
+	begin: Block::Hash,
+) -> ClientResult<Option<Vec<u8>>> {
+	let begin_number = blockchain.expect_block_number_from_id(&BlockId::Hash(begin))?;
+	let canonical_begin = blockchain.expect_block_hash_from_id(&BlockId::Number(begin_number))?;
+	if begin != canonical_begin {
+		return Err(ClientError::Backend(
+			format!("Cannot generate warp sync proof for non-canonical block: {}", begin),
+		));
+	}
+
+	let mut set_id = if begin_number.is_zero() {
+		0
+	} else {
+		match changes.iter().find(|(_, number)| *number == begin_number) {
+			Some((set_id, _)) => set_id + 1,
+			None => return Err(ClientError::Backend(
+				format!("Cannot generate warp sync proof for block not ending an authority set: {}", begin),
+			)),
+		}
+	};
+
+	let mut fragments = Vec::new();
+	let mut is_finished = true;
+	for (change_set_id, number) in changes.iter().filter(|(_, number)| *number > begin_number) {
+		if *change_set_id != set_id {
+			trace!(
+				target: "finality",
+				"Can not prove authority set changes after forced change of set #{}.",
+				set_id,
+			);
+
+			is_finished = false;
+			break;
+		}
+		if fragments.len() == MAX_CHANGES_IN_WARP_SYNC_PROOF {
+			is_finished = false;
+			break;
+		}
+
+		let id = BlockId::Number(*number);
+		let justification = blockchain.justification(id)?.ok_or_else(|| ClientError::Backend(
+			format!("Missing justification of block #{} ending authority set #{}", number, set_id),
+		))?;
+		fragments.push(WarpSyncFragment { header: blockchain.expect_header(id)?, justification });
+		set_id += 1;
+	}
+
+	// search for the latest justification of the current set
+	let mut target = None;
+	if is_finished {
+		let last_number = fragments.last().map_or(begin_number, |fragment| *fragment.header.number());
+		let mut number = blockchain.info().finalized_number;
+		while number > last_number {
+			let id = BlockId::Number(number);
+			if let Some(justification) = blockchain.justification(id)? {
+				target = Some(WarpSyncFragment { header: blockchain.expect_header(id)?, justification });
+				break;
+			}
+			number = number - One::one();
+		}
+	}
+
+	if fragments.is_empty() && target.is_none() {
+		trace!(
+			target: "finality",
+			"No justifications found when making warp sync proof for {}. Returning empty proof.",
+			begin,
+		);
+
+		return Ok(None);
+	}
+
+	trace!(
+		target: "finality",
+		"Built warp sync proof for {} of {} authority set changes.",
+		begin,
+		fragments.len(),
+	);
+
+	Ok(Some(WarpSyncProof { changes: fragments, target, is_finished }.encode()))
+}
+
+/// Check the proof of the authority set changes that follow a block finalized by the given set.
+pub(crate) fn check_warp_sync_proof<Block: BlockT, J>(
+	mut set_id: u64,
+	mut authorities: Vec<(AuthorityId, u64)>,
+	remote_proof: &[u8],
+) -> ClientResult<WarpSyncEffects<Block::Header>>
+	where
+		J: ProvableJustification<Block::Header>,
+{
+	let proof = WarpSyncProof::<Block::Header>::decode(&mut &remote_proof[..])
+		.map_err(|_| ClientError::BadJustification("failed to decode warp sync proof".into()))?;
+
+	// a truncated proof must prove at least one change, and can't have a target
+	if !proof.is_finished && (proof.changes.is_empty() || proof.target.is_some()) {
+		return Err(ClientError::BadJustification("redundant warp sync proof".into()));
+	}
+
+	let mut changes = Vec::with_capacity(proof.changes.len());
+	let mut change_authorities = Vec::with_capacity(proof.changes.len());
+	let mut latest = None;
+	for fragment in proof.changes {
+		check_warp_sync_fragment::<_, J>(set_id, &authorities, &fragment)?;
+
+		// the runtime only schedules changes enacted by the block that signals them
+		let change = find_scheduled_change::<Block>(&fragment.header)
+			.filter(|change| change.delay.is_zero())
+			.ok_or_else(|| ClientError::BadJustification("missing authority set change".into()))?;
+
+		changes.push((set_id, *fragment.header.number()));
+		change_authorities.push(authorities);
+		set_id += 1;
+		authorities = change.next_authorities;
+		latest = Some(fragment);
+	}
+
+	if let Some(target) = proof.target {
+		check_warp_sync_fragment::<_, J>(set_id, &authorities, &target)?;
+		latest = Some(target);
+	}
+
+	let latest = latest.ok_or_else(|| ClientError::BadJustification("empty warp sync proof".into()))?;
+	Ok(WarpSyncEffects {
+		changes,
+		change_authorities,
+		set_id,
+		authorities,
+		header: latest.header,
+		justification: latest.justification,
+		is_finished: proof.is_finished,
+	})
+}
+
+/// Check that the block of the fragment is finalized by the given set.
+fn check_warp_sync_fragment<Header: HeaderT, J: ProvableJustification<Header>>(
+	set_id: u64,
+	authorities: &[(AuthorityId, u64)],
+	fragment: &WarpSyncFragment<Header>,
+) -> ClientResult<()> {
+	let justification = J::decode_and_verify(&fragment.justification, set_id, authorities)?;
+	if justification.target_hash() != fragment.header.hash() {
+		return Err(ClientError::BadJustification("justification of another block".into()));
+	}
+
+	Ok(())
+}
+
+/// Verifier of the warp sync proofs, used by the network.
+///
+/// Once the state of the target block is imported, its authority set becomes the current one
+/// and the voter is restarted from that block. The changes signaled before the target but
+/// enacted after it are lost, which doesn't happen as long as the runtime only schedules changes
+/// enacted by the block that signals them.
+pub(crate) struct WarpSyncVerifier<B, E, Block: BlockT<Hash=H256>, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	send_voter_commands: mpsc::UnboundedSender<VoterCommand<Block::Hash, NumberFor<Block>>>,
+	/// The latest block proven to be final.
+	last_block: Block::Hash,
+	/// The id of the set that finalizes the blocks after `last_block`.
+	set_id: u64,
+	/// The authorities of the set that finalizes the blocks after `last_block`.
+	authorities: Vec<(AuthorityId, u64)>,
+	/// The authority set changes proven so far.
+	changes: AuthoritySetChanges<NumberFor<Block>>,
+	/// The authorities of the set ending with each of `changes`.
+	change_authorities: Vec<Vec<(AuthorityId, u64)>>,
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA> WarpSyncVerifier<B, E, Block, RA> {
+	/// Create a verifier of the proofs that start at the genesis block, finalized by the given
+	/// set.
+	pub(crate) fn new(
+		client: Arc<Client<B, E, Block, RA>>,
+		authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+		send_voter_commands: mpsc::UnboundedSender<VoterCommand<Block::Hash, NumberFor<Block>>>,
+		genesis_hash: Block::Hash,
+		genesis_authorities: Vec<(AuthorityId, u64)>,
+	) -> Self {
+		WarpSyncVerifier {
+			client,
+			authority_set,
+			send_voter_commands,
+			last_block: genesis_hash,
+			set_id: 0,
+			authorities: genesis_authorities,
+			changes: Vec::new(),
+			change_authorities: Vec::new(),
+		}
+	}
+}
+
+impl<B, E, Block, RA> NetworkWarpSyncVerifier<Block> for WarpSyncVerifier<B, E, Block, RA>
+	where
+		Block: BlockT<Hash=H256>,
+		NumberFor<Block>: BlockNumberOps,
+		B: Backend<Block, Blake2Hasher> + 'static,
+		E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+		RA: Send + Sync,
+{
+	fn verify(&mut self, begin: &Block::Hash, proof: &[u8]) -> Result<WarpSyncProgress<Block>, String> {
+		if *begin != self.last_block {
+			return Err(format!("Unexpected start of warp sync proof: {}", begin));
+		}
+
+		let effects = check_warp_sync_proof::<Block, GrandpaJustification<Block>>(
+			self.set_id,
+			self.authorities.clone(),
+			proof,
+		).map_err(|e| e.to_string())?;
+
+		self.changes.extend(effects.changes);
+		self.change_authorities.extend(effects.change_authorities);
+		self.set_id = effects.set_id;
+		self.authorities = effects.authorities;
+		self.last_block = effects.header.hash();
+
+		if effects.is_finished {
+			Ok(WarpSyncProgress::Complete(effects.header, effects.justification))
+		} else {
+			Ok(WarpSyncProgress::Partial(self.last_block))
+		}
+	}
+
+	fn apply(&mut self, header: &Block::Header) -> Result<(), String> {
+		let hash = header.hash();
+		if hash != self.last_block {
+			return Err(format!("Block {} isn't the target of the warp sync", hash));
+		}
+
+		let mut authority_set = self.authority_set.inner().write();
+		*authority_set = AuthoritySet::new(self.authorities.clone(), self.set_id);
+		let new_set = NewAuthoritySet {
+			canon_hash: hash,
+			canon_number: *header.number(),
+			set_id: self.set_id,
+			authorities: self.authorities.clone(),
+		};
+
+		aux_schema::update_authority_set::<Block, _, _>(
+			&authority_set,
+			Some(&new_set),
+			|insert| self.client.insert_aux(insert, &[]),
+		).and_then(|_| aux_schema::update_authority_set_changes(
+			&self.changes,
+			|insert| self.client.insert_aux(insert, &[]),
+		)).map_err(|e| e.to_string())?;
+
+		// the voter might not be running, e.g. on nodes that aren't authorities
+		let _ = self.send_voter_commands.unbounded_send(VoterCommand::ChangeAuthorities(new_set));
+		Ok(())
+	}
+
+	fn check_historic_justification(
+		&self,
+		header: &Block::Header,
+		justification: &Justification,
+	) -> Result<bool, String> {
+		// only the blocks ending the proven sets are needed to prove the changes to other nodes
+		let position = match self.changes.iter().position(|(_, number)| number == header.number()) {
+			Some(position) => position,
+			None => return Ok(false),
+		};
+
+		let (set_id, _) = self.changes[position];
+		let fragment = WarpSyncFragment { header: header.clone(), justification: justification.clone() };
+		check_warp_sync_fragment::<_, GrandpaJustification<Block>>(
+			set_id,
+			&self.change_authorities[position],
+			&fragment,
+		).map(|_| true).map_err(|e| e.to_string())
+	}
+}
+
 /// Check GRANDPA proof-of-finality for the given block.
 ///
 /// Returns the vector of headers that MUST be validated + imported
@@ -547,6 +896,9 @@ pub(crate) trait ProvableJustification<Header: HeaderT>: Encode + Decode {
 	/// Verify justification with respect to authorities set and authorities set id.
 	fn verify(&self, set_id: u64, authorities: &[(AuthorityId, u64)]) -> ClientResult<()>;
 
+	/// Hash of the block that the justification finalizes.
+	fn target_hash(&self) -> Header::Hash;
+
 	/// Decode and verify justification.
 	fn decode_and_verify(
 		justification: &Justification,
@@ -567,6 +919,10 @@ impl<Block: BlockT<Hash=H256>> ProvableJustification<Block::Header> for GrandpaJ
 	fn verify(&self, set_id: u64, authorities: &[(AuthorityId, u64)]) -> ClientResult<()> {
 		GrandpaJustification::verify(self, set_id, &authorities.iter().cloned().collect())
 	}
+
+	fn target_hash(&self) -> Block::Hash {
+		self.commit.target_hash
+	}
 }
 
 #[cfg(test)]
@@ -576,6 +932,9 @@ pub(crate) mod tests {
 	use test_client::client::in_mem::Blockchain as InMemoryBlockchain;
 	use super::*;
 	use primitives::crypto::Public;
+	use client::blockchain::HeaderBackend;
+	use fg_primitives::{GRANDPA_ENGINE_ID, ConsensusLog, ScheduledChange};
+	use sr_primitives::generic::DigestItem;
 
 	type FinalityProof = super::FinalityProof<Header>;
 
@@ -620,6 +979,10 @@ pub(crate) mod tests {
 				Err(ClientError::BadJustification("test".into()))
 			}
 		}
+
+		fn target_hash(&self) -> H256 {
+			header(self.1.first().cloned().unwrap_or_default() as u64).hash()
+		}
 	}
 
 	fn header(number: u64) -> Header {
@@ -981,4 +1344,98 @@ pub(crate) mod tests {
 		).unwrap();
 		assert!(proof_of_4.is_none());
 	}
+
+	#[derive(Debug, PartialEq, Encode, Decode)]
+	struct TestWarpJustification(u64, H256);
+
+	impl ProvableJustification<Header> for TestWarpJustification {
+		fn verify(&self, set_id: u64, _authorities: &[(AuthorityId, u64)]) -> ClientResult<()> {
+			if self.0 == set_id {
+				Ok(())
+			} else {
+				Err(ClientError::BadJustification("test".into()))
+			}
+		}
+
+		fn target_hash(&self) -> H256 {
+			self.1
+		}
+	}
+
+	fn warp_sync_blockchain() -> (InMemoryBlockchain<Block>, Vec<(AuthorityId, u64)>) {
+		let new_authorities = vec![(AuthorityId::from_slice(&[2u8; 32]), 1u64)];
+		let mut header1 = Header::new(
+			1,
+			H256::from_low_u64_be(0),
+			H256::from_low_u64_be(0),
+			header(0).hash(),
+			Default::default(),
+		);
+		header1.digest_mut().push(DigestItem::Consensus(
+			GRANDPA_ENGINE_ID,
+			ConsensusLog::ScheduledChange(ScheduledChange {
+				next_authorities: new_authorities.clone(),
+				delay: 0,
+			}).encode(),
+		));
+		let header2 = Header::new(
+			2,
+			H256::from_low_u64_be(0),
+			H256::from_low_u64_be(0),
+			header1.hash(),
+			Default::default(),
+		);
+		let header3 = Header::new(
+			3,
+			H256::from_low_u64_be(0),
+			H256::from_low_u64_be(0),
+			header2.hash(),
+			Default::default(),
+		);
+		let just1 = TestWarpJustification(0, header1.hash()).encode();
+		let just2 = TestWarpJustification(1, header2.hash()).encode();
+
+		let blockchain = InMemoryBlockchain::<Block>::new();
+		blockchain.insert(header(0).hash(), header(0), None, None, NewBlockState::Final).unwrap();
+		blockchain.insert(header1.hash(), header1, Some(just1), None, NewBlockState::Final).unwrap();
+		blockchain.insert(header2.hash(), header2, Some(just2), None, NewBlockState::Final).unwrap();
+		blockchain.insert(header3.hash(), header3, None, None, NewBlockState::Final).unwrap();
+		(blockchain, new_authorities)
+	}
+
+	#[test]
+	fn warp_sync_proof_works() {
+		let (blockchain, new_authorities) = warp_sync_blockchain();
+
+		// set #0 ends at block 1, which signals set #1
+		// the latest justification of set #1 is for block 2
+		// => the proof contains the blocks 1 and 2
+		let proof = prove_warp_sync(&blockchain, &vec![(0, 1)], header(0).hash()).unwrap().unwrap();
+		let effects = check_warp_sync_proof::<Block, TestWarpJustification>(
+			0,
+			vec![(AuthorityId::from_slice(&[1u8; 32]), 1u64)],
+			&proof,
+		).unwrap();
+
+		let header2 = blockchain.header(BlockId::Number(2)).unwrap().unwrap();
+		assert_eq!(effects, WarpSyncEffects {
+			changes: vec![(0, 1)],
+			change_authorities: vec![vec![(AuthorityId::from_slice(&[1u8; 32]), 1u64)]],
+			set_id: 1,
+			authorities: new_authorities,
+			justification: TestWarpJustification(1, header2.hash()).encode(),
+			header: header2,
+			is_finished: true,
+		});
+	}
+
+	#[test]
+	fn warp_sync_proof_is_none_after_forced_change() {
+		let (blockchain, _) = warp_sync_blockchain();
+
+		// set #0 has been replaced by a forced change, and set #1 ends at block 1
+		// => the justification of block 1 can't be verified by set #0
+		let proof = prove_warp_sync(&blockchain, &vec![(1, 1)], header(0).hash()).unwrap();
+		assert!(proof.is_none());
+	}
 }
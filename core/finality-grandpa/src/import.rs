@@ -31,6 +31,7 @@ use consensus_common::{
 	SelectChain,
 };
 use fg_primitives::{GRANDPA_ENGINE_ID, ScheduledChange, ConsensusLog};
+use network::config::BoxWarpSyncVerifier;
 use sr_primitives::Justification;
 use sr_primitives::generic::{BlockId, OpaqueDigestItemId};
 use sr_primitives::traits::{
@@ -42,6 +43,7 @@ use crate::{Error, CommandOrError, NewAuthoritySet, VoterCommand};
 use crate::authorities::{AuthoritySet, SharedAuthoritySet, DelayKind, PendingChange};
 use crate::consensus_changes::SharedConsensusChanges;
 use crate::environment::finalize_block;
+use crate::finality_proof::WarpSyncVerifier;
 use crate::justification::GrandpaJustification;
 
 /// A block-import handler for GRANDPA.
@@ -168,7 +170,7 @@ impl<'a, Block: 'a + BlockT> Drop for PendingSetChanges<'a, Block> {
 	}
 }
 
-fn find_scheduled_change<B: BlockT>(header: &B::Header)
+pub(crate) fn find_scheduled_change<B: BlockT>(header: &B::Header)
 	-> Option<ScheduledChange<NumberFor<B>>>
 {
 	let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
@@ -536,6 +538,21 @@ where
 	E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
 	RA: Send + Sync,
 {
+	/// Create a verifier of the warp sync proofs, for nodes whose database is empty.
+	///
+	/// Once the network imports the state of the block that the warp sync reached, the
+	/// authority set of that block becomes the current one.
+	pub fn warp_sync_verifier(&self) -> BoxWarpSyncVerifier<Block> where RA: 'static {
+		let genesis_hash = self.inner.info().chain.genesis_hash;
+		let genesis_authorities = self.authority_set.inner().read().current().1.to_vec();
+		Box::new(WarpSyncVerifier::new(
+			self.inner.clone(),
+			self.authority_set.clone(),
+			self.send_voter_commands.clone(),
+			genesis_hash,
+			genesis_authorities,
+		))
+	}
 
 	/// Import a block justification and finalize the block.
 	///
@@ -20,7 +20,7 @@ use super::*;
 use environment::HasVoted;
 use network::test::{Block, DummySpecialization, Hash, TestNetFactory, Peer, PeersClient};
 use network::test::{PassThroughVerifier};
-use network::config::{ProtocolConfig, Roles, BoxFinalityProofRequestBuilder};
+use network::config::{ProtocolConfig, Roles, BoxFinalityProofRequestBuilder, SyncMode};
 use parking_lot::Mutex;
 use futures03::{StreamExt as _, TryStreamExt as _};
 use tokio::runtime::current_thread;
@@ -95,6 +95,8 @@ impl TestNetFactory for GrandpaTestNet {
 		// the authority role ensures gossip hits all nodes here.
 		ProtocolConfig {
 			roles: Roles::AUTHORITY,
+			keep_block_bodies: None,
+			sync_mode: SyncMode::Full,
 		}
 	}
 
@@ -16,33 +16,49 @@
 
 use crate::{
 	debug_info, discovery::DiscoveryBehaviour, discovery::DiscoveryOut, DiscoveryNetBehaviour,
-	protocol::event::DhtEvent
+	protocol::event::{DhtEvent, Event}
 };
 use crate::{ExHashT, specialization::NetworkSpecialization};
-use crate::protocol::{CustomMessageOutcome, Protocol};
+use crate::config::ProtocolId;
+use crate::protocol::{CustomMessageOutcome, Protocol, RequestProtocol, MAX_RESPONSE_SIZE};
+use crate::notifications::{self, Notifications};
+use crate::request_responses::{self, RequestFailure, RequestResponses};
+use bytes::Bytes;
 use futures::prelude::*;
 use libp2p::NetworkBehaviour;
 use libp2p::core::{Multiaddr, PeerId, PublicKey};
 use libp2p::kad::record;
 use libp2p::swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess};
 use libp2p::core::{nodes::Substream, muxing::StreamMuxerBox};
-use log::warn;
+use log::{debug, warn};
 use sr_primitives::traits::Block as BlockT;
-use std::iter;
+use std::{collections::HashSet, iter, time::Duration};
 use void;
 
+/// Maximum size of a request sent on a request-response protocol.
+const MAX_REQUEST_SIZE: usize = 1024 * 1024;
+
 /// General behaviour of the network. Combines all protocols together.
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "BehaviourOut<B>", poll_method = "poll")]
 pub struct Behaviour<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	/// All the substrate-specific protocols.
 	substrate: Protocol<B, S, H>,
+	/// Request-response protocols, on which the substrate-specific protocols send their requests
+	/// to the peers that support them. Must be polled after `substrate`.
+	request_responses: RequestResponses<Substream<StreamMuxerBox>>,
+	/// Notifications protocols registered by the upper layers.
+	notifications: Notifications<Substream<StreamMuxerBox>>,
 	/// Periodically pings and identifies the nodes we are connected to, and store information in a
 	/// cache.
 	debug_info: debug_info::DebugInfoBehaviour<Substream<StreamMuxerBox>>,
 	/// Discovers nodes of the network.
 	discovery: DiscoveryBehaviour<Substream<StreamMuxerBox>>,
 
+	/// Names of the request-response protocols.
+	#[behaviour(ignore)]
+	request_protocols: Vec<(RequestProtocol, Bytes)>,
+
 	/// Queue of events to produce for the outside.
 	#[behaviour(ignore)]
 	events: Vec<BehaviourOut<B>>,
@@ -52,21 +68,44 @@ pub struct Behaviour<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 pub enum BehaviourOut<B: BlockT> {
 	SubstrateAction(CustomMessageOutcome<B>),
 	Dht(DhtEvent),
+	/// Event about a notifications protocol.
+	Notifications(Event),
 }
 
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Behaviour<B, S, H> {
 	/// Builds a new `Behaviour`.
 	pub fn new(
 		substrate: Protocol<B, S, H>,
+		protocol_id: &ProtocolId,
 		user_agent: String,
 		local_public_key: PublicKey,
 		known_addresses: Vec<(PeerId, Multiaddr)>,
+		private_peers: HashSet<PeerId>,
 		enable_mdns: bool,
 	) -> Self {
+		let request_protocols = vec![
+			(RequestProtocol::Sync, request_protocol_name(protocol_id, b"sync")),
+			(RequestProtocol::Light, request_protocol_name(protocol_id, b"light")),
+		];
+		let request_responses = RequestResponses::new(request_protocols.iter().map(|(protocol, name)| {
+			request_responses::ProtocolConfig {
+				name: name.clone(),
+				max_request_size: MAX_REQUEST_SIZE,
+				max_response_size: MAX_RESPONSE_SIZE,
+				request_timeout: match protocol {
+					RequestProtocol::Sync => Duration::from_secs(40),
+					RequestProtocol::Light => Duration::from_secs(15),
+				},
+			}
+		}));
+
 		Behaviour {
 			substrate,
+			request_responses,
+			notifications: Notifications::new(),
+			request_protocols,
 			debug_info: debug_info::DebugInfoBehaviour::new(user_agent, local_public_key.clone()),
-			discovery: DiscoveryBehaviour::new(local_public_key, known_addresses, enable_mdns),
+			discovery: DiscoveryBehaviour::new(local_public_key, known_addresses, private_peers, enable_mdns),
 			events: Vec::new(),
 		}
 	}
@@ -81,6 +120,12 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Behaviour<B, S, H> {
 		self.discovery.add_known_address(peer_id, addr)
 	}
 
+	/// Adds an address for the given peer to the Kademlia routing table, where it can expire like
+	/// the addresses that the nodes report.
+	pub fn add_discovered_address(&mut self, peer_id: &PeerId, addr: Multiaddr) {
+		self.discovery.add_self_reported_address(peer_id, addr)
+	}
+
 	/// Borrows `self` and returns a struct giving access to the information about a node.
 	///
 	/// Returns `None` if we don't know anything about this node. Always returns `Some` for nodes
@@ -109,6 +154,33 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Behaviour<B, S, H> {
 	pub fn put_value(&mut self, key: record::Key, value: Vec<u8>) {
 		self.discovery.put_value(key, value);
 	}
+
+	/// Registers a notifications protocol. See `NetworkService::register_notifications_protocol`.
+	pub fn register_notifications_protocol(&mut self, protocol: Bytes, handshake: Vec<u8>) {
+		self.notifications.register_protocol(protocol, handshake);
+	}
+
+	/// Sends a notification to `target` on the notifications protocol named `protocol`.
+	pub fn write_notification(&mut self, target: &PeerId, protocol: Bytes, message: Vec<u8>) {
+		self.notifications.write_notification(target, protocol, message);
+	}
+
+	/// Returns the request-response protocol with the given name.
+	fn request_protocol(&self, name: &Bytes) -> Option<RequestProtocol> {
+		self.request_protocols.iter()
+			.find(|(_, n)| n == name)
+			.map(|(protocol, _)| *protocol)
+	}
+}
+
+/// Returns the name of the request-response protocol `/substrate/<protocol_id>/<kind>/1`.
+fn request_protocol_name(protocol_id: &ProtocolId, kind: &[u8]) -> Bytes {
+	let mut name = Bytes::from_static(b"/substrate/");
+	name.extend_from_slice(protocol_id.as_bytes());
+	name.extend_from_slice(b"/");
+	name.extend_from_slice(kind);
+	name.extend_from_slice(b"/1");
+	name
 }
 
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventProcess<void::Void> for
@@ -121,7 +193,64 @@ Behaviour<B, S, H> {
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventProcess<CustomMessageOutcome<B>> for
 Behaviour<B, S, H> {
 	fn inject_event(&mut self, event: CustomMessageOutcome<B>) {
-		self.events.push(BehaviourOut::SubstrateAction(event));
+		match event {
+			CustomMessageOutcome::Request { target, protocol, request } => {
+				let name = self.request_protocols.iter()
+					.find(|(p, _)| *p == protocol)
+					.map(|(_, name)| name.clone())
+					.expect("request_protocols contains all the request protocols; qed");
+				self.request_responses.send_request(&target, name, request);
+			}
+			event => self.events.push(BehaviourOut::SubstrateAction(event)),
+		}
+	}
+}
+
+impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventProcess<request_responses::Event>
+	for Behaviour<B, S, H> {
+	fn inject_event(&mut self, event: request_responses::Event) {
+		match event {
+			request_responses::Event::InboundRequest { peer, protocol, request_id, request } => {
+				let protocol = self.request_protocol(&protocol)
+					.expect("we only support the protocols of request_protocols; qed");
+				if let Some(response) = self.substrate.on_request(peer.clone(), protocol, &request) {
+					self.request_responses.send_response(&peer, request_id, response);
+				}
+			}
+			request_responses::Event::RequestFinished { peer, protocol, result, .. } => {
+				let protocol = self.request_protocol(&protocol)
+					.expect("we only send requests on the protocols of request_protocols; qed");
+				match result {
+					Ok(response) => match self.substrate.on_response(peer, protocol, &response) {
+						CustomMessageOutcome::None => {}
+						outcome => self.events.push(BehaviourOut::SubstrateAction(outcome)),
+					},
+					// The substrate-specific protocols clean up when the peer disconnects.
+					Err(RequestFailure::NotConnected) => {}
+					Err(err) => {
+						debug!(target: "sync", "Request on the {:?} protocol to {} failed: {}", protocol, peer, err);
+						self.substrate.on_request_failed(peer);
+					}
+				}
+			}
+		}
+	}
+}
+
+impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventProcess<notifications::Event>
+	for Behaviour<B, S, H> {
+	fn inject_event(&mut self, event: notifications::Event) {
+		let event = match event {
+			notifications::Event::Opened { peer, protocol, handshake } =>
+				Event::NotificationStreamOpened { remote: peer, protocol, handshake },
+			notifications::Event::Closed { peer, protocol } =>
+				Event::NotificationStreamClosed { remote: peer, protocol },
+			notifications::Event::Notification { peer, protocol, message } =>
+				Event::NotificationReceived { remote: peer, protocol, message },
+			notifications::Event::Clogged { peer, protocol } =>
+				Event::NotificationStreamClogged { remote: peer, protocol },
+		};
+		self.events.push(BehaviourOut::Notifications(event));
 	}
 }
 
@@ -18,12 +18,13 @@
 
 use client::{self, Client as SubstrateClient, ClientInfo, CallExecutor};
 use client::error::Error;
-use client::light::fetcher::ChangesProof;
+use client::light::fetcher::{ChangesProof, encode_compact_proof};
 use consensus::{BlockImport, BlockStatus, Error as ConsensusError};
 use sr_primitives::traits::{Block as BlockT, Header as HeaderT};
 use sr_primitives::generic::{BlockId};
 use sr_primitives::Justification;
 use primitives::{H256, Blake2Hasher, storage::StorageKey};
+use crate::message::StateEntry;
 
 /// Local client abstraction for the network.
 pub trait Client<Block: BlockT>: Send + Sync {
@@ -59,9 +60,21 @@ pub trait Client<Block: BlockT>: Send + Sync {
 		keys: &[Vec<u8>],
 	) -> Result<Vec<Vec<u8>>, Error>;
 
+	/// Get storage range read execution proof.
+	fn read_range_proof(
+		&self,
+		block: &Block::Hash,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: Option<u32>,
+	) -> Result<Vec<Vec<u8>>, Error>;
+
 	/// Get method execution proof.
 	fn execution_proof(&self, block: &Block::Hash, method: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), Error>;
 
+	/// Encode a read or execution proof of the state at the given block in the compact encoding.
+	fn compact_proof(&self, block: &Block::Hash, proof: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error>;
+
 	/// Get key changes proof.
 	fn key_changes_proof(
 		&self,
@@ -75,18 +88,61 @@ pub trait Client<Block: BlockT>: Send + Sync {
 
 	/// Returns `true` if the given `block` is a descendent of `base`.
 	fn is_descendent_of(&self, base: &Block::Hash, block: &Block::Hash) -> Result<bool, Error>;
+
+	/// Get a part of the state of the block, starting after `start_key` (and `start_child_key`
+	/// in the child trie stored at `start_key`, if given), of roughly `max_size` bytes.
+	/// Also returns whether the end of the state was reached and the proof of the entries.
+	fn state_chunk(
+		&self,
+		block: &Block::Hash,
+		start_key: Option<&[u8]>,
+		start_child_key: Option<&[u8]>,
+		max_size: usize,
+	) -> Result<(Vec<StateEntry>, bool, Vec<Vec<u8>>), Error>;
+
+	/// Check a part of the state of a block, downloaded during a warp sync, against the state root
+	/// of the block and store it.
+	fn import_state_chunk(
+		&self,
+		state_root: &Block::Hash,
+		start_key: Option<&[u8]>,
+		start_child_key: Option<&[u8]>,
+		entries: Vec<StateEntry>,
+		complete: bool,
+		proof: Vec<Vec<u8>>,
+	) -> Result<(), Error>;
+
+	/// Import the finalized block whose state was stored with `import_state_chunk`, in a database
+	/// that only contains the genesis block.
+	fn import_state(&self, header: Block::Header, justification: Option<Justification>) -> Result<(), Error>;
+
+	/// Import a block below the block whose state was imported with `import_state`.
+	fn import_historic_block(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> Result<(), Error>;
 }
 
 /// Finality proof provider.
 pub trait FinalityProofProvider<Block: BlockT>: Send + Sync {
 	/// Prove finality of the block.
 	fn prove_finality(&self, for_block: Block::Hash, request: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+	/// Prove the authority set changes that happened after the block `begin`, which is either
+	/// the genesis block or the last block proved by a previous warp sync proof.
+	fn prove_warp_sync(&self, begin: Block::Hash) -> Result<Option<Vec<u8>>, Error>;
 }
 
 impl<Block: BlockT> FinalityProofProvider<Block> for () {
 	fn prove_finality(&self, _for_block: Block::Hash, _request: &[u8]) -> Result<Option<Vec<u8>>, Error> {
 		Ok(None)
 	}
+
+	fn prove_warp_sync(&self, _begin: Block::Hash) -> Result<Option<Vec<u8>>, Error> {
+		Ok(None)
+	}
 }
 
 impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
@@ -138,10 +194,27 @@ impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
 			.read_child_proof(&BlockId::Hash(block.clone()), storage_key, keys)
 	}
 
+	fn read_range_proof(
+		&self,
+		block: &Block::Hash,
+		prefix: &[u8],
+		start_key: Option<&[u8]>,
+		count: Option<u32>,
+	) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block, RA>)
+			.read_range_proof(&BlockId::Hash(block.clone()), prefix, start_key, count)
+	}
+
 	fn execution_proof(&self, block: &Block::Hash, method: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), Error> {
 		(self as &SubstrateClient<B, E, Block, RA>).execution_proof(&BlockId::Hash(block.clone()), method, data)
 	}
 
+	fn compact_proof(&self, block: &Block::Hash, proof: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, Error> {
+		let header = (self as &SubstrateClient<B, E, Block, RA>).header(&BlockId::Hash(block.clone()))?
+			.ok_or_else(|| Error::UnknownBlock(format!("{}", block)))?;
+		encode_compact_proof::<Blake2Hasher>(header.state_root(), proof)
+	}
+
 	fn key_changes_proof(
 		&self,
 		first: Block::Hash,
@@ -163,4 +236,50 @@ impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
 
 		Ok(ancestor.hash == *base)
 	}
+
+	fn state_chunk(
+		&self,
+		block: &Block::Hash,
+		start_key: Option<&[u8]>,
+		start_child_key: Option<&[u8]>,
+		max_size: usize,
+	) -> Result<(Vec<StateEntry>, bool, Vec<Vec<u8>>), Error> {
+		let (entries, complete, proof) = (self as &SubstrateClient<B, E, Block, RA>)
+			.read_state_chunk(&BlockId::Hash(block.clone()), start_key, start_child_key, max_size)?;
+		let entries = entries.into_iter().map(|(storage_key, key, value)| match storage_key {
+			Some(storage_key) => StateEntry::Child(storage_key, key, value),
+			None => StateEntry::Top(key, value),
+		}).collect();
+		Ok((entries, complete, proof))
+	}
+
+	fn import_state_chunk(
+		&self,
+		state_root: &Block::Hash,
+		start_key: Option<&[u8]>,
+		start_child_key: Option<&[u8]>,
+		entries: Vec<StateEntry>,
+		complete: bool,
+		proof: Vec<Vec<u8>>,
+	) -> Result<(), Error> {
+		let entries = entries.into_iter().map(|entry| match entry {
+			StateEntry::Top(key, value) => (None, key, value),
+			StateEntry::Child(storage_key, key, value) => (Some(storage_key), key, value),
+		}).collect::<Vec<_>>();
+		(self as &SubstrateClient<B, E, Block, RA>)
+			.import_state_chunk(*state_root, start_key, start_child_key, &entries, complete, proof)
+	}
+
+	fn import_state(&self, header: Block::Header, justification: Option<Justification>) -> Result<(), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).import_state(header, justification)
+	}
+
+	fn import_historic_block(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> Result<(), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).import_historic_block(header, body, justification)
+	}
 }
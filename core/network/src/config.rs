@@ -27,7 +27,7 @@ use crate::on_demand_layer::OnDemand;
 use crate::service::{ExHashT, TransactionPool};
 use bitflags::bitflags;
 use consensus::{block_validation::BlockAnnounceValidator, import_queue::ImportQueue};
-use sr_primitives::traits::{Block as BlockT};
+use sr_primitives::{Justification, traits::{Block as BlockT}};
 use std::sync::Arc;
 use libp2p::identity::{Keypair, secp256k1, ed25519};
 use libp2p::wasm_ext;
@@ -41,6 +41,10 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	/// Assigned roles for our node (full, light, ...).
 	pub roles: Roles,
 
+	/// Number of finalized blocks whose bodies are kept by our node, or `None` if it keeps all of
+	/// them. This is advertised to other nodes, so that they don't request older bodies from us.
+	pub keep_block_bodies: Option<u32>,
+
 	/// Network layer configuration.
 	pub network_config: NetworkConfiguration,
 
@@ -58,6 +62,11 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	/// This object, if `Some`, is used when we need a proof of finality from another node.
 	pub finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
 
+	/// How to verify the proofs downloaded during a warp sync.
+	///
+	/// Warp sync is only possible if this is `Some`. See [`SyncMode::Warp`].
+	pub warp_sync_verifier: Option<BoxWarpSyncVerifier<B>>,
+
 	/// The `OnDemand` object acts as a "receiver" for block data requests from the client.
 	/// If `Some`, the network worker will process these requests and answer them.
 	/// Normally used only for light clients.
@@ -149,6 +158,38 @@ impl<B: BlockT> FinalityProofRequestBuilder<B> for DummyFinalityProofRequestBuil
 /// Shared finality proof request builder struct used by the queue.
 pub type BoxFinalityProofRequestBuilder<B> = Box<dyn FinalityProofRequestBuilder<B> + Send + Sync>;
 
+/// Verifier of the proofs of authority set changes downloaded during a warp sync.
+pub trait WarpSyncVerifier<B: BlockT>: Send {
+	/// Verify a proof of the authority set changes that happened after the block `begin`, which
+	/// is the genesis block or the last block proved by the previously verified proof.
+	fn verify(&mut self, begin: &B::Hash, proof: &[u8]) -> Result<WarpSyncProgress<B>, String>;
+
+	/// Called once the state of the block `header`, the target of the warp sync, has been
+	/// imported. The authority set it was proved with becomes the current one.
+	fn apply(&mut self, header: &B::Header) -> Result<(), String>;
+
+	/// Check the justification of a block downloaded while backfilling. Only the justifications
+	/// of the blocks ending a proven authority set can be checked, and they are the only ones
+	/// worth keeping: returns `Ok(false)` for the others, which are dropped.
+	fn check_historic_justification(
+		&self,
+		header: &B::Header,
+		justification: &Justification,
+	) -> Result<bool, String>;
+}
+
+/// Outcome of the verification of a warp sync proof.
+pub enum WarpSyncProgress<B: BlockT> {
+	/// More authority set changes follow. The next proof must begin at the given block.
+	Partial(B::Hash),
+	/// The proof reaches a recent finalized block, given with its justification. It is the target
+	/// of the warp sync.
+	Complete(B::Header, Justification),
+}
+
+/// Shared warp sync verifier struct used by sync.
+pub type BoxWarpSyncVerifier<B> = Box<dyn WarpSyncVerifier<B> + Send + Sync>;
+
 /// Name of a protocol, transmitted on the wire. Should be unique for each chain.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ProtocolId(smallvec::SmallVec<[u8; 6]>);
@@ -256,6 +297,10 @@ pub struct NetworkConfiguration {
 	pub reserved_nodes: Vec<String>,
 	/// The non-reserved peer mode.
 	pub non_reserved_mode: NonReservedPeerMode,
+	/// Role of the node in a sentry-node setup.
+	pub sentry_mode: SentryMode,
+	/// How the chain is synced.
+	pub sync_mode: SyncMode,
 	/// Client identifier. Sent over the wire for debugging purposes.
 	pub client_version: String,
 	/// Name of the node. Sent over the wire for debugging purposes.
@@ -277,6 +322,8 @@ impl Default for NetworkConfiguration {
 			out_peers: 75,
 			reserved_nodes: Vec::new(),
 			non_reserved_mode: NonReservedPeerMode::Accept,
+			sentry_mode: SentryMode::Disabled,
+			sync_mode: SyncMode::Full,
 			client_version: "unknown".into(),
 			node_name: "unknown".into(),
 			transport: TransportConfig::Normal {
@@ -359,6 +406,63 @@ impl NonReservedPeerMode {
 	}
 }
 
+/// Role of the node in a sentry-node setup.
+///
+/// A validator can hide its address from the rest of the network by only connecting to a set of
+/// *sentry* nodes. The sentries are regular full nodes that relay the gossip messages and block
+/// announcements of the validator, and whose addresses the validator publishes in place of its own.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SentryMode {
+	/// The node isn't part of a sentry-node setup. This is the default.
+	Disabled,
+	/// The node is a validator that only connects to the given sentry nodes.
+	///
+	/// This implies that only the sentry nodes are allowed to connect to us.
+	Validator {
+		/// Addresses of the sentry nodes. Must contain the `/p2p/` component.
+		sentry_nodes: Vec<String>,
+	},
+	/// The node is a sentry of the given validators.
+	///
+	/// We always keep a slot for the validators, and never share their addresses with the rest of
+	/// the network.
+	Sentry {
+		/// Addresses of the validators. Must contain the `/p2p/` component.
+		validators: Vec<String>,
+	},
+}
+
+impl SentryMode {
+	/// Returns the addresses of the sentry nodes if the node is a validator behind sentries.
+	pub fn sentry_nodes(&self) -> Option<&[String]> {
+		match self {
+			SentryMode::Validator { sentry_nodes } => Some(sentry_nodes),
+			SentryMode::Disabled | SentryMode::Sentry { .. } => None,
+		}
+	}
+}
+
+/// How the chain is synced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+	/// Download and import every block since genesis. This is the default.
+	Full,
+	/// Download the proofs of the authority set changes up to a recent finalized block, then the
+	/// state of that block, and sync the following blocks normally.
+	///
+	/// Only used by full nodes whose database contains only the genesis block, and if the
+	/// network is given a [`WarpSyncVerifier`]. The block import of the consensus engine must not
+	/// need the ancestry of the target block.
+	///
+	/// Peers only answer the requests of warp sync proofs if they have tracked the authority set
+	/// changes since genesis: nodes whose database predates the tracking answer with no proof.
+	Warp {
+		/// Whether to also download the blocks below the target of the warp sync once the state
+		/// is imported, from the target down to genesis.
+		backfill: bool,
+	},
+}
+
 /// The configuration of a node's secret key, describing the type of key
 /// and how it is obtained. A node's identity keypair is the result of
 /// the evaluation of the node key configuration.
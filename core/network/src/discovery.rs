@@ -33,6 +33,10 @@
 //! Additionally, the `DiscoveryBehaviour` is also capable of storing and loading value in the
 //! network-wide DHT.
 //!
+//! The addresses of the *private* nodes passed to the constructor, such as the validators we are a
+//! sentry of, are never added to the Kademlia routing table and are therefore never shared with
+//! the rest of the network.
+//!
 //! ## Usage
 //!
 //! The `DiscoveryBehaviour` generates events of type `DiscoveryOut`, most notably
@@ -61,7 +65,7 @@ use libp2p::core::{nodes::Substream, muxing::StreamMuxerBox};
 use libp2p::mdns::{Mdns, MdnsEvent};
 use libp2p::multiaddr::Protocol;
 use log::{debug, info, trace, warn};
-use std::{cmp, collections::VecDeque, time::Duration};
+use std::{cmp, collections::{HashSet, VecDeque}, time::Duration};
 use tokio_io::{AsyncRead, AsyncWrite};
 
 /// Implementation of `NetworkBehaviour` that discovers the nodes on the network.
@@ -69,6 +73,8 @@ pub struct DiscoveryBehaviour<TSubstream> {
 	/// User-defined list of nodes and their addresses. Typically includes bootstrap nodes and
 	/// reserved nodes.
 	user_defined: Vec<(PeerId, Multiaddr)>,
+	/// Nodes whose addresses must not be shared through Kademlia.
+	private_peers: HashSet<PeerId>,
 	/// Kademlia requests and answers.
 	kademlia: Kademlia<TSubstream, MemoryStore>,
 	/// Discovers nodes on the local network.
@@ -89,10 +95,12 @@ pub struct DiscoveryBehaviour<TSubstream> {
 impl<TSubstream> DiscoveryBehaviour<TSubstream> {
 	/// Builds a new `DiscoveryBehaviour`.
 	///
-	/// `user_defined` is a list of known address for nodes that never expire.
+	/// `user_defined` is a list of known address for nodes that never expire. The addresses of the
+	/// nodes in `private_peers` are never put in the Kademlia routing table.
 	pub fn new(
 		local_public_key: PublicKey,
 		user_defined: Vec<(PeerId, Multiaddr)>,
+		private_peers: HashSet<PeerId>,
 		enable_mdns: bool
 	) -> Self {
 		if enable_mdns {
@@ -104,11 +112,14 @@ impl<TSubstream> DiscoveryBehaviour<TSubstream> {
 		let store = MemoryStore::new(local_id.clone());
 		let mut kademlia = Kademlia::new(local_id.clone(), store);
 		for (peer_id, addr) in &user_defined {
-			kademlia.add_address(peer_id, addr.clone());
+			if !private_peers.contains(peer_id) {
+				kademlia.add_address(peer_id, addr.clone());
+			}
 		}
 
 		DiscoveryBehaviour {
 			user_defined,
+			private_peers,
 			kademlia,
 			next_kad_random_query: Delay::new(Duration::new(0, 0)).compat(),
 			duration_to_next_kad: Duration::from_secs(1),
@@ -152,7 +163,9 @@ impl<TSubstream> DiscoveryBehaviour<TSubstream> {
 	/// **Note**: It is important that you call this method, otherwise the discovery mechanism will
 	/// not properly work.
 	pub fn add_self_reported_address(&mut self, peer_id: &PeerId, addr: Multiaddr) {
-		self.kademlia.add_address(peer_id, addr);
+		if !self.private_peers.contains(peer_id) {
+			self.kademlia.add_address(peer_id, addr);
+		}
 	}
 
 	/// Start fetching a record from the DHT.
@@ -231,16 +244,23 @@ where
 
 	fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
 		self.num_connections += 1;
-		NetworkBehaviour::inject_connected(&mut self.kademlia, peer_id, endpoint)
+		// Kademlia would otherwise insert the address we dialed in its routing table.
+		if !self.private_peers.contains(&peer_id) {
+			NetworkBehaviour::inject_connected(&mut self.kademlia, peer_id, endpoint)
+		}
 	}
 
 	fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
 		self.num_connections -= 1;
-		NetworkBehaviour::inject_disconnected(&mut self.kademlia, peer_id, endpoint)
+		if !self.private_peers.contains(peer_id) {
+			NetworkBehaviour::inject_disconnected(&mut self.kademlia, peer_id, endpoint)
+		}
 	}
 
 	fn inject_replaced(&mut self, peer_id: PeerId, closed: ConnectedPoint, opened: ConnectedPoint) {
-		NetworkBehaviour::inject_replaced(&mut self.kademlia, peer_id, closed, opened)
+		if !self.private_peers.contains(&peer_id) {
+			NetworkBehaviour::inject_replaced(&mut self.kademlia, peer_id, closed, opened)
+		}
 	}
 
 	fn inject_node_event(
@@ -448,7 +468,7 @@ mod tests {
 					upgrade::apply(out.stream, upgrade, endpoint)
 				});
 
-			let behaviour = DiscoveryBehaviour::new(keypair.public(), user_defined.clone(), false);
+			let behaviour = DiscoveryBehaviour::new(keypair.public(), user_defined.clone(), HashSet::new(), false);
 			let mut swarm = Swarm::new(transport, behaviour, keypair.public().into_peer_id());
 			let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
 
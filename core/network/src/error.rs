@@ -28,6 +28,10 @@ pub enum Error {
 	Io(std::io::Error),
 	/// Client error
 	Client(client::error::Error),
+	/// Address of a sentry node, or of a validator we are a sentry of, that doesn't end with
+	/// `/p2p/<PeerId>`.
+	#[display(fmt="Invalid sentry-mode address: {}", _0)]
+	InvalidSentryAddress(String),
 }
 
 impl std::error::Error for Error {
@@ -35,6 +39,7 @@ impl std::error::Error for Error {
 		match self {
 			Error::Io(ref err) => Some(err),
 			Error::Client(ref err) => Some(err),
+			Error::InvalidSentryAddress(_) => None,
 		}
 	}
 }
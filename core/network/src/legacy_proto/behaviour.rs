@@ -371,6 +371,11 @@ impl<B: BlockT, TSubstream> LegacyProto<B, TSubstream> {
 		self.peerset.debug_info()
 	}
 
+	/// Returns the reputation of all the nodes known by the peerset manager.
+	pub fn peerset_reputations(&mut self) -> Vec<(PeerId, i32)> {
+		self.peerset.reputations()
+	}
+
 	/// Function that is called when the peerset wants us to connect to a node.
 	fn peerset_report_connect(&mut self, peer_id: PeerId) {
 		let mut occ_entry = match self.peers.entry(peer_id) {
@@ -68,6 +68,7 @@ fn build_nodes()
 			},
 			reserved_only: false,
 			reserved_nodes: Vec::new(),
+			reputations: Vec::new(),
 		});
 
 		let behaviour = CustomProtoWithAddr {
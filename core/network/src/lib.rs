@@ -111,6 +111,17 @@
 //! protocol negotiated is based on the *protocol ID* passed as part of the network configuration.
 //! This protocol ID should be unique for each chain and prevents nodes from different chains from
 //! connecting to each other. More information below.
+//! - We open an ephemeral substream for each block request and each light-client request sent to
+//! nodes that support it. The protocol negotiated is `/substrate/<protocol-id>/sync/1` for block
+//! and finality proof requests, and `/substrate/<protocol-id>/light/1` for light-client requests.
+//! The request and then the response are each sent as a single message prefixed with its length,
+//! after which the substream is closed. Each of these protocols has its own timeout and limits
+//! on the size of requests and responses.
+//! - We open one substream for each notifications protocol registered through
+//! `NetworkService::register_notifications_protocol`, whose name is chosen by the caller. The
+//! first message sent on the substream is a handshake, and the following ones are notifications.
+//! Each side writes only on the substreams it opened. Contrary to the Substrate substream, these
+//! substreams never keep the connection alive on their own.
 //!
 //! ## The Substrate substream
 //!
@@ -122,6 +133,10 @@
 //!
 //! For more information about the PSM, see the *substrate-peerset* crate.
 //!
+//! The reputations of the nodes known by the PSM, and the addresses of the ones whose reputation
+//! isn't negative, are regularly saved in the network configuration directory and loaded back
+//! when the node starts.
+//!
 //! Note that at the moment there is no mechanism in place to solve the issues that arise where the
 //! two sides of a connection open the unique substream simultaneously. In order to not run into
 //! issues, only the dialer of a connection is allowed to open the unique substream. When the
@@ -138,9 +153,11 @@
 //!
 //! Communications within this substream include:
 //!
-//! - Syncing. Blocks are announced and requested from other nodes.
+//! - Syncing. Blocks are announced and requested from other nodes. Nodes with a protocol version
+//! of 5 or more receive block requests on their own substreams instead, as described above.
 //! - Light-client requests. When a light client requires information, a random node we have a
-//! substream open with is chosen, and the information is requested from it.
+//! substream open with is chosen, and the information is requested from it. As for block
+//! requests, nodes with a protocol version of 5 or more receive them on their own substreams.
 //! - Gossiping. Used for example by grandpa.
 //! - Network specialization. The network protocol can be specialized through a template parameter
 //! of the network service. This specialization is free to send and receive messages with the
@@ -173,10 +190,13 @@
 mod behaviour;
 mod chain;
 mod legacy_proto;
+mod notifications;
 mod debug_info;
 mod discovery;
 mod on_demand_layer;
+mod peer_store;
 mod protocol;
+mod request_responses;
 mod service;
 mod transport;
 
@@ -265,6 +285,8 @@ pub struct NetworkStatePeer {
 	pub open: bool,
 	/// List of addresses known for this node.
 	pub known_addresses: HashSet<Multiaddr>,
+	/// Reputation of the node in the peerset manager.
+	pub reputation: i32,
 }
 
 /// Part of the `NetworkState` struct. Unstable.
@@ -277,6 +299,9 @@ pub struct NetworkStateNotConnectedPeer {
 	pub version_string: Option<String>,
 	/// Latest ping duration with this node, if we were ever connected to this node.
 	pub latest_ping_time: Option<Duration>,
+	/// Reputation of the node in the peerset manager. Persisted across restarts, alongside with
+	/// the addresses of the nodes whose reputation isn't negative.
+	pub reputation: i32,
 }
 
 /// Part of the `NetworkState` struct. Unstable.
@@ -0,0 +1,804 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Notifications protocols.
+//!
+//! The `Notifications` struct implements the `NetworkBehaviour` trait of libp2p and lets the
+//! upper layers register named protocols over which they send one-way messages, named
+//! *notifications*, to the nodes we're connected to.
+//!
+//! For each registered protocol, we open one substream with each node we're connected to. The
+//! first message written on the substream is the *handshake* passed when registering the
+//! protocol, and all the following ones are notifications. All the messages are prefixed with
+//! their length (as an unsigned varint). Substreams are one-directional: we only write on the
+//! substreams we open, and only read from the substreams opened by the remote.
+//!
+//! Each substream has its own queue of notifications waiting to be sent. If the remote doesn't
+//! read the notifications of a protocol fast enough, this queue fills up and the new notifications
+//! of this protocol are discarded, without affecting the other protocols. An `Event::Clogged` is
+//! generated when that happens, so that the upper layers can slow down.
+//!
+//! If the substream that we open fails, it is opened again after `REOPEN_DELAY`. If the remote
+//! refuses it because it doesn't support the protocol, it is opened again once the remote opens
+//! its own substream for this protocol, which it does after registering the protocol.
+//!
+//! The notifications protocols never keep a connection alive on their own.
+
+use bytes::Bytes;
+use fnv::FnvHashSet;
+use futures::prelude::*;
+use futures03::{compat::Compat, TryFutureExt as _};
+use futures_timer::Delay;
+use libp2p::core::{ConnectedPoint, Multiaddr, Negotiated, PeerId};
+use libp2p::core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeError, UpgradeInfo};
+use libp2p::swarm::{
+	IntoProtocolsHandler, KeepAlive, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+	ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
+};
+use libp2p::tokio_codec::Framed;
+use log::{debug, trace, warn};
+use smallvec::SmallVec;
+use std::{collections::VecDeque, io, iter, marker::PhantomData, time::Duration, vec};
+use tokio_io::{AsyncRead, AsyncWrite};
+use unsigned_varint::codec::UviBytes;
+
+/// Maximum size of a handshake or of a notification.
+const MAX_NOTIFICATION_SIZE: usize = 16 * 1024 * 1024;
+
+/// Maximum number of notifications waiting to be sent on a substream. Notifications written while
+/// the queue is full are discarded.
+const MAX_PENDING_NOTIFICATIONS: usize = 512;
+
+/// Time after which we open a substream again if it failed.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+
+/// Event generated by `Notifications`.
+#[derive(Debug)]
+pub enum Event {
+	/// A remote opened a substream for one of our protocols.
+	Opened {
+		/// Node that opened the substream.
+		peer: PeerId,
+		/// Name of the protocol.
+		protocol: Bytes,
+		/// Handshake that the remote sent when opening the substream.
+		handshake: Vec<u8>,
+	},
+
+	/// A substream previously reported as opened has been closed.
+	Closed {
+		/// Node that had opened the substream.
+		peer: PeerId,
+		/// Name of the protocol.
+		protocol: Bytes,
+	},
+
+	/// A remote sent us a notification.
+	Notification {
+		/// Node that sent the notification.
+		peer: PeerId,
+		/// Name of the protocol the notification was sent on.
+		protocol: Bytes,
+		/// The notification itself.
+		message: Vec<u8>,
+	},
+
+	/// Too many notifications are waiting to be sent to a remote, and the new ones are discarded
+	/// until it reads them.
+	Clogged {
+		/// Node that doesn't read the notifications fast enough.
+		peer: PeerId,
+		/// Name of the protocol.
+		protocol: Bytes,
+	},
+}
+
+/// Implementation of `NetworkBehaviour` for notifications protocols.
+pub struct Notifications<TSubstream> {
+	/// Names of the registered protocols, with the handshake to send when opening a substream.
+	protocols: Vec<(Bytes, Vec<u8>)>,
+	/// Nodes we're connected to.
+	connected: FnvHashSet<PeerId>,
+	/// Substreams opened by the remotes and that we have reported with an `Event::Opened`.
+	open: FnvHashSet<(PeerId, Bytes)>,
+	/// Actions to produce from `poll()`.
+	events: VecDeque<NetworkBehaviourAction<NotificationsHandlerIn, Event>>,
+	/// Marker to pin the generics.
+	marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> Notifications<TSubstream> {
+	/// Builds a new `Notifications` without any protocol.
+	pub fn new() -> Self {
+		Notifications {
+			protocols: Vec::new(),
+			connected: FnvHashSet::default(),
+			open: FnvHashSet::default(),
+			events: VecDeque::new(),
+			marker: PhantomData,
+		}
+	}
+
+	/// Registers the protocol named `protocol`.
+	///
+	/// A substream is opened for this protocol with all the nodes we're connected to and all the
+	/// nodes we connect to in the future, and `handshake` is sent as its first message.
+	pub fn register_protocol(&mut self, protocol: Bytes, handshake: Vec<u8>) {
+		if self.protocols.iter().any(|(name, _)| *name == protocol) {
+			warn!(target: "sub-libp2p", "Notifications protocol {:?} registered twice", protocol);
+			return
+		}
+
+		for peer_id in &self.connected {
+			self.events.push_back(NetworkBehaviourAction::SendEvent {
+				peer_id: peer_id.clone(),
+				event: NotificationsHandlerIn::Register {
+					protocol: protocol.clone(),
+					handshake: handshake.clone(),
+				},
+			});
+		}
+
+		self.protocols.push((protocol, handshake));
+	}
+
+	/// Sends a notification to `target` on the protocol named `protocol`.
+	///
+	/// The notification is discarded if we aren't connected to `target`, if `target` refused our
+	/// substream, or if too many notifications are already waiting to be sent to it.
+	pub fn write_notification(&mut self, target: &PeerId, protocol: Bytes, message: Vec<u8>) {
+		if !self.connected.contains(target) {
+			trace!(target: "sub-libp2p", "Dropping notification to disconnected {:?}", target);
+			return
+		}
+
+		self.events.push_back(NetworkBehaviourAction::SendEvent {
+			peer_id: target.clone(),
+			event: NotificationsHandlerIn::Send { protocol, message },
+		});
+	}
+
+	/// Reports all the substreams opened by `peer` as closed.
+	fn close_all(&mut self, peer: &PeerId) {
+		let closed = self.open.iter()
+			.filter(|(p, _)| p == peer)
+			.cloned()
+			.collect::<Vec<_>>();
+
+		for (peer, protocol) in closed {
+			self.open.remove(&(peer.clone(), protocol.clone()));
+			self.events.push_back(NetworkBehaviourAction::GenerateEvent(Event::Closed { peer, protocol }));
+		}
+	}
+}
+
+impl<TSubstream> NetworkBehaviour for Notifications<TSubstream>
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type ProtocolsHandler = NotificationsHandler<TSubstream>;
+	type OutEvent = Event;
+
+	fn new_handler(&mut self) -> Self::ProtocolsHandler {
+		NotificationsHandler::new(self.protocols.clone())
+	}
+
+	fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+		Vec::new()
+	}
+
+	fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+		self.connected.insert(peer_id);
+	}
+
+	fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+		self.connected.remove(peer_id);
+		self.close_all(peer_id);
+	}
+
+	fn inject_replaced(&mut self, peer_id: PeerId, _: ConnectedPoint, _: ConnectedPoint) {
+		// The substreams were handled by the handler of the closed connection. The new handler
+		// opens its own substreams.
+		self.close_all(&peer_id);
+	}
+
+	fn inject_node_event(&mut self, peer_id: PeerId, event: NotificationsHandlerOut) {
+		let event = match event {
+			NotificationsHandlerOut::Opened { protocol, handshake } => {
+				self.open.insert((peer_id.clone(), protocol.clone()));
+				Event::Opened { peer: peer_id, protocol, handshake }
+			}
+			NotificationsHandlerOut::Closed { protocol } => {
+				if !self.open.remove(&(peer_id.clone(), protocol.clone())) {
+					return
+				}
+				Event::Closed { peer: peer_id, protocol }
+			}
+			NotificationsHandlerOut::Notification { protocol, message } =>
+				Event::Notification { peer: peer_id, protocol, message },
+			NotificationsHandlerOut::Clogged { protocol } =>
+				Event::Clogged { peer: peer_id, protocol },
+		};
+
+		self.events.push_back(NetworkBehaviourAction::GenerateEvent(event));
+	}
+
+	fn poll(
+		&mut self,
+		_: &mut impl PollParameters,
+	) -> Async<
+		NetworkBehaviourAction<
+			<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent,
+			Self::OutEvent
+		>
+	> {
+		if let Some(event) = self.events.pop_front() {
+			return Async::Ready(event)
+		}
+
+		Async::NotReady
+	}
+}
+
+/// Event sent from `Notifications` to a `NotificationsHandler`.
+#[derive(Debug)]
+pub enum NotificationsHandlerIn {
+	/// A new protocol has been registered.
+	Register {
+		protocol: Bytes,
+		handshake: Vec<u8>,
+	},
+	/// Send a notification to the remote.
+	Send {
+		protocol: Bytes,
+		message: Vec<u8>,
+	},
+}
+
+/// Event sent from a `NotificationsHandler` to `Notifications`.
+#[derive(Debug)]
+pub enum NotificationsHandlerOut {
+	/// The remote opened a substream.
+	Opened {
+		protocol: Bytes,
+		handshake: Vec<u8>,
+	},
+	/// A substream opened by the remote has been closed.
+	Closed {
+		protocol: Bytes,
+	},
+	/// The remote sent a notification.
+	Notification {
+		protocol: Bytes,
+		message: Vec<u8>,
+	},
+	/// The queue of notifications to send to the remote is full.
+	Clogged {
+		protocol: Bytes,
+	},
+}
+
+/// Substream on which the messages are prefixed with their length.
+type NotificationsSubstream<TSubstream> = Framed<Negotiated<TSubstream>, UviBytes>;
+
+/// State of the substream that we open for a protocol.
+enum OutboundState<TSubstream> {
+	/// The substream is being opened.
+	Opening,
+	/// The substream is open and the handshake has been sent.
+	Open(NotificationsSubstream<TSubstream>),
+	/// The substream failed and is opened again once the delay has elapsed. Notifications are
+	/// discarded in the meantime.
+	Reopening(Compat<Delay>),
+	/// The remote doesn't support the protocol. The substream is opened again once the remote
+	/// opens its own. Notifications are discarded in the meantime.
+	Closed,
+}
+
+/// State of a protocol within a `NotificationsHandler`.
+struct ProtocolState<TSubstream> {
+	/// Name of the protocol.
+	name: Bytes,
+	/// Handshake to send when opening the substream.
+	handshake: Vec<u8>,
+	/// The substream that we open.
+	outbound: OutboundState<TSubstream>,
+	/// Notifications waiting to be written on `outbound`.
+	pending: VecDeque<Bytes>,
+	/// Whether notifications have been discarded because `pending` is full, since it last wasn't.
+	clogged: bool,
+	/// The substream opened by the remote, if any.
+	inbound: Option<NotificationsSubstream<TSubstream>>,
+}
+
+/// Implementation of `ProtocolsHandler` for notifications protocols. Handles a single connection.
+pub struct NotificationsHandler<TSubstream> {
+	/// State of each registered protocol.
+	protocols: Vec<ProtocolState<TSubstream>>,
+	/// Events to produce from `poll()`.
+	events: SmallVec<[ProtocolsHandlerEvent<NotificationsOut, Bytes, NotificationsHandlerOut>; 4]>,
+}
+
+impl<TSubstream> NotificationsHandler<TSubstream> {
+	fn new(protocols: Vec<(Bytes, Vec<u8>)>) -> Self {
+		let mut handler = NotificationsHandler {
+			protocols: Vec::with_capacity(protocols.len()),
+			events: SmallVec::new(),
+		};
+
+		for (protocol, handshake) in protocols {
+			handler.register(protocol, handshake);
+		}
+
+		handler
+	}
+
+	/// Adds a protocol and starts opening its substream.
+	fn register(&mut self, protocol: Bytes, handshake: Vec<u8>) {
+		if self.protocols.iter().any(|state| state.name == protocol) {
+			return
+		}
+
+		let state = ProtocolState {
+			name: protocol,
+			handshake,
+			outbound: OutboundState::Opening,
+			pending: VecDeque::new(),
+			clogged: false,
+			inbound: None,
+		};
+		self.events.push(open_request(&state));
+		self.protocols.push(state);
+	}
+}
+
+/// Builds the request to open the substream of `state`.
+fn open_request<TSubstream>(
+	state: &ProtocolState<TSubstream>,
+) -> ProtocolsHandlerEvent<NotificationsOut, Bytes, NotificationsHandlerOut> {
+	ProtocolsHandlerEvent::OutboundSubstreamRequest {
+		protocol: SubstreamProtocol::new(NotificationsOut {
+			protocol: state.name.clone(),
+			handshake: state.handshake.clone(),
+		}),
+		info: state.name.clone(),
+	}
+}
+
+impl<TSubstream> ProtocolsHandler for NotificationsHandler<TSubstream>
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type InEvent = NotificationsHandlerIn;
+	type OutEvent = NotificationsHandlerOut;
+	type Error = void::Void;
+	type Substream = TSubstream;
+	type InboundProtocol = NotificationsIn;
+	type OutboundProtocol = NotificationsOut;
+	type OutboundOpenInfo = Bytes;
+
+	fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+		SubstreamProtocol::new(NotificationsIn {
+			protocols: self.protocols.iter().map(|state| state.name.clone()).collect(),
+		})
+	}
+
+	fn inject_fully_negotiated_inbound(&mut self, opened: InboundSubstream<TSubstream>) {
+		let state = match self.protocols.iter_mut().find(|state| state.name == opened.protocol) {
+			Some(state) => state,
+			None => return,
+		};
+
+		// The remote supports the protocol now.
+		if let OutboundState::Closed = state.outbound {
+			state.outbound = OutboundState::Opening;
+			self.events.push(open_request(state));
+		}
+
+		// A remote opening a second substream for the same protocol replaces the first one.
+		if state.inbound.replace(opened.substream).is_some() {
+			self.events.push(ProtocolsHandlerEvent::Custom(NotificationsHandlerOut::Closed {
+				protocol: opened.protocol.clone(),
+			}));
+		}
+
+		self.events.push(ProtocolsHandlerEvent::Custom(NotificationsHandlerOut::Opened {
+			protocol: opened.protocol,
+			handshake: opened.handshake,
+		}));
+	}
+
+	fn inject_fully_negotiated_outbound(
+		&mut self,
+		substream: NotificationsSubstream<TSubstream>,
+		protocol: Bytes,
+	) {
+		if let Some(state) = self.protocols.iter_mut().find(|state| state.name == protocol) {
+			state.outbound = OutboundState::Open(substream);
+		}
+	}
+
+	fn inject_event(&mut self, event: NotificationsHandlerIn) {
+		match event {
+			NotificationsHandlerIn::Register { protocol, handshake } => self.register(protocol, handshake),
+			NotificationsHandlerIn::Send { protocol, message } => {
+				let state = match self.protocols.iter_mut().find(|state| state.name == protocol) {
+					Some(state) => state,
+					None => {
+						debug!(target: "sub-libp2p", "Notification on unregistered protocol {:?}", protocol);
+						return
+					}
+				};
+
+				match state.outbound {
+					OutboundState::Closed | OutboundState::Reopening(_) => {
+						trace!(target: "sub-libp2p", "Dropping notification on closed substream {:?}", protocol);
+					}
+					_ if state.pending.len() >= MAX_PENDING_NOTIFICATIONS => {
+						debug!(target: "sub-libp2p", "Too many pending notifications on {:?}, dropping", protocol);
+						if !state.clogged {
+							state.clogged = true;
+							self.events.push(ProtocolsHandlerEvent::Custom(NotificationsHandlerOut::Clogged {
+								protocol,
+							}));
+						}
+					}
+					_ => {
+						state.clogged = false;
+						state.pending.push_back(Bytes::from(message));
+					}
+				}
+			}
+		}
+	}
+
+	fn inject_dial_upgrade_error(&mut self, protocol: Bytes, err: ProtocolsHandlerUpgrErr<io::Error>) {
+		debug!(target: "sub-libp2p", "Failed to open notifications substream {:?}: {:?}", protocol, err);
+		if let Some(state) = self.protocols.iter_mut().find(|state| state.name == protocol) {
+			state.outbound = match err {
+				ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => OutboundState::Closed,
+				_ => OutboundState::Reopening(Delay::new(REOPEN_DELAY).compat()),
+			};
+			state.pending.clear();
+		}
+	}
+
+	fn connection_keep_alive(&self) -> KeepAlive {
+		KeepAlive::No
+	}
+
+	fn poll(
+		&mut self,
+	) -> Poll<
+		ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
+		Self::Error,
+	> {
+		if !self.events.is_empty() {
+			return Ok(Async::Ready(self.events.remove(0)))
+		}
+
+		for state in self.protocols.iter_mut() {
+			let failed = match &mut state.outbound {
+				OutboundState::Open(substream) => match send_pending(substream, &mut state.pending) {
+					Ok(()) => false,
+					Err(err) => {
+						debug!(target: "sub-libp2p", "Error on notifications substream {:?}: {}",
+							state.name, err);
+						true
+					}
+				},
+				OutboundState::Reopening(delay) => match delay.poll() {
+					Ok(Async::NotReady) => false,
+					Ok(Async::Ready(())) | Err(_) => {
+						state.outbound = OutboundState::Opening;
+						return Ok(Async::Ready(open_request(state)))
+					}
+				},
+				OutboundState::Opening | OutboundState::Closed => false,
+			};
+			if failed {
+				state.outbound = OutboundState::Reopening(Delay::new(REOPEN_DELAY).compat());
+				state.pending.clear();
+			}
+
+			let closed = if let Some(substream) = &mut state.inbound {
+				match substream.poll() {
+					Ok(Async::Ready(Some(message))) => {
+						return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(
+							NotificationsHandlerOut::Notification {
+								protocol: state.name.clone(),
+								message: message.to_vec(),
+							}
+						)))
+					}
+					Ok(Async::NotReady) => false,
+					Ok(Async::Ready(None)) => true,
+					Err(err) => {
+						debug!(target: "sub-libp2p", "Error on notifications substream {:?}: {}",
+							state.name, err);
+						true
+					}
+				}
+			} else {
+				false
+			};
+			if closed {
+				state.inbound = None;
+				return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(NotificationsHandlerOut::Closed {
+					protocol: state.name.clone(),
+				})))
+			}
+		}
+
+		Ok(Async::NotReady)
+	}
+}
+
+/// Writes as many of the `pending` notifications as possible on `substream`, then flushes it.
+fn send_pending<TSubstream>(
+	substream: &mut NotificationsSubstream<TSubstream>,
+	pending: &mut VecDeque<Bytes>,
+) -> Result<(), io::Error>
+where
+	TSubstream: AsyncRead + AsyncWrite,
+{
+	while let Some(message) = pending.pop_front() {
+		if let AsyncSink::NotReady(message) = substream.start_send(message)? {
+			pending.push_front(message);
+			break
+		}
+	}
+
+	substream.poll_complete()?;
+	Ok(())
+}
+
+/// Builds the codec of the notifications substreams.
+fn codec() -> UviBytes {
+	let mut codec = UviBytes::default();
+	codec.set_max_len(MAX_NOTIFICATION_SIZE);
+	codec
+}
+
+/// Substream opened by a remote, whose handshake has been read.
+pub struct InboundSubstream<TSubstream> {
+	/// Name of the protocol.
+	protocol: Bytes,
+	/// Handshake sent by the remote.
+	handshake: Vec<u8>,
+	/// Substream to read the notifications from.
+	substream: NotificationsSubstream<TSubstream>,
+}
+
+/// Upgrade that accepts a substream on any of our protocols and reads its handshake.
+#[derive(Debug, Clone)]
+pub struct NotificationsIn {
+	protocols: Vec<Bytes>,
+}
+
+impl UpgradeInfo for NotificationsIn {
+	type Info = Bytes;
+	type InfoIter = vec::IntoIter<Bytes>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		self.protocols.clone().into_iter()
+	}
+}
+
+impl<TSubstream> InboundUpgrade<TSubstream> for NotificationsIn
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type Output = InboundSubstream<TSubstream>;
+	type Error = io::Error;
+	type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+	fn upgrade_inbound(self, substream: Negotiated<TSubstream>, protocol: Bytes) -> Self::Future {
+		let future = Framed::new(substream, codec())
+			.into_future()
+			.map_err(|(err, _)| err)
+			.and_then(move |(handshake, substream)| match handshake {
+				Some(handshake) => Ok(InboundSubstream {
+					protocol,
+					handshake: handshake.to_vec(),
+					substream,
+				}),
+				None => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+			});
+
+		Box::new(future)
+	}
+}
+
+/// Upgrade that opens a substream and sends the handshake.
+#[derive(Debug, Clone)]
+pub struct NotificationsOut {
+	/// Name of the protocol.
+	protocol: Bytes,
+	/// Handshake to send.
+	handshake: Vec<u8>,
+}
+
+impl UpgradeInfo for NotificationsOut {
+	type Info = Bytes;
+	type InfoIter = iter::Once<Bytes>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		iter::once(self.protocol.clone())
+	}
+}
+
+impl<TSubstream> OutboundUpgrade<TSubstream> for NotificationsOut
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type Output = NotificationsSubstream<TSubstream>;
+	type Error = io::Error;
+	type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+	fn upgrade_outbound(self, substream: Negotiated<TSubstream>, _: Bytes) -> Self::Future {
+		Box::new(Framed::new(substream, codec()).send(Bytes::from(self.handshake)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use futures::prelude::*;
+	use libp2p::identity::Keypair;
+	use libp2p::Multiaddr;
+	use libp2p::core::upgrade;
+	use libp2p::core::transport::{Transport, MemoryTransport};
+	use libp2p::core::upgrade::{InboundUpgradeExt, OutboundUpgradeExt};
+	use libp2p::swarm::Swarm;
+	use super::{Event, Notifications};
+
+	#[test]
+	fn notifications_are_received() {
+		const PROTOCOL: &[u8] = b"/test/notifications/1";
+		let mut first_addr = None;
+
+		let mut swarms = (0..2u8).map(|n| {
+			let keypair = Keypair::generate_ed25519();
+
+			let transport = MemoryTransport
+				.with_upgrade(libp2p::secio::SecioConfig::new(keypair.clone()))
+				.and_then(move |out, endpoint| {
+					let peer_id = out.remote_key.into_peer_id();
+					let peer_id2 = peer_id.clone();
+					let upgrade = libp2p::yamux::Config::default()
+						.map_inbound(move |muxer| (peer_id, muxer))
+						.map_outbound(move |muxer| (peer_id2, muxer));
+					upgrade::apply(out.stream, upgrade, endpoint)
+				});
+
+			let mut behaviour = Notifications::new();
+			behaviour.register_protocol(Bytes::from_static(PROTOCOL), vec![n]);
+			let mut swarm = Swarm::new(transport, behaviour, keypair.public().into_peer_id());
+			let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+			Swarm::listen_on(&mut swarm, listen_addr.clone()).unwrap();
+
+			match first_addr {
+				None => first_addr = Some(listen_addr),
+				Some(ref addr) => Swarm::dial_addr(&mut swarm, addr.clone()).unwrap(),
+			}
+			swarm
+		}).collect::<Vec<_>>();
+
+		let target = Swarm::local_peer_id(&swarms[0]).clone();
+		let mut sent = false;
+		let mut handshake = None;
+
+		let fut = futures::future::poll_fn::<_, (), _>(move || {
+			loop {
+				// Only write the notification once the connection is established.
+				if !sent && swarms[1].connected.contains(&target) {
+					swarms[1].write_notification(&target, Bytes::from_static(PROTOCOL), vec![1, 2, 3]);
+					sent = true;
+				}
+
+				match swarms[1].poll().unwrap() {
+					Async::Ready(_) => {}
+					Async::NotReady => break,
+				}
+			}
+
+			loop {
+				match swarms[0].poll().unwrap() {
+					Async::Ready(Some(Event::Opened { handshake: h, .. })) => handshake = Some(h),
+					Async::Ready(Some(Event::Notification { message, .. })) =>
+						return Ok(Async::Ready((handshake.take(), message))),
+					Async::Ready(_) => {}
+					Async::NotReady => return Ok(Async::NotReady),
+				}
+			}
+		});
+
+		let (handshake, message) = tokio::runtime::Runtime::new().unwrap().block_on(fut).unwrap();
+		assert_eq!(handshake, Some(vec![1]));
+		assert_eq!(message, vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn substream_is_reopened_when_remote_registers_protocol() {
+		const PROTOCOL: &[u8] = b"/test/notifications/1";
+		let mut first_addr = None;
+
+		// Only the first node supports the protocol when they connect.
+		let mut swarms = (0..2u8).map(|n| {
+			let keypair = Keypair::generate_ed25519();
+
+			let transport = MemoryTransport
+				.with_upgrade(libp2p::secio::SecioConfig::new(keypair.clone()))
+				.and_then(move |out, endpoint| {
+					let peer_id = out.remote_key.into_peer_id();
+					let peer_id2 = peer_id.clone();
+					let upgrade = libp2p::yamux::Config::default()
+						.map_inbound(move |muxer| (peer_id, muxer))
+						.map_outbound(move |muxer| (peer_id2, muxer));
+					upgrade::apply(out.stream, upgrade, endpoint)
+				});
+
+			let mut behaviour = Notifications::new();
+			if n == 0 {
+				behaviour.register_protocol(Bytes::from_static(PROTOCOL), vec![n]);
+			}
+			let mut swarm = Swarm::new(transport, behaviour, keypair.public().into_peer_id());
+			let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+			Swarm::listen_on(&mut swarm, listen_addr.clone()).unwrap();
+
+			match first_addr {
+				None => first_addr = Some(listen_addr),
+				Some(ref addr) => Swarm::dial_addr(&mut swarm, addr.clone()).unwrap(),
+			}
+			swarm
+		}).collect::<Vec<_>>();
+
+		let first = Swarm::local_peer_id(&swarms[0]).clone();
+		let second = Swarm::local_peer_id(&swarms[1]).clone();
+		let mut registered = false;
+
+		let fut = futures::future::poll_fn::<_, (), _>(move || {
+			loop {
+				// The second node registers the protocol once connected, and opens its substream.
+				if !registered && swarms[1].connected.contains(&first) {
+					swarms[1].register_protocol(Bytes::from_static(PROTOCOL), vec![1]);
+					registered = true;
+				}
+
+				match swarms[1].poll().unwrap() {
+					Async::Ready(Some(Event::Notification { message, .. })) =>
+						return Ok(Async::Ready(message)),
+					Async::Ready(_) => {}
+					Async::NotReady => break,
+				}
+			}
+
+			loop {
+				match swarms[0].poll().unwrap() {
+					// The first node opens its substream again, and the notification waits for it.
+					Async::Ready(Some(Event::Opened { .. })) =>
+						swarms[0].write_notification(&second, Bytes::from_static(PROTOCOL), vec![4, 5, 6]),
+					Async::Ready(_) => {}
+					Async::NotReady => return Ok(Async::NotReady),
+				}
+			}
+		});
+
+		let message = tokio::runtime::Runtime::new().unwrap().block_on(fut).unwrap();
+		assert_eq!(message, vec![4, 5, 6]);
+	}
+}
@@ -25,7 +25,7 @@ use parking_lot::Mutex;
 use client::error::Error as ClientError;
 use client::light::fetcher::{Fetcher, FetchChecker, RemoteHeaderRequest,
 	RemoteCallRequest, RemoteReadRequest, RemoteChangesRequest,
-	RemoteReadChildRequest, RemoteBodyRequest};
+	RemoteReadChildRequest, RemoteReadRangeRequest, RemoteBodyRequest};
 use sr_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor};
 
 /// Implements the `Fetcher` trait of the client. Makes it possible for the light client to perform
@@ -86,6 +86,7 @@ impl<B> Fetcher<B> for OnDemand<B> where
 {
 	type RemoteHeaderResult = Compat01As03<RemoteResponse<B::Header>>;
 	type RemoteReadResult = Compat01As03<RemoteResponse<HashMap<Vec<u8>, Option<Vec<u8>>>>>;
+	type RemoteReadRangeResult = Compat01As03<RemoteResponse<Vec<(Vec<u8>, Vec<u8>)>>>;
 	type RemoteCallResult = Compat01As03<RemoteResponse<Vec<u8>>>;
 	type RemoteChangesResult = Compat01As03<RemoteResponse<Vec<(NumberFor<B>, u32)>>>;
 	type RemoteBodyResult = Compat01As03<RemoteResponse<Vec<B::Extrinsic>>>;
@@ -111,6 +112,15 @@ impl<B> Fetcher<B> for OnDemand<B> where
 		RemoteResponse { receiver }.compat()
 	}
 
+	fn remote_read_range(
+		&self,
+		request: RemoteReadRangeRequest<B::Header>
+	) -> Self::RemoteReadRangeResult {
+		let (sender, receiver) = oneshot::channel();
+		let _ = self.requests_send.unbounded_send(RequestData::RemoteReadRange(request, sender));
+		RemoteResponse { receiver }.compat()
+	}
+
 	fn remote_call(&self, request: RemoteCallRequest<B::Header>) -> Self::RemoteCallResult {
 		let (sender, receiver) = oneshot::channel();
 		let _ = self.requests_send.unbounded_send(RequestData::RemoteCall(request, sender));
@@ -0,0 +1,178 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistence of the nodes we know about across restarts.
+//!
+//! The reputations of the nodes known by the peerset manager, and the addresses of the nodes
+//! whose reputation isn't negative, are saved as JSON in the `peers.json` file of the network
+//! configuration directory. When loading them back, the reputations are decayed according to the
+//! time during which we were offline, but much more slowly than while running: a node that we
+//! banned shouldn't be forgiven by merely restarting.
+
+use libp2p::{Multiaddr, PeerId};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{cmp, fs, io, path::Path, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// Name of the file, within the network configuration directory, where the nodes are saved.
+const FILE_NAME: &str = "peers.json";
+/// Maximum number of nodes whose addresses are saved. The ones with the highest reputation are
+/// kept.
+const MAX_NODES_WITH_ADDRESSES: usize = 1000;
+/// While offline, reputations decay as if only one second had passed every that many seconds.
+/// The reputation of a banned node thus takes about an hour offline to get over the ban, and
+/// about three hours to halve.
+const OFFLINE_DECAY_SLOWDOWN: u32 = 300;
+
+/// A node that we know about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownNode {
+	/// Identity of the node.
+	pub peer_id: PeerId,
+	/// Reputation of the node in the peerset manager.
+	pub reputation: i32,
+	/// Addresses of the node. Empty for the nodes whose reputation is negative.
+	pub addresses: Vec<Multiaddr>,
+}
+
+/// Content of the file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredNodes {
+	/// Time at which the file was written, in seconds since the UNIX epoch.
+	saved_at: u64,
+	/// The nodes themselves.
+	nodes: Vec<StoredNode>,
+}
+
+/// Node as written in the file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredNode {
+	/// Identity of the node, in base58.
+	peer_id: String,
+	reputation: i32,
+	addresses: Vec<Multiaddr>,
+}
+
+/// Loads the nodes saved in `dir` by `save`.
+///
+/// Returns an empty list if nothing has been saved yet, or if the file is corrupted.
+pub fn load(dir: &Path) -> Vec<KnownNode> {
+	let stored = match fs::read(dir.join(FILE_NAME)) {
+		Ok(content) => match serde_json::from_slice::<StoredNodes>(&content) {
+			Ok(stored) => stored,
+			Err(err) => {
+				warn!(target: "sub-libp2p", "Ignoring corrupted list of known nodes: {}", err);
+				return Vec::new()
+			}
+		},
+		Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Vec::new(),
+		Err(err) => {
+			warn!(target: "sub-libp2p", "Failed to read the list of known nodes: {}", err);
+			return Vec::new()
+		}
+	};
+
+	let offline = Duration::from_secs(unix_time().saturating_sub(stored.saved_at));
+	stored.nodes.into_iter().filter_map(|node| {
+		let peer_id = node.peer_id.parse::<PeerId>().ok()?;
+		Some(KnownNode {
+			peer_id,
+			reputation: peerset::decay_reputation(node.reputation, offline / OFFLINE_DECAY_SLOWDOWN),
+			addresses: if node.reputation >= 0 { node.addresses } else { Vec::new() },
+		})
+	}).collect()
+}
+
+/// Saves `nodes` in `dir`, replacing what was saved before.
+///
+/// Only the addresses of the `MAX_NODES_WITH_ADDRESSES` nodes with the highest reputation are
+/// saved, and the nodes with neither a reputation nor an address are skipped.
+pub fn save(dir: &Path, mut nodes: Vec<KnownNode>) -> io::Result<()> {
+	nodes.sort_by_key(|node| cmp::Reverse(node.reputation));
+
+	let nodes = nodes.into_iter().enumerate().filter_map(|(n, node)| {
+		let addresses = if node.reputation >= 0 && n < MAX_NODES_WITH_ADDRESSES {
+			node.addresses
+		} else {
+			Vec::new()
+		};
+		if node.reputation == 0 && addresses.is_empty() {
+			return None
+		}
+		Some(StoredNode { peer_id: node.peer_id.to_base58(), reputation: node.reputation, addresses })
+	}).collect();
+
+	let content = serde_json::to_vec(&StoredNodes { saved_at: unix_time(), nodes })
+		.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+	// Write to a temporary file first, so that we never leave a truncated file behind.
+	fs::create_dir_all(dir)?;
+	let tmp_path = dir.join(format!("{}.tmp", FILE_NAME));
+	fs::write(&tmp_path, content)?;
+	fs::rename(tmp_path, dir.join(FILE_NAME))
+}
+
+/// Returns the number of seconds since the UNIX epoch.
+fn unix_time() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::{Multiaddr, PeerId};
+	use std::fs;
+	use super::{FILE_NAME, KnownNode, StoredNode, StoredNodes, load, save, unix_time};
+
+	#[test]
+	fn nodes_are_saved_and_loaded() {
+		let dir = tempdir::TempDir::new("peer-store").unwrap();
+		let address: Multiaddr = "/ip4/127.0.0.1/tcp/30333".parse().unwrap();
+		let good = KnownNode { peer_id: PeerId::random(), reputation: 0, addresses: vec![address.clone()] };
+		let bad = KnownNode { peer_id: PeerId::random(), reputation: -1000, addresses: vec![address] };
+		let unknown = KnownNode { peer_id: PeerId::random(), reputation: 0, addresses: Vec::new() };
+
+		assert!(load(dir.path()).is_empty());
+		save(dir.path(), vec![bad.clone(), good.clone(), unknown]).unwrap();
+
+		// The addresses of nodes with a bad reputation are forgotten.
+		let loaded = load(dir.path());
+		assert_eq!(loaded.len(), 2);
+		assert_eq!(loaded[0], good);
+		assert_eq!(loaded[1].peer_id, bad.peer_id);
+		assert!(loaded[1].reputation < 0);
+		assert!(loaded[1].addresses.is_empty());
+	}
+
+	#[test]
+	fn reputations_decay_slowly_while_offline() {
+		let dir = tempdir::TempDir::new("peer-store").unwrap();
+		let peer_id = PeerId::random();
+		let stored = StoredNodes {
+			saved_at: unix_time() - 30 * 60,
+			nodes: vec![StoredNode { peer_id: peer_id.to_base58(), reputation: i32::min_value(), addresses: Vec::new() }],
+		};
+		fs::write(dir.path().join(FILE_NAME), serde_json::to_vec(&stored).unwrap()).unwrap();
+
+		// Half an hour of decay while running would bring any reputation back to zero, but a node
+		// banned before a restart must still be banned.
+		let loaded = load(dir.path());
+		assert_eq!(loaded.len(), 1);
+		assert_eq!(loaded[0].peer_id, peer_id);
+		assert!(loaded[0].reputation < i32::min_value() / 10 * 8);
+	}
+}
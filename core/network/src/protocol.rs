@@ -22,7 +22,7 @@ use libp2p::{Multiaddr, PeerId};
 use libp2p::core::{ConnectedPoint, nodes::Substream, muxing::StreamMuxerBox};
 use libp2p::swarm::{ProtocolsHandler, IntoProtocolsHandler};
 use libp2p::swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
-use primitives::storage::StorageKey;
+use primitives::{Blake2Hasher, storage::StorageKey};
 use consensus::{
 	BlockOrigin,
 	block_validation::BlockAnnounceValidator,
@@ -41,14 +41,15 @@ use light_dispatch::{LightDispatch, LightDispatchNetwork, RequestData};
 use specialization::NetworkSpecialization;
 use sync::{ChainSync, SyncState};
 use crate::service::{TransactionPool, ExHashT};
-use crate::config::{BoxFinalityProofRequestBuilder, Roles};
+use crate::config::{BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier, Roles, SyncMode};
 use rustc_hex::ToHex;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::{cmp, num::NonZeroUsize, time};
 use log::{trace, debug, warn, error};
 use crate::chain::{Client, FinalityProofProvider};
-use client::light::fetcher::{FetchChecker, ChangesProof};
+use client::light::fetcher::{FetchChecker, ChangesProof, decode_compact_proof};
+use codec::{Decode, Encode};
 use crate::error;
 use util::LruHashSet;
 
@@ -67,12 +68,26 @@ const TICK_TIMEOUT: time::Duration = time::Duration::from_millis(1100);
 const PROPAGATE_TIMEOUT: time::Duration = time::Duration::from_millis(2900);
 
 /// Current protocol version.
-pub(crate) const CURRENT_VERSION: u32 = 4;
+pub(crate) const CURRENT_VERSION: u32 = 9;
 /// Lowest version we support
 pub(crate) const MIN_VERSION: u32 = 3;
+/// Lowest version whose nodes receive our requests on the request-response protocols rather than
+/// on the legacy substream.
+const REQUEST_RESPONSES_VERSION: u32 = 5;
+/// Lowest version whose nodes answer warp sync proof and state requests, with state responses
+/// that are proven against the state root of the block.
+pub(crate) const WARP_SYNC_VERSION: u32 = 9;
+/// Lowest version whose nodes exchange read and execution proofs in the compact encoding.
+const COMPACT_PROOF_VERSION: u32 = 7;
+/// Lowest version whose nodes answer remote read range requests.
+pub(crate) const READ_RANGE_VERSION: u32 = 8;
 
 // Maximum allowed entries in `BlockResponse`
 const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
+/// Maximum size of a response received on a request-response protocol.
+pub(crate) const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+/// Maximum size of the keys and values in a `StateResponse`.
+const MAX_STATE_RESPONSE_SIZE: usize = 2 * 1024 * 1024;
 /// When light node connects to the full node and the full node is behind light node
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it unuseful
 /// and disconnect to free connection slot.
@@ -91,6 +106,9 @@ const PEER_BEHIND_US_LIGHT_REPUTATION_CHANGE: i32 = -(1 << 8);
 const NEW_EXTRINSIC_REPUTATION_CHANGE: i32 = 1 << 7;
 /// We sent an RPC query to the given node, but it failed.
 const RPC_FAILED_REPUTATION_CHANGE: i32 = -(1 << 12);
+/// Reputation change when a peer sends us a request or a response that we can't decode or that
+/// doesn't belong to the request-response protocol it was sent on.
+const BAD_REQUEST_REPUTATION_CHANGE: i32 = -(1 << 12);
 
 // Lock must always be taken in order declared here.
 pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
@@ -113,6 +131,8 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	transaction_pool: Arc<dyn TransactionPool<H, B>>,
 	/// When asked for a proof of finality, we use this struct to build one.
 	finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
+	/// Requests to send on the request-response protocols.
+	pending_requests: PendingRequests,
 	/// Handles opening the unique substream and sending and receiving raw messages.
 	behaviour: LegacyProto<B, Substream<StreamMuxerBox>>,
 }
@@ -150,10 +170,78 @@ pub struct PeerInfo<B: BlockT> {
 	pub best_hash: B::Hash,
 	/// Peer best block number
 	pub best_number: <B::Header as HeaderT>::Number,
+	/// Number of finalized blocks whose bodies the peer keeps, or `None` if it keeps all of them.
+	pub keep_block_bodies: Option<u32>,
+}
+
+/// Request-response protocol that requests are sent on, for the peers that support them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestProtocol {
+	/// Block and finality proof requests.
+	Sync,
+	/// Light client requests.
+	Light,
+}
+
+impl RequestProtocol {
+	/// Returns the protocol that `message` is sent on, or `None` if it isn't a request.
+	fn of_request<B: BlockT>(message: &Message<B>) -> Option<Self> {
+		match message {
+			GenericMessage::BlockRequest(_) | GenericMessage::FinalityProofRequest(_) |
+			GenericMessage::WarpSyncRequest(_) | GenericMessage::StateRequest(_) =>
+				Some(RequestProtocol::Sync),
+			GenericMessage::RemoteCallRequest(_) | GenericMessage::RemoteReadRequest(_) |
+			GenericMessage::RemoteReadChildRequest(_) | GenericMessage::RemoteReadRangeRequest(_) |
+			GenericMessage::RemoteHeaderRequest(_) | GenericMessage::RemoteChangesRequest(_) =>
+				Some(RequestProtocol::Light),
+			_ => None,
+		}
+	}
+
+	/// Returns true if `message` is a response to a request sent on this protocol.
+	fn is_response<B: BlockT>(&self, message: &Message<B>) -> bool {
+		match (self, message) {
+			(RequestProtocol::Sync, GenericMessage::BlockResponse(_)) |
+			(RequestProtocol::Sync, GenericMessage::FinalityProofResponse(_)) |
+			(RequestProtocol::Sync, GenericMessage::WarpSyncResponse(_)) |
+			(RequestProtocol::Sync, GenericMessage::StateResponse(_)) |
+			(RequestProtocol::Light, GenericMessage::RemoteCallResponse(_)) |
+			(RequestProtocol::Light, GenericMessage::RemoteReadResponse(_)) |
+			(RequestProtocol::Light, GenericMessage::RemoteHeaderResponse(_)) |
+			(RequestProtocol::Light, GenericMessage::RemoteChangesResponse(_)) => true,
+			_ => false,
+		}
+	}
+}
+
+/// Requests waiting to be sent on the request-response protocols.
+struct PendingRequests {
+	/// Peers that receive our requests on the request-response protocols.
+	peers: HashSet<PeerId>,
+	/// Encoded requests, with the peer and the protocol to send them to.
+	queue: VecDeque<(PeerId, RequestProtocol, Vec<u8>)>,
+}
+
+impl PendingRequests {
+	/// Sends `message` to `who`, either on a request-response protocol if `message` is a request
+	/// and `who` supports them, or on the legacy substream.
+	fn send<B: BlockT>(
+		&mut self,
+		behaviour: &mut LegacyProto<B, Substream<StreamMuxerBox>>,
+		who: &PeerId,
+		message: Message<B>,
+	) {
+		match RequestProtocol::of_request(&message) {
+			Some(protocol) if self.peers.contains(who) =>
+				self.queue.push_back((who.clone(), protocol, message.encode())),
+			_ => behaviour.send_packet(who, message),
+		}
+	}
 }
 
 struct LightDispatchIn<'a, B: BlockT> {
 	behaviour: &'a mut LegacyProto<B, Substream<StreamMuxerBox>>,
+	requests: &'a mut PendingRequests,
 	peerset: peerset::PeersetHandle,
 }
 
@@ -172,7 +260,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a, B> {
 			block,
 		});
 
-		self.behaviour.send_packet(who, message)
+		self.requests.send(self.behaviour, who, message)
 	}
 
 	fn send_read_request(
@@ -188,7 +276,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a, B> {
 			keys,
 		});
 
-		self.behaviour.send_packet(who, message)
+		self.requests.send(self.behaviour, who, message)
 	}
 
 	fn send_read_child_request(
@@ -206,7 +294,27 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a, B> {
 			keys,
 		});
 
-		self.behaviour.send_packet(who, message)
+		self.requests.send(self.behaviour, who, message)
+	}
+
+	fn send_read_range_request(
+		&mut self,
+		who: &PeerId,
+		id: RequestId,
+		block: <B as BlockT>::Hash,
+		prefix: Vec<u8>,
+		start_key: Option<Vec<u8>>,
+		count: Option<u32>,
+	) {
+		let message = message::generic::Message::RemoteReadRangeRequest(message::RemoteReadRangeRequest {
+			id,
+			block,
+			prefix,
+			start_key,
+			count,
+		});
+
+		self.requests.send(self.behaviour, who, message)
 	}
 
 	fn send_call_request(
@@ -224,7 +332,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a, B> {
 			data,
 		});
 
-		self.behaviour.send_packet(who, message)
+		self.requests.send(self.behaviour, who, message)
 	}
 
 	fn send_changes_request(
@@ -248,7 +356,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a, B> {
 			key,
 		});
 
-		self.behaviour.send_packet(who, message)
+		self.requests.send(self.behaviour, who, message)
 	}
 
 	fn send_body_request(
@@ -270,7 +378,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a, B> {
 			max,
 		});
 
-		self.behaviour.send_packet(who, message)
+		self.requests.send(self.behaviour, who, message)
 	}
 }
 
@@ -317,21 +425,11 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 	}
 
 	fn send_consensus(&mut self, who: PeerId, consensus: ConsensusMessage) {
-		send_message(
-			self.behaviour,
-			&mut self.context_data.peers,
-			who,
-			GenericMessage::Consensus(consensus)
-		)
+		self.behaviour.send_packet(&who, GenericMessage::Consensus(consensus))
 	}
 
 	fn send_chain_specific(&mut self, who: PeerId, message: Vec<u8>) {
-		send_message(
-			self.behaviour,
-			&mut self.context_data.peers,
-			who,
-			GenericMessage::ChainSpecific(message)
-		)
+		self.behaviour.send_packet(&who, GenericMessage::ChainSpecific(message))
 	}
 }
 
@@ -347,12 +445,22 @@ struct ContextData<B: BlockT, H: ExHashT> {
 pub struct ProtocolConfig {
 	/// Assigned roles.
 	pub roles: Roles,
+	/// Number of finalized blocks whose bodies are kept, or `None` if all of them are.
+	pub keep_block_bodies: Option<u32>,
+	/// How the chain is synced.
+	pub sync_mode: SyncMode,
+	/// Validators we are a sentry of. Their gossip messages are relayed to our other peers as
+	/// soon as they are received.
+	pub sentry_validators: HashSet<PeerId>,
 }
 
 impl Default for ProtocolConfig {
 	fn default() -> ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
+			keep_block_bodies: None,
+			sync_mode: SyncMode::Full,
+			sentry_validators: HashSet::new(),
 		}
 	}
 }
@@ -367,6 +475,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		transaction_pool: Arc<dyn TransactionPool<H, B>>,
 		finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
 		finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
+		warp_sync_verifier: Option<BoxWarpSyncVerifier<B>>,
 		protocol_id: ProtocolId,
 		peerset_config: peerset::PeersetConfig,
 		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>
@@ -377,12 +486,17 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			chain.clone(),
 			&info,
 			finality_proof_request_builder,
+			config.sync_mode.clone(),
+			warp_sync_verifier,
 			block_announce_validator,
 		);
 		let (peerset, peerset_handle) = peerset::Peerset::from_config(peerset_config);
 		let versions = &((MIN_VERSION as u8)..=(CURRENT_VERSION as u8)).collect::<Vec<u8>>();
 		let behaviour = LegacyProto::new(protocol_id, versions, peerset);
 
+		let mut consensus_gossip = ConsensusGossip::new();
+		consensus_gossip.set_relayed_peers(config.sentry_validators.clone());
+
 		let protocol = Protocol {
 			tick_timeout: Box::new(futures_timer::Interval::new(TICK_TIMEOUT).map(|v| Ok::<_, ()>(v)).compat()),
 			propagate_timeout: Box::new(futures_timer::Interval::new(PROPAGATE_TIMEOUT).map(|v| Ok::<_, ()>(v)).compat()),
@@ -395,11 +509,15 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			genesis_hash: info.chain.genesis_hash,
 			sync,
 			specialization,
-			consensus_gossip: ConsensusGossip::new(),
+			consensus_gossip,
 			handshaking_peers: HashMap::new(),
 			transaction_pool,
 			finality_proof_provider,
 			peerset_handle: peerset_handle.clone(),
+			pending_requests: PendingRequests {
+				peers: HashSet::new(),
+				queue: VecDeque::new(),
+			},
 			behaviour,
 		};
 
@@ -431,6 +549,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.behaviour.peerset_debug_info()
 	}
 
+	/// Returns the reputation of all the nodes known by the peerset manager.
+	pub fn peerset_reputations(&mut self) -> Vec<(PeerId, i32)> {
+		self.behaviour.peerset_reputations()
+	}
+
 	/// Returns the number of peers we're connected to.
 	pub fn num_connected_peers(&self) -> usize {
 		self.context_data.peers.values().count()
@@ -471,6 +594,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	pub(crate) fn add_light_client_request(&mut self, rq: RequestData<B>) {
 		self.light_dispatch.add_request(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, rq);
 	}
@@ -526,7 +650,20 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	) -> CustomMessageOutcome<B> {
 		match message {
 			GenericMessage::Status(s) => self.on_status_message(who, s),
-			GenericMessage::BlockRequest(r) => self.on_block_request(who, r),
+			request @ GenericMessage::BlockRequest(_) |
+			request @ GenericMessage::FinalityProofRequest(_) |
+			request @ GenericMessage::WarpSyncRequest(_) |
+			request @ GenericMessage::StateRequest(_) |
+			request @ GenericMessage::RemoteCallRequest(_) |
+			request @ GenericMessage::RemoteReadRequest(_) |
+			request @ GenericMessage::RemoteReadChildRequest(_) |
+			request @ GenericMessage::RemoteReadRangeRequest(_) |
+			request @ GenericMessage::RemoteHeaderRequest(_) |
+			request @ GenericMessage::RemoteChangesRequest(_) => {
+				if let Some(response) = self.answer_request(who.clone(), request) {
+					self.send_message(who, response);
+				}
+			},
 			GenericMessage::BlockResponse(r) => {
 				// Note, this is safe because only `ordinary bodies` and `remote bodies` are received in this matter.
 				if self.is_light_response(&who, r.id) {
@@ -546,27 +683,20 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			},
 			GenericMessage::Transactions(m) =>
 				self.on_extrinsics(who, m),
-			GenericMessage::RemoteCallRequest(request) => self.on_remote_call_request(who, request),
 			GenericMessage::RemoteCallResponse(response) =>
 				self.on_remote_call_response(who, response),
-			GenericMessage::RemoteReadRequest(request) =>
-				self.on_remote_read_request(who, request),
 			GenericMessage::RemoteReadResponse(response) =>
 				self.on_remote_read_response(who, response),
-			GenericMessage::RemoteHeaderRequest(request) =>
-				self.on_remote_header_request(who, request),
 			GenericMessage::RemoteHeaderResponse(response) =>
 				self.on_remote_header_response(who, response),
-			GenericMessage::RemoteChangesRequest(request) =>
-				self.on_remote_changes_request(who, request),
 			GenericMessage::RemoteChangesResponse(response) =>
 				self.on_remote_changes_response(who, response),
-			GenericMessage::FinalityProofRequest(request) =>
-				self.on_finality_proof_request(who, request),
 			GenericMessage::FinalityProofResponse(response) =>
 				return self.on_finality_proof_response(who, response),
-			GenericMessage::RemoteReadChildRequest(request) =>
-				self.on_remote_read_child_request(who, request),
+			GenericMessage::WarpSyncResponse(response) =>
+				self.on_warp_sync_response(who, response),
+			GenericMessage::StateResponse(response) =>
+				self.on_state_response(who, response),
 			GenericMessage::Consensus(msg) => {
 				if self.context_data.peers.get(&who).map_or(false, |peer| peer.info.protocol_version > 2) {
 					self.consensus_gossip.on_incoming(
@@ -586,9 +716,82 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		CustomMessageOutcome::None
 	}
 
+	/// Builds the response to a request of `who`, whatever the substream it was received on.
+	///
+	/// Returns `None` if `request` isn't a request, or if `who` shouldn't have sent it.
+	fn answer_request(&mut self, who: PeerId, request: Message<B>) -> Option<Message<B>> {
+		match request {
+			GenericMessage::BlockRequest(request) =>
+				self.on_block_request(who, request).map(GenericMessage::BlockResponse),
+			GenericMessage::FinalityProofRequest(request) =>
+				Some(self.on_finality_proof_request(who, request)),
+			GenericMessage::WarpSyncRequest(request) =>
+				Some(self.on_warp_sync_request(who, request)),
+			GenericMessage::StateRequest(request) =>
+				Some(self.on_state_request(who, request)),
+			GenericMessage::RemoteCallRequest(request) =>
+				Some(self.on_remote_call_request(who, request)),
+			GenericMessage::RemoteReadRequest(request) =>
+				Some(self.on_remote_read_request(who, request)),
+			GenericMessage::RemoteReadChildRequest(request) =>
+				Some(self.on_remote_read_child_request(who, request)),
+			GenericMessage::RemoteReadRangeRequest(request) =>
+				Some(self.on_remote_read_range_request(who, request)),
+			GenericMessage::RemoteHeaderRequest(request) =>
+				Some(self.on_remote_header_request(who, request)),
+			GenericMessage::RemoteChangesRequest(request) =>
+				Some(self.on_remote_changes_request(who, request)),
+			_ => None,
+		}
+	}
+
+	/// Called when `who` sent us a request on the request-response protocol `protocol`.
+	///
+	/// Returns the encoded response, or `None` if the request shouldn't be answered.
+	pub fn on_request(&mut self, who: PeerId, protocol: RequestProtocol, request: &[u8]) -> Option<Vec<u8>> {
+		match Message::<B>::decode(&mut &request[..]) {
+			Ok(request) if RequestProtocol::of_request(&request) == Some(protocol) =>
+				self.answer_request(who, request).map(|response| response.encode()),
+			_ => {
+				debug!(target: "sync", "Invalid request on the {:?} protocol from {}", protocol, who);
+				self.peerset_handle.report_peer(who, BAD_REQUEST_REPUTATION_CHANGE);
+				None
+			}
+		}
+	}
+
+	/// Called when `who` answered a request that we sent on the request-response protocol
+	/// `protocol`.
+	pub fn on_response(
+		&mut self,
+		who: PeerId,
+		protocol: RequestProtocol,
+		response: &[u8],
+	) -> CustomMessageOutcome<B> {
+		match Message::<B>::decode(&mut &response[..]) {
+			Ok(response) if protocol.is_response(&response) => self.on_custom_message(who, response),
+			_ => {
+				debug!(target: "sync", "Invalid response on the {:?} protocol from {}", protocol, who);
+				self.behaviour.disconnect_peer(&who);
+				self.peerset_handle.report_peer(who, BAD_REQUEST_REPUTATION_CHANGE);
+				CustomMessageOutcome::None
+			}
+		}
+	}
+
+	/// Called when a request that we sent to `who` on a request-response protocol has failed.
+	///
+	/// The peer is disconnected, which lets sync and the light client dispatch send the request
+	/// to another peer.
+	pub fn on_request_failed(&mut self, who: PeerId) {
+		self.behaviour.disconnect_peer(&who);
+		self.peerset_handle.report_peer(who, RPC_FAILED_REPUTATION_CHANGE);
+	}
+
 	fn send_message(&mut self, who: PeerId, message: Message<B>) {
 		send_message::<B, H>(
 			&mut self.behaviour,
+			&mut self.pending_requests,
 			&mut self.context_data.peers,
 			who,
 			message,
@@ -644,6 +847,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		// lock all the the peer lists so that add/remove peer events are in order
 		let removed = {
 			self.handshaking_peers.remove(&peer);
+			self.pending_requests.peers.remove(&peer);
+			self.pending_requests.queue.retain(|(who, _, _)| who != &peer);
 			self.context_data.peers.remove(&peer)
 		};
 		if let Some(peer_data) = removed {
@@ -655,6 +860,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			self.specialization.on_disconnect(&mut context, peer.clone());
 			self.light_dispatch.on_disconnect(LightDispatchIn {
 				behaviour: &mut self.behaviour,
+				requests: &mut self.pending_requests,
 				peerset: self.peerset_handle.clone(),
 			}, peer);
 		}
@@ -680,7 +886,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		&mut self,
 		peer: PeerId,
 		request: message::BlockRequest<B>
-	) {
+	) -> Option<message::BlockResponse<B>> {
 		trace!(target: "sync", "BlockRequest {} from {}: from {:?} to {:?} max {:?}",
 			request.id,
 			peer,
@@ -693,7 +899,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			trace!(target: "sync", "Peer {} is trying to sync from the light node", peer);
 			self.behaviour.disconnect_peer(&peer);
 			self.peerset_handle.report_peer(peer, i32::min_value());
-			return;
+			return None;
 		}
 
 		let mut blocks = Vec::new();
@@ -707,6 +913,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let get_justification = request
 			.fields
 			.contains(message::BlockAttributes::JUSTIFICATION);
+		// Size of the encoded response without the blocks: the message index, the request id and
+		// the length of the blocks.
+		let mut total_size = 1 + request.id.encoded_size() + codec::Compact(max as u32).encoded_size();
 		while let Some(header) = self.context_data.chain.header(&id).unwrap_or(None) {
 			if blocks.len() >= max {
 				break;
@@ -734,6 +943,13 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				message_queue: None,
 				justification,
 			};
+			// A response always contains the first block, or the requester would keep asking for it.
+			let block_size = block_data.encoded_size();
+			if !blocks.is_empty() && total_size + block_size > MAX_RESPONSE_SIZE {
+				trace!(target: "sync", "BlockResponse to {} reached the maximum response size", peer);
+				break;
+			}
+			total_size += block_size;
 			blocks.push(block_data);
 			match request.direction {
 				message::Direction::Ascending => id = BlockId::Number(number + One::one()),
@@ -750,7 +966,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			blocks: blocks,
 		};
 		trace!(target: "sync", "Sending BlockResponse with {} blocks", response.blocks.len());
-		self.send_message(peer, GenericMessage::BlockResponse(response))
+		Some(response)
 	}
 
 	/// Adjusts the reputation of a node.
@@ -819,6 +1035,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.maintain_peers();
 		self.light_dispatch.maintain_peers(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		});
 	}
@@ -914,7 +1131,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 						protocol_version: status.version,
 						roles: status.roles,
 						best_hash: status.best_hash,
-						best_number: status.best_number
+						best_number: status.best_number,
+						keep_block_bodies: status.keep_block_bodies,
 					}
 				},
 				None => {
@@ -932,6 +1150,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				obsolete_requests: HashMap::new(),
 			};
 			self.context_data.peers.insert(who.clone(), peer);
+			if status.version >= REQUEST_RESPONSES_VERSION {
+				self.pending_requests.peers.insert(who.clone());
+			}
 
 			debug!(target: "sync", "Connected {}", who);
 			status.version
@@ -940,8 +1161,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let info = self.context_data.peers.get(&who).expect("We just inserted above; QED").info.clone();
 		self.light_dispatch.on_connect(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
-		}, who.clone(), status.roles, status.best_number);
+		}, who.clone(), status.roles, status.version, status.best_number);
 		match self.sync.new_peer(who.clone(), info) {
 			Ok(None) => (),
 			Ok(Some(req)) => self.send_message(who.clone(), GenericMessage::BlockRequest(req)),
@@ -1100,6 +1322,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			best_number: info.chain.best_number,
 			best_hash: info.chain.best_hash,
 			chain_status: self.specialization.status(),
+			keep_block_bodies: self.config.keep_block_bodies,
 		};
 
 		self.send_message(who, GenericMessage::Status(status))
@@ -1112,6 +1335,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		}
 		self.light_dispatch.update_best_number(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, who.clone(), *announce.header.number());
 
@@ -1204,7 +1428,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		&mut self,
 		who: PeerId,
 		request: message::RemoteCallRequest<B::Hash>,
-	) {
+	) -> Message<B> {
 		trace!(target: "sync", "Remote call request {} from {} ({} at {})",
 			request.id,
 			who,
@@ -1216,7 +1440,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			&request.method,
 			&request.data,
 		) {
-			Ok((_, proof)) => proof,
+			Ok((_, proof)) => self.encode_proof(&who, &request.block, proof),
 			Err(error) => {
 				trace!(target: "sync", "Remote call request {} from {} ({} at {}) failed with: {}",
 					request.id,
@@ -1230,13 +1454,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			}
 		};
 
-		self.send_message(
-			who,
-			GenericMessage::RemoteCallResponse(message::RemoteCallResponse {
-				id: request.id,
-				proof,
-			}),
-		);
+		GenericMessage::RemoteCallResponse(message::RemoteCallResponse {
+			id: request.id,
+			proof,
+		})
 	}
 
 	/// Request a justification for the given block.
@@ -1274,7 +1495,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			match result {
 				Ok((id, req)) => {
 					let msg = GenericMessage::BlockRequest(req);
-					send_message(&mut self.behaviour, &mut self.context_data.peers, id, msg)
+					send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, msg)
 				}
 				Err(sync::BadPeer(id, repu)) => {
 					self.behaviour.disconnect_peer(&id);
@@ -1308,20 +1529,62 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	fn on_remote_call_response(
 		&mut self,
 		who: PeerId,
-		response: message::RemoteCallResponse
+		mut response: message::RemoteCallResponse
 	) {
 		trace!(target: "sync", "Remote call response {} from {}", response.id, who);
+		response.proof = self.decode_proof(&who, response.proof);
 		self.light_dispatch.on_remote_call_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
 
+	/// Returns true if `who` exchanges read and execution proofs in the compact encoding.
+	fn uses_compact_proofs(&self, who: &PeerId) -> bool {
+		self.context_data.peers.get(who)
+			.map_or(false, |peer| peer.info.protocol_version >= COMPACT_PROOF_VERSION)
+	}
+
+	/// Encodes a read or execution proof of the state at `block` in the format `who` expects.
+	fn encode_proof(&self, who: &PeerId, block: &B::Hash, proof: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+		if proof.is_empty() || !self.uses_compact_proofs(who) {
+			return proof;
+		}
+
+		match self.context_data.chain.compact_proof(block, &proof) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Failed to encode compact proof at {} for {}: {}",
+					block, who, error);
+				Default::default()
+			}
+		}
+	}
+
+	/// Decodes a read or execution proof received from `who` into the full trie nodes.
+	///
+	/// A proof that can't be decoded is replaced with an empty one, which fails the check of the
+	/// response.
+	fn decode_proof(&self, who: &PeerId, proof: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+		if proof.is_empty() || !self.uses_compact_proofs(who) {
+			return proof;
+		}
+
+		match decode_compact_proof::<Blake2Hasher>(&proof) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Failed to decode compact proof from {}: {}", who, error);
+				Default::default()
+			}
+		}
+	}
+
 	fn on_remote_read_request(
 		&mut self,
 		who: PeerId,
 		request: message::RemoteReadRequest<B::Hash>,
-	) {
+	) -> Message<B> {
 		let keys_str = || match request.keys.len() {
 			1 => request.keys[0].to_hex::<String>(),
 			_ => format!(
@@ -1334,7 +1597,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		trace!(target: "sync", "Remote read request {} from {} ({} at {})",
 			request.id, who, keys_str(), request.block);
 		let proof = match self.context_data.chain.read_proof(&request.block, &request.keys) {
-			Ok(proof) => proof,
+			Ok(proof) => self.encode_proof(&who, &request.block, proof),
 			Err(error) => {
 				trace!(target: "sync", "Remote read request {} from {} ({} at {}) failed with: {}",
 					request.id,
@@ -1346,20 +1609,17 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				Default::default()
 			}
 		};
-		self.send_message(
-			who,
-			GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
-				id: request.id,
-				proof,
-			}),
-		);
+		GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
+			id: request.id,
+			proof,
+		})
 	}
 
 	fn on_remote_read_child_request(
 		&mut self,
 		who: PeerId,
 		request: message::RemoteReadChildRequest<B::Hash>,
-	) {
+	) -> Message<B> {
 		let keys_str = || match request.keys.len() {
 			1 => request.keys[0].to_hex::<String>(),
 			_ => format!(
@@ -1376,7 +1636,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			&request.storage_key,
 			&request.keys,
 		) {
-			Ok(proof) => proof,
+			Ok(proof) => self.encode_proof(&who, &request.block, proof),
 			Err(error) => {
 				trace!(target: "sync", "Remote read child request {} from {} ({} {} at {}) failed with: {}",
 					request.id,
@@ -1389,23 +1649,63 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				Default::default()
 			}
 		};
-		self.send_message(
+		GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
+			id: request.id,
+			proof,
+		})
+	}
+
+	fn on_remote_read_range_request(
+		&mut self,
+		who: PeerId,
+		request: message::RemoteReadRangeRequest<B::Hash>,
+	) -> Message<B> {
+		// A start key below the prefix reads the range from the prefix. The number of keys and
+		// the size of the range are capped by `read_range_proof`.
+		let start_key = request.start_key.as_ref()
+			.map(|key| &key[..])
+			.filter(|key| *key >= &request.prefix[..]);
+		trace!(target: "sync", "Remote read range request {} from {} ({} after {:?} at {})",
+			request.id,
 			who,
-			GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
-				id: request.id,
-				proof,
-			}),
+			request.prefix.to_hex::<String>(),
+			start_key.map(|key| key.to_hex::<String>()),
+			request.block,
 		);
+		let proof = match self.context_data.chain.read_range_proof(
+			&request.block,
+			&request.prefix,
+			start_key,
+			request.count,
+		) {
+			Ok(proof) => self.encode_proof(&who, &request.block, proof),
+			Err(error) => {
+				trace!(target: "sync", "Remote read range request {} from {} ({} at {}) failed with: {}",
+					request.id,
+					who,
+					request.prefix.to_hex::<String>(),
+					request.block,
+					error
+				);
+				Default::default()
+			}
+		};
+		GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
+			id: request.id,
+			proof,
+		})
 	}
 
 	fn on_remote_read_response(
 		&mut self,
 		who: PeerId,
-		response: message::RemoteReadResponse
+		mut response: message::RemoteReadResponse
 	) {
 		trace!(target: "sync", "Remote read response {} from {}", response.id, who);
+		response.proof = self.decode_proof(&who, response.proof);
 		self.light_dispatch.on_remote_read_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1414,7 +1714,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		&mut self,
 		who: PeerId,
 		request: message::RemoteHeaderRequest<NumberFor<B>>,
-	) {
+	) -> Message<B> {
 		trace!(target: "sync", "Remote header proof request {} from {} ({})",
 			request.id, who, request.block);
 		let (header, proof) = match self.context_data.chain.header_proof(request.block) {
@@ -1429,14 +1729,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				(Default::default(), Default::default())
 			}
 		};
-		self.send_message(
-			who,
-			GenericMessage::RemoteHeaderResponse(message::RemoteHeaderResponse {
-				id: request.id,
-				header,
-				proof,
-			}),
-		);
+		GenericMessage::RemoteHeaderResponse(message::RemoteHeaderResponse {
+			id: request.id,
+			header,
+			proof,
+		})
 	}
 
 	fn on_remote_header_response(
@@ -1447,6 +1744,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		trace!(target: "sync", "Remote header proof response {} from {}", response.id, who);
 		self.light_dispatch.on_remote_header_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1455,7 +1753,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		&mut self,
 		who: PeerId,
 		request: message::RemoteChangesRequest<B::Hash>,
-	) {
+	) -> Message<B> {
 		trace!(target: "sync", "Remote changes proof request {} from {} for key {} ({}..{})",
 			request.id,
 			who,
@@ -1499,16 +1797,13 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				}
 			}
 		};
-		self.send_message(
-			who,
-			GenericMessage::RemoteChangesResponse(message::RemoteChangesResponse {
-				id: request.id,
-				max: proof.max_block,
-				proof: proof.proof,
-				roots: proof.roots.into_iter().collect(),
-				roots_proof: proof.roots_proof,
-			}),
-		);
+		GenericMessage::RemoteChangesResponse(message::RemoteChangesResponse {
+			id: request.id,
+			max: proof.max_block,
+			proof: proof.proof,
+			roots: proof.roots.into_iter().collect(),
+			roots_proof: proof.roots_proof,
+		})
 	}
 
 	fn on_remote_changes_response(
@@ -1523,6 +1818,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		);
 		self.light_dispatch.on_remote_changes_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1531,7 +1827,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		&mut self,
 		who: PeerId,
 		request: message::FinalityProofRequest<B::Hash>,
-	) {
+	) -> Message<B> {
 		trace!(target: "sync", "Finality proof request from {} for {}", who, request.block);
 		let finality_proof = self.finality_proof_provider.as_ref()
 			.ok_or_else(|| String::from("Finality provider is not configured"))
@@ -1549,14 +1845,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				None
 			},
 		};
- 		self.send_message(
-			who,
-			GenericMessage::FinalityProofResponse(message::FinalityProofResponse {
-				id: 0,
-				block: request.block,
-				proof: finality_proof,
-			}),
-		);
+		GenericMessage::FinalityProofResponse(message::FinalityProofResponse {
+			id: 0,
+			block: request.block,
+			proof: finality_proof,
+		})
 	}
 
 	fn on_finality_proof_response(
@@ -1577,6 +1870,85 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		}
 	}
 
+	fn on_warp_sync_request(
+		&mut self,
+		who: PeerId,
+		request: message::WarpSyncRequest<B::Hash>,
+	) -> Message<B> {
+		trace!(target: "sync", "Warp sync proof request from {} starting at {}", who, request.begin);
+		let proof = self.finality_proof_provider.as_ref()
+			.ok_or_else(|| String::from("Finality provider is not configured"))
+			.and_then(|provider| provider.prove_warp_sync(request.begin).map_err(|e| e.to_string()));
+		let proof = match proof {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "Warp sync proof request from {} starting at {} failed with: {}",
+					who,
+					request.begin,
+					error
+				);
+				None
+			},
+		};
+		GenericMessage::WarpSyncResponse(message::WarpSyncResponse {
+			id: 0,
+			begin: request.begin,
+			proof,
+		})
+	}
+
+	fn on_warp_sync_response(&mut self, who: PeerId, response: message::WarpSyncResponse<B::Hash>) {
+		trace!(target: "sync", "Warp sync proof response from {} starting at {}", who, response.begin);
+		if let Err(sync::BadPeer(id, repu)) = self.sync.on_warp_sync_data(who, response) {
+			self.behaviour.disconnect_peer(&id);
+			self.peerset_handle.report_peer(id, repu);
+		}
+	}
+
+	fn on_state_request(
+		&mut self,
+		who: PeerId,
+		request: message::StateRequest<B::Hash>,
+	) -> Message<B> {
+		trace!(target: "sync", "State request from {} for {}", who, request.block);
+		// Light nodes have no state to provide.
+		let chunk = if self.config.roles.is_full() {
+			self.context_data.chain.state_chunk(
+				&request.block,
+				request.start_key.as_ref().map(|key| &key[..]),
+				request.start_child_key.as_ref().map(|key| &key[..]),
+				MAX_STATE_RESPONSE_SIZE,
+			)
+		} else {
+			Ok((Vec::new(), false, Vec::new()))
+		};
+		let (entries, complete, proof) = match chunk {
+			Ok(chunk) => chunk,
+			Err(error) => {
+				trace!(target: "sync", "State request from {} for {} failed with: {}",
+					who,
+					request.block,
+					error
+				);
+				(Vec::new(), false, Vec::new())
+			},
+		};
+		GenericMessage::StateResponse(message::StateResponse {
+			id: 0,
+			entries,
+			complete,
+			proof,
+		})
+	}
+
+	fn on_state_response(&mut self, who: PeerId, response: message::StateResponse) {
+		trace!(target: "sync", "State response from {} with {} entries", who, response.entries.len());
+		if let Err(sync::BadPeer(id, repu)) = self.sync.on_state_data(who, response) {
+			self.behaviour.disconnect_peer(&id);
+			self.peerset_handle.report_peer(id, repu);
+		}
+	}
+
 	fn on_remote_body_response(
 		&mut self,
 		peer: PeerId,
@@ -1584,6 +1956,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	) {
 		self.light_dispatch.on_remote_body_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			requests: &mut self.pending_requests,
 			peerset: self.peerset_handle.clone(),
 		}, peer, response);
 	}
@@ -1595,11 +1968,18 @@ pub enum CustomMessageOutcome<B: BlockT> {
 	BlockImport(BlockOrigin, Vec<IncomingBlock<B>>),
 	JustificationImport(Origin, B::Hash, NumberFor<B>, Justification),
 	FinalityProofImport(Origin, B::Hash, NumberFor<B>, Vec<u8>),
+	/// Encoded request to send to a peer on a request-response protocol.
+	Request {
+		target: PeerId,
+		protocol: RequestProtocol,
+		request: Vec<u8>,
+	},
 	None,
 }
 
 fn send_message<B: BlockT, H: ExHashT>(
 	behaviour: &mut LegacyProto<B, Substream<StreamMuxerBox>>,
+	requests: &mut PendingRequests,
 	peers: &mut HashMap<PeerId, Peer<B, H>>,
 	who: PeerId,
 	mut message: Message<B>,
@@ -1615,7 +1995,7 @@ fn send_message<B: BlockT, H: ExHashT>(
 			peer.block_request = Some((time::Instant::now(), r.clone()));
 		}
 	}
-	behaviour.send_packet(&who, message);
+	requests.send(behaviour, &who, message);
 }
 
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviour for
@@ -1664,14 +2044,33 @@ Protocol<B, S, H> {
 			self.propagate_extrinsics();
 		}
 
+		for sync::BadPeer(id, repu) in self.sync.poll_warp_sync_imports() {
+			self.behaviour.disconnect_peer(&id);
+			self.peerset_handle.report_peer(id, repu);
+		}
+
 		for (id, r) in self.sync.block_requests() {
-			send_message(&mut self.behaviour, &mut self.context_data.peers, id, GenericMessage::BlockRequest(r))
+			send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, GenericMessage::BlockRequest(r))
 		}
 		for (id, r) in self.sync.justification_requests() {
-			send_message(&mut self.behaviour, &mut self.context_data.peers, id, GenericMessage::BlockRequest(r))
+			send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, GenericMessage::BlockRequest(r))
 		}
 		for (id, r) in self.sync.finality_proof_requests() {
-			send_message(&mut self.behaviour, &mut self.context_data.peers, id, GenericMessage::FinalityProofRequest(r))
+			send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, GenericMessage::FinalityProofRequest(r))
+		}
+		if let Some((id, r)) = self.sync.warp_sync_request() {
+			send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, GenericMessage::WarpSyncRequest(r))
+		}
+		if let Some((id, r)) = self.sync.state_request() {
+			send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, GenericMessage::StateRequest(r))
+		}
+		if let Some((id, r)) = self.sync.historic_block_request() {
+			send_message(&mut self.behaviour, &mut self.pending_requests, &mut self.context_data.peers, id, GenericMessage::BlockRequest(r))
+		}
+
+		if let Some((target, protocol, request)) = self.pending_requests.queue.pop_front() {
+			let outcome = CustomMessageOutcome::Request { target, protocol, request };
+			return Async::Ready(NetworkBehaviourAction::GenerateEvent(outcome));
 		}
 
 		let event = match self.behaviour.poll(params) {
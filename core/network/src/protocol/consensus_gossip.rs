@@ -261,6 +261,9 @@ pub struct ConsensusGossip<B: BlockT> {
 	known_messages: LruCache<B::Hash, ()>,
 	validators: HashMap<ConsensusEngineId, Arc<dyn Validator<B>>>,
 	next_broadcast: time::Instant,
+	/// Peers whose kept messages are propagated as soon as they are received, such as the
+	/// validators we are a sentry of, instead of on the next rebroadcast.
+	relayed_peers: HashSet<PeerId>,
 }
 
 impl<B: BlockT> ConsensusGossip<B> {
@@ -273,9 +276,16 @@ impl<B: BlockT> ConsensusGossip<B> {
 			known_messages: LruCache::new(KNOWN_MESSAGES_CACHE_SIZE),
 			validators: Default::default(),
 			next_broadcast: time::Instant::now() + REBROADCAST_INTERVAL,
+			relayed_peers: HashSet::new(),
 		}
 	}
 
+	/// Sets the peers whose kept messages are propagated to the other peers as soon as they are
+	/// received.
+	pub fn set_relayed_peers(&mut self, peers: HashSet<PeerId>) {
+		self.relayed_peers = peers;
+	}
+
 	/// Closes all notification streams.
 	pub fn abort(&mut self) {
 		self.live_message_sinks.clear();
@@ -501,7 +511,22 @@ impl<B: BlockT> ConsensusGossip<B> {
 					}
 				}
 				if keep {
+					let relay = if self.relayed_peers.contains(&who) {
+						Some(message.clone())
+					} else {
+						None
+					};
 					self.register_message_hashed(message_hash, topic, message, Some(who.clone()));
+					if let Some(message) = relay {
+						trace!(target: "gossip", "Relaying message from {}", who);
+						propagate(
+							protocol,
+							iter::once((&message_hash, &topic, &message)),
+							MessageIntent::Broadcast,
+							&mut self.peers,
+							&self.validators,
+						);
+					}
 				}
 			} else {
 				trace!(target:"gossip", "Ignored statement from unregistered peer {}", who);
@@ -17,7 +17,8 @@
 //! Network event types. These are are not the part of the protocol, but rather
 //! events that happen on the network like DHT get/put results received.
 
-use libp2p::kad::record::Key;
+use bytes::Bytes;
+use libp2p::{PeerId, kad::record::Key};
 
 /// Events generated by DHT as a response to get_value and put_value requests.
 #[derive(Debug, Clone)]
@@ -40,4 +41,41 @@ pub enum DhtEvent {
 pub enum Event {
 	/// Event generated by a DHT.
 	Dht(DhtEvent),
+
+	/// A remote opened a substream with us for a notifications protocol that we registered.
+	NotificationStreamOpened {
+		/// Node that opened the substream.
+		remote: PeerId,
+		/// Name of the notifications protocol.
+		protocol: Bytes,
+		/// Handshake that the remote sent as the first message of the substream.
+		handshake: Vec<u8>,
+	},
+
+	/// A substream previously reported with `NotificationStreamOpened` has been closed.
+	NotificationStreamClosed {
+		/// Node that had opened the substream.
+		remote: PeerId,
+		/// Name of the notifications protocol.
+		protocol: Bytes,
+	},
+
+	/// A remote sent us a notification on a notifications protocol that we registered.
+	NotificationReceived {
+		/// Node that sent the notification.
+		remote: PeerId,
+		/// Name of the notifications protocol.
+		protocol: Bytes,
+		/// The notification itself.
+		message: Vec<u8>,
+	},
+
+	/// Too many notifications are waiting to be sent to a remote on a notifications protocol, and
+	/// the new ones are discarded until it reads them.
+	NotificationStreamClogged {
+		/// Node that doesn't read the notifications fast enough.
+		remote: PeerId,
+		/// Name of the notifications protocol.
+		protocol: Bytes,
+	},
 }
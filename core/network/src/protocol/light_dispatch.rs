@@ -28,10 +28,11 @@ use linked_hash_map::{Entry, LinkedHashMap};
 use client::error::Error as ClientError;
 use client::light::fetcher::{FetchChecker, RemoteHeaderRequest,
 	RemoteCallRequest, RemoteReadRequest, RemoteChangesRequest, ChangesProof,
-	RemoteReadChildRequest, RemoteBodyRequest};
+	RemoteReadChildRequest, RemoteReadRangeRequest, RemoteBodyRequest};
 use crate::message::{self, BlockAttributes, Direction, FromBlock, RequestId};
 use libp2p::PeerId;
 use crate::config::Roles;
+use crate::protocol::{MIN_VERSION, READ_RANGE_VERSION};
 use sr_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor};
 
 /// Remote request timeout.
@@ -71,6 +72,17 @@ pub trait LightDispatchNetwork<B: BlockT> {
 		keys: Vec<Vec<u8>>,
 	);
 
+	/// Send to `who` a range read request.
+	fn send_read_range_request(
+		&mut self,
+		who: &PeerId,
+		id: RequestId,
+		block: <B as BlockT>::Hash,
+		prefix: Vec<u8>,
+		start_key: Option<Vec<u8>>,
+		count: Option<u32>,
+	);
+
 	/// Send to `who` a call request.
 	fn send_call_request(
 		&mut self,
@@ -123,6 +135,8 @@ pub struct LightDispatch<B: BlockT> {
 	idle_peers: VecDeque<PeerId>,
 	/// Best known block for each node in `active_peers` and `idle_peers`.
 	best_blocks: HashMap<PeerId, NumberFor<B>>,
+	/// Protocol version of each node in `active_peers` and `idle_peers`.
+	protocol_versions: HashMap<PeerId, u32>,
 }
 
 struct Request<Block: BlockT> {
@@ -149,6 +163,10 @@ pub(crate) enum RequestData<Block: BlockT> {
 		RemoteReadChildRequest<Block::Header>,
 		OneShotSender<Result<HashMap<Vec<u8>, Option<Vec<u8>>>, ClientError>>
 	),
+	RemoteReadRange(
+		RemoteReadRangeRequest<Block::Header>,
+		OneShotSender<Result<Vec<(Vec<u8>, Vec<u8>)>, ClientError>>
+	),
 	RemoteCall(RemoteCallRequest<Block::Header>, OneShotSender<Result<Vec<u8>, ClientError>>),
 	RemoteChanges(
 		RemoteChangesRequest<Block::Header>,
@@ -195,6 +213,14 @@ impl<Block: BlockT> FetchChecker<Block> for AlwaysBadChecker {
 		Err(ClientError::Msg("AlwaysBadChecker".into()))
 	}
 
+	fn check_read_range_proof(
+		&self,
+		_request: &RemoteReadRangeRequest<Block::Header>,
+		_remote_proof: Vec<Vec<u8>>
+	) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ClientError> {
+		Err(ClientError::Msg("AlwaysBadChecker".into()))
+	}
+
 	fn check_execution_proof(
 		&self,
 		_request: &RemoteCallRequest<Block::Header>,
@@ -232,6 +258,7 @@ impl<B: BlockT> LightDispatch<B> where
 			active_peers: LinkedHashMap::new(),
 			idle_peers: VecDeque::new(),
 			best_blocks: HashMap::new(),
+			protocol_versions: HashMap::new(),
 		}
 	}
 
@@ -314,6 +341,7 @@ impl<B: BlockT> LightDispatch<B> where
 		network: impl LightDispatchNetwork<B>,
 		peer: PeerId,
 		role: Roles,
+		protocol_version: u32,
 		best_number: NumberFor<B>
 	) {
 		if !role.is_full() {
@@ -321,6 +349,7 @@ impl<B: BlockT> LightDispatch<B> where
 		}
 
 		self.idle_peers.push_back(peer.clone());
+		self.protocol_versions.insert(peer.clone(), protocol_version);
 		self.best_blocks.insert(peer, best_number);
 
 		self.dispatch(network);
@@ -413,6 +442,18 @@ impl<B: BlockT> LightDispatch<B> where
 						RequestData::RemoteReadChild(request, sender)
 					),
 			}},
+			RequestData::RemoteReadRange(request, sender) => {
+				match checker.check_read_range_proof(&request, response.proof) {
+					Ok(response) => {
+						// we do not bother if receiver has been dropped already
+						let _ = sender.send(Ok(response));
+						Accept::Ok
+					},
+					Err(error) => Accept::CheckFailed(
+						error,
+						RequestData::RemoteReadRange(request, sender)
+					),
+			}},
 			data => Accept::Unexpected(data),
 		})
 	}
@@ -521,6 +562,7 @@ impl<B: BlockT> LightDispatch<B> where
 	/// Puts back the active request that this node was performing into `pending_requests`.
 	fn remove_peer(&mut self, peer: PeerId) {
 		self.best_blocks.remove(&peer);
+		self.protocol_versions.remove(&peer);
 
 		if let Some(request) = self.active_peers.remove(&peer) {
 			self.pending_requests.push_front(request);
@@ -556,7 +598,12 @@ impl<B: BlockT> LightDispatch<B> where
 					.expect("entries are inserted into best_blocks when peer is connected;
 						entries are removed from best_blocks when peer is disconnected;
 						peer is in idle_peers and thus connected; qed");
+				let peer_version = self.protocol_versions.get(&peer)
+					.expect("entries are inserted into protocol_versions when peer is connected;
+						entries are removed from protocol_versions when peer is disconnected;
+						peer is in idle_peers and thus connected; qed");
 				request.required_block() <= *peer_best_block
+					&& request.required_version() <= *peer_version
 			};
 
 			if !can_be_processed_by_peer {
@@ -594,12 +641,21 @@ impl<Block: BlockT> Request<Block> {
 			RequestData::RemoteHeader(ref data, _) => data.block,
 			RequestData::RemoteRead(ref data, _) => *data.header.number(),
 			RequestData::RemoteReadChild(ref data, _) => *data.header.number(),
+			RequestData::RemoteReadRange(ref data, _) => *data.header.number(),
 			RequestData::RemoteCall(ref data, _) => *data.header.number(),
 			RequestData::RemoteChanges(ref data, _) => data.max_block.0,
 			RequestData::RemoteBody(ref data, _) => *data.header.number(),
 		}
 	}
 
+	/// Returns the lowest protocol version of the remote with which it can fulfill this request.
+	fn required_version(&self) -> u32 {
+		match self.data {
+			RequestData::RemoteReadRange(..) => READ_RANGE_VERSION,
+			_ => MIN_VERSION,
+		}
+	}
+
 	fn send_to(&self, out: &mut impl LightDispatchNetwork<Block>, peer: &PeerId) {
 		match self.data {
 			RequestData::RemoteHeader(ref data, _) =>
@@ -623,6 +679,15 @@ impl<Block: BlockT> Request<Block> {
 					data.storage_key.clone(),
 					data.keys.clone(),
 				),
+			RequestData::RemoteReadRange(ref data, _) =>
+				out.send_read_range_request(
+					peer,
+					self.id,
+					data.block,
+					data.prefix.clone(),
+					data.start_key.clone(),
+					data.count,
+				),
 			RequestData::RemoteCall(ref data, _) =>
 				out.send_call_request(
 					peer,
@@ -664,6 +729,7 @@ impl<Block: BlockT> RequestData<Block> {
 			RequestData::RemoteCall(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteRead(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteReadChild(_, sender) => { let _ = sender.send(Err(error)); },
+			RequestData::RemoteReadRange(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteChanges(_, sender) => { let _ = sender.send(Err(error)); },
 			RequestData::RemoteBody(_, sender) => { let _ = sender.send(Err(error)); },
 		}
@@ -680,10 +746,11 @@ pub mod tests {
 	use client::{error::{Error as ClientError, Result as ClientResult}};
 	use client::light::fetcher::{FetchChecker, RemoteHeaderRequest,
 		ChangesProof, RemoteCallRequest, RemoteReadRequest,
-		RemoteReadChildRequest, RemoteChangesRequest, RemoteBodyRequest};
+		RemoteReadChildRequest, RemoteReadRangeRequest, RemoteChangesRequest, RemoteBodyRequest};
 	use crate::config::Roles;
 	use crate::message::{self, BlockAttributes, Direction, FromBlock, RequestId};
 	use libp2p::PeerId;
+	use crate::protocol::{CURRENT_VERSION, READ_RANGE_VERSION};
 	use super::{REQUEST_TIMEOUT, LightDispatch, LightDispatchNetwork, RequestData};
 	use test_client::runtime::{changes_trie_config, Block, Extrinsic, Header};
 
@@ -734,6 +801,17 @@ pub mod tests {
 			}
 		}
 
+		fn check_read_range_proof(
+			&self,
+			request: &RemoteReadRangeRequest<Header>,
+			_: Vec<Vec<u8>>
+		) -> ClientResult<Vec<(Vec<u8>, Vec<u8>)>> {
+			match self.ok {
+				true => Ok(vec![(request.prefix.clone(), vec![42])]),
+				false => Err(ClientError::Backend("Test error".into())),
+			}
+		}
+
 		fn check_execution_proof(&self, _: &RemoteCallRequest<Header>, _: Vec<Vec<u8>>) -> ClientResult<Vec<u8>> {
 			match self.ok {
 				true => Ok(vec![42]),
@@ -808,6 +886,8 @@ pub mod tests {
 		fn send_read_request(&mut self, _: &PeerId, _: RequestId, _: <B as BlockT>::Hash, _: Vec<Vec<u8>>) {}
 		fn send_read_child_request(&mut self, _: &PeerId, _: RequestId, _: <B as BlockT>::Hash, _: Vec<u8>,
 			_: Vec<Vec<u8>>) {}
+		fn send_read_range_request(&mut self, _: &PeerId, _: RequestId, _: <B as BlockT>::Hash, _: Vec<u8>,
+			_: Option<Vec<u8>>, _: Option<u32>) {}
 		fn send_call_request(&mut self, _: &PeerId, _: RequestId, _: <B as BlockT>::Hash, _: String, _: Vec<u8>) {}
 		fn send_changes_request(&mut self, _: &PeerId, _: RequestId, _: <B as BlockT>::Hash, _: <B as BlockT>::Hash,
 			_: <B as BlockT>::Hash, _: <B as BlockT>::Hash, _: Option<Vec<u8>>, _: Vec<u8>) {}
@@ -826,9 +906,9 @@ pub mod tests {
 		let peer0 = PeerId::random();
 		let peer1 = PeerId::random();
 		let peer2 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0, Roles::LIGHT, 1000);
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 2000);
-		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::AUTHORITY, 3000);
+		light_dispatch.on_connect(&mut network_interface, peer0, Roles::LIGHT, CURRENT_VERSION, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 2000);
+		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::AUTHORITY, CURRENT_VERSION, 3000);
 		assert_eq!(vec![peer1.clone(), peer2.clone()], light_dispatch.idle_peers.iter().cloned().collect::<Vec<_>>());
 		assert_eq!(light_dispatch.best_blocks.get(&peer1), Some(&2000));
 		assert_eq!(light_dispatch.best_blocks.get(&peer2), Some(&3000));
@@ -840,7 +920,7 @@ pub mod tests {
 
 		let mut network_interface = DummyNetwork::default();
 		let mut light_dispatch = dummy(true);
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 100);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 100);
 		assert_eq!(1, total_peers(&light_dispatch));
 		assert!(!light_dispatch.best_blocks.is_empty());
 
@@ -855,8 +935,8 @@ pub mod tests {
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
 		let peer1 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 		assert_eq!(vec![peer0.clone(), peer1.clone()], light_dispatch.idle_peers.iter().cloned().collect::<Vec<_>>());
 		assert!(light_dispatch.active_peers.is_empty());
 
@@ -882,7 +962,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(true);
 		let peer0 = PeerId::random();
 		let mut network_interface = DummyNetwork::default();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteCall(RemoteCallRequest {
 			block: Default::default(),
@@ -909,7 +989,7 @@ pub mod tests {
 			retry_count: Some(1),
 		}, oneshot::channel().0));
 
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 		receive_call_response(&mut network_interface, &mut light_dispatch, peer0.clone(), 0);
 		assert_disconnected_peer(&network_interface);
 		assert_eq!(light_dispatch.pending_requests.len(), 1);
@@ -920,7 +1000,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(true);
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		receive_call_response(&mut network_interface, &mut light_dispatch, peer0, 0);
 		assert_disconnected_peer(&network_interface);
@@ -931,7 +1011,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(false);
 		let peer0 = PeerId::random();
 		let mut network_interface = DummyNetwork::default();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteCall(RemoteCallRequest {
 			block: Default::default(),
@@ -956,7 +1036,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(false);
 		let mut network_interface = DummyNetwork::default();
 		for i in 0..retry_count+1 {
-			light_dispatch.on_connect(&mut network_interface, peer_ids[i].clone(), Roles::FULL, 1000);
+			light_dispatch.on_connect(&mut network_interface, peer_ids[i].clone(), Roles::FULL, CURRENT_VERSION, 1000);
 		}
 
 		let (tx, mut response) = oneshot::channel();
@@ -981,7 +1061,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(true);
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		let (tx, response) = oneshot::channel();
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteCall(RemoteCallRequest {
@@ -1001,7 +1081,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(true);
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		let (tx, response) = oneshot::channel();
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteRead(RemoteReadRequest {
@@ -1023,7 +1103,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(true);
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		let (tx, response) = oneshot::channel();
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteReadChild(RemoteReadChildRequest {
@@ -1042,12 +1122,61 @@ pub mod tests {
 		assert_eq!(response.wait().unwrap().unwrap().remove(b":key".as_ref()).unwrap(), Some(vec![42]));
 	}
 
+	#[test]
+	fn receives_remote_read_range_response() {
+		let mut light_dispatch = dummy(true);
+		let mut network_interface = DummyNetwork::default();
+		let peer0 = PeerId::random();
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
+
+		let (tx, response) = oneshot::channel();
+		light_dispatch.add_request(&mut network_interface, RequestData::RemoteReadRange(RemoteReadRangeRequest {
+			header: dummy_header(),
+			block: Default::default(),
+			prefix: b":key".to_vec(),
+			start_key: None,
+			count: None,
+			retry_count: None,
+		}, tx));
+
+		light_dispatch.on_remote_read_response(&mut network_interface,
+			peer0.clone(), message::RemoteReadResponse {
+				id: 0,
+				proof: vec![vec![2]],
+		});
+		assert_eq!(response.wait().unwrap().unwrap(), vec![(b":key".to_vec(), vec![42])]);
+	}
+
+	#[test]
+	fn remote_read_range_request_is_only_sent_to_peers_that_answer_it() {
+		let mut light_dispatch = dummy(true);
+		let mut network_interface = DummyNetwork::default();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, READ_RANGE_VERSION - 1, 1000);
+
+		light_dispatch.add_request(&mut network_interface, RequestData::RemoteReadRange(RemoteReadRangeRequest {
+			header: dummy_header(),
+			block: Default::default(),
+			prefix: b":key".to_vec(),
+			start_key: None,
+			count: None,
+			retry_count: None,
+		}, oneshot::channel().0));
+		assert_eq!(light_dispatch.pending_requests.len(), 1);
+		assert!(light_dispatch.active_peers.is_empty());
+
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, READ_RANGE_VERSION, 1000);
+		assert!(light_dispatch.pending_requests.is_empty());
+		assert!(light_dispatch.active_peers.contains_key(&peer1));
+	}
+
 	#[test]
 	fn receives_remote_header_response() {
 		let mut light_dispatch = dummy(true);
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		let (tx, response) = oneshot::channel();
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteHeader(RemoteHeaderRequest {
@@ -1078,7 +1207,7 @@ pub mod tests {
 		let mut light_dispatch = dummy(true);
 		let mut network_interface = DummyNetwork::default();
 		let peer0 = PeerId::random();
-		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, CURRENT_VERSION, 1000);
 
 		let (tx, response) = oneshot::channel();
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteChanges(RemoteChangesRequest {
@@ -1109,7 +1238,7 @@ pub mod tests {
 		let peer1 = PeerId::random();
 		let peer2 = PeerId::random();
 
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 100);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 100);
 
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteHeader(RemoteHeaderRequest {
 			cht_root: Default::default(),
@@ -1127,7 +1256,7 @@ pub mod tests {
 			retry_count: None,
 		}, oneshot::channel().0));
 
-		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::FULL, 150);
+		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::FULL, CURRENT_VERSION, 150);
 
 		assert_eq!(vec![peer1.clone(), peer2.clone()], light_dispatch.idle_peers.iter().cloned().collect::<Vec<_>>());
 		assert_eq!(light_dispatch.pending_requests.len(), 3);
@@ -1174,9 +1303,9 @@ pub mod tests {
 			retry_count: None,
 		}, oneshot::channel().0));
 
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 200);
-		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::FULL, 200);
-		light_dispatch.on_connect(&mut network_interface, peer3.clone(), Roles::FULL, 250);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 200);
+		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::FULL, CURRENT_VERSION, 200);
+		light_dispatch.on_connect(&mut network_interface, peer3.clone(), Roles::FULL, CURRENT_VERSION, 250);
 
 		assert_eq!(vec![peer1.clone(), peer2.clone()], light_dispatch.idle_peers.iter().cloned().collect::<Vec<_>>());
 		assert_eq!(light_dispatch.pending_requests.len(), 1);
@@ -1199,7 +1328,7 @@ pub mod tests {
 			retry_count: None,
 		}, oneshot::channel().0));
 
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 250);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 250);
 
 		assert!(light_dispatch.idle_peers.iter().cloned().collect::<Vec<_>>().is_empty());
 		assert_eq!(light_dispatch.pending_requests.len(), 1);
@@ -1212,7 +1341,7 @@ pub mod tests {
 		let peer1 = PeerId::random();
 
 		let header = dummy_header();
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 250);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 250);
 
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteBody(RemoteBodyRequest {
 			header: header.clone(),
@@ -1249,7 +1378,7 @@ pub mod tests {
 		let peer1 = PeerId::random();
 
 		let header = dummy_header();
-		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 250);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, CURRENT_VERSION, 250);
 
 		light_dispatch.add_request(&mut network_interface, RequestData::RemoteBody(RemoteBodyRequest {
 			header: header.clone(),
@@ -24,7 +24,8 @@ pub use self::generic::{
 	RemoteHeaderRequest, RemoteHeaderResponse,
 	RemoteChangesRequest, RemoteChangesResponse,
 	FinalityProofRequest, FinalityProofResponse,
-	FromBlock, RemoteReadChildRequest,
+	FromBlock, RemoteReadChildRequest, RemoteReadRangeRequest,
+	WarpSyncRequest, WarpSyncResponse, StateRequest,
 };
 
 /// A unique ID of a request.
@@ -134,6 +135,30 @@ pub struct RemoteReadResponse {
 	pub proof: Vec<Vec<u8>>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Entry of the state of a block.
+pub enum StateEntry {
+	/// A key/value pair of the main trie.
+	Top(Vec<u8>, Vec<u8>),
+	/// A key/value pair of the child trie with the given storage key.
+	Child(Vec<u8>, Vec<u8>, Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// State response.
+pub struct StateResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Entries of the state following the requested key, in order. The entries of a child trie
+	/// follow the entry of the main trie that contains its root.
+	pub entries: Vec<StateEntry>,
+	/// True if `entries` reach the end of the state.
+	pub complete: bool,
+	/// Proof of `entries` against the state root of the block, containing all the trie nodes
+	/// that are required to find them, so that no entry can be omitted.
+	pub proof: Vec<Vec<u8>>,
+}
+
 /// Generic types.
 pub mod generic {
 	use codec::{Encode, Decode, Input, Output};
@@ -142,7 +167,7 @@ pub mod generic {
 	use super::{
 		RemoteReadResponse, Transactions, Direction,
 		RequestId, BlockAttributes, RemoteCallResponse, ConsensusEngineId,
-		BlockState,
+		BlockState, StateResponse,
 	};
 	/// Consensus is mostly opaque to us
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
@@ -216,13 +241,23 @@ pub mod generic {
 		FinalityProofRequest(FinalityProofRequest<Hash>),
 		/// Finality proof reponse.
 		FinalityProofResponse(FinalityProofResponse<Hash>),
+		/// Remote storage range read request.
+		RemoteReadRangeRequest(RemoteReadRangeRequest<Hash>),
+		/// Warp sync request.
+		WarpSyncRequest(WarpSyncRequest<Hash>),
+		/// Warp sync response.
+		WarpSyncResponse(WarpSyncResponse<Hash>),
+		/// State request.
+		StateRequest(StateRequest<Hash>),
+		/// State response.
+		StateResponse(StateResponse),
 		/// Chain-specific message.
 		#[codec(index = "255")]
 		ChainSpecific(Vec<u8>),
 	}
 
 	/// Status sent on connection.
-	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	#[derive(Debug, PartialEq, Eq, Clone)]
 	pub struct Status<Hash, Number> {
 		/// Protocol version.
 		pub version: u32,
@@ -238,6 +273,41 @@ pub mod generic {
 		pub genesis_hash: Hash,
 		/// Chain-specific status.
 		pub chain_status: Vec<u8>,
+		/// Number of finalized blocks whose bodies the node keeps, or `None` if it keeps all of them.
+		pub keep_block_bodies: Option<u32>,
+	}
+
+	// Custom Encode/Decode impl to maintain backwards compatibility with nodes that keep all block
+	// bodies and don't send `keep_block_bodies`.
+	// This assumes that the packet contains nothing but the status message.
+	impl<Hash: Encode, Number: Encode> Encode for Status<Hash, Number> {
+		fn encode_to<T: Output>(&self, dest: &mut T) {
+			self.version.encode_to(dest);
+			self.min_supported_version.encode_to(dest);
+			self.roles.encode_to(dest);
+			self.best_number.encode_to(dest);
+			self.best_hash.encode_to(dest);
+			self.genesis_hash.encode_to(dest);
+			self.chain_status.encode_to(dest);
+			if let Some(keep_block_bodies) = &self.keep_block_bodies {
+				keep_block_bodies.encode_to(dest);
+			}
+		}
+	}
+
+	impl<Hash: Decode, Number: Decode> Decode for Status<Hash, Number> {
+		fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+			Ok(Status {
+				version: Decode::decode(input)?,
+				min_supported_version: Decode::decode(input)?,
+				roles: Decode::decode(input)?,
+				best_number: Decode::decode(input)?,
+				best_hash: Decode::decode(input)?,
+				genesis_hash: Decode::decode(input)?,
+				chain_status: Decode::decode(input)?,
+				keep_block_bodies: u32::decode(input).ok(),
+			})
+		}
 	}
 
 	/// Request block data from a peer.
@@ -342,6 +412,21 @@ pub mod generic {
 		pub keys: Vec<Vec<u8>>,
 	}
 
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Remote storage range read request.
+	pub struct RemoteReadRangeRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block at which to perform call.
+		pub block: H,
+		/// Prefix of the storage keys.
+		pub prefix: Vec<u8>,
+		/// Only read the keys after this key.
+		pub start_key: Option<Vec<u8>>,
+		/// Maximal number of keys to read.
+		pub count: Option<u32>,
+	}
+
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
 	/// Remote header request.
 	pub struct RemoteHeaderRequest<N> {
@@ -419,4 +504,74 @@ pub mod generic {
 		/// Finality proof (if available).
 		pub proof: Option<Vec<u8>>,
 	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Warp sync request.
+	pub struct WarpSyncRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Hash of the block from which the authority set changes must be proved: the genesis
+		/// block, or the last block of the previous proof.
+		pub begin: H,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Warp sync response.
+	pub struct WarpSyncResponse<H> {
+		/// Id of a request this response was made for.
+		pub id: RequestId,
+		/// Hash of the block (the same as in the WarpSyncRequest).
+		pub begin: H,
+		/// Proof of the authority set changes following the block (if available).
+		pub proof: Option<Vec<u8>>,
+	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Request of a part of the state of a block.
+	pub struct StateRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Block whose state is requested.
+		pub block: H,
+		/// Key of the main trie after which to start, or `None` to start from the beginning.
+		pub start_key: Option<Vec<u8>>,
+		/// If `Some`, the previous response stopped in the child trie whose root is stored at
+		/// `start_key`, and this is the key of that child trie after which to continue.
+		pub start_child_key: Option<Vec<u8>>,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use codec::{Encode, Decode};
+	use crate::config::Roles;
+	use super::generic::Status;
+
+	fn status(keep_block_bodies: Option<u32>) -> Status<u64, u64> {
+		Status {
+			version: 8,
+			min_supported_version: 3,
+			roles: Roles::FULL,
+			best_number: 100,
+			best_hash: 1,
+			genesis_hash: 2,
+			chain_status: vec![3, 4],
+			keep_block_bodies,
+		}
+	}
+
+	#[test]
+	fn status_without_keep_block_bodies_is_compatible() {
+		let encoded = status(None).encode();
+		let legacy = (8u32, 3u32, Roles::FULL, 100u64, 1u64, 2u64, vec![3u8, 4]).encode();
+		assert_eq!(encoded, legacy);
+		assert_eq!(Status::decode(&mut &legacy[..]).unwrap(), status(None));
+	}
+
+	#[test]
+	fn status_with_keep_block_bodies_roundtrips() {
+		let encoded = status(Some(256)).encode();
+		assert_eq!(encoded.len(), status(None).encode().len() + 4);
+		assert_eq!(Status::decode(&mut &encoded[..]).unwrap(), status(Some(256)));
+	}
 }
@@ -34,24 +34,27 @@ use consensus::{BlockOrigin, BlockStatus,
 	import_queue::{IncomingBlock, BlockImportResult, BlockImportError}
 };
 use crate::{
-	config::{Roles, BoxFinalityProofRequestBuilder},
-	message::{self, generic::FinalityProofRequest, BlockAnnounce, BlockAttributes, BlockRequest, BlockResponse,
-	FinalityProofResponse},
+	config::{Roles, BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier, SyncMode},
+	message::{self, generic::{FinalityProofRequest, StateRequest, WarpSyncRequest, WarpSyncResponse},
+	BlockAnnounce, BlockAttributes, BlockRequest, BlockResponse, FinalityProofResponse, StateResponse},
 	protocol
 };
 use either::Either;
+use futures::Async;
 use extra_requests::ExtraRequests;
 use libp2p::PeerId;
 use log::{debug, trace, warn, info, error};
 use sr_primitives::{
 	Justification,
 	generic::BlockId,
-	traits::{Block as BlockT, Header, NumberFor, Zero, One, CheckedSub, SaturatedConversion}
+	traits::{Block as BlockT, Header, NumberFor, Zero, One, CheckedSub, SaturatedConversion, Saturating}
 };
 use std::{fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc};
+use warp::{WarpSync, ImportOutcome};
 
 mod blocks;
 mod extra_requests;
+mod warp;
 
 /// Maximum blocks to request in a single packet.
 const MAX_BLOCKS_TO_REQUEST: usize = 128;
@@ -93,6 +96,9 @@ const BAD_BLOCK_REPUTATION_CHANGE: i32 = -(1 << 29);
 /// Reputation change for peers which send us a block with bad justifications.
 const BAD_JUSTIFICATION_REPUTATION_CHANGE: i32 = -(1 << 16);
 
+/// Reputation change for peers which send us an invalid warp sync proof, state or historic block.
+const BAD_WARP_SYNC_DATA_REPUTATION_CHANGE: i32 = -(1 << 20);
+
 /// The main data structure which contains all the state for a chains
 /// active syncing strategy.
 pub struct ChainSync<B: BlockT> {
@@ -127,7 +133,11 @@ pub struct ChainSync<B: BlockT> {
 	/// A flag that caches idle state with no pending requests.
 	is_idle: bool,
 	/// A type to check incoming block announcements.
-	block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>
+	block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
+	/// Warp sync in progress, if any.
+	warp_sync: Option<WarpSync<B>>,
+	/// The last block whose body none of the peers keeps, to warn about it once.
+	missing_body: Option<NumberFor<B>>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -145,7 +155,12 @@ pub struct PeerSync<B: BlockT> {
 	pub state: PeerSyncState<B>,
 	/// A queue of blocks that this peer has announced to us, should only
 	/// contain `ANNOUNCE_HISTORY_SIZE` entries.
-	pub recently_announced: VecDeque<B::Hash>
+	pub recently_announced: VecDeque<B::Hash>,
+	/// Number of finalized blocks whose bodies the peer keeps, or `None` if
+	/// it keeps all of them.
+	pub keep_block_bodies: Option<u32>,
+	/// Version of the protocol used by the peer.
+	pub protocol_version: u32,
 }
 
 /// The sync status of a peer we are trying to sync with
@@ -182,7 +197,15 @@ pub enum PeerSyncState<B: BlockT> {
 	/// Downloading justification for given block hash.
 	DownloadingJustification(B::Hash),
 	/// Downloading finality proof for given block hash.
-	DownloadingFinalityProof(B::Hash)
+	DownloadingFinalityProof(B::Hash),
+	/// Downloading the proof of the authority set changes that follow the
+	/// given block hash.
+	DownloadingWarpProof(B::Hash),
+	/// Downloading the state of the given block hash.
+	DownloadingState(B::Hash),
+	/// Downloading the blocks below the target of a warp sync, starting from
+	/// the given Number.
+	DownloadingHistoric(NumberFor<B>),
 }
 
 impl<B: BlockT> PeerSyncState<B> {
@@ -282,6 +305,8 @@ impl<B: BlockT> ChainSync<B> {
 		client: Arc<dyn crate::chain::Client<B>>,
 		info: &ClientInfo<B>,
 		request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
+		sync_mode: SyncMode,
+		warp_sync_verifier: Option<BoxWarpSyncVerifier<B>>,
 		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>
 	) -> Self {
 		let mut required_block_attributes = BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION;
@@ -290,6 +315,26 @@ impl<B: BlockT> ChainSync<B> {
 			required_block_attributes |= BlockAttributes::BODY
 		}
 
+		let warp_sync = match (sync_mode, warp_sync_verifier) {
+			(SyncMode::Full, _) => None,
+			(SyncMode::Warp { .. }, None) => {
+				warn!(target: "sync", "Warp sync isn't supported by this chain, syncing every block instead");
+				None
+			}
+			(SyncMode::Warp { .. }, Some(_)) if !role.is_full() || !info.chain.best_number.is_zero() => {
+				info!(target: "sync", "Only full nodes with an empty database can warp sync, syncing every block instead");
+				None
+			}
+			(SyncMode::Warp { backfill }, Some(verifier)) =>
+				match WarpSync::new(client.clone(), verifier, backfill, info.chain.genesis_hash) {
+					Ok(warp_sync) => Some(warp_sync),
+					Err(e) => {
+						warn!(target: "sync", "Failed to start the warp sync import thread, syncing every block instead: {}", e);
+						None
+					}
+				},
+		};
+
 		ChainSync {
 			client,
 			peers: HashMap::new(),
@@ -306,6 +351,8 @@ impl<B: BlockT> ChainSync<B> {
 			fork_targets: Default::default(),
 			is_idle: false,
 			block_announce_validator,
+			warp_sync,
+			missing_body: None,
 		}
 	}
 
@@ -348,6 +395,21 @@ impl<B: BlockT> ChainSync<B> {
 		if !info.roles.is_full() {
 			return Ok(None)
 		}
+		// Our chain is empty until the warp sync imports the state of its target block, after
+		// which the common numbers are updated by `on_block_queued`.
+		if self.is_warp_syncing() {
+			debug!(target:"sync", "New peer with best hash {} ({}) during warp sync.", info.best_hash, info.best_number);
+			self.peers.insert(who, PeerSync {
+				common_number: Zero::zero(),
+				best_hash: info.best_hash,
+				best_number: info.best_number,
+				state: PeerSyncState::Available,
+				recently_announced: Default::default(),
+				keep_block_bodies: info.keep_block_bodies,
+				protocol_version: info.protocol_version,
+			});
+			return Ok(None)
+		}
 		match self.block_status(&info.best_hash) {
 			Err(e) => {
 				debug!(target:"sync", "Error reading blockchain: {:?}", e);
@@ -377,7 +439,9 @@ impl<B: BlockT> ChainSync<B> {
 						best_hash: info.best_hash,
 						best_number: info.best_number,
 						state: PeerSyncState::Available,
-						recently_announced: Default::default()
+						recently_announced: Default::default(),
+						keep_block_bodies: info.keep_block_bodies,
+						protocol_version: info.protocol_version,
 					});
 					return Ok(None)
 				}
@@ -391,6 +455,8 @@ impl<B: BlockT> ChainSync<B> {
 						best_number: info.best_number,
 						state: PeerSyncState::Available,
 						recently_announced: Default::default(),
+						keep_block_bodies: info.keep_block_bodies,
+						protocol_version: info.protocol_version,
 					});
 					return Ok(self.select_new_blocks(who).map(|(_, req)| req))
 				}
@@ -411,7 +477,9 @@ impl<B: BlockT> ChainSync<B> {
 						common_best,
 						AncestorSearchState::ExponentialBackoff(One::one())
 					),
-					recently_announced: Default::default()
+					recently_announced: Default::default(),
+					keep_block_bodies: info.keep_block_bodies,
+					protocol_version: info.protocol_version,
 				});
 				self.is_idle = false;
 
@@ -425,6 +493,8 @@ impl<B: BlockT> ChainSync<B> {
 					best_number: info.best_number,
 					state: PeerSyncState::Available,
 					recently_announced: Default::default(),
+					keep_block_bodies: info.keep_block_bodies,
+					protocol_version: info.protocol_version,
 				});
 				self.is_idle = false;
 				Ok(None)
@@ -548,15 +618,74 @@ impl<B: BlockT> ChainSync<B> {
 		})
 	}
 
+	/// Returns true while a warp sync downloads the state of its target block, during which the
+	/// blocks aren't synced.
+	fn is_warp_syncing(&self) -> bool {
+		self.warp_sync.as_ref().map_or(false, |warp_sync| warp_sync.is_downloading())
+	}
+
+	/// Get the warp sync proof request to send, if any.
+	pub fn warp_sync_request(&mut self) -> Option<(PeerId, WarpSyncRequest<B::Hash>)> {
+		let warp_sync = self.warp_sync.as_ref()?;
+		let request = warp_sync.proof_request()?;
+		if self.peers.values().any(|p| p.state == PeerSyncState::DownloadingWarpProof(request.begin)) {
+			return None
+		}
+		let (who, peer) = self.peers.iter_mut()
+			.filter(|(who, peer)| peer.state.is_available()
+				&& peer.protocol_version >= protocol::WARP_SYNC_VERSION
+				&& warp_sync.is_usable(who))
+			.max_by_key(|(_, peer)| peer.best_number)?;
+		trace!(target: "sync", "Requesting warp sync proof from {} starting at {}", who, request.begin);
+		peer.state = PeerSyncState::DownloadingWarpProof(request.begin);
+		Some((who.clone(), request))
+	}
+
+	/// Get the request for the next part of the state of the warp sync target, if any.
+	pub fn state_request(&mut self) -> Option<(PeerId, StateRequest<B::Hash>)> {
+		let warp_sync = self.warp_sync.as_ref()?;
+		let (number, request) = warp_sync.state_request()?;
+		if self.peers.values().any(|p| p.state == PeerSyncState::DownloadingState(request.block)) {
+			return None
+		}
+		let (who, peer) = self.peers.iter_mut()
+			.find(|(who, peer)| peer.state.is_available()
+				&& peer.protocol_version >= protocol::WARP_SYNC_VERSION
+				&& peer.best_number >= number
+				&& warp_sync.is_usable(who))?;
+		trace!(target: "sync", "Requesting state of {} from {}", request.block, who);
+		peer.state = PeerSyncState::DownloadingState(request.block);
+		Some((who.clone(), request))
+	}
+
+	/// Get the request for the next historic blocks to backfill after a warp sync, if any.
+	pub fn historic_block_request(&mut self) -> Option<(PeerId, BlockRequest<B>)> {
+		let warp_sync = self.warp_sync.as_ref()?;
+		let (number, request) = warp_sync.backfill_request()?;
+		if self.peers.values().any(|p| p.state == PeerSyncState::DownloadingHistoric(number)) {
+			return None
+		}
+		// Only the peers that keep all the block bodies can serve historic blocks.
+		let (who, peer) = self.peers.iter_mut()
+			.find(|(_, peer)| peer.state.is_available()
+				&& peer.keep_block_bodies.is_none()
+				&& peer.common_number >= number)?;
+		trace!(target: "sync", "Requesting historic blocks from #{} from {}", number, who);
+		peer.state = PeerSyncState::DownloadingHistoric(number);
+		Some((who.clone(), request))
+	}
+
 	/// Get an iterator over all block requests of all peers.
 	pub fn block_requests(&mut self) -> impl Iterator<Item = (PeerId, BlockRequest<B>)> + '_ {
-		if self.is_idle {
+		if self.is_idle || self.is_warp_syncing() {
 			return Either::Left(std::iter::empty())
 		}
 		if self.queue_blocks.len() > MAX_IMPORTING_BLOCKS {
 			trace!(target: "sync", "Too many blocks in the queue.");
 			return Either::Left(std::iter::empty())
 		}
+		let body_sources = body_sources(&self.peers);
+		let missing_body = &mut self.missing_body;
 		let blocks = &mut self.blocks;
 		let attrs = &self.required_block_attributes;
 		let fork_targets = &self.fork_targets;
@@ -586,7 +715,9 @@ impl<B: BlockT> ChainSync<B> {
 				peer.state = PeerSyncState::DownloadingStale(hash);
 				have_requests = true;
 				Some((id.clone(), req))
-			} else if let Some((range, req)) = peer_block_request(id, peer, blocks, attrs) {
+			} else if let Some((range, req)) =
+				peer_block_request(id, peer, blocks, attrs, &body_sources, missing_body)
+			{
 				peer.state = PeerSyncState::DownloadingNew(range.start);
 				trace!(target: "sync", "New block request for {}", id);
 				have_requests = true;
@@ -700,9 +831,22 @@ impl<B: BlockT> ChainSync<B> {
 						}
 					}
 
+					PeerSyncState::DownloadingHistoric(_) => {
+						peer.state = PeerSyncState::Available;
+						if let Some(warp_sync) = self.warp_sync.as_mut() {
+							if let Err(e) = warp_sync.on_historic_blocks(blocks) {
+								debug!(target: "sync", "Invalid historic blocks from {}: {}", who, e);
+								return Err(BadPeer(who, BAD_WARP_SYNC_DATA_REPUTATION_CHANGE))
+							}
+						}
+						Vec::new()
+					}
+
 					| PeerSyncState::Available
 					| PeerSyncState::DownloadingJustification(..)
-					| PeerSyncState::DownloadingFinalityProof(..) => Vec::new()
+					| PeerSyncState::DownloadingFinalityProof(..)
+					| PeerSyncState::DownloadingWarpProof(..)
+					| PeerSyncState::DownloadingState(..) => Vec::new()
 				}
 			} else {
 				Vec::new()
@@ -809,6 +953,187 @@ impl<B: BlockT> ChainSync<B> {
 		Ok(OnBlockFinalityProof::Nothing)
 	}
 
+
+	/// Handle a response from the remote to a warp sync proof request that we made.
+	pub fn on_warp_sync_data(&mut self, who: PeerId, response: WarpSyncResponse<B::Hash>) -> Result<(), BadPeer> {
+		let peer =
+			if let Some(peer) = self.peers.get_mut(&who) {
+				peer
+			} else {
+				error!(target: "sync", "Called on_warp_sync_data with a bad peer ID");
+				return Ok(())
+			};
+		let begin = match peer.state {
+			PeerSyncState::DownloadingWarpProof(begin) => begin,
+			_ => return Ok(()),
+		};
+		peer.state = PeerSyncState::Available;
+
+		let warp_sync = match self.warp_sync.as_mut() {
+			Some(warp_sync) => warp_sync,
+			None => return Ok(()),
+		};
+		if response.begin != begin {
+			debug!(target: "sync", "Warp sync proof from {} starts at an unexpected block", who);
+			warp_sync.on_peer_failed(who.clone());
+			self.check_warp_sync_failed();
+			return Err(BadPeer(who, BAD_WARP_SYNC_DATA_REPUTATION_CHANGE))
+		}
+		let result = match response.proof {
+			None => {
+				debug!(target: "sync", "{} can't prove the authority set changes after {}", who, begin);
+				warp_sync.on_peer_failed(who);
+				Ok(())
+			}
+			Some(proof) => match warp_sync.on_proof(begin, &proof) {
+				Ok(()) => Ok(()),
+				Err(e) => {
+					debug!(target: "sync", "Invalid warp sync proof from {}: {}", who, e);
+					warp_sync.on_peer_failed(who.clone());
+					Err(BadPeer(who, BAD_WARP_SYNC_DATA_REPUTATION_CHANGE))
+				}
+			},
+		};
+		self.check_warp_sync_failed();
+		result
+	}
+
+	/// Handle a response from the remote to a state request that we made.
+	///
+	/// Each part of the state of the warp sync target is checked and stored by the import thread
+	/// of the warp sync, whose outcome is processed by `poll_warp_sync_imports`.
+	pub fn on_state_data(&mut self, who: PeerId, response: StateResponse) -> Result<(), BadPeer> {
+		let peer =
+			if let Some(peer) = self.peers.get_mut(&who) {
+				peer
+			} else {
+				error!(target: "sync", "Called on_state_data with a bad peer ID");
+				return Ok(())
+			};
+		if let PeerSyncState::DownloadingState(_) = peer.state {
+			peer.state = PeerSyncState::Available;
+		} else {
+			return Ok(())
+		}
+
+		let warp_sync = match self.warp_sync.as_mut() {
+			Some(warp_sync) => warp_sync,
+			None => return Ok(()),
+		};
+		// An empty response means that the peer doesn't have the state, e.g. because it was pruned.
+		if response.entries.is_empty() && !response.complete {
+			debug!(target: "sync", "{} doesn't have the state of the warp sync target", who);
+			warp_sync.on_peer_failed(who);
+			self.check_warp_sync_failed();
+			return Ok(())
+		}
+		if let Err(e) = warp_sync.on_state(who.clone(), response) {
+			debug!(target: "sync", "Invalid state response from {}: {}", who, e);
+			warp_sync.on_peer_failed(who.clone());
+			self.check_warp_sync_failed();
+			return Err(BadPeer(who, BAD_WARP_SYNC_DATA_REPUTATION_CHANGE))
+		}
+		Ok(())
+	}
+
+	/// Process the outcomes of the imports of the state and of the historic blocks downloaded
+	/// during a warp sync, which run on a separate thread so that they don't block the network.
+	///
+	/// Returns the peers that provided an invalid part of the state.
+	pub fn poll_warp_sync_imports(&mut self) -> Vec<BadPeer> {
+		let mut bad_peers = Vec::new();
+		loop {
+			let outcome = match self.warp_sync.as_mut().map(|warp_sync| warp_sync.poll_import()) {
+				Some(Ok(Async::Ready(Some(outcome)))) => outcome,
+				Some(Ok(Async::Ready(None))) | Some(Err(())) => {
+					error!(target: "sync", "Warp sync import thread stopped unexpectedly");
+					self.abandon_warp_sync();
+					break
+				}
+				Some(Ok(Async::NotReady)) | None => break,
+			};
+			if let Some(bad_peer) = self.on_warp_sync_import(outcome) {
+				bad_peers.push(bad_peer);
+			}
+		}
+		bad_peers
+	}
+
+	/// Process the outcome of an import of data downloaded during a warp sync.
+	fn on_warp_sync_import(&mut self, outcome: ImportOutcome<B>) -> Option<BadPeer> {
+		let warp_sync = self.warp_sync.as_mut()?;
+		match outcome {
+			ImportOutcome::StateChunk(_, Ok(())) => {
+				warp_sync.on_state_chunk_imported();
+				None
+			}
+			ImportOutcome::StateChunk(who, Err(ClientError::InvalidStateProof(e))) => {
+				debug!(target: "sync", "Invalid state response from {}: {}", who, e);
+				warp_sync.on_state_chunk_invalid();
+				warp_sync.on_peer_failed(who.clone());
+				self.check_warp_sync_failed();
+				Some(BadPeer(who, BAD_WARP_SYNC_DATA_REPUTATION_CHANGE))
+			}
+			ImportOutcome::StateChunk(_, Err(e)) => {
+				error!(target: "sync", "Failed to store the state of the warp sync target: {:?}", e);
+				self.abandon_warp_sync();
+				None
+			}
+			ImportOutcome::State(header, Ok(())) => {
+				let (hash, number) = (header.hash(), *header.number());
+				if let Err(e) = warp_sync.on_state_imported(&header) {
+					error!(target: "sync", "Failed to apply the authority set of the warp sync target: {}", e);
+				}
+				if warp_sync.is_done() {
+					self.warp_sync = None;
+				}
+				info!(target: "sync", "Warp sync reached #{} ({}), syncing the following blocks", number, hash);
+				self.is_idle = false;
+				self.on_block_queued(&hash, number);
+				None
+			}
+			ImportOutcome::State(header, Err(e)) => {
+				error!(target: "sync", "Failed to import #{} ({}): {:?}", header.number(), header.hash(), e);
+				self.abandon_warp_sync();
+				None
+			}
+			ImportOutcome::HistoricBlocks(result) => {
+				warp_sync.on_historic_blocks_imported();
+				if let Err((header, e)) = result {
+					warn!(
+						target: "sync",
+						"Failed to import historic block #{} ({}): {:?}",
+						header.number(),
+						header.hash(),
+						e,
+					);
+					// The blocks below it couldn't be imported either.
+					warp_sync.stop_backfill();
+				}
+				if warp_sync.is_done() {
+					info!(target: "sync", "Warp sync complete, all historic blocks imported");
+					self.warp_sync = None;
+				}
+				None
+			}
+		}
+	}
+
+	/// Abandons the warp sync if too many peers failed to provide a valid proof or state.
+	fn check_warp_sync_failed(&mut self) {
+		if self.warp_sync.as_ref().map_or(false, |warp_sync| warp_sync.has_failed()) {
+			warn!(target: "sync", "Too many peers failed to provide a valid warp sync proof or state");
+			self.abandon_warp_sync();
+		}
+	}
+
+	/// Stops the warp sync. Every block is synced from genesis instead.
+	fn abandon_warp_sync(&mut self) {
+		warn!(target: "sync", "Abandoning warp sync, syncing every block instead");
+		self.warp_sync = None;
+		self.is_idle = false;
+	}
+
 	/// A batch of blocks have been processed, with or without errors.
 	///
 	/// Call this when a batch of blocks have been processed by the import
@@ -1121,6 +1446,7 @@ impl<B: BlockT> ChainSync<B> {
 			return None
 		}
 
+		let body_sources = body_sources(&self.peers);
 		let peer = self.peers.get_mut(&who)?;
 
 		if !peer.state.is_available() {
@@ -1136,7 +1462,14 @@ impl<B: BlockT> ChainSync<B> {
 			peer.best_number
 		);
 
-		if let Some((range, req)) = peer_block_request(&who, peer, &mut self.blocks, &self.required_block_attributes) {
+		if let Some((range, req)) = peer_block_request(
+			&who,
+			peer,
+			&mut self.blocks,
+			&self.required_block_attributes,
+			&body_sources,
+			&mut self.missing_body,
+		) {
 			trace!(target: "sync", "Requesting blocks from {}, ({} to {})", who, range.start, range.end);
 			peer.state = PeerSyncState::DownloadingNew(range.start);
 			Some((range, req))
@@ -1238,14 +1571,51 @@ fn handle_ancestor_search_state<B: BlockT>(
 	}
 }
 
+/// The best block and the number of kept block bodies of every peer, see `keeps_block_body`.
+fn body_sources<B: BlockT>(peers: &HashMap<PeerId, PeerSync<B>>) -> Vec<(NumberFor<B>, Option<u32>)> {
+	peers.values().map(|peer| (peer.best_number, peer.keep_block_bodies)).collect()
+}
+
+/// Whether a peer with the given best block, that deletes the bodies of blocks finalized more
+/// than `keep_block_bodies` blocks ago, still has the body of block `number`. Its finalized block
+/// is at most its best block.
+fn keeps_block_body<B: BlockT>(
+	best_number: NumberFor<B>,
+	keep_block_bodies: Option<u32>,
+	number: NumberFor<B>,
+) -> bool {
+	keep_block_bodies.map_or(true, |keep| number > best_number.saturating_sub(keep.into()))
+}
+
 /// Get a new block request for the peer if any.
+///
+/// Blocks whose body the peer doesn't keep are left to the other peers in `body_sources`. If none
+/// of them keeps it either, a warning is logged once per block in `missing_body`.
 fn peer_block_request<B: BlockT>(
 	id: &PeerId,
 	peer: &PeerSync<B>,
 	blocks: &mut BlockCollection<B>,
 	attrs: &message::BlockAttributes,
+	body_sources: &[(NumberFor<B>, Option<u32>)],
+	missing_body: &mut Option<NumberFor<B>>,
 ) -> Option<(Range<NumberFor<B>>, BlockRequest<B>)> {
 	if let Some(range) = blocks.needed_blocks(id.clone(), MAX_BLOCKS_TO_REQUEST, peer.best_number, peer.common_number) {
+		if attrs.contains(BlockAttributes::BODY) &&
+			!keeps_block_body::<B>(peer.best_number, peer.keep_block_bodies, range.start)
+		{
+			blocks.clear_peer_download(id);
+			if body_sources.iter().any(|(best, keep)| keeps_block_body::<B>(*best, *keep, range.start)) {
+				trace!(target: "sync", "Peer {} doesn't keep the body of block #{}, leaving it to other peers", id, range.start);
+			} else if *missing_body != Some(range.start) {
+				warn!(
+					target: "sync",
+					"None of the connected peers keeps the body of block #{}, waiting for a peer that does",
+					range.start,
+				);
+				*missing_body = Some(range.start);
+			}
+			return None;
+		}
 		let request = message::generic::BlockRequest {
 			id: 0,
 			fields: attrs.clone(),
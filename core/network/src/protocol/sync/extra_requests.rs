@@ -489,7 +489,9 @@ mod tests {
 				best_hash: Hash::random(),
 				best_number: g.gen(),
 				state: ArbitraryPeerSyncState::arbitrary(g).0,
-				recently_announced: Default::default()
+				recently_announced: Default::default(),
+				keep_block_bodies: None,
+				protocol_version: 0,
 			};
 			ArbitraryPeerSync(ps)
 		}
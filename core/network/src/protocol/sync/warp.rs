@@ -0,0 +1,442 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State of a warp sync.
+//!
+//! A warp sync downloads the proofs of the authority set changes since genesis, up to a recent
+//! finalized block whose justification they prove, then the state of that block. The block is
+//! imported without its ancestry, and the following blocks are synced normally. The blocks below
+//! it can then be downloaded from the most recent to the oldest, which is called backfilling.
+
+use codec::Encode;
+use client::error::Error as ClientError;
+use crate::{
+	chain::Client,
+	config::{BoxWarpSyncVerifier, WarpSyncProgress},
+	message::{self, BlockAttributes, StateEntry, StateResponse, generic::{StateRequest, WarpSyncRequest}},
+};
+use futures::{prelude::*, sync::mpsc as futures_mpsc};
+use libp2p::PeerId;
+use log::{debug, info};
+use sr_primitives::{
+	Justification,
+	traits::{Block as BlockT, Header as HeaderT, Hash as HashT, HashFor, NumberFor, One, Zero},
+};
+use std::{collections::HashSet, io, sync::{Arc, mpsc}, thread};
+
+/// Number of peers that may fail to provide a valid proof or state before we give up the warp
+/// sync and sync every block instead.
+const MAX_FAILED_PEERS: usize = 5;
+
+/// Maximum number of blocks to request at once while backfilling.
+const MAX_BACKFILL_BLOCKS: u32 = 128;
+
+/// Header, body and justification of a block to import below the target of the warp sync.
+pub(crate) type HistoricBlock<B> = (
+	<B as BlockT>::Header,
+	Option<Vec<<B as BlockT>::Extrinsic>>,
+	Option<Justification>,
+);
+
+/// Progress of a warp sync.
+enum Phase<B: BlockT> {
+	/// Downloading the proof of the authority set changes that follow the given block.
+	Proofs(B::Hash),
+	/// Downloading the state of the target block.
+	State(StateDownload<B>),
+	/// The whole state is stored and the target block is being imported.
+	Importing,
+	/// Downloading the blocks below the target, from the block with the given hash and number
+	/// down to genesis.
+	Backfill(B::Hash, NumberFor<B>),
+	/// The warp sync is over.
+	Done,
+}
+
+/// State of the target block being downloaded. Each part of the state is checked and stored as
+/// soon as it is received, so only the position of the next part is kept.
+struct StateDownload<B: BlockT> {
+	header: B::Header,
+	justification: Justification,
+	/// Key of the main trie of the last entry stored.
+	start_key: Option<Vec<u8>>,
+	/// Key of the last entry stored if it belongs to the child trie stored at `start_key`.
+	start_child_key: Option<Vec<u8>>,
+	/// Position after the part of the state being checked and stored, if any, and whether it
+	/// reaches the end of the state.
+	importing: Option<(Option<Vec<u8>>, Option<Vec<u8>>, bool)>,
+}
+
+impl<B: BlockT> StateDownload<B> {
+	fn new(header: B::Header, justification: Justification) -> Self {
+		StateDownload {
+			header,
+			justification,
+			start_key: None,
+			start_child_key: None,
+			importing: None,
+		}
+	}
+}
+
+/// Data downloaded during a warp sync, to be imported on the import thread.
+enum ImportTask<B: BlockT> {
+	/// A part of the state of the target block, received from the given peer, that follows the
+	/// given keys.
+	StateChunk {
+		who: PeerId,
+		state_root: B::Hash,
+		start_key: Option<Vec<u8>>,
+		start_child_key: Option<Vec<u8>>,
+		response: StateResponse,
+	},
+	/// The target block, whose whole state is stored.
+	State(B::Header, Justification),
+	/// Blocks below the target, from the most recent to the oldest.
+	HistoricBlocks(Vec<HistoricBlock<B>>),
+}
+
+/// Outcome of the import of data downloaded during a warp sync, in the order of the imports.
+pub(crate) enum ImportOutcome<B: BlockT> {
+	/// A part of the state received from the given peer was checked and stored, or not.
+	StateChunk(PeerId, Result<(), ClientError>),
+	/// The target block was imported, or not.
+	State(B::Header, Result<(), ClientError>),
+	/// Blocks below the target were imported, or the import failed at the given block and
+	/// the following ones were skipped.
+	HistoricBlocks(Result<(), (B::Header, ClientError)>),
+}
+
+/// Warp sync in progress.
+pub(crate) struct WarpSync<B: BlockT> {
+	verifier: BoxWarpSyncVerifier<B>,
+	backfill: bool,
+	phase: Phase<B>,
+	/// Peers that failed to provide a valid proof or state. They aren't asked again.
+	failed_peers: HashSet<PeerId>,
+	/// Sends the downloaded data to the import thread, so that it doesn't block the network.
+	import_tasks: mpsc::Sender<ImportTask<B>>,
+	/// Outcomes of the imports, sent back by the import thread.
+	import_outcomes: futures_mpsc::UnboundedReceiver<ImportOutcome<B>>,
+	/// Number of batches of historic blocks sent to the import thread that aren't imported yet.
+	pending_historic_imports: usize,
+}
+
+impl<B: BlockT> WarpSync<B> {
+	/// Starts a warp sync from the genesis block, with a thread that imports the downloaded data
+	/// into `client`.
+	pub(crate) fn new(
+		client: Arc<dyn Client<B>>,
+		verifier: BoxWarpSyncVerifier<B>,
+		backfill: bool,
+		genesis_hash: B::Hash,
+	) -> io::Result<Self> {
+		let (import_tasks, tasks) = mpsc::channel();
+		let (outcomes, import_outcomes) = futures_mpsc::unbounded();
+		thread::Builder::new()
+			.name("warp-sync-import".into())
+			.spawn(move || import_thread(client, tasks, outcomes))?;
+
+		Ok(WarpSync {
+			verifier,
+			backfill,
+			phase: Phase::Proofs(genesis_hash),
+			failed_peers: HashSet::new(),
+			import_tasks,
+			import_outcomes,
+			pending_historic_imports: 0,
+		})
+	}
+
+	/// Returns true until the state of the target block is imported. The regular sync is paused
+	/// in the meantime.
+	pub(crate) fn is_downloading(&self) -> bool {
+		match self.phase {
+			Phase::Proofs(_) | Phase::State(_) | Phase::Importing => true,
+			Phase::Backfill(..) | Phase::Done => false,
+		}
+	}
+
+	/// Returns true once the warp sync is over, including the backfill.
+	pub(crate) fn is_done(&self) -> bool {
+		match self.phase {
+			Phase::Done => self.pending_historic_imports == 0,
+			_ => false,
+		}
+	}
+
+	/// Returns true if too many peers failed to provide a valid proof or state, in which case
+	/// the warp sync should be abandoned.
+	pub(crate) fn has_failed(&self) -> bool {
+		self.is_downloading() && self.failed_peers.len() >= MAX_FAILED_PEERS
+	}
+
+	/// Returns true if requests can be sent to the given peer.
+	pub(crate) fn is_usable(&self, who: &PeerId) -> bool {
+		!self.failed_peers.contains(who)
+	}
+
+	/// Notes that the given peer failed to provide a valid proof or state.
+	pub(crate) fn on_peer_failed(&mut self, who: PeerId) {
+		self.failed_peers.insert(who);
+	}
+
+	/// Returns the next proof request to send, if any.
+	pub(crate) fn proof_request(&self) -> Option<WarpSyncRequest<B::Hash>> {
+		match self.phase {
+			Phase::Proofs(begin) => Some(WarpSyncRequest { id: 0, begin }),
+			_ => None,
+		}
+	}
+
+	/// Returns the next state request to send, if any, with the number of the target block.
+	/// Nothing is requested while the last part of the state received is being imported.
+	pub(crate) fn state_request(&self) -> Option<(NumberFor<B>, StateRequest<B::Hash>)> {
+		match self.phase {
+			Phase::State(ref state) if state.importing.is_none() => Some((*state.header.number(), StateRequest {
+				id: 0,
+				block: state.header.hash(),
+				start_key: state.start_key.clone(),
+				start_child_key: state.start_child_key.clone(),
+			})),
+			_ => None,
+		}
+	}
+
+	/// Returns the next backfill request to send, if any, with the number of the first block it
+	/// requests.
+	pub(crate) fn backfill_request(&self) -> Option<(NumberFor<B>, message::BlockRequest<B>)> {
+		match self.phase {
+			Phase::Backfill(hash, number) => Some((number, message::generic::BlockRequest {
+				id: 0,
+				fields: BlockAttributes::HEADER | BlockAttributes::BODY | BlockAttributes::JUSTIFICATION,
+				from: message::FromBlock::Hash(hash),
+				to: None,
+				direction: message::Direction::Descending,
+				max: Some(MAX_BACKFILL_BLOCKS),
+			})),
+			_ => None,
+		}
+	}
+
+	/// Verifies the proof of the authority set changes that follow the block `begin`.
+	pub(crate) fn on_proof(&mut self, begin: B::Hash, proof: &[u8]) -> Result<(), String> {
+		match self.phase {
+			Phase::Proofs(expected) if expected == begin => {},
+			_ => return Err("Unexpected proof".into()),
+		}
+
+		match self.verifier.verify(&begin, proof)? {
+			WarpSyncProgress::Partial(next) if next == begin =>
+				return Err("Proof doesn't prove any change".into()),
+			WarpSyncProgress::Partial(next) => self.phase = Phase::Proofs(next),
+			WarpSyncProgress::Complete(header, justification) =>
+				self.phase = Phase::State(StateDownload::new(header, justification)),
+		}
+		Ok(())
+	}
+
+	/// Sends a part of the state of the target block, received from `who`, to the import thread
+	/// to be checked against the state root of the block and stored.
+	pub(crate) fn on_state(&mut self, who: PeerId, response: StateResponse) -> Result<(), &'static str> {
+		let state = match self.phase {
+			Phase::State(ref mut state) if state.importing.is_none() => state,
+			_ => return Err("Unexpected state"),
+		};
+
+		let (next_key, next_child_key) = match response.entries.last() {
+			Some(StateEntry::Top(key, _)) => (Some(key.clone()), None),
+			Some(StateEntry::Child(storage_key, key, _)) => (Some(storage_key.clone()), Some(key.clone())),
+			None => (state.start_key.clone(), state.start_child_key.clone()),
+		};
+		state.importing = Some((next_key, next_child_key, response.complete));
+		let task = ImportTask::StateChunk {
+			who,
+			state_root: *state.header.state_root(),
+			start_key: state.start_key.clone(),
+			start_child_key: state.start_child_key.clone(),
+			response,
+		};
+		self.send_import(task);
+		Ok(())
+	}
+
+	/// Called when the part of the state sent to the import thread by `on_state` has been stored.
+	/// Once the whole state is stored, the target block is imported.
+	pub(crate) fn on_state_chunk_imported(&mut self) {
+		let complete = match self.phase {
+			Phase::State(ref mut state) => match state.importing.take() {
+				Some((start_key, start_child_key, complete)) => {
+					state.start_key = start_key;
+					state.start_child_key = start_child_key;
+					complete
+				},
+				None => false,
+			},
+			_ => false,
+		};
+		if !complete {
+			return
+		}
+
+		match std::mem::replace(&mut self.phase, Phase::Importing) {
+			Phase::State(state) => {
+				info!(
+					target: "sync",
+					"Importing #{} ({}), whose state is stored",
+					state.header.number(),
+					state.header.hash(),
+				);
+				self.send_import(ImportTask::State(state.header, state.justification));
+			},
+			_ => unreachable!("the state is only complete in the state phase; qed"),
+		}
+	}
+
+	/// Called when the part of the state sent to the import thread by `on_state` was invalid. It
+	/// is requested again from another peer.
+	pub(crate) fn on_state_chunk_invalid(&mut self) {
+		if let Phase::State(ref mut state) = self.phase {
+			state.importing = None;
+		}
+	}
+
+	/// Called when the target block, whose state was stored, has been imported. The authority set
+	/// of the target block becomes the current one.
+	pub(crate) fn on_state_imported(&mut self, header: &B::Header) -> Result<(), String> {
+		self.verifier.apply(header)?;
+		self.phase = if self.backfill && *header.number() > One::one() {
+			Phase::Backfill(*header.parent_hash(), *header.number() - One::one())
+		} else {
+			Phase::Done
+		};
+		Ok(())
+	}
+
+	/// Stops downloading the blocks below the target.
+	pub(crate) fn stop_backfill(&mut self) {
+		if let Phase::Backfill(..) = self.phase {
+			self.phase = Phase::Done;
+		}
+	}
+
+	/// Checks blocks received while backfilling, in ascending order, and sends the ones that
+	/// follow the last block received to the import thread, from the most recent to the oldest.
+	///
+	/// Only the justifications that the verifier can check are kept.
+	pub(crate) fn on_historic_blocks(&mut self, blocks: Vec<message::BlockData<B>>) -> Result<(), &'static str> {
+		let (mut expected_hash, mut expected_number) = match self.phase {
+			Phase::Backfill(hash, number) => (hash, number),
+			_ => return Err("Unexpected blocks"),
+		};
+
+		let mut output = Vec::with_capacity(blocks.len());
+		for block in blocks.into_iter().rev() {
+			let header = block.header.ok_or("Missing header")?;
+			if header.hash() != expected_hash || *header.number() != expected_number {
+				return Err("Unexpected block")
+			}
+			let body = block.body.ok_or("Missing body")?;
+			let extrinsics_root = HashFor::<B>::ordered_trie_root(body.iter().map(Encode::encode).collect());
+			if extrinsics_root != *header.extrinsics_root() {
+				return Err("Body doesn't match the header")
+			}
+			let justification = match block.justification {
+				Some(justification) => match self.verifier.check_historic_justification(&header, &justification) {
+					Ok(true) => Some(justification),
+					Ok(false) => None,
+					Err(e) => {
+						debug!(target: "sync", "Invalid justification of historic block #{}: {}", expected_number, e);
+						return Err("Invalid justification")
+					},
+				},
+				None => None,
+			};
+
+			expected_hash = *header.parent_hash();
+			expected_number = expected_number - One::one();
+			output.push((header, Some(body), justification));
+			if expected_number.is_zero() {
+				break
+			}
+		}
+
+		if output.is_empty() {
+			return Err("No blocks")
+		}
+		self.phase = if expected_number.is_zero() {
+			Phase::Done
+		} else {
+			Phase::Backfill(expected_hash, expected_number)
+		};
+		self.pending_historic_imports += 1;
+		self.send_import(ImportTask::HistoricBlocks(output));
+		Ok(())
+	}
+
+	/// Called when a batch of blocks sent to the import thread by `on_historic_blocks` has been
+	/// imported, or failed to be.
+	pub(crate) fn on_historic_blocks_imported(&mut self) {
+		self.pending_historic_imports = self.pending_historic_imports.saturating_sub(1);
+	}
+
+	/// Polls the outcomes of the imports. The stream only ends if the import thread stopped
+	/// unexpectedly.
+	pub(crate) fn poll_import(&mut self) -> Poll<Option<ImportOutcome<B>>, ()> {
+		self.import_outcomes.poll()
+	}
+
+	fn send_import(&self, task: ImportTask<B>) {
+		// The import thread only stops if it panicked, which `poll_import` reports.
+		let _ = self.import_tasks.send(task);
+	}
+}
+
+/// Imports the data downloaded during a warp sync, in order, until the warp sync is dropped.
+fn import_thread<B: BlockT>(
+	client: Arc<dyn Client<B>>,
+	tasks: mpsc::Receiver<ImportTask<B>>,
+	outcomes: futures_mpsc::UnboundedSender<ImportOutcome<B>>,
+) {
+	for task in tasks {
+		let outcome = match task {
+			ImportTask::StateChunk { who, state_root, start_key, start_child_key, response } => {
+				let result = client.import_state_chunk(
+					&state_root,
+					start_key.as_ref().map(|key| &key[..]),
+					start_child_key.as_ref().map(|key| &key[..]),
+					response.entries,
+					response.complete,
+					response.proof,
+				);
+				ImportOutcome::StateChunk(who, result)
+			},
+			ImportTask::State(header, justification) => {
+				let result = client.import_state(header.clone(), Some(justification));
+				ImportOutcome::State(header, result)
+			},
+			ImportTask::HistoricBlocks(blocks) => {
+				let result = blocks.into_iter().try_for_each(|(header, body, justification)|
+					client.import_historic_block(header.clone(), body, justification).map_err(|e| (header, e))
+				);
+				ImportOutcome::HistoricBlocks(result)
+			},
+		};
+		if outcomes.unbounded_send(outcome).is_err() {
+			break
+		}
+	}
+}
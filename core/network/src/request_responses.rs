@@ -0,0 +1,733 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Request-response protocols.
+//!
+//! The `RequestResponses` struct implements the `NetworkBehaviour` trait of libp2p and lets us
+//! send requests to the nodes we're connected to and answer the requests they send us. Each kind
+//! of request has its own protocol name, passed as a `ProtocolConfig` alongside with the maximum
+//! size of requests and responses and the time after which a request is considered failed.
+//!
+//! Each request is sent on a separate substream. The dialer writes the request as a single
+//! message prefixed with its length (as an unsigned varint), then the listener writes back the
+//! response the same way and closes the substream. A slow or large response therefore never delays
+//! the other requests sent to the same node.
+//!
+//! A request that fails, for example because the remote doesn't support its protocol or didn't
+//! answer in time, is reported with an `Event::RequestFinished` but doesn't close the connection.
+
+use bytes::Bytes;
+use fnv::{FnvHashMap, FnvHashSet};
+use futures::prelude::*;
+use futures03::{compat::Compat, TryFutureExt as _};
+use futures_timer::Delay;
+use libp2p::core::{ConnectedPoint, Multiaddr, Negotiated, PeerId};
+use libp2p::core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, UpgradeError};
+use libp2p::swarm::{
+	IntoProtocolsHandler, KeepAlive, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+	ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
+};
+use libp2p::tokio_codec::Framed;
+use log::debug;
+use smallvec::SmallVec;
+use std::{collections::VecDeque, io, iter, marker::PhantomData, sync::Arc, vec};
+use std::time::{Duration, Instant};
+use tokio_io::{AsyncRead, AsyncWrite};
+use unsigned_varint::codec::UviBytes;
+
+/// Time after which a connection is closed if we have no request or response in progress on it,
+/// unless it is kept alive by another protocol.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of requests of a remote that we are answering at the same time on a connection.
+/// Further requests are refused by closing their substream.
+const MAX_PENDING_RESPONSES: usize = 32;
+
+/// Configuration of a request-response protocol.
+#[derive(Debug, Clone)]
+pub struct ProtocolConfig {
+	/// Name of the protocol, as negotiated on the wire.
+	pub name: Bytes,
+	/// Maximum size of a request, in bytes. Larger requests are rejected.
+	pub max_request_size: usize,
+	/// Maximum size of a response, in bytes. Larger responses are rejected.
+	pub max_response_size: usize,
+	/// Time after which a request we sent is considered failed. This is also the time we give
+	/// ourselves to answer the requests of the remotes.
+	pub request_timeout: Duration,
+}
+
+/// Identifier of a request that we sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+/// Identifier of a request that a remote sent us. Only unique within a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InboundRequestId(u64);
+
+/// Reason why a request failed.
+#[derive(Debug, derive_more::Display)]
+pub enum RequestFailure {
+	/// We aren't connected to the node, or the connection was closed before the response arrived.
+	#[display(fmt = "Not connected to the node")]
+	NotConnected,
+	/// The node doesn't support the protocol of the request.
+	#[display(fmt = "Protocol not supported")]
+	UnsupportedProtocol,
+	/// The node didn't answer in time.
+	#[display(fmt = "Request timed out")]
+	Timeout,
+	/// Error while sending the request or reading the response, for example because the response
+	/// exceeded the maximum size.
+	#[display(fmt = "{}", _0)]
+	Io(io::Error),
+}
+
+/// Event generated by `RequestResponses`.
+#[derive(Debug)]
+pub enum Event {
+	/// A remote sent us a request. It can be answered by calling `send_response`.
+	InboundRequest {
+		/// Node that sent the request.
+		peer: PeerId,
+		/// Name of the protocol the request was sent on.
+		protocol: Bytes,
+		/// Identifier to pass to `send_response`.
+		request_id: InboundRequestId,
+		/// The request itself.
+		request: Vec<u8>,
+	},
+
+	/// A request that we sent has either been answered or has failed.
+	RequestFinished {
+		/// Node the request was sent to.
+		peer: PeerId,
+		/// Name of the protocol the request was sent on.
+		protocol: Bytes,
+		/// Identifier returned by `send_request`.
+		request_id: RequestId,
+		/// The response, or the reason why there isn't any.
+		result: Result<Vec<u8>, RequestFailure>,
+	},
+}
+
+/// Implementation of `NetworkBehaviour` for request-response protocols.
+pub struct RequestResponses<TSubstream> {
+	/// The protocols we support, shared with the handlers.
+	protocols: Arc<Vec<ProtocolConfig>>,
+	/// Nodes we're connected to.
+	connected: FnvHashSet<PeerId>,
+	/// Requests that we sent and that haven't finished yet, with the node and the protocol they
+	/// were sent to.
+	pending_requests: FnvHashMap<RequestId, (PeerId, Bytes)>,
+	/// Identifier of the next request that we send.
+	next_request_id: u64,
+	/// Actions to produce from `poll()`.
+	events: VecDeque<NetworkBehaviourAction<RequestResponsesHandlerIn, Event>>,
+	/// Marker to pin the generics.
+	marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> RequestResponses<TSubstream> {
+	/// Builds a new `RequestResponses` supporting the given protocols.
+	pub fn new(protocols: impl IntoIterator<Item = ProtocolConfig>) -> Self {
+		RequestResponses {
+			protocols: Arc::new(protocols.into_iter().collect()),
+			connected: FnvHashSet::default(),
+			pending_requests: FnvHashMap::default(),
+			next_request_id: 0,
+			events: VecDeque::new(),
+			marker: PhantomData,
+		}
+	}
+
+	/// Sends a request to `target` on the protocol named `protocol`.
+	///
+	/// The outcome is later reported with an `Event::RequestFinished` carrying the returned
+	/// identifier. This is also the case if we aren't connected to `target`.
+	pub fn send_request(&mut self, target: &PeerId, protocol: Bytes, request: Vec<u8>) -> RequestId {
+		let request_id = RequestId(self.next_request_id);
+		self.next_request_id += 1;
+
+		if self.connected.contains(target) {
+			self.pending_requests.insert(request_id, (target.clone(), protocol.clone()));
+			self.events.push_back(NetworkBehaviourAction::SendEvent {
+				peer_id: target.clone(),
+				event: RequestResponsesHandlerIn::SendRequest { request_id, protocol, request },
+			});
+		} else {
+			self.events.push_back(NetworkBehaviourAction::GenerateEvent(Event::RequestFinished {
+				peer: target.clone(),
+				protocol,
+				request_id,
+				result: Err(RequestFailure::NotConnected),
+			}));
+		}
+
+		request_id
+	}
+
+	/// Answers a request that `peer` sent us.
+	///
+	/// Has no effect if the request has timed out or if the connection has been closed since.
+	pub fn send_response(&mut self, peer: &PeerId, request_id: InboundRequestId, response: Vec<u8>) {
+		if self.connected.contains(peer) {
+			self.events.push_back(NetworkBehaviourAction::SendEvent {
+				peer_id: peer.clone(),
+				event: RequestResponsesHandlerIn::SendResponse { request_id, response },
+			});
+		}
+	}
+
+	/// Reports all the pending requests to `peer` as failed.
+	fn fail_pending_requests(&mut self, peer: &PeerId) {
+		let failed = self.pending_requests.iter()
+			.filter(|(_, (target, _))| target == peer)
+			.map(|(request_id, _)| *request_id)
+			.collect::<Vec<_>>();
+
+		for request_id in failed {
+			if let Some((peer, protocol)) = self.pending_requests.remove(&request_id) {
+				self.events.push_back(NetworkBehaviourAction::GenerateEvent(Event::RequestFinished {
+					peer,
+					protocol,
+					request_id,
+					result: Err(RequestFailure::NotConnected),
+				}));
+			}
+		}
+	}
+}
+
+impl<TSubstream> NetworkBehaviour for RequestResponses<TSubstream>
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type ProtocolsHandler = RequestResponsesHandler<TSubstream>;
+	type OutEvent = Event;
+
+	fn new_handler(&mut self) -> Self::ProtocolsHandler {
+		RequestResponsesHandler::new(self.protocols.clone())
+	}
+
+	fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+		Vec::new()
+	}
+
+	fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+		self.connected.insert(peer_id);
+	}
+
+	fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+		self.connected.remove(peer_id);
+		self.fail_pending_requests(peer_id);
+	}
+
+	fn inject_replaced(&mut self, peer_id: PeerId, _: ConnectedPoint, _: ConnectedPoint) {
+		// The requests in progress were handled by the handler of the closed connection.
+		self.fail_pending_requests(&peer_id);
+	}
+
+	fn inject_node_event(&mut self, peer_id: PeerId, event: RequestResponsesHandlerOut) {
+		match event {
+			RequestResponsesHandlerOut::Request { request_id, protocol, request } => {
+				self.events.push_back(NetworkBehaviourAction::GenerateEvent(Event::InboundRequest {
+					peer: peer_id,
+					protocol,
+					request_id,
+					request,
+				}));
+			}
+			RequestResponsesHandlerOut::Finished { request_id, result } => {
+				// Requests of a replaced connection have already been reported as failed.
+				if let Some((peer, protocol)) = self.pending_requests.remove(&request_id) {
+					self.events.push_back(NetworkBehaviourAction::GenerateEvent(Event::RequestFinished {
+						peer,
+						protocol,
+						request_id,
+						result,
+					}));
+				}
+			}
+		}
+	}
+
+	fn poll(
+		&mut self,
+		_: &mut impl PollParameters,
+	) -> Async<
+		NetworkBehaviourAction<
+			<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent,
+			Self::OutEvent
+		>
+	> {
+		if let Some(event) = self.events.pop_front() {
+			return Async::Ready(event)
+		}
+
+		Async::NotReady
+	}
+}
+
+/// Event sent from `RequestResponses` to a `RequestResponsesHandler`.
+#[derive(Debug)]
+pub enum RequestResponsesHandlerIn {
+	/// Send a request to the remote.
+	SendRequest {
+		request_id: RequestId,
+		protocol: Bytes,
+		request: Vec<u8>,
+	},
+	/// Answer a request that the remote sent us.
+	SendResponse {
+		request_id: InboundRequestId,
+		response: Vec<u8>,
+	},
+}
+
+/// Event sent from a `RequestResponsesHandler` to `RequestResponses`.
+#[derive(Debug)]
+pub enum RequestResponsesHandlerOut {
+	/// The remote sent us a request.
+	Request {
+		request_id: InboundRequestId,
+		protocol: Bytes,
+		request: Vec<u8>,
+	},
+	/// A request that we sent has either been answered or has failed.
+	Finished {
+		request_id: RequestId,
+		result: Result<Vec<u8>, RequestFailure>,
+	},
+}
+
+/// Implementation of `ProtocolsHandler` for request-response protocols. Handles a single
+/// connection.
+pub struct RequestResponsesHandler<TSubstream> {
+	/// The protocols we support.
+	protocols: Arc<Vec<ProtocolConfig>>,
+	/// Requests of the remote that we haven't answered yet, with the time after which we give up.
+	pending_responses: FnvHashMap<InboundRequestId, (Negotiated<TSubstream>, Compat<Delay>)>,
+	/// Responses being written to the remote, with the time after which we give up.
+	sending_responses: Vec<(Box<dyn Future<Item = (), Error = io::Error> + Send>, Compat<Delay>)>,
+	/// Number of requests that we sent and that haven't finished yet.
+	num_pending_requests: usize,
+	/// Identifier of the next request of the remote.
+	next_inbound_id: u64,
+	/// Last time we had a request or a response in progress.
+	last_activity: Instant,
+	/// Events to produce from `poll()`.
+	events: SmallVec<[ProtocolsHandlerEvent<RequestUpgrade, RequestId, RequestResponsesHandlerOut>; 4]>,
+}
+
+impl<TSubstream> RequestResponsesHandler<TSubstream> {
+	fn new(protocols: Arc<Vec<ProtocolConfig>>) -> Self {
+		RequestResponsesHandler {
+			protocols,
+			pending_responses: FnvHashMap::default(),
+			sending_responses: Vec::new(),
+			num_pending_requests: 0,
+			next_inbound_id: 0,
+			last_activity: Instant::now(),
+			events: SmallVec::new(),
+		}
+	}
+}
+
+impl<TSubstream> ProtocolsHandler for RequestResponsesHandler<TSubstream>
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type InEvent = RequestResponsesHandlerIn;
+	type OutEvent = RequestResponsesHandlerOut;
+	type Error = void::Void;
+	type Substream = TSubstream;
+	type InboundProtocol = RequestsUpgrade;
+	type OutboundProtocol = RequestUpgrade;
+	type OutboundOpenInfo = RequestId;
+
+	fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+		SubstreamProtocol::new(RequestsUpgrade { protocols: self.protocols.clone() })
+	}
+
+	fn inject_fully_negotiated_inbound(&mut self, request: InboundRequest<TSubstream>) {
+		if self.pending_responses.len() + self.sending_responses.len() >= MAX_PENDING_RESPONSES {
+			debug!(target: "sub-libp2p", "Refusing request: too many requests in progress");
+			return
+		}
+
+		let request_id = InboundRequestId(self.next_inbound_id);
+		self.next_inbound_id += 1;
+
+		let deadline = Delay::new(request.timeout).compat();
+		self.pending_responses.insert(request_id, (request.substream, deadline));
+		self.events.push(ProtocolsHandlerEvent::Custom(RequestResponsesHandlerOut::Request {
+			request_id,
+			protocol: request.protocol,
+			request: request.request,
+		}));
+	}
+
+	fn inject_fully_negotiated_outbound(&mut self, response: Vec<u8>, request_id: RequestId) {
+		self.num_pending_requests -= 1;
+		self.events.push(ProtocolsHandlerEvent::Custom(RequestResponsesHandlerOut::Finished {
+			request_id,
+			result: Ok(response),
+		}));
+	}
+
+	fn inject_event(&mut self, event: RequestResponsesHandlerIn) {
+		match event {
+			RequestResponsesHandlerIn::SendRequest { request_id, protocol, request } => {
+				let config = match self.protocols.iter().find(|config| config.name == protocol) {
+					Some(config) => config,
+					None => {
+						self.events.push(ProtocolsHandlerEvent::Custom(RequestResponsesHandlerOut::Finished {
+							request_id,
+							result: Err(RequestFailure::UnsupportedProtocol),
+						}));
+						return
+					}
+				};
+
+				let upgrade = RequestUpgrade {
+					protocol: config.name.clone(),
+					request,
+					max_response_size: config.max_response_size,
+				};
+				self.num_pending_requests += 1;
+				self.events.push(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+					protocol: SubstreamProtocol::new(upgrade).with_timeout(config.request_timeout),
+					info: request_id,
+				});
+			}
+			RequestResponsesHandlerIn::SendResponse { request_id, response } => {
+				if let Some((substream, deadline)) = self.pending_responses.remove(&request_id) {
+					let future = write_message(substream, response)
+						.and_then(tokio_io::io::shutdown)
+						.map(|_| ());
+					self.sending_responses.push((Box::new(future), deadline));
+				}
+			}
+		}
+	}
+
+	fn inject_dial_upgrade_error(&mut self, request_id: RequestId, err: ProtocolsHandlerUpgrErr<io::Error>) {
+		self.num_pending_requests -= 1;
+		let failure = match err {
+			ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => RequestFailure::Timeout,
+			ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => RequestFailure::UnsupportedProtocol,
+			ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(err)) => RequestFailure::Io(err),
+		};
+		self.events.push(ProtocolsHandlerEvent::Custom(RequestResponsesHandlerOut::Finished {
+			request_id,
+			result: Err(failure),
+		}));
+	}
+
+	fn connection_keep_alive(&self) -> KeepAlive {
+		if self.num_pending_requests > 0 || !self.pending_responses.is_empty() ||
+			!self.sending_responses.is_empty()
+		{
+			KeepAlive::Yes
+		} else {
+			KeepAlive::Until(self.last_activity + IDLE_TIMEOUT)
+		}
+	}
+
+	fn poll(
+		&mut self,
+	) -> Poll<
+		ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
+		Self::Error,
+	> {
+		if !self.events.is_empty() {
+			return Ok(Async::Ready(self.events.remove(0)))
+		}
+
+		if let KeepAlive::Yes = self.connection_keep_alive() {
+			self.last_activity = Instant::now();
+		}
+
+		// Give up on the requests of the remote that we didn't answer in time. Dropping the
+		// substream lets the remote know.
+		self.pending_responses.retain(|request_id, (_, deadline)| match deadline.poll() {
+			Ok(Async::NotReady) => true,
+			Ok(Async::Ready(())) | Err(_) => {
+				debug!(target: "sub-libp2p", "Inbound request {:?} not answered in time", request_id);
+				false
+			}
+		});
+
+		for n in (0..self.sending_responses.len()).rev() {
+			let (response, deadline) = &mut self.sending_responses[n];
+			let finished = match response.poll() {
+				Ok(Async::NotReady) => match deadline.poll() {
+					Ok(Async::NotReady) => false,
+					Ok(Async::Ready(())) | Err(_) => {
+						debug!(target: "sub-libp2p", "Timeout while sending a response");
+						true
+					}
+				},
+				Ok(Async::Ready(())) => true,
+				Err(err) => {
+					debug!(target: "sub-libp2p", "Error while sending a response: {}", err);
+					true
+				}
+			};
+			if finished {
+				self.sending_responses.swap_remove(n);
+			}
+		}
+
+		Ok(Async::NotReady)
+	}
+}
+
+/// Writes `message` prefixed with its length, then flushes the substream.
+fn write_message<TSubstream>(
+	substream: TSubstream,
+	message: Vec<u8>,
+) -> impl Future<Item = TSubstream, Error = io::Error>
+where
+	TSubstream: AsyncWrite,
+{
+	let mut length = unsigned_varint::encode::usize_buffer();
+	let mut data = unsigned_varint::encode::usize(message.len(), &mut length).to_vec();
+	data.extend_from_slice(&message);
+
+	tokio_io::io::write_all(substream, data)
+		.and_then(|(substream, _)| tokio_io::io::flush(substream))
+}
+
+/// Request of a remote that has been read on an inbound substream.
+pub struct InboundRequest<TSubstream> {
+	/// Name of the protocol the request was sent on.
+	protocol: Bytes,
+	/// The request itself.
+	request: Vec<u8>,
+	/// Time we have to answer the request.
+	timeout: Duration,
+	/// Substream to write the response to.
+	substream: Negotiated<TSubstream>,
+}
+
+/// Upgrade that accepts a request on any of our protocols.
+#[derive(Debug, Clone)]
+pub struct RequestsUpgrade {
+	protocols: Arc<Vec<ProtocolConfig>>,
+}
+
+impl UpgradeInfo for RequestsUpgrade {
+	type Info = Bytes;
+	type InfoIter = vec::IntoIter<Bytes>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		self.protocols.iter()
+			.map(|config| config.name.clone())
+			.collect::<Vec<_>>()
+			.into_iter()
+	}
+}
+
+impl<TSubstream> InboundUpgrade<TSubstream> for RequestsUpgrade
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type Output = InboundRequest<TSubstream>;
+	type Error = io::Error;
+	type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+	fn upgrade_inbound(self, substream: Negotiated<TSubstream>, protocol: Bytes) -> Self::Future {
+		let config = self.protocols.iter()
+			.find(|config| config.name == protocol)
+			.expect("the negotiated protocol is one of those returned by protocol_info; qed");
+		let timeout = config.request_timeout;
+		let mut codec = UviBytes::default();
+		codec.set_max_len(config.max_request_size);
+
+		let future = Framed::new(substream, codec)
+			.into_future()
+			.map_err(|(err, _)| err)
+			.and_then(move |(request, substream)| match request {
+				Some(request) => Ok(InboundRequest {
+					protocol,
+					request: request.to_vec(),
+					timeout,
+					substream: substream.into_inner(),
+				}),
+				None => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+			});
+
+		Box::new(future)
+	}
+}
+
+/// Upgrade that sends a request and reads the response.
+#[derive(Debug, Clone)]
+pub struct RequestUpgrade {
+	/// Name of the protocol to send the request on.
+	protocol: Bytes,
+	/// The request itself.
+	request: Vec<u8>,
+	/// Maximum size of the response.
+	max_response_size: usize,
+}
+
+impl UpgradeInfo for RequestUpgrade {
+	type Info = Bytes;
+	type InfoIter = iter::Once<Bytes>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		iter::once(self.protocol.clone())
+	}
+}
+
+impl<TSubstream> OutboundUpgrade<TSubstream> for RequestUpgrade
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type Output = Vec<u8>;
+	type Error = io::Error;
+	type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+	fn upgrade_outbound(self, substream: Negotiated<TSubstream>, _: Bytes) -> Self::Future {
+		let max_response_size = self.max_response_size;
+
+		let future = write_message(substream, self.request)
+			.and_then(move |substream| {
+				let mut codec = UviBytes::default();
+				codec.set_max_len(max_response_size);
+				Framed::new(substream, codec)
+					.into_future()
+					.map_err(|(err, _)| err)
+			})
+			.and_then(|(response, _)| match response {
+				Some(response) => Ok(response.to_vec()),
+				None => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+			});
+
+		Box::new(future)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use futures::prelude::*;
+	use libp2p::identity::Keypair;
+	use libp2p::Multiaddr;
+	use libp2p::core::upgrade;
+	use libp2p::core::transport::{Transport, MemoryTransport};
+	use libp2p::core::upgrade::{InboundUpgradeExt, OutboundUpgradeExt};
+	use libp2p::swarm::Swarm;
+	use std::{io, time::Duration};
+	use super::{Event, ProtocolConfig, RequestFailure, RequestResponses};
+
+	/// Connects two nodes supporting the given protocols and returns the outcome of a request
+	/// sent by the second one to the first one on the second protocol. The first node answers
+	/// requests by reversing them.
+	fn request_outcome(protocols: [&'static [u8]; 2], request: Vec<u8>) -> Result<Vec<u8>, RequestFailure> {
+		let mut first_addr = None;
+
+		let mut swarms = protocols.iter().map(|protocol| {
+			let keypair = Keypair::generate_ed25519();
+
+			let transport = MemoryTransport
+				.with_upgrade(libp2p::secio::SecioConfig::new(keypair.clone()))
+				.and_then(move |out, endpoint| {
+					let peer_id = out.remote_key.into_peer_id();
+					let peer_id2 = peer_id.clone();
+					let upgrade = libp2p::yamux::Config::default()
+						.map_inbound(move |muxer| (peer_id, muxer))
+						.map_outbound(move |muxer| (peer_id2, muxer));
+					upgrade::apply(out.stream, upgrade, endpoint)
+				});
+
+			let behaviour = RequestResponses::new(vec![ProtocolConfig {
+				name: Bytes::from_static(*protocol),
+				max_request_size: 1024,
+				max_response_size: 1024,
+				request_timeout: Duration::from_secs(10),
+			}]);
+			let mut swarm = Swarm::new(transport, behaviour, keypair.public().into_peer_id());
+			let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+			Swarm::listen_on(&mut swarm, listen_addr.clone()).unwrap();
+
+			match first_addr {
+				None => first_addr = Some(listen_addr),
+				Some(ref addr) => Swarm::dial_addr(&mut swarm, addr.clone()).unwrap(),
+			}
+			swarm
+		}).collect::<Vec<_>>();
+
+		let target = Swarm::local_peer_id(&swarms[0]).clone();
+		let mut request = Some(request);
+
+		let fut = futures::future::poll_fn::<_, (), _>(move || {
+			loop {
+				match swarms[0].poll().unwrap() {
+					Async::Ready(Some(Event::InboundRequest { peer, request_id, mut request, .. })) => {
+						request.reverse();
+						swarms[0].send_response(&peer, request_id, request);
+					}
+					Async::Ready(_) => {}
+					Async::NotReady => break,
+				}
+			}
+
+			loop {
+				// Only send the request once the connection is established.
+				if swarms[1].connected.contains(&target) {
+					if let Some(request) = request.take() {
+						swarms[1].send_request(&target, Bytes::from_static(protocols[1]), request);
+					}
+				}
+
+				match swarms[1].poll().unwrap() {
+					Async::Ready(Some(Event::RequestFinished { result, .. })) =>
+						return Ok(Async::Ready(result)),
+					Async::Ready(_) => {}
+					Async::NotReady => return Ok(Async::NotReady),
+				}
+			}
+		});
+
+		tokio::runtime::Runtime::new().unwrap().block_on(fut).unwrap()
+	}
+
+	#[test]
+	fn request_is_answered() {
+		let response = request_outcome([b"/test/1", b"/test/1"], vec![1, 2, 3]);
+		assert_eq!(response.unwrap(), vec![3, 2, 1]);
+	}
+
+	#[test]
+	fn unsupported_protocol_fails() {
+		match request_outcome([b"/test/1", b"/other/1"], vec![1, 2, 3]) {
+			Err(RequestFailure::UnsupportedProtocol) => {}
+			other => panic!("unexpected outcome: {:?}", other),
+		}
+	}
+
+	#[test]
+	fn oversized_request_fails() {
+		// The remote closes the substream without answering.
+		match request_outcome([b"/test/1", b"/test/1"], vec![0; 2048]) {
+			Err(RequestFailure::Io(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+			other => panic!("unexpected outcome: {:?}", other),
+		}
+	}
+}
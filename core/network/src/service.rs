@@ -25,13 +25,15 @@
 //! The methods of the [`NetworkService`] are implemented by sending a message over a channel,
 //! which is then processed by [`NetworkWorker::poll`].
 
-use std::{collections::{HashMap, HashSet}, fs, marker::PhantomData, io, path::Path};
+use std::{collections::{HashMap, HashSet}, fs, marker::PhantomData, io, path::{Path, PathBuf}};
+use std::time::Duration;
 use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}};
 
+use bytes::Bytes;
 use consensus::import_queue::{ImportQueue, Link};
 use consensus::import_queue::{BlockImportResult, BlockImportError};
 use futures::{prelude::*, sync::mpsc};
-use futures03::TryFutureExt as _;
+use futures03::{StreamExt as _, TryFutureExt as _, TryStreamExt as _};
 use log::{warn, error, info};
 use libp2p::{PeerId, Multiaddr, kad::record};
 use libp2p::core::{transport::boxed::Boxed, muxing::StreamMuxerBox};
@@ -42,15 +44,23 @@ use sr_primitives::{traits::{Block as BlockT, NumberFor}, ConsensusEngineId};
 
 use crate::{behaviour::{Behaviour, BehaviourOut}, config::{parse_str_addr, parse_addr}};
 use crate::{NetworkState, NetworkStateNotConnectedPeer, NetworkStatePeer};
-use crate::{transport, config::NodeKeyConfig, config::NonReservedPeerMode};
+use crate::{transport, config::NodeKeyConfig, config::NonReservedPeerMode, config::SentryMode};
 use crate::config::{Params, TransportConfig};
 use crate::error::Error;
+use crate::peer_store;
 use crate::protocol::{self, Protocol, Context, CustomMessageOutcome, PeerInfo};
 use crate::protocol::consensus_gossip::{ConsensusGossip, MessageRecipient as GossipMessageRecipient};
 use crate::protocol::{event::Event, light_dispatch::{AlwaysBadChecker, RequestData}};
 use crate::protocol::specialization::NetworkSpecialization;
 use crate::protocol::sync::SyncState;
 
+/// Name of the peerset priority group containing the validators we are a sentry of.
+const SENTRY_VALIDATORS_GROUP: &str = "sentry-validators";
+/// Interval at which we save the reputations and addresses of the nodes we know about.
+const SAVE_KNOWN_NODES_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Number of events that an `event_stream` holds before the network waits for it to be read.
+const EVENT_STREAM_BUFFER_SIZE: usize = 256;
+
 /// Minimum Requirements for a Hash within Networking
 pub trait ExHashT:
 	::std::hash::Hash + Eq + ::std::fmt::Debug + Clone + Send + Sync + 'static
@@ -140,9 +150,19 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 		let mut known_addresses = Vec::new();
 		let mut bootnodes = Vec::new();
 		let mut reserved_nodes = Vec::new();
+		// Validators we are a sentry of, whose addresses we must keep private.
+		let mut private_peers = HashSet::new();
+
+		// A validator behind sentries doesn't connect to anyone else, not even to the bootnodes.
+		let is_validator_behind_sentries = params.network_config.sentry_mode.sentry_nodes().is_some();
+		let boot_nodes = if is_validator_behind_sentries {
+			&[][..]
+		} else {
+			&params.network_config.boot_nodes[..]
+		};
 
 		// Process the bootnodes.
-		for bootnode in params.network_config.boot_nodes.iter() {
+		for bootnode in boot_nodes.iter() {
 			match parse_str_addr(bootnode) {
 				Ok((peer_id, addr)) => {
 					bootnodes.push(peer_id.clone());
@@ -162,12 +182,50 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			}
 		}
 
+		// Initialize the sentry nodes or the validators we are a sentry of.
+		let mut sentry_group = None;
+		match &params.network_config.sentry_mode {
+			SentryMode::Disabled => {},
+			SentryMode::Validator { sentry_nodes } => for sentry in sentry_nodes {
+				// A validator behind sentries only talks to them, which reserved-only mode
+				// guarantees.
+				let (peer_id, addr) = parse_str_addr(sentry)
+					.map_err(|err| Error::InvalidSentryAddress(format!("{}: {}", sentry, err)))?;
+				reserved_nodes.push(peer_id.clone());
+				known_addresses.push((peer_id, addr));
+			},
+			SentryMode::Sentry { validators } => {
+				let mut group = HashSet::new();
+				for validator in validators {
+					let (peer_id, addr) = parse_str_addr(validator)
+						.map_err(|err| Error::InvalidSentryAddress(format!("{}: {}", validator, err)))?;
+					group.insert(peer_id.clone());
+					private_peers.insert(peer_id.clone());
+					known_addresses.push((peer_id, addr));
+				}
+				sentry_group = Some(group);
+			},
+		}
+
+		// Nodes saved during the previous run. A validator behind sentries must only ever talk to
+		// its sentries, and therefore doesn't keep track of the other nodes.
+		let peer_store_path = if is_validator_behind_sentries {
+			None
+		} else {
+			params.network_config.net_config_path.as_ref().map(PathBuf::from)
+		};
+		let known_nodes = peer_store_path.as_ref()
+			.map(|path| peer_store::load(path))
+			.unwrap_or_default();
+
 		let peerset_config = peerset::PeersetConfig {
 			in_peers: params.network_config.in_peers,
 			out_peers: params.network_config.out_peers,
 			bootnodes,
-			reserved_only: params.network_config.non_reserved_mode == NonReservedPeerMode::Deny,
+			reserved_only: params.network_config.non_reserved_mode == NonReservedPeerMode::Deny ||
+				is_validator_behind_sentries,
 			reserved_nodes,
+			reputations: known_nodes.iter().map(|node| (node.peer_id.clone(), node.reputation)).collect(),
 		};
 
 		// Private and public keys configuration.
@@ -179,10 +237,16 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 		let local_peer_id = local_public.clone().into_peer_id();
 		info!(target: "sub-libp2p", "Local node identity is: {}", local_peer_id.to_base58());
 
+		let protocol_id = params.protocol_id;
 		let num_connected = Arc::new(AtomicUsize::new(0));
 		let is_major_syncing = Arc::new(AtomicBool::new(false));
 		let (protocol, peerset_handle) = Protocol::new(
-			protocol::ProtocolConfig { roles: params.roles },
+			protocol::ProtocolConfig {
+				roles: params.roles,
+				keep_block_bodies: params.keep_block_bodies,
+				sync_mode: params.network_config.sync_mode.clone(),
+				sentry_validators: sentry_group.clone().unwrap_or_default(),
+			},
 			params.chain,
 			params.on_demand.as_ref().map(|od| od.checker().clone())
 				.unwrap_or(Arc::new(AlwaysBadChecker)),
@@ -190,11 +254,17 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			params.transaction_pool,
 			params.finality_proof_provider,
 			params.finality_proof_request_builder,
-			params.protocol_id,
+			params.warp_sync_verifier,
+			protocol_id.clone(),
 			peerset_config,
 			params.block_announce_validator
 		)?;
 
+		// Sentries always keep a slot for the validators they protect.
+		if let Some(group) = sentry_group {
+			peerset_handle.set_priority_group(SENTRY_VALIDATORS_GROUP.to_owned(), group);
+		}
+
 		// Build the swarm.
 		let (mut swarm, bandwidth) = {
 			let user_agent = format!(
@@ -204,12 +274,15 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			);
 			let behaviour = Behaviour::new(
 				protocol,
+				&protocol_id,
 				user_agent,
 				local_public,
 				known_addresses,
+				private_peers,
 				match params.network_config.transport {
 					TransportConfig::MemoryOnly => false,
-					TransportConfig::Normal { enable_mdns, .. } => enable_mdns,
+					// mDNS would announce a validator behind sentries to the local network.
+					TransportConfig::Normal { enable_mdns, .. } => enable_mdns && !is_validator_behind_sentries,
 				}
 			);
 			let (transport, bandwidth) = {
@@ -235,6 +308,13 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			Swarm::<B, S, H>::add_external_address(&mut swarm, addr.clone());
 		}
 
+		// Add the addresses saved during the previous run.
+		for node in known_nodes {
+			for addr in node.addresses {
+				swarm.add_discovered_address(&node.peer_id, addr);
+			}
+		}
+
 		let external_addresses = Arc::new(Mutex::new(Vec::new()));
 
 		let service = Arc::new(NetworkService {
@@ -257,6 +337,10 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			import_queue: params.import_queue,
 			from_worker,
 			light_client_rqs: params.on_demand.and_then(|od| od.extract_receiver()),
+			event_streams: Vec::new(),
+			peer_store_path,
+			save_known_nodes_interval: Box::new(futures_timer::Interval::new(SAVE_KNOWN_NODES_INTERVAL)
+				.map(|v| Ok::<_, ()>(v)).compat()),
 		})
 	}
 
@@ -328,6 +412,10 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 	pub fn network_state(&mut self) -> NetworkState {
 		let swarm = &mut self.network_service;
 		let open = swarm.user_protocol().open_peers().cloned().collect::<Vec<_>>();
+		let reputations = swarm.user_protocol_mut().peerset_reputations()
+			.into_iter()
+			.collect::<HashMap<_, _>>();
+		let reputation = |peer_id: &PeerId| reputations.get(peer_id).cloned().unwrap_or(0);
 
 		let connected_peers = {
 			let swarm = &mut *swarm;
@@ -351,6 +439,7 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 					enabled: swarm.user_protocol().is_enabled(&peer_id),
 					open: swarm.user_protocol().is_open(&peer_id),
 					known_addresses,
+					reputation: reputation(peer_id),
 				}))
 			}).collect()
 		};
@@ -366,6 +455,7 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 					latest_ping_time: swarm.node(&peer_id).and_then(|i| i.latest_ping()),
 					known_addresses: NetworkBehaviour::addresses_of_peer(&mut **swarm, &peer_id)
 						.into_iter().collect(),
+					reputation: reputation(&peer_id),
 				})
 			}).collect()
 		};
@@ -487,6 +577,46 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 			.unbounded_send(ServerToWorkerMsg::PutValue(key, value));
 	}
 
+	/// Registers a notifications protocol named `protocol_name`.
+	///
+	/// From now on, we open a substream with this name with each node we're connected to, and
+	/// send `handshake` as its first message. The substreams opened by the remotes, the handshakes
+	/// they sent us and the notifications they write are reported through `event_stream`.
+	///
+	/// Each notifications protocol has its own substreams, and therefore doesn't slow down the
+	/// other protocols and the gossiping of the Substrate substream.
+	pub fn register_notifications_protocol(&self, protocol_name: impl Into<Bytes>, handshake: Vec<u8>) {
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::RegisterNotifProtocol(protocol_name.into(), handshake));
+	}
+
+	/// Sends a notification to `target` on the notifications protocol named `protocol_name`.
+	///
+	/// The protocol must have been registered with `register_notifications_protocol`. The
+	/// notification is silently discarded if we aren't connected to `target`, if `target` doesn't
+	/// support the protocol, or if too many notifications are waiting to be sent to `target` on
+	/// this protocol. The latter is reported with an `Event::NotificationStreamClogged`.
+	pub fn write_notification(&self, target: PeerId, protocol_name: impl Into<Bytes>, message: Vec<u8>) {
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::WriteNotification(target, protocol_name.into(), message));
+	}
+
+	/// Returns a stream of the events generated by the network, including the events about the
+	/// notifications protocols.
+	///
+	/// Events generated before this method is called are not reported.
+	///
+	/// The stream holds at most `EVENT_STREAM_BUFFER_SIZE` events. The network stops processing
+	/// its events, including the notifications it receives, while the stream is full, so it must
+	/// be polled continuously.
+	pub fn event_stream(&self) -> mpsc::Receiver<Event> {
+		let (tx, rx) = mpsc::channel(EVENT_STREAM_BUFFER_SIZE);
+		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::EventStream(tx));
+		rx
+	}
+
 	/// Connect to unreserved peers and allow unreserved peers to connect.
 	pub fn accept_unreserved_peers(&self) {
 		self.peerset.set_reserved_only(false);
@@ -615,6 +745,9 @@ enum ServerToWorkerMsg<B: BlockT, S: NetworkSpecialization<B>> {
 	PutValue(record::Key, Vec<u8>),
 	AddKnownAddress(PeerId, Multiaddr),
 	SyncFork(Vec<PeerId>, B::Hash, NumberFor<B>),
+	RegisterNotifProtocol(Bytes, Vec<u8>),
+	WriteNotification(PeerId, Bytes, Vec<u8>),
+	EventStream(mpsc::Sender<Event>),
 }
 
 /// Main network worker. Must be polled in order for the network to advance.
@@ -638,6 +771,68 @@ pub struct NetworkWorker<B: BlockT + 'static, S: NetworkSpecialization<B>, H: Ex
 	from_worker: mpsc::UnboundedReceiver<ServerToWorkerMsg<B, S>>,
 	/// Receiver for queries from the light client that must be processed.
 	light_client_rqs: Option<mpsc::UnboundedReceiver<RequestData<B>>>,
+	/// Senders for events that happen on the network, obtained through `event_stream`.
+	event_streams: Vec<mpsc::Sender<Event>>,
+	/// Directory where the reputations and addresses of the nodes we know about are saved.
+	peer_store_path: Option<PathBuf>,
+	/// Interval at which we save the nodes we know about.
+	save_known_nodes_interval: Box<dyn Stream<Item = (), Error = ()> + Send>,
+}
+
+impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker<B, S, H> {
+	/// Returns `true` if all the event streams have room for an event. Otherwise, the current task
+	/// is notified once they have. Event streams whose receiver has been dropped are removed.
+	fn event_streams_ready(&mut self) -> bool {
+		let mut ready = true;
+		for n in (0..self.event_streams.len()).rev() {
+			match self.event_streams[n].poll_ready() {
+				Ok(Async::Ready(())) => {},
+				Ok(Async::NotReady) => ready = false,
+				Err(_) => {
+					self.event_streams.swap_remove(n);
+				},
+			}
+		}
+		ready
+	}
+
+	/// Sends `event` to all the event streams, which must be ready.
+	fn send_to_event_streams(&mut self, event: &Event) {
+		for sender in self.event_streams.iter_mut() {
+			// Errors are dropped receivers, removed by `event_streams_ready`.
+			let _ = sender.start_send(event.clone());
+		}
+	}
+
+	/// Saves the reputations of the nodes we know about, and the addresses of the good ones, so
+	/// that they can be loaded back on the next start.
+	fn save_known_nodes(&mut self) {
+		let path = match self.peer_store_path {
+			Some(ref path) => path,
+			None => return,
+		};
+
+		let swarm = &mut self.network_service;
+		let reputations = swarm.user_protocol_mut().peerset_reputations();
+		let nodes = reputations.into_iter().map(|(peer_id, reputation)| {
+			let addresses = if reputation >= 0 {
+				NetworkBehaviour::addresses_of_peer(&mut **swarm, &peer_id)
+			} else {
+				Vec::new()
+			};
+			peer_store::KnownNode { peer_id, reputation, addresses }
+		}).collect();
+
+		if let Err(err) = peer_store::save(path, nodes) {
+			warn!(target: "sub-libp2p", "Failed to save the list of known nodes: {}", err);
+		}
+	}
+}
+
+impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Drop for NetworkWorker<B, S, H> {
+	fn drop(&mut self) {
+		self.save_known_nodes();
+	}
 }
 
 impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Stream for NetworkWorker<B, S, H> {
@@ -695,10 +890,21 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Stream for Ne
 					self.network_service.add_known_address(peer_id, addr),
 				ServerToWorkerMsg::SyncFork(peer_ids, hash, number) =>
 					self.network_service.user_protocol_mut().set_sync_fork_request(peer_ids, &hash, number),
+				ServerToWorkerMsg::RegisterNotifProtocol(protocol, handshake) =>
+					self.network_service.register_notifications_protocol(protocol, handshake),
+				ServerToWorkerMsg::WriteNotification(target, protocol, message) =>
+					self.network_service.write_notification(&target, protocol, message),
+				ServerToWorkerMsg::EventStream(sender) =>
+					self.event_streams.push(sender),
 			}
 		}
 
 		loop {
+			// Wait for the event streams to have room for the next event before processing it.
+			if !self.event_streams_ready() {
+				break
+			}
+
 			// Process the next action coming from the network.
 			let poll_value = self.network_service.poll();
 
@@ -706,10 +912,15 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Stream for Ne
 				Ok(Async::NotReady) => break,
 				Ok(Async::Ready(Some(BehaviourOut::SubstrateAction(outcome)))) => outcome,
 				Ok(Async::Ready(Some(BehaviourOut::Dht(ev)))) => {
-					self.network_service.user_protocol_mut()
-						.on_event(Event::Dht(ev.clone()));
-
-					return Ok(Async::Ready(Some(Event::Dht(ev))));
+					let event = Event::Dht(ev);
+					self.network_service.user_protocol_mut().on_event(event.clone());
+					self.send_to_event_streams(&event);
+					return Ok(Async::Ready(Some(event)));
+				},
+				Ok(Async::Ready(Some(BehaviourOut::Notifications(event)))) => {
+					self.network_service.user_protocol_mut().on_event(event.clone());
+					self.send_to_event_streams(&event);
+					return Ok(Async::Ready(Some(event)));
 				},
 				Ok(Async::Ready(None)) => CustomMessageOutcome::None,
 				Err(err) => {
@@ -725,10 +936,15 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Stream for Ne
 					self.import_queue.import_justification(origin, hash, nb, justification),
 				CustomMessageOutcome::FinalityProofImport(origin, hash, nb, proof) =>
 					self.import_queue.import_finality_proof(origin, hash, nb, proof),
-				CustomMessageOutcome::None => {}
+				// Requests are sent by the `Behaviour` and never reach us.
+				CustomMessageOutcome::Request { .. } | CustomMessageOutcome::None => {}
 			}
 		}
 
+		while let Ok(Async::Ready(Some(()))) = self.save_known_nodes_interval.poll() {
+			self.save_known_nodes();
+		}
+
 		// Update the variables shared with the `NetworkService`.
 		self.num_connected.store(self.network_service.user_protocol_mut().num_connected_peers(), Ordering::Relaxed);
 		{
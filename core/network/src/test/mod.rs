@@ -19,7 +19,11 @@
 #[cfg(test)]
 mod block_import;
 #[cfg(test)]
+mod sentry;
+#[cfg(test)]
 mod sync;
+#[cfg(test)]
+mod warp;
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -48,8 +52,10 @@ use consensus::{BlockOrigin, ForkChoiceStrategy, BlockImportParams, BlockCheckPa
 use futures::prelude::*;
 use futures03::{StreamExt as _, TryStreamExt as _};
 use crate::{NetworkWorker, NetworkService, ReportHandle, config::ProtocolId};
-use crate::config::{NetworkConfiguration, TransportConfig, BoxFinalityProofRequestBuilder};
-use libp2p::PeerId;
+use crate::config::{
+	NetworkConfiguration, TransportConfig, SentryMode, BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier,
+};
+use libp2p::{Multiaddr, PeerId};
 use parking_lot::Mutex;
 use primitives::H256;
 use crate::protocol::{Context, ProtocolConfig};
@@ -224,6 +230,8 @@ pub struct Peer<D, S: NetworkSpecialization<Block>> {
 	select_chain: Option<LongestChain<test_client::Backend, Block>>,
 	backend: Option<Arc<test_client::Backend>>,
 	network: NetworkWorker<Block, S, <Block as BlockT>::Hash>,
+	/// Address the network listens on.
+	listen_addr: Multiaddr,
 	imported_blocks_stream: Box<dyn Stream<Item = BlockImportNotification<Block>, Error = ()> + Send>,
 	finality_notification_stream: Box<dyn Stream<Item = FinalityNotification<Block>, Error = ()> + Send>,
 }
@@ -470,6 +478,21 @@ impl<B: BlockT, T: ?Sized + Verifier<B>> Verifier<B> for VerifierAdapter<T> {
 	}
 }
 
+/// Sentry mode of a new peer created with `config`. The validators it is a sentry of must be
+/// part of `peers`.
+fn sentry_mode<D, S: NetworkSpecialization<Block>>(peers: &[Peer<D, S>], config: &ProtocolConfig) -> SentryMode {
+	if config.sentry_validators.is_empty() {
+		return SentryMode::Disabled
+	}
+
+	SentryMode::Sentry {
+		validators: peers.iter()
+			.filter(|peer| config.sentry_validators.contains(&peer.id()))
+			.map(|peer| format!("{}/p2p/{}", peer.listen_addr, peer.id().to_base58()))
+			.collect(),
+	}
+}
+
 pub trait TestNetFactory: Sized {
 	type Specialization: NetworkSpecialization<Block> + SpecializationFactory;
 	type Verifier: 'static + Verifier<Block>;
@@ -507,6 +530,11 @@ pub trait TestNetFactory: Sized {
 		None
 	}
 
+	/// Get warp sync verifier of full peers (if supported).
+	fn make_warp_sync_verifier(&self, _client: PeersClient) -> Option<BoxWarpSyncVerifier<Block>> {
+		None
+	}
+
 	fn default_config() -> ProtocolConfig {
 		ProtocolConfig::default()
 	}
@@ -558,14 +586,18 @@ pub trait TestNetFactory: Sized {
 
 		let network = NetworkWorker::new(crate::config::Params {
 			roles: config.roles,
+			keep_block_bodies: config.keep_block_bodies,
 			network_config: NetworkConfiguration {
 				listen_addresses: vec![listen_addr.clone()],
 				transport: TransportConfig::MemoryOnly,
+				sync_mode: config.sync_mode.clone(),
+				sentry_mode: sentry_mode(self.peers(), config),
 				..NetworkConfiguration::default()
 			},
 			chain: client.clone(),
 			finality_proof_provider: self.make_finality_proof_provider(PeersClient::Full(client.clone(), backend.clone())),
 			finality_proof_request_builder,
+			warp_sync_verifier: self.make_warp_sync_verifier(PeersClient::Full(client.clone(), backend.clone())),
 			on_demand: None,
 			transaction_pool: Arc::new(EmptyTransactionPool),
 			protocol_id: ProtocolId::from(&b"test-protocol-name"[..]),
@@ -594,6 +626,7 @@ pub trait TestNetFactory: Sized {
 				block_import: Box::new(block_import),
 				verifier,
 				network,
+				listen_addr,
 			});
 		});
 	}
@@ -632,14 +665,17 @@ pub trait TestNetFactory: Sized {
 
 		let network = NetworkWorker::new(crate::config::Params {
 			roles: config.roles,
+			keep_block_bodies: config.keep_block_bodies,
 			network_config: NetworkConfiguration {
 				listen_addresses: vec![listen_addr.clone()],
 				transport: TransportConfig::MemoryOnly,
+				sync_mode: config.sync_mode.clone(),
 				..NetworkConfiguration::default()
 			},
 			chain: client.clone(),
 			finality_proof_provider: self.make_finality_proof_provider(PeersClient::Light(client.clone(), backend.clone())),
 			finality_proof_request_builder,
+			warp_sync_verifier: None,
 			on_demand: None,
 			transaction_pool: Arc::new(EmptyTransactionPool),
 			protocol_id: ProtocolId::from(&b"test-protocol-name"[..]),
@@ -668,6 +704,7 @@ pub trait TestNetFactory: Sized {
 				imported_blocks_stream,
 				finality_notification_stream,
 				network,
+				listen_addr,
 			});
 		});
 	}
@@ -0,0 +1,129 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::protocol::consensus_gossip::{
+	ConsensusMessage, TopicNotification, Validator, ValidatorContext, ValidationResult,
+};
+use futures::sync::mpsc;
+use std::collections::HashSet;
+use std::sync::mpsc as std_mpsc;
+use std::time::{Duration, Instant};
+use tokio::runtime::current_thread;
+use super::*;
+
+const ENGINE_ID: sr_primitives::ConsensusEngineId = [1, 2, 3, 4];
+
+struct AllowAll;
+
+impl Validator<Block> for AllowAll {
+	fn validate(
+		&self,
+		_context: &mut dyn ValidatorContext<Block>,
+		_sender: &PeerId,
+		_data: &[u8],
+	) -> ValidationResult<H256> {
+		ValidationResult::ProcessAndKeep(H256::default())
+	}
+}
+
+/// Builds a network of a validator (peer 0), a sentry of that validator (peer 1) and an outsider
+/// (peer 2) that can only reach the validator through its sentry.
+fn sentry_network(runtime: &mut current_thread::Runtime) -> TestNet {
+	let mut net = TestNet::new(0);
+
+	net.add_full_peer(&Default::default());
+	net.peer(0).network_service().deny_unreserved_peers();
+
+	let mut sentry_validators = HashSet::new();
+	sentry_validators.insert(net.peer(0).id());
+	net.add_full_peer(&ProtocolConfig { sentry_validators, ..Default::default() });
+	let sentry = format!("{}/p2p/{}", net.peers()[1].listen_addr, net.peer(1).id().to_base58());
+	net.peer(0).network_service().add_reserved_peer(sentry).unwrap();
+
+	net.add_full_peer(&Default::default());
+
+	runtime.block_on(futures::future::poll_fn::<(), (), _>(|| -> Result<_, ()> {
+		net.poll();
+		if net.peer(0).num_peers() == 1 && net.peer(1).num_peers() == 2 && net.peer(2).num_peers() == 1 {
+			Ok(Async::Ready(()))
+		} else {
+			Ok(Async::NotReady)
+		}
+	})).unwrap();
+
+	net
+}
+
+fn gossip_receiver(net: &mut TestNet, peer: usize) -> mpsc::UnboundedReceiver<TopicNotification> {
+	let (tx, rx) = std_mpsc::channel();
+	net.peer(peer).network_service().with_gossip(move |gossip, context| {
+		gossip.register_validator(context, ENGINE_ID, Arc::new(AllowAll));
+		let _ = tx.send(gossip.messages_for(ENGINE_ID, H256::default()));
+	});
+	net.poll();
+	rx.recv_timeout(Duration::from_secs(10)).unwrap()
+}
+
+#[test]
+fn sentry_relays_validator_gossip_at_once() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = sentry_network(&mut runtime);
+
+	let _ = gossip_receiver(&mut net, 0);
+	let _ = gossip_receiver(&mut net, 1);
+	let mut outsider_messages = gossip_receiver(&mut net, 2);
+
+	net.peer(0).network_service().with_gossip(|gossip, context| {
+		let message = ConsensusMessage { engine_id: ENGINE_ID, data: vec![42] };
+		gossip.multicast(context, H256::default(), message, false);
+	});
+
+	// Well before the next rebroadcast of the sentry.
+	let deadline = Instant::now() + Duration::from_secs(10);
+	let notification = runtime.block_on(futures::future::poll_fn::<_, (), _>(|| -> Result<_, ()> {
+		net.poll();
+		assert!(Instant::now() < deadline, "validator gossip was not relayed by its sentry");
+		match outsider_messages.poll() {
+			Ok(Async::Ready(Some(notification))) => Ok(Async::Ready(notification)),
+			_ => Ok(Async::NotReady),
+		}
+	})).unwrap();
+
+	assert_eq!(notification.message, vec![42]);
+	assert_eq!(notification.sender, Some(net.peer(1).id()));
+	assert_eq!(net.peer(2).num_peers(), 1);
+}
+
+#[test]
+fn sentry_relays_validator_blocks() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = sentry_network(&mut runtime);
+
+	let hash = net.peer(0).push_blocks(1, false);
+
+	runtime.block_on(futures::future::poll_fn::<(), (), _>(|| -> Result<_, ()> {
+		net.poll();
+		if net.peer(2).client().info().chain.best_hash == hash {
+			Ok(Async::Ready(()))
+		} else {
+			Ok(Async::NotReady)
+		}
+	})).unwrap();
+
+	assert_eq!(net.peer(2).num_peers(), 1);
+}
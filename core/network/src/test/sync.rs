@@ -592,3 +592,42 @@ fn can_sync_explicit_forks() {
 		Ok(Async::Ready(()))
 	})).unwrap();
 }
+
+#[test]
+fn syncs_block_bodies_from_peers_that_keep_them() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = TestNet::new(1);
+
+	// peer1 only keeps the bodies of its last 10 blocks, peer2 keeps all of them.
+	let mut pruning_config = ProtocolConfig::default();
+	pruning_config.keep_block_bodies = Some(10);
+	net.add_full_peer(&pruning_config);
+	net.add_full_peer(&ProtocolConfig::default());
+
+	net.peer(1).push_blocks(100, false);
+	net.peer(2).push_blocks(100, false);
+	net.block_until_sync(&mut runtime);
+	let peer2 = &net.peers()[2];
+	assert!(net.peers()[0].blockchain_canon_equals(peer2));
+}
+
+#[test]
+fn does_not_sync_block_bodies_no_peer_keeps() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = TestNet::new(1);
+
+	let mut pruning_config = ProtocolConfig::default();
+	pruning_config.keep_block_bodies = Some(10);
+	net.add_full_peer(&pruning_config);
+	net.peer(1).push_blocks(100, false);
+
+	// Poll for a few seconds and make sure peer0 doesn't request the bodies peer1 deleted.
+	let mut delay = futures_timer::Delay::new(Duration::from_secs(5)).compat();
+	runtime.block_on(futures::future::poll_fn::<(), (), _>(|| {
+		net.poll();
+		delay.poll().map_err(|_| ())
+	})).unwrap();
+	assert_eq!(net.peer(0).client.info().chain.best_number, 0);
+}
@@ -0,0 +1,142 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use codec::{Encode, Decode};
+use crate::config::{SyncMode, WarpSyncProgress, WarpSyncVerifier};
+use tokio::runtime::current_thread;
+use super::*;
+
+type TestHeader = <Block as BlockT>::Header;
+
+/// Proves the latest finalized block of the peer, without any authority set change.
+struct TestWarpSyncProofProvider(PeersClient);
+
+impl FinalityProofProvider<Block> for TestWarpSyncProofProvider {
+	fn prove_finality(&self, _for_block: Hash, _request: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		Ok(None)
+	}
+
+	fn prove_warp_sync(&self, _begin: Hash) -> ClientResult<Option<Vec<u8>>> {
+		let finalized_number = self.0.info().chain.finalized_number;
+		if finalized_number == 0 {
+			return Ok(None)
+		}
+		Ok(self.0.header(&BlockId::Number(finalized_number))?.map(|header| header.encode()))
+	}
+}
+
+/// Accepts the block of the proof as the target of the warp sync, and records the targets applied.
+struct TestWarpSyncVerifier(Arc<Mutex<Vec<Hash>>>);
+
+impl WarpSyncVerifier<Block> for TestWarpSyncVerifier {
+	fn verify(&mut self, _begin: &Hash, proof: &[u8]) -> Result<WarpSyncProgress<Block>, String> {
+		let header = TestHeader::decode(&mut &proof[..]).map_err(|e| e.what().to_string())?;
+		Ok(WarpSyncProgress::Complete(header, Vec::new()))
+	}
+
+	fn apply(&mut self, header: &TestHeader) -> Result<(), String> {
+		self.0.lock().push(header.hash());
+		Ok(())
+	}
+
+	fn check_historic_justification(
+		&self,
+		_header: &TestHeader,
+		_justification: &Justification,
+	) -> Result<bool, String> {
+		Ok(false)
+	}
+}
+
+struct WarpSyncTestNet {
+	net: TestNet,
+	applied_targets: Arc<Mutex<Vec<Hash>>>,
+}
+
+impl TestNetFactory for WarpSyncTestNet {
+	type Specialization = DummySpecialization;
+	type Verifier = PassThroughVerifier;
+	type PeerData = ();
+
+	fn from_config(config: &ProtocolConfig) -> Self {
+		WarpSyncTestNet {
+			net: TestNet::from_config(config),
+			applied_targets: Arc::new(Mutex::new(Vec::new())),
+		}
+	}
+
+	fn make_verifier(&self, client: PeersClient, config: &ProtocolConfig, peer_data: &()) -> Self::Verifier {
+		self.net.make_verifier(client, config, peer_data)
+	}
+
+	fn peer(&mut self, i: usize) -> &mut Peer<Self::PeerData, Self::Specialization> {
+		self.net.peer(i)
+	}
+
+	fn peers(&self) -> &Vec<Peer<Self::PeerData, Self::Specialization>> {
+		self.net.peers()
+	}
+
+	fn mut_peers<F: FnOnce(&mut Vec<Peer<Self::PeerData, Self::Specialization>>)>(&mut self, closure: F) {
+		self.net.mut_peers(closure)
+	}
+
+	fn make_finality_proof_provider(&self, client: PeersClient) -> Option<Arc<dyn FinalityProofProvider<Block>>> {
+		Some(Arc::new(TestWarpSyncProofProvider(client)))
+	}
+
+	fn make_warp_sync_verifier(&self, _client: PeersClient) -> Option<BoxWarpSyncVerifier<Block>> {
+		Some(Box::new(TestWarpSyncVerifier(self.applied_targets.clone())))
+	}
+}
+
+#[test]
+fn warp_sync_imports_state_of_finalized_block_and_backfills() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = WarpSyncTestNet::new(1);
+	net.peer(0).push_blocks(10, false);
+	net.peer(0).client().finalize_block(BlockId::Number(8), None, true).unwrap();
+	let target = net.peer(0).client().header(&BlockId::Number(8)).unwrap().unwrap();
+	let best_hash = net.peer(0).client().info().chain.best_hash;
+
+	net.add_full_peer(&ProtocolConfig {
+		sync_mode: SyncMode::Warp { backfill: true },
+		..Default::default()
+	});
+
+	// wait until the blocks after the target are synced and the blocks below it are backfilled.
+	runtime.block_on(futures::future::poll_fn::<(), (), _>(|| -> Result<_, ()> {
+		net.poll();
+		let client = net.peers()[1].client();
+		if client.info().chain.best_hash == best_hash && client.header(&BlockId::Number(1)).unwrap().is_some() {
+			Ok(Async::Ready(()))
+		} else {
+			Ok(Async::NotReady)
+		}
+	})).unwrap();
+
+	// the state of the target was downloaded and its block imported, instead of every block.
+	assert_eq!(*net.applied_targets.lock(), vec![target.hash()]);
+	let client = net.peers()[1].client();
+	assert_eq!(client.info().chain.finalized_hash, target.hash());
+	for number in 1..8 {
+		assert_eq!(
+			client.header(&BlockId::Number(number)).unwrap(),
+			net.peers()[0].client().header(&BlockId::Number(number)).unwrap(),
+		);
+	}
+}
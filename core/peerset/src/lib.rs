@@ -19,7 +19,7 @@
 
 mod peersstate;
 
-use std::{collections::{HashSet, HashMap}, collections::VecDeque, time::Instant};
+use std::{collections::{HashSet, HashMap}, collections::VecDeque, time::{Duration, Instant}};
 use futures::{prelude::*, channel::mpsc};
 use libp2p::PeerId;
 use log::{debug, error, trace};
@@ -145,6 +145,10 @@ pub struct PeersetConfig {
 	/// > **Note**: Keep in mind that the networking has to know an address for these nodes,
 	/// >			otherwise it will not be able to connect to them.
 	pub reserved_nodes: Vec<PeerId>,
+
+	/// Initial reputations of nodes, typically those returned by `Peerset::reputations` during a
+	/// previous run. These nodes are considered as discovered.
+	pub reputations: Vec<(PeerId, i32)>,
 }
 
 /// Side of the peer set manager owned by the network. In other words, the "receiving" side.
@@ -195,6 +199,13 @@ impl Peerset {
 				debug!(target: "peerset", "Duplicate bootnode in config: {:?}", peer_id);
 			}
 		}
+		for (peer_id, reputation) in config.reputations {
+			match peerset.data.peer(&peer_id) {
+				peersstate::Peer::Unknown(entry) => entry.discover().set_reputation(reputation),
+				peersstate::Peer::NotConnected(mut entry) => entry.set_reputation(reputation),
+				peersstate::Peer::Connected(_) => unreachable!("We aren't connected to anyone yet; qed"),
+			}
+		}
 
 		peerset.alloc_slots();
 		(peerset, handle)
@@ -289,22 +300,8 @@ impl Peerset {
 		};
 
 		// For each elapsed second, move the node reputation towards zero.
-		// If we multiply each second the reputation by `k` (where `k` is between 0 and 1), it
-		// takes `ln(0.5) / ln(k)` seconds to reduce the reputation by half. Use this formula to
-		// empirically determine a value of `k` that looks correct.
 		for _ in 0..secs_diff {
 			for peer in self.data.peers().cloned().collect::<Vec<_>>() {
-				// We use `k = 0.98`, so we divide by `50`. With that value, it takes 34.3 seconds
-				// to reduce the reputation by half.
-				fn reput_tick(reput: i32) -> i32 {
-					let mut diff = reput / 50;
-					if diff == 0 && reput < 0 {
-						diff = -1;
-					} else if diff == 0 && reput > 0 {
-						diff = 1;
-					}
-					reput.saturating_sub(diff)
-				}
 				match self.data.peer(&peer) {
 					peersstate::Peer::Connected(mut peer) =>
 						peer.set_reputation(reput_tick(peer.reputation())),
@@ -469,6 +466,52 @@ impl Peerset {
 	pub fn get_priority_group(&self, group_id: &str) -> Option<HashSet<PeerId>> {
 		self.data.get_priority_group(group_id)
 	}
+
+	/// Returns the current reputation of all the nodes we know about.
+	pub fn reputations(&mut self) -> Vec<(PeerId, i32)> {
+		self.update_time();
+
+		self.data.peers().cloned().collect::<Vec<_>>().into_iter().map(|peer_id| {
+			let reputation = match self.data.peer(&peer_id) {
+				peersstate::Peer::Connected(entry) => entry.reputation(),
+				peersstate::Peer::NotConnected(entry) => entry.reputation(),
+				peersstate::Peer::Unknown(_) =>
+					unreachable!("We iterate over the known peers; QED")
+			};
+			(peer_id, reputation)
+		}).collect()
+	}
+}
+
+/// Moves a reputation towards zero by one second's worth of decay.
+///
+/// If we multiply each second the reputation by `k` (where `k` is between 0 and 1), it takes
+/// `ln(0.5) / ln(k)` seconds to reduce the reputation by half. Use this formula to empirically
+/// determine a value of `k` that looks correct.
+fn reput_tick(reput: i32) -> i32 {
+	// We use `k = 0.98`, so we divide by `50`. With that value, it takes 34.3 seconds to reduce
+	// the reputation by half.
+	let mut diff = reput / 50;
+	if diff == 0 && reput < 0 {
+		diff = -1;
+	} else if diff == 0 && reput > 0 {
+		diff = 1;
+	}
+	reput.saturating_sub(diff)
+}
+
+/// Returns what `reputation` becomes after `elapsed` has passed, for example while the node was
+/// offline.
+pub fn decay_reputation(mut reputation: i32, elapsed: Duration) -> i32 {
+	// Every reputation reaches zero after a bounded number of ticks, after which there is no
+	// point in continuing.
+	for _ in 0..elapsed.as_secs() {
+		if reputation == 0 {
+			break
+		}
+		reputation = reput_tick(reputation);
+	}
+	reputation
 }
 
 impl Stream for Peerset {
@@ -510,7 +553,7 @@ impl Stream for Peerset {
 mod tests {
 	use libp2p::PeerId;
 	use futures::prelude::*;
-	use super::{PeersetConfig, Peerset, Message, IncomingIndex, BANNED_THRESHOLD};
+	use super::{PeersetConfig, Peerset, Message, IncomingIndex, BANNED_THRESHOLD, decay_reputation};
 	use std::{pin::Pin, task::Poll, thread, time::Duration};
 
 	fn assert_messages(mut peerset: Peerset, messages: Vec<Message>) -> Peerset {
@@ -540,6 +583,7 @@ mod tests {
 			bootnodes: vec![bootnode],
 			reserved_only: true,
 			reserved_nodes: Vec::new(),
+			reputations: Vec::new(),
 		};
 
 		let (peerset, handle) = Peerset::from_config(config);
@@ -568,6 +612,7 @@ mod tests {
 			bootnodes: vec![bootnode.clone()],
 			reserved_only: false,
 			reserved_nodes: Vec::new(),
+			reputations: Vec::new(),
 		};
 
 		let (mut peerset, _handle) = Peerset::from_config(config);
@@ -595,6 +640,7 @@ mod tests {
 			bootnodes: vec![bootnode.clone()],
 			reserved_only: false,
 			reserved_nodes: vec![],
+			reputations: vec![],
 		};
 
 		let (mut peerset, _handle) = Peerset::from_config(config);
@@ -608,6 +654,34 @@ mod tests {
 		]);
 	}
 
+	#[test]
+	fn test_peerset_initial_reputations() {
+		let banned = PeerId::random();
+		let known = PeerId::random();
+		let (mut peerset, _handle) = Peerset::from_config(PeersetConfig {
+			in_peers: 25,
+			out_peers: 25,
+			bootnodes: vec![],
+			reserved_only: false,
+			reserved_nodes: vec![],
+			reputations: vec![(banned.clone(), BANNED_THRESHOLD - 1), (known.clone(), 100)],
+		});
+
+		let mut reputations = peerset.reputations();
+		reputations.sort_by_key(|(_, reputation)| *reputation);
+		assert_eq!(reputations, vec![(banned.clone(), BANNED_THRESHOLD - 1), (known.clone(), 100)]);
+
+		// Only the node with a good reputation is connected to, and the banned one is refused.
+		peerset.incoming(banned, IncomingIndex(1));
+		assert_messages(peerset, vec![
+			Message::Connect(known),
+			Message::Reject(IncomingIndex(1)),
+		]);
+
+		assert_eq!(decay_reputation(100, Duration::from_secs(0)), 100);
+		assert_eq!(decay_reputation(i32::min_value(), Duration::from_secs(3600)), 0);
+	}
+
 	#[test]
 	fn test_peerset_banned() {
 		let (mut peerset, handle) = Peerset::from_config(PeersetConfig {
@@ -616,6 +690,7 @@ mod tests {
 			bootnodes: vec![],
 			reserved_only: false,
 			reserved_nodes: vec![],
+			reputations: vec![],
 		});
 
 		// We ban a node by setting its reputation under the threshold.
@@ -25,17 +25,20 @@ pub mod blake2 {
 	#[cfg(feature = "std")]
 	use crate::hashing::blake2_256;
 
+	#[cfg(not(feature = "std"))]
+	use runtime_interface::{RIType, wasm::{IntoFFIValue, FromFFIValue}};
+
+	// `sr-io` depends on this crate, so the `blake2_256` host function of its `hashing` runtime
+	// interface is declared here directly.
 	#[cfg(not(feature = "std"))]
 	extern "C" {
-		fn ext_blake2_256(data: *const u8, len: u32, out: *mut u8);
+		fn ext_hashing_blake2_256_version_1(data: <[u8] as RIType>::FFIType) -> <[u8; 32] as RIType>::FFIType;
 	}
 	#[cfg(not(feature = "std"))]
 	fn blake2_256(data: &[u8]) -> [u8; 32] {
-		let mut result: [u8; 32] = Default::default();
-		unsafe {
-			ext_blake2_256(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
+		let data = data.into_ffi_value();
+		let result = unsafe { ext_hashing_blake2_256_version_1(data.get()) };
+		<[u8; 32]>::from_ffi_value(result)
 	}
 
 	/// Concrete implementation of Hasher using Blake2b 256-bit hashes
@@ -20,7 +20,7 @@ use crate::{crypto::KeyTypeId, ed25519, sr25519};
 
 use std::{fmt::{Debug, Display}, panic::UnwindSafe};
 
-pub use externalities::{Externalities, ExternalitiesExt};
+pub use externalities::{Externalities, ExternalitiesExt, ExtensionStore};
 
 /// Something that generates, stores and provides access to keys.
 pub trait BareCryptoStore: Send + Sync {
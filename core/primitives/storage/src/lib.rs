@@ -67,6 +67,13 @@ pub mod well_known_keys {
 	/// Changes trie configuration is stored under this key.
 	pub const CHANGES_TRIE_CONFIG: &'static [u8] = b":changes_trie";
 
+	/// New child tries get a unique id if a value is stored under this key.
+	///
+	/// The value of a child trie in the top trie is then its root followed by its unique id, and
+	/// its nodes are stored in a key space of their own. Child tries of chains that don't set this
+	/// key only store their root, as before unique ids were introduced.
+	pub const CHILD_TRIE_UNIQUE_IDS: &'static [u8] = b":child_trie_unique_ids";
+
 	/// Prefix of child storage keys.
 	pub const CHILD_STORAGE_KEY_PREFIX: &'static [u8] = b":child_storage:";
 
@@ -0,0 +1,57 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Policy-related types.
+//!
+//! Some RPC methods are expensive for the node, for example because they re-execute blocks. They
+//! are only allowed when the RPC servers aren't exposed publicly, or when the node operator
+//! explicitly allows them.
+
+/// Whether the RPC methods that are unsafe to expose publicly are denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenyUnsafe {
+	/// Unsafe RPC methods fail with `UnsafeRpcError`.
+	Yes,
+	/// All RPC methods are allowed.
+	No,
+}
+
+impl DenyUnsafe {
+	/// Returns an error if the RPC methods that are unsafe to expose publicly are denied.
+	pub fn check_if_safe(self) -> Result<(), UnsafeRpcError> {
+		match self {
+			DenyUnsafe::Yes => Err(UnsafeRpcError),
+			DenyUnsafe::No => Ok(()),
+		}
+	}
+}
+
+/// An unsafe RPC method was called while they are denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+#[display(fmt = "RPC call is unsafe to be called externally")]
+pub struct UnsafeRpcError;
+
+impl std::error::Error for UnsafeRpcError {}
+
+impl From<UnsafeRpcError> for jsonrpc_core::Error {
+	fn from(error: UnsafeRpcError) -> jsonrpc_core::Error {
+		jsonrpc_core::Error {
+			code: jsonrpc_core::ErrorCode::MethodNotFound,
+			message: error.to_string(),
+			data: None,
+		}
+	}
+}
@@ -0,0 +1,70 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Substrate state API helpers.
+
+use primitives::{Bytes, storage::{StorageKey, StorageData}};
+use serde::{Serialize, Deserialize};
+
+/// A host function call made by the runtime.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostCallTrace {
+	/// Name of the host function.
+	pub function: String,
+	/// Arguments the host function was called with.
+	pub args: Vec<String>,
+	/// Value returned by the host function.
+	pub result: Option<String>,
+	/// Error returned by the host function.
+	pub error: Option<String>,
+	/// Storage key accessed by the host function.
+	pub storage_key: Option<Bytes>,
+	/// Storage key of the child trie accessed by the host function.
+	pub child_storage_key: Option<Bytes>,
+	/// Prefix of the storage keys accessed by the host function.
+	pub storage_prefix: Option<Bytes>,
+	/// Size of the data returned to the runtime.
+	pub result_len: Option<u32>,
+	/// Execution time in nanoseconds.
+	pub duration: u64,
+}
+
+/// The host calls made by the runtime while executing a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockTrace {
+	/// Host calls made while initializing the block.
+	pub initialize: Vec<HostCallTrace>,
+	/// Host calls made while applying the extrinsics, one entry per extrinsic in block order.
+	pub extrinsics: Vec<Vec<HostCallTrace>>,
+	/// Host calls made while finalizing the block.
+	pub finalize: Vec<HostCallTrace>,
+}
+
+/// A storage entry changed by a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDiff {
+	/// Storage key of the child trie of the entry, if it is in a child trie.
+	pub child_storage_key: Option<StorageKey>,
+	/// Key of the entry.
+	pub key: StorageKey,
+	/// Value before the block.
+	pub old_value: Option<StorageData>,
+	/// Value after the block.
+	pub new_value: Option<StorageData>,
+}
@@ -17,6 +17,7 @@
 //! Substrate state API.
 
 pub mod error;
+pub mod helpers;
 
 use jsonrpc_core::Result as RpcResult;
 use jsonrpc_core::futures::Future;
@@ -28,6 +29,7 @@ use runtime_version::RuntimeVersion;
 use self::error::FutureResult;
 
 pub use self::gen_client::Client as StateClient;
+pub use self::helpers::{BlockTrace, HostCallTrace, StorageDiff};
 
 /// Substrate state API
 #[rpc]
@@ -43,6 +45,28 @@ pub trait StateApi<Hash> {
 	#[rpc(name = "state_getKeys")]
 	fn storage_keys(&self, prefix: StorageKey, hash: Option<Hash>) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns at most `count` keys with prefix, in lexicographic order, starting after
+	/// `start_key` if it is given. Leave the prefix empty to page through all the keys.
+	#[rpc(name = "state_getKeysPaged")]
+	fn storage_keys_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<StorageKey>>;
+
+	/// Returns at most `count` keys with prefix and their values, in lexicographic order, starting
+	/// after `start_key` if it is given. Leave the prefix empty to page through all the entries.
+	#[rpc(name = "state_getPairsPaged")]
+	fn storage_pairs_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>>;
+
 	/// Returns a storage entry at a specific block's state.
 	#[rpc(name = "state_getStorage", alias("state_getStorageAt"))]
 	fn storage(&self, key: StorageKey, hash: Option<Hash>) -> FutureResult<Option<StorageData>>;
@@ -111,6 +135,15 @@ pub trait StateApi<Hash> {
 		hash: Option<Hash>
 	) -> FutureResult<Vec<StorageChangeSet<Hash>>>;
 
+	/// Re-executes a block and returns the host calls made by the runtime, per extrinsic.
+	#[rpc(name = "state_traceBlock")]
+	fn trace_block(&self, block: Hash) -> FutureResult<BlockTrace>;
+
+	/// Returns the storage entries changed by a block with their old and new values,
+	/// optionally only the ones with keys that start with `prefix`.
+	#[rpc(name = "state_getStorageDiff")]
+	fn storage_diff(&self, block: Hash, prefix: Option<StorageKey>) -> FutureResult<Vec<StorageDiff>>;
+
 	/// New runtime version subscription
 	#[pubsub(
 		subscription = "state_runtimeVersion",
@@ -23,7 +23,7 @@
 mod helpers;
 mod metadata;
 
-pub use api::Subscriptions;
+pub use api::{DenyUnsafe, Subscriptions};
 pub use self::metadata::Metadata;
 pub use rpc::IoHandlerExtension as RpcExtension;
 
@@ -26,10 +26,10 @@ use std::sync::Arc;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use rpc::{
 	Result as RpcResult,
-	futures::Future,
+	futures::{Future, future::result},
 };
 
-use api::Subscriptions;
+use api::{DenyUnsafe, Subscriptions};
 use client::{
 	Client, CallExecutor,
 	runtime_api::Metadata,
@@ -48,6 +48,9 @@ use self::error::{Error, FutureResult};
 
 pub use api::state::*;
 
+/// Maximal number of keys returned by one paged storage request.
+const STORAGE_KEYS_PAGED_MAX_COUNT: u32 = 1000;
+
 /// State backend API.
 pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 	where
@@ -71,6 +74,27 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		prefix: StorageKey,
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns at most `count` keys with prefix and their values, starting after `start_key`.
+	fn storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>>;
+
+	/// Returns at most `count` keys with prefix, starting after `start_key`.
+	fn storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(self.storage_pairs_paged(block, prefix, count, start_key)
+			.map(|pairs| pairs.into_iter().map(|(key, _)| key).collect()))
+	}
+
 	/// Returns a storage entry at a specific block's state.
 	fn storage(
 		&self,
@@ -147,6 +171,18 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		keys: Vec<StorageKey>,
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>>;
 
+	/// Re-executes a block and returns the host calls made by the runtime, per extrinsic.
+	///
+	/// This is an unsafe RPC method, as re-executing blocks is expensive.
+	fn trace_block(&self, block: Block::Hash) -> FutureResult<BlockTrace>;
+
+	/// Returns the storage entries changed by a block with their old and new values.
+	fn storage_diff(
+		&self,
+		block: Block::Hash,
+		prefix: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageDiff>>;
+
 	/// New runtime version subscription
 	fn subscribe_runtime_version(
 		&self,
@@ -181,6 +217,7 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 pub fn new_full<B, E, Block: BlockT, RA>(
 	client: Arc<Client<B, E, Block, RA>>,
 	subscriptions: Subscriptions,
+	deny_unsafe: DenyUnsafe,
 ) -> State<B, E, Block, RA>
 	where
 		Block: BlockT<Hash=H256> + 'static,
@@ -192,6 +229,7 @@ pub fn new_full<B, E, Block: BlockT, RA>(
 {
 	State {
 		backend: Box::new(self::state_full::FullState::new(client, subscriptions)),
+		deny_unsafe,
 	}
 }
 
@@ -201,6 +239,7 @@ pub fn new_light<B, E, Block: BlockT, RA, F: Fetcher<Block>>(
 	subscriptions: Subscriptions,
 	remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
 	fetcher: Arc<F>,
+	deny_unsafe: DenyUnsafe,
 ) -> State<B, E, Block, RA>
 	where
 		Block: BlockT<Hash=H256> + 'static,
@@ -216,12 +255,15 @@ pub fn new_light<B, E, Block: BlockT, RA, F: Fetcher<Block>>(
 			remote_blockchain,
 			fetcher,
 		)),
+		deny_unsafe,
 	}
 }
 
 /// State API with subscriptions support.
 pub struct State<B, E, Block, RA> {
 	backend: Box<dyn StateBackend<B, E, Block, RA>>,
+	/// Whether to deny unsafe calls.
+	deny_unsafe: DenyUnsafe,
 }
 
 impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
@@ -245,6 +287,38 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.storage_keys(block, key_prefix)
 	}
 
+	fn storage_keys_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<StorageKey>> {
+		if count > STORAGE_KEYS_PAGED_MAX_COUNT {
+			return Box::new(result(Err(
+				Error::InvalidCount { value: count, max: STORAGE_KEYS_PAGED_MAX_COUNT }
+			)));
+		}
+		let prefix = prefix.unwrap_or(StorageKey(Vec::new()));
+		self.backend.storage_keys_paged(block, prefix, count, start_key)
+	}
+
+	fn storage_pairs_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		if count > STORAGE_KEYS_PAGED_MAX_COUNT {
+			return Box::new(result(Err(
+				Error::InvalidCount { value: count, max: STORAGE_KEYS_PAGED_MAX_COUNT }
+			)));
+		}
+		let prefix = prefix.unwrap_or(StorageKey(Vec::new()));
+		self.backend.storage_pairs_paged(block, prefix, count, start_key)
+	}
+
 	fn storage(&self, key: StorageKey, block: Option<Block::Hash>) -> FutureResult<Option<StorageData>> {
 		self.backend.storage(block, key)
 	}
@@ -306,6 +380,26 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.query_storage(from, to, keys)
 	}
 
+	fn trace_block(&self, block: Block::Hash) -> FutureResult<BlockTrace> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(result(Err(err.into())));
+		}
+
+		self.backend.trace_block(block)
+	}
+
+	fn storage_diff(
+		&self,
+		block: Block::Hash,
+		prefix: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageDiff>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(result(Err(err.into())));
+		}
+
+		self.backend.storage_diff(block, prefix)
+	}
+
 	fn subscribe_storage(
 		&self,
 		meta: Self::Metadata,
@@ -24,7 +24,7 @@ use log::warn;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use rpc::{
 	Result as RpcResult,
-	futures::{stream, Future, Sink, Stream, future::result},
+	futures::{stream, Future, Sink, Stream, future::result, sync::oneshot},
 };
 
 use api::Subscriptions;
@@ -42,7 +42,12 @@ use sr_primitives::{
 	traits::{Block as BlockT, Header, NumberFor, ProvideRuntimeApi, SaturatedConversion},
 };
 
-use super::{StateBackend, error::{FutureResult, Error, Result}, client_err};
+use substrate_executor::trace::{HostCall, HostCallDetail};
+
+use super::{
+	StateBackend, BlockTrace, HostCallTrace, StorageDiff, error::{FutureResult, Error, Result},
+	client_err,
+};
 
 /// Ranges to query in state_queryStorage.
 struct QueryStorageRange<Block: BlockT> {
@@ -265,6 +270,24 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 				.map_err(client_err)))
 	}
 
+	fn storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| self.client.storage_pairs_paged(
+					&BlockId::Hash(block),
+					&prefix,
+					start_key.as_ref(),
+					count,
+				))
+				.map_err(client_err)))
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -354,6 +377,40 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 		Box::new(result(call_fn()))
 	}
 
+	fn trace_block(&self, block: Block::Hash) -> FutureResult<BlockTrace> {
+		let client = self.client.clone();
+		spawn_blocking("rpc-trace-block", move ||
+			client.trace_block(&BlockId::Hash(block))
+				.map(|trace| BlockTrace {
+					initialize: host_call_traces(trace.initialize),
+					extrinsics: trace.extrinsics.into_iter().map(host_call_traces).collect(),
+					finalize: host_call_traces(trace.finalize),
+				})
+				.map_err(client_err)
+		)
+	}
+
+	fn storage_diff(
+		&self,
+		block: Block::Hash,
+		prefix: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageDiff>> {
+		let client = self.client.clone();
+		spawn_blocking("rpc-storage-diff", move ||
+			client.storage_diff(&BlockId::Hash(block), &prefix.unwrap_or(StorageKey(Vec::new())))
+				.map(|diff| diff.into_iter()
+					.map(|(child_storage_key, key, old_value, new_value)| StorageDiff {
+						child_storage_key,
+						key,
+						old_value,
+						new_value,
+					})
+					.collect()
+				)
+				.map_err(client_err)
+		)
+	}
+
 	fn subscribe_runtime_version(
 		&self,
 		_meta: crate::metadata::Metadata,
@@ -507,3 +564,57 @@ fn invalid_block_range<H: Header>(from: Option<&H>, to: Option<&H>, reason: Stri
 		details: reason,
 	}
 }
+
+/// Converts the host calls recorded by the executor into their RPC representation.
+fn host_call_traces(calls: Vec<HostCall>) -> Vec<HostCallTrace> {
+	calls.into_iter().map(|call| {
+		let mut trace = HostCallTrace {
+			function: call.function,
+			args: call.args.iter().map(|arg| format!("{:?}", arg)).collect(),
+			result: call.result.map(|result| format!("{:?}", result)),
+			error: call.error,
+			storage_key: None,
+			child_storage_key: None,
+			storage_prefix: None,
+			result_len: None,
+			duration: call.duration.as_secs()
+				.saturating_mul(1_000_000_000)
+				.saturating_add(u64::from(call.duration.subsec_nanos())),
+		};
+
+		for detail in call.details {
+			match detail {
+				HostCallDetail::StorageKey(key) => trace.storage_key = Some(key.into()),
+				HostCallDetail::ChildStorageKey(key) => trace.child_storage_key = Some(key.into()),
+				HostCallDetail::StoragePrefix(prefix) => trace.storage_prefix = Some(prefix.into()),
+				HostCallDetail::ResultLen(len) => trace.result_len = Some(len),
+			}
+		}
+
+		trace
+	}).collect()
+}
+
+/// Runs `f` on a dedicated thread, so that expensive calls don't block the RPC server.
+fn spawn_blocking<T, F>(name: &str, f: F) -> FutureResult<T>
+	where
+		T: Send + 'static,
+		F: FnOnce() -> Result<T> + Send + 'static,
+{
+	let (tx, rx) = oneshot::channel();
+	let spawned = std::thread::Builder::new()
+		.name(name.into())
+		.spawn(move || {
+			let _ = tx.send(f());
+		});
+	if let Err(err) = spawned {
+		return Box::new(result(Err(client_err(client::error::Error::Msg(
+			format!("Failed to spawn the {} thread: {}", name, err)
+		)))));
+	}
+
+	Box::new(rx.then(|res| match res {
+		Ok(res) => res,
+		Err(_) => Err(client_err(client::error::Error::Msg("Blocking RPC call panicked".into()))),
+	}))
+}
@@ -17,6 +17,7 @@
 //! State API backend for light nodes.
 
 use std::{
+	pin::Pin,
 	sync::Arc,
 	collections::{HashSet, HashMap, hash_map::Entry},
 };
@@ -44,7 +45,10 @@ use client::{
 	error::Error as ClientError,
 	light::{
 		blockchain::{future_header, RemoteBlockchain},
-		fetcher::{Fetcher, RemoteCallRequest, RemoteReadRequest, RemoteReadChildRequest},
+		fetcher::{
+			Fetcher, RemoteCallRequest, RemoteReadRequest, RemoteReadChildRequest,
+			RemoteReadRangeRequest,
+		},
 	},
 };
 use primitives::{
@@ -57,7 +61,7 @@ use sr_primitives::{
 	traits::Block as BlockT,
 };
 
-use super::{StateBackend, error::{FutureResult, Error}, client_err};
+use super::{StateBackend, BlockTrace, StorageDiff, error::{FutureResult, Error}, client_err};
 
 /// Storage data map of storage keys => (optional) storage value.
 type StorageMap = HashMap<StorageKey, Option<StorageData>>;
@@ -202,6 +206,42 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
 	}
 
+	fn storage_pairs_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: StorageKey,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<(StorageKey, StorageData)>> {
+		let block = self.block_or_best(block);
+		let fetcher = self.fetcher.clone();
+		let pairs = resolve_header(&*self.remote_blockchain, &*self.fetcher, block)
+			.then(move |result| match result {
+				Ok(header) => Either::Left(read_range_in_parts(
+					move |start_key, count| fetcher.remote_read_range(RemoteReadRangeRequest {
+						block,
+						header: header.clone(),
+						prefix: prefix.0.clone(),
+						start_key,
+						count: Some(count),
+						retry_count: Default::default(),
+					}).then(|result| ready(result.map_err(client_err))),
+					start_key.map(|key| key.0),
+					count,
+					Vec::new(),
+				).then(|result| ready(result
+					.map(|pairs| pairs
+						.into_iter()
+						.map(|(key, value)| (StorageKey(key), StorageData(value)))
+						.collect()
+					)
+				))),
+				Err(error) => Either::Right(ready(Err(error))),
+			});
+
+		Box::new(pairs.boxed().compat())
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -313,6 +353,18 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
 	}
 
+	fn trace_block(&self, _block: Block::Hash) -> FutureResult<BlockTrace> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
+	fn storage_diff(
+		&self,
+		_block: Block::Hash,
+		_prefix: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageDiff>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
 	fn subscribe_storage(
 		&self,
 		_meta: crate::metadata::Metadata,
@@ -575,6 +627,37 @@ fn storage<Block: BlockT, F: Fetcher<Block>>(
 		})
 }
 
+/// Read the first `count` keys of a range of keys, with their values, in parts.
+///
+/// A remote range read ends early when its proof grows too large, so the rest of the range is read
+/// again from the last key read, until `count` keys are read or a read returns no keys.
+fn read_range_in_parts<Read, ReadFuture>(
+	read: Read,
+	start_key: Option<Vec<u8>>,
+	count: u32,
+	mut pairs: Vec<(Vec<u8>, Vec<u8>)>,
+) -> Pin<Box<dyn std::future::Future<Output = Result<Vec<(Vec<u8>, Vec<u8>)>, Error>> + Send>>
+	where
+		Read: Fn(Option<Vec<u8>>, u32) -> ReadFuture + Send + 'static,
+		ReadFuture: std::future::Future<Output = Result<Vec<(Vec<u8>, Vec<u8>)>, Error>> + Send + 'static,
+{
+	let remaining = count.saturating_sub(pairs.len() as u32);
+	read(start_key, remaining).then(move |result| match result {
+		Ok(part) => {
+			let is_end = part.is_empty();
+			pairs.extend(part);
+			if is_end || pairs.len() as u32 >= count {
+				pairs.truncate(count as usize);
+				Either::Left(ready(Ok(pairs)))
+			} else {
+				let start_key = pairs.last().map(|(key, _)| key.clone());
+				Either::Right(read_range_in_parts(read, start_key, count, pairs))
+			}
+		},
+		Err(error) => Either::Left(ready(Err(error))),
+	}).boxed()
+}
+
 /// Returns subscription stream that issues request on every imported block and
 /// if value has changed from previous block, emits (stream) item.
 fn subscription_stream<
@@ -750,6 +833,36 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn read_range_in_parts_reads_until_count_or_end() {
+		let keys = vec![vec![1], vec![2], vec![3], vec![4], vec![5]];
+		let read_parts = |count| {
+			let requests = Arc::new(Mutex::new(0));
+			let requests_issued = requests.clone();
+			let keys = keys.clone();
+			let pairs = read_range_in_parts(
+				move |start_key: Option<Vec<u8>>, requested: u32| {
+					*requests_issued.lock() += 1;
+					// every remote read is truncated to two keys, as if its proof was too large
+					ready(Ok(keys.iter()
+						.filter(|key| start_key.as_ref().map_or(true, |start_key| *key > start_key))
+						.take(requested.min(2) as usize)
+						.map(|key| (key.clone(), key.clone()))
+						.collect()))
+				},
+				None,
+				count,
+				Vec::new(),
+			).compat().wait().unwrap();
+			let requests = *requests.lock();
+			(pairs.into_iter().map(|(key, _)| key).collect::<Vec<_>>(), requests)
+		};
+
+		assert_eq!(read_parts(3), (keys[..3].to_vec(), 2));
+		assert_eq!(read_parts(4), (keys[..4].to_vec(), 2));
+		assert_eq!(read_parts(10), (keys.clone(), 4));
+	}
+
 	#[test]
 	fn maybe_share_remote_request_shares_request() {
 		type UnreachableFuture = futures03::future::Ready<Result<u32, Error>>;
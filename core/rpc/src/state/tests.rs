@@ -42,7 +42,7 @@ fn should_return_storage() {
 		.add_extra_child_storage(STORAGE_KEY.to_vec(), KEY.to_vec(), CHILD_VALUE.to_vec())
 		.build();
 	let genesis_hash = client.genesis_hash();
-	let client = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())));
+	let client = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
 	let key = StorageKey(KEY.to_vec());
 	let storage_key = StorageKey(STORAGE_KEY.to_vec());
 
@@ -70,6 +70,37 @@ fn should_return_storage() {
 
 }
 
+#[test]
+fn should_return_storage_pages() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = TestClientBuilder::new()
+		.add_extra_storage(b":mock1".to_vec(), vec![1])
+		.add_extra_storage(b":mock2".to_vec(), vec![2])
+		.add_extra_storage(b":mock3".to_vec(), vec![3])
+		.build();
+	let genesis_hash = client.genesis_hash();
+	let client = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
+	let prefix = Some(StorageKey(b":mock".to_vec()));
+
+	assert_eq!(
+		client.storage_keys_paged(prefix.clone(), 2, None, Some(genesis_hash)).wait().unwrap(),
+		vec![StorageKey(b":mock1".to_vec()), StorageKey(b":mock2".to_vec())],
+	);
+	assert_eq!(
+		client.storage_pairs_paged(
+			prefix.clone(),
+			2,
+			Some(StorageKey(b":mock2".to_vec())),
+			None,
+		).wait().unwrap(),
+		vec![(StorageKey(b":mock3".to_vec()), StorageData(vec![3]))],
+	);
+	assert_matches!(
+		client.storage_keys_paged(prefix, 1001, None, None).wait(),
+		Err(Error::InvalidCount { value: 1001, max: 1000 })
+	);
+}
+
 #[test]
 fn should_return_child_storage() {
 	let core = tokio::runtime::Runtime::new().unwrap();
@@ -77,7 +108,7 @@ fn should_return_child_storage() {
 		.add_child_storage("test", "key", vec![42_u8])
 		.build());
 	let genesis_hash = client.genesis_hash();
-	let client = new_full(client, Subscriptions::new(Arc::new(core.executor())));
+	let client = new_full(client, Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
 	let child_key = StorageKey(well_known_keys::CHILD_STORAGE_KEY_PREFIX.iter().chain(b"test").cloned().collect());
 	let key = StorageKey(b"key".to_vec());
 
@@ -102,7 +133,7 @@ fn should_call_contract() {
 	let core = tokio::runtime::Runtime::new().unwrap();
 	let client = Arc::new(test_client::new());
 	let genesis_hash = client.genesis_hash();
-	let client = new_full(client, Subscriptions::new(Arc::new(core.executor())));
+	let client = new_full(client, Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
 
 	assert_matches!(
 		client.call("balanceOf".into(), Bytes(vec![1,2,3]), Some(genesis_hash).into()).wait(),
@@ -118,7 +149,7 @@ fn should_notify_about_storage_changes() {
 
 	{
 		let client = Arc::new(test_client::new());
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+		let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)), DenyUnsafe::No);
 
 		api.subscribe_storage(Default::default(), subscriber, None.into());
 
@@ -150,7 +181,7 @@ fn should_send_initial_storage_changes_and_notifications() {
 
 	{
 		let client = Arc::new(test_client::new());
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+		let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)), DenyUnsafe::No);
 
 		let alice_balance_key = blake2_256(&runtime::system::balance_of_key(AccountKeyring::Alice.into()));
 
@@ -181,11 +212,72 @@ fn should_send_initial_storage_changes_and_notifications() {
 	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
 }
 
+#[test]
+fn should_trace_block() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
+
+	let mut builder = client.new_block(Default::default()).unwrap();
+	builder.push_storage_change(vec![42], Some(vec![1, 2, 3])).unwrap();
+	let block = builder.bake().unwrap();
+	let hash = block.header.hash();
+	client.import(BlockOrigin::Own, block).unwrap();
+
+	let trace = api.trace_block(hash).wait().unwrap();
+	assert_eq!(trace.extrinsics.len(), 1);
+	assert!(!trace.initialize.is_empty());
+	assert!(
+		trace.extrinsics[0].iter().any(|call|
+			call.function == "ext_set_storage" && call.storage_key == Some(vec![42].into())
+		)
+	);
+	assert!(trace.extrinsics[0].iter().all(|call| call.error.is_none()));
+
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::Yes);
+	assert_matches!(api.trace_block(hash).wait(), Err(Error::UnsafeRpcCalled(_)));
+}
+
+#[test]
+fn should_return_storage_diff() {
+	const KEY: &[u8] = b":mock";
+
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
+
+	let mut builder = client.new_block(Default::default()).unwrap();
+	builder.push_storage_change(KEY.to_vec(), Some(vec![1, 2, 3])).unwrap();
+	let block = builder.bake().unwrap();
+	let hash = block.header.hash();
+	client.import(BlockOrigin::Own, block).unwrap();
+
+	let diff = api.storage_diff(hash, None).wait().unwrap();
+	assert!(diff.contains(&StorageDiff {
+		child_storage_key: None,
+		key: StorageKey(KEY.to_vec()),
+		old_value: None,
+		new_value: Some(StorageData(vec![1, 2, 3])),
+	}));
+	assert_eq!(
+		api.storage_diff(hash, Some(StorageKey(KEY.to_vec()))).wait().unwrap(),
+		vec![StorageDiff {
+			child_storage_key: None,
+			key: StorageKey(KEY.to_vec()),
+			old_value: None,
+			new_value: Some(StorageData(vec![1, 2, 3])),
+		}],
+	);
+
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::Yes);
+	assert_matches!(api.storage_diff(hash, None).wait(), Err(Error::UnsafeRpcCalled(_)));
+}
+
 #[test]
 fn should_query_storage() {
 	fn run_tests(client: Arc<TestClient>) {
 		let core = tokio::runtime::Runtime::new().unwrap();
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+		let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
 
 		let add_block = |nonce| {
 			let mut builder = client.new_block(Default::default()).unwrap();
@@ -276,13 +368,14 @@ fn should_return_runtime_version() {
 	let core = tokio::runtime::Runtime::new().unwrap();
 
 	let client = Arc::new(test_client::new());
-	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
 
 	let result = "{\"specName\":\"test\",\"implName\":\"parity-test\",\"authoringVersion\":1,\
 		\"specVersion\":1,\"implVersion\":1,\"apis\":[[\"0xdf6acb689907609b\",2],\
 		[\"0x37e397fc7c91f5e4\",1],[\"0xd2bc9897eed08f15\",1],[\"0x40fe3ad401f8959a\",3],\
 		[\"0xc6e9a76309f39b09\",1],[\"0xdd718d5cc53262d4\",1],[\"0xcbca25e39f142387\",1],\
-		[\"0xf78b278be53f454c\",1],[\"0xab3c0572291feb8b\",1],[\"0xbc9d89904f5b923f\",1]]}";
+		[\"0xf78b278be53f454c\",1],[\"0xab3c0572291feb8b\",1],[\"0xbc9d89904f5b923f\",1]],\
+		\"hostInterfaces\":[]}";
 
 	let runtime_version = api.runtime_version(None.into()).wait().unwrap();
 	let serialized = serde_json::to_string(&runtime_version).unwrap();
@@ -290,6 +383,11 @@ fn should_return_runtime_version() {
 
 	let deserialized: RuntimeVersion = serde_json::from_str(result).unwrap();
 	assert_eq!(deserialized, runtime_version);
+
+	// Versions serialized before `hostInterfaces` was introduced can still be read.
+	let without_host_interfaces = result.replace(",\"hostInterfaces\":[]", "");
+	let deserialized: RuntimeVersion = serde_json::from_str(&without_host_interfaces).unwrap();
+	assert_eq!(deserialized, runtime_version);
 }
 
 #[test]
@@ -299,7 +397,7 @@ fn should_notify_on_runtime_version_initially() {
 
 	{
 		let client = Arc::new(test_client::new());
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+		let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())), DenyUnsafe::No);
 
 		api.subscribe_runtime_version(Default::default(), subscriber);
 
@@ -0,0 +1,167 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This crate provides procedural macros for usage within the context of the Substrate runtime
+//! interface.
+//!
+//! The following macros are provided:
+//!
+//! 1. The [`#[runtime_interface]`](attr.runtime_interface.html) attribute macro for generating the
+//!    runtime interfaces.
+
+extern crate proc_macro;
+
+use syn::{parse_macro_input, ItemTrait};
+
+mod runtime_interface;
+mod utils;
+
+/// Attribute macro for transforming a trait declaration into a runtime interface.
+///
+/// A runtime interface is a fixed interface between a Substrate compatible runtime and the native
+/// node. This interface is callable from a native and a wasm runtime. The macro will generate the
+/// corresponding code for the native implementation and the code for calling from the wasm side
+/// to the native implementation.
+///
+/// The macro expects the runtime interface declaration as trait declaration. Every method needs
+/// to provide its implementation as default body. A method can take `&mut self` to get access
+/// to the `Externalities` or no `self` at all. Methods must not be generic and the return type
+/// must not be a reference.
+///
+/// The trait is replaced by a module with the name of the trait in snake case. The module
+/// contains a function for each method of the trait that can be called from the runtime, the
+/// `extern` declarations of the host functions for the wasm side and a `HostFunctions` type
+/// that implements `wasm_interface::HostFunctions` and needs to be registered in the executor.
+///
+/// The host function for a method `method` of the trait `Trait` is exported as
+/// `ext_trait_method_version_1`.
+///
+/// # Versioning
+///
+/// Once a runtime was compiled against a host function, the host function can not change anymore,
+/// as the runtime is stored on chain and needs to be executable forever. To change a host
+/// function, a new version of it needs to be declared with the `#[version(X)]` attribute. The old
+/// versions stay in the trait. Methods without this attribute have the version `1`.
+///
+/// The runtime always calls the latest version of a method, while the executor provides all
+/// versions. The host function of version `X` is exported as `ext_trait_method_version_X`.
+///
+/// The interface as a whole has the version of its latest method. The generated module contains
+/// its name and version as `INTERFACE_NAME` and `INTERFACE_VERSION`. A runtime lists the interfaces
+/// it uses in the `host_interfaces` of its `RuntimeVersion` with
+/// `sr_version::create_host_interfaces_vec!`, and the executor refuses to run a runtime that needs
+/// a newer version of an interface than it provides, naming the interface and the version.
+///
+/// # Example
+///
+/// ```ignore
+/// #[runtime_interface]
+/// trait Interface {
+///     /// A function that can be called from native/wasm.
+///     ///
+///     /// The implementation given to this function is only compiled on native.
+///     fn call(data: &[u8]) -> Vec<u8> {
+///         // Here you could call some rather complex code that only compiles on native or
+///         // is way faster in native than executing it in wasm.
+///         Vec::new()
+///     }
+///
+///     /// A function can take a `&mut self` to get access to the `Externalities`.
+///     fn set_or_clear(&mut self, key: &[u8], value: Option<Vec<u8>>) {
+///         match value {
+///             Some(value) => self.set_storage(key.to_vec(), value),
+///             None => self.clear_storage(key),
+///         }
+///     }
+///
+///     /// The first version of a function that is kept for old runtimes.
+///     fn sum(data: &[u8]) -> u32 {
+///         data.iter().map(|v| *v as u32).sum()
+///     }
+///
+///     /// The second version of the function that is called by new runtimes.
+///     #[version(2)]
+///     fn sum(data: &[u8]) -> u64 {
+///         data.iter().map(|v| *v as u64).sum()
+///     }
+/// }
+/// ```
+///
+/// The example above expands roughly to:
+///
+/// ```ignore
+/// mod interface {
+///     #[cfg(feature = "std")]
+///     pub fn call(data: &[u8]) -> Vec<u8> {
+///         <&mut dyn Externalities as Interface>::call_version_1(data)
+///     }
+///
+///     #[cfg(not(feature = "std"))]
+///     pub fn call(data: &[u8]) -> Vec<u8> {
+///         let data = <[u8] as IntoFFIValue>::into_ffi_value(data);
+///         let result = unsafe { ext_interface_call_version_1(data.get()) };
+///         <Vec<u8> as FromFFIValue>::from_ffi_value(result)
+///     }
+///
+///     #[cfg(not(feature = "std"))]
+///     extern "C" {
+///         pub fn ext_interface_call_version_1(
+///             data: <[u8] as RIType>::FFIType,
+///         ) -> <Vec<u8> as RIType>::FFIType;
+///     }
+///
+///     // The same for `set_or_clear`, calling the implementation through
+///     // `with_externalities`, and for `sum`, calling `ext_interface_sum_version_2`.
+///
+///     #[cfg(feature = "std")]
+///     pub trait Interface {
+///         // The signatures of all versions of all methods, e.g. `call_version_1`,
+///         // `sum_version_1` and `sum_version_2`.
+///     }
+///
+///     #[cfg(feature = "std")]
+///     impl Interface for &mut dyn Externalities {
+///         // The implementations of the methods.
+///     }
+///
+///     #[cfg(feature = "std")]
+///     pub struct HostFunctions;
+///
+///     #[cfg(feature = "std")]
+///     impl wasm_interface::HostFunctions for HostFunctions {
+///         // Returns `ext_interface_call_version_1`, `ext_interface_set_or_clear_version_1`,
+///         // `ext_interface_sum_version_1` and `ext_interface_sum_version_2`.
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn runtime_interface(
+	attrs: proc_macro::TokenStream,
+	input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	if !attrs.is_empty() {
+		return syn::Error::new(
+			proc_macro2::Span::call_site(),
+			"`runtime_interface` does not expect any arguments",
+		).to_compile_error().into();
+	}
+
+	let trait_def = parse_macro_input!(input as ItemTrait);
+
+	runtime_interface::runtime_interface_impl(trait_def)
+		.unwrap_or_else(|e| e.to_compile_error())
+		.into()
+}
@@ -0,0 +1,163 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generates the bare function interface for a given trait definition.
+//!
+//! The bare functions are the functions that are called by the runtime. For every method of the
+//! trait a function with the same name is generated, that always calls the latest version of the
+//! method. On `std` the function calls the implementation of the trait directly. On `no_std` it
+//! converts the arguments into their ffi representation, calls the host function and converts
+//! the returned value.
+//!
+//! The `extern` declarations of the host functions are generated as well. A runtime only imports
+//! the latest version of every host function.
+
+use crate::utils::{
+	generate_crate_access, create_host_function_ident, get_function_argument_names,
+	get_function_argument_types_without_ref, get_function_argument_types_ref_and_mut,
+	get_method_attributes_without_version, generate_call_to_trait, RuntimeInterface,
+};
+
+use syn::{Ident, ItemTrait, TraitItemMethod, FnArg, Result, ReturnType, spanned::Spanned};
+
+use proc_macro2::TokenStream;
+
+use quote::{quote, quote_spanned};
+
+/// Generate the bare-function interface.
+pub fn generate(trait_def: &ItemTrait, runtime_interface: &RuntimeInterface) -> Result<TokenStream> {
+	let trait_name = &trait_def.ident;
+	let extern_host_functions = generate_extern_host_functions(trait_name, runtime_interface)?;
+
+	runtime_interface.latest_versions().try_fold(extern_host_functions, |mut t, (version, m)| {
+		t.extend(function_std_impl(trait_name, m, version)?);
+		t.extend(function_no_std_impl(trait_name, m, version)?);
+		Ok(t)
+	})
+}
+
+/// Generates the `extern` declarations of the latest version of all host functions.
+fn generate_extern_host_functions(
+	trait_name: &Ident,
+	runtime_interface: &RuntimeInterface,
+) -> Result<TokenStream> {
+	let crate_ = generate_crate_access();
+
+	let declarations = runtime_interface.latest_versions().map(|(version, method)| {
+		let function = create_host_function_ident(&method.sig.ident, version, trait_name);
+		let arg_names = get_function_argument_names(&method.sig);
+		let arg_types = get_function_argument_types_without_ref(&method.sig);
+		let output = match &method.sig.decl.output {
+			ReturnType::Default => quote!(),
+			ReturnType::Type(_, ty) => quote! {
+				-> <#ty as #crate_::RIType>::FFIType
+			},
+		};
+
+		quote_spanned! { method.span() =>
+			/// The host function of the latest version of this method.
+			pub fn #function( #( #arg_names: <#arg_types as #crate_::RIType>::FFIType ),* ) #output;
+		}
+	});
+
+	Ok(
+		quote! {
+			#[cfg(not(feature = "std"))]
+			extern "C" {
+				#( #declarations )*
+			}
+		}
+	)
+}
+
+/// Generates the bare function implementation for `cfg(not(feature = "std"))`.
+fn function_no_std_impl(
+	trait_name: &Ident,
+	method: &TraitItemMethod,
+	version: u32,
+) -> Result<TokenStream> {
+	let crate_ = generate_crate_access();
+	let function_name = &method.sig.ident;
+	let host_function_name = create_host_function_ident(function_name, version, trait_name);
+	let args = method.sig.decl.inputs.iter().filter(|a| match a {
+		FnArg::Captured(_) => true,
+		_ => false,
+	});
+	let arg_names = get_function_argument_names(&method.sig);
+	let arg_names2 = get_function_argument_names(&method.sig);
+	let arg_types = get_function_argument_types_without_ref(&method.sig);
+	// References are passed as they are, owned values need to be borrowed.
+	let ref_and_mut = get_function_argument_types_ref_and_mut(&method.sig)
+		.map(|ref_and_mut| if ref_and_mut.is_some() { quote!() } else { quote!( & ) });
+	let return_value = &method.sig.decl.output;
+	let attrs = get_method_attributes_without_version(method);
+
+	let call_host_function = quote! {
+		unsafe { #host_function_name( #( #arg_names2.get() ),* ) }
+	};
+
+	let call_and_convert_return_value = match return_value {
+		ReturnType::Default => call_host_function,
+		ReturnType::Type(_, ty) => quote! {
+			let result = #call_host_function;
+			<#ty as #crate_::wasm::FromFFIValue>::from_ffi_value(result)
+		},
+	};
+
+	Ok(
+		quote! {
+			#[cfg(not(feature = "std"))]
+			#( #attrs )*
+			pub fn #function_name( #( #args, )* ) #return_value {
+				// Generate all wrapped ffi values.
+				#(
+					let #arg_names = <#arg_types as #crate_::wasm::IntoFFIValue>::into_ffi_value(
+						#ref_and_mut #arg_names,
+					);
+				)*
+
+				#call_and_convert_return_value
+			}
+		}
+	)
+}
+
+/// Generates the bare function implementation for `cfg(feature = "std")`.
+fn function_std_impl(
+	trait_name: &Ident,
+	method: &TraitItemMethod,
+	version: u32,
+) -> Result<TokenStream> {
+	let method_name = &method.sig.ident;
+	let args = method.sig.decl.inputs.iter().filter(|a| match a {
+		FnArg::Captured(_) => true,
+		_ => false,
+	});
+	let arg_names = get_function_argument_names(&method.sig).map(|n| quote!( #n ));
+	let return_value = &method.sig.decl.output;
+	let attrs = get_method_attributes_without_version(method);
+	let call_to_trait = generate_call_to_trait(trait_name, method, version, arg_names);
+
+	Ok(
+		quote! {
+			#[cfg(feature = "std")]
+			#( #attrs )*
+			pub fn #method_name( #( #args, )* ) #return_value {
+				#call_to_trait
+			}
+		}
+	)
+}
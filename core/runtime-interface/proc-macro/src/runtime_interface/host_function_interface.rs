@@ -0,0 +1,252 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generates the host functions for a given trait definition.
+//!
+//! For every version of every method of the trait a host function is generated. The host function
+//! reads the arguments from the wasm side, converts them into their host representation, calls
+//! the implementation of the version and writes the return value back. All host functions are
+//! returned by the `HostFunctions` type that implements `wasm_interface::HostFunctions`. This way
+//! the executor provides all versions side by side and runtimes that were compiled against an
+//! older version of a function can still be executed.
+
+use crate::utils::{
+	generate_crate_access, create_host_function_ident, get_function_argument_names,
+	get_function_argument_types_without_ref, get_function_argument_types_ref_and_mut,
+	generate_call_to_trait, RuntimeInterface,
+};
+
+use syn::{ItemTrait, TraitItemMethod, Result, ReturnType, Ident};
+
+use proc_macro2::{TokenStream, Span};
+
+use quote::quote;
+
+use inflector::Inflector;
+
+/// Generate the struct that implements `HostFunctions` for all versions of all methods of the given
+/// trait.
+pub fn generate(trait_def: &ItemTrait, runtime_interface: &RuntimeInterface) -> Result<TokenStream> {
+	let trait_name = &trait_def.ident;
+	let host_functions = runtime_interface.all_versions()
+		.map(|(version, m)| generate_host_function_implementation(trait_name, m, version))
+		.collect::<Result<Vec<_>>>()?;
+	let crate_ = generate_crate_access();
+
+	Ok(
+		quote! {
+			/// Provides implementations for the host functions of this runtime interface.
+			///
+			/// It needs to be registered in the executor to make the host functions available
+			/// for the wasm runtime.
+			#[cfg(feature = "std")]
+			pub struct HostFunctions;
+
+			#[cfg(feature = "std")]
+			impl #crate_::wasm_interface::HostFunctions for HostFunctions {
+				fn host_functions() -> Vec<&'static dyn #crate_::wasm_interface::Function> {
+					vec![ #( #host_functions ),* ]
+				}
+
+				fn interfaces() -> Vec<(&'static str, u32)> {
+					vec![ (INTERFACE_NAME, INTERFACE_VERSION) ]
+				}
+			}
+		}
+	)
+}
+
+/// Generates the host function struct that implements `wasm_interface::Function` and returns a
+/// static reference to this struct.
+fn generate_host_function_implementation(
+	trait_name: &Ident,
+	method: &TraitItemMethod,
+	version: u32,
+) -> Result<TokenStream> {
+	let name = create_host_function_ident(&method.sig.ident, version, trait_name).to_string();
+	let struct_name = Ident::new(&name.to_pascal_case(), Span::call_site());
+	let crate_ = generate_crate_access();
+	let signature = generate_wasm_interface_signature_for_host_function(method)?;
+	let wasm_to_ffi_values = generate_wasm_to_ffi_values(method)?;
+	let ffi_to_host_values = generate_ffi_to_host_value(method)?;
+	let host_function_call = generate_host_function_call(trait_name, method, version)?;
+	let convert_return_value = generate_return_value_into_wasm_value(method);
+
+	Ok(
+		quote! {
+			{
+				struct #struct_name;
+
+				impl #crate_::wasm_interface::Function for #struct_name {
+					fn name(&self) -> &str {
+						#name
+					}
+
+					fn signature(&self) -> #crate_::wasm_interface::Signature {
+						#signature
+					}
+
+					fn execute(
+						&self,
+						__function_context__: &mut dyn #crate_::wasm_interface::FunctionContext,
+						args: &mut dyn Iterator<Item = #crate_::wasm_interface::Value>,
+					) -> std::result::Result<Option<#crate_::wasm_interface::Value>, String> {
+						#( #wasm_to_ffi_values )*
+						#( #ffi_to_host_values )*
+						#host_function_call
+						#convert_return_value
+					}
+				}
+
+				&#struct_name as &dyn #crate_::wasm_interface::Function
+			}
+		}
+	)
+}
+
+/// Generate the `wasm_interface::Signature` for the given host function `sig`.
+fn generate_wasm_interface_signature_for_host_function(
+	method: &TraitItemMethod,
+) -> Result<TokenStream> {
+	let crate_ = generate_crate_access();
+	let return_value = match &method.sig.decl.output {
+		ReturnType::Type(_, ty) =>
+			quote! {
+				Some(
+					<
+						<#ty as #crate_::RIType>::FFIType as #crate_::wasm_interface::IntoValue
+					>::VALUE_TYPE
+				)
+			},
+		ReturnType::Default => quote!( None ),
+	};
+	let arg_types = get_function_argument_types_without_ref(&method.sig)
+		.map(|ty| quote! {
+			<<#ty as #crate_::RIType>::FFIType as #crate_::wasm_interface::IntoValue>::VALUE_TYPE
+		});
+
+	Ok(
+		quote! {
+			#crate_::wasm_interface::Signature {
+				args: std::borrow::Cow::Borrowed(&[ #( #arg_types ),* ]),
+				return_value: #return_value,
+			}
+		}
+	)
+}
+
+/// Generate the code that converts the wasm values given to `HostFunctions::execute` into the
+/// FFI values.
+fn generate_wasm_to_ffi_values(method: &TraitItemMethod) -> Result<Vec<TokenStream>> {
+	let crate_ = generate_crate_access();
+	let function_name = &method.sig.ident;
+	let error_message = format!(
+		"Number of arguments given to `{}` does not match the expected number of arguments!",
+		function_name,
+	);
+
+	get_function_argument_names(&method.sig)
+		.zip(get_function_argument_types_without_ref(&method.sig))
+		.map(|(name, ty)| {
+			let try_from_error = format!(
+				"Could not instantiate `{}` from wasm value while executing `{}`!",
+				name,
+				function_name,
+			);
+			let var_name = generate_ffi_value_var_name(name);
+
+			Ok(quote! {
+				let val = args.next().ok_or_else(|| #error_message)?;
+				let #var_name = <
+					<#ty as #crate_::RIType>::FFIType as #crate_::wasm_interface::TryFromValue
+				>::try_from_value(val).ok_or_else(|| #try_from_error)?;
+			})
+		})
+		.collect()
+}
+
+/// Generate the code to convert the ffi values on the host to the host values using `FromFFIValue`.
+fn generate_ffi_to_host_value(method: &TraitItemMethod) -> Result<Vec<TokenStream>> {
+	let crate_ = generate_crate_access();
+
+	get_function_argument_names(&method.sig)
+		.zip(get_function_argument_types_without_ref(&method.sig))
+		.map(|(name, ty)| {
+			let ffi_value_var_name = generate_ffi_value_var_name(name);
+
+			Ok(quote! {
+				let #name = <#ty as #crate_::host::FromFFIValue>::from_ffi_value(
+					__function_context__,
+					#ffi_value_var_name,
+				)?;
+			})
+		})
+		.collect()
+}
+
+/// Generate the code to call the implementation of the given version and to store the result, if
+/// there is any.
+fn generate_host_function_call(
+	trait_name: &Ident,
+	method: &TraitItemMethod,
+	version: u32,
+) -> Result<TokenStream> {
+	let result_var_name = generate_host_function_result_var_name(&method.sig.ident);
+	let ref_and_mut = get_function_argument_types_ref_and_mut(&method.sig);
+	let names = get_function_argument_names(&method.sig);
+
+	let var_access = names.zip(ref_and_mut).map(|(n, ref_and_mut)| {
+		quote!( #ref_and_mut #n )
+	});
+
+	let call = generate_call_to_trait(trait_name, method, version, var_access);
+
+	Ok(
+		match &method.sig.decl.output {
+			ReturnType::Default => quote!( #call; ),
+			ReturnType::Type(_, _) => quote!( let #result_var_name = #call; ),
+		}
+	)
+}
+
+/// Generate the variable name that stores the result of the host function.
+fn generate_host_function_result_var_name(name: &Ident) -> Ident {
+	Ident::new(&format!("{}_result", name), Span::call_site())
+}
+
+/// Generate the variable name that stores the FFI value.
+fn generate_ffi_value_var_name(name: &Ident) -> Ident {
+	Ident::new(&format!("{}_ffi_value", name), Span::call_site())
+}
+
+/// Generate the code that converts the return value into the appropriate wasm value.
+fn generate_return_value_into_wasm_value(method: &TraitItemMethod) -> TokenStream {
+	let crate_ = generate_crate_access();
+
+	match &method.sig.decl.output {
+		ReturnType::Default => quote!( Ok(None) ),
+		ReturnType::Type(_, ty) => {
+			let result_var_name = generate_host_function_result_var_name(&method.sig.ident);
+
+			quote! {
+				<#ty as #crate_::host::IntoFFIValue>::into_ffi_value(
+					#result_var_name,
+					__function_context__,
+				).map(#crate_::wasm_interface::IntoValue::into_value).map(Some)
+			}
+		}
+	}
+}
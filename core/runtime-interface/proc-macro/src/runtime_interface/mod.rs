@@ -0,0 +1,72 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generates the runtime interface from a trait declaration.
+
+use crate::utils::{
+	generate_runtime_interface_include, create_module_name, check_trait_methods,
+	get_runtime_interface,
+};
+
+use syn::{ItemTrait, Result};
+
+use proc_macro2::TokenStream;
+
+use quote::quote;
+
+mod bare_function_interface;
+mod host_function_interface;
+mod trait_decl_impl;
+
+/// Implementation of the `runtime_interface` attribute.
+///
+/// It expects the trait definition the attribute was put above and returns the generated module.
+pub fn runtime_interface_impl(trait_def: ItemTrait) -> Result<TokenStream> {
+	check_trait_methods(&trait_def)?;
+	let runtime_interface = get_runtime_interface(&trait_def)?;
+
+	let bare_functions = bare_function_interface::generate(&trait_def, &runtime_interface)?;
+	let crate_include = generate_runtime_interface_include();
+	let mod_name = create_module_name(&trait_def.ident);
+	let trait_decl_impl = trait_decl_impl::process(&trait_def, &runtime_interface)?;
+	let host_functions = host_function_interface::generate(&trait_def, &runtime_interface)?;
+	let vis = &trait_def.vis;
+	let attrs = &trait_def.attrs;
+	let interface_name = mod_name.to_string();
+	let interface_version = runtime_interface.version();
+
+	let res = quote! {
+		#( #attrs )*
+		#vis mod #mod_name {
+			use super::*;
+			#crate_include
+
+			/// The name under which runtimes declare this interface in their `RuntimeVersion`.
+			pub const INTERFACE_NAME: &str = #interface_name;
+
+			/// The version of this interface, which is the latest version of any of its methods.
+			pub const INTERFACE_VERSION: u32 = #interface_version;
+
+			#bare_functions
+
+			#trait_decl_impl
+
+			#host_functions
+		}
+	};
+
+	Ok(res)
+}
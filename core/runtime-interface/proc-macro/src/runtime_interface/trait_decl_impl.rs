@@ -0,0 +1,105 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generates the trait declaration and the implementation of the trait for
+//! `&mut dyn Externalities`.
+//!
+//! The trait is only available on `std`. The trait declaration only contains the method
+//! signatures, the actual implementation of the runtime interface is moved into the
+//! implementation for `&mut dyn Externalities`. This way the implementation has access to the
+//! `Externalities` through `self`.
+//!
+//! Every version of a method is declared as its own method, named
+//! `{method_name}_version_{version}`.
+
+use crate::utils::{
+	generate_crate_access, create_function_ident_with_version, is_version_attribute,
+	RuntimeInterface,
+};
+
+use syn::{ItemTrait, TraitItemMethod, Result, parse_quote};
+
+use proc_macro2::TokenStream;
+
+use quote::quote;
+
+/// Process the given trait definition, by outputting the trait declaration and the
+/// implementation for `&mut dyn Externalities`.
+pub fn process(trait_def: &ItemTrait, runtime_interface: &RuntimeInterface) -> Result<TokenStream> {
+	let trait_decl = declare_trait(trait_def, runtime_interface);
+	let impl_trait = impl_trait_for_externalities(trait_def, runtime_interface);
+
+	Ok(quote! { #trait_decl #impl_trait })
+}
+
+/// Returns a copy of the given method that is renamed to include the version and that has the
+/// `#[version(X)]` attribute removed.
+fn versioned_method(method: &TraitItemMethod, version: u32) -> TraitItemMethod {
+	let mut method = method.clone();
+	method.sig.ident = create_function_ident_with_version(&method.sig.ident, version);
+	method.attrs.retain(|a| !is_version_attribute(a));
+	method
+}
+
+/// Declare the trait with all its methods, but without their implementations.
+///
+/// The declaration always is `pub`, because it is only accessible through the module that
+/// is generated by the macro.
+fn declare_trait(trait_def: &ItemTrait, runtime_interface: &RuntimeInterface) -> TokenStream {
+	let trait_name = &trait_def.ident;
+	let methods = runtime_interface.all_versions().map(|(version, m)| {
+		let mut method = versioned_method(m, version);
+		method.default = None;
+		method.semi_token = Some(parse_quote!( ; ));
+		method
+	});
+
+	quote! {
+		/// The implementation of every version of the methods of this runtime interface.
+		#[cfg(feature = "std")]
+		pub trait #trait_name {
+			#( #methods )*
+		}
+	}
+}
+
+/// Implements the given trait definition for `&mut dyn Externalities`.
+fn impl_trait_for_externalities(
+	trait_def: &ItemTrait,
+	runtime_interface: &RuntimeInterface,
+) -> TokenStream {
+	let trait_name = &trait_def.ident;
+	let crate_ = generate_crate_access();
+	let methods = runtime_interface.all_versions()
+		.map(|(version, m)| method_implementation(&versioned_method(m, version)));
+
+	quote! {
+		#[cfg(feature = "std")]
+		impl #trait_name for &mut dyn #crate_::Externalities {
+			#( #methods )*
+		}
+	}
+}
+
+/// Generates the implementation of the given method, using its default implementation.
+fn method_implementation(method: &TraitItemMethod) -> TokenStream {
+	let sig = &method.sig;
+	let block = &method.default;
+
+	quote! {
+		#sig #block
+	}
+}
@@ -0,0 +1,380 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Util function used by this crate.
+
+use proc_macro2::{TokenStream, Span};
+
+use syn::{
+	Ident, Error, Result, MethodSig, FnArg, ArgCaptured, Pat, Type, TypeReference, TraitItem,
+	TraitItemMethod, ItemTrait, ReturnType, Attribute, Meta, NestedMeta, Lit, spanned::Spanned,
+};
+
+use proc_macro_crate::crate_name;
+
+use std::{env, collections::BTreeMap};
+
+use quote::quote;
+
+use inflector::Inflector;
+
+/// The name of the crate the generated code depends on.
+const RUNTIME_INTERFACE_CRATE: &str = "substrate-runtime-interface";
+
+/// Generates the include for the runtime-interface crate.
+pub fn generate_runtime_interface_include() -> TokenStream {
+	if env::var("CARGO_PKG_NAME").unwrap() == RUNTIME_INTERFACE_CRATE {
+		TokenStream::new()
+	} else {
+		match crate_name(RUNTIME_INTERFACE_CRATE) {
+			Ok(crate_name) => {
+				let crate_name = Ident::new(&crate_name, Span::call_site());
+				quote!(
+					#[doc(hidden)]
+					extern crate #crate_name as proc_macro_runtime_interface;
+				)
+			},
+			Err(e) => {
+				let err = Error::new(Span::call_site(), &e).to_compile_error();
+				quote!( #err )
+			}
+		}
+	}
+}
+
+/// Generates the access to the `substrate-runtime-interface` crate.
+pub fn generate_crate_access() -> TokenStream {
+	if env::var("CARGO_PKG_NAME").unwrap() == RUNTIME_INTERFACE_CRATE {
+		quote!( substrate_runtime_interface )
+	} else {
+		quote!( proc_macro_runtime_interface )
+	}
+}
+
+/// Create the name of the module that replaces the given trait.
+pub fn create_module_name(trait_name: &Ident) -> Ident {
+	Ident::new(&trait_name.to_string().to_snake_case(), trait_name.span())
+}
+
+/// The name of the attribute that sets the version of a runtime interface method.
+const VERSION_ATTRIBUTE: &str = "version";
+
+/// Create the exchangeable host function identifier for the given function name and version.
+///
+/// The identifier is `ext_{trait_name}_{method_name}_version_{version}`, with the trait name in
+/// snake case.
+pub fn create_host_function_ident(method_name: &Ident, version: u32, trait_name: &Ident) -> Ident {
+	Ident::new(
+		&format!(
+			"ext_{}_{}_version_{}",
+			trait_name.to_string().to_snake_case(),
+			method_name,
+			version,
+		),
+		Span::call_site(),
+	)
+}
+
+/// Create the identifier of the trait method that implements the given version of a function.
+pub fn create_function_ident_with_version(name: &Ident, version: u32) -> Ident {
+	Ident::new(&format!("{}_version_{}", name, version), Span::call_site())
+}
+
+/// A function of a runtime interface with all its versions.
+pub struct RuntimeInterfaceFunction<'a> {
+	/// The versions of the function, ordered by their version number.
+	versions: BTreeMap<u32, &'a TraitItemMethod>,
+}
+
+impl<'a> RuntimeInterfaceFunction<'a> {
+	/// Returns the latest version of this function.
+	pub fn latest_version(&self) -> (u32, &'a TraitItemMethod) {
+		let (version, method) = self.versions.iter().next_back()
+			.expect("A function is only created with at least one version; qed");
+		(*version, *method)
+	}
+}
+
+/// All functions of a runtime interface, in the order of their first declaration.
+pub struct RuntimeInterface<'a> {
+	functions: Vec<(&'a Ident, RuntimeInterfaceFunction<'a>)>,
+}
+
+impl<'a> RuntimeInterface<'a> {
+	/// Returns the latest version of every function.
+	pub fn latest_versions(&self) -> impl Iterator<Item = (u32, &'a TraitItemMethod)> + '_ {
+		self.functions.iter().map(|(_, f)| f.latest_version())
+	}
+
+	/// Returns all versions of every function.
+	pub fn all_versions(&self) -> impl Iterator<Item = (u32, &'a TraitItemMethod)> + '_ {
+		self.functions.iter()
+			.flat_map(|(_, f)| f.versions.iter().map(|(v, m)| (*v, *m)))
+	}
+
+	/// Returns the version of the interface, which is the latest version of any of its functions.
+	pub fn version(&self) -> u32 {
+		self.latest_versions().map(|(version, _)| version).max().unwrap_or(1)
+	}
+}
+
+/// Collects the functions of the given trait definition with all their versions.
+///
+/// The trait methods need to be checked with [`check_trait_methods`] before.
+pub fn get_runtime_interface<'a>(trait_def: &'a ItemTrait) -> Result<RuntimeInterface<'a>> {
+	let mut functions: Vec<(&'a Ident, RuntimeInterfaceFunction<'a>)> = Vec::new();
+
+	for method in get_trait_methods(trait_def) {
+		let version = get_method_version(method)?;
+		let name = &method.sig.ident;
+
+		let index = match functions.iter().position(|(n, _)| *n == name) {
+			Some(index) => index,
+			None => {
+				functions.push((name, RuntimeInterfaceFunction { versions: BTreeMap::new() }));
+				functions.len() - 1
+			}
+		};
+
+		if functions[index].1.versions.insert(version, method).is_some() {
+			return Err(
+				Error::new(
+					name.span(),
+					format!("Version {} of `{}` is declared multiple times.", version, name),
+				)
+			)
+		}
+	}
+
+	Ok(RuntimeInterface { functions })
+}
+
+/// Returns the version of the given method, as given by the `#[version(X)]` attribute.
+///
+/// Methods without this attribute have the version `1`.
+fn get_method_version(method: &TraitItemMethod) -> Result<u32> {
+	let mut versions = method.attrs.iter().filter(|a| is_version_attribute(a));
+
+	let version = match versions.next() {
+		Some(attr) => parse_version_attribute(attr)?,
+		None => return Ok(1),
+	};
+
+	if let Some(attr) = versions.next() {
+		return Err(Error::new(attr.span(), "Only one `#[version(X)]` attribute is allowed."))
+	}
+
+	Ok(version)
+}
+
+/// Returns if the given attribute is the `#[version(X)]` attribute.
+pub fn is_version_attribute(attr: &Attribute) -> bool {
+	attr.path.is_ident(VERSION_ATTRIBUTE)
+}
+
+/// Parses the version from the given `#[version(X)]` attribute.
+fn parse_version_attribute(attr: &Attribute) -> Result<u32> {
+	let error = || Error::new(
+		attr.span(),
+		"Expected a version attribute of the form `#[version(X)]`, with `X` being at least `1`.",
+	);
+
+	let nested = match attr.parse_meta()? {
+		Meta::List(list) => list.nested,
+		_ => return Err(error()),
+	};
+
+	if nested.len() != 1 {
+		return Err(error())
+	}
+
+	match nested.first().map(|n| n.into_value()) {
+		Some(NestedMeta::Literal(Lit::Int(version)))
+			if version.value() >= 1 && version.value() <= u64::from(u32::max_value()) =>
+				Ok(version.value() as u32),
+		_ => Err(error()),
+	}
+}
+
+/// Returns the attributes of the given method without the `#[version(X)]` attribute.
+pub fn get_method_attributes_without_version<'a>(
+	method: &'a TraitItemMethod,
+) -> impl Iterator<Item = &'a Attribute> {
+	method.attrs.iter().filter(|a| !is_version_attribute(a))
+}
+
+/// Generates the call to the trait implementation of the given method, that implements the given
+/// version.
+///
+/// `arg_names` are the expressions that are passed as arguments. If the method takes `&mut self`, the
+/// call is made with the `Externalities` that are set while executing the call.
+pub fn generate_call_to_trait(
+	trait_name: &Ident,
+	method: &TraitItemMethod,
+	version: u32,
+	arg_names: impl Iterator<Item = TokenStream>,
+) -> TokenStream {
+	let crate_ = generate_crate_access();
+	let method_name = create_function_ident_with_version(&method.sig.ident, version);
+	let expect_msg = format!(
+		"`{}` called outside of an Externalities-provided environment.",
+		method.sig.ident,
+	);
+
+	if takes_mut_self(&method.sig) {
+		quote! {
+			#crate_::with_externalities(
+				|mut ext| #trait_name::#method_name(&mut ext, #( #arg_names, )*)
+			).expect(#expect_msg)
+		}
+	} else {
+		quote! {
+			<&mut dyn #crate_::Externalities as #trait_name>::#method_name(#( #arg_names, )*)
+		}
+	}
+}
+
+/// Returns the function arguments of the given `MethodSig`, minus any `self` arguments.
+///
+/// Every returned argument is guaranteed to be an identifier pattern, this is checked by
+/// [`check_trait_method`].
+pub fn get_function_arguments<'a>(sig: &'a MethodSig) -> impl Iterator<Item = &'a ArgCaptured> {
+	sig.decl.inputs.iter().filter_map(|a| match a {
+		FnArg::Captured(arg) => Some(arg),
+		_ => None,
+	})
+}
+
+/// Returns the function argument names of the given `MethodSig`, minus any `self`.
+pub fn get_function_argument_names<'a>(sig: &'a MethodSig) -> impl Iterator<Item = &'a Ident> {
+	get_function_arguments(sig).filter_map(|a| match &a.pat {
+		Pat::Ident(pat) => Some(&pat.ident),
+		_ => None,
+	})
+}
+
+/// Returns the function argument types of the given `MethodSig`, minus any `Self` type.
+///
+/// If a type is a reference, the reference is removed and only the referenced type is returned.
+/// This is required to look up the `RIType` implementation, which is implemented for the
+/// referenced type.
+pub fn get_function_argument_types_without_ref<'a>(
+	sig: &'a MethodSig,
+) -> impl Iterator<Item = &'a Type> {
+	get_function_arguments(sig).map(|a| match &a.ty {
+		Type::Reference(TypeReference { elem, .. }) => &**elem,
+		ty => ty,
+	})
+}
+
+/// Returns if the function argument types of the given `MethodSig` are references.
+///
+/// For every argument a token is returned that needs to be prepended to the argument name to
+/// pass it in the same way to another function. This is either `&` or nothing.
+pub fn get_function_argument_types_ref_and_mut<'a>(
+	sig: &'a MethodSig,
+) -> impl Iterator<Item = Option<TokenStream>> + 'a {
+	get_function_arguments(sig).map(|a| match &a.ty {
+		Type::Reference(_) => Some(quote!( & )),
+		_ => None,
+	})
+}
+
+/// Returns if the given `MethodSig` takes `&mut self`.
+pub fn takes_mut_self(sig: &MethodSig) -> bool {
+	sig.decl.inputs.iter().any(|a| match a {
+		FnArg::SelfRef(_) => true,
+		_ => false,
+	})
+}
+
+/// Returns an iterator over all trait methods of the given trait definition.
+///
+/// The trait methods need to be checked with [`check_trait_methods`] before.
+pub fn get_trait_methods<'a>(trait_def: &'a ItemTrait) -> impl Iterator<Item = &'a TraitItemMethod> {
+	trait_def.items.iter().filter_map(|i| match i {
+		TraitItem::Method(ref method) => Some(method),
+		_ => None,
+	})
+}
+
+/// Checks that the given trait only contains methods that are supported by the runtime
+/// interface.
+pub fn check_trait_methods(trait_def: &ItemTrait) -> Result<()> {
+	if !trait_def.generics.params.is_empty() {
+		return Err(
+			Error::new(trait_def.generics.span(), "Generic traits are not supported.")
+		)
+	}
+
+	if !trait_def.supertraits.is_empty() {
+		return Err(
+			Error::new(trait_def.supertraits.span(), "Super traits are not supported.")
+		)
+	}
+
+	trait_def.items.iter().try_for_each(|item| match item {
+		TraitItem::Method(method) => check_trait_method(method),
+		item => Err(
+			Error::new(item.span(), "Only methods are supported in a runtime interface.")
+		),
+	})
+}
+
+/// Checks a single trait method.
+fn check_trait_method(method: &TraitItemMethod) -> Result<()> {
+	if method.default.is_none() {
+		return Err(Error::new(method.sig.ident.span(), "Methods need to have an implementation."))
+	}
+
+	if !method.sig.decl.generics.params.is_empty() {
+		return Err(
+			Error::new(method.sig.decl.generics.span(), "Generic methods are not supported.")
+		)
+	}
+
+	if let ReturnType::Type(_, ty) = &method.sig.decl.output {
+		if let Type::Reference(_) = **ty {
+			return Err(Error::new(ty.span(), "References are not supported as return type."))
+		}
+	}
+
+	method.sig.decl.inputs.iter().try_for_each(|input| match input {
+		FnArg::SelfRef(arg) if arg.mutability.is_some() => Ok(()),
+		FnArg::SelfRef(arg) => Err(
+			Error::new(arg.span(), "Only `&mut self` is supported, use `&mut self` instead.")
+		),
+		FnArg::SelfValue(arg) => Err(
+			Error::new(arg.span(), "Taking `self` by value is not supported.")
+		),
+		FnArg::Captured(arg) => {
+			match &arg.pat {
+				Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => {},
+				pat => return Err(
+					Error::new(pat.span(), "Only identifiers are supported as argument pattern.")
+				),
+			}
+
+			match &arg.ty {
+				Type::Reference(TypeReference { mutability: Some(_), .. }) => Err(
+					Error::new(arg.ty.span(), "Mutable references are not supported.")
+				),
+				_ => Ok(()),
+			}
+		},
+		arg => Err(Error::new(arg.span(), "Unsupported argument.")),
+	})
+}
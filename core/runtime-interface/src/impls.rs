@@ -0,0 +1,294 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Provides implementations for the runtime interface traits.
+
+use crate::{RIType, pack_ptr_and_len, unpack_ptr_and_len, pass_by::PassByCodec};
+
+#[cfg(feature = "std")]
+use crate::host::*;
+#[cfg(not(feature = "std"))]
+use crate::wasm::*;
+
+#[cfg(feature = "std")]
+use wasm_interface::{FunctionContext, Pointer, Result};
+
+use codec::{Encode, Decode};
+
+use rstd::vec::Vec;
+
+/// Implement the traits for the given primitive traits.
+macro_rules! impl_traits_for_primitives {
+	(
+		$(
+			$rty:ty, $fty:ty,
+		)*
+	) => {
+		$(
+			/// The type is passed directly.
+			impl RIType for $rty {
+				type FFIType = $fty;
+			}
+
+			#[cfg(not(feature = "std"))]
+			impl IntoFFIValue for $rty {
+				type Owned = ();
+
+				fn into_ffi_value(&self) -> WrappedFFIValue<$fty> {
+					(*self as $fty).into()
+				}
+			}
+
+			#[cfg(not(feature = "std"))]
+			impl FromFFIValue for $rty {
+				fn from_ffi_value(arg: $fty) -> $rty {
+					arg as $rty
+				}
+			}
+
+			#[cfg(feature = "std")]
+			impl FromFFIValue for $rty {
+				type SelfInstance = $rty;
+
+				fn from_ffi_value(_: &mut dyn FunctionContext, arg: $fty) -> Result<$rty> {
+					Ok(arg as $rty)
+				}
+			}
+
+			#[cfg(feature = "std")]
+			impl IntoFFIValue for $rty {
+				fn into_ffi_value(self, _: &mut dyn FunctionContext) -> Result<$fty> {
+					Ok(self as $fty)
+				}
+			}
+		)*
+	}
+}
+
+impl_traits_for_primitives! {
+	u8, u32,
+	u16, u32,
+	u32, u32,
+	u64, u64,
+	i8, i32,
+	i16, i32,
+	i32, i32,
+	i64, i64,
+}
+
+/// `bool` is passed as `u32`.
+///
+/// - `1`: true
+/// - `0`: false
+impl RIType for bool {
+	type FFIType = u32;
+}
+
+#[cfg(not(feature = "std"))]
+impl IntoFFIValue for bool {
+	type Owned = ();
+
+	fn into_ffi_value(&self) -> WrappedFFIValue<u32> {
+		(if *self { 1 } else { 0 }).into()
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl FromFFIValue for bool {
+	fn from_ffi_value(arg: u32) -> bool {
+		arg == 1
+	}
+}
+
+#[cfg(feature = "std")]
+impl FromFFIValue for bool {
+	type SelfInstance = bool;
+
+	fn from_ffi_value(_: &mut dyn FunctionContext, arg: u32) -> Result<bool> {
+		Ok(arg == 1)
+	}
+}
+
+#[cfg(feature = "std")]
+impl IntoFFIValue for bool {
+	fn into_ffi_value(self, _: &mut dyn FunctionContext) -> Result<u32> {
+		Ok(if self { 1 } else { 0 })
+	}
+}
+
+/// Allocate memory in the wasm instance, copy `data` into it and return the packed pointer and
+/// length.
+#[cfg(feature = "std")]
+fn write_into_memory(context: &mut dyn FunctionContext, data: &[u8]) -> Result<u64> {
+	let ptr = context.allocate_memory(data.len() as u32)?;
+	context.write_memory(ptr, data)?;
+	Ok(pack_ptr_and_len(ptr.into(), data.len() as u32))
+}
+
+/// Read the data pointed to by the packed pointer and length from the wasm memory.
+#[cfg(feature = "std")]
+fn read_from_memory(context: &mut dyn FunctionContext, arg: u64) -> Result<Vec<u8>> {
+	let (ptr, len) = unpack_ptr_and_len(arg);
+	context.read_memory(Pointer::new(ptr), len)
+}
+
+/// The type is passed as `u64`.
+///
+/// The `u64` value is build by `length 32bit << 32 | pointer 32bit`
+impl RIType for Vec<u8> {
+	type FFIType = u64;
+}
+
+#[cfg(feature = "std")]
+impl IntoFFIValue for Vec<u8> {
+	fn into_ffi_value(self, context: &mut dyn FunctionContext) -> Result<u64> {
+		write_into_memory(context, &self)
+	}
+}
+
+#[cfg(feature = "std")]
+impl FromFFIValue for Vec<u8> {
+	type SelfInstance = Vec<u8>;
+
+	fn from_ffi_value(context: &mut dyn FunctionContext, arg: u64) -> Result<Vec<u8>> {
+		read_from_memory(context, arg)
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl IntoFFIValue for Vec<u8> {
+	type Owned = ();
+
+	fn into_ffi_value(&self) -> WrappedFFIValue<u64> {
+		pack_ptr_and_len(self.as_ptr() as u32, self.len() as u32).into()
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl FromFFIValue for Vec<u8> {
+	fn from_ffi_value(arg: u64) -> Vec<u8> {
+		let (ptr, len) = unpack_ptr_and_len(arg);
+		let len = len as usize;
+
+		if len == 0 {
+			Vec::new()
+		} else {
+			// The memory was allocated by the host using the allocator of the runtime, so it
+			// is safe to take ownership of it.
+			unsafe { Vec::from_raw_parts(ptr as *mut u8, len, len) }
+		}
+	}
+}
+
+/// The type is passed as `u64`.
+///
+/// The `u64` value is build by `length 32bit << 32 | pointer 32bit`
+///
+/// The type is only usable behind a reference, as `&[u8]`.
+impl RIType for [u8] {
+	type FFIType = u64;
+}
+
+#[cfg(feature = "std")]
+impl FromFFIValue for [u8] {
+	type SelfInstance = Vec<u8>;
+
+	fn from_ffi_value(context: &mut dyn FunctionContext, arg: u64) -> Result<Vec<u8>> {
+		read_from_memory(context, arg)
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl IntoFFIValue for [u8] {
+	type Owned = ();
+
+	fn into_ffi_value(&self) -> WrappedFFIValue<u64> {
+		pack_ptr_and_len(self.as_ptr() as u32, self.len() as u32).into()
+	}
+}
+
+/// The type is passed as `u64`.
+///
+/// The `u64` value is build by `length 32bit << 32 | pointer 32bit`
+///
+/// The type is only usable behind a reference, as `&str`.
+impl RIType for str {
+	type FFIType = u64;
+}
+
+#[cfg(feature = "std")]
+impl FromFFIValue for str {
+	type SelfInstance = String;
+
+	fn from_ffi_value(context: &mut dyn FunctionContext, arg: u64) -> Result<String> {
+		let vec = read_from_memory(context, arg)?;
+		String::from_utf8(vec).map_err(|_| "Invalid utf8 data provided".into())
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl IntoFFIValue for str {
+	type Owned = ();
+
+	fn into_ffi_value(&self) -> WrappedFFIValue<u64> {
+		pack_ptr_and_len(self.as_ptr() as u32, self.len() as u32).into()
+	}
+}
+
+/// The type is passed SCALE encoded as `u64`.
+///
+/// The `u64` value is build by `length 32bit << 32 | pointer 32bit`
+impl<T: PassByCodec> RIType for T {
+	type FFIType = u64;
+}
+
+#[cfg(feature = "std")]
+impl<T: PassByCodec> IntoFFIValue for T {
+	fn into_ffi_value(self, context: &mut dyn FunctionContext) -> Result<u64> {
+		let data = self.encode();
+		write_into_memory(context, &data)
+	}
+}
+
+#[cfg(feature = "std")]
+impl<T: PassByCodec> FromFFIValue for T {
+	type SelfInstance = T;
+
+	fn from_ffi_value(context: &mut dyn FunctionContext, arg: u64) -> Result<T> {
+		let data = read_from_memory(context, arg)?;
+		T::decode(&mut &data[..])
+			.map_err(|e| format!("Could not decode value from wasm: {}", e.what()))
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: PassByCodec> IntoFFIValue for T {
+	type Owned = Vec<u8>;
+
+	fn into_ffi_value(&self) -> WrappedFFIValue<u64, Vec<u8>> {
+		let data = self.encode();
+		let ffi_value = pack_ptr_and_len(data.as_ptr() as u32, data.len() as u32);
+		(ffi_value, data).into()
+	}
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: PassByCodec> FromFFIValue for T {
+	fn from_ffi_value(arg: u64) -> T {
+		let data = <Vec<u8> as FromFFIValue>::from_ffi_value(arg);
+		T::decode(&mut &data[..]).expect("Host to wasm values are encoded correctly; qed")
+	}
+}
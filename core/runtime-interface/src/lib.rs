@@ -0,0 +1,482 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Substrate runtime interface
+//!
+//! This crate provides types, traits and macros around runtime interfaces. A runtime interface is
+//! a fixed interface between a Substrate runtime and a Substrate node. For a native runtime the
+//! interface maps to a direct function call of the implementation. For a wasm runtime the
+//! interface maps to an external function call. These external functions are exported by the
+//! wasm executor and they map to the same implementation as the native calls.
+//!
+//! A runtime interface is declared once with the [`runtime_interface`] attribute and the macro
+//! generates everything that is required on both sides of the boundary:
+//!
+//! - the functions the runtime calls, which either call the implementation directly (`std`) or
+//! call the imported host function (`no_std`),
+//! - the `extern "C"` declarations of the host functions for the wasm runtime,
+//! - the host function implementations that are registered in the executor, which read the
+//! arguments from the wasm memory and call the implementation.
+//!
+//! # Using a type in a runtime interface
+//!
+//! Any type that should be used in a runtime interface as argument or return value needs to
+//! implement [`RIType`]. The associated type `FFIType` is the type that is used in the FFI
+//! function to represent the actual type. The following types are supported out of the box:
+//!
+//! - The integer types `u8`, `u16`, `u32`, `u64`, `i8`, `i16`, `i32`, `i64` and `bool` are
+//! passed as their wasm counterparts.
+//! - `&[u8]`, `Vec<u8>` and `&str` are passed as a pointer and a length packed into an `u64`.
+//! - Any type implementing [`pass_by::PassByCodec`] is passed SCALE encoded, again as a pointer
+//! and a length packed into an `u64`. This includes `Option<T>`, `Result<T, E>` and fixed size
+//! byte arrays.
+//!
+//! # Example
+//!
+//! ```
+//! use substrate_runtime_interface::runtime_interface;
+//!
+//! #[runtime_interface]
+//! trait Interface {
+//!     /// A function that can be called from native/wasm.
+//!     ///
+//!     /// The implementation given to this function is only compiled on native.
+//!     fn call_some_complex_code(data: &[u8]) -> Vec<u8> {
+//!         // Here you could call some rather complex code that only compiles on native or is
+//!         // way faster in native than executing it in wasm.
+//!         Vec::new()
+//!     }
+//!
+//!     /// A function can take a `&mut self` to get access to the `Externalities`.
+//!     fn set_or_clear(&mut self, key: &[u8], value: Option<Vec<u8>>) {
+//!         match value {
+//!             Some(value) => self.set_storage(key.to_vec(), value),
+//!             None => self.clear_storage(key),
+//!         }
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! The example generates a module `interface` that contains the functions
+//! `interface::call_some_complex_code` and `interface::set_or_clear`. The host functions are
+//! exported under the names `ext_interface_call_some_complex_code_version_1` and
+//! `ext_interface_set_or_clear_version_1`. On the node side `interface::HostFunctions` implements
+//! `HostFunctions` and needs to be registered in the executor, see
+//! `substrate_executor::NativeExecutionDispatch::ExtendHostFunctions`.
+//!
+//! # Versioning
+//!
+//! Runtimes are stored on chain and need to stay executable, so a host function can never change
+//! after it was released. Instead, a new version of the function is added with the
+//! `#[version(X)]` attribute, while the old version is kept. The runtime calls the latest version,
+//! the executor provides all versions side by side. See [`runtime_interface`] for more
+//! information.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Make the code generated by the proc-macro resolve inside this crate as well.
+extern crate self as substrate_runtime_interface;
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use wasm_interface;
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use externalities::{set_and_run_with_externalities, with_externalities, Externalities};
+
+#[doc(hidden)]
+pub use codec;
+
+#[doc(hidden)]
+pub use rstd;
+
+pub use runtime_interface_proc_macro::runtime_interface;
+
+pub(crate) mod impls;
+#[cfg(feature = "std")]
+pub mod host;
+#[cfg(not(feature = "std"))]
+pub mod wasm;
+pub mod pass_by;
+
+/// Something that can be used by the runtime interface as type to communicate between wasm and the
+/// host.
+///
+/// Every type that should be used in a runtime interface function signature needs to implement
+/// this trait.
+pub trait RIType {
+	/// The ffi type that is used to represent `Self`.
+	#[cfg(feature = "std")]
+	type FFIType: wasm_interface::IntoValue + wasm_interface::TryFromValue;
+	/// The ffi type that is used to represent `Self`.
+	#[cfg(not(feature = "std"))]
+	type FFIType;
+}
+
+/// Pack a pointer and a length into an `u64`.
+///
+/// The pointer is stored in the lower 32 bits and the length in the upper 32 bits.
+pub fn pack_ptr_and_len(ptr: u32, len: u32) -> u64 {
+	(u64::from(len) << 32) | u64::from(ptr)
+}
+
+/// Unpacks an `u64` into the pointer and the length.
+///
+/// Reverses [`pack_ptr_and_len`].
+pub fn unpack_ptr_and_len(val: u64) -> (u32, u32) {
+	let ptr = (val & (!0u32 as u64)) as u32;
+	let len = (val >> 32) as u32;
+
+	(ptr, len)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use wasm_interface::{
+		FunctionContext, HostFunctions, MemoryId, Pointer, Result, Sandbox, Value, ValueType, WordSize,
+	};
+	use state_machine::BasicExternalities;
+	use codec::{Encode, Decode};
+
+	#[runtime_interface]
+	trait TestInterface {
+		fn add(a: u32, b: u32) -> u32 {
+			a + b
+		}
+
+		fn concat(data: &[u8], other: Vec<u8>) -> Vec<u8> {
+			let mut res = data.to_vec();
+			res.extend(other);
+			res
+		}
+
+		fn is_empty(data: &str) -> bool {
+			data.is_empty()
+		}
+
+		fn set_or_clear(&mut self, key: &[u8], value: Option<Vec<u8>>) {
+			match value {
+				Some(value) => self.set_storage(key.to_vec(), value),
+				None => self.clear_storage(key),
+			}
+		}
+
+		fn read(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+			self.storage(key)
+		}
+
+		fn double(value: u32) -> u32 {
+			value * 2
+		}
+
+		#[version(2)]
+		fn double(value: u64) -> u64 {
+			value * 2 + 1
+		}
+	}
+
+	/// A `FunctionContext` that is backed by a plain buffer and allocates with a bump pointer.
+	struct TestContext {
+		memory: Vec<u8>,
+		next_free: u32,
+		allocations: std::collections::HashMap<u32, u32>,
+		sandbox: NoSandbox,
+	}
+
+	/// A `Sandbox` that fails every call, as no sandboxed instances can be created in the tests.
+	struct NoSandbox;
+
+	impl Sandbox for NoSandbox {
+		fn memory_get(&mut self, _: MemoryId, _: WordSize, _: Pointer<u8>, _: WordSize) -> Result<u32> {
+			Err("The sandbox isn't available".into())
+		}
+
+		fn memory_set(&mut self, _: MemoryId, _: WordSize, _: Pointer<u8>, _: WordSize) -> Result<u32> {
+			Err("The sandbox isn't available".into())
+		}
+
+		fn memory_teardown(&mut self, _: MemoryId) -> Result<()> {
+			Err("The sandbox isn't available".into())
+		}
+
+		fn memory_new(&mut self, _: u32, _: u32) -> Result<MemoryId> {
+			Err("The sandbox isn't available".into())
+		}
+
+		fn invoke(&mut self, _: u32, _: &str, _: &[u8], _: Pointer<u8>, _: WordSize, _: u32) -> Result<u32> {
+			Err("The sandbox isn't available".into())
+		}
+
+		fn instance_teardown(&mut self, _: u32) -> Result<()> {
+			Err("The sandbox isn't available".into())
+		}
+
+		fn instance_new(&mut self, _: u32, _: &[u8], _: &[u8], _: u32) -> Result<u32> {
+			Err("The sandbox isn't available".into())
+		}
+	}
+
+	impl TestContext {
+		fn new() -> Self {
+			TestContext {
+				memory: vec![0; 1024],
+				next_free: 8,
+				allocations: Default::default(),
+				sandbox: NoSandbox,
+			}
+		}
+
+		fn write(&mut self, data: &[u8]) -> u64 {
+			let ptr = self.allocate_memory(data.len() as u32).unwrap();
+			self.write_memory(ptr, data).unwrap();
+			pack_ptr_and_len(ptr.into(), data.len() as u32)
+		}
+
+		fn read(&self, value: Option<Value>) -> Vec<u8> {
+			match value {
+				Some(Value::I64(val)) => {
+					let (ptr, len) = unpack_ptr_and_len(val as u64);
+					self.read_memory(Pointer::new(ptr), len).unwrap()
+				},
+				val => panic!("Expected a packed pointer and length, got: {:?}", val),
+			}
+		}
+	}
+
+	impl FunctionContext for TestContext {
+		fn read_memory_into(&self, address: Pointer<u8>, dest: &mut [u8]) -> Result<()> {
+			let start = u32::from(address) as usize;
+			let data = self.memory.get(start..start + dest.len()).ok_or("Read out of bounds")?;
+			dest.copy_from_slice(data);
+			Ok(())
+		}
+
+		fn write_memory(&mut self, address: Pointer<u8>, data: &[u8]) -> Result<()> {
+			let start = u32::from(address) as usize;
+			self.memory.get_mut(start..start + data.len())
+				.ok_or("Write out of bounds")?
+				.copy_from_slice(data);
+			Ok(())
+		}
+
+		fn allocate_memory(&mut self, size: WordSize) -> Result<Pointer<u8>> {
+			let ptr = self.next_free;
+			self.next_free += size;
+			self.allocations.insert(ptr, size);
+			Ok(Pointer::new(ptr))
+		}
+
+		fn deallocate_memory(&mut self, ptr: Pointer<u8>) -> Result<()> {
+			self.allocations.remove(&u32::from(ptr));
+			Ok(())
+		}
+
+		fn reallocate_memory(&mut self, ptr: Pointer<u8>, size: WordSize) -> Result<Pointer<u8>> {
+			let old_size = *self.allocations.get(&u32::from(ptr)).ok_or("Invalid pointer")?;
+			let data = self.read_memory(ptr, old_size.min(size))?;
+			let new_ptr = self.allocate_memory(size)?;
+			self.write_memory(new_ptr, &data)?;
+			self.deallocate_memory(ptr)?;
+			Ok(new_ptr)
+		}
+
+		fn sandbox(&mut self) -> &mut dyn Sandbox {
+			&mut self.sandbox
+		}
+	}
+
+	/// Execute the host function `ext_test_interface_{name}`.
+	fn execute(name: &str, context: &mut TestContext, args: &[Value]) -> Result<Option<Value>> {
+		let name = format!("ext_test_interface_{}", name);
+		let functions = test_interface::HostFunctions::host_functions();
+		let function = functions.iter().find(|f| f.name() == name).expect("Function exists");
+
+		function.execute(context, &mut args.iter().cloned())
+	}
+
+	#[test]
+	fn host_functions_are_named_after_trait_and_method() {
+		let names = test_interface::HostFunctions::host_functions()
+			.iter()
+			.map(|f| f.name().to_string())
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			names,
+			vec![
+				"ext_test_interface_add_version_1",
+				"ext_test_interface_concat_version_1",
+				"ext_test_interface_is_empty_version_1",
+				"ext_test_interface_set_or_clear_version_1",
+				"ext_test_interface_read_version_1",
+				"ext_test_interface_double_version_1",
+				"ext_test_interface_double_version_2",
+			],
+		);
+	}
+
+	#[test]
+	fn interface_has_the_version_of_its_latest_method() {
+		assert_eq!(test_interface::INTERFACE_NAME, "test_interface");
+		assert_eq!(test_interface::INTERFACE_VERSION, 2);
+		assert_eq!(test_interface::HostFunctions::interfaces(), vec![("test_interface", 2)]);
+	}
+
+	#[test]
+	fn reallocation_keeps_the_content() {
+		let mut context = TestContext::new();
+		let ptr = context.allocate_memory(4).unwrap();
+		context.write_memory(ptr, &[1, 2, 3, 4]).unwrap();
+
+		let grown = context.reallocate_memory(ptr, 8).unwrap();
+		assert_eq!(context.read_memory(grown, 4).unwrap(), vec![1, 2, 3, 4]);
+
+		let shrunk = context.reallocate_memory(grown, 2).unwrap();
+		assert_eq!(context.read_memory(shrunk, 2).unwrap(), vec![1, 2]);
+		assert!(context.reallocate_memory(grown, 2).is_err());
+	}
+
+	#[test]
+	fn host_function_signatures_use_ffi_types() {
+		let functions = test_interface::HostFunctions::host_functions();
+
+		let add = functions[0].signature();
+		assert_eq!(&add.args[..], &[ValueType::I32, ValueType::I32][..]);
+		assert_eq!(add.return_value, Some(ValueType::I32));
+
+		let set_or_clear = functions[3].signature();
+		assert_eq!(&set_or_clear.args[..], &[ValueType::I64, ValueType::I64][..]);
+		assert_eq!(set_or_clear.return_value, None);
+	}
+
+	#[test]
+	fn bare_functions_call_the_implementation_on_native() {
+		assert_eq!(test_interface::add(1, 2), 3);
+		assert_eq!(test_interface::concat(&[1, 2], vec![3]), vec![1, 2, 3]);
+		assert!(test_interface::is_empty(""));
+
+		let mut ext = BasicExternalities::default();
+		set_and_run_with_externalities(&mut ext, || {
+			test_interface::set_or_clear(b"key", Some(b"value".to_vec()));
+			assert_eq!(test_interface::read(b"key"), Some(b"value".to_vec()));
+
+			test_interface::set_or_clear(b"key", None);
+			assert_eq!(test_interface::read(b"key"), None);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "called outside of an Externalities-provided environment")]
+	fn bare_functions_with_self_require_externalities() {
+		test_interface::read(b"key");
+	}
+
+	#[test]
+	fn all_versions_are_provided_and_the_latest_is_called() {
+		let functions = test_interface::HostFunctions::host_functions();
+
+		let version_1 = functions[5].signature();
+		assert_eq!(&version_1.args[..], &[ValueType::I32][..]);
+		assert_eq!(version_1.return_value, Some(ValueType::I32));
+
+		let version_2 = functions[6].signature();
+		assert_eq!(&version_2.args[..], &[ValueType::I64][..]);
+		assert_eq!(version_2.return_value, Some(ValueType::I64));
+
+		let mut context = TestContext::new();
+		let res = execute("double_version_1", &mut context, &[Value::I32(2)]);
+		assert_eq!(res, Ok(Some(Value::I32(4))));
+		let res = execute("double_version_2", &mut context, &[Value::I64(2)]);
+		assert_eq!(res, Ok(Some(Value::I64(5))));
+
+		assert_eq!(test_interface::double(2), 5);
+	}
+
+	#[test]
+	fn host_functions_convert_primitive_values() {
+		let mut context = TestContext::new();
+
+		let res = execute("add_version_1", &mut context, &[Value::I32(5), Value::I32(6)]);
+		assert_eq!(res, Ok(Some(Value::I32(11))));
+	}
+
+	#[test]
+	fn host_functions_read_and_write_the_wasm_memory() {
+		let mut context = TestContext::new();
+		let data = context.write(&[1, 2, 3]);
+		let other = context.write(&[4, 5]);
+
+		let res = execute(
+			"concat_version_1",
+			&mut context,
+			&[Value::I64(data as i64), Value::I64(other as i64)],
+		).unwrap();
+		assert_eq!(context.read(res), vec![1, 2, 3, 4, 5]);
+
+		let data = context.write(b"");
+		let res = execute("is_empty_version_1", &mut context, &[Value::I64(data as i64)]);
+		assert_eq!(res, Ok(Some(Value::I32(1))));
+	}
+
+	#[test]
+	fn host_functions_pass_codec_values_encoded() {
+		let mut context = TestContext::new();
+		let mut ext = BasicExternalities::default();
+
+		set_and_run_with_externalities(&mut ext, || {
+			let key = context.write(b"key");
+			let value = context.write(&Some(b"value".to_vec()).encode());
+			let res = execute(
+				"set_or_clear_version_1",
+				&mut context,
+				&[Value::I64(key as i64), Value::I64(value as i64)],
+			);
+			assert_eq!(res, Ok(None));
+
+			let res = execute("read_version_1", &mut context, &[Value::I64(key as i64)]).unwrap();
+			assert_eq!(
+				Option::<Vec<u8>>::decode(&mut &context.read(res)[..]).unwrap(),
+				Some(b"value".to_vec()),
+			);
+		});
+	}
+
+	#[test]
+	fn host_functions_reject_wrong_arguments() {
+		let mut context = TestContext::new();
+
+		assert!(execute("add_version_1", &mut context, &[Value::I32(5)]).is_err());
+		assert!(execute("add_version_1", &mut context, &[Value::I64(5), Value::I32(6)]).is_err());
+
+		let data = context.write(&[0xff, 0xfe]);
+		assert!(execute("is_empty_version_1", &mut context, &[Value::I64(data as i64)]).is_err());
+	}
+
+	#[test]
+	fn ptr_len_packing_unpacking() {
+		const PTR: u32 = 0x1337;
+		const LEN: u32 = 0x7f000000;
+
+		let packed = pack_ptr_and_len(PTR, LEN);
+		let (ptr, len) = unpack_ptr_and_len(packed);
+
+		assert_eq!(PTR, ptr);
+		assert_eq!(LEN, len);
+	}
+}
@@ -0,0 +1,57 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Provides the [`PassByCodec`] marker trait for passing arbitrary SCALE encodable types through
+//! the runtime interface.
+
+use codec::Codec;
+
+/// Something that should be passed by SCALE encoding it through the runtime interface.
+///
+/// Implementing this trait for a type implements [`RIType`](crate::RIType) and the conversions
+/// between the host and the wasm side for it. The encoded value is passed as a pointer and a
+/// length packed into an `u64`.
+///
+/// # Example
+///
+/// ```
+/// # use substrate_runtime_interface::pass_by::PassByCodec;
+/// # use codec::{Encode, Decode};
+/// #[derive(Encode, Decode)]
+/// struct Test {
+///     data: Vec<u32>,
+///     flag: bool,
+/// }
+///
+/// impl PassByCodec for Test {}
+/// ```
+pub trait PassByCodec: Codec {}
+
+impl<T: Codec> PassByCodec for Option<T> {}
+
+impl<T: Codec, E: Codec> PassByCodec for Result<T, E> {}
+
+impl PassByCodec for rstd::vec::Vec<rstd::vec::Vec<u8>> {}
+
+macro_rules! impl_pass_by_codec_for_array {
+	( $( $n:expr ),* $(,)? ) => {
+		$(
+			impl PassByCodec for [u8; $n] {}
+		)*
+	}
+}
+
+impl_pass_by_codec_for_array!(8, 16, 20, 32, 33, 64, 65);
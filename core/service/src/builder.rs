@@ -35,7 +35,10 @@ use futures03::{
 use keystore::{Store as Keystore, KeyStorePtr};
 use log::{info, warn};
 use network::{FinalityProofProvider, OnDemand, NetworkService, NetworkStateInfo, DhtEvent};
-use network::{config::BoxFinalityProofRequestBuilder, specialization::NetworkSpecialization};
+use network::{
+	config::{BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier},
+	specialization::NetworkSpecialization,
+};
 use parking_lot::{Mutex, RwLock};
 use primitives::{Blake2Hasher, H256, Hasher};
 use rpc::{self, system::SystemInfo};
@@ -60,6 +63,7 @@ use transaction_pool::txpool::{self, ChainApi, Pool as TransactionPool};
 /// - [`with_import_queue`](ServiceBuilder::with_import_queue)
 /// - [`with_network_protocol`](ServiceBuilder::with_network_protocol)
 /// - [`with_finality_proof_provider`](ServiceBuilder::with_finality_proof_provider)
+/// - [`with_warp_sync_verifier`](ServiceBuilder::with_warp_sync_verifier)
 /// - [`with_transaction_pool`](ServiceBuilder::with_transaction_pool)
 ///
 /// After this is done, call [`build`](ServiceBuilder::build) to construct the service.
@@ -67,7 +71,7 @@ use transaction_pool::txpool::{self, ChainApi, Pool as TransactionPool};
 /// The order in which the `with_*` methods are called doesn't matter, as the correct binding of
 /// generics is done when you call `build`.
 ///
-pub struct ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+pub struct ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv,
 	TNetP, TExPool, TRpc, TRpcB, Backend>
 {
 	config: Configuration<TCfg, TGen, TCSExt>,
@@ -79,6 +83,7 @@ pub struct ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImp
 	import_queue: TImpQu,
 	finality_proof_request_builder: Option<TFprb>,
 	finality_proof_provider: Option<TFpp>,
+	warp_sync_verifier: Option<TWsv>,
 	network_protocol: TNetP,
 	transaction_pool: Arc<TExPool>,
 	rpc_extensions: TRpc,
@@ -150,6 +155,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		(),
 		BoxFinalityProofRequestBuilder<TBl>,
 		Arc<dyn FinalityProofProvider<TBl>>,
+		BoxWarpSyncVerifier<TBl>,
 		(),
 		(),
 		(),
@@ -159,19 +165,25 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		let keystore = Keystore::open(config.keystore_path.clone(), config.keystore_password.clone())?;
 
 		let db_settings = client_db::DatabaseSettings {
-			cache_size: None,
 			state_cache_size: config.state_cache_size,
 			state_cache_child_ratio:
 				config.state_cache_child_ratio.map(|v| (v, 100)),
-			path: config.database_path.clone(),
 			pruning: config.pruning.clone(),
+			keep_block_bodies: config.keep_block_bodies,
+			source: config.database.clone(),
 		};
 
 		let executor = NativeExecutor::<TExecDisp>::new(
 			config.wasm_method,
 			config.default_heap_pages,
+			config.max_runtime_instances,
 		);
 
+		let wasm_override = config.wasm_runtime_overrides
+			.as_ref()
+			.map(client::WasmOverride::new)
+			.transpose()?;
+
 		let fork_blocks = config.chain_spec
 			.extensions()
 			.get::<client::ForkBlocks<TBl>>()
@@ -185,6 +197,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			fork_blocks,
 			config.execution_strategies.clone(),
 			Some(keystore.clone()),
+			wasm_override,
 		)?;
 
 		let client = Arc::new(client);
@@ -201,6 +214,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			import_queue: (),
 			finality_proof_request_builder: None,
 			finality_proof_provider: None,
+			warp_sync_verifier: None,
 			network_protocol: (),
 			transaction_pool: Arc::new(()),
 			rpc_extensions: Default::default(),
@@ -225,6 +239,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		(),
 		BoxFinalityProofRequestBuilder<TBl>,
 		Arc<dyn FinalityProofProvider<TBl>>,
+		BoxWarpSyncVerifier<TBl>,
 		(),
 		(),
 		(),
@@ -234,17 +249,18 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		let keystore = Keystore::open(config.keystore_path.clone(), config.keystore_password.clone())?;
 
 		let db_settings = client_db::DatabaseSettings {
-			cache_size: config.database_cache_size.map(|u| u as usize),
 			state_cache_size: config.state_cache_size,
 			state_cache_child_ratio:
 				config.state_cache_child_ratio.map(|v| (v, 100)),
-			path: config.database_path.clone(),
 			pruning: config.pruning.clone(),
+			keep_block_bodies: None,
+			source: config.database.clone(),
 		};
 
 		let executor = NativeExecutor::<TExecDisp>::new(
 			config.wasm_method,
 			config.default_heap_pages,
+			config.max_runtime_instances,
 		);
 
 		let db_storage = client_db::light::LightStorage::new(db_settings)?;
@@ -274,6 +290,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			import_queue: (),
 			finality_proof_request_builder: None,
 			finality_proof_provider: None,
+			warp_sync_verifier: None,
 			network_protocol: (),
 			transaction_pool: Arc::new(()),
 			rpc_extensions: Default::default(),
@@ -284,8 +301,8 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 	}
 }
 
-impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc, TRpcB, Backend>
-	ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP, TExPool, TRpc, TRpcB, Backend>
+	ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv,
 		TNetP, TExPool, TRpc, TRpcB, Backend> {
 
 	/// Returns a reference to the client that was stored in this builder.
@@ -309,7 +326,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		select_chain_builder: impl FnOnce(
 			&Configuration<TCfg, TGen, TCSExt>, &Arc<Backend>
 		) -> Result<Option<USc>, Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, USc, TImpQu, TFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, USc, TImpQu, TFprb, TFpp, TWsv,
 		TNetP, TExPool, TRpc, TRpcB, Backend>, Error> {
 		let select_chain = select_chain_builder(&self.config, &self.backend)?;
 
@@ -323,6 +340,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -336,7 +354,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 	pub fn with_select_chain<USc>(
 		self,
 		builder: impl FnOnce(&Configuration<TCfg, TGen, TCSExt>, &Arc<Backend>) -> Result<USc, Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, USc, TImpQu, TFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, USc, TImpQu, TFprb, TFpp, TWsv,
 		TNetP, TExPool, TRpc, TRpcB, Backend>, Error> {
 		self.with_opt_select_chain(|cfg, b| builder(cfg, b).map(Option::Some))
 	}
@@ -346,7 +364,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		self,
 		builder: impl FnOnce(&Configuration<TCfg, TGen, TCSExt>, Arc<TCl>, Option<TSc>, Arc<TExPool>)
 			-> Result<UImpQu, Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, UImpQu, TFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, UImpQu, TFprb, TFpp, TWsv,
 			TNetP, TExPool, TRpc, TRpcB, Backend>, Error>
 	where TSc: Clone {
 		let import_queue = builder(
@@ -366,6 +384,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -379,7 +398,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 	pub fn with_network_protocol<UNetP>(
 		self,
 		network_protocol_builder: impl FnOnce(&Configuration<TCfg, TGen, TCSExt>) -> Result<UNetP, Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv,
 		UNetP, TExPool, TRpc, TRpcB, Backend>, Error> {
 		let network_protocol = network_protocol_builder(&self.config)?;
 
@@ -393,6 +412,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -418,6 +438,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		TImpQu,
 		TFprb,
 		Arc<dyn FinalityProofProvider<TBl>>,
+		TWsv,
 		TNetP,
 		TExPool,
 		TRpc,
@@ -436,6 +457,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -461,6 +483,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		TImpQu,
 		TFprb,
 		Arc<dyn FinalityProofProvider<TBl>>,
+		TWsv,
 		TNetP,
 		TExPool,
 		TRpc,
@@ -470,6 +493,51 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		self.with_opt_finality_proof_provider(|client, backend| build(client, backend).map(Option::Some))
 	}
 
+	/// Defines how to verify the proofs downloaded during a warp sync.
+	pub fn with_warp_sync_verifier(
+		self,
+		build: impl FnOnce(Arc<TCl>, Arc<Backend>) -> Result<BoxWarpSyncVerifier<TBl>, Error>
+	) -> Result<ServiceBuilder<
+		TBl,
+		TRtApi,
+		TCfg,
+		TGen,
+		TCSExt,
+		TCl,
+		TFchr,
+		TSc,
+		TImpQu,
+		TFprb,
+		TFpp,
+		BoxWarpSyncVerifier<TBl>,
+		TNetP,
+		TExPool,
+		TRpc,
+		TRpcB,
+		Backend,
+	>, Error> {
+		let warp_sync_verifier = build(self.client.clone(), self.backend.clone())?;
+
+		Ok(ServiceBuilder {
+			config: self.config,
+			client: self.client,
+			backend: self.backend,
+			keystore: self.keystore,
+			fetcher: self.fetcher,
+			select_chain: self.select_chain,
+			import_queue: self.import_queue,
+			finality_proof_request_builder: self.finality_proof_request_builder,
+			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: Some(warp_sync_verifier),
+			network_protocol: self.network_protocol,
+			transaction_pool: self.transaction_pool,
+			rpc_extensions: self.rpc_extensions,
+			rpc_builder: self.rpc_builder,
+			dht_event_tx: self.dht_event_tx,
+			marker: self.marker,
+		})
+	}
+
 	/// Defines which import queue to use.
 	pub fn with_import_queue_and_opt_fprb<UImpQu, UFprb>(
 		self,
@@ -481,7 +549,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			Option<TSc>,
 			Arc<TExPool>,
 		) -> Result<(UImpQu, Option<UFprb>), Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, UImpQu, UFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, UImpQu, UFprb, TFpp, TWsv,
 		TNetP, TExPool, TRpc, TRpcB, Backend>, Error>
 	where TSc: Clone, TFchr: Clone {
 		let (import_queue, fprb) = builder(
@@ -503,6 +571,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue,
 			finality_proof_request_builder: fprb,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -523,7 +592,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			Option<TSc>,
 			Arc<TExPool>,
 		) -> Result<(UImpQu, UFprb), Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, UImpQu, UFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, UImpQu, UFprb, TFpp, TWsv,
 			TNetP, TExPool, TRpc, TRpcB, Backend>, Error>
 	where TSc: Clone, TFchr: Clone {
 		self.with_import_queue_and_opt_fprb(|cfg, cl, b, f, sc, tx|
@@ -536,7 +605,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 	pub fn with_transaction_pool<UExPool>(
 		self,
 		transaction_pool_builder: impl FnOnce(transaction_pool::txpool::Options, Arc<TCl>) -> Result<UExPool, Error>
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv,
 		TNetP, UExPool, TRpc, TRpcB, Backend>, Error> {
 		let transaction_pool = transaction_pool_builder(self.config.transaction_pool.clone(), self.client.clone())?;
 
@@ -550,6 +619,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: Arc::new(transaction_pool),
 			rpc_extensions: self.rpc_extensions,
@@ -563,7 +633,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 	pub fn with_rpc_extensions<URpc>(
 		self,
 		rpc_ext_builder: impl FnOnce(Arc<TCl>, Arc<TExPool>) -> URpc
-	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv,
 		TNetP, TExPool, URpc, TRpcB, Backend>, Error> {
 		let rpc_extensions = rpc_ext_builder(self.client.clone(), self.transaction_pool.clone());
 
@@ -577,6 +647,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions,
@@ -591,7 +662,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		pub fn with_dht_event_tx(
 			self,
 			dht_event_tx: mpsc::Sender<DhtEvent>,
-		) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+		) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv,
 								   TNetP, TExPool, TRpc, TRpcB, Backend>, Error> {
 			Ok(ServiceBuilder {
 				config: self.config,
@@ -603,6 +674,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 				import_queue: self.import_queue,
 				finality_proof_request_builder: self.finality_proof_request_builder,
 				finality_proof_provider: self.finality_proof_provider,
+				warp_sync_verifier: self.warp_sync_verifier,
 				network_protocol: self.network_protocol,
 				transaction_pool: self.transaction_pool,
 				rpc_extensions: self.rpc_extensions,
@@ -618,7 +690,11 @@ pub trait RpcBuilder<TBl: BlockT, TBackend, TExec, TRtApi> {
 	/// Build chain RPC handler.
 	fn build_chain(&self, subscriptions: rpc::Subscriptions) -> rpc::chain::Chain<TBackend, TExec, TBl, TRtApi>;
 	/// Build state RPC handler.
-	fn build_state(&self, subscriptions: rpc::Subscriptions) -> rpc::state::State<TBackend, TExec, TBl, TRtApi>;
+	fn build_state(
+		&self,
+		subscriptions: rpc::Subscriptions,
+		deny_unsafe: rpc::DenyUnsafe,
+	) -> rpc::state::State<TBackend, TExec, TBl, TRtApi>;
 }
 
 /// RPC handlers builder for full nodes.
@@ -646,8 +722,9 @@ impl<TBl, TRtApi, TExecDisp> RpcBuilder<TBl, TFullBackend<TBl>, TFullCallExecuto
 	fn build_state(
 		&self,
 		subscriptions: rpc::Subscriptions,
+		deny_unsafe: rpc::DenyUnsafe,
 	) -> rpc::state::State<TFullBackend<TBl>, TFullCallExecutor<TBl, TExecDisp>, TBl, TRtApi> {
-		rpc::state::new_full(self.client.clone(), subscriptions)
+		rpc::state::new_full(self.client.clone(), subscriptions, deny_unsafe)
 	}
 }
 
@@ -681,12 +758,14 @@ impl<TBl, TRtApi, TExecDisp> RpcBuilder<TBl, TLightBackend<TBl>, TLightCallExecu
 	fn build_state(
 		&self,
 		subscriptions: rpc::Subscriptions,
+		deny_unsafe: rpc::DenyUnsafe,
 	) -> rpc::state::State<TLightBackend<TBl>, TLightCallExecutor<TBl, TExecDisp>, TBl, TRtApi> {
 		rpc::state::new_light(
 			self.client.clone(),
 			subscriptions,
 			self.remote_blockchain.clone(),
 			self.fetcher.clone(),
+			deny_unsafe,
 		)
 	}
 }
@@ -700,6 +779,13 @@ pub trait ServiceBuilderImport {
 		exit: impl Future<Item=(),Error=()> + Send + 'static,
 		input: impl Read + Seek,
 	) -> Result<Box<dyn Future<Item = (), Error = ()> + Send>, Error>;
+
+	/// Imports a state snapshot as the new best and finalized block.
+	fn import_state(
+		self,
+		exit: impl Future<Item=(),Error=()> + Send + 'static,
+		input: impl Read,
+	) -> Result<(), Error>;
 }
 
 /// Implemented on `ServiceBuilder`. Allows exporting blocks once you have given all the required
@@ -717,6 +803,14 @@ pub trait ServiceBuilderExport {
 		to: Option<NumberFor<Self::Block>>,
 		json: bool
 	) -> Result<(), Error>;
+
+	/// Performs the export of the state of `block`, or of the finalized block if `None`.
+	fn export_state(
+		&self,
+		exit: impl Future<Item=(),Error=()> + Send + 'static,
+		output: impl Write,
+		block: Option<NumberFor<Self::Block>>,
+	) -> Result<(), Error>;
 }
 
 /// Implemented on `ServiceBuilder`. Allows reverting the chain once you have given all the
@@ -734,11 +828,11 @@ pub trait ServiceBuilderRevert {
 
 impl<
 	TBl, TRtApi, TCfg, TGen, TCSExt, TBackend,
-	TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP,
+	TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP,
 	TExPool, TRpc, TRpcB, Backend
 > ServiceBuilderImport for ServiceBuilder<
 	TBl, TRtApi, TCfg, TGen, TCSExt, Client<TBackend, TExec, TBl, TRtApi>,
-	TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc, TRpcB, Backend
+	TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP, TExPool, TRpc, TRpcB, Backend
 > where
 	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
 	TBackend: 'static + client::backend::Backend<TBl, Blake2Hasher> + Send,
@@ -756,11 +850,20 @@ impl<
 		import_blocks!(TBl, client, queue, exit, input)
 			.map(|f| Box::new(f) as Box<_>)
 	}
+
+	fn import_state(
+		self,
+		exit: impl Future<Item=(),Error=()> + Send + 'static,
+		input: impl Read,
+	) -> Result<(), Error> {
+		let client = self.client;
+		import_state!(TBl, client, exit, input)
+	}
 }
 
-impl<TBl, TRtApi, TCfg, TGen, TCSExt, TBackend, TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc, TRpcB>
+impl<TBl, TRtApi, TCfg, TGen, TCSExt, TBackend, TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP, TExPool, TRpc, TRpcB>
 	ServiceBuilderExport for ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, Client<TBackend, TExec, TBl, TRtApi>,
-		TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc, TRpcB, TBackend>
+		TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP, TExPool, TRpc, TRpcB, TBackend>
 where
 	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
 	TBackend: 'static + client::backend::Backend<TBl, Blake2Hasher> + Send,
@@ -779,11 +882,21 @@ where
 		let client = &self.client;
 		export_blocks!(client, exit, output, from, to, json)
 	}
+
+	fn export_state(
+		&self,
+		exit: impl Future<Item=(),Error=()> + Send + 'static,
+		mut output: impl Write,
+		block: Option<NumberFor<TBl>>,
+	) -> Result<(), Error> {
+		let client = &self.client;
+		export_state!(client, exit, output, block)
+	}
 }
 
-impl<TBl, TRtApi, TCfg, TGen, TCSExt, TBackend, TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc, TRpcB>
+impl<TBl, TRtApi, TCfg, TGen, TCSExt, TBackend, TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP, TExPool, TRpc, TRpcB>
 	ServiceBuilderRevert for ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, Client<TBackend, TExec, TBl, TRtApi>,
-		TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc, TRpcB, TBackend>
+		TFchr, TSc, TImpQu, TFprb, TFpp, TWsv, TNetP, TExPool, TRpc, TRpcB, TBackend>
 where
 	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
 	TBackend: 'static + client::backend::Backend<TBl, Blake2Hasher> + Send,
@@ -813,6 +926,7 @@ ServiceBuilder<
 	TImpQu,
 	BoxFinalityProofRequestBuilder<TBl>,
 	Arc<dyn FinalityProofProvider<TBl>>,
+	BoxWarpSyncVerifier<TBl>,
 	TNetP,
 	TransactionPool<TExPoolApi>,
 	TRpc,
@@ -864,6 +978,7 @@ ServiceBuilder<
 			import_queue,
 			finality_proof_request_builder,
 			finality_proof_provider,
+			warp_sync_verifier,
 			network_protocol,
 			transaction_pool,
 			rpc_extensions,
@@ -889,6 +1004,7 @@ ServiceBuilder<
 					import_queue,
 					finality_proof_request_builder,
 					finality_proof_provider,
+					warp_sync_verifier,
 					network_protocol,
 					transaction_pool,
 					rpc_extensions,
@@ -897,7 +1013,7 @@ ServiceBuilder<
 			},
 			|h, c, tx, r| maintain_transaction_pool(h, c, tx, r),
 			|n, o, p, ns, v| offchain_workers(n, o, p, ns, v),
-			|c, ssb, si, te, tp, ext, ks| start_rpc(&rpc_builder, c, ssb, si, te, tp, ext, ks),
+			|c, ssb, si, te, tp, ext, ks, du| start_rpc(&rpc_builder, c, ssb, si, te, tp, ext, ks, du),
 		)
 	}
 }
@@ -911,6 +1027,7 @@ pub(crate) fn start_rpc<Api, Backend, Block, Executor, PoolApi, RpcB>(
 	transaction_pool: Arc<TransactionPool<PoolApi>>,
 	rpc_extensions: impl rpc::RpcExtension<rpc::Metadata>,
 	keystore: KeyStorePtr,
+	deny_unsafe: rpc::DenyUnsafe,
 ) -> rpc_servers::RpcHandler<rpc::Metadata>
 where
 	Block: BlockT<Hash = <Blake2Hasher as primitives::Hasher>::Out>,
@@ -926,7 +1043,7 @@ where
 	use rpc::{chain, state, author, system};
 	let subscriptions = rpc::Subscriptions::new(task_executor);
 	let chain = rpc_builder.build_chain(subscriptions.clone());
-	let state = rpc_builder.build_state(subscriptions.clone());
+	let state = rpc_builder.build_state(subscriptions.clone(), deny_unsafe);
 	let author = rpc::author::Author::new(
 		client,
 		transaction_pool,
@@ -18,6 +18,141 @@
 
 use crate::error;
 use chain_spec::{ChainSpec, RuntimeGenesis, Extension};
+use codec::{Decode, Encode, Input};
+use log::info;
+use primitives::{Hasher, storage::well_known_keys};
+use client::backend::BlockImportOperation;
+use sr_primitives::{StorageOverlay, ChildrenStorageOverlay, traits::Block as BlockT};
+use state_machine::backend::Backend as StateBackend;
+use std::{io::Write, sync::mpsc::Receiver};
+
+/// An entry of a state snapshot.
+///
+/// A snapshot, as written by `export_state!`, is the encoded header of the block followed by the
+/// entries of its state and `StateEntry::End`. The value of a child trie in the main trie
+/// precedes the contents of the child trie.
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub enum StateEntry {
+	/// A key/value pair of the main trie.
+	Top(Vec<u8>, Vec<u8>),
+	/// A key/value pair of the child trie with the given storage key.
+	Child(Vec<u8>, Vec<u8>, Vec<u8>),
+	/// The end of the snapshot.
+	End,
+}
+
+/// Write all key/value pairs of `state`, including the ones of its child tries, to `output`.
+///
+/// Returns the number of written key/value pairs.
+pub fn write_state<H: Hasher, S: StateBackend<H>>(
+	state: &S,
+	output: &mut impl Write,
+	exit: &Receiver<()>,
+) -> error::Result<u64> {
+	let state_err = |e: S::Error| format!("Error reading state: {}", e);
+	let mut count = 0;
+	let mut key = Vec::new();
+	let mut value = state.storage(&key).map_err(state_err)?;
+	loop {
+		if exit.try_recv().is_ok() {
+			return Err("State export interrupted".into());
+		}
+		if let Some(value) = value {
+			output.write_all(&StateEntry::Top(key.clone(), value).encode())?;
+			count += 1;
+			if well_known_keys::is_child_storage_key(&key) {
+				count += write_child_state(state, &key, output)?;
+			}
+			if count % 100_000 == 0 {
+				info!("#{} storage entries", count);
+			}
+		}
+		key = match state.next_storage_key(&key).map_err(state_err)? {
+			Some(key) => key,
+			None => break,
+		};
+		value = state.storage(&key).map_err(state_err)?;
+	}
+	output.write_all(&StateEntry::End.encode())?;
+	Ok(count)
+}
+
+fn write_child_state<H: Hasher, S: StateBackend<H>>(
+	state: &S,
+	storage_key: &[u8],
+	output: &mut impl Write,
+) -> error::Result<u64> {
+	let state_err = |e: S::Error| format!("Error reading state: {}", e);
+	let mut count = 0;
+	let mut key = Vec::new();
+	let mut value = state.child_storage(storage_key, &key).map_err(state_err)?;
+	loop {
+		if let Some(value) = value {
+			output.write_all(&StateEntry::Child(storage_key.to_vec(), key.clone(), value).encode())?;
+			count += 1;
+		}
+		key = match state.next_child_storage_key(storage_key, &key).map_err(state_err)? {
+			Some(key) => key,
+			None => break,
+		};
+		value = state.child_storage(storage_key, &key).map_err(state_err)?;
+	}
+	Ok(count)
+}
+
+/// Number of entries of a state snapshot passed at once to the import operation.
+const STATE_IMPORT_BATCH: usize = 100_000;
+
+/// Read the entries of a state snapshot written by `write_state` from `input` and import them
+/// into `op` in batches.
+///
+/// Returns the root of the imported state.
+pub fn read_state<Block: BlockT, H: Hasher<Out=Block::Hash>>(
+	input: &mut impl Input,
+	op: &mut impl BlockImportOperation<Block, H>,
+	exit: &Receiver<()>,
+) -> error::Result<H::Out> {
+	read_state_in_batches(input, op, exit, STATE_IMPORT_BATCH)
+}
+
+fn read_state_in_batches<Block: BlockT, H: Hasher<Out=Block::Hash>>(
+	input: &mut impl Input,
+	op: &mut impl BlockImportOperation<Block, H>,
+	exit: &Receiver<()>,
+	batch_size: usize,
+) -> error::Result<H::Out> {
+	let mut top = StorageOverlay::new();
+	let mut children = ChildrenStorageOverlay::new();
+	let mut batch = 0;
+	let mut count: u64 = 0;
+	loop {
+		if exit.try_recv().is_ok() {
+			return Err("State import interrupted".into());
+		}
+		match StateEntry::decode(input).map_err(|e| format!("Error reading file: {}", e))? {
+			StateEntry::Top(key, value) => {
+				top.insert(key, value);
+			},
+			StateEntry::Child(storage_key, key, value) => {
+				children.entry(storage_key).or_default().insert(key, value);
+			},
+			StateEntry::End => break,
+		}
+		batch += 1;
+		if batch == batch_size {
+			op.import_state_batch(
+				std::mem::replace(&mut top, StorageOverlay::new()),
+				std::mem::replace(&mut children, ChildrenStorageOverlay::new()),
+			)?;
+			batch = 0;
+		}
+		count += 1;
+		if count % 100_000 == 0 {
+			info!("#{} storage entries were read", count);
+		}
+	}
+	Ok(op.import_state(top, children)?)
+}
 
 /// Defines the logic for an operation exporting blocks within a range.
 #[macro_export]
@@ -206,6 +341,66 @@ macro_rules! import_blocks {
 }}
 }
 
+/// Export the state of a block, the finalized one by default, as a state snapshot.
+#[macro_export]
+macro_rules! export_state {
+($client:ident, $exit:ident, $output:ident, $block:ident) => {{
+	let id = match $block {
+		Some(number) => BlockId::Number(number),
+		None => BlockId::Hash($client.info().chain.finalized_hash),
+	};
+	let header = $client.header(&id)?.ok_or_else(|| format!("Unknown block {}", id))?;
+	let state = $client.state_at(&id)?;
+
+	let (exit_send, exit_recv) = std::sync::mpsc::channel();
+	std::thread::spawn(move || {
+		let _ = $exit.wait();
+		let _ = exit_send.send(());
+	});
+	info!("Exporting state of #{} ({})", header.number(), header.hash());
+	$output.write_all(&header.encode())?;
+	let count = $crate::chain_ops::write_state(&state, &mut $output, &exit_recv)?;
+	info!("Exported {} storage entries", count);
+	Ok(())
+}}
+}
+
+/// Import a state snapshot into a database that only contains the genesis block. The block of
+/// the snapshot becomes the best and finalized block, without its ancestry.
+#[macro_export]
+macro_rules! import_state {
+($block:ty, $client:ident, $exit:ident, $input:ident) => {{
+	use client::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+
+	let (exit_send, exit_recv) = std::sync::mpsc::channel();
+	std::thread::spawn(move || {
+		let _ = $exit.wait();
+		let _ = exit_send.send(());
+	});
+
+	let mut io_reader_input = IoReader($input);
+	let header = <<$block as BlockT>::Header as Decode>::decode(&mut io_reader_input)
+		.map_err(|e| format!("Error reading file: {}", e))?;
+	let (number, hash) = (*header.number(), header.hash());
+	info!("Importing state of #{} ({})", number, hash);
+
+	let backend = $client.backend();
+	let mut op = backend.begin_operation()?;
+	let root = $crate::chain_ops::read_state(&mut io_reader_input, &mut op, &exit_recv)?;
+	if root != *header.state_root() {
+		return Err(format!(
+			"Invalid state root: expected {}, got {}",
+			header.state_root(),
+			root,
+		).into());
+	}
+	op.set_block_data(header, None, None, NewBlockState::Final)?;
+	backend.commit_operation(op)?;
+	info!("Imported state of #{} ({})", number, hash);
+	Ok(())
+}}
+}
+
 /// Revert the chain some number of blocks.
 #[macro_export]
 macro_rules! revert_chain {
@@ -229,3 +424,52 @@ pub fn build_spec<G, E>(spec: ChainSpec<G, E>, raw: bool) -> error::Result<Strin
 {
 	Ok(spec.to_json(raw)?)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use client::backend::{Backend as _, NewBlockState};
+	use sr_primitives::generic::BlockId;
+	use sr_primitives::traits::Header as _;
+	use substrate_test_runtime_client::{prelude::*, runtime::Header};
+
+	#[test]
+	fn state_snapshot_roundtrips() {
+		let storage_key = b":child_storage:default:snapshot";
+		let client = TestClientBuilder::new()
+			.add_child_storage(&storage_key[..], vec![1], vec![2])
+			.add_child_storage(&storage_key[..], vec![3], vec![4])
+			.add_child_storage(&storage_key[..], vec![5], vec![6])
+			.build();
+		let genesis = client.header(&BlockId::Number(0)).unwrap().unwrap();
+		let state = client.state_at(&BlockId::Number(0)).unwrap();
+		let (_exit_send, exit) = std::sync::mpsc::channel();
+
+		let mut snapshot = Vec::new();
+		let count = write_state(&state, &mut snapshot, &exit).unwrap();
+		assert!(count > 3);
+
+		// the state is imported as the one of a block without ancestry in a new database
+		let other = TestClientBuilder::new().build();
+		let backend = other.backend();
+		let mut op = backend.begin_operation().unwrap();
+		let root = read_state_in_batches(&mut &snapshot[..], &mut op, &exit, 2).unwrap();
+		assert_eq!(root, *genesis.state_root());
+
+		let header = Header {
+			number: 10,
+			parent_hash: Default::default(),
+			state_root: root,
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		op.set_block_data(header, None, None, NewBlockState::Final).unwrap();
+		backend.commit_operation(op).unwrap();
+
+		let imported = other.state_at(&BlockId::Number(10)).unwrap();
+		let mut exported = Vec::new();
+		write_state(&imported, &mut exported, &exit).unwrap();
+		assert_eq!(exported, snapshot);
+		assert_eq!(imported.child_storage(&storage_key[..], &[3]).unwrap(), Some(vec![4]));
+	}
+}
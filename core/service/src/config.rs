@@ -17,7 +17,7 @@
 //! Service configuration.
 
 pub use client::ExecutionStrategies;
-pub use client_db::PruningMode;
+pub use client_db::{PruningMode, DatabaseSettingsSrc as DatabaseConfig};
 pub use network::config::{ExtTransport, NetworkConfiguration, Roles};
 pub use substrate_executor::WasmExecutionMethod;
 
@@ -28,6 +28,35 @@ use primitives::crypto::Protected;
 use target_info::Target;
 use tel::TelemetryEndpoints;
 
+/// Which RPC methods are exposed by the HTTP & WS servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcMethods {
+	/// Expose unsafe methods only on servers that listen on a loopback interface.
+	Auto,
+	/// Only expose safe methods.
+	Safe,
+	/// Expose every method.
+	Unsafe,
+}
+
+impl Default for RpcMethods {
+	fn default() -> Self {
+		RpcMethods::Auto
+	}
+}
+
+impl RpcMethods {
+	/// Whether unsafe methods should be denied on a server listening on `address`.
+	pub fn deny_unsafe(&self, address: &SocketAddr) -> rpc::DenyUnsafe {
+		match self {
+			RpcMethods::Safe => rpc::DenyUnsafe::Yes,
+			RpcMethods::Unsafe => rpc::DenyUnsafe::No,
+			RpcMethods::Auto if address.ip().is_loopback() => rpc::DenyUnsafe::No,
+			RpcMethods::Auto => rpc::DenyUnsafe::Yes,
+		}
+	}
+}
+
 /// Service configuration.
 #[derive(Clone)]
 pub struct Configuration<C, G, E = NoExtension> {
@@ -45,16 +74,16 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub network: NetworkConfiguration,
 	/// Path to key files.
 	pub keystore_path: PathBuf,
-	/// Path to the database.
-	pub database_path: PathBuf,
-	/// Cache Size for internal database in MiB
-	pub database_cache_size: Option<u32>,
+	/// Configuration of the database.
+	pub database: DatabaseConfig,
 	/// Size of internal state cache in Bytes
 	pub state_cache_size: usize,
 	/// Size in percent of cache size dedicated to child tries
 	pub state_cache_child_ratio: Option<usize>,
 	/// Pruning settings.
 	pub pruning: PruningMode,
+	/// Number of finalized blocks to keep the bodies of. `None` keeps all bodies.
+	pub keep_block_bodies: Option<u32>,
 	/// Chain configuration.
 	pub chain_spec: ChainSpec<G, E>,
 	/// Custom configuration.
@@ -65,6 +94,8 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub wasm_method: WasmExecutionMethod,
 	/// Execution strategies.
 	pub execution_strategies: ExecutionStrategies,
+	/// Directory with Wasm runtimes that override the on-chain runtimes of the same version.
+	pub wasm_runtime_overrides: Option<PathBuf>,
 	/// RPC over HTTP binding address. `None` if disabled.
 	pub rpc_http: Option<SocketAddr>,
 	/// RPC over Websockets binding address. `None` if disabled.
@@ -73,6 +104,8 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub rpc_ws_max_connections: Option<usize>,
 	/// CORS settings for HTTP & WS servers. `None` if all origins are allowed.
 	pub rpc_cors: Option<Vec<String>>,
+	/// Which RPC methods the HTTP & WS servers expose.
+	pub rpc_methods: RpcMethods,
 	/// Telemetry service URL. `None` if disabled.
 	pub telemetry_endpoints: Option<TelemetryEndpoints>,
 	/// External WASM transport for the telemetry. If `Some`, when connection to a telemetry
@@ -80,6 +113,8 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub telemetry_external_transport: Option<ExtTransport>,
 	/// The default number of 64KB pages to allocate for Wasm execution
 	pub default_heap_pages: Option<u64>,
+	/// The maximum number of runtimes with different code to keep cached. `None` if default.
+	pub max_runtime_instances: Option<usize>,
 	/// Should offchain workers be executed.
 	pub offchain_worker: bool,
 	/// Enable authoring even when offline.
@@ -113,21 +148,27 @@ impl<C, G, E> Configuration<C, G, E> where
 			transaction_pool: Default::default(),
 			network: Default::default(),
 			keystore_path: Default::default(),
-			database_path: Default::default(),
-			database_cache_size: Default::default(),
+			database: DatabaseConfig::Path {
+				path: Default::default(),
+				cache_size: Default::default(),
+			},
 			state_cache_size: Default::default(),
 			state_cache_child_ratio: Default::default(),
 			custom: Default::default(),
 			pruning: PruningMode::default(),
+			keep_block_bodies: None,
 			wasm_method: WasmExecutionMethod::Interpreted,
 			execution_strategies: Default::default(),
+			wasm_runtime_overrides: None,
 			rpc_http: None,
 			rpc_ws: None,
 			rpc_ws_max_connections: None,
 			rpc_cors: Some(vec![]),
+			rpc_methods: Default::default(),
 			telemetry_endpoints: None,
 			telemetry_external_transport: None,
 			default_heap_pages: None,
+			max_runtime_instances: None,
 			offchain_worker: Default::default(),
 			force_authoring: false,
 			disable_grandpa: false,
@@ -52,7 +52,7 @@ use sr_primitives::traits::NumberFor;
 
 pub use self::error::Error;
 pub use self::builder::{ServiceBuilder, ServiceBuilderExport, ServiceBuilderImport, ServiceBuilderRevert};
-pub use config::{Configuration, Roles, PruningMode};
+pub use config::{Configuration, Roles, PruningMode, DatabaseConfig, RpcMethods};
 pub use chain_spec::{ChainSpec, Properties, RuntimeGenesis, Extension as ChainSpecExtension};
 pub use transaction_pool::txpool::{
 	self, Pool as TransactionPool, Options as TransactionPoolOptions, ChainApi, IntoPoolError
@@ -153,6 +153,7 @@ macro_rules! new_impl {
 			import_queue,
 			finality_proof_request_builder,
 			finality_proof_provider,
+			warp_sync_verifier,
 			network_protocol,
 			transaction_pool,
 			rpc_extensions,
@@ -195,10 +196,12 @@ macro_rules! new_impl {
 
 		let network_params = network::config::Params {
 			roles: $config.roles,
+			keep_block_bodies: $config.keep_block_bodies,
 			network_config: $config.network.clone(),
 			chain: client.clone(),
 			finality_proof_provider,
 			finality_proof_request_builder,
+			warp_sync_verifier,
 			on_demand,
 			transaction_pool: transaction_pool_adapter.clone() as _,
 			import_queue,
@@ -345,7 +348,7 @@ macro_rules! new_impl {
 
 		// RPC
 		let (system_rpc_tx, system_rpc_rx) = futures03::channel::mpsc::unbounded();
-		let gen_handler = || {
+		let gen_handler = |deny_unsafe: rpc::DenyUnsafe| {
 			let system_info = rpc::system::SystemInfo {
 				chain_name: $config.chain_spec.name().into(),
 				impl_name: $config.impl_name.into(),
@@ -361,9 +364,10 @@ macro_rules! new_impl {
 				transaction_pool.clone(),
 				rpc_extensions.clone(),
 				keystore.clone(),
+				deny_unsafe,
 			)
 		};
-		let rpc_handlers = gen_handler();
+		let rpc_handlers = gen_handler(rpc::DenyUnsafe::No);
 		let rpc = start_rpc_servers(&$config, gen_handler)?;
 
 
@@ -762,9 +766,18 @@ fn build_network_future<
 		}
 
 		// Main network polling.
-		while let Ok(Async::Ready(Some(Event::Dht(event)))) = network.poll().map_err(|err| {
-			warn!(target: "service", "Error in network: {:?}", err);
-		}) {
+		loop {
+			let event = match network.poll() {
+				Ok(Async::Ready(Some(Event::Dht(event)))) => event,
+				// Other events are handled through `NetworkService::event_stream`.
+				Ok(Async::Ready(Some(_))) => continue,
+				Ok(Async::Ready(None)) | Ok(Async::NotReady) => break,
+				Err(err) => {
+					warn!(target: "service", "Error in network: {:?}", err);
+					break
+				}
+			};
+
 			// Given that core/authority-discovery is the only upper stack consumer of Dht events at the moment, all Dht
 			// events are being passed on to the authority-discovery module. In the future there might be multiple
 			// consumers of these events. In that case this would need to be refactored to properly dispatch the events,
@@ -823,7 +836,7 @@ impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Drop for
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(not(target_os = "unknown"))]
-fn start_rpc_servers<C, G, E, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadata>>(
+fn start_rpc_servers<C, G, E, H: FnMut(rpc::DenyUnsafe) -> rpc_servers::RpcHandler<rpc::Metadata>>(
 	config: &Configuration<C, G, E>,
 	mut gen_handler: H
 ) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> {
@@ -848,7 +861,11 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadat
 	Ok(Box::new((
 		maybe_start_server(
 			config.rpc_http,
-			|address| rpc_servers::start_http(address, config.rpc_cors.as_ref(), gen_handler()),
+			|address| rpc_servers::start_http(
+				address,
+				config.rpc_cors.as_ref(),
+				gen_handler(config.rpc_methods.deny_unsafe(address)),
+			),
 		)?,
 		maybe_start_server(
 			config.rpc_ws,
@@ -856,7 +873,7 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadat
 				address,
 				config.rpc_ws_max_connections,
 				config.rpc_cors.as_ref(),
-				gen_handler(),
+				gen_handler(config.rpc_methods.deny_unsafe(address)),
 			),
 		)?.map(Mutex::new),
 	)))
@@ -864,7 +881,7 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadat
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(target_os = "unknown")]
-fn start_rpc_servers<C, G, E, H: FnMut() -> components::RpcHandler>(
+fn start_rpc_servers<C, G, E, H: FnMut(rpc::DenyUnsafe) -> components::RpcHandler>(
 	_: &Configuration<C, G, E>,
 	_: H
 ) -> Result<Box<std::any::Any + Send + Sync>, error::Error> {
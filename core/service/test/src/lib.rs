@@ -30,10 +30,14 @@ use service::{
 	ChainSpec,
 	Configuration,
 	Roles,
+	DatabaseConfig,
 	Error,
 };
 use network::{multiaddr, Multiaddr};
-use network::config::{NetworkConfiguration, TransportConfig, NodeKeyConfig, Secret, NonReservedPeerMode};
+use network::config::{
+	NetworkConfiguration, TransportConfig, NodeKeyConfig, Secret, NonReservedPeerMode, SentryMode,
+	SyncMode,
+};
 use sr_primitives::{generic::BlockId, traits::Block as BlockT};
 
 /// Maximum duration of single wait call.
@@ -153,6 +157,8 @@ fn node_config<G, E: Clone> (
 		out_peers: 450,
 		reserved_nodes: vec![],
 		non_reserved_mode: NonReservedPeerMode::Accept,
+		sentry_mode: SentryMode::Disabled,
+		sync_mode: SyncMode::Full,
 		client_version: "network/test/0.1".to_owned(),
 		node_name: "unknown".to_owned(),
 		transport: TransportConfig::Normal {
@@ -170,11 +176,14 @@ fn node_config<G, E: Clone> (
 		network: network_config,
 		keystore_path: root.join("key"),
 		keystore_password: None,
-		database_path: root.join("db"),
-		database_cache_size: None,
+		database: DatabaseConfig::Path {
+			path: root.join("db"),
+			cache_size: None,
+		},
 		state_cache_size: 16777216,
 		state_cache_child_ratio: None,
 		pruning: Default::default(),
+		keep_block_bodies: None,
 		chain_spec: (*spec).clone(),
 		custom: Default::default(),
 		name: format!("Node {}", index),
@@ -184,9 +193,12 @@ fn node_config<G, E: Clone> (
 		rpc_ws: None,
 		rpc_ws_max_connections: None,
 		rpc_cors: None,
+		rpc_methods: Default::default(),
 		telemetry_endpoints: None,
 		telemetry_external_transport: None,
 		default_heap_pages: None,
+		max_runtime_instances: None,
+		wasm_runtime_overrides: None,
 		offchain_worker: false,
 		force_authoring: false,
 		disable_grandpa: false,
@@ -111,6 +111,8 @@ mod utils;
 ///     impl_version: 0,
 ///     // Here we are exposing the runtime api versions.
 ///     apis: RUNTIME_API_VERSIONS,
+///     // Here we are declaring the runtime interfaces the runtime uses.
+///     host_interfaces: version::create_host_interfaces_vec!(),
 /// };
 ///
 /// # fn main() {}
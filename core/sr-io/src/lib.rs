@@ -28,6 +28,8 @@
 
 use rstd::vec::Vec;
 
+use runtime_interface::runtime_interface;
+
 use primitives::{
 	crypto::KeyTypeId, ed25519, sr25519, H256,
 	offchain::{
@@ -115,7 +117,7 @@ export_api! {
 		/// Clear the storage of a key.
 		fn clear_child_storage(storage_key: &[u8], key: &[u8]);
 
-		/// Clear an entire child storage.
+		/// Clear an entire child storage, in constant time if the child trie has a unique id.
 		fn kill_child_storage(storage_key: &[u8]);
 
 		/// Check whether a given `key` exists in storage.
@@ -124,6 +126,12 @@ export_api! {
 		/// Check whether a given `key` exists in storage.
 		fn exists_child_storage(storage_key: &[u8], key: &[u8]) -> bool;
 
+		/// Get the next key in storage after the given one in lexicographic order.
+		fn next_storage_key(key: &[u8]) -> Option<Vec<u8>>;
+
+		/// Get the next key in child storage after the given one in lexicographic order.
+		fn next_child_storage_key(storage_key: &[u8], key: &[u8]) -> Option<Vec<u8>>;
+
 		/// Clear the storage entries with a key that starts with the given prefix.
 		fn clear_prefix(prefix: &[u8]);
 
@@ -139,6 +147,22 @@ export_api! {
 		/// "Commit" all existing operations and get the resultant storage change root.
 		fn storage_changes_root(parent_hash: [u8; 32]) -> Option<[u8; 32]>;
 
+		/// Start a new nested storage transaction.
+		///
+		/// All storage changes made from now on can be reverted by
+		/// `storage_rollback_transaction` or kept by `storage_commit_transaction`.
+		fn storage_start_transaction();
+
+		/// Revert all storage changes made since the innermost open transaction was started.
+		///
+		/// Panics if there is no open transaction.
+		fn storage_rollback_transaction();
+
+		/// Keep all storage changes made since the innermost open transaction was started.
+		///
+		/// Panics if there is no open transaction.
+		fn storage_commit_transaction();
+
 		/// A trie root formed from the iterated items.
 		fn blake2_256_trie_root(input: Vec<(Vec<u8>, Vec<u8>)>) -> H256;
 
@@ -210,28 +234,42 @@ export_api! {
 	}
 }
 
-export_api! {
-	pub(crate) trait HashingApi {
-		/// Conduct a 256-bit Keccak hash.
-		fn keccak_256(data: &[u8]) -> [u8; 32] ;
+/// Interface that provides functions for hashing with different algorithms.
+#[runtime_interface]
+pub trait Hashing {
+	/// Conduct a 256-bit Keccak hash.
+	fn keccak_256(data: &[u8]) -> [u8; 32] {
+		tiny_keccak::keccak256(data)
+	}
 
-		/// Conduct a 128-bit Blake2 hash.
-		fn blake2_128(data: &[u8]) -> [u8; 16];
+	/// Conduct a 128-bit Blake2 hash.
+	fn blake2_128(data: &[u8]) -> [u8; 16] {
+		primitives::blake2_128(data)
+	}
 
-		/// Conduct a 256-bit Blake2 hash.
-		fn blake2_256(data: &[u8]) -> [u8; 32];
+	/// Conduct a 256-bit Blake2 hash.
+	fn blake2_256(data: &[u8]) -> [u8; 32] {
+		primitives::blake2_256(data)
+	}
 
-		/// Conduct four XX hashes to give a 256-bit result.
-		fn twox_256(data: &[u8]) -> [u8; 32];
+	/// Conduct four XX hashes to give a 256-bit result.
+	fn twox_256(data: &[u8]) -> [u8; 32] {
+		primitives::twox_256(data)
+	}
 
-		/// Conduct two XX hashes to give a 128-bit result.
-		fn twox_128(data: &[u8]) -> [u8; 16];
+	/// Conduct two XX hashes to give a 128-bit result.
+	fn twox_128(data: &[u8]) -> [u8; 16] {
+		primitives::twox_128(data)
+	}
 
-		/// Conduct two XX hashes to give a 64-bit result.
-		fn twox_64(data: &[u8]) -> [u8; 8];
+	/// Conduct two XX hashes to give a 64-bit result.
+	fn twox_64(data: &[u8]) -> [u8; 8] {
+		primitives::twox_64(data)
 	}
 }
 
+pub use hashing::{keccak_256, blake2_128, blake2_256, twox_256, twox_128, twox_64};
+
 export_api! {
 	pub(crate) trait OffchainApi {
 		/// Returns if the local node is a potential validator.
@@ -355,7 +393,7 @@ export_api! {
 /// API trait that should cover all other APIs.
 ///
 /// Implement this to make sure you implement all APIs.
-trait Api: StorageApi + OtherApi + CryptoApi + HashingApi + OffchainApi {}
+trait Api: StorageApi + OtherApi + CryptoApi + OffchainApi {}
 
 mod imp {
 	use super::*;
@@ -15,7 +15,7 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use primitives::{
-	blake2_128, blake2_256, twox_128, twox_256, twox_64, ed25519, Blake2Hasher, sr25519, Pair, H256,
+	ed25519, Blake2Hasher, sr25519, Pair, H256,
 	traits::KeystoreExt, storage::ChildStorageKey, hexdisplay::HexDisplay, Hasher,
 	offchain::{self, OffchainExt},
 };
@@ -133,6 +133,19 @@ impl StorageApi for () {
 		}).unwrap_or(false)
 	}
 
+	fn next_storage_key(key: &[u8]) -> Option<Vec<u8>> {
+		with_externalities(|ext| ext.next_storage_key(key))
+			.expect("next_storage_key cannot be called outside of an Externalities-provided environment.")
+	}
+
+	fn next_child_storage_key(storage_key: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+		with_externalities(|ext| {
+			let storage_key = child_storage_key_or_panic(storage_key);
+			ext.next_child_storage_key(storage_key, key)
+		})
+		.expect("next_child_storage_key cannot be called outside of an Externalities-provided environment.")
+	}
+
 	fn clear_prefix(prefix: &[u8]) {
 		with_externalities(|ext| ext.clear_prefix(prefix));
 	}
@@ -163,6 +176,22 @@ impl StorageApi for () {
 		).unwrap_or(Ok(None)).expect("Invalid parent hash passed to storage_changes_root")
 	}
 
+	fn storage_start_transaction() {
+		with_externalities(|ext| ext.storage_start_transaction());
+	}
+
+	fn storage_rollback_transaction() {
+		with_externalities(|ext| ext.storage_rollback_transaction())
+			.unwrap_or(Ok(()))
+			.expect("No open storage transaction to roll back")
+	}
+
+	fn storage_commit_transaction() {
+		with_externalities(|ext| ext.storage_commit_transaction())
+			.unwrap_or(Ok(()))
+			.expect("No open storage transaction to commit")
+	}
+
 	fn blake2_256_trie_root(input: Vec<(Vec<u8>, Vec<u8>)>) -> H256 {
 		Layout::<Blake2Hasher>::trie_root(input)
 	}
@@ -286,32 +315,6 @@ impl CryptoApi for () {
 	}
 }
 
-impl HashingApi for () {
-	fn keccak_256(data: &[u8]) -> [u8; 32] {
-		tiny_keccak::keccak256(data)
-	}
-
-	fn blake2_128(data: &[u8]) -> [u8; 16] {
-		blake2_128(data)
-	}
-
-	fn blake2_256(data: &[u8]) -> [u8; 32] {
-		blake2_256(data)
-	}
-
-	fn twox_256(data: &[u8]) -> [u8; 32] {
-		twox_256(data)
-	}
-
-	fn twox_128(data: &[u8]) -> [u8; 16] {
-		twox_128(data)
-	}
-
-	fn twox_64(data: &[u8]) -> [u8; 8] {
-		twox_64(data)
-	}
-}
-
 fn with_offchain<R>(f: impl FnOnce(&mut dyn offchain::Externalities) -> R, msg: &'static str) -> R {
 	with_externalities(|ext| ext
 		.extension::<OffchainExt>()
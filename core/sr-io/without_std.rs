@@ -190,6 +190,15 @@ pub mod ext {
 		/// - `0` if no value exists to the given key. `written_out` is set to `u32::max_value()`.
 		/// - Otherwise, pointer to the value in memory. `written_out` contains the length of the value.
 		fn ext_get_allocated_storage(key_data: *const u8, key_len: u32, written_out: *mut u32) -> *mut u8;
+		/// Gets the next key in storage after the given key in lexicographic order.
+		///
+		/// The host allocates the memory for storing the key.
+		///
+		/// # Returns
+		///
+		/// - `0` if there is no next key. `written_out` is set to `u32::max_value()`.
+		/// - Otherwise, pointer to the next key in memory. `written_out` contains the length of the key.
+		fn ext_next_storage_key(key_data: *const u8, key_len: u32, written_out: *mut u32) -> *mut u8;
 		/// Gets the value of the given key from storage.
 		///
 		/// The value is written into `value` starting at `value_offset`.
@@ -210,6 +219,12 @@ pub mod ext {
 		) -> u32;
 		/// Gets the trie root of the storage.
 		fn ext_storage_root(result: *mut u8);
+		/// Start a new nested storage transaction.
+		fn ext_storage_start_transaction();
+		/// Revert the storage changes of the innermost open storage transaction and close it.
+		fn ext_storage_rollback_transaction();
+		/// Keep the storage changes of the innermost open storage transaction and close it.
+		fn ext_storage_commit_transaction();
 		/// Get the change trie root of the current storage overlay at a block with given parent.
 		///
 		/// # Returns
@@ -277,6 +292,18 @@ pub mod ext {
 		) -> *mut u8;
 		/// A child storage function.
 		///
+		/// See [`ext_next_storage_key`] for details.
+		///
+		/// A child storage is used e.g. by a contract.
+		fn ext_next_child_storage_key(
+			storage_key_data: *const u8,
+			storage_key_len: u32,
+			key_data: *const u8,
+			key_len: u32,
+			written_out: *mut u32,
+		) -> *mut u8;
+		/// A child storage function.
+		///
 		/// See [`ext_get_storage_into`] for details.
 		///
 		/// A child storage is used e.g. by a contract.
@@ -312,18 +339,6 @@ pub mod ext {
 			lens_len: u32,
 			result: *mut u8
 		);
-		/// BLAKE2_128 hash
-		fn ext_blake2_128(data: *const u8, len: u32, out: *mut u8);
-		/// BLAKE2_256 hash
-		fn ext_blake2_256(data: *const u8, len: u32, out: *mut u8);
-		/// XX64 hash
-		fn ext_twox_64(data: *const u8, len: u32, out: *mut u8);
-		/// XX128 hash
-		fn ext_twox_128(data: *const u8, len: u32, out: *mut u8);
-		/// XX256 hash
-		fn ext_twox_256(data: *const u8, len: u32, out: *mut u8);
-		/// Keccak256 hash
-		fn ext_keccak_256(data: *const u8, len: u32, out: *mut u8);
 
 		/// Returns all `ed25519` public keys for the given key type from the keystore.
 		fn ext_ed25519_public_keys(id: *const u8, result_len: *mut u32) -> *mut u8;
@@ -671,6 +686,28 @@ impl StorageApi for () {
 		}
 	}
 
+	fn next_storage_key(key: &[u8]) -> Option<Vec<u8>> {
+		let mut length: u32 = 0;
+		unsafe {
+			let ptr = ext_next_storage_key.get()(key.as_ptr(), key.len() as u32, &mut length);
+			from_raw_parts(ptr, length)
+		}
+	}
+
+	fn next_child_storage_key(storage_key: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+		let mut length: u32 = 0;
+		unsafe {
+			let ptr = ext_next_child_storage_key.get()(
+				storage_key.as_ptr(),
+				storage_key.len() as u32,
+				key.as_ptr(),
+				key.len() as u32,
+				&mut length
+			);
+			from_raw_parts(ptr, length)
+		}
+	}
+
 	fn clear_prefix(prefix: &[u8]) {
 		unsafe {
 			ext_clear_prefix.get()(
@@ -731,6 +768,24 @@ impl StorageApi for () {
 		}
 	}
 
+	fn storage_start_transaction() {
+		unsafe {
+			ext_storage_start_transaction.get()();
+		}
+	}
+
+	fn storage_rollback_transaction() {
+		unsafe {
+			ext_storage_rollback_transaction.get()();
+		}
+	}
+
+	fn storage_commit_transaction() {
+		unsafe {
+			ext_storage_commit_transaction.get()();
+		}
+	}
+
 
 	fn blake2_256_trie_root(_input: Vec<(Vec<u8>, Vec<u8>)>) -> H256 {
 		unimplemented!()
@@ -782,56 +837,6 @@ impl OtherApi for () {
 	}
 }
 
-impl HashingApi for () {
-	fn keccak_256(data: &[u8]) -> [u8; 32] {
-		let mut result: [u8; 32] = Default::default();
-		unsafe {
-			ext_keccak_256.get()(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
-	}
-
-	fn blake2_128(data: &[u8]) -> [u8; 16] {
-		let mut result: [u8; 16] = Default::default();
-		unsafe {
-			ext_blake2_128.get()(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
-	}
-
-	fn blake2_256(data: &[u8]) -> [u8; 32] {
-		let mut result: [u8; 32] = Default::default();
-		unsafe {
-			ext_blake2_256.get()(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
-	}
-
-	fn twox_256(data: &[u8]) -> [u8; 32] {
-		let mut result: [u8; 32] = Default::default();
-		unsafe {
-			ext_twox_256.get()(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
-	}
-
-	fn twox_128(data: &[u8]) -> [u8; 16] {
-		let mut result: [u8; 16] = Default::default();
-		unsafe {
-			ext_twox_128.get()(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
-	}
-
-	fn twox_64(data: &[u8]) -> [u8; 8] {
-		let mut result: [u8; 8] = Default::default();
-		unsafe {
-			ext_twox_64.get()(data.as_ptr(), data.len() as u32, result.as_mut_ptr());
-		}
-		result
-	}
-}
-
 impl CryptoApi for () {
 	fn ed25519_public_keys(id: KeyTypeId) -> Vec<ed25519::Public> {
 		let mut res_len = 0u32;
@@ -20,6 +20,8 @@ pub extern crate alloc;
 extern "C" {
 	fn ext_malloc(size: u32) -> *mut u8;
 	fn ext_free(ptr: *mut u8);
+	#[cfg(feature = "host_realloc")]
+	fn ext_realloc(ptr: *mut u8, size: u32) -> *mut u8;
 }
 
 /// Wasm allocator
@@ -42,6 +44,13 @@ mod __impl {
 		unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
 			super::ext_free(ptr as *mut u8)
 		}
+
+		// Without `host_realloc`, the default implementation allocates, copies and frees, so that
+		// the runtime doesn't import `ext_realloc` and still runs on hosts that don't provide it.
+		#[cfg(feature = "host_realloc")]
+		unsafe fn realloc(&self, ptr: *mut u8, _layout: Layout, new_size: usize) -> *mut u8 {
+			super::ext_realloc(ptr, new_size as u32)
+		}
 	}
 }
 
@@ -29,7 +29,7 @@ use sr_primitives::traits::RuntimeApiInfo;
 
 use codec::Encode;
 #[cfg(feature = "std")]
-use codec::Decode;
+use codec::{Decode, Input};
 use sr_primitives::RuntimeString;
 pub use sr_primitives::create_runtime_str;
 
@@ -57,13 +57,56 @@ macro_rules! create_apis_vec {
 	( $y:expr ) => { & $y }
 }
 
+/// A vector of pairs of runtime interface name and version. For `"std"` builds, this is a `Cow`.
+#[cfg(feature = "std")]
+pub type HostInterfacesVec = ::std::borrow::Cow<'static, [(RuntimeString, u32)]>;
+/// A vector of pairs of runtime interface name and version. For `"no-std"` builds, this is just a
+/// reference.
+#[cfg(not(feature = "std"))]
+pub type HostInterfacesVec = &'static [(RuntimeString, u32)];
+
+/// Create a vector of host interface declarations from the modules generated by
+/// `#[runtime_interface]`.
+///
+/// ```ignore
+/// host_interfaces: create_host_interfaces_vec!(my_interface, other_crate::other_interface),
+/// ```
+#[macro_export]
+#[cfg(feature = "std")]
+macro_rules! create_host_interfaces_vec {
+	( $( $( $interface:ident )::+ ),* $(,)? ) => {
+		::std::borrow::Cow::Borrowed(&[
+			$(
+				(
+					$crate::create_runtime_str!($( $interface )::+ ::INTERFACE_NAME),
+					$( $interface )::+ ::INTERFACE_VERSION,
+				)
+			),*
+		])
+	}
+}
+#[macro_export]
+#[cfg(not(feature = "std"))]
+macro_rules! create_host_interfaces_vec {
+	( $( $( $interface:ident )::+ ),* $(,)? ) => {
+		&[
+			$(
+				(
+					$crate::create_runtime_str!($( $interface )::+ ::INTERFACE_NAME),
+					$( $interface )::+ ::INTERFACE_VERSION,
+				)
+			),*
+		]
+	}
+}
+
 /// Runtime version.
 /// This should not be thought of as classic Semver (major/minor/tiny).
 /// This triplet have different semantics and mis-interpretation could cause problems.
 /// In particular: bug fixes should result in an increment of `spec_version` and possibly `authoring_version`,
 /// absolutely not `impl_version` since they change the semantics of the runtime.
 #[derive(Clone, PartialEq, Eq, Encode, Default)]
-#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize, Decode))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 #[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
 pub struct RuntimeVersion {
 	/// Identifies the different Substrate runtimes. There'll be at least polkadot and node.
@@ -104,6 +147,41 @@ pub struct RuntimeVersion {
 		)
 	)]
 	pub apis: ApisVec,
+
+	/// List of the runtime interfaces the runtime was compiled against, along with their
+	/// versions.
+	///
+	/// The executor refuses to run a runtime that needs a newer version of an interface than it
+	/// provides. Runtimes built before this field was introduced decode it as empty.
+	#[cfg_attr(feature = "std", serde(default))]
+	pub host_interfaces: HostInterfacesVec,
+}
+
+#[cfg(feature = "std")]
+impl Decode for RuntimeVersion {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		let spec_name = Decode::decode(input)?;
+		let impl_name = Decode::decode(input)?;
+		let authoring_version = Decode::decode(input)?;
+		let spec_version = Decode::decode(input)?;
+		let impl_version = Decode::decode(input)?;
+		let apis = Vec::<(ApiId, u32)>::decode(input)?.into();
+		// `host_interfaces` is missing from the versions of old runtimes.
+		let host_interfaces = match input.remaining_len()? {
+			Some(0) => Default::default(),
+			_ => Vec::<(RuntimeString, u32)>::decode(input)?.into(),
+		};
+
+		Ok(RuntimeVersion {
+			spec_name,
+			impl_name,
+			authoring_version,
+			spec_version,
+			impl_version,
+			apis,
+			host_interfaces,
+		})
+	}
 }
 
 #[cfg(feature = "std")]
@@ -144,6 +222,19 @@ impl RuntimeVersion {
 			s == &A::ID && pred(*v)
 		})
 	}
+
+	/// Returns the runtime interfaces, with their versions, that this runtime needs but that are
+	/// not provided, or only in an older version, by the given interfaces.
+	pub fn unsupported_host_interfaces<'a>(
+		&'a self,
+		provided: &'a [(&str, u32)],
+	) -> impl Iterator<Item = (&'a str, u32)> + 'a {
+		self.host_interfaces.iter()
+			.filter(move |(name, version)| {
+				!provided.iter().any(|(n, v)| *n == &name[..] && v >= version)
+			})
+			.map(|(name, version)| (&name[..], *version))
+	}
 }
 
 #[cfg(feature = "std")]
@@ -233,3 +324,25 @@ mod apis_serialize {
 		Ok(arr)
 	}
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn host_interfaces_are_optional_when_decoding() {
+		let version = RuntimeVersion {
+			spec_name: "test".into(),
+			host_interfaces: vec![("storage".into(), 2)].into(),
+			..Default::default()
+		};
+		let encoded = version.encode();
+		assert_eq!(RuntimeVersion::decode(&mut &encoded[..]).unwrap(), version);
+
+		// The version of a runtime built before `host_interfaces` was introduced.
+		let old = RuntimeVersion { host_interfaces: Default::default(), ..version };
+		let mut encoded = old.encode();
+		assert_eq!(encoded.pop(), Some(0));
+		assert_eq!(RuntimeVersion::decode(&mut &encoded[..]).unwrap(), old);
+	}
+}
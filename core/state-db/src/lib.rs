@@ -28,6 +28,11 @@
 //! # Pruning.
 //! See `RefWindow` for pruning algorithm details. `StateDb` prunes on each canonicalization until pruning
 //! constraints are satisfied.
+//!
+//! The pruning mode is stored in the database when it is first opened. Opening it later with an
+//! incompatible mode fails, since e.g. an archive database can't be pruned and a pruned database
+//! can't serve as an archive. The modes that can open a database created before the mode was
+//! stored are inferred from its canonicalization and pruning metadata.
 
 mod noncanonical;
 mod pruning;
@@ -35,10 +40,10 @@ mod pruning;
 
 use std::fmt;
 use parking_lot::RwLock;
-use codec::Codec;
+use codec::{Codec, Encode, Decode};
 use std::collections::{HashMap, hash_map::Entry};
-use noncanonical::NonCanonicalOverlay;
-use pruning::RefWindow;
+use noncanonical::{NonCanonicalOverlay, LAST_CANONICAL};
+use pruning::{RefWindow, LAST_PRUNED};
 use log::trace;
 
 /// Database value type.
@@ -77,6 +82,13 @@ pub enum Error<E: fmt::Debug> {
 	InvalidBlockNumber,
 	/// Trying to insert block with unknown parent.
 	InvalidParent,
+	/// The database was created with a pruning mode that is incompatible with the requested one.
+	IncompatiblePruningModes {
+		/// Pruning mode stored in the database.
+		stored: String,
+		/// Requested pruning mode.
+		requested: String,
+	},
 }
 
 /// Pinning error type.
@@ -99,6 +111,12 @@ impl<E: fmt::Debug> fmt::Debug for Error<E> {
 			Error::InvalidBlock => write!(f, "Trying to canonicalize invalid block"),
 			Error::InvalidBlockNumber => write!(f, "Trying to insert block with invalid number"),
 			Error::InvalidParent => write!(f, "Trying to insert block with unknown parent"),
+			Error::IncompatiblePruningModes { stored, requested } => write!(
+				f,
+				"Incompatible pruning modes [stored: {}; requested: {}]",
+				stored,
+				requested,
+			),
 		}
 	}
 }
@@ -110,6 +128,9 @@ pub struct ChangeSet<H: Hash> {
 	pub inserted: Vec<(H, DBValue)>,
 	/// Deleted nodes.
 	pub deleted: Vec<H>,
+	/// Deleted key prefixes. Every node with a key that starts with one of these is deleted,
+	/// e.g. all nodes of a removed child trie.
+	pub deleted_prefixes: Vec<Vec<u8>>,
 }
 
 
@@ -122,13 +143,19 @@ pub struct CommitSet<H: Hash> {
 	pub meta: ChangeSet<Vec<u8>>,
 }
 
-/// Pruning constraints. If none are specified pruning is
+/// Pruning constraints. Blocks are pruned while any of the specified constraints is exceeded.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct Constraints {
-	/// Maximum blocks. Defaults to 0 when unspecified, effectively keeping only non-canonical states.
+	/// Maximum blocks. Defaults to 0 when no other constraint is specified, effectively keeping
+	/// only non-canonical states.
 	pub max_blocks: Option<u32>,
 	/// Maximum memory in the pruning overlay.
 	pub max_mem: Option<usize>,
+	/// Maximum time in seconds between the oldest and the latest kept block.
+	pub max_age: Option<u64>,
+	/// Maximum size in bytes of the nodes kept on disk for the state of the kept blocks, i.e. of
+	/// the nodes that pruning them would remove.
+	pub max_size: Option<u64>,
 }
 
 /// Pruning mode.
@@ -147,7 +174,24 @@ impl PruningMode {
 	pub fn keep_blocks(n: u32) -> PruningMode {
 		PruningMode::Constrained(Constraints {
 			max_blocks: Some(n),
-			max_mem: None,
+			..Default::default()
+		})
+	}
+
+	/// Create a mode that keeps the state of the blocks canonicalized within given number of
+	/// seconds.
+	pub fn keep_for(seconds: u64) -> PruningMode {
+		PruningMode::Constrained(Constraints {
+			max_age: Some(seconds),
+			..Default::default()
+		})
+	}
+
+	/// Create a mode that keeps the state of as many blocks as fit in given number of bytes.
+	pub fn keep_state_size(bytes: u64) -> PruningMode {
+		PruningMode::Constrained(Constraints {
+			max_size: Some(bytes),
+			..Default::default()
 		})
 	}
 
@@ -159,6 +203,15 @@ impl PruningMode {
 		}
 	}
 
+	/// Identifier of the mode as stored in the database. Modes with the same identifier can
+	/// reopen each other's databases.
+	pub fn id(&self) -> &'static [u8] {
+		match self {
+			PruningMode::ArchiveAll => PRUNING_MODE_ARCHIVE,
+			PruningMode::ArchiveCanonical => PRUNING_MODE_ARCHIVE_CANON,
+			PruningMode::Constrained(_) => PRUNING_MODE_CONSTRAINED,
+		}
+	}
 }
 
 impl Default for PruningMode {
@@ -167,12 +220,50 @@ impl Default for PruningMode {
 	}
 }
 
+const PRUNING_MODE: &[u8] = b"mode";
+const PRUNING_MODE_ARCHIVE: &[u8] = b"archive";
+const PRUNING_MODE_ARCHIVE_CANON: &[u8] = b"archive_canonical";
+const PRUNING_MODE_CONSTRAINED: &[u8] = b"constrained";
+
+/// Modes that can open a database created before the pruning mode was stored, inferred from its
+/// metadata. `None` if the database can be opened with any mode: it is new, or it is an archive
+/// whose states are kept by any mode.
+fn compatible_modes<D: MetaDb>(db: &D) -> Result<Option<&'static [&'static [u8]]>, Error<D::Error>> {
+	if db.get_meta(&to_meta_key(LAST_PRUNED, &())).map_err(|e| Error::Db(e))?.is_some() {
+		return Ok(Some(&[PRUNING_MODE_CONSTRAINED]));
+	}
+	// canonicalized but never pruned: the states of the non-canonical blocks have been discarded.
+	if db.get_meta(&to_meta_key(LAST_CANONICAL, &())).map_err(|e| Error::Db(e))?.is_some() {
+		return Ok(Some(&[PRUNING_MODE_CONSTRAINED, PRUNING_MODE_ARCHIVE_CANON]));
+	}
+	Ok(None)
+}
+
 fn to_meta_key<S: Codec>(suffix: &[u8], data: &S) -> Vec<u8> {
 	let mut buffer = data.encode();
 	buffer.extend(suffix);
 	buffer
 }
 
+/// Encode a journal record followed by the prefixes deleted by its block.
+fn encode_journal_record<R: Encode>(record: &R, deleted_prefixes: &[Vec<u8>]) -> Vec<u8> {
+	let mut buffer = record.encode();
+	deleted_prefixes.encode_to(&mut buffer);
+	buffer
+}
+
+/// Decode a journal record encoded with `encode_journal_record`. Records written before deleted
+/// prefixes were journaled end after the record itself.
+fn decode_journal_record<R: Decode>(input: &mut &[u8]) -> Result<(R, Vec<Vec<u8>>), codec::Error> {
+	let record = R::decode(input)?;
+	let deleted_prefixes = if input.is_empty() {
+		Vec::new()
+	} else {
+		Decode::decode(input)?
+	};
+	Ok((record, deleted_prefixes))
+}
+
 struct StateDbSync<BlockHash: Hash, Key: Hash> {
 	mode: PruningMode,
 	non_canonical: NonCanonicalOverlay<BlockHash, Key>,
@@ -204,6 +295,7 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		match self.mode {
 			PruningMode::ArchiveAll => {
 				changeset.deleted.clear();
+				changeset.deleted_prefixes.clear();
 				// write changes immediately
 				Ok(CommitSet {
 					data: changeset,
@@ -216,7 +308,28 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		}
 	}
 
-	pub fn canonicalize_block<E: fmt::Debug>(&mut self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<E>> {
+	pub fn import_state<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, timestamp: u64, mut changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		changeset.deleted.clear();
+		changeset.deleted_prefixes.clear();
+		let mut commit = CommitSet {
+			data: changeset,
+			meta: Default::default(),
+		};
+		match self.mode {
+			PruningMode::ArchiveAll => {},
+			PruningMode::Constrained(_) | PruningMode::ArchiveCanonical => {
+				self.non_canonical.import_state(hash, number, &mut commit)?;
+				if let Some(ref mut pruning) = self.pruning {
+					pruning.import_state(hash, number, timestamp, &mut commit);
+				}
+			}
+		}
+		Ok(commit)
+	}
+
+	pub fn canonicalize_block<D: NodeDb>(&mut self, hash: &BlockHash, timestamp: u64, db: &D) -> Result<CommitSet<Key>, Error<D::Error>>
+		where Key: AsRef<D::Key>
+	{
 		let mut commit = CommitSet::default();
 		if self.mode == PruningMode::ArchiveAll {
 			return Ok(commit)
@@ -225,12 +338,23 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 			Ok(()) => {
 				if self.mode == PruningMode::ArchiveCanonical {
 					commit.data.deleted.clear();
+					commit.data.deleted_prefixes.clear();
 				}
 			}
 			Err(e) => return Err(e),
 		};
+		let state_size = match self.mode {
+			PruningMode::Constrained(Constraints { max_size: Some(_), .. }) => {
+				let mut state_size = 0;
+				for key in commit.data.deleted.iter() {
+					state_size += self.get(key, db)?.map_or(0, |value| value.len() as u64);
+				}
+				state_size
+			},
+			_ => 0,
+		};
 		if let Some(ref mut pruning) = self.pruning {
-			pruning.note_canonical(&hash, &mut commit);
+			pruning.note_canonical(&hash, timestamp, state_size, &mut commit);
 		}
 		self.prune(&mut commit);
 		Ok(commit)
@@ -255,8 +379,15 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 
 	fn prune(&mut self, commit: &mut CommitSet<Key>) {
 		if let (&mut Some(ref mut pruning), &PruningMode::Constrained(ref constraints)) = (&mut self.pruning, &self.mode) {
+			let max_blocks = match constraints {
+				Constraints { max_blocks: None, max_age: None, max_size: None, .. } => Some(0),
+				_ => constraints.max_blocks,
+			};
 			loop {
-				if pruning.window_size() <= constraints.max_blocks.unwrap_or(0) as u64 {
+				let exceeded = max_blocks.map_or(false, |m| pruning.window_size() > m as u64) ||
+					constraints.max_age.map_or(false, |m| pruning.window_age() > m) ||
+					constraints.max_size.map_or(false, |m| pruning.window_state_size() > m);
+				if !exceeded {
 					break;
 				}
 
@@ -362,11 +493,35 @@ pub struct StateDb<BlockHash: Hash, Key: Hash> {
 }
 
 impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
-	/// Creates a new instance. Does not expect any metadata in the database.
-	pub fn new<D: MetaDb>(mode: PruningMode, db: &D) -> Result<StateDb<BlockHash, Key>, Error<D::Error>> {
-		Ok(StateDb {
+	/// Creates a new instance. Fails if the database was created with an incompatible pruning
+	/// mode. Returns a commit that stores the mode if the database has none yet.
+	pub fn new<D: MetaDb>(mode: PruningMode, db: &D) -> Result<(CommitSet<Key>, StateDb<BlockHash, Key>), Error<D::Error>> {
+		let mut commit = CommitSet::default();
+		let mode_key = to_meta_key(PRUNING_MODE, &());
+		let incompatible = |stored: &[&[u8]]| Error::IncompatiblePruningModes {
+			stored: stored.iter()
+				.map(|id| String::from_utf8_lossy(id).into_owned())
+				.collect::<Vec<_>>()
+				.join(" or "),
+			requested: String::from_utf8_lossy(mode.id()).into_owned(),
+		};
+		match db.get_meta(&mode_key).map_err(|e| Error::Db(e))? {
+			Some(stored) => if stored != mode.id() {
+				return Err(incompatible(&[&stored[..]]));
+			},
+			None => {
+				if let Some(modes) = compatible_modes(db)? {
+					if !modes.contains(&mode.id()) {
+						return Err(incompatible(modes));
+					}
+				}
+				commit.meta.inserted.push((mode_key, mode.id().to_vec()));
+			},
+		}
+		let state_db = StateDb {
 			db: RwLock::new(StateDbSync::new(mode, db)?)
-		})
+		};
+		Ok((commit, state_db))
 	}
 
 	/// Add a new non-canonical block.
@@ -374,9 +529,22 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 		self.db.write().insert_block(hash, number, parent_hash, changeset)
 	}
 
-	/// Finalize a previously inserted block.
-	pub fn canonicalize_block<E: fmt::Debug>(&self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<E>> {
-		self.db.write().canonicalize_block(hash)
+	/// Add a canonical block whose state is imported in full, e.g. from a state snapshot, without
+	/// its ancestry. Fails if there are non-canonical blocks.
+	///
+	/// The pruning window restarts at the imported block; the state of the blocks before it is
+	/// kept in the database.
+	pub fn import_state<E: fmt::Debug>(&self, hash: &BlockHash, number: u64, timestamp: u64, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		self.db.write().import_state(hash, number, timestamp, changeset)
+	}
+
+	/// Finalize a previously inserted block. `timestamp` is the time of the block in seconds
+	/// since the UNIX epoch, used to prune by age. The nodes deleted by the block are read from
+	/// `db` to prune by size.
+	pub fn canonicalize_block<D: NodeDb>(&self, hash: &BlockHash, timestamp: u64, db: &D) -> Result<CommitSet<Key>, Error<D::Error>>
+		where Key: AsRef<D::Key>
+	{
+		self.db.write().canonicalize_block(hash, timestamp, db)
 	}
 
 	/// Prevents pruning of specified block and its descendants.
@@ -428,12 +596,13 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 mod tests {
 	use std::io;
 	use primitives::H256;
-	use crate::{StateDb, PruningMode, Constraints};
+	use crate::{StateDb, PruningMode, Constraints, Error, PRUNING_MODE, to_meta_key};
 	use crate::test::{make_db, make_changeset, TestDb};
 
 	fn make_test_db(settings: PruningMode) -> (TestDb, StateDb<H256, H256>) {
 		let mut db = make_db(&[91, 921, 922, 93, 94]);
-		let state_db = StateDb::new(settings, &db).unwrap();
+		let (commit, state_db) = StateDb::new(settings, &db).unwrap();
+		db.commit(&commit);
 
 		db.commit(
 			&state_db
@@ -476,7 +645,7 @@ mod tests {
 				.unwrap(),
 		);
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(1)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(1), 10, &db).unwrap());
 		state_db.apply_pending();
 		db.commit(
 			&state_db
@@ -489,9 +658,9 @@ mod tests {
 				.unwrap(),
 		);
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(21)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(21), 20, &db).unwrap());
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(3)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(3), 30, &db).unwrap());
 		state_db.apply_pending();
 
 		(db, state_db)
@@ -515,6 +684,8 @@ mod tests {
 		let (db, _) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(0),
 			max_mem: None,
+			max_age: None,
+			max_size: None,
 		}));
 		assert!(db.data_eq(&make_db(&[21, 3, 922, 94])));
 	}
@@ -524,6 +695,8 @@ mod tests {
 		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(1),
 			max_mem: None,
+			max_age: None,
+			max_size: None,
 		}));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
@@ -537,6 +710,8 @@ mod tests {
 		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
 			max_blocks: Some(2),
 			max_mem: None,
+			max_age: None,
+			max_size: None,
 		}));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
@@ -544,4 +719,119 @@ mod tests {
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(22), 2));
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
+
+	#[test]
+	fn prune_by_age() {
+		let (db, sdb) = make_test_db(PruningMode::keep_for(10));
+		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
+		assert!(!sdb.is_pruned(&H256::from_low_u64_be(21), 2));
+		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
+
+		let (db, _) = make_test_db(PruningMode::keep_for(0));
+		assert!(db.data_eq(&make_db(&[21, 3, 922, 93, 94])));
+	}
+
+	#[test]
+	fn prune_by_state_size() {
+		let (db, sdb) = make_test_db(PruningMode::keep_state_size(96));
+		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
+		assert!(!sdb.is_pruned(&H256::from_low_u64_be(21), 2));
+		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
+
+		let (db, _) = make_test_db(PruningMode::keep_state_size(32));
+		assert!(db.data_eq(&make_db(&[21, 3, 922, 93, 94])));
+	}
+
+	#[test]
+	fn prune_by_any_exceeded_constraint() {
+		let (db, _) = make_test_db(PruningMode::Constrained(Constraints {
+			max_blocks: Some(2),
+			max_mem: None,
+			max_age: Some(0),
+			max_size: None,
+		}));
+		assert!(db.data_eq(&make_db(&[21, 3, 922, 93, 94])));
+	}
+
+	#[test]
+	fn pruning_mode_is_stored() {
+		let (db, _) = make_test_db(PruningMode::keep_blocks(2));
+		assert!(StateDb::<H256, H256>::new(PruningMode::keep_for(3600), &db).is_ok());
+		assert!(StateDb::<H256, H256>::new(PruningMode::keep_blocks(10), &db).unwrap().0.meta.inserted.is_empty());
+		match StateDb::<H256, H256>::new(PruningMode::ArchiveAll, &db) {
+			Err(Error::IncompatiblePruningModes { stored, requested }) => {
+				assert_eq!(stored, "constrained");
+				assert_eq!(requested, "archive");
+			},
+			_ => panic!("Archive mode must not open a pruned database"),
+		}
+
+		let (db, _) = make_test_db(PruningMode::ArchiveCanonical);
+		assert!(StateDb::<H256, H256>::new(PruningMode::ArchiveCanonical, &db).is_ok());
+		assert!(StateDb::<H256, H256>::new(PruningMode::default(), &db).is_err());
+	}
+
+	#[test]
+	fn pruning_mode_is_inferred() {
+		let mode_key = to_meta_key(PRUNING_MODE, &());
+		let (mut db, _) = make_test_db(PruningMode::keep_blocks(2));
+		db.meta.remove(&mode_key);
+		match StateDb::<H256, H256>::new(PruningMode::ArchiveCanonical, &db) {
+			Err(Error::IncompatiblePruningModes { stored, .. }) => assert_eq!(stored, "constrained"),
+			_ => panic!("A pruned database must not be opened as an archive"),
+		}
+		let (commit, _) = StateDb::<H256, H256>::new(PruningMode::keep_for(3600), &db).unwrap();
+		assert_eq!(commit.meta.inserted, vec![(mode_key.clone(), b"constrained".to_vec())]);
+
+		let (mut db, _) = make_test_db(PruningMode::ArchiveCanonical);
+		db.meta.remove(&mode_key);
+		assert!(StateDb::<H256, H256>::new(PruningMode::ArchiveAll, &db).is_err());
+		assert!(StateDb::<H256, H256>::new(PruningMode::ArchiveCanonical, &db).is_ok());
+		assert!(StateDb::<H256, H256>::new(PruningMode::keep_blocks(2), &db).is_ok());
+
+		let (mut db, _) = make_test_db(PruningMode::ArchiveAll);
+		db.meta.remove(&mode_key);
+		assert!(StateDb::<H256, H256>::new(PruningMode::keep_blocks(2), &db).is_ok());
+	}
+
+	#[test]
+	fn import_state_restarts_at_imported_block() {
+		let mode = PruningMode::Constrained(Constraints {
+			max_blocks: Some(1),
+			max_mem: None,
+			max_age: None,
+			max_size: None,
+		});
+		let mut db = make_db(&[]);
+		let (commit, state_db) = StateDb::new(mode.clone(), &db).unwrap();
+		db.commit(&commit);
+		db.commit(
+			&state_db
+				.import_state::<io::Error>(&H256::from_low_u64_be(10), 10, 100, make_changeset(&[10], &[]))
+				.unwrap(),
+		);
+		state_db.apply_pending();
+		assert_eq!(state_db.best_canonical(), Some(10));
+		assert!(!state_db.is_pruned(&H256::from_low_u64_be(10), 10));
+
+		db.commit(
+			&state_db
+				.insert_block::<io::Error>(
+					&H256::from_low_u64_be(11),
+					11,
+					&H256::from_low_u64_be(10),
+					make_changeset(&[11], &[10]),
+				)
+				.unwrap(),
+		);
+		state_db.apply_pending();
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(11), 110, &db).unwrap());
+		state_db.apply_pending();
+		assert!(state_db.is_pruned(&H256::from_low_u64_be(10), 10));
+		assert!(db.data_eq(&make_db(&[10, 11])));
+
+		let (_, restored): (_, StateDb<H256, H256>) = StateDb::new(mode, &db).unwrap();
+		assert_eq!(restored.best_canonical(), Some(11));
+		assert!(!restored.is_pruned(&H256::from_low_u64_be(11), 11));
+	}
 }
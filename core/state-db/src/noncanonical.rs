@@ -22,12 +22,15 @@
 
 use std::fmt;
 use std::collections::{HashMap, VecDeque, hash_map::Entry};
-use super::{Error, DBValue, ChangeSet, CommitSet, MetaDb, Hash, to_meta_key};
+use super::{
+	Error, DBValue, ChangeSet, CommitSet, MetaDb, Hash, to_meta_key, encode_journal_record,
+	decode_journal_record,
+};
 use codec::{Encode, Decode};
 use log::trace;
 
 const NON_CANONICAL_JOURNAL: &[u8] = b"noncanonical_journal";
-const LAST_CANONICAL: &[u8] = b"last_canonical";
+pub(crate) const LAST_CANONICAL: &[u8] = b"last_canonical";
 
 /// See module documentation.
 pub struct NonCanonicalOverlay<BlockHash: Hash, Key: Hash> {
@@ -58,6 +61,7 @@ struct BlockOverlay<BlockHash: Hash, Key: Hash> {
 	journal_key: Vec<u8>,
 	inserted: Vec<Key>,
 	deleted: Vec<Key>,
+	deleted_prefixes: Vec<Vec<u8>>,
 }
 
 fn insert_values<Key: Hash>(values: &mut HashMap<Key, (u32, DBValue)>, inserted: Vec<(Key, DBValue)>) {
@@ -143,13 +147,15 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 					let journal_key = to_journal_key(block, index);
 					match db.get_meta(&journal_key).map_err(|e| Error::Db(e))? {
 						Some(record) => {
-							let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+							let (record, deleted_prefixes): (JournalRecord<BlockHash, Key>, _) =
+								decode_journal_record(&mut &record[..])?;
 							let inserted = record.inserted.iter().map(|(k, _)| k.clone()).collect();
 							let overlay = BlockOverlay {
 								hash: record.hash.clone(),
 								journal_key,
 								inserted: inserted,
 								deleted: record.deleted,
+								deleted_prefixes,
 							};
 							insert_values(&mut values, record.inserted);
 							trace!(target: "state-db", "Uncanonicalized journal entry {}.{} ({} inserted, {} deleted)", block, index, overlay.inserted.len(), overlay.deleted.len());
@@ -224,6 +230,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			journal_key: journal_key.clone(),
 			inserted: inserted,
 			deleted: changeset.deleted.clone(),
+			deleted_prefixes: changeset.deleted_prefixes.clone(),
 		};
 		level.push(overlay);
 		self.parents.insert(hash.clone(), parent_hash.clone());
@@ -233,13 +240,33 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			inserted: changeset.inserted,
 			deleted: changeset.deleted,
 		};
-		commit.meta.inserted.push((journal_key, journal_record.encode()));
+		commit.meta.inserted.push((
+			journal_key,
+			encode_journal_record(&journal_record, &changeset.deleted_prefixes),
+		));
 		trace!(target: "state-db", "Inserted uncanonicalized changeset {}.{} ({} inserted, {} deleted)", number, index, journal_record.inserted.len(), journal_record.deleted.len());
 		insert_values(&mut self.values, journal_record.inserted);
 		self.pending_insertions.push(hash.clone());
 		Ok(commit)
 	}
 
+	/// Make a block whose state is imported without its ancestry the last canonicalized block.
+	/// Expects no blocks in the overlay.
+	pub fn import_state<E: fmt::Debug>(
+		&mut self,
+		hash: &BlockHash,
+		number: u64,
+		commit: &mut CommitSet<Key>,
+	) -> Result<(), Error<E>> {
+		if !self.levels.is_empty() || !self.pending_canonicalizations.is_empty() {
+			return Err(Error::InvalidBlock);
+		}
+		let last_canonicalized = (hash.clone(), number);
+		commit.meta.inserted.push((to_meta_key(LAST_CANONICAL, &()), last_canonicalized.encode()));
+		self.last_canonicalized = Some(last_canonicalized);
+		Ok(())
+	}
+
 	fn discard_journals(
 		&self,
 		level_index: usize,
@@ -317,6 +344,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		commit.data.inserted.extend(overlay.inserted.iter()
 			.map(|k| (k.clone(), self.values.get(k).expect("For each key in overlays there's a value in values").1.clone())));
 		commit.data.deleted.extend(overlay.deleted.clone());
+		commit.data.deleted_prefixes.extend(overlay.deleted_prefixes.clone());
 
 		commit.meta.deleted.append(&mut discarded_journals);
 		let canonicalized = (hash.clone(), self.front_block_number() + self.pending_canonicalizations.len() as u64);
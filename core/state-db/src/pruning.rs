@@ -20,14 +20,17 @@
 //! There is also a global index of node key to block number.
 //! If a node is re-inserted into the window it gets removed from
 //! the death list.
+//! Each block also records its time and the size of the nodes in its death list, which stay on
+//! disk until the block is pruned, so that the window can be bounded by age and disk usage as well
+//! as by the number of blocks.
 //! The changes are journaled in the DB.
 
 use std::collections::{HashMap, HashSet, VecDeque};
 use codec::{Encode, Decode};
-use crate::{CommitSet, Error, MetaDb, to_meta_key, Hash};
+use crate::{CommitSet, Error, MetaDb, to_meta_key, Hash, encode_journal_record, decode_journal_record};
 use log::{trace, warn};
 
-const LAST_PRUNED: &[u8] = b"last_pruned";
+pub(crate) const LAST_PRUNED: &[u8] = b"last_pruned";
 const PRUNING_JOURNAL: &[u8] = b"pruning_journal";
 
 /// See module documentation.
@@ -44,6 +47,8 @@ pub struct RefWindow<BlockHash: Hash, Key: Hash> {
 	/// Number of calls of `prune_one` after
 	/// last call `apply_pending` or `revert_pending`
 	pending_prunings: usize,
+	/// Total size of the nodes in the death rows of the window, not counting pending prunings.
+	state_size: u64,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -51,6 +56,11 @@ struct DeathRow<BlockHash: Hash, Key: Hash> {
 	hash: BlockHash,
 	journal_key: Vec<u8>,
 	deleted: HashSet<Key>,
+	deleted_prefixes: Vec<Vec<u8>>,
+	/// Time of the block, in seconds since the UNIX epoch.
+	timestamp: u64,
+	/// Size of the nodes in `deleted` when the block was added to the window.
+	state_size: u64,
 }
 
 #[derive(Encode, Decode)]
@@ -79,6 +89,7 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			pending_number: pending_number,
 			pending_canonicalizations: 0,
 			pending_prunings: 0,
+			state_size: 0,
 		};
 		// read the journal
 		trace!(target: "state-db", "Reading pruning journal. Pending #{}", pending_number);
@@ -86,9 +97,26 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			let journal_key = to_journal_key(block);
 			match db.get_meta(&journal_key).map_err(|e| Error::Db(e))? {
 				Some(record) => {
-					let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+					let input = &mut &record[..];
+					let (record, deleted_prefixes): (JournalRecord<BlockHash, Key>, _) =
+						decode_journal_record(input)?;
+					// Records written before the window could be bounded by age and size have no
+					// block info. They are treated as the oldest possible blocks.
+					let (timestamp, state_size) = if input.is_empty() {
+						(0, 0)
+					} else {
+						Decode::decode(input)?
+					};
 					trace!(target: "state-db", "Pruning journal entry {} ({} inserted, {} deleted)", block, record.inserted.len(), record.deleted.len());
-					pruning.import(&record.hash, journal_key, record.inserted.into_iter(), record.deleted);
+					pruning.import(
+						&record.hash,
+						journal_key,
+						record.inserted.into_iter(),
+						record.deleted,
+						deleted_prefixes,
+						timestamp,
+						state_size,
+					);
 				},
 				None => break,
 			}
@@ -97,7 +125,16 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		Ok(pruning)
 	}
 
-	fn import<I: IntoIterator<Item=Key>>(&mut self, hash: &BlockHash, journal_key: Vec<u8>, inserted: I, deleted: Vec<Key>) {
+	fn import<I: IntoIterator<Item=Key>>(
+		&mut self,
+		hash: &BlockHash,
+		journal_key: Vec<u8>,
+		inserted: I,
+		deleted: Vec<Key>,
+		deleted_prefixes: Vec<Vec<u8>>,
+		timestamp: u64,
+		state_size: u64,
+	) {
 		// remove all re-inserted keys from death rows
 		for k in inserted {
 			if let Some(block) = self.death_index.remove(&k) {
@@ -114,15 +151,33 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			DeathRow {
 				hash: hash.clone(),
 				deleted: deleted.into_iter().collect(),
+				deleted_prefixes,
 				journal_key: journal_key,
+				timestamp,
+				state_size,
 			}
 		);
+		self.state_size += state_size;
 	}
 
 	pub fn window_size(&self) -> u64 {
 		(self.death_rows.len() - self.pending_prunings) as u64
 	}
 
+	/// Time in seconds between the first and the last block in the window.
+	pub fn window_age(&self) -> u64 {
+		match (self.death_rows.get(self.pending_prunings), self.death_rows.back()) {
+			(Some(first), Some(last)) => last.timestamp.saturating_sub(first.timestamp),
+			_ => 0,
+		}
+	}
+
+	/// Total size of the nodes that pruning the blocks in the window would remove. Nodes that are
+	/// inserted again before being pruned are still counted.
+	pub fn window_state_size(&self) -> u64 {
+		self.state_size
+	}
+
 	pub fn next_hash(&self) -> Option<BlockHash> {
 		self.death_rows.get(self.pending_prunings).map(|r| r.hash.clone())
 	}
@@ -145,8 +200,10 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			trace!(target: "state-db", "Pruning {:?} ({} deleted)", pruned.hash, pruned.deleted.len());
 			let index = self.pending_number + self.pending_prunings as u64;
 			commit.data.deleted.extend(pruned.deleted.iter().cloned());
+			commit.data.deleted_prefixes.extend(pruned.deleted_prefixes.iter().cloned());
 			commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), index.encode()));
 			commit.meta.deleted.push(pruned.journal_key.clone());
+			self.state_size -= pruned.state_size;
 			self.pending_prunings += 1;
 		} else {
 			warn!(target: "state-db", "Trying to prune when there's nothing to prune");
@@ -154,10 +211,20 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 	}
 
 	/// Add a change set to the window. Creates a journal record and pushes it to `commit`
-	pub fn note_canonical(&mut self, hash: &BlockHash, commit: &mut CommitSet<Key>) {
+	///
+	/// `timestamp` is the time of the block in seconds since the UNIX epoch and `state_size` the
+	/// size of the nodes deleted by `commit`.
+	pub fn note_canonical(
+		&mut self,
+		hash: &BlockHash,
+		timestamp: u64,
+		state_size: u64,
+		commit: &mut CommitSet<Key>,
+	) {
 		trace!(target: "state-db", "Adding to pruning window: {:?} ({} inserted, {} deleted)", hash, commit.data.inserted.len(), commit.data.deleted.len());
 		let inserted = commit.data.inserted.iter().map(|(k, _)| k.clone()).collect();
 		let deleted = ::std::mem::replace(&mut commit.data.deleted, Vec::new());
+		let deleted_prefixes = ::std::mem::replace(&mut commit.data.deleted_prefixes, Vec::new());
 		let journal_record = JournalRecord {
 			hash: hash.clone(),
 			inserted,
@@ -165,11 +232,45 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		};
 		let block = self.pending_number + self.death_rows.len() as u64;
 		let journal_key = to_journal_key(block);
-		commit.meta.inserted.push((journal_key.clone(), journal_record.encode()));
-		self.import(&journal_record.hash, journal_key, journal_record.inserted.into_iter(), journal_record.deleted);
+		let mut encoded = encode_journal_record(&journal_record, &deleted_prefixes);
+		(timestamp, state_size).encode_to(&mut encoded);
+		commit.meta.inserted.push((journal_key.clone(), encoded));
+		self.import(
+			&journal_record.hash,
+			journal_key,
+			journal_record.inserted.into_iter(),
+			journal_record.deleted,
+			deleted_prefixes,
+			timestamp,
+			state_size,
+		);
 		self.pending_canonicalizations += 1;
 	}
 
+	/// Restart the window at a block whose state is imported without its ancestry. The blocks in
+	/// the window are dropped without being pruned.
+	pub fn import_state(&mut self, hash: &BlockHash, number: u64, timestamp: u64, commit: &mut CommitSet<Key>) {
+		trace!(target: "state-db", "Restarting pruning window at #{} ({:?})", number, hash);
+		// The journal record of the imported block replaces any record with the same key.
+		let journal_key = to_journal_key(number);
+		for row in self.death_rows.drain(..) {
+			if row.journal_key != journal_key {
+				commit.meta.deleted.push(row.journal_key);
+			}
+		}
+		self.death_index.clear();
+		self.state_size = 0;
+		self.pending_number = number;
+		self.pending_prunings = 0;
+		self.pending_canonicalizations = 0;
+		match number.checked_sub(1) {
+			Some(last_pruned) =>
+				commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), last_pruned.encode())),
+			None => commit.meta.deleted.push(to_meta_key(LAST_PRUNED, &())),
+		}
+		self.note_canonical(hash, timestamp, 0, commit);
+	}
+
 	/// Apply all pending changes
 	pub fn apply_pending(&mut self) {
 		self.pending_canonicalizations = 0;
@@ -195,6 +296,7 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		self.death_index.retain(|_, block| *block < new_max_block);
 		self.pending_canonicalizations = 0;
 		self.pending_prunings = 0;
+		self.state_size = self.death_rows.iter().map(|r| r.state_size).sum();
 	}
 }
 
@@ -210,6 +312,7 @@ mod tests {
 		assert_eq!(pruning.pending_number, restored.pending_number);
 		assert_eq!(pruning.death_rows, restored.death_rows);
 		assert_eq!(pruning.death_index, restored.death_index);
+		assert_eq!(pruning.state_size, restored.state_size);
 	}
 
 	#[test]
@@ -240,7 +343,7 @@ mod tests {
 		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
 		let mut commit = make_commit(&[4, 5], &[1, 3]);
 		let h = H256::random();
-		pruning.note_canonical(&h, &mut commit);
+		pruning.note_canonical(&h, 0, 0, &mut commit);
 		db.commit(&commit);
 		assert!(pruning.have_block(&h));
 		pruning.apply_pending();
@@ -263,15 +366,67 @@ mod tests {
 		assert_eq!(pruning.pending_number, 1);
 	}
 
+	#[test]
+	fn prune_deleted_prefixes() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
+		let mut commit = make_commit(&[4], &[]);
+		commit.data.deleted_prefixes.push(H256::from_low_u64_be(2).as_bytes().to_vec());
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(commit.data.deleted_prefixes.is_empty());
+		assert!(db.data_eq(&make_db(&[1, 2, 3, 4])));
+		check_journal(&pruning, &db);
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[1, 3, 4])));
+		assert!(pruning.death_rows.is_empty());
+	}
+
+	#[test]
+	fn window_age_and_state_size() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
+		let mut commit = make_commit(&[4, 5], &[1]);
+		pruning.note_canonical(&H256::random(), 100, 64, &mut commit);
+		db.commit(&commit);
+		let mut commit = make_commit(&[6], &[2]);
+		pruning.note_canonical(&H256::random(), 160, 32, &mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(pruning.window_age(), 60);
+		assert_eq!(pruning.window_state_size(), 3 * 32);
+		check_journal(&pruning, &db);
+
+		let mut commit = make_commit(&[7], &[]);
+		pruning.note_canonical(&H256::random(), 200, 32, &mut commit);
+		pruning.revert_pending();
+		assert_eq!(pruning.window_age(), 60);
+		assert_eq!(pruning.window_state_size(), 3 * 32);
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit);
+		db.commit(&commit);
+		assert_eq!(pruning.window_age(), 0);
+		assert_eq!(pruning.window_state_size(), 32);
+		pruning.apply_pending();
+		assert_eq!(pruning.window_state_size(), 32);
+		check_journal(&pruning, &db);
+	}
+
 	#[test]
 	fn prune_two() {
 		let mut db = make_db(&[1, 2, 3]);
 		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
 		let mut commit = make_commit(&[4], &[1]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		let mut commit = make_commit(&[5], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		pruning.apply_pending();
 		assert!(db.data_eq(&make_db(&[1, 2, 3, 4, 5])));
@@ -296,10 +451,10 @@ mod tests {
 		let mut db = make_db(&[1, 2, 3]);
 		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
 		let mut commit = make_commit(&[4], &[1]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		let mut commit = make_commit(&[5], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3, 4, 5])));
 		let mut commit = CommitSet::default();
@@ -319,13 +474,13 @@ mod tests {
 		let mut db = make_db(&[1, 2, 3]);
 		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		let mut commit = make_commit(&[2], &[]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 		pruning.apply_pending();
@@ -352,13 +507,13 @@ mod tests {
 		let mut db = make_db(&[1, 2, 3]);
 		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		let mut commit = make_commit(&[2], &[]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), 0, 0, &mut commit);
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 
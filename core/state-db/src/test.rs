@@ -36,10 +36,10 @@ impl MetaDb for TestDb {
 
 impl NodeDb for TestDb {
 	type Error = ();
-	type Key = H256;
+	type Key = [u8];
 
-	fn get(&self, key: &H256) -> Result<Option<DBValue>, ()> {
-		Ok(self.data.get(key).cloned())
+	fn get(&self, key: &[u8]) -> Result<Option<DBValue>, ()> {
+		Ok(self.data.get(&H256::from_slice(key)).cloned())
 	}
 }
 
@@ -50,6 +50,9 @@ impl TestDb {
 		for k in commit.data.deleted.iter() {
 			self.data.remove(k);
 		}
+		for prefix in commit.data.deleted_prefixes.iter() {
+			self.data.retain(|k, _| !k.as_bytes().starts_with(prefix));
+		}
 		self.meta.extend(commit.meta.inserted.iter().cloned());
 		for k in commit.meta.deleted.iter() {
 			self.meta.remove(k);
@@ -70,6 +73,7 @@ pub fn make_changeset(inserted: &[u64], deleted: &[u64]) -> ChangeSet<H256> {
 			})
 			.collect(),
 		deleted: deleted.iter().map(|v| H256::from_low_u64_be(*v)).collect(),
+		deleted_prefixes: Vec::new(),
 	}
 }
 
@@ -19,10 +19,12 @@
 use std::{error, fmt, cmp::Ord, collections::HashMap, marker::PhantomData};
 use log::warn;
 use hash_db::Hasher;
+use primitives::storage::well_known_keys;
 use crate::trie_backend::TrieBackend;
 use crate::trie_backend_essence::TrieBackendStorage;
 use trie::{
 	TrieMut, MemoryDB, child_trie_root, default_child_trie_root, TrieConfiguration,
+	child_trie_unique_id, encode_child_trie_value, decode_child_trie_value,
 	trie_types::{TrieDBMut, Layout},
 };
 
@@ -66,6 +68,16 @@ pub trait Backend<H: Hasher>: std::fmt::Debug {
 		Ok(self.child_storage(storage_key, key)?.is_some())
 	}
 
+	/// Return the next key in storage in lexicographic order or `None` if there is no value.
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+
+	/// Return the next key in child storage in lexicographic order or `None` if there is no value.
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8]
+	) -> Result<Option<Vec<u8>>, Self::Error>;
+
 	/// Retrieve all entries keys of child storage and call `f` for each of those keys.
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F);
 
@@ -93,9 +105,19 @@ pub trait Backend<H: Hasher>: std::fmt::Debug {
 		H::Out: Ord;
 
 	/// Calculate the child storage root, with given delta over what is already stored in
-	/// the backend, and produce a "transaction" that can be used to commit. The second argument
-	/// is true if child storage root equals default storage root.
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	/// the backend, and produce a "transaction" that can be used to commit. The first value is
+	/// the value to store for the child trie in the top trie, i.e. the child storage root followed
+	/// by the unique id of the child trie. The second value is true if child storage root equals
+	/// default storage root.
+	///
+	/// If `killed` is true, the content of the child trie in the backend is discarded and the
+	/// delta is applied to a new, empty child trie with a new unique id.
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 	where
 		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 		H::Out: Ord;
@@ -125,6 +147,10 @@ pub trait Backend<H: Hasher>: std::fmt::Debug {
 	/// Calculate the storage root, with given delta over what is already stored
 	/// in the backend, and produce a "transaction" that can be used to commit.
 	/// Does include child storage updates.
+	///
+	/// A child trie is killed by a change of its value in `delta` that removes it or that gives
+	/// it another unique id. The nodes of a killed child trie are not removed by the
+	/// transaction, they are left to be garbage collected by the database.
 	fn full_storage_root<I1, I2i, I2>(
 		&self,
 		delta: I1,
@@ -136,12 +162,23 @@ pub trait Backend<H: Hasher>: std::fmt::Debug {
 		I2: IntoIterator<Item=(Vec<u8>, I2i)>,
 		<H as Hasher>::Out: Ord,
 	{
+		let delta: Vec<_> = delta.into_iter().collect();
 		let mut txs: Self::Transaction = Default::default();
 		let mut child_roots: Vec<_> = Default::default();
 		// child first
 		for (storage_key, child_delta) in child_deltas {
+			let killed = match delta.iter().rev().find(|(key, _)| *key == storage_key) {
+				Some((_, change)) => {
+					let current = self.storage(&storage_key).unwrap_or_default();
+					is_child_trie_killed::<H>(
+						current.as_ref().map(AsRef::as_ref),
+						change.as_ref().map(AsRef::as_ref),
+					)
+				},
+				None => false,
+			};
 			let (child_root, empty, child_txs) =
-				self.child_storage_root(&storage_key[..], child_delta);
+				self.child_storage_root(&storage_key[..], child_delta, killed);
 			txs.consolidate(child_txs);
 			if empty {
 				child_roots.push((storage_key, None));
@@ -170,6 +207,18 @@ impl<'a, T: Backend<H>, H: Hasher> Backend<H> for &'a T {
 		(*self).child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		(*self).next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8]
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		(*self).next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
 		(*self).for_keys_in_child_storage(storage_key, f)
 	}
@@ -190,12 +239,17 @@ impl<'a, T: Backend<H>, H: Hasher> Backend<H> for &'a T {
 		(*self).storage_root(delta)
 	}
 
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 	where
 		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 		H::Out: Ord,
 	{
-		(*self).child_storage_root(storage_key, delta)
+		(*self).child_storage_root(storage_key, delta, killed)
 	}
 
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
@@ -207,6 +261,22 @@ impl<'a, T: Backend<H>, H: Hasher> Backend<H> for &'a T {
 	}
 }
 
+/// Returns true if the `change` of the value of a child trie in the top trie kills the child
+/// trie whose value in the backend is `current`: either the child trie is removed, or it is
+/// replaced with a child trie with another unique id.
+///
+/// A child trie without a unique id is never killed, its keys are deleted one by one instead.
+pub fn is_child_trie_killed<H: Hasher>(current: Option<&[u8]>, change: Option<&[u8]>) -> bool {
+	let current_id = match current {
+		Some(current) => decode_child_trie_value::<Layout<H>>(current).1,
+		None => return false,
+	};
+	!current_id.is_empty() && match change {
+		Some(change) => current_id != decode_child_trie_value::<Layout<H>>(change).1,
+		None => true,
+	}
+}
+
 /// Trait that allows consolidate two transactions together.
 pub trait Consolidate {
 	/// Consolidate two transactions into one.
@@ -359,6 +429,25 @@ impl<H: Hasher> InMemory<H> {
 	pub fn child_storage_keys(&self) -> impl Iterator<Item=&[u8]> {
 		self.inner.iter().filter_map(|item| item.0.as_ref().map(|v|&v[..]))
 	}
+
+	/// The unique id of a new child trie at `storage_key`, empty if unique ids are not enabled.
+	fn new_child_unique_id(&self, storage_key: &[u8]) -> Vec<u8> {
+		let top = self.inner.get(&None);
+		if !top.map_or(false, |map| map.contains_key(well_known_keys::CHILD_TRIE_UNIQUE_IDS)) {
+			return Vec::new();
+		}
+		let root = Layout::<H>::trie_root(top.into_iter().flat_map(|map| map.iter()));
+		child_trie_unique_id::<Layout<H>>(storage_key, root.as_ref())
+	}
+
+	/// The unique id of the child trie at `storage_key` if it exists, the id of a new child trie
+	/// otherwise.
+	fn child_unique_id(&self, storage_key: &[u8]) -> Vec<u8> {
+		match self.inner.get(&None).and_then(|map| map.get(storage_key)) {
+			Some(value) => decode_child_trie_value::<Layout<H>>(value).1.to_vec(),
+			None => self.new_child_unique_id(storage_key),
+		}
+	}
 }
 
 impl<H: Hasher> Backend<H> for InMemory<H> {
@@ -378,6 +467,18 @@ impl<H: Hasher> Backend<H> for InMemory<H> {
 		Ok(self.inner.get(&None).map(|map| map.get(key).is_some()).unwrap_or(false))
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		Ok(self.inner.get(&None).and_then(|map| next_key(map, key)))
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8]
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		Ok(self.inner.get(&Some(storage_key.to_vec())).and_then(|map| next_key(map, key)))
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.inner.get(&None).map(|map| map.keys().filter(|key| key.starts_with(prefix)).map(|k| &**k).for_each(f));
 	}
@@ -417,14 +518,26 @@ impl<H: Hasher> Backend<H> for InMemory<H> {
 		(root, full_transaction)
 	}
 
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 	where
 		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 		H::Out: Ord
 	{
 		let storage_key = storage_key.to_vec();
+		let existing = self.inner.get(&Some(storage_key.clone()));
+		let unique_id = if killed {
+			self.new_child_unique_id(&storage_key)
+		} else {
+			self.child_unique_id(&storage_key)
+		};
 
-		let existing_pairs = self.inner.get(&Some(storage_key.clone()))
+		let existing_pairs = existing
+			.filter(|_| !killed)
 			.into_iter()
 			.flat_map(|map| map.iter().map(|(k, v)| (k.clone(), Some(v.clone()))));
 
@@ -437,11 +550,18 @@ impl<H: Hasher> Backend<H> for InMemory<H> {
 				.filter_map(|(k, maybe_val)| maybe_val.map(|val| (k, val)))
 		);
 
-		let full_transaction = transaction.into_iter().map(|(k, v)| (Some(storage_key.clone()), k, v)).collect();
+		// the content of a killed child trie is removed before the delta is applied.
+		let full_transaction = existing
+			.filter(|_| killed)
+			.into_iter()
+			.flat_map(|map| map.keys().map(|k| (k.clone(), None)))
+			.chain(transaction)
+			.map(|(k, v)| (Some(storage_key.clone()), k, v))
+			.collect();
 
 		let is_default = root == default_child_trie_root::<Layout<H>>(&storage_key);
 
-		(root, is_default, full_transaction)
+		(encode_child_trie_value(&root, &unique_id), is_default, full_transaction)
 	}
 
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
@@ -473,7 +593,8 @@ impl<H: Hasher> Backend<H> for InMemory<H> {
 		for (storage_key, map) in &self.inner {
 			if let Some(storage_key) = storage_key.as_ref() {
 				let ch = insert_into_memory_db::<H, _>(&mut mdb, map.clone().into_iter())?;
-				new_child_roots.push((storage_key.clone(), ch.as_ref().into()));
+				let unique_id = self.child_unique_id(storage_key);
+				new_child_roots.push((storage_key.clone(), encode_child_trie_value(ch.as_ref(), &unique_id)));
 			} else {
 				root_map = Some(map);
 			}
@@ -494,6 +615,11 @@ impl<H: Hasher> Backend<H> for InMemory<H> {
 	}
 }
 
+/// Returns the smallest key of `map` that is strictly greater than `key`.
+fn next_key(map: &HashMap<Vec<u8>, Vec<u8>>, key: &[u8]) -> Option<Vec<u8>> {
+	map.keys().filter(|k| &k[..] > key).min().cloned()
+}
+
 /// Insert input pairs into memory db.
 pub(crate) fn insert_into_memory_db<H, I>(mdb: &mut MemoryDB<H>, input: I) -> Option<H::Out>
 	where
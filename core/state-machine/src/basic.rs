@@ -19,7 +19,7 @@
 use std::{collections::HashMap, any::{TypeId, Any}, iter::FromIterator};
 use crate::backend::{Backend, InMemory};
 use hash_db::Hasher;
-use trie::{TrieConfiguration, default_child_trie_root};
+use trie::{TrieConfiguration, default_child_trie_root, decode_child_trie_value};
 use trie::trie_types::Layout;
 use primitives::{
 	storage::{well_known_keys::is_child_storage_key, ChildStorageKey},
@@ -32,6 +32,9 @@ use log::warn;
 pub struct BasicExternalities {
 	top: HashMap<Vec<u8>, Vec<u8>>,
 	children: HashMap<Vec<u8>, HashMap<Vec<u8>, Vec<u8>>>,
+	/// Snapshots of the storage taken when the open storage transactions were started, innermost
+	/// last.
+	transactions: Vec<(HashMap<Vec<u8>, Vec<u8>>, HashMap<Vec<u8>, HashMap<Vec<u8>, Vec<u8>>>)>,
 }
 
 impl BasicExternalities {
@@ -43,6 +46,7 @@ impl BasicExternalities {
 		BasicExternalities {
 			top,
 			children,
+			transactions: Vec::new(),
 		}
 	}
 
@@ -83,6 +87,7 @@ impl From<HashMap<Vec<u8>, Vec<u8>>> for BasicExternalities {
 		BasicExternalities {
 			top: hashmap,
 			children: Default::default(),
+			transactions: Vec::new(),
 		}
 	}
 }
@@ -146,6 +151,15 @@ impl Externalities for BasicExternalities {
 		}
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.top.keys().filter(|k| &k[..] > key).min().cloned()
+	}
+
+	fn next_child_storage_key(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<Vec<u8>> {
+		self.children.get(storage_key.as_ref())
+			.and_then(|child| child.keys().filter(|k| &k[..] > key).min().cloned())
+	}
+
 	fn kill_child_storage(&mut self, storage_key: ChildStorageKey) {
 		self.children.remove(storage_key.as_ref());
 	}
@@ -196,7 +210,9 @@ impl Externalities for BasicExternalities {
 		if let Some(child) = self.children.get(storage_key.as_ref()) {
 			let delta = child.clone().into_iter().map(|(k, v)| (k, Some(v)));
 
-			InMemory::<Blake2Hasher>::default().child_storage_root(storage_key.as_ref(), delta).0
+			let value = InMemory::<Blake2Hasher>::default()
+				.child_storage_root(storage_key.as_ref(), delta, false).0;
+			decode_child_trie_value::<Layout<Blake2Hasher>>(&value).0.to_vec()
 		} else {
 			default_child_trie_root::<Layout<Blake2Hasher>>(storage_key.as_ref())
 		}
@@ -205,6 +221,21 @@ impl Externalities for BasicExternalities {
 	fn storage_changes_root(&mut self, _parent: H256) -> Result<Option<H256>, ()> {
 		Ok(None)
 	}
+
+	fn storage_start_transaction(&mut self) {
+		self.transactions.push((self.top.clone(), self.children.clone()));
+	}
+
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()> {
+		let (top, children) = self.transactions.pop().ok_or(())?;
+		self.top = top;
+		self.children = children;
+		Ok(())
+	}
+
+	fn storage_commit_transaction(&mut self) -> Result<(), ()> {
+		self.transactions.pop().map(|_| ()).ok_or(())
+	}
 }
 
 impl externalities::ExtensionStore for BasicExternalities {
@@ -21,7 +21,7 @@ use std::collections::btree_map::Entry;
 use codec::Decode;
 use hash_db::Hasher;
 use num_traits::One;
-use crate::backend::Backend;
+use crate::backend::{Backend, is_child_trie_killed};
 use crate::overlayed_changes::OverlayedChanges;
 use crate::trie_backend_essence::TrieBackendEssence;
 use crate::changes_trie::build_iterator::digest_build_iterator;
@@ -140,8 +140,8 @@ fn prepare_extrinsics_input_inner<'a, B, H, Number>(
 	committed.iter().flat_map(|c| c.iter())
 		.chain(prospective.iter().flat_map(|c| c.iter()))
 		.filter(|( _, v)| v.extrinsics.is_some())
-		.try_fold(BTreeMap::new(), |mut map: BTreeMap<&[u8], (ExtrinsicIndex<Number>, Vec<u32>)>, (k, v)| {
-			match map.entry(k) {
+		.try_fold(BTreeMap::new(), |mut map: BTreeMap<Vec<u8>, (ExtrinsicIndex<Number>, Vec<u32>)>, (k, v)| {
+			match map.entry(k.clone()) {
 				Entry::Vacant(entry) => {
 					// ignore temporary values (values that have null value at the end of operation
 					// AND are not in storage at the beginning of operation
@@ -183,9 +183,59 @@ fn prepare_extrinsics_input_inner<'a, B, H, Number>(
 
 			Ok(map)
 		})
+		.and_then(|mut map| {
+			if let Some(sk) = storage_key.as_ref() {
+				insert_killed_child_keys(backend, block, changes, sk, &mut map)?;
+			}
+			Ok(map)
+		})
 		.map(|pairs| pairs.into_iter().map(|(_, (k, v))| InputPair::ExtrinsicIndex(k, v)))
 }
 
+/// Insert the keys of the child trie at `storage_key` in the backend if the child trie was
+/// killed. The overlay only records the kill, as a change of the value of the child trie in the
+/// top trie, so its keys are enumerated here, with the extrinsics that changed that value.
+fn insert_killed_child_keys<B, H, Number>(
+	backend: &B,
+	block: &Number,
+	changes: &OverlayedChanges,
+	storage_key: &[u8],
+	map: &mut BTreeMap<Vec<u8>, (ExtrinsicIndex<Number>, Vec<u32>)>,
+) -> Result<(), String>
+	where
+		B: Backend<H>,
+		H: Hasher,
+		Number: BlockNumber,
+{
+	let change = match changes.storage(storage_key) {
+		Some(change) => change,
+		None => return Ok(()),
+	};
+	let current = backend.storage(storage_key).map_err(|e| format!("{}", e))?;
+	if !is_child_trie_killed::<H>(current.as_ref().map(AsRef::as_ref), change) {
+		return Ok(());
+	}
+
+	let extrinsics = changes.committed.top.get(storage_key).into_iter()
+		.chain(changes.prospective.top.get(storage_key))
+		.flat_map(|value| value.extrinsics.iter().flatten().cloned())
+		.collect::<Vec<_>>();
+	if extrinsics.is_empty() {
+		return Ok(());
+	}
+
+	backend.for_keys_in_child_storage(storage_key, |key| {
+		let entry = map.entry(key.to_vec()).or_insert_with(|| (ExtrinsicIndex {
+			block: block.clone(),
+			key: key.to_vec(),
+		}, Vec::new()));
+		entry.1.extend(extrinsics.iter().cloned());
+		entry.1.sort_unstable();
+		entry.1.dedup();
+	});
+	Ok(())
+}
+
 
 /// Prepare DigestIndex input pairs.
 fn prepare_digest_input<'a, H, Number>(
@@ -330,7 +380,7 @@ fn prepare_digest_input<'a, H, Number>(
 mod test {
 	use codec::Encode;
 	use primitives::Blake2Hasher;
-	use primitives::storage::well_known_keys::{EXTRINSIC_INDEX};
+	use primitives::storage::well_known_keys::{EXTRINSIC_INDEX, CHILD_TRIE_UNIQUE_IDS};
 	use crate::backend::InMemory;
 	use crate::changes_trie::{RootsStorage, Configuration, storage::InMemoryStorage};
 	use crate::changes_trie::build_cache::{IncompleteCacheAction, IncompleteCachedBuildData};
@@ -453,6 +503,7 @@ mod test {
 				].into_iter().collect(),
 			},
 			changes_trie_config: Some(config.clone()),
+			transactions: Vec::new(),
 		};
 
 		(backend, storage, changes, config)
@@ -691,6 +742,50 @@ mod test {
 		test_with_zero(17);
 	}
 
+	#[test]
+	fn build_changes_trie_nodes_records_keys_of_killed_child_tries() {
+		let (_, storage, mut changes, config) = prepare_for_build(0);
+		let child_trie_key = b"3".to_vec();
+		let backend = InMemory::<Blake2Hasher>::default().update(vec![
+			(None, CHILD_TRIE_UNIQUE_IDS.to_vec(), Some(vec![1])),
+		]);
+		let (_, transaction) = backend.full_storage_root(
+			std::iter::empty(),
+			vec![(child_trie_key.clone(), vec![(vec![110], Some(vec![1])), (vec![111], Some(vec![2]))])],
+		);
+		let backend = backend.update(transaction);
+
+		// the child trie is killed by extrinsic 1, only recording the removal of its value in the
+		// top trie, then 111 is set again by extrinsic 2
+		changes.prospective.top.insert(child_trie_key.clone(), OverlayedValue {
+			value: None,
+			extrinsics: Some(vec![1].into_iter().collect())
+		});
+		changes.prospective.children.insert(child_trie_key.clone(), vec![
+			(vec![111], OverlayedValue {
+				value: Some(vec![3]),
+				extrinsics: Some(vec![2].into_iter().collect())
+			}),
+		].into_iter().collect());
+
+		let parent = AnchorBlockId { hash: Default::default(), number: 0 };
+		let changes_trie_nodes = prepare_input(
+			&backend,
+			&storage,
+			configuration_range(&config, 0),
+			&changes,
+			&parent,
+		).unwrap();
+		let child_index = ChildIndex { block: 1u64, storage_key: child_trie_key };
+		let child_changes_trie_nodes = changes_trie_nodes.1.into_iter()
+			.find(|(index, _)| *index == child_index)
+			.map(|(_, nodes)| nodes.collect::<Vec<_>>());
+		assert_eq!(child_changes_trie_nodes, Some(vec![
+			InputPair::ExtrinsicIndex(ExtrinsicIndex { block: 1, key: vec![110] }, vec![1]),
+			InputPair::ExtrinsicIndex(ExtrinsicIndex { block: 1, key: vec![111] }, vec![1, 2]),
+		]));
+	}
+
 	#[test]
 	fn cache_is_used_when_changes_trie_is_built() {
 		let (backend, mut storage, changes, _) = prepare_for_build(0);
@@ -24,7 +24,9 @@ use hash_db::Hasher;
 use num_traits::Zero;
 use trie::Recorder;
 use crate::changes_trie::{AnchorBlockId, ConfigurationRange, RootsStorage, Storage, BlockNumber};
-use crate::changes_trie::input::{DigestIndex, ExtrinsicIndex, DigestIndexValue, ExtrinsicIndexValue};
+use crate::changes_trie::input::{
+	DigestIndex, ExtrinsicIndex, DigestIndexValue, ExtrinsicIndexValue, InputKey,
+};
 use crate::changes_trie::storage::{TrieBackendAdapter, InMemoryStorage};
 use crate::changes_trie::input::ChildIndex;
 use crate::changes_trie::surface_iterator::{surface_iterator, SurfaceIterator};
@@ -180,6 +182,58 @@ pub fn key_changes_proof_check_with_db<'a, H: Hasher, Number: BlockNumber>(
 	}.collect()
 }
 
+/// Return the keys that have been changed at given block, along with the storage key of their
+/// child trie for the changes of child tries.
+///
+/// Only the keys of the top trie that start with `prefix` and the keys of the child tries whose
+/// storage key starts with `prefix` are returned. Fails if the changes trie of the block has
+/// been pruned.
+pub fn block_changes<H: Hasher, Number: BlockNumber>(
+	storage: &dyn Storage<H, Number>,
+	anchor: &AnchorBlockId<H::Out, Number>,
+	block: Number,
+	prefix: &[u8],
+) -> Result<Vec<(Option<Vec<u8>>, Vec<u8>)>, String> {
+	let trie_root = storage.root(anchor, block.clone())?
+		.ok_or_else(|| format!("Changes trie root for block {} is not found", block.clone()))?;
+	let trie = |root| TrieBackendEssence::<_, H>::new(TrieBackendAdapter::new(storage), root);
+	let extrinsics_prefix = ExtrinsicIndex::key_neutral_prefix(block.clone());
+
+	let mut changes = Vec::new();
+	let mut child_roots = Vec::new();
+	let top = trie(trie_root);
+	top.try_for_key_values_with_prefix(&extrinsics_prefix, |key, _| {
+		if let Ok(InputKey::ExtrinsicIndex(index)) = InputKey::<Number>::decode(&mut &key[..]) {
+			if index.key.starts_with(prefix) {
+				changes.push((None, index.key));
+			}
+		}
+	})?;
+	top.try_for_key_values_with_prefix(&ChildIndex::key_neutral_prefix(block.clone()), |key, value| {
+		if let Ok(InputKey::ChildIndex(index)) = InputKey::<Number>::decode(&mut &key[..]) {
+			if index.storage_key.starts_with(prefix) {
+				child_roots.push((index.storage_key, value.to_vec()));
+			}
+		}
+	})?;
+
+	for (storage_key, root) in child_roots {
+		let mut child_root = H::Out::default();
+		match <Vec<u8>>::decode(&mut &root[..]) {
+			Ok(ref root) if root.len() == child_root.as_ref().len() =>
+				child_root.as_mut().copy_from_slice(&root[..]),
+			_ => return Err(format!("Invalid changes trie root of child trie at block {}", block)),
+		}
+		trie(child_root).try_for_key_values_with_prefix(&extrinsics_prefix, |key, _| {
+			if let Ok(InputKey::ExtrinsicIndex(index)) = InputKey::<Number>::decode(&mut &key[..]) {
+				changes.push((Some(storage_key.clone()), index.key));
+			}
+		})?;
+	}
+
+	Ok(changes)
+}
+
 /// Drilldown iterator - receives 'digest points' from surface iterator and explores
 /// every point until extrinsic is found.
 pub struct DrilldownIteratorEssence<'a, H, Number>
@@ -444,6 +498,28 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn block_changes_works() {
+		let (_, storage) = prepare_for_drilldown();
+		let anchor = AnchorBlockId { hash: Default::default(), number: 16 };
+
+		assert_eq!(
+			block_changes::<Blake2Hasher, u64>(&storage, &anchor, 8, &[]),
+			Ok(vec![(None, vec![42])]),
+		);
+		assert_eq!(block_changes::<Blake2Hasher, u64>(&storage, &anchor, 8, &[43]), Ok(vec![]));
+		assert_eq!(block_changes::<Blake2Hasher, u64>(&storage, &anchor, 7, &[]), Ok(vec![]));
+		assert_eq!(
+			block_changes::<Blake2Hasher, u64>(&storage, &anchor, 16, &[]),
+			Ok(vec![(Some(b"1".to_vec()), vec![42])]),
+		);
+		assert!(block_changes::<Blake2Hasher, u64>(&storage, &anchor, 17, &[]).is_err());
+
+		// the roots are still known once the tries are pruned
+		storage.clear_storage();
+		assert!(block_changes::<Blake2Hasher, u64>(&storage, &anchor, 8, &[]).is_err());
+	}
+
 	#[test]
 	fn drilldown_iterator_works() {
 		let (config, storage) = prepare_for_drilldown();
@@ -61,7 +61,7 @@ pub use self::build_cache::{BuildCache, CachedBuildData, CacheAction};
 pub use self::storage::InMemoryStorage;
 pub use self::changes_iterator::{
 	key_changes, key_changes_proof,
-	key_changes_proof_check, key_changes_proof_check_with_db,
+	key_changes_proof_check, key_changes_proof_check_with_db, block_changes,
 };
 pub use self::prune::{prune, oldest_non_pruned_trie};
 
@@ -17,7 +17,7 @@
 //! Concrete externalities implementation.
 
 use crate::{
-	backend::Backend, OverlayedChanges,
+	backend::{Backend, is_child_trie_killed}, OverlayedChanges,
 	changes_trie::{
 		Storage as ChangesTrieStorage, CacheAction as ChangesTrieCacheAction, build_changes_trie,
 	},
@@ -28,7 +28,7 @@ use primitives::{
 	storage::{ChildStorageKey, well_known_keys::is_child_storage_key},
 	traits::Externalities, hexdisplay::HexDisplay, hash::H256,
 };
-use trie::{trie_types::Layout, MemoryDB, default_child_trie_root};
+use trie::{trie_types::Layout, MemoryDB, default_child_trie_root, decode_child_trie_value};
 use externalities::Extensions;
 
 use std::{error, fmt, any::{Any, TypeId}};
@@ -146,6 +146,18 @@ where
 	fn mark_dirty(&mut self) {
 		self.storage_transaction = None;
 	}
+
+	/// Returns true if the child trie was killed by the overlay, i.e. its content in the backend
+	/// must not be read anymore.
+	fn child_trie_killed(&self, storage_key: &[u8]) -> bool {
+		match self.overlay.storage(storage_key) {
+			Some(change) => {
+				let current = self.backend.storage(storage_key).expect(EXT_NOT_ALLOWED_TO_FAIL);
+				is_child_trie_killed::<H>(current.as_ref().map(AsRef::as_ref), change)
+			},
+			None => false,
+		}
+	}
 }
 
 #[cfg(test)]
@@ -232,9 +244,11 @@ where
 		let result = self.overlay
 			.child_storage(storage_key.as_ref(), key)
 			.map(|x| x.map(|x| x.to_vec()))
-			.unwrap_or_else(||
+			.unwrap_or_else(|| if self.child_trie_killed(storage_key.as_ref()) {
+				None
+			} else {
 				self.backend.child_storage(storage_key.as_ref(), key).expect(EXT_NOT_ALLOWED_TO_FAIL)
-			);
+			});
 
 		trace!(target: "state-trace", "{:04x}: GetChild({}) {}={:?}",
 			self.id,
@@ -251,9 +265,11 @@ where
 		let result = self.overlay
 			.child_storage(storage_key.as_ref(), key)
 			.map(|x| x.map(|x| H::hash(x)))
-			.unwrap_or_else(||
+			.unwrap_or_else(|| if self.child_trie_killed(storage_key.as_ref()) {
+				None
+			} else {
 				self.backend.storage_hash(key).expect(EXT_NOT_ALLOWED_TO_FAIL)
-			);
+			});
 
 		trace!(target: "state-trace", "{:04x}: ChildHash({}) {}={:?}",
 			self.id,
@@ -316,7 +332,7 @@ where
 
 		let result = match self.overlay.child_storage(storage_key.as_ref(), key) {
 			Some(x) => x.is_some(),
-			_ => self.backend
+			_ => !self.child_trie_killed(storage_key.as_ref()) && self.backend
 				.exists_child_storage(storage_key.as_ref(), key)
 				.expect(EXT_NOT_ALLOWED_TO_FAIL),
 		};
@@ -330,6 +346,69 @@ where
 		result
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Option<Vec<u8>> {
+		let mut next_backend_key = self.backend.next_storage_key(key).expect(EXT_NOT_ALLOWED_TO_FAIL);
+		let mut next_overlay_key_change = self.overlay.next_storage_key_change(key);
+
+		loop {
+			let overlay_key = match next_overlay_key_change {
+				Some(overlay_key) => overlay_key,
+				None => return next_backend_key,
+			};
+			if next_backend_key.as_ref().map_or(false, |backend_key| &backend_key[..] < overlay_key.0) {
+				return next_backend_key;
+			}
+			if overlay_key.1.value.is_some() {
+				return Some(overlay_key.0.to_vec());
+			}
+
+			// The key has been deleted in the overlay, look further.
+			if next_backend_key.as_ref().map_or(false, |backend_key| &backend_key[..] == overlay_key.0) {
+				next_backend_key = self.backend.next_storage_key(overlay_key.0)
+					.expect(EXT_NOT_ALLOWED_TO_FAIL);
+			}
+			next_overlay_key_change = self.overlay.next_storage_key_change(overlay_key.0);
+		}
+	}
+
+	fn next_child_storage_key(&self, storage_key: ChildStorageKey, key: &[u8]) -> Option<Vec<u8>> {
+		let child_trie_killed = self.child_trie_killed(storage_key.as_ref());
+		let next_backend_child_key = |key: &[u8]| if child_trie_killed {
+			None
+		} else {
+			self.backend
+				.next_child_storage_key(storage_key.as_ref(), key)
+				.expect(EXT_NOT_ALLOWED_TO_FAIL)
+		};
+		let mut next_backend_key = next_backend_child_key(key);
+		let mut next_overlay_key_change = self.overlay.next_child_storage_key_change(
+			storage_key.as_ref(),
+			key
+		);
+
+		loop {
+			let overlay_key = match next_overlay_key_change {
+				Some(overlay_key) => overlay_key,
+				None => return next_backend_key,
+			};
+			if next_backend_key.as_ref().map_or(false, |backend_key| &backend_key[..] < overlay_key.0) {
+				return next_backend_key;
+			}
+			if overlay_key.1.value.is_some() {
+				return Some(overlay_key.0.to_vec());
+			}
+
+			// The key has been deleted in the overlay, look further.
+			if next_backend_key.as_ref().map_or(false, |backend_key| &backend_key[..] == overlay_key.0) {
+				next_backend_key = next_backend_child_key(overlay_key.0);
+			}
+			next_overlay_key_change = self.overlay.next_child_storage_key_change(
+				storage_key.as_ref(),
+				overlay_key.0,
+			);
+		}
+	}
+
 	fn place_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
 		trace!(target: "state-trace", "{:04x}: Put {}={:?}",
 			self.id,
@@ -373,9 +452,24 @@ where
 
 		self.mark_dirty();
 		self.overlay.clear_child_storage(storage_key.as_ref());
-		self.backend.for_keys_in_child_storage(storage_key.as_ref(), |key| {
-			self.overlay.set_child_storage(storage_key.as_ref().to_vec(), key.to_vec(), None);
-		});
+		if self.child_trie_killed(storage_key.as_ref()) {
+			// the content of the child trie in the backend is already discarded.
+			return;
+		}
+
+		let has_unique_id = self.backend.storage(storage_key.as_ref())
+			.expect(EXT_NOT_ALLOWED_TO_FAIL)
+			.map_or(false, |value| !decode_child_trie_value::<Layout<H>>(&value).1.is_empty());
+		if has_unique_id {
+			// Removing a child trie with a unique id from the top trie discards its content in the
+			// backend at once, in constant time. Its nodes are garbage collected by the database,
+			// and its keys are only enumerated when building the changes trie.
+			self.overlay.set_storage(storage_key.into_owned(), None);
+		} else {
+			self.backend.for_keys_in_child_storage(storage_key.as_ref(), |key| {
+				self.overlay.set_child_storage(storage_key.as_ref().to_vec(), key.to_vec(), None);
+			});
+		}
 	}
 
 	fn clear_prefix(&mut self, prefix: &[u8]) {
@@ -455,6 +549,7 @@ where
 		if self.storage_transaction.is_some() {
 			let root = self
 				.storage(storage_key.as_ref())
+				.map(|value| decode_child_trie_value::<Layout<H>>(&value).0.to_vec())
 				.unwrap_or(
 					default_child_trie_root::<Layout<H>>(storage_key.as_ref())
 				);
@@ -466,8 +561,9 @@ where
 			root
 		} else {
 			let storage_key = storage_key.as_ref();
+			let killed = self.child_trie_killed(storage_key);
 
-			let (root, is_empty, _) = {
+			let (value, is_empty, _) = {
 				let delta = self.overlay.committed.children.get(storage_key)
 					.into_iter()
 					.flat_map(|map| map.clone().into_iter().map(|(k, v)| (k, v.value)))
@@ -475,14 +571,15 @@ where
 							.into_iter()
 							.flat_map(|map| map.clone().into_iter().map(|(k, v)| (k, v.value))));
 
-				self.backend.child_storage_root(storage_key, delta)
+				self.backend.child_storage_root(storage_key, delta, killed)
 			};
 
 			if is_empty {
 				self.overlay.set_storage(storage_key.into(), None);
 			} else {
-				self.overlay.set_storage(storage_key.into(), Some(root.clone()));
+				self.overlay.set_storage(storage_key.into(), Some(value.clone()));
 			}
+			let root = decode_child_trie_value::<Layout<H>>(&value).0.to_vec();
 
 			trace!(target: "state-trace", "{:04x}: ChildRoot({}) {}",
 				self.id,
@@ -510,6 +607,22 @@ where
 		);
 		result
 	}
+
+	fn storage_start_transaction(&mut self) {
+		trace!(target: "state-trace", "{:04x}: StartTransaction", self.id);
+		self.overlay.start_transaction();
+	}
+
+	fn storage_rollback_transaction(&mut self) -> Result<(), ()> {
+		trace!(target: "state-trace", "{:04x}: RollbackTransaction", self.id);
+		self.mark_dirty();
+		self.overlay.rollback_transaction().map_err(|_| ())
+	}
+
+	fn storage_commit_transaction(&mut self) -> Result<(), ()> {
+		trace!(target: "state-trace", "{:04x}: CommitTransaction", self.id);
+		self.overlay.commit_transaction().map_err(|_| ())
+	}
 }
 
 impl<'a, H, B, T, N> externalities::ExtensionStore for Ext<'a, H, N, B, T>
@@ -529,7 +642,7 @@ mod tests {
 	use super::*;
 	use hex_literal::hex;
 	use codec::Encode;
-	use primitives::{Blake2Hasher, storage::well_known_keys::EXTRINSIC_INDEX};
+	use primitives::{Blake2Hasher, storage::well_known_keys::{self, EXTRINSIC_INDEX}};
 	use crate::{
 		changes_trie::{
 			Configuration as ChangesTrieConfiguration,
@@ -558,6 +671,7 @@ mod tests {
 				digest_interval: 0,
 				digest_levels: 0,
 			}),
+			transactions: Vec::new(),
 		}
 	}
 
@@ -603,4 +717,150 @@ mod tests {
 			Some(hex!("96f5aae4690e7302737b6f9b7f8567d5bbb9eac1c315f80101235a92d9ec27f4").into()),
 		);
 	}
+
+	#[test]
+	fn next_storage_key_works() {
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_storage(vec![20], None);
+		overlay.set_storage(vec![30], Some(vec![31]));
+		let backend: TestBackend = vec![
+			(None, vec![10], Some(vec![10])),
+			(None, vec![20], Some(vec![20])),
+			(None, vec![40], Some(vec![40])),
+		].into();
+
+		let ext = TestExt::new(&mut overlay, &backend, None, None);
+
+		// next_backend < next_overlay
+		assert_eq!(ext.next_storage_key(&[5]), Some(vec![10]));
+
+		// next_backend == next_overlay but next_overlay is a delete
+		assert_eq!(ext.next_storage_key(&[10]), Some(vec![30]));
+
+		// next_overlay < next_backend
+		assert_eq!(ext.next_storage_key(&[20]), Some(vec![30]));
+
+		// next_backend exist but next_overlay doesn't exist
+		assert_eq!(ext.next_storage_key(&[30]), Some(vec![40]));
+
+		drop(ext);
+		overlay.set_storage(vec![50], Some(vec![50]));
+		let ext = TestExt::new(&mut overlay, &backend, None, None);
+
+		// next_overlay exist but next_backend doesn't exist
+		assert_eq!(ext.next_storage_key(&[40]), Some(vec![50]));
+
+		assert_eq!(ext.next_storage_key(&[50]), None);
+	}
+
+	#[test]
+	fn next_storage_key_skips_many_deleted_keys() {
+		let mut overlay = OverlayedChanges::default();
+		for i in 0..100_000u32 {
+			overlay.set_storage(i.to_be_bytes().to_vec(), None);
+		}
+		let backend: TestBackend = vec![
+			(None, 50_000u32.to_be_bytes().to_vec(), Some(vec![1])),
+			(None, vec![0xff; 4], Some(vec![2])),
+		].into();
+
+		let ext = TestExt::new(&mut overlay, &backend, None, None);
+
+		assert_eq!(ext.next_storage_key(&[]), Some(vec![0xff; 4]));
+	}
+
+	#[test]
+	fn next_child_storage_key_works() {
+		let child = || ChildStorageKey::from_slice(b":child_storage:default:Child1").unwrap();
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_child_storage(child().as_ref().to_vec(), vec![20], None);
+		overlay.set_child_storage(child().as_ref().to_vec(), vec![30], Some(vec![31]));
+		let backend: TestBackend = vec![
+			(Some(child().as_ref().to_vec()), vec![10], Some(vec![10])),
+			(Some(child().as_ref().to_vec()), vec![20], Some(vec![20])),
+			(Some(child().as_ref().to_vec()), vec![40], Some(vec![40])),
+		].into();
+
+		let ext = TestExt::new(&mut overlay, &backend, None, None);
+
+		assert_eq!(ext.next_child_storage_key(child(), &[5]), Some(vec![10]));
+		assert_eq!(ext.next_child_storage_key(child(), &[10]), Some(vec![30]));
+		assert_eq!(ext.next_child_storage_key(child(), &[20]), Some(vec![30]));
+		assert_eq!(ext.next_child_storage_key(child(), &[30]), Some(vec![40]));
+		assert_eq!(ext.next_child_storage_key(child(), &[40]), None);
+	}
+
+	fn kill_and_recreate_child(backend: &TestBackend) -> (TestBackend, OverlayedChanges) {
+		let child = || ChildStorageKey::from_slice(b":child_storage:default:Child1").unwrap();
+		let mut overlay = OverlayedChanges::default();
+		let ((transaction, _), _) = {
+			let mut ext = TestExt::new(&mut overlay, backend, None, None);
+			ext.kill_child_storage(child());
+			ext.set_child_storage(child(), vec![30], vec![30]);
+
+			assert_eq!(ext.child_storage(child(), &[10]), None);
+			assert!(!ext.exists_child_storage(child(), &[20]));
+			assert_eq!(ext.child_storage(child(), &[30]), Some(vec![30]));
+			assert_eq!(ext.next_child_storage_key(child(), &[]), Some(vec![30]));
+			ext.transaction()
+		};
+
+		let backend = backend.update(transaction);
+		assert_eq!(backend.child_keys(child().as_ref(), &[]), vec![vec![30]]);
+		(backend, overlay)
+	}
+
+	fn backend_with_child(unique_ids: bool) -> TestBackend {
+		let child = ChildStorageKey::from_slice(b":child_storage:default:Child1").unwrap();
+		let backend = if unique_ids {
+			TestBackend::default().update(vec![
+				(None, well_known_keys::CHILD_TRIE_UNIQUE_IDS.to_vec(), Some(vec![1])),
+			])
+		} else {
+			TestBackend::default()
+		};
+		let (_, transaction) = backend.full_storage_root(
+			std::iter::empty(),
+			vec![(child.as_ref().to_vec(), vec![(vec![10], Some(vec![10])), (vec![20], Some(vec![20]))])],
+		);
+		backend.update(transaction)
+	}
+
+	#[test]
+	fn kill_child_storage_discards_backend_content() {
+		let child = || ChildStorageKey::from_slice(b":child_storage:default:Child1").unwrap();
+		let backend = backend_with_child(true);
+		let old_value = backend.storage(child().as_ref()).unwrap().unwrap();
+
+		let (backend, overlay) = kill_and_recreate_child(&backend);
+
+		// only the removal of the child trie from the top trie is recorded.
+		assert_eq!(overlay.child_storage(child().as_ref(), &[10]), None);
+		assert_eq!(overlay.child_storage(child().as_ref(), &[20]), None);
+
+		let new_value = backend.storage(child().as_ref()).unwrap().unwrap();
+		assert!(!decode_child_trie_value::<Layout<Blake2Hasher>>(&old_value).1.is_empty());
+		assert_ne!(
+			decode_child_trie_value::<Layout<Blake2Hasher>>(&new_value).1,
+			decode_child_trie_value::<Layout<Blake2Hasher>>(&old_value).1,
+		);
+	}
+
+	#[test]
+	fn child_tries_keep_their_root_only_without_unique_ids() {
+		let child = || ChildStorageKey::from_slice(b":child_storage:default:Child1").unwrap();
+		let backend = backend_with_child(false);
+		let old_value = backend.storage(child().as_ref()).unwrap().unwrap();
+		assert_eq!(old_value.len(), 32);
+
+		let (backend, overlay) = kill_and_recreate_child(&backend);
+
+		// the deletions of the keys are part of the storage changes.
+		assert_eq!(overlay.child_storage(child().as_ref(), &[10]), Some(None));
+		assert_eq!(overlay.child_storage(child().as_ref(), &[20]), Some(None));
+
+		let new_value = backend.storage(child().as_ref()).unwrap().unwrap();
+		assert_eq!(new_value.len(), 32);
+		assert_ne!(new_value, old_value);
+	}
 }
@@ -54,13 +54,15 @@ pub use changes_trie::{
 	CacheAction as ChangesTrieCacheAction,
 	ConfigurationRange as ChangesTrieConfigurationRange,
 	key_changes, key_changes_proof, key_changes_proof_check,
+	block_changes as block_changes_from_changes_trie,
 	prune as prune_changes_tries,
 	oldest_non_pruned_trie as oldest_non_pruned_changes_trie,
 };
-pub use overlayed_changes::OverlayedChanges;
+pub use overlayed_changes::{OverlayedChanges, NoOpenTransaction};
 pub use proving_backend::{
 	create_proof_check_backend, create_proof_check_backend_storage,
-	Recorder as ProofRecorder, ProvingBackend,
+	create_compact_proof_check_backend, create_recording_proof_check_backend,
+	Recorder as ProofRecorder, ProvingBackend, ProofRecordingStorage,
 };
 pub use trie_backend_essence::{TrieBackendStorage, Storage};
 pub use trie_backend::TrieBackend;
@@ -633,6 +635,53 @@ where
 	Ok(proving_backend.extract_proof())
 }
 
+/// Maximum number of keys in the range of a range read proof.
+pub const MAX_READ_RANGE_PROOF_COUNT: u32 = 1024;
+
+/// Size of the keys and values of the range of a range read proof after which the range ends.
+pub const MAX_READ_RANGE_PROOF_SIZE: usize = 2 * 1024 * 1024;
+
+/// Generate storage read proof for a range of keys.
+///
+/// The range contains the keys that start with `prefix` and that are greater than `start_key`,
+/// or all keys that start with `prefix` if `start_key` is `None`. At most `count` keys are read
+/// if it is given, and never more than `MAX_READ_RANGE_PROOF_COUNT`. The range also ends with
+/// the key that brings the size of the keys and values read to `MAX_READ_RANGE_PROOF_SIZE`, so
+/// a range may have to be read in several parts. The proof contains all nodes that are required
+/// to find the keys of the range, so that it can't be checked if any of these keys is omitted.
+pub fn prove_read_range<B, H>(
+	mut backend: B,
+	prefix: &[u8],
+	start_key: Option<&[u8]>,
+	count: Option<u32>,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	H::Out: Ord,
+{
+	let trie_backend = backend.as_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+	prove_read_range_on_trie_backend(trie_backend, prefix, start_key, count)
+}
+
+/// Generate storage read proof for a range of keys on pre-created trie backend.
+pub fn prove_read_range_on_trie_backend<S, H>(
+	trie_backend: &TrieBackend<S, H>,
+	prefix: &[u8],
+	start_key: Option<&[u8]>,
+	count: Option<u32>,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	S: trie_backend_essence::TrieBackendStorage<H>,
+	H: Hasher,
+	H::Out: Ord,
+{
+	let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+	read_proof_range(&proving_backend, prefix, start_key, count)?;
+	Ok(proving_backend.extract_proof())
+}
+
 /// Check storage read proof, generated by `prove_read` call.
 pub fn read_proof_check<H, I>(
 	root: H::Out,
@@ -705,6 +754,278 @@ where
 	proving_backend.child_storage(storage_key, key).map_err(|e| Box::new(e) as Box<dyn Error>)
 }
 
+/// Check storage read proof for a range of keys, generated by `prove_read_range` call.
+///
+/// Returns the keys of the range with their values, in order. Fails if the proof doesn't contain
+/// all nodes that are required to find the keys of the range, e.g. because a key was omitted.
+pub fn read_range_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	prefix: &[u8],
+	start_key: Option<&[u8]>,
+	count: Option<u32>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<dyn Error>>
+where
+	H: Hasher,
+	H::Out: Ord,
+{
+	let proving_backend = create_proof_check_backend::<H>(root, proof)?;
+	read_proof_range(&proving_backend, prefix, start_key, count)
+}
+
+/// Read the keys and values of the range of a range read proof, within the limits of the proofs.
+fn read_proof_range<H, B>(
+	backend: &B,
+	prefix: &[u8],
+	start_key: Option<&[u8]>,
+	count: Option<u32>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<dyn Error>> where H: Hasher, B: Backend<H> {
+	let count = count.map_or(MAX_READ_RANGE_PROOF_COUNT, |count| count.min(MAX_READ_RANGE_PROOF_COUNT));
+	read_range_with_max_size(backend, prefix, start_key, Some(count), MAX_READ_RANGE_PROOF_SIZE)
+}
+
+/// Read the keys and values of a range of keys, as described in `prove_read_range`, but without
+/// the limits of range read proofs.
+pub fn read_range<H, B>(
+	backend: &B,
+	prefix: &[u8],
+	start_key: Option<&[u8]>,
+	count: Option<u32>,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<dyn Error>> where H: Hasher, B: Backend<H> {
+	read_range_with_max_size(backend, prefix, start_key, count, usize::max_value())
+}
+
+/// Read the keys and values of a range of keys, stopping after the key that brings the size of
+/// the keys and values read to `max_size`.
+fn read_range_with_max_size<H, B>(
+	backend: &B,
+	prefix: &[u8],
+	start_key: Option<&[u8]>,
+	count: Option<u32>,
+	max_size: usize,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Box<dyn Error>> where H: Hasher, B: Backend<H> {
+	let map_e = |e| Box::new(e) as Box<dyn Error>;
+	let count = count.map_or(usize::max_value(), |count| count as usize);
+	let mut result = Vec::new();
+	let mut size = 0;
+
+	// All keys of the range are greater than a start key that is lower than the prefix.
+	let start_key = start_key.filter(|start_key| *start_key >= prefix);
+
+	// The prefix itself is the first key of the range if it is set.
+	let mut current = match start_key {
+		Some(start_key) => start_key.to_vec(),
+		None => {
+			if count > 0 {
+				if let Some(value) = backend.storage(prefix).map_err(map_e)? {
+					size += prefix.len() + value.len();
+					result.push((prefix.to_vec(), value));
+				}
+			}
+			prefix.to_vec()
+		},
+	};
+
+	while result.len() < count && size < max_size {
+		let next = match backend.next_storage_key(&current).map_err(map_e)? {
+			Some(next) if next.starts_with(prefix) => next,
+			_ => break,
+		};
+		let value = backend.storage(&next).map_err(map_e)?
+			.ok_or_else(|| Box::new(ExecutionError::InvalidProof) as Box<dyn Error>)?;
+		size += next.len() + value.len();
+		result.push((next.clone(), value));
+		current = next;
+	}
+
+	Ok(result)
+}
+
+/// Entry of the state of a block: the storage key of its child trie if it belongs to one, its key
+/// and its value.
+pub type StateEntry = (Option<Vec<u8>>, Vec<u8>, Vec<u8>);
+
+/// Generate proof of a part of the state of a block, read as described in `read_state_chunk`.
+///
+/// Returns the entries, whether the end of the state was reached and the proof, which contains
+/// all nodes that are required to find the entries, so that it can't be checked if any entry is
+/// omitted.
+pub fn prove_state_chunk<B, H>(
+	mut backend: B,
+	start_key: Option<&[u8]>,
+	start_child_key: Option<&[u8]>,
+	max_size: usize,
+) -> Result<(Vec<StateEntry>, bool, Vec<Vec<u8>>), Box<dyn Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	H::Out: Ord,
+{
+	let trie_backend = backend.as_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+	let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+	let (entries, complete) = read_state_chunk(&proving_backend, start_key, start_child_key, max_size)?;
+	Ok((entries, complete, proving_backend.extract_proof()))
+}
+
+/// Check proof of a part of the state of a block, generated by `prove_state_chunk` call.
+///
+/// Fails unless `entries` are exactly the entries that follow the start keys in the state with
+/// the given root, up to the end of the state if `complete` is set, or up to a valid place to
+/// resume from otherwise. Returns the nodes of the proof that were read, with their prefix, so
+/// that they can be stored like the nodes of any other trie.
+pub fn state_chunk_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	start_key: Option<&[u8]>,
+	start_child_key: Option<&[u8]>,
+	entries: &[StateEntry],
+	complete: bool,
+) -> Result<trie::PrefixedMemoryDB<H>, Box<dyn Error>>
+where
+	H: Hasher,
+	H::Out: Ord,
+{
+	let invalid_proof = || Box::new(ExecutionError::InvalidProof) as Box<dyn Error>;
+	let proving_backend = create_recording_proof_check_backend::<H>(root, proof)?;
+	let mut cursor = StateCursor::new(start_key, start_child_key);
+	for (storage_key, key, value) in entries {
+		match cursor.next_key(&proving_backend)? {
+			Some((ref next_storage_key, ref next_key))
+				if next_storage_key == storage_key && next_key == key => {},
+			_ => return Err(invalid_proof()),
+		}
+		if state_entry_value(&proving_backend, storage_key.as_ref(), key)? != *value {
+			return Err(invalid_proof())
+		}
+		cursor.advance(storage_key.clone(), key.clone());
+	}
+
+	match cursor.next_key(&proving_backend)? {
+		None if complete => {},
+		Some(_) if !complete && !entries.is_empty() && cursor.is_resumable() => {},
+		_ => return Err(invalid_proof()),
+	}
+
+	Ok(proving_backend.into_storage().into_nodes())
+}
+
+/// Read a part of the state of a block, in order. The entries of a child trie follow the entry
+/// of the main trie that contains its root.
+///
+/// Reading starts after `start_key` in the main trie, or from the beginning if it is `None`.
+/// If `start_child_key` is given, reading resumes after it in the child trie whose root is stored
+/// at `start_key`. It stops once the keys and values read exceed `max_size` bytes, but never
+/// between the entry of a non-empty child trie in the main trie and the first entry of the child
+/// trie, so that the last entry returned is always a valid place to resume from.
+///
+/// Returns the entries and whether the end of the state was reached.
+pub fn read_state_chunk<H, B>(
+	backend: &B,
+	start_key: Option<&[u8]>,
+	start_child_key: Option<&[u8]>,
+	max_size: usize,
+) -> Result<(Vec<StateEntry>, bool), Box<dyn Error>> where H: Hasher, B: Backend<H> {
+	let mut cursor = StateCursor::new(start_key, start_child_key);
+	let mut entries = Vec::new();
+	let mut size = 0;
+
+	loop {
+		let (storage_key, key) = match cursor.next_key(backend)? {
+			Some(next) => next,
+			None => return Ok((entries, true)),
+		};
+		if size >= max_size && cursor.is_resumable() {
+			return Ok((entries, false))
+		}
+		let value = state_entry_value(backend, storage_key.as_ref(), &key)?;
+		size += key.len() + value.len();
+		cursor.advance(storage_key.clone(), key.clone());
+		entries.push((storage_key, key, value));
+	}
+}
+
+/// Read the value of an entry of the state whose key was found by a `StateCursor`.
+fn state_entry_value<H, B>(
+	backend: &B,
+	storage_key: Option<&Vec<u8>>,
+	key: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> where H: Hasher, B: Backend<H> {
+	let value = match storage_key {
+		Some(storage_key) => backend.child_storage(storage_key, key),
+		None => backend.storage(key),
+	};
+	value.map_err(|e| Box::new(e) as Box<dyn Error>)?
+		.ok_or_else(|| Box::new(ExecutionError::InvalidProof) as Box<dyn Error>)
+}
+
+/// Position in the state of a block while it is read in order, after the last entry read.
+struct StateCursor {
+	/// Key of the last entry read in the main trie.
+	key: Option<Vec<u8>>,
+	/// Storage key of the child trie being read and key of the last entry read in it.
+	child: Option<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl StateCursor {
+	fn new(start_key: Option<&[u8]>, start_child_key: Option<&[u8]>) -> Self {
+		let child = match (start_key, start_child_key) {
+			(Some(storage_key), Some(child_key)) => Some((storage_key.to_vec(), Some(child_key.to_vec()))),
+			_ => None,
+		};
+		StateCursor { key: start_key.map(|key| key.to_vec()), child }
+	}
+
+	/// Find the storage key and key of the next entry, leaving the child trie being read if it
+	/// has no more entries.
+	fn next_key<H, B>(
+		&mut self,
+		backend: &B,
+	) -> Result<Option<(Option<Vec<u8>>, Vec<u8>)>, Box<dyn Error>> where H: Hasher, B: Backend<H> {
+		let map_e = |e| Box::new(e) as Box<dyn Error>;
+		if let Some((storage_key, current)) = self.child.as_ref() {
+			let key = match current {
+				Some(key) => backend.next_child_storage_key(storage_key, key).map_err(map_e)?,
+				None if backend.exists_child_storage(storage_key, &[]).map_err(map_e)? => Some(Vec::new()),
+				None => backend.next_child_storage_key(storage_key, &[]).map_err(map_e)?,
+			};
+			match key {
+				Some(key) => return Ok(Some((Some(storage_key.clone()), key))),
+				None => self.child = None,
+			}
+		}
+
+		let key = match self.key {
+			Some(ref key) => backend.next_storage_key(key).map_err(map_e)?,
+			None if backend.exists_storage(&[]).map_err(map_e)? => Some(Vec::new()),
+			None => backend.next_storage_key(&[]).map_err(map_e)?,
+		};
+		Ok(key.map(|key| (None, key)))
+	}
+
+	/// Move after the entry with the given storage key and key, entering the child trie whose
+	/// root it contains if any.
+	fn advance(&mut self, storage_key: Option<Vec<u8>>, key: Vec<u8>) {
+		match storage_key {
+			Some(storage_key) => self.child = Some((storage_key, Some(key))),
+			None => {
+				self.child = if well_known_keys::is_child_storage_key(&key) {
+					Some((key.clone(), None))
+				} else {
+					None
+				};
+				self.key = Some(key);
+			},
+		}
+	}
+
+	/// Whether reading can resume from the current position, i.e. it isn't between the entry of
+	/// a non-empty child trie and its first entry.
+	fn is_resumable(&self) -> bool {
+		self.child.as_ref().map_or(true, |(_, key)| key.is_some())
+	}
+}
+
 /// Sets overlayed changes' changes trie configuration. Returns error if configuration
 /// differs from previous OR config decode has failed.
 fn set_changes_trie_config(
@@ -1076,6 +1397,161 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn prove_read_range_and_proof_check_works() {
+		let remote_backend = trie_backend::tests::test_trie();
+		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		let remote_proof = prove_read_range(trie_backend::tests::test_trie(), b"value", None, None)
+			.unwrap();
+		assert_eq!(
+			read_range_proof_check::<Blake2Hasher>(
+				remote_root,
+				remote_proof,
+				b"value",
+				None,
+				None,
+			).unwrap(),
+			vec![(b"value1".to_vec(), vec![42]), (b"value2".to_vec(), vec![24])],
+		);
+
+		let remote_proof = prove_read_range_on_trie_backend(
+			&remote_backend,
+			b"value",
+			Some(b"value1"),
+			None,
+		).unwrap();
+		assert_eq!(
+			read_range_proof_check::<Blake2Hasher>(
+				remote_root,
+				remote_proof,
+				b"value",
+				Some(b"value1"),
+				None,
+			).unwrap(),
+			vec![(b"value2".to_vec(), vec![24])],
+		);
+
+		// a proof of the first key of the range doesn't prove that there are no more keys
+		let remote_proof = prove_read_range_on_trie_backend(&remote_backend, b"value", None, Some(1))
+			.unwrap();
+		assert_eq!(
+			read_range_proof_check::<Blake2Hasher>(
+				remote_root,
+				remote_proof.clone(),
+				b"value",
+				None,
+				Some(1),
+			).unwrap(),
+			vec![(b"value1".to_vec(), vec![42])],
+		);
+		assert!(read_range_proof_check::<Blake2Hasher>(
+			remote_root,
+			remote_proof,
+			b"value",
+			None,
+			None,
+		).is_err());
+
+		// a start key that is lower than the prefix reads the whole range
+		let remote_proof = prove_read_range_on_trie_backend(&remote_backend, b"value", Some(b"a"), None)
+			.unwrap();
+		assert_eq!(
+			read_range_proof_check::<Blake2Hasher>(
+				remote_root,
+				remote_proof,
+				b"value",
+				Some(b"a"),
+				None,
+			).unwrap(),
+			vec![(b"value1".to_vec(), vec![42]), (b"value2".to_vec(), vec![24])],
+		);
+	}
+
+	#[test]
+	fn read_range_stops_at_max_size() {
+		let backend = trie_backend::tests::test_trie();
+		assert_eq!(
+			read_range_with_max_size(&backend, b"value", None, None, 7).unwrap(),
+			vec![(b"value1".to_vec(), vec![42])],
+		);
+		assert_eq!(
+			read_range_with_max_size(&backend, b"value", None, None, 8).unwrap(),
+			vec![(b"value1".to_vec(), vec![42]), (b"value2".to_vec(), vec![24])],
+		);
+	}
+
+	#[test]
+	fn prove_state_chunk_and_proof_check_works() {
+		let remote_backend = trie_backend::tests::test_trie();
+		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+
+		// read the state in small chunks and rebuild it from the nodes of the checked proofs
+		let mut nodes = trie::PrefixedMemoryDB::<Blake2Hasher>::default();
+		let mut entries = Vec::new();
+		let (mut start_key, mut start_child_key): (Option<Vec<u8>>, Option<Vec<u8>>) = (None, None);
+		loop {
+			let (chunk, complete, proof) = prove_state_chunk(
+				trie_backend::tests::test_trie(),
+				start_key.as_ref().map(|key| &key[..]),
+				start_child_key.as_ref().map(|key| &key[..]),
+				16,
+			).unwrap();
+			let chunk_nodes = state_chunk_proof_check::<Blake2Hasher>(
+				remote_root,
+				proof.clone(),
+				start_key.as_ref().map(|key| &key[..]),
+				start_child_key.as_ref().map(|key| &key[..]),
+				&chunk,
+				complete,
+			).unwrap();
+			nodes.consolidate(chunk_nodes);
+
+			// omitting an entry or hiding the rest of the state is detected
+			assert!(state_chunk_proof_check::<Blake2Hasher>(
+				remote_root,
+				proof.clone(),
+				start_key.as_ref().map(|key| &key[..]),
+				start_child_key.as_ref().map(|key| &key[..]),
+				&chunk[1..],
+				complete,
+			).is_err());
+			if !complete {
+				assert!(state_chunk_proof_check::<Blake2Hasher>(
+					remote_root,
+					proof,
+					start_key.as_ref().map(|key| &key[..]),
+					start_child_key.as_ref().map(|key| &key[..]),
+					&chunk,
+					true,
+				).is_err());
+			}
+
+			match chunk.last() {
+				Some((Some(storage_key), key, _)) => {
+					start_key = Some(storage_key.clone());
+					start_child_key = Some(key.clone());
+				},
+				Some((None, key, _)) => {
+					start_key = Some(key.clone());
+					start_child_key = None;
+				},
+				None => unreachable!(),
+			}
+			entries.extend(chunk);
+			if complete {
+				break;
+			}
+		}
+
+		assert_eq!(entries, read_state_chunk(&remote_backend, None, None, usize::max_value()).unwrap().0);
+		let local_backend = TrieBackend::new(nodes, remote_root);
+		assert_eq!(local_backend.pairs(), remote_backend.pairs());
+		assert_eq!(
+			local_backend.child_storage(b":child_storage:default:sub1", b"value3").unwrap(),
+			Some(vec![142]),
+		);
+	}
+
 	#[test]
 	fn cannot_change_changes_trie_config() {
 		let backend = trie_backend::tests::test_trie();
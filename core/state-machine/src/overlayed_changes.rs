@@ -18,7 +18,8 @@
 
 #[cfg(test)]
 use std::iter::FromIterator;
-use std::collections::{HashMap, BTreeSet};
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use std::ops::Bound;
 use codec::Decode;
 use crate::changes_trie::{NO_EXTRINSIC_INDEX, Configuration as ChangesTrieConfig};
 use primitives::storage::well_known_keys::EXTRINSIC_INDEX;
@@ -27,12 +28,18 @@ use primitives::storage::well_known_keys::EXTRINSIC_INDEX;
 ///
 /// A transaction shares all prospective changes within an inner overlay
 /// that can be cleared.
+///
+/// Storage transactions can be nested on top of the prospective changes. Every open storage
+/// transaction records the prospective values it overwrites, so that its changes can be
+/// rolled back without touching the changes made before it was started.
 #[derive(Debug, Default, Clone)]
 pub struct OverlayedChanges {
 	/// Changes that are not yet committed.
 	pub(crate) prospective: OverlayedChangeSet,
 	/// Committed changes.
 	pub(crate) committed: OverlayedChangeSet,
+	/// The open storage transactions, innermost last.
+	pub(crate) transactions: Vec<TransactionJournal>,
 	/// Changes trie configuration. None by default, but could be installed by the
 	/// runtime if it supports change tries.
 	pub(crate) changes_trie_config: Option<ChangesTrieConfig>,
@@ -54,9 +61,98 @@ pub struct OverlayedValue {
 #[cfg_attr(test, derive(PartialEq))]
 pub struct OverlayedChangeSet {
 	/// Top level storage changes.
-	pub top: HashMap<Vec<u8>, OverlayedValue>,
+	pub top: BTreeMap<Vec<u8>, OverlayedValue>,
 	/// Child storage changes.
-	pub children: HashMap<Vec<u8>, HashMap<Vec<u8>, OverlayedValue>>,
+	pub children: HashMap<Vec<u8>, BTreeMap<Vec<u8>, OverlayedValue>>,
+}
+
+/// The prospective values that were overwritten during a storage transaction.
+///
+/// Only the first value of every key is recorded, rolling back the transaction restores the
+/// prospective change set to the state it had when the transaction was started.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TransactionJournal {
+	/// Prospective values of top level keys, `None` if the key had no prospective value.
+	top: HashMap<Vec<u8>, Option<OverlayedValue>>,
+	/// Prospective values of child keys, per storage key.
+	children: HashMap<Vec<u8>, ChildJournal>,
+}
+
+/// The prospective values of a child storage that were overwritten during a storage transaction.
+#[derive(Debug, Default, Clone)]
+struct ChildJournal {
+	/// Whether the prospective change set contained this child storage.
+	existed: bool,
+	/// Prospective values of the child keys, `None` if the key had no prospective value.
+	values: HashMap<Vec<u8>, Option<OverlayedValue>>,
+}
+
+/// Error returned when committing or rolling back a storage transaction while none is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoOpenTransaction;
+
+impl TransactionJournal {
+	/// Record the prospective value of `key`, unless it is already recorded.
+	fn record_top(&mut self, prospective: &OverlayedChangeSet, key: &[u8]) {
+		if !self.top.contains_key(key) {
+			self.top.insert(key.to_vec(), prospective.top.get(key).cloned());
+		}
+	}
+
+	/// Record the prospective values of `keys` in the child storage `storage_key`, unless they are
+	/// already recorded.
+	fn record_child<'a>(
+		&mut self,
+		prospective: &OverlayedChangeSet,
+		storage_key: &[u8],
+		keys: impl Iterator<Item=&'a [u8]>,
+	) {
+		let child = prospective.children.get(storage_key);
+		let journal = self.children.entry(storage_key.to_vec())
+			.or_insert_with(|| ChildJournal { existed: child.is_some(), values: HashMap::new() });
+		for key in keys {
+			if !journal.values.contains_key(key) {
+				journal.values.insert(key.to_vec(), child.and_then(|child| child.get(key)).cloned());
+			}
+		}
+	}
+
+	/// Merge the journal of a committed inner transaction into this one.
+	fn merge(&mut self, inner: TransactionJournal) {
+		for (key, value) in inner.top {
+			self.top.entry(key).or_insert(value);
+		}
+		for (storage_key, inner_child) in inner.children {
+			let child = self.children.entry(storage_key)
+				.or_insert_with(|| ChildJournal { existed: inner_child.existed, values: HashMap::new() });
+			for (key, value) in inner_child.values {
+				child.values.entry(key).or_insert(value);
+			}
+		}
+	}
+
+	/// Restore the recorded values in the given prospective change set.
+	fn revert(self, prospective: &mut OverlayedChangeSet) {
+		for (key, value) in self.top {
+			match value {
+				Some(value) => { prospective.top.insert(key, value); },
+				None => { prospective.top.remove(&key); },
+			}
+		}
+		for (storage_key, child) in self.children {
+			if !child.existed {
+				prospective.children.remove(&storage_key);
+				continue;
+			}
+			let map = prospective.children.entry(storage_key).or_default();
+			for (key, value) in child.values {
+				match value {
+					Some(value) => { map.insert(key, value); },
+					None => { map.remove(&key); },
+				}
+			}
+		}
+	}
 }
 
 #[cfg(test)]
@@ -132,11 +228,52 @@ impl OverlayedChanges {
 		None
 	}
 
+	/// Returns the next (in lexicographic order) key that has a change in the overlay, together
+	/// with its value, or `None` if there is no such key.
+	///
+	/// The value is `None` if the key has been deleted.
+	pub fn next_storage_key_change(&self, key: &[u8]) -> Option<(&[u8], &OverlayedValue)> {
+		let range = (Bound::Excluded(key), Bound::Unbounded);
+
+		let next_prospective_key = self.prospective.top
+			.range::<[u8], _>(range)
+			.next()
+			.map(|(k, v)| (&k[..], v));
+
+		let next_committed_key = self.committed.top
+			.range::<[u8], _>(range)
+			.next()
+			.map(|(k, v)| (&k[..], v));
+
+		next_change(next_prospective_key, next_committed_key)
+	}
+
+	/// Returns the next (in lexicographic order) child key that has a change in the overlay,
+	/// together with its value, or `None` if there is no such key.
+	///
+	/// The value is `None` if the key has been deleted.
+	pub fn next_child_storage_key_change(
+		&self,
+		storage_key: &[u8],
+		key: &[u8]
+	) -> Option<(&[u8], &OverlayedValue)> {
+		let range = (Bound::Excluded(key), Bound::Unbounded);
+
+		let next_prospective_key = self.prospective.children.get(storage_key)
+			.and_then(|map| map.range::<[u8], _>(range).next().map(|(k, v)| (&k[..], v)));
+
+		let next_committed_key = self.committed.children.get(storage_key)
+			.and_then(|map| map.range::<[u8], _>(range).next().map(|(k, v)| (&k[..], v)));
+
+		next_change(next_prospective_key, next_committed_key)
+	}
+
 	/// Inserts the given key-value pair into the prospective change set.
 	///
 	/// `None` can be used to delete a value specified by the given key.
 	pub(crate) fn set_storage(&mut self, key: Vec<u8>, val: Option<Vec<u8>>) {
 		let extrinsic_index = self.extrinsic_index();
+		self.record_top(&key);
 		let entry = self.prospective.top.entry(key).or_default();
 		entry.value = val;
 
@@ -151,6 +288,7 @@ impl OverlayedChanges {
 	/// `None` can be used to delete a value specified by the given key.
 	pub(crate) fn set_child_storage(&mut self, storage_key: Vec<u8>, key: Vec<u8>, val: Option<Vec<u8>>) {
 		let extrinsic_index = self.extrinsic_index();
+		self.record_child(&storage_key, std::iter::once(&key[..]));
 		let map_entry = self.prospective.children.entry(storage_key).or_default();
 		let entry = map_entry.entry(key).or_default();
 		entry.value = val;
@@ -169,6 +307,10 @@ impl OverlayedChanges {
 	/// [`discard_prospective`]: #method.discard_prospective
 	pub(crate) fn clear_child_storage(&mut self, storage_key: &[u8]) {
 		let extrinsic_index = self.extrinsic_index();
+		if !self.transactions.is_empty() {
+			let keys = self.child_keys(storage_key, |_| true);
+			self.record_child(storage_key, keys.iter().map(AsRef::as_ref));
+		}
 		let map_entry = self.prospective.children.entry(storage_key.to_vec()).or_default();
 
 		map_entry.values_mut().for_each(|e| {
@@ -205,6 +347,14 @@ impl OverlayedChanges {
 	/// [`discard_prospective`]: #method.discard_prospective
 	pub(crate) fn clear_prefix(&mut self, prefix: &[u8]) {
 		let extrinsic_index = self.extrinsic_index();
+		if !self.transactions.is_empty() {
+			let keys = self.prospective.top.keys()
+				.chain(self.committed.top.keys())
+				.filter(|key| key.starts_with(prefix))
+				.cloned()
+				.collect::<Vec<_>>();
+			keys.iter().for_each(|key| self.record_top(key));
+		}
 
 		// Iterate over all prospective and mark all keys that share
 		// the given prefix as removed (None).
@@ -236,6 +386,10 @@ impl OverlayedChanges {
 
 	pub(crate) fn clear_child_prefix(&mut self, storage_key: &[u8], prefix: &[u8]) {
 		let extrinsic_index = self.extrinsic_index();
+		if !self.transactions.is_empty() {
+			let keys = self.child_keys(storage_key, |key| key.starts_with(prefix));
+			self.record_child(storage_key, keys.iter().map(AsRef::as_ref));
+		}
 		let map_entry = self.prospective.children.entry(storage_key.to_vec()).or_default();
 
 		for (key, entry) in map_entry.iter_mut() {
@@ -266,17 +420,87 @@ impl OverlayedChanges {
 		}
 	}
 
+	/// Start a new storage transaction.
+	///
+	/// The changes made from now on can be reverted by [`rollback_transaction`] or kept by
+	/// [`commit_transaction`]. Transactions can be nested.
+	///
+	/// [`rollback_transaction`]: #method.rollback_transaction
+	/// [`commit_transaction`]: #method.commit_transaction
+	pub fn start_transaction(&mut self) {
+		self.transactions.push(Default::default());
+	}
+
+	/// Revert the changes made since the innermost open storage transaction was started and close
+	/// it.
+	pub fn rollback_transaction(&mut self) -> Result<(), NoOpenTransaction> {
+		let journal = self.transactions.pop().ok_or(NoOpenTransaction)?;
+		journal.revert(&mut self.prospective);
+		Ok(())
+	}
+
+	/// Keep the changes made since the innermost open storage transaction was started and close
+	/// it.
+	///
+	/// The changes become part of the enclosing transaction, if any, and are reverted if that one
+	/// is rolled back.
+	pub fn commit_transaction(&mut self) -> Result<(), NoOpenTransaction> {
+		let journal = self.transactions.pop().ok_or(NoOpenTransaction)?;
+		if let Some(outer) = self.transactions.last_mut() {
+			outer.merge(journal);
+		}
+		Ok(())
+	}
+
+	/// Returns the number of open storage transactions.
+	pub fn transaction_depth(&self) -> usize {
+		self.transactions.len()
+	}
+
+	/// Record the prospective value of `key` in the innermost open storage transaction.
+	fn record_top(&mut self, key: &[u8]) {
+		if let Some(journal) = self.transactions.last_mut() {
+			journal.record_top(&self.prospective, key);
+		}
+	}
+
+	/// Record the prospective values of `keys` in the child storage `storage_key` in the
+	/// innermost open storage transaction.
+	fn record_child<'a>(&mut self, storage_key: &[u8], keys: impl Iterator<Item=&'a [u8]>) {
+		if let Some(journal) = self.transactions.last_mut() {
+			journal.record_child(&self.prospective, storage_key, keys);
+		}
+	}
+
+	/// Returns the prospective and committed keys of the child storage `storage_key` that match
+	/// `filter`.
+	fn child_keys(&self, storage_key: &[u8], filter: impl Fn(&[u8]) -> bool) -> Vec<Vec<u8>> {
+		self.prospective.children.get(storage_key).into_iter()
+			.chain(self.committed.children.get(storage_key))
+			.flat_map(|map| map.keys())
+			.filter(|key| filter(key))
+			.cloned()
+			.collect()
+	}
+
 	/// Discard prospective changes to state.
+	///
+	/// This also closes all open storage transactions.
 	pub fn discard_prospective(&mut self) {
 		self.prospective.clear();
+		self.transactions.clear();
 	}
 
 	/// Commit prospective changes to state.
+	///
+	/// This also closes all open storage transactions, keeping their changes.
 	pub fn commit_prospective(&mut self) {
+		self.transactions.clear();
 		if self.committed.is_empty() {
 			::std::mem::swap(&mut self.prospective, &mut self.committed);
 		} else {
-			for (key, val) in self.prospective.top.drain() {
+			let top = ::std::mem::replace(&mut self.prospective.top, BTreeMap::new());
+			for (key, val) in top.into_iter() {
 				let entry = self.committed.top.entry(key).or_default();
 				entry.value = val.value;
 
@@ -285,9 +509,9 @@ impl OverlayedChanges {
 						.extend(prospective_extrinsics);
 				}
 			}
-			for (storage_key, mut map) in self.prospective.children.drain() {
+			for (storage_key, map) in self.prospective.children.drain() {
 				let map_dest = self.committed.children.entry(storage_key).or_default();
-				for (key, val) in map.drain() {
+				for (key, val) in map.into_iter() {
 					let entry = map_dest.entry(key).or_default();
 					entry.value = val.value;
 
@@ -341,6 +565,20 @@ impl OverlayedChanges {
 	}
 }
 
+/// Returns the smaller of the next prospective and next committed change, preferring the
+/// prospective change if both are for the same key.
+fn next_change<'a>(
+	next_prospective_key: Option<(&'a [u8], &'a OverlayedValue)>,
+	next_committed_key: Option<(&'a [u8], &'a OverlayedValue)>,
+) -> Option<(&'a [u8], &'a OverlayedValue)> {
+	match (next_committed_key, next_prospective_key) {
+		(Some(committed_key), Some(prospective_key)) if committed_key.0 < prospective_key.0 =>
+			Some(committed_key),
+		(committed_key, None) => committed_key,
+		(_, prospective_key) => prospective_key,
+	}
+}
+
 #[cfg(test)]
 impl From<Option<Vec<u8>>> for OverlayedValue {
 	fn from(value: Option<Vec<u8>>) -> OverlayedValue {
@@ -359,7 +597,7 @@ mod tests {
 	use crate::ext::Ext;
 	use super::*;
 
-	fn strip_extrinsic_index(map: &HashMap<Vec<u8>, OverlayedValue>) -> HashMap<Vec<u8>, OverlayedValue> {
+	fn strip_extrinsic_index(map: &BTreeMap<Vec<u8>, OverlayedValue>) -> BTreeMap<Vec<u8>, OverlayedValue> {
 		let mut clone = map.clone();
 		clone.remove(&EXTRINSIC_INDEX.to_vec());
 		clone
@@ -393,6 +631,80 @@ mod tests {
 		assert!(overlayed.storage(&key).unwrap().is_none());
 	}
 
+	#[test]
+	fn storage_transactions_work() {
+		let mut overlayed = OverlayedChanges::default();
+
+		let key = vec![42, 69, 169, 142];
+		let other = vec![42, 69, 169, 143];
+
+		overlayed.set_storage(key.clone(), Some(vec![1]));
+		overlayed.commit_prospective();
+		overlayed.set_storage(key.clone(), Some(vec![2]));
+
+		overlayed.start_transaction();
+		overlayed.set_storage(key.clone(), Some(vec![3]));
+		overlayed.set_storage(other.clone(), Some(vec![3]));
+
+		overlayed.start_transaction();
+		overlayed.set_storage(key.clone(), None);
+		assert!(overlayed.storage(&key).unwrap().is_none());
+		assert_eq!(overlayed.transaction_depth(), 2);
+		assert_eq!(overlayed.rollback_transaction(), Ok(()));
+		assert_eq!(overlayed.storage(&key).unwrap(), Some(&[3][..]));
+
+		overlayed.start_transaction();
+		overlayed.set_storage(other.clone(), Some(vec![4]));
+		assert_eq!(overlayed.commit_transaction(), Ok(()));
+		assert_eq!(overlayed.storage(&other).unwrap(), Some(&[4][..]));
+
+		// Rolling back the outer transaction reverts the committed inner transaction as well.
+		assert_eq!(overlayed.rollback_transaction(), Ok(()));
+		assert_eq!(overlayed.storage(&key).unwrap(), Some(&[2][..]));
+		assert!(overlayed.storage(&other).is_none());
+		assert!(!overlayed.prospective.top.contains_key(&other));
+
+		assert_eq!(overlayed.rollback_transaction(), Err(NoOpenTransaction));
+		assert_eq!(overlayed.commit_transaction(), Err(NoOpenTransaction));
+	}
+
+	#[test]
+	fn storage_transactions_revert_cleared_prefixes_and_children() {
+		let mut overlayed = OverlayedChanges::default();
+		let child = b":child_storage:default:child".to_vec();
+
+		overlayed.set_storage(vec![1, 1], Some(vec![1]));
+		overlayed.set_child_storage(child.clone(), vec![1], Some(vec![1]));
+		overlayed.commit_prospective();
+		overlayed.set_storage(vec![1, 2], Some(vec![2]));
+
+		overlayed.start_transaction();
+		overlayed.clear_prefix(&[1]);
+		overlayed.clear_child_storage(&child);
+		overlayed.set_child_storage(b":child_storage:default:other".to_vec(), vec![1], Some(vec![1]));
+		assert!(overlayed.storage(&[1, 1]).unwrap().is_none());
+		assert!(overlayed.storage(&[1, 2]).unwrap().is_none());
+		assert!(overlayed.child_storage(&child, &[1]).unwrap().is_none());
+		assert_eq!(overlayed.rollback_transaction(), Ok(()));
+
+		assert_eq!(overlayed.storage(&[1, 1]).unwrap(), Some(&[1][..]));
+		assert_eq!(overlayed.storage(&[1, 2]).unwrap(), Some(&[2][..]));
+		assert_eq!(overlayed.child_storage(&child, &[1]).unwrap(), Some(&[1][..]));
+		assert!(overlayed.prospective.children.is_empty());
+	}
+
+	#[test]
+	fn discarding_prospective_closes_storage_transactions() {
+		let mut overlayed = OverlayedChanges::default();
+
+		overlayed.start_transaction();
+		overlayed.set_storage(vec![1], Some(vec![1]));
+		overlayed.discard_prospective();
+
+		assert_eq!(overlayed.transaction_depth(), 0);
+		assert!(overlayed.storage(&[1]).is_none());
+	}
+
 	#[test]
 	fn overlayed_storage_root_works() {
 		let initial: HashMap<_, _> = vec![
@@ -529,4 +841,74 @@ mod tests {
 		assert_eq!(overlay.prospective,
 			Default::default());
 	}
+
+	#[test]
+	fn next_storage_key_change_works() {
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_storage(vec![20], Some(vec![20]));
+		overlay.set_storage(vec![30], Some(vec![30]));
+		overlay.set_storage(vec![40], Some(vec![40]));
+		overlay.commit_prospective();
+		overlay.set_storage(vec![10], Some(vec![10]));
+		overlay.set_storage(vec![30], None);
+
+		// next_prospective < next_committed
+		let next_to_5 = overlay.next_storage_key_change(&[5]).unwrap();
+		assert_eq!(next_to_5.0.to_vec(), vec![10]);
+		assert_eq!(next_to_5.1.value, Some(vec![10]));
+
+		// next_committed < next_prospective
+		let next_to_10 = overlay.next_storage_key_change(&[10]).unwrap();
+		assert_eq!(next_to_10.0.to_vec(), vec![20]);
+		assert_eq!(next_to_10.1.value, Some(vec![20]));
+
+		// next_committed == next_prospective
+		let next_to_20 = overlay.next_storage_key_change(&[20]).unwrap();
+		assert_eq!(next_to_20.0.to_vec(), vec![30]);
+		assert_eq!(next_to_20.1.value, None);
+
+		// next_committed, no next_prospective
+		let next_to_30 = overlay.next_storage_key_change(&[30]).unwrap();
+		assert_eq!(next_to_30.0.to_vec(), vec![40]);
+		assert_eq!(next_to_30.1.value, Some(vec![40]));
+
+		overlay.set_storage(vec![50], Some(vec![50]));
+		// next_prospective, no next_committed
+		let next_to_40 = overlay.next_storage_key_change(&[40]).unwrap();
+		assert_eq!(next_to_40.0.to_vec(), vec![50]);
+		assert_eq!(next_to_40.1.value, Some(vec![50]));
+
+		assert!(overlay.next_storage_key_change(&[50]).is_none());
+	}
+
+	#[test]
+	fn next_child_storage_key_change_works() {
+		let child = b"Child1".to_vec();
+		let mut overlay = OverlayedChanges::default();
+		overlay.set_child_storage(child.clone(), vec![20], Some(vec![20]));
+		overlay.set_child_storage(child.clone(), vec![30], Some(vec![30]));
+		overlay.set_child_storage(child.clone(), vec![40], Some(vec![40]));
+		overlay.commit_prospective();
+		overlay.set_child_storage(child.clone(), vec![10], Some(vec![10]));
+		overlay.set_child_storage(child.clone(), vec![30], None);
+
+		let next_to_5 = overlay.next_child_storage_key_change(&child, &[5]).unwrap();
+		assert_eq!(next_to_5.0.to_vec(), vec![10]);
+		assert_eq!(next_to_5.1.value, Some(vec![10]));
+
+		let next_to_10 = overlay.next_child_storage_key_change(&child, &[10]).unwrap();
+		assert_eq!(next_to_10.0.to_vec(), vec![20]);
+		assert_eq!(next_to_10.1.value, Some(vec![20]));
+
+		let next_to_20 = overlay.next_child_storage_key_change(&child, &[20]).unwrap();
+		assert_eq!(next_to_20.0.to_vec(), vec![30]);
+		assert_eq!(next_to_20.1.value, None);
+
+		let next_to_30 = overlay.next_child_storage_key_change(&child, &[30]).unwrap();
+		assert_eq!(next_to_30.0.to_vec(), vec![40]);
+		assert_eq!(next_to_30.1.value, Some(vec![40]));
+
+		assert!(overlay.next_child_storage_key_change(&child, &[40]).is_none());
+		assert!(overlay.next_child_storage_key_change(b"Child2", &[0]).is_none());
+	}
 }
@@ -18,15 +18,19 @@
 
 use std::{cell::RefCell, rc::Rc};
 use log::debug;
-use hash_db::{Hasher, HashDB, EMPTY_PREFIX};
+use parking_lot::RwLock;
+use hash_db::{Hasher, HashDB, EMPTY_PREFIX, Prefix};
 use trie::{
-	MemoryDB, PrefixedMemoryDB, default_child_trie_root,
-	read_trie_value_with, read_child_trie_value_with, record_all_keys
+	MemoryDB, PrefixedMemoryDB, DBValue, default_child_trie_root, decode_child_trie_value,
+	read_trie_value_with, read_child_trie_value_with, record_all_keys,
+	encode_compact_proof, decode_compact_proof,
 };
 pub use trie::Recorder;
 pub use trie::trie_types::{Layout, TrieError};
 use crate::trie_backend::TrieBackend;
-use crate::trie_backend_essence::{Ephemeral, TrieBackendEssence, TrieBackendStorage};
+use crate::trie_backend_essence::{
+	Ephemeral, TrieBackendEssence, TrieBackendStorage, next_key_bound,
+};
 use crate::{Error, ExecutionError, Backend};
 
 /// Patricia trie-based backend essence which also tracks all touched storage trie values.
@@ -63,8 +67,13 @@ impl<'a, S, H> ProvingBackendEssence<'a, S, H>
 		storage_key: &[u8],
 		key: &[u8]
 	) -> Result<Option<Vec<u8>>, String> {
-		let root = self.storage(storage_key)?
-			.unwrap_or(default_child_trie_root::<Layout<H>>(storage_key));
+		let (root, unique_id) = match self.storage(storage_key)? {
+			Some(value) => {
+				let (root, unique_id) = decode_child_trie_value::<Layout<H>>(&value);
+				(root.to_vec(), unique_id.to_vec())
+			},
+			None => (default_child_trie_root::<Layout<H>>(storage_key), Vec::new()),
+		};
 
 		let mut read_overlay = S::Overlay::default();
 		let eph = Ephemeral::new(
@@ -76,6 +85,7 @@ impl<'a, S, H> ProvingBackendEssence<'a, S, H>
 
 		read_child_trie_value_with::<Layout<H>, _, _>(
 			storage_key,
+			&unique_id,
 			&eph,
 			&root,
 			key,
@@ -83,6 +93,33 @@ impl<'a, S, H> ProvingBackendEssence<'a, S, H>
 		).map_err(map_e)
 	}
 
+	/// Return the next key in storage after `key` and record the trie nodes that prove it.
+	///
+	/// The iteration visits the path to `key++0` and the path from there to the next key, so
+	/// looking up both of them records every node that is needed to check the result.
+	pub fn next_storage_key(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+		let next_key = self.backend.next_storage_key(key)?;
+		self.storage(&next_key_bound(key))?;
+		if let Some(next_key) = next_key.as_ref() {
+			self.storage(next_key)?;
+		}
+		Ok(next_key)
+	}
+
+	/// Return the next key in child storage after `key` and record the trie nodes that prove it.
+	pub fn next_child_storage_key(
+		&mut self,
+		storage_key: &[u8],
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, String> {
+		let next_key = self.backend.next_child_storage_key(storage_key, key)?;
+		self.child_storage(storage_key, &next_key_bound(key))?;
+		if let Some(next_key) = next_key.as_ref() {
+			self.child_storage(storage_key, next_key)?;
+		}
+		Ok(next_key)
+	}
+
 	pub fn record_all_keys(&mut self) {
 		let mut read_overlay = S::Overlay::default();
 		let eph = Ephemeral::new(
@@ -137,6 +174,14 @@ impl<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> ProvingBackend<'a, S, H>
 			.map(|n| n.data.to_vec())
 			.collect()
 	}
+
+	/// Consume the backend, extracting the gathered proof in the compact encoding.
+	///
+	/// The proof can be checked with [`create_compact_proof_check_backend`].
+	pub fn extract_compact_proof(&self) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+		encode_compact_proof::<H>(self.backend.root(), &self.extract_proof())
+			.map_err(|e| Box::new(e) as Box<dyn Error>)
+	}
 }
 
 impl<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> std::fmt::Debug for ProvingBackend<'a, S, H> {
@@ -171,6 +216,26 @@ impl<'a, S, H> Backend<H> for ProvingBackend<'a, S, H>
 		}.child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		ProvingBackendEssence {
+			backend: self.backend.essence(),
+			proof_recorder: &mut *self.proof_recorder.try_borrow_mut()
+				.expect("only fails when already borrowed; next_storage_key() is non-reentrant; qed"),
+		}.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8]
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		ProvingBackendEssence {
+			backend: self.backend.essence(),
+			proof_recorder: &mut *self.proof_recorder.try_borrow_mut()
+				.expect("only fails when already borrowed; next_child_storage_key() is non-reentrant; qed"),
+		}.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
 		self.backend.for_keys_in_child_storage(storage_key, f)
 	}
@@ -205,12 +270,17 @@ impl<'a, S, H> Backend<H> for ProvingBackend<'a, S, H>
 		self.backend.storage_root(delta)
 	}
 
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 	where
 		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 		H::Out: Ord
 	{
-		self.backend.child_storage_root(storage_key, delta)
+		self.backend.child_storage_root(storage_key, delta, killed)
 	}
 }
 
@@ -231,6 +301,70 @@ where
 	}
 }
 
+/// Create proof check backend from a proof in the compact encoding.
+pub fn create_compact_proof_check_backend<H>(
+	root: H::Out,
+	compact_proof: Vec<Vec<u8>>,
+) -> Result<TrieBackend<MemoryDB<H>, H>, Box<dyn Error>>
+where
+	H: Hasher,
+{
+	let (proof_root, proof) = decode_compact_proof::<H>(&compact_proof)
+		.map_err(|_| Box::new(ExecutionError::InvalidProof) as Box<dyn Error>)?;
+
+	if proof_root == root {
+		create_proof_check_backend(root, proof)
+	} else {
+		Err(Box::new(ExecutionError::InvalidProof))
+	}
+}
+
+/// Create proof check backend that keeps the nodes read from the proof.
+pub fn create_recording_proof_check_backend<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+) -> Result<TrieBackend<ProofRecordingStorage<H>, H>, Box<dyn Error>>
+where
+	H: Hasher,
+{
+	let proof = create_proof_check_backend_storage(proof);
+
+	if proof.contains(&root, EMPTY_PREFIX) {
+		Ok(TrieBackend::new(ProofRecordingStorage { proof, nodes: Default::default() }, root))
+	} else {
+		Err(Box::new(ExecutionError::InvalidProof))
+	}
+}
+
+/// Storage of a proof check backend that keeps the nodes read from the proof with their prefix,
+/// so that they can be stored in a database like the nodes of any other trie.
+pub struct ProofRecordingStorage<H: Hasher> {
+	proof: MemoryDB<H>,
+	nodes: RwLock<PrefixedMemoryDB<H>>,
+}
+
+impl<H: Hasher> ProofRecordingStorage<H> {
+	/// Consume the storage, returning the nodes that were read.
+	pub fn into_nodes(self) -> PrefixedMemoryDB<H> {
+		self.nodes.into_inner()
+	}
+}
+
+impl<H: Hasher> TrieBackendStorage<H> for ProofRecordingStorage<H> {
+	type Overlay = PrefixedMemoryDB<H>;
+
+	fn get(&self, key: &H::Out, prefix: Prefix) -> Result<Option<DBValue>, String> {
+		let value = HashDB::get(&self.proof, key, prefix);
+		if let Some(value) = value.as_ref() {
+			let mut nodes = self.nodes.write();
+			if !HashDB::contains(&*nodes, key, prefix) {
+				nodes.emplace(key.clone(), prefix, value.clone());
+			}
+		}
+		Ok(value)
+	}
+}
+
 /// Create in-memory storage of proof check backend.
 pub fn create_proof_check_backend_storage<H>(
 	proof: Vec<Vec<u8>>
@@ -291,6 +425,26 @@ mod tests {
 		assert_eq!(trie_mdb.drain(), proving_mdb.drain());
 	}
 
+	#[test]
+	fn next_storage_key_proof_recorded_and_checked() {
+		let contents = (0..64).map(|i| (None, vec![i * 2], Some(vec![i]))).collect::<Vec<_>>();
+		let in_memory = InMemory::<Blake2Hasher>::default();
+		let mut in_memory = in_memory.update(contents);
+		let trie = in_memory.as_trie_backend().unwrap();
+		let trie_root = trie.storage_root(::std::iter::empty()).0;
+
+		let proving = ProvingBackend::new(trie);
+		assert_eq!(proving.next_storage_key(&[41]).unwrap(), Some(vec![42]));
+		assert_eq!(proving.next_storage_key(&[42]).unwrap(), Some(vec![44]));
+		assert_eq!(proving.next_storage_key(&[126]).unwrap(), None);
+
+		let proof = proving.extract_proof();
+		let proof_check = create_proof_check_backend::<Blake2Hasher>(trie_root, proof).unwrap();
+		assert_eq!(proof_check.next_storage_key(&[41]).unwrap(), Some(vec![42]));
+		assert_eq!(proof_check.next_storage_key(&[42]).unwrap(), Some(vec![44]));
+		assert_eq!(proof_check.next_storage_key(&[126]).unwrap(), None);
+	}
+
 	#[test]
 	fn proof_recorded_and_checked() {
 		let contents = (0..64).map(|i| (None, vec![i], Some(vec![i]))).collect::<Vec<_>>();
@@ -377,6 +531,57 @@ mod tests {
 			proof_check.child_storage(&own1[..], &[64]).unwrap().unwrap(),
 			vec![64]
 		);
+
+		let proving = ProvingBackend::new(trie);
+		assert_eq!(proving.storage(&[42]).unwrap().unwrap(), vec![42]);
+		assert_eq!(proving.child_storage(&own1[..], &[64]), Ok(Some(vec![64])));
+
+		let proof = proving.extract_compact_proof().unwrap();
+		let proof_check = create_compact_proof_check_backend::<Blake2Hasher>(
+			in_memory_root.into(),
+			proof
+		).unwrap();
+		assert_eq!(proof_check.storage(&[42]).unwrap().unwrap(), vec![42]);
+		assert_eq!(
+			proof_check.child_storage(&own1[..], &[64]).unwrap().unwrap(),
+			vec![64]
+		);
 	}
 
+	#[test]
+	fn compact_proof_is_smaller_and_checked() {
+		let contents = (0..64).map(|i| (None, vec![i], Some(vec![i]))).collect::<Vec<_>>();
+		let in_memory = InMemory::<Blake2Hasher>::default();
+		let mut in_memory = in_memory.update(contents);
+		let trie = in_memory.as_trie_backend().unwrap();
+		let trie_root = trie.storage_root(::std::iter::empty()).0;
+
+		let read = |proving: &ProvingBackend<MemoryDB<Blake2Hasher>, Blake2Hasher>| {
+			assert_eq!(proving.storage(&[3]).unwrap().unwrap(), vec![3]);
+			assert_eq!(proving.storage(&[42]).unwrap().unwrap(), vec![42]);
+		};
+
+		let proving = ProvingBackend::new(trie);
+		read(&proving);
+		let proof = proving.extract_proof();
+
+		let proving = ProvingBackend::new(trie);
+		read(&proving);
+		let compact_proof = proving.extract_compact_proof().unwrap();
+
+		let size = |proof: &Vec<Vec<u8>>| proof.iter().map(|node| node.len()).sum::<usize>();
+		assert!(size(&compact_proof) < size(&proof));
+
+		let proof_check = create_compact_proof_check_backend::<Blake2Hasher>(
+			trie_root,
+			compact_proof.clone(),
+		).unwrap();
+		assert_eq!(proof_check.storage(&[3]).unwrap().unwrap(), vec![3]);
+		assert_eq!(proof_check.storage(&[42]).unwrap().unwrap(), vec![42]);
+
+		assert!(create_compact_proof_check_backend::<Blake2Hasher>(
+			Default::default(),
+			compact_proof,
+		).is_err());
+	}
 }
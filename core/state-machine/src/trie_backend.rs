@@ -18,8 +18,12 @@
 
 use log::{warn, debug};
 use hash_db::Hasher;
-use trie::{Trie, delta_trie_root, default_child_trie_root, child_delta_trie_root};
+use trie::{
+	Trie, delta_trie_root, default_child_trie_root, child_delta_trie_root, child_trie_unique_id,
+	encode_child_trie_value, decode_child_trie_value,
+};
 use trie::trie_types::{TrieDB, TrieError, Layout};
+use primitives::storage::well_known_keys;
 use crate::trie_backend_essence::{TrieBackendEssence, TrieBackendStorage, Ephemeral};
 use crate::Backend;
 
@@ -36,6 +40,18 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackend<S, H> {
 		}
 	}
 
+	/// The unique id of a new child trie at `storage_key`, empty if unique ids are not enabled.
+	fn new_child_unique_id(&self, storage_key: &[u8]) -> Vec<u8> {
+		match self.essence.storage(well_known_keys::CHILD_TRIE_UNIQUE_IDS) {
+			Ok(Some(_)) => child_trie_unique_id::<Layout<H>>(storage_key, self.essence.root().as_ref()),
+			Ok(None) => Vec::new(),
+			Err(e) => {
+				warn!(target: "trie", "Failed to read whether child tries have unique ids: {}", e);
+				Vec::new()
+			},
+		}
+	}
+
 	/// Get backend essence reference.
 	pub fn essence(&self) -> &TrieBackendEssence<S, H> {
 		&self.essence
@@ -78,6 +94,18 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 		self.essence.child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.essence.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8]
+	) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.essence.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.essence.for_keys_with_prefix(prefix, f)
 	}
@@ -159,7 +187,12 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 		(root, write_overlay)
 	}
 
-	fn child_storage_root<I>(&self, storage_key: &[u8], delta: I) -> (Vec<u8>, bool, Self::Transaction)
+	fn child_storage_root<I>(
+		&self,
+		storage_key: &[u8],
+		delta: I,
+		killed: bool,
+	) -> (Vec<u8>, bool, Self::Transaction)
 	where
 		I: IntoIterator<Item=(Vec<u8>, Option<Vec<u8>>)>,
 		H::Out: Ord
@@ -167,12 +200,24 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 		let default_root = default_child_trie_root::<Layout<H>>(storage_key);
 
 		let mut write_overlay = S::Overlay::default();
-		let mut root = match self.storage(storage_key) {
-			Ok(value) => value.unwrap_or(default_root.clone()),
-			Err(e) => {
-				warn!(target: "trie", "Failed to read child storage root: {}", e);
-				default_root.clone()
+		let existing = if killed {
+			None
+		} else {
+			match self.storage(storage_key) {
+				Ok(value) => value,
+				Err(e) => {
+					warn!(target: "trie", "Failed to read child storage root: {}", e);
+					None
+				},
+			}
+		};
+		// a new child trie starts from the default root in a key space of its own.
+		let (mut root, unique_id) = match existing {
+			Some(value) => {
+				let (root, unique_id) = decode_child_trie_value::<Layout<H>>(&value);
+				(root.to_vec(), unique_id.to_vec())
 			},
+			None => (default_root.clone(), self.new_child_unique_id(storage_key)),
 		};
 
 		{
@@ -183,6 +228,7 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 
 			match child_delta_trie_root::<Layout<H>, _, _, _, _>(
 				storage_key,
+				&unique_id,
 				&mut eph,
 				root.clone(),
 				delta
@@ -194,7 +240,7 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 
 		let is_default = root == default_root;
 
-		(root, is_default, write_overlay)
+		(encode_child_trie_value(&root, &unique_id), is_default, write_overlay)
 	}
 
 	fn as_trie_backend(&mut self) -> Option<&TrieBackend<Self::TrieBackendStorage, H>> {
@@ -295,4 +341,16 @@ pub mod tests {
 		expected.insert(b"value2".to_vec());
 		assert_eq!(seen, expected);
 	}
+
+	#[test]
+	fn next_storage_key_works() {
+		let trie = test_trie();
+
+		assert_eq!(trie.next_storage_key(b"").unwrap(), Some(b":child_storage:default:sub1".to_vec()));
+		assert_eq!(trie.next_storage_key(b"k").unwrap(), Some(b"key".to_vec()));
+		assert_eq!(trie.next_storage_key(b"key").unwrap(), Some(b"value1".to_vec()));
+		assert_eq!(trie.next_storage_key(b"value1").unwrap(), Some(b"value2".to_vec()));
+		assert_eq!(trie.next_storage_key(b"value2").unwrap(), Some(vec![128]));
+		assert_eq!(trie.next_storage_key(&[254]).unwrap(), None);
+	}
 }
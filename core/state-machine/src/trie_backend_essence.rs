@@ -21,9 +21,9 @@ use std::ops::Deref;
 use std::sync::Arc;
 use log::{debug, warn};
 use hash_db::{self, Hasher, EMPTY_PREFIX, Prefix};
-use trie::{Trie, MemoryDB, PrefixedMemoryDB, DBValue,
+use trie::{Trie, MemoryDB, PrefixedMemoryDB, DBValue, KeySpacedDB,
 	default_child_trie_root, read_trie_value, read_child_trie_value,
-	for_keys_in_child_trie};
+	for_keys_in_child_trie, decode_child_trie_value};
 use trie::trie_types::{TrieDB, TrieError, Layout};
 use crate::backend::Consolidate;
 
@@ -63,6 +63,82 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 		self.storage
 	}
 
+	/// Return the next key in the trie i.e. the minimum key that is strictly superior to `key` in
+	/// lexicographic order.
+	pub fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+		self.next_storage_key_from_root(&self.root, &[], key)
+	}
+
+	/// Get the root and the unique id of the child trie at `storage_key`.
+	///
+	/// A child trie that does not exist has the default root and an empty unique id.
+	pub fn child_root_and_unique_id(&self, storage_key: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+		Ok(match self.storage(storage_key)? {
+			Some(value) => {
+				let (root, unique_id) = decode_child_trie_value::<Layout<H>>(&value);
+				(root.to_vec(), unique_id.to_vec())
+			},
+			None => (default_child_trie_root::<Layout<H>>(storage_key), Vec::new()),
+		})
+	}
+
+	/// Return the next key in the child trie i.e. the minimum key that is strictly superior to
+	/// `key` in lexicographic order.
+	pub fn next_child_storage_key(
+		&self,
+		storage_key: &[u8],
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, String> {
+		let child_value = match self.storage(storage_key)? {
+			Some(child_value) => child_value,
+			None => return Ok(None),
+		};
+		let (child_root, unique_id) = decode_child_trie_value::<Layout<H>>(&child_value);
+
+		let mut root = H::Out::default();
+		if child_root.len() != root.as_ref().len() {
+			return Err(format!("Invalid child storage hash at {:?}", storage_key));
+		}
+		// note: child_root and hash must be same size, panics otherwise.
+		root.as_mut().copy_from_slice(child_root);
+
+		self.next_storage_key_from_root(&root, unique_id, key)
+	}
+
+	/// Return next key from main trie or child trie by providing corresponding root and the key
+	/// space of its nodes.
+	fn next_storage_key_from_root(
+		&self,
+		root: &H::Out,
+		keyspace: &[u8],
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, String> {
+		let mut read_overlay = S::Overlay::default();
+		let eph = Ephemeral {
+			storage: &self.storage,
+			overlay: &mut read_overlay,
+		};
+		let db = KeySpacedDB::new(&eph, keyspace);
+
+		let trie = TrieDB::<H>::new(&db, root)
+			.map_err(|e| format!("TrieDB creation error: {}", e))?;
+		let mut iter = trie.iter()
+			.map_err(|e| format!("TrieDB iteration error: {}", e))?;
+
+		// The key just after the one given in input, basically `key++0`. No key can be placed
+		// between `key` and `key++0`, so the first key that is greater or equal to it is the next
+		// key.
+		iter.seek(&next_key_bound(key))
+			.map_err(|e| format!("TrieDB iterator seek error: {}", e))?;
+
+		match iter.next() {
+			Some(next) => next
+				.map(|(next_key, _)| Some(next_key))
+				.map_err(|e| format!("TrieDB iterator next error: {}", e)),
+			None => Ok(None),
+		}
+	}
+
 	/// Get the value of storage at given key.
 	pub fn storage(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
 		let mut read_overlay = S::Overlay::default();
@@ -78,8 +154,7 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 
 	/// Get the value of child storage at given key.
 	pub fn child_storage(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, String> {
-		let root = self.storage(storage_key)?
-			.unwrap_or(default_child_trie_root::<Layout<H>>(storage_key));
+		let (root, unique_id) = self.child_root_and_unique_id(storage_key)?;
 
 		let mut read_overlay = S::Overlay::default();
 		let eph = Ephemeral {
@@ -89,13 +164,13 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 
 		let map_e = |e| format!("Trie lookup error: {}", e);
 
-		read_child_trie_value::<Layout<H>, _>(storage_key, &eph, &root, key).map_err(map_e)
+		read_child_trie_value::<Layout<H>, _>(storage_key, &unique_id, &eph, &root, key).map_err(map_e)
 	}
 
 	/// Retrieve all entries keys of child storage and call `f` for each of those keys.
 	pub fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
-		let root = match self.storage(storage_key) {
-			Ok(v) => v.unwrap_or(default_child_trie_root::<Layout<H>>(storage_key)),
+		let (root, unique_id) = match self.child_root_and_unique_id(storage_key) {
+			Ok(v) => v,
 			Err(e) => {
 				debug!(target: "trie", "Error while iterating child storage: {}", e);
 				return;
@@ -110,6 +185,7 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 
 		if let Err(e) = for_keys_in_child_trie::<Layout<H>, _, Ephemeral<S, H>>(
 			storage_key,
+			&unique_id,
 			&eph,
 			&root,
 			f,
@@ -120,8 +196,8 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 
 	/// Execute given closure for all keys starting with prefix.
 	pub fn for_child_keys_with_prefix<F: FnMut(&[u8])>(&self, storage_key: &[u8], prefix: &[u8], mut f: F) {
-		let root_vec = match self.storage(storage_key) {
-			Ok(v) => v.unwrap_or(default_child_trie_root::<Layout<H>>(storage_key)),
+		let (root_vec, unique_id) = match self.child_root_and_unique_id(storage_key) {
+			Ok(v) => v,
 			Err(e) => {
 				debug!(target: "trie", "Error while iterating child storage: {}", e);
 				return;
@@ -130,29 +206,43 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 		let mut root = H::Out::default();
 		root.as_mut().copy_from_slice(&root_vec);
 
-		self.keys_values_with_prefix_inner(&root, prefix, |k, _v| f(k))
+		self.keys_values_with_prefix_inner(&root, &unique_id, prefix, |k, _v| f(k))
 	}
 
 	/// Execute given closure for all keys starting with prefix.
 	pub fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], mut f: F) {
-		self.keys_values_with_prefix_inner(&self.root, prefix, |k, _v| f(k))
+		self.keys_values_with_prefix_inner(&self.root, &[], prefix, |k, _v| f(k))
 	}
 
 
 	fn keys_values_with_prefix_inner<F: FnMut(&[u8], &[u8])>(
 		&self,
 		root: &H::Out,
+		keyspace: &[u8],
 		prefix: &[u8],
-		mut f: F,
+		f: F,
 	) {
+		if let Err(e) = self.try_keys_values_with_prefix_inner(root, keyspace, prefix, f) {
+			debug!(target: "trie", "Error while iterating by prefix: {}", e);
+		}
+	}
+
+	fn try_keys_values_with_prefix_inner<F: FnMut(&[u8], &[u8])>(
+		&self,
+		root: &H::Out,
+		keyspace: &[u8],
+		prefix: &[u8],
+		mut f: F,
+	) -> Result<(), String> {
 		let mut read_overlay = S::Overlay::default();
 		let eph = Ephemeral {
 			storage: &self.storage,
 			overlay: &mut read_overlay,
 		};
+		let db = KeySpacedDB::new(&eph, keyspace);
 
 		let mut iter = move || -> Result<(), Box<TrieError<H::Out>>> {
-			let trie = TrieDB::<H>::new(&eph, root)?;
+			let trie = TrieDB::<H>::new(&db, root)?;
 			let mut iter = trie.iter()?;
 
 			iter.seek(prefix)?;
@@ -170,16 +260,32 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 			Ok(())
 		};
 
-		if let Err(e) = iter() {
-			debug!(target: "trie", "Error while iterating by prefix: {}", e);
-		}
+		iter().map_err(|e| format!("{}", e))
 	}
 
 	/// Execute given closure for all key and values starting with prefix.
 	pub fn for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(&self, prefix: &[u8], f: F) {
-		self.keys_values_with_prefix_inner(&self.root, prefix, f)
+		self.keys_values_with_prefix_inner(&self.root, &[], prefix, f)
 	}
 
+	/// Execute given closure for all key and values starting with prefix, failing if a trie node
+	/// is missing, e.g. because it has been pruned, instead of stopping silently.
+	pub fn try_for_key_values_with_prefix<F: FnMut(&[u8], &[u8])>(
+		&self,
+		prefix: &[u8],
+		f: F,
+	) -> Result<(), String> {
+		self.try_keys_values_with_prefix_inner(&self.root, &[], prefix, f)
+	}
+
+}
+
+/// Returns `key++0`, the smallest key that is strictly greater than `key`.
+pub(crate) fn next_key_bound(key: &[u8]) -> Vec<u8> {
+	let mut bound = Vec::with_capacity(key.len() + 1);
+	bound.extend_from_slice(key);
+	bound.push(0);
+	bound
 }
 
 pub(crate) struct Ephemeral<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> {
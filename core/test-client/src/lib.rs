@@ -221,9 +221,9 @@ impl<E, Backend, G: GenesisInit> TestClientBuilder<
 		Block: BlockT<Hash=<Blake2Hasher as Hasher>::Out>,
 	{
 		let executor = executor.into().unwrap_or_else(||
-			NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+			NativeExecutor::new(WasmExecutionMethod::Interpreted, None, None)
 		);
-		let executor = LocalCallExecutor::new(self.backend.clone(), executor, self.keystore.take());
+		let executor = LocalCallExecutor::new(self.backend.clone(), executor, self.keystore.take(), None);
 
 		self.build_with_executor(executor)
 	}
@@ -29,7 +29,7 @@ pub use generic_test_client::*;
 pub use runtime;
 
 use primitives::sr25519;
-use runtime::genesismap::{GenesisConfig, additional_storage_with_genesis};
+use runtime::genesismap::{GenesisConfig, additional_storage_with_genesis, genesis_child_trie_value};
 use sr_primitives::traits::{Block as BlockT, Header as HeaderT, Hash as HashT};
 
 /// A prelude to import in tests.
@@ -121,10 +121,7 @@ impl generic_test_client::GenesisInit for GenesisParameters {
 		let mut storage = self.genesis_config().genesis_map();
 
 		let child_roots = storage.1.iter().map(|(sk, child_map)| {
-			let state_root = <<<runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
-				child_map.clone().into_iter().collect()
-			);
-			(sk.clone(), state_root.encode())
+			(sk.clone(), genesis_child_trie_value(&storage.0, sk, child_map))
 		});
 		let state_root = <<<runtime::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
 			storage.0.clone().into_iter().chain(child_roots).collect()
@@ -261,8 +258,8 @@ pub fn new_light() -> (
 	let storage = client_db::light::LightStorage::new_test();
 	let blockchain = Arc::new(client::light::blockchain::Blockchain::new(storage));
 	let backend = Arc::new(LightBackend::new(blockchain.clone()));
-	let executor = NativeExecutor::new(WasmExecutionMethod::Interpreted, None);
-	let local_call_executor = client::LocalCallExecutor::new(backend.clone(), executor, None);
+	let executor = NativeExecutor::new(WasmExecutionMethod::Interpreted, None, None);
+	let local_call_executor = client::LocalCallExecutor::new(backend.clone(), executor, None, None);
 	let call_executor = LightExecutor::new(
 		backend.clone(),
 		local_call_executor,
@@ -20,7 +20,8 @@ use std::collections::HashMap;
 use runtime_io::{blake2_256, twox_128};
 use super::{AuthorityId, AccountId, WASM_BINARY};
 use codec::{Encode, KeyedVec, Joiner};
-use primitives::{ChangesTrieConfiguration, map, storage::well_known_keys};
+use primitives::{ChangesTrieConfiguration, Blake2Hasher, map, storage::well_known_keys};
+use substrate_trie::{Layout, child_trie_unique_id, encode_child_trie_value};
 use sr_primitives::traits::{Block as BlockT, Hash as HashT, Header as HeaderT};
 
 /// Configuration of a general Substrate test genesis block.
@@ -91,10 +92,7 @@ pub fn insert_genesis_block(
 	)
 ) -> primitives::hash::H256 {
 	let child_roots = storage.1.iter().map(|(sk, child_map)| {
-		let state_root = <<<crate::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
-			child_map.clone().into_iter().collect(),
-		);
-		(sk.clone(), state_root.encode())
+		(sk.clone(), genesis_child_trie_value(&storage.0, sk, child_map))
 	});
 	let state_root = <<<crate::Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
 		storage.0.clone().into_iter().chain(child_roots).collect()
@@ -105,6 +103,24 @@ pub fn insert_genesis_block(
 	genesis_hash
 }
 
+/// The value of a genesis child trie with the given contents in the top trie: the root of the
+/// child trie, followed by its unique id if the `top` storage enables unique ids.
+pub fn genesis_child_trie_value(
+	top: &HashMap<Vec<u8>, Vec<u8>>,
+	storage_key: &[u8],
+	child_map: &HashMap<Vec<u8>, Vec<u8>>,
+) -> Vec<u8> {
+	type Hashing = <<crate::Block as BlockT>::Header as HeaderT>::Hashing;
+	let root = <Hashing as HashT>::trie_root(child_map.clone().into_iter().collect());
+	if !top.contains_key(well_known_keys::CHILD_TRIE_UNIQUE_IDS) {
+		return root.as_ref().to_vec();
+	}
+	// Genesis child tries are created on top of the top storage.
+	let top_root = <Hashing as HashT>::trie_root(top.clone().into_iter().collect());
+	let unique_id = child_trie_unique_id::<Layout<Blake2Hasher>>(storage_key, top_root.as_ref());
+	encode_child_trie_value(root.as_ref(), &unique_id)
+}
+
 pub fn additional_storage_with_genesis(genesis_block: &crate::Block) -> HashMap<Vec<u8>, Vec<u8>> {
 	map![
 		twox_128(&b"latest"[..]).to_vec() => genesis_block.hash().as_fixed_bytes().to_vec()
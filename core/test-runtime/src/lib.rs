@@ -77,6 +77,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_version: 1,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
+	host_interfaces: runtime_version::create_host_interfaces_vec!(),
 };
 
 fn version() -> RuntimeVersion {
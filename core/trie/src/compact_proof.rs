@@ -0,0 +1,369 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compact encoding of storage proofs.
+//!
+//! A storage proof is a set of trie nodes. Most of the size of a branch node are the hashes of its
+//! children, but a verifier can recompute the hash of every child that is part of the proof
+//! itself. The compact encoding therefore orders the nodes so that every node is directly followed
+//! by the nodes of its subtrees, in depth-first order, and replaces the reference of every child
+//! that follows in the encoding with an empty reference. Since the proof is a set, every node is
+//! only encoded once: a child that was already encoded, e.g. an identical subtree, keeps its hash.
+//!
+//! Nodes that can't be reached from the root, e.g. the nodes of child tries, are encoded the same
+//! way after the nodes of the main trie, starting from every node that is not referenced by any
+//! other node of the proof.
+
+use rstd::vec::Vec;
+use rstd::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
+use codec::{Encode, Decode, Compact};
+use hash_db::Hasher;
+use trie_db::nibble_ops;
+use crate::error::Error;
+use crate::node_codec::{Bitmap, BITMAP_LENGTH};
+use crate::node_header::NodeHeader;
+
+/// A trie node split into the part that is kept as it is and its child references.
+struct RawNode<'a> {
+	/// The header, partial key, children bitmap and value of the node.
+	head: &'a [u8],
+	/// The child references of a branch node.
+	children: [Option<&'a [u8]>; nibble_ops::NIBBLE_LENGTH],
+}
+
+impl<'a> RawNode<'a> {
+	fn decode(data: &'a [u8]) -> Result<Self, Error> {
+		let input = &mut &*data;
+		let head = NodeHeader::decode(input)?;
+		let mut children = [None; nibble_ops::NIBBLE_LENGTH];
+
+		if let NodeHeader::Branch(has_value, nibble_count) = head {
+			let partial_len = (nibble_count + (nibble_ops::NIBBLE_PER_BYTE - 1))
+				/ nibble_ops::NIBBLE_PER_BYTE;
+			take(input, partial_len)?;
+			let bitmap = Bitmap::decode(take(input, BITMAP_LENGTH)?)?;
+			if has_value {
+				let count = <Compact<u32>>::decode(input)?.0 as usize;
+				take(input, count)?;
+			}
+			let head_len = data.len() - input.len();
+
+			for i in 0..nibble_ops::NIBBLE_LENGTH {
+				if bitmap.value_at(i) {
+					let count = <Compact<u32>>::decode(input)?.0 as usize;
+					children[i] = Some(take(input, count)?);
+				}
+			}
+			if !input.is_empty() {
+				return Err(Error::BadFormat);
+			}
+
+			Ok(RawNode { head: &data[..head_len], children })
+		} else {
+			Ok(RawNode { head: data, children })
+		}
+	}
+
+	/// Encode the node, replacing its child references with the ones returned by `child`.
+	fn encode_with<'b>(&self, mut child: impl FnMut(usize, &'a [u8]) -> &'b [u8]) -> Vec<u8> {
+		let mut output = self.head.to_vec();
+		for (i, reference) in self.children.iter().enumerate() {
+			if let Some(reference) = *reference {
+				child(i, reference).encode_to(&mut output);
+			}
+		}
+		output
+	}
+}
+
+fn take<'a>(input: &mut &'a [u8], count: usize) -> Result<&'a [u8], Error> {
+	if input.len() < count {
+		return Err(Error::BadFormat);
+	}
+	let r = &(*input)[..count];
+	*input = &(*input)[count..];
+	Ok(r)
+}
+
+/// Encode the nodes of a storage proof for the trie with the given `root` compactly.
+///
+/// Returns `Error::IncompleteProof` if `root` is not part of the proof.
+pub fn encode_compact_proof<H: Hasher>(
+	root: &H::Out,
+	proof: &[Vec<u8>],
+) -> Result<Vec<Vec<u8>>, Error> {
+	let mut nodes = BTreeMap::new();
+	for node in proof {
+		nodes.insert(H::hash(node).as_ref().to_vec(), &node[..]);
+	}
+	if !nodes.contains_key(root.as_ref()) {
+		return Err(Error::IncompleteProof);
+	}
+
+	let mut encoder = Encoder {
+		nodes: &nodes,
+		encoded: BTreeSet::new(),
+		output: Vec::with_capacity(nodes.len()),
+	};
+	encoder.encoded.insert(root.as_ref().to_vec());
+	encoder.encode(root.as_ref())?;
+
+	// Nodes that are referenced by another node are encoded with that node, so the remaining
+	// nodes are encoded starting from the ones that are not referenced at all.
+	let mut referenced = BTreeSet::new();
+	for node in nodes.values() {
+		referenced.extend(RawNode::decode(node)?.children.iter().filter_map(|child| *child));
+	}
+	let (unreferenced, referenced): (Vec<_>, Vec<_>) = proof.iter()
+		.map(|node| H::hash(node).as_ref().to_vec())
+		.partition(|hash| !referenced.contains(&hash[..]));
+
+	for hash in unreferenced.into_iter().chain(referenced) {
+		if encoder.encoded.insert(hash.clone()) {
+			encoder.encode(&hash)?;
+		}
+	}
+
+	Ok(encoder.output)
+}
+
+struct Encoder<'a> {
+	/// All nodes of the proof by their hash.
+	nodes: &'a BTreeMap<Vec<u8>, &'a [u8]>,
+	/// The hashes of the nodes that are already in the output, or will be.
+	encoded: BTreeSet<Vec<u8>>,
+	output: Vec<Vec<u8>>,
+}
+
+impl<'a> Encoder<'a> {
+	/// Encode the node with the given hash and the nodes of all its omitted children.
+	fn encode<'b>(&mut self, hash: &'b [u8]) -> Result<(), Error> where 'a: 'b {
+		let nodes = self.nodes;
+		// The children are popped in the order they appear in their parent, after all the
+		// nodes of the subtrees of their preceding siblings.
+		let mut pending = Vec::new();
+		pending.push(hash);
+		while let Some(hash) = pending.pop() {
+			let node = RawNode::decode(nodes[hash])?;
+
+			let mut omitted = Vec::new();
+			let encoded = &mut self.encoded;
+			self.output.push(node.encode_with(|_, reference| {
+				if nodes.contains_key(reference) && encoded.insert(reference.to_vec()) {
+					omitted.push(reference);
+					&[]
+				} else {
+					reference
+				}
+			}));
+
+			pending.extend(omitted.into_iter().rev());
+		}
+		Ok(())
+	}
+}
+
+/// Decode a proof encoded by [`encode_compact_proof`].
+///
+/// Returns the root of the main trie of the proof and the nodes of the proof.
+pub fn decode_compact_proof<H: Hasher>(
+	encoded: &[Vec<u8>],
+) -> Result<(H::Out, Vec<Vec<u8>>), Error> {
+	let mut input = encoded.iter();
+	let mut output = Vec::with_capacity(encoded.len());
+
+	let root = decode_node::<H>(&mut input, &mut output)?;
+	while input.len() > 0 {
+		decode_node::<H>(&mut input, &mut output)?;
+	}
+
+	Ok((root, output))
+}
+
+/// A node whose omitted children are being decoded.
+struct DecodeFrame<'a, H: Hasher> {
+	node: RawNode<'a>,
+	/// The hashes of the omitted children decoded so far.
+	children: [Option<H::Out>; nibble_ops::NIBBLE_LENGTH],
+	/// The index after the omitted child that is being decoded.
+	next: usize,
+}
+
+impl<'a, H: Hasher> DecodeFrame<'a, H> {
+	fn new(input: &mut rstd::slice::Iter<'a, Vec<u8>>) -> Result<Self, Error> {
+		Ok(DecodeFrame {
+			node: RawNode::decode(input.next().ok_or(Error::IncompleteProof)?)?,
+			children: Default::default(),
+			next: 0,
+		})
+	}
+}
+
+/// Decode the next node and the nodes of all its omitted children from `input`.
+///
+/// The nodes are decoded with an explicit stack, so a deep or malicious proof can't exhaust the
+/// native stack. Returns the hash of the node.
+fn decode_node<'a, H: Hasher>(
+	input: &mut rstd::slice::Iter<'a, Vec<u8>>,
+	output: &mut Vec<Vec<u8>>,
+) -> Result<H::Out, Error> {
+	let mut stack = Vec::new();
+	stack.push(DecodeFrame::<H>::new(input)?);
+	loop {
+		let frame = stack.last_mut().expect("the stack is only empty after returning; qed");
+		let omitted = (frame.next..nibble_ops::NIBBLE_LENGTH)
+			.find(|&i| frame.node.children[i].map_or(false, |reference| reference.is_empty()));
+		if let Some(i) = omitted {
+			frame.next = i + 1;
+			stack.push(DecodeFrame::new(input)?);
+			continue;
+		}
+
+		let frame = stack.pop().expect("the stack is only empty after returning; qed");
+		let node = frame.node.encode_with(|i, reference| match frame.children[i] {
+			Some(ref hash) => hash.as_ref(),
+			None => reference,
+		});
+		let hash = H::hash(&node);
+		output.push(node);
+
+		match stack.last_mut() {
+			Some(parent) => {
+				let i = parent.next - 1;
+				parent.children[i] = Some(hash);
+			},
+			None => return Ok(hash),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{MemoryDB, TrieDBMut, Layout, TrieMut, Recorder, read_trie_value_with};
+	use primitives::Blake2Hasher;
+	use hash_db::{HashDB, EMPTY_PREFIX};
+
+	fn test_trie() -> (MemoryDB<Blake2Hasher>, <Blake2Hasher as Hasher>::Out) {
+		let mut db = MemoryDB::default();
+		let mut root = Default::default();
+		{
+			let mut trie = TrieDBMut::<Layout<Blake2Hasher>>::new(&mut db, &mut root);
+			for i in 0..255u8 {
+				trie.insert(&[i, i / 2], &[i; 40]).unwrap();
+				// Identical subtrees.
+				trie.insert(&[i, 255], &[0; 40]).unwrap();
+			}
+		}
+		(db, root)
+	}
+
+	fn record(
+		db: &MemoryDB<Blake2Hasher>,
+		root: &<Blake2Hasher as Hasher>::Out,
+		keys: &[&[u8]],
+	) -> Vec<Vec<u8>> {
+		let mut recorder = Recorder::new();
+		for key in keys {
+			read_trie_value_with::<Layout<Blake2Hasher>, _, _>(db, root, key, &mut recorder)
+				.unwrap();
+		}
+		let mut proof = recorder.drain().into_iter()
+			.map(|record| record.data.to_vec())
+			.collect::<Vec<_>>();
+		proof.sort();
+		proof.dedup();
+		proof
+	}
+
+	#[test]
+	fn compact_proof_roundtrips() {
+		let (db, root) = test_trie();
+		let proof = record(&db, &root, &[&[1, 0], &[2, 1], &[2, 255], &[3, 255], &[200, 100]]);
+
+		let encoded = encode_compact_proof::<Blake2Hasher>(&root, &proof).unwrap();
+		assert_eq!(encoded.len(), proof.len());
+		let (decoded_root, mut decoded) = decode_compact_proof::<Blake2Hasher>(&encoded).unwrap();
+
+		assert_eq!(decoded_root, root);
+		decoded.sort();
+		assert_eq!(decoded, proof);
+	}
+
+	#[test]
+	fn compact_proof_is_smaller() {
+		let (db, root) = test_trie();
+		let proof = record(&db, &root, &[&[1, 0], &[2, 1], &[3, 1], &[4, 2]]);
+
+		let encoded = encode_compact_proof::<Blake2Hasher>(&root, &proof).unwrap();
+		let proof_size = proof.iter().map(|node| node.len()).sum::<usize>();
+		let encoded_size = encoded.iter().map(|node| node.len()).sum::<usize>();
+		assert!(encoded_size + 32 * (proof.len() - 1) <= proof_size);
+
+		let mut check_db = MemoryDB::<Blake2Hasher>::default();
+		for node in decode_compact_proof::<Blake2Hasher>(&encoded).unwrap().1 {
+			check_db.insert(EMPTY_PREFIX, &node);
+		}
+		assert_eq!(
+			crate::read_trie_value::<Layout<Blake2Hasher>, _>(&check_db, &root, &[2, 1]).unwrap(),
+			Some(vec![2; 40]),
+		);
+	}
+
+	#[test]
+	fn deep_compact_proof_roundtrips() {
+		let mut db = MemoryDB::<Blake2Hasher>::default();
+		let mut root = Default::default();
+		// Keys sharing ever longer prefixes create a chain of nested branches.
+		let keys = (0..200usize).map(|i| {
+			let mut key = vec![0u8; i];
+			key.push(1);
+			key
+		}).collect::<Vec<_>>();
+		{
+			let mut trie = TrieDBMut::<Layout<Blake2Hasher>>::new(&mut db, &mut root);
+			for key in &keys {
+				trie.insert(key, &[1; 40]).unwrap();
+			}
+		}
+		let last = keys.last().unwrap();
+		let proof = record(&db, &root, &[&last[..]]);
+
+		let encoded = encode_compact_proof::<Blake2Hasher>(&root, &proof).unwrap();
+		let (decoded_root, mut decoded) = decode_compact_proof::<Blake2Hasher>(&encoded).unwrap();
+
+		assert_eq!(decoded_root, root);
+		decoded.sort();
+		assert_eq!(decoded, proof);
+	}
+
+	#[test]
+	fn compact_proof_without_root_is_incomplete() {
+		let (db, root) = test_trie();
+		let proof = record(&db, &root, &[&[1, 0]]);
+
+		assert_eq!(
+			encode_compact_proof::<Blake2Hasher>(&Default::default(), &proof),
+			Err(Error::IncompleteProof),
+		);
+
+		let encoded = encode_compact_proof::<Blake2Hasher>(&root, &proof).unwrap();
+		assert_eq!(
+			decode_compact_proof::<Blake2Hasher>(&encoded[..encoded.len() - 1]),
+			Err(Error::IncompleteProof),
+		);
+	}
+}
@@ -17,7 +17,9 @@ pub enum Error {
 	/// Bad format.
 	BadFormat,
 	/// Decoding error.
-	Decode(codec::Error)
+	Decode(codec::Error),
+	/// A compact proof is missing nodes.
+	IncompleteProof,
 }
 
 impl From<codec::Error> for Error {
@@ -32,6 +34,7 @@ impl StdError for Error {
 		match self {
 			Error::BadFormat => "Bad format error",
 			Error::Decode(_) => "Decoding error",
+			Error::IncompleteProof => "Incomplete proof",
 		}
 	}
 }
@@ -42,6 +45,7 @@ impl fmt::Display for Error {
 		match self {
 			Error::Decode(e) => write!(f, "Decode error: {}", e.what()),
 			Error::BadFormat => write!(f, "Bad format"),
+			Error::IncompleteProof => write!(f, "Incomplete proof"),
 		}
 	}
 }
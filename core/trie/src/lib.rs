@@ -22,16 +22,20 @@ mod error;
 mod node_header;
 mod node_codec;
 mod trie_stream;
+mod compact_proof;
 
 use rstd::boxed::Box;
 use rstd::vec::Vec;
-use hash_db::Hasher;
+use rstd::marker::PhantomData;
+use hash_db::{Hasher, Prefix};
 /// Our `NodeCodec`-specific error.
 pub use error::Error;
 /// The Substrate format implementation of `TrieStream`.
 pub use trie_stream::TrieStream;
 /// The Substrate format implementation of `NodeCodec`.
 pub use node_codec::NodeCodec;
+/// Compact encoding of storage proofs.
+pub use compact_proof::{encode_compact_proof, decode_compact_proof};
 /// Various re-exports from the `trie-db` crate.
 pub use trie_db::{
 	Trie, TrieMut, DBValue, Recorder, CError, Query, TrieLayout, TrieConfiguration, nibble_ops,
@@ -171,6 +175,38 @@ pub fn default_child_trie_root<L: TrieConfiguration>(_storage_key: &[u8]) -> Vec
 	L::trie_root::<_, Vec<u8>, Vec<u8>>(core::iter::empty()).as_ref().iter().cloned().collect()
 }
 
+/// Determine the unique id of a new child trie with the given storage key, created on top of the
+/// state with root `parent_root`.
+///
+/// The unique id is used as a key space for the nodes of the child trie in the database, so that
+/// a child trie that is deleted and created again under the same storage key never shares nodes
+/// with its previous incarnation. The root of the parent state differs between blocks, so a new
+/// child trie never gets the id of a deleted one.
+pub fn child_trie_unique_id<L: TrieConfiguration>(storage_key: &[u8], parent_root: &[u8]) -> Vec<u8> {
+	let mut input = Vec::with_capacity(storage_key.len() + parent_root.len());
+	input.extend_from_slice(storage_key);
+	input.extend_from_slice(parent_root);
+	L::Hash::hash(&input).as_ref().to_vec()
+}
+
+/// Encode the value that is stored for a child trie in its parent trie: the root of the child
+/// trie followed by its unique id.
+pub fn encode_child_trie_value(root: &[u8], unique_id: &[u8]) -> Vec<u8> {
+	let mut value = Vec::with_capacity(root.len() + unique_id.len());
+	value.extend_from_slice(root);
+	value.extend_from_slice(unique_id);
+	value
+}
+
+/// Split the value that is stored for a child trie in its parent trie into the root and the
+/// unique id of the child trie.
+///
+/// Child tries that were created without a unique id only store their root; their nodes live in
+/// the key space of the parent trie and the returned id is empty.
+pub fn decode_child_trie_value<L: TrieConfiguration>(value: &[u8]) -> (&[u8], &[u8]) {
+	value.split_at(rstd::cmp::min(<L::Hash as Hasher>::LENGTH, value.len()))
+}
+
 /// Determine a child trie root given its ordered contents, closed form. H is the default hasher,
 /// but a generic implementation may ignore this type parameter and use other hashers.
 pub fn child_trie_root<L: TrieConfiguration, I, A, B>(_storage_key: &[u8], input: I) -> Vec<u8>
@@ -184,8 +220,11 @@ pub fn child_trie_root<L: TrieConfiguration, I, A, B>(_storage_key: &[u8], input
 
 /// Determine a child trie root given a hash DB and delta values. H is the default hasher,
 /// but a generic implementation may ignore this type parameter and use other hashers.
+///
+/// The nodes of the child trie are read from and written to the key space `keyspace` of `db`.
 pub fn child_delta_trie_root<L: TrieConfiguration, I, A, B, DB>(
 	_storage_key: &[u8],
+	keyspace: &[u8],
 	db: &mut DB,
 	root_vec: Vec<u8>,
 	delta: I
@@ -202,7 +241,8 @@ pub fn child_delta_trie_root<L: TrieConfiguration, I, A, B, DB>(
 	root.as_mut().copy_from_slice(&root_vec);
 
 	{
-		let mut trie = TrieDBMut::<L>::from_existing(&mut *db, &mut root)?;
+		let mut db = KeySpacedDBMut::new(&mut *db, keyspace);
+		let mut trie = TrieDBMut::<L>::from_existing(&mut db, &mut root)?;
 
 		for (key, change) in delta {
 			match change {
@@ -218,6 +258,7 @@ pub fn child_delta_trie_root<L: TrieConfiguration, I, A, B, DB>(
 /// Call `f` for all keys in a child trie.
 pub fn for_keys_in_child_trie<L: TrieConfiguration, F: FnMut(&[u8]), DB>(
 	_storage_key: &[u8],
+	keyspace: &[u8],
 	db: &DB,
 	root_slice: &[u8],
 	mut f: F
@@ -230,7 +271,8 @@ pub fn for_keys_in_child_trie<L: TrieConfiguration, F: FnMut(&[u8]), DB>(
 	// root is fetched from DB, not writable by runtime, so it's always valid.
 	root.as_mut().copy_from_slice(root_slice);
 
-	let trie = TrieDB::<L>::new(&*db, &root)?;
+	let db = KeySpacedDB::new(&*db, keyspace);
+	let trie = TrieDB::<L>::new(&db, &root)?;
 	let iter = trie.iter()?;
 
 	for x in iter {
@@ -267,6 +309,7 @@ pub fn record_all_keys<L: TrieConfiguration, DB>(
 /// Read a value from the child trie.
 pub fn read_child_trie_value<L: TrieConfiguration, DB>(
 	_storage_key: &[u8],
+	keyspace: &[u8],
 	db: &DB,
 	root_slice: &[u8],
 	key: &[u8]
@@ -279,12 +322,14 @@ pub fn read_child_trie_value<L: TrieConfiguration, DB>(
 	// root is fetched from DB, not writable by runtime, so it's always valid.
 	root.as_mut().copy_from_slice(root_slice);
 
-	Ok(TrieDB::<L>::new(&*db, &root)?.get(key).map(|x| x.map(|val| val.to_vec()))?)
+	let db = KeySpacedDB::new(&*db, keyspace);
+	Ok(TrieDB::<L>::new(&db, &root)?.get(key).map(|x| x.map(|val| val.to_vec()))?)
 }
 
 /// Read a value from the child trie with given query.
 pub fn read_child_trie_value_with<L: TrieConfiguration, Q: Query<L::Hash, Item=DBValue>, DB>(
 	_storage_key: &[u8],
+	keyspace: &[u8],
 	db: &DB,
 	root_slice: &[u8],
 	key: &[u8],
@@ -298,7 +343,94 @@ pub fn read_child_trie_value_with<L: TrieConfiguration, Q: Query<L::Hash, Item=D
 	// root is fetched from DB, not writable by runtime, so it's always valid.
 	root.as_mut().copy_from_slice(root_slice);
 
-	Ok(TrieDB::<L>::new(&*db, &root)?.get_with(key, query).map(|x| x.map(|val| val.to_vec()))?)
+	let db = KeySpacedDB::new(&*db, keyspace);
+	Ok(TrieDB::<L>::new(&db, &root)?.get_with(key, query).map(|x| x.map(|val| val.to_vec()))?)
+}
+
+/// Read-only `HashDB` that looks up every node in the key space `keyspace` of the wrapped
+/// database, by prepending the key space to the prefix of the node.
+pub struct KeySpacedDB<'a, DB, H>(&'a DB, &'a [u8], PhantomData<H>);
+
+/// `HashDB` that reads and writes every node in the key space `keyspace` of the wrapped
+/// database. Mutable variant of [`KeySpacedDB`].
+pub struct KeySpacedDBMut<'a, DB, H>(&'a mut DB, &'a [u8], PhantomData<H>);
+
+/// Prepend the key space `keyspace` to `prefix`.
+pub fn keyspace_as_prefix_alloc(keyspace: &[u8], prefix: Prefix) -> (Vec<u8>, Option<u8>) {
+	let mut result = Vec::with_capacity(keyspace.len() + prefix.0.len());
+	result.extend_from_slice(keyspace);
+	result.extend_from_slice(prefix.0);
+	(result, prefix.1)
+}
+
+impl<'a, DB, H> KeySpacedDB<'a, DB, H> where H: Hasher {
+	/// Wrap `db` to access the key space `keyspace`.
+	pub fn new(db: &'a DB, keyspace: &'a [u8]) -> Self {
+		KeySpacedDB(db, keyspace, PhantomData)
+	}
+}
+
+impl<'a, DB, H> KeySpacedDBMut<'a, DB, H> where H: Hasher {
+	/// Wrap `db` to access the key space `keyspace`.
+	pub fn new(db: &'a mut DB, keyspace: &'a [u8]) -> Self {
+		KeySpacedDBMut(db, keyspace, PhantomData)
+	}
+}
+
+impl<'a, DB, H, T> hash_db::HashDBRef<H, T> for KeySpacedDB<'a, DB, H> where
+	DB: hash_db::HashDBRef<H, T>,
+	H: Hasher,
+{
+	fn get(&self, key: &H::Out, prefix: Prefix) -> Option<T> {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.get(key, (&derived_prefix.0, derived_prefix.1))
+	}
+
+	fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.contains(key, (&derived_prefix.0, derived_prefix.1))
+	}
+}
+
+impl<'a, DB, H, T> hash_db::HashDB<H, T> for KeySpacedDBMut<'a, DB, H> where
+	DB: hash_db::HashDB<H, T>,
+	H: Hasher,
+{
+	fn get(&self, key: &H::Out, prefix: Prefix) -> Option<T> {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.get(key, (&derived_prefix.0, derived_prefix.1))
+	}
+
+	fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.contains(key, (&derived_prefix.0, derived_prefix.1))
+	}
+
+	fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.insert((&derived_prefix.0, derived_prefix.1), value)
+	}
+
+	fn emplace(&mut self, key: H::Out, prefix: Prefix, value: T) {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.emplace(key, (&derived_prefix.0, derived_prefix.1), value)
+	}
+
+	fn remove(&mut self, key: &H::Out, prefix: Prefix) {
+		let derived_prefix = keyspace_as_prefix_alloc(self.1, prefix);
+		self.0.remove(key, (&derived_prefix.0, derived_prefix.1))
+	}
+}
+
+impl<'a, DB, H, T> hash_db::AsHashDB<H, T> for KeySpacedDBMut<'a, DB, H> where
+	DB: hash_db::HashDB<H, T>,
+	H: Hasher,
+{
+	fn as_hash_db(&self) -> &dyn hash_db::HashDB<H, T> { &*self }
+
+	fn as_hash_db_mut<'b>(&'b mut self) -> &'b mut (dyn hash_db::HashDB<H, T> + 'b) {
+		&mut *self
+	}
 }
 
 /// Constants used into trie simplification codec.
@@ -616,4 +748,46 @@ mod tests {
 
 		assert_eq!(pairs, iter_pairs);
 	}
+
+	#[test]
+	fn child_tries_in_different_key_spaces_do_not_share_nodes() {
+		let storage_key = b":child_storage:default:child";
+		let empty_root = default_child_trie_root::<Layout>(storage_key);
+		let mut db = PrefixedMemoryDB::<Blake2Hasher>::default();
+
+		let root = child_delta_trie_root::<Layout, _, _, _, _>(
+			storage_key,
+			b"first",
+			&mut db,
+			empty_root.clone(),
+			vec![(b"key".to_vec(), Some(b"value".to_vec()))],
+		).unwrap();
+		assert_eq!(
+			read_child_trie_value::<Layout, _>(storage_key, b"first", &db, &root, b"key").unwrap(),
+			Some(b"value".to_vec()),
+		);
+
+		// The same content under another unique id has the same root, but its nodes are stored
+		// separately.
+		let mut other_db = PrefixedMemoryDB::<Blake2Hasher>::default();
+		let other_root = child_delta_trie_root::<Layout, _, _, _, _>(
+			storage_key,
+			b"second",
+			&mut other_db,
+			empty_root,
+			vec![(b"key".to_vec(), Some(b"value".to_vec()))],
+		).unwrap();
+		assert_eq!(root, other_root);
+		assert!(read_child_trie_value::<Layout, _>(storage_key, b"second", &db, &root, b"key").is_err());
+		assert!(db.keys().keys().all(|key| key.starts_with(b"first")));
+	}
+
+	#[test]
+	fn child_trie_value_roundtrips() {
+		let root = default_child_trie_root::<Layout>(b"child");
+		let unique_id = child_trie_unique_id::<Layout>(b"child", &root);
+		let value = encode_child_trie_value(&root, &unique_id);
+		assert_eq!(decode_child_trie_value::<Layout>(&value), (&root[..], &unique_id[..]));
+		assert_eq!(decode_child_trie_value::<Layout>(&root), (&root[..], &[][..]));
+	}
 }
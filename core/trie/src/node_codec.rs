@@ -220,7 +220,7 @@ fn partial_encode(partial: Partial, node_kind: NodeKind) -> Vec<u8> {
 	output
 }
 
-const BITMAP_LENGTH: usize = 2;
+pub(crate) const BITMAP_LENGTH: usize = 2;
 
 /// Radix 16 trie, bitmap encoding implementation,
 /// it contains children mapping information for a branch
@@ -201,6 +201,11 @@ pub trait FunctionContext {
 	fn allocate_memory(&mut self, size: WordSize) -> Result<Pointer<u8>>;
 	/// Deallocate a given memory instance.
 	fn deallocate_memory(&mut self, ptr: Pointer<u8>) -> Result<()>;
+	/// Change the size of a memory instance to `size` bytes.
+	///
+	/// Returns the new location of the memory instance, which contains the content of the old
+	/// instance up to the smaller of both sizes.
+	fn reallocate_memory(&mut self, ptr: Pointer<u8>, size: WordSize) -> Result<Pointer<u8>>;
 	/// Provides access to the sandbox.
 	fn sandbox(&mut self) -> &mut dyn Sandbox;
 }
@@ -212,7 +217,7 @@ pub type MemoryId = u32;
 pub trait Sandbox {
 	/// Get sandbox memory from the `memory_id` instance at `offset` into the given buffer.
 	fn memory_get(
-		&self,
+		&mut self,
 		memory_id: MemoryId,
 		offset: WordSize,
 		buf_ptr: Pointer<u8>,
@@ -253,12 +258,74 @@ pub trait Sandbox {
 	) -> Result<u32>;
 }
 
+/// The separator between the name and the version of a versioned host function.
+const HOST_FUNCTION_VERSION_SEPARATOR: &str = "_version_";
+
+/// Splits the given host function name into the name of the function and its version.
+///
+/// Versioned host functions are named `{name}_version_{version}`. Host functions that don't
+/// follow this naming scheme have the version `1`.
+pub fn host_function_name_and_version(name: &str) -> (&str, u32) {
+	name.rfind(HOST_FUNCTION_VERSION_SEPARATOR)
+		.and_then(|pos| {
+			let version = &name[pos + HOST_FUNCTION_VERSION_SEPARATOR.len()..];
+			version.parse().ok().map(|version| (&name[..pos], version))
+		})
+		.unwrap_or((name, 1))
+}
+
 /// Something that provides implementations for host functions.
+///
+/// The trait is implemented for tuples, to combine the host functions of multiple
+/// implementations.
 pub trait HostFunctions {
 	/// Returns all host functions.
-	fn functions() -> &'static [&'static dyn Function];
+	fn host_functions() -> Vec<&'static dyn Function>;
+
+	/// Returns the names and versions of the runtime interfaces whose host functions are
+	/// returned by `host_functions`.
+	///
+	/// A runtime declares the interfaces it was compiled against in its `RuntimeVersion`. This is
+	/// checked against the interfaces returned here before executing the runtime.
+	fn interfaces() -> Vec<(&'static str, u32)> {
+		Vec::new()
+	}
 }
 
+/// Implements `HostFunctions` for the given tuple and all its sub tuples.
+///
+/// The host functions of the tuple elements are concatenated in order.
+macro_rules! impl_host_functions_for_tuples {
+	() => {
+		impl HostFunctions for () {
+			fn host_functions() -> Vec<&'static dyn Function> {
+				Vec::new()
+			}
+		}
+	};
+	( $first:ident $( , $rest:ident )* ) => {
+		impl<$first: HostFunctions, $( $rest: HostFunctions ),*> HostFunctions
+			for ($first, $( $rest, )*)
+		{
+			fn host_functions() -> Vec<&'static dyn Function> {
+				let mut host_functions = $first::host_functions();
+				$( host_functions.extend($rest::host_functions()); )*
+				host_functions
+			}
+
+			fn interfaces() -> Vec<(&'static str, u32)> {
+				let mut interfaces = $first::interfaces();
+				$( interfaces.extend($rest::interfaces()); )*
+				interfaces
+			}
+		}
+
+		impl_host_functions_for_tuples!( $( $rest ),* );
+	};
+}
+
+impl_host_functions_for_tuples!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 /// Something that can be converted into a wasm compatible `Value`.
 pub trait IntoValue {
 	/// The type of the value in wasm.
@@ -321,4 +388,74 @@ mod tests {
 		assert_eq!(ptr.offset(10).unwrap(), Pointer::new(80));
 		assert_eq!(ptr.offset(32).unwrap(), Pointer::new(256));
 	}
+
+	#[test]
+	fn host_function_name_and_version_works() {
+		assert_eq!(host_function_name_and_version("ext_print_utf8"), ("ext_print_utf8", 1));
+		assert_eq!(
+			host_function_name_and_version("ext_misc_print_version_1"),
+			("ext_misc_print", 1),
+		);
+		assert_eq!(
+			host_function_name_and_version("ext_misc_print_version_12"),
+			("ext_misc_print", 12),
+		);
+		assert_eq!(
+			host_function_name_and_version("ext_misc_print_version_x"),
+			("ext_misc_print_version_x", 1),
+		);
+	}
+
+	struct TestFunction(&'static str);
+
+	impl Function for TestFunction {
+		fn name(&self) -> &str {
+			self.0
+		}
+
+		fn signature(&self) -> Signature {
+			Signature::new_with_args(Vec::new())
+		}
+
+		fn execute(
+			&self,
+			_: &mut dyn FunctionContext,
+			_: &mut dyn Iterator<Item = Value>,
+		) -> Result<Option<Value>> {
+			Ok(None)
+		}
+	}
+
+	struct First;
+
+	impl HostFunctions for First {
+		fn host_functions() -> Vec<&'static dyn Function> {
+			vec![&TestFunction("first")]
+		}
+	}
+
+	struct Second;
+
+	impl HostFunctions for Second {
+		fn host_functions() -> Vec<&'static dyn Function> {
+			vec![&TestFunction("second_a"), &TestFunction("second_b")]
+		}
+	}
+
+	#[test]
+	fn host_functions_of_tuples_are_concatenated() {
+		let names = |functions: Vec<&'static dyn Function>| {
+			functions.iter().map(|f| f.name().to_string()).collect::<Vec<_>>()
+		};
+
+		assert!(<()>::host_functions().is_empty());
+		assert_eq!(
+			names(<(First, Second)>::host_functions()),
+			vec!["first", "second_a", "second_b"],
+		);
+		assert_eq!(
+			names(<(Second, (), First)>::host_functions()),
+			vec!["second_a", "second_b", "first"],
+		);
+	}
 }
@@ -98,6 +98,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_version: 4,
 	impl_version: 4,
 	apis: RUNTIME_API_VERSIONS,
+	host_interfaces: version::create_host_interfaces_vec!(),
 };
 
 pub const MILLISECS_PER_BLOCK: u64 = 6000;
@@ -55,6 +55,7 @@ macro_rules! new_full_start {
 	($config:expr) => {{
 		type RpcExtension = jsonrpc_core::IoHandler<substrate_rpc::Metadata>;
 		let mut import_setup = None;
+		let mut warp_sync_verifier = None;
 		let inherent_data_providers = inherents::InherentDataProviders::new();
 
 		let builder = substrate_service::ServiceBuilder::new_full::<
@@ -74,6 +75,7 @@ macro_rules! new_full_start {
 						client.clone(), &*client, select_chain
 					)?;
 				let justification_import = grandpa_block_import.clone();
+				warp_sync_verifier = Some(grandpa_block_import.warp_sync_verifier());
 
 				let (block_import, babe_link) = babe::block_import(
 					babe::Config::get_or_compute(&*client)?,
@@ -95,6 +97,9 @@ macro_rules! new_full_start {
 				import_setup = Some((block_import, grandpa_link, babe_link));
 				Ok(import_queue)
 			})?
+			.with_warp_sync_verifier(|_client, _backend| warp_sync_verifier.take()
+				.ok_or_else(|| "The warp sync verifier is created along with the import queue".into())
+			)?
 			.with_rpc_extensions(|client, pool| -> RpcExtension {
 				node_rpc::create(client, pool)
 			})?;
@@ -116,12 +121,22 @@ macro_rules! new_full {
 			is_authority,
 			force_authoring,
 			name,
-			disable_grandpa
+			disable_grandpa,
+			sentry_nodes,
 		) = (
 			$config.roles.is_authority(),
 			$config.force_authoring,
 			$config.name.clone(),
-			$config.disable_grandpa
+			$config.disable_grandpa,
+			$config.network.sentry_mode.sentry_nodes().map(|nodes| {
+				nodes.iter().filter_map(|node| match node.parse() {
+					Ok(addr) => Some(addr),
+					Err(_) => {
+						log::warn!("Not a valid sentry node address: {}", node);
+						None
+					}
+				}).collect::<Vec<_>>()
+			}),
 		);
 
 		let (builder, mut import_setup, inherent_data_providers) = new_full_start!($config);
@@ -173,6 +188,7 @@ macro_rules! new_full {
 			let authority_discovery = authority_discovery::AuthorityDiscovery::new(
 				service.client(),
 				service.network(),
+				sentry_nodes,
 				dht_event_rx,
 			);
 			service.spawn_task(authority_discovery);
@@ -116,7 +116,7 @@ mod tests {
 	}
 
 	fn executor() -> NativeExecutor<Executor> {
-		NativeExecutor::new(WasmExecutionMethod::Interpreted, None)
+		NativeExecutor::new(WasmExecutionMethod::Interpreted, None, None)
 	}
 
 	fn set_heap_pages<E: Externalities>(ext: &mut E, heap_pages: u64) {
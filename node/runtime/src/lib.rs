@@ -85,8 +85,9 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// implementation changes and behavior does not, then leave spec_version as
 	// is and increment impl_version.
 	spec_version: 180,
-	impl_version: 180,
+	impl_version: 181,
 	apis: RUNTIME_API_VERSIONS,
+	host_interfaces: version::create_host_interfaces_vec!(),
 };
 
 /// Native version.
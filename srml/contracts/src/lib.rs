@@ -126,7 +126,7 @@ use support::{
 };
 use support::traits::{OnFreeBalanceZero, OnUnbalanced, Currency, Get, Time, Randomness};
 use system::{ensure_signed, RawOrigin, ensure_root};
-use primitives::storage::well_known_keys::CHILD_STORAGE_KEY_PREFIX;
+use primitives::storage::well_known_keys::{CHILD_STORAGE_KEY_PREFIX, CHILD_TRIE_UNIQUE_IDS};
 
 pub type CodeHash<T> = <T as system::Trait>::Hash;
 pub type TrieId = Vec<u8>;
@@ -852,6 +852,13 @@ decl_storage! {
 		/// The price of one unit of gas.
 		GasPrice get(gas_price) config(): BalanceOf<T> = 1.into();
 	}
+	add_extra_genesis {
+		build(|_config: &GenesisConfig<T>| {
+			// Give the tries of the contracts unique ids, so that removing a contract discards its
+			// storage in constant time.
+			runtime_io::set_storage(CHILD_TRIE_UNIQUE_IDS, &[1]);
+		});
+	}
 }
 
 impl<T: Trait> OnFreeBalanceZero<T::AccountId> for Module<T> {
@@ -71,6 +71,9 @@ use proc_macro::TokenStream;
 ///   * `prefix`: `$module_prefix ++ " " ++ $storage_name`
 ///   * `Hasher`: $hash
 ///
+///   Maps with `hasher(blake2_128_concat)` also implement
+///   [`IterableStorageMap`](../srml_support/storage/trait.IterableStorageMap.html).
+///
 /// * Linked map: `Foo: linked_map hasher($hash) type => type`: Implements the
 ///   [`StorageLinkedMap`](../srml_support/storage/trait.StorageLinkedMap.html) trait using the
 ///   [`StorageLinkedMap generator`](../srml_support/storage/generator/trait.StorageLinkedMap.html).
@@ -117,6 +120,8 @@ use proc_macro::TokenStream;
 /// * `twox_256` - TwoX with with 256bit.
 /// * `blake2_128` - Blake2 with 128bit.
 /// * `blake2_256` - Blake2 with 256bit.
+/// * `blake2_128_concat` - Blake2 with 128bit + key concatenated, with the entries stored under a
+///   common prefix so they can be iterated with their keys. Only supported by `map`.
 ///
 /// Basic storage can be extended as such:
 ///
@@ -371,6 +371,7 @@ pub enum HasherKind {
 	Twox256,
 	Twox128,
 	Twox64Concat,
+	Blake2_128Concat,
 }
 
 impl HasherKind {
@@ -381,6 +382,7 @@ impl HasherKind {
 			HasherKind::Twox256 => quote!( Twox256 ),
 			HasherKind::Twox128 => quote!( Twox128 ),
 			HasherKind::Twox64Concat => quote!( Twox64Concat ),
+			HasherKind::Blake2_128Concat => quote!( Blake2_128Concat ),
 		}
 	}
 
@@ -391,6 +393,7 @@ impl HasherKind {
 			HasherKind::Twox256 => quote!( StorageHasher::Twox256 ),
 			HasherKind::Twox128 => quote!( StorageHasher::Twox128 ),
 			HasherKind::Twox64Concat => quote!( StorageHasher::Twox64Concat ),
+			HasherKind::Blake2_128Concat => quote!( StorageHasher::Blake2_128Concat ),
 		}
 	}
 }
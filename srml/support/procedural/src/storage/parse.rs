@@ -34,6 +34,7 @@ mod keyword {
 	syn::custom_keyword!(twox_256);
 	syn::custom_keyword!(twox_128);
 	syn::custom_keyword!(twox_64_concat);
+	syn::custom_keyword!(blake2_128_concat);
 	syn::custom_keyword!(hasher);
 }
 
@@ -177,6 +178,7 @@ enum Hasher {
 	Twox256(keyword::twox_256),
 	Twox128(keyword::twox_128),
 	Twox64Concat(keyword::twox_64_concat),
+	Blake2_128Concat(keyword::blake2_128_concat),
 }
 
 #[derive(Parse, ToTokens, Debug)]
@@ -205,10 +207,24 @@ impl From<Hasher> for super::HasherKind {
 			Hasher::Twox256(_) => super::HasherKind::Twox256,
 			Hasher::Twox128(_) => super::HasherKind::Twox128,
 			Hasher::Twox64Concat(_) => super::HasherKind::Twox64Concat,
+			Hasher::Blake2_128Concat(_) => super::HasherKind::Blake2_128Concat,
 		}
 	}
 }
 
+/// Returns an error if `hasher` can only be used by maps.
+fn ensure_not_map_only(hasher: &Hasher) -> syn::Result<()> {
+	use syn::spanned::Spanned;
+
+	match hasher {
+		Hasher::Blake2_128Concat(keyword) => Err(syn::Error::new(
+			keyword.span(),
+			"`blake2_128_concat` can only be used as the hasher of a `map`",
+		)),
+		_ => Ok(()),
+	}
+}
+
 fn get_module_instance(
 	instance: Option<syn::Ident>,
 	instantiable: Option<syn::Ident>,
@@ -329,17 +345,30 @@ pub fn parse(input: syn::parse::ParseStream) -> syn::Result<super::DeclStorageDe
 			),
 			DeclStorageType::LinkedMap(map) => super::StorageLineTypeDef::LinkedMap(
 				super::MapDef {
-					hasher: map.hasher.inner.map(Into::into)
-						.unwrap_or(super::HasherKind::Blake2_256),
+					hasher: {
+						if let Some(hasher) = map.hasher.inner.as_ref() {
+							ensure_not_map_only(&hasher.inner.content)?;
+						}
+						map.hasher.inner.map(Into::into)
+							.unwrap_or(super::HasherKind::Blake2_256)
+					},
 					key: map.key,
 					value: map.value,
 				}
 			),
 			DeclStorageType::DoubleMap(map) => super::StorageLineTypeDef::DoubleMap(
 				super::DoubleMapDef {
-					hasher1: map.hasher.inner.map(Into::into)
-						.unwrap_or(super::HasherKind::Blake2_256),
-					hasher2: map.key2_hasher.into(),
+					hasher1: {
+						if let Some(hasher) = map.hasher.inner.as_ref() {
+							ensure_not_map_only(&hasher.inner.content)?;
+						}
+						map.hasher.inner.map(Into::into)
+							.unwrap_or(super::HasherKind::Blake2_256)
+					},
+					hasher2: {
+						ensure_not_map_only(&map.key2_hasher)?;
+						map.key2_hasher.into()
+					},
 					key1: map.key1,
 					key2: map.key2.content,
 					value: map.value,
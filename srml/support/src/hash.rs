@@ -27,6 +27,7 @@ pub trait Hashable: Sized {
 	fn twox_128(&self) -> [u8; 16];
 	fn twox_256(&self) -> [u8; 32];
 	fn twox_64_concat(&self) -> Vec<u8>;
+	fn blake2_128_concat(&self) -> Vec<u8>;
 }
 
 impl<T: Codec> Hashable for T {
@@ -45,12 +46,21 @@ impl<T: Codec> Hashable for T {
 	fn twox_64_concat(&self) -> Vec<u8> {
 		self.using_encoded(Twox64Concat::hash)
 	}
+	fn blake2_128_concat(&self) -> Vec<u8> {
+		self.using_encoded(Blake2_128Concat::hash)
+	}
 }
 
 /// Hasher to use to hash keys to insert to storage.
 pub trait StorageHasher: 'static {
 	type Output: AsRef<[u8]>;
 	fn hash(x: &[u8]) -> Self::Output;
+
+	/// The prefix under which the entries of the map with the given `prefix` are stored, if they
+	/// are stored under `map_prefix(prefix) ++ hash(key)` instead of `hash(prefix ++ key)`.
+	fn map_prefix(_prefix: &[u8]) -> Option<Vec<u8>> {
+		None
+	}
 }
 
 /// Hash storage keys with `concat(twox64(key), key)`
@@ -84,6 +94,23 @@ impl StorageHasher for Blake2_256 {
 	}
 }
 
+/// Hash storage keys with `concat(blake2_128(key), key)`, and store the entries of a map under
+/// `twox128(prefix)`, so they can be iterated and their keys decoded.
+pub struct Blake2_128Concat;
+impl StorageHasher for Blake2_128Concat {
+	type Output = Vec<u8>;
+	fn hash(x: &[u8]) -> Vec<u8> {
+		blake2_128(x)
+			.iter()
+			.chain(x.into_iter())
+			.cloned()
+			.collect::<Vec<_>>()
+	}
+	fn map_prefix(prefix: &[u8]) -> Option<Vec<u8>> {
+		Some(twox_128(prefix).to_vec())
+	}
+}
+
 /// Hash storage keys with twox 128
 pub struct Twox128;
 impl StorageHasher for Twox128 {
@@ -111,4 +138,10 @@ mod tests {
 		let r = Twox64Concat::hash(b"foo");
 		assert_eq!(r.split_at(8), (&twox_128(b"foo")[..8], &b"foo"[..]))
 	}
+
+	#[test]
+	fn test_blake2_128_concat() {
+		let r = Blake2_128Concat::hash(b"foo");
+		assert_eq!(r.split_at(16), (&blake2_128(b"foo")[..], &b"foo"[..]))
+	}
 }
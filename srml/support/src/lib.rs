@@ -62,8 +62,12 @@ pub mod unsigned;
 pub mod error;
 pub mod traits;
 
-pub use self::hash::{Twox256, Twox128, Blake2_256, Blake2_128, Twox64Concat, Hashable};
-pub use self::storage::{StorageValue, StorageMap, StorageLinkedMap, StorageDoubleMap};
+pub use self::hash::{
+	Twox256, Twox128, Blake2_256, Blake2_128, Twox64Concat, Blake2_128Concat, Hashable,
+};
+pub use self::storage::{
+	StorageValue, StorageMap, IterableStorageMap, StorageLinkedMap, StorageDoubleMap,
+};
 pub use self::dispatch::{Parameter, Callable, IsSubType};
 pub use sr_primitives::{self, ConsensusEngineId, print, traits::Printable};
 
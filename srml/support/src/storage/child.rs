@@ -87,6 +87,9 @@ pub fn exists(storage_key: &[u8], key: &[u8]) -> bool {
 }
 
 /// Remove all `storage_key` key/values
+///
+/// This takes constant time, independent of the number of key/values in the child trie, if the
+/// child trie has a unique id: see `well_known_keys::CHILD_TRIE_UNIQUE_IDS`.
 pub fn kill_storage(storage_key: &[u8]) {
 	runtime_io::kill_child_storage(storage_key)
 }
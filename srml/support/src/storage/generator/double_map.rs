@@ -137,6 +137,14 @@ where
 		unhashed::kill_prefix(Self::storage_double_map_final_key1(k1).as_ref())
 	}
 
+	fn iter_prefix<KArg1>(k1: KArg1) -> storage::generator::PrefixIterator<V>
+		where KArg1: EncodeLike<K1>
+	{
+		storage::generator::PrefixIterator::new(
+			Self::storage_double_map_final_key1(k1).as_ref().to_vec()
+		)
+	}
+
 	fn mutate<KArg1, KArg2, R, F>(k1: KArg1, k2: KArg2, f: F) -> R
 	where
 		KArg1: EncodeLike<K1>,
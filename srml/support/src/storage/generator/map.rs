@@ -16,9 +16,11 @@
 
 #[cfg(not(feature = "std"))]
 use rstd::prelude::*;
-use rstd::borrow::Borrow;
-use codec::{FullCodec, FullEncode, Encode, EncodeLike, Ref, EncodeAppend};
-use crate::{storage::{self, unhashed}, hash::StorageHasher, traits::Len};
+use rstd::{borrow::Borrow, marker::PhantomData};
+use codec::{FullCodec, FullEncode, Decode, Encode, EncodeLike, Ref, EncodeAppend};
+use crate::{
+	storage::{self, unhashed}, hash::{StorageHasher, Blake2_128Concat}, traits::Len,
+};
 
 /// Generator for `StorageMap` used by `decl_storage`.
 ///
@@ -27,6 +29,12 @@ use crate::{storage::{self, unhashed}, hash::StorageHasher, traits::Len};
 /// Hasher(prefix ++ key)
 /// ```
 ///
+/// If the hasher has a `map_prefix`, like `Blake2_128Concat`, the value is stored at the
+/// following key instead, which keeps all entries of the map under a common prefix:
+/// ```nocompile
+/// Hasher::map_prefix(prefix) ++ Hasher(key)
+/// ```
+///
 /// # Warning
 ///
 /// If the keys are not trusted (e.g. can be set by a user), a cryptographic `hasher` such as
@@ -48,13 +56,99 @@ pub trait StorageMap<K: FullEncode, V: FullCodec> {
 	fn from_query_to_optional_value(v: Self::Query) -> Option<V>;
 
 	/// Generate the full key used in top storage.
-	fn storage_map_final_key<KeyArg>(key: KeyArg) -> <Self::Hasher as StorageHasher>::Output
+	fn storage_map_final_key<KeyArg>(key: KeyArg) -> Vec<u8>
 	where
 		KeyArg: EncodeLike<K>,
 	{
-		let mut final_key = Self::prefix().to_vec();
-		key.borrow().encode_to(&mut final_key);
-		Self::Hasher::hash(&final_key)
+		match Self::Hasher::map_prefix(Self::prefix()) {
+			Some(mut final_key) => {
+				key.borrow().using_encoded(|key| {
+					final_key.extend_from_slice(Self::Hasher::hash(key).as_ref())
+				});
+				final_key
+			},
+			None => {
+				let mut final_key = Self::prefix().to_vec();
+				key.borrow().encode_to(&mut final_key);
+				Self::Hasher::hash(&final_key).as_ref().to_vec()
+			},
+		}
+	}
+}
+
+/// Iterator over the entries in storage whose final key starts with a given prefix, in
+/// lexicographic order of their final keys.
+///
+/// The entries are found with `next_storage_key`, so the iterator reflects the storage at the time
+/// `next` is called and entries inserted before the current position are not returned.
+///
+/// The entries of a `StorageMap` only share a prefix in storage if its hasher has a `map_prefix`,
+/// see `IterableStorageMap`, or if they are nested under one, like the entries of a
+/// `StorageDoubleMap` with the same first key.
+pub struct PrefixIterator<V> {
+	prefix: Vec<u8>,
+	previous_key: Vec<u8>,
+	_phantom: PhantomData<V>,
+}
+
+impl<V> PrefixIterator<V> {
+	/// Create an iterator over all entries whose final key starts with `prefix`.
+	pub fn new(prefix: Vec<u8>) -> Self {
+		PrefixIterator {
+			previous_key: prefix.clone(),
+			prefix,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<V: Decode> Iterator for PrefixIterator<V> {
+	/// The final key of the entry and its value.
+	type Item = (Vec<u8>, V);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let key = runtime_io::next_storage_key(&self.previous_key)
+				.filter(|key| key.starts_with(&self.prefix))?;
+			self.previous_key = key.clone();
+
+			match unhashed::get(&key) {
+				Some(value) => return Some((key, value)),
+				// The entry was removed in the meantime, continue with the next one.
+				None => continue,
+			}
+		}
+	}
+}
+
+/// Iterator over the entries of an `IterableStorageMap`, with their keys decoded from their final
+/// keys.
+pub struct MapIterator<K, V> {
+	entries: PrefixIterator<V>,
+	key_offset: usize,
+	_phantom: PhantomData<K>,
+}
+
+impl<K, V> MapIterator<K, V> {
+	/// Create an iterator over the entries whose final key starts with `prefix`, and ends with
+	/// their encoded key after `key_offset` bytes.
+	pub fn new(prefix: Vec<u8>, key_offset: usize) -> Self {
+		MapIterator {
+			entries: PrefixIterator::new(prefix),
+			key_offset,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<K: Decode, V: Decode> Iterator for MapIterator<K, V> {
+	type Item = (K, V);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (final_key, value) = self.entries.next()?;
+		let key = K::decode(&mut &final_key[self.key_offset..])
+			.expect("the final keys of the entries of the map end with their encoded key; qed");
+		Some((key, value))
 	}
 }
 
@@ -62,7 +156,7 @@ impl<K: FullEncode, V: FullCodec, G: StorageMap<K, V>> storage::StorageMap<K, V>
 	type Query = G::Query;
 
 	fn hashed_key_for<KeyArg: EncodeLike<K>>(key: KeyArg) -> Vec<u8> {
-		Self::storage_map_final_key(key).as_ref().to_vec()
+		Self::storage_map_final_key(key)
 	}
 
 	fn swap<KeyArg1: EncodeLike<K>, KeyArg2: EncodeLike<K>>(key1: KeyArg1, key2: KeyArg2) {
@@ -170,3 +264,19 @@ impl<K: FullEncode, V: FullCodec, G: StorageMap<K, V>> storage::StorageMap<K, V>
 		}
 	}
 }
+
+impl<K, V, G> storage::IterableStorageMap<K, V> for G
+where
+	K: FullCodec,
+	V: FullCodec,
+	G: StorageMap<K, V, Hasher = Blake2_128Concat>,
+{
+	fn iter() -> MapIterator<K, V> {
+		let prefix = G::Hasher::map_prefix(G::prefix()).expect(
+			"`Blake2_128Concat` stores the entries of a map under a prefix; qed"
+		);
+		// The final keys are `prefix ++ blake2_128(key) ++ key`.
+		let key_offset = prefix.len() + 16;
+		MapIterator::new(prefix, key_offset)
+	}
+}
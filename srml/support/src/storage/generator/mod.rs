@@ -27,6 +27,6 @@ mod double_map;
 mod value;
 
 pub use linked_map::{StorageLinkedMap, Enumerator, Linkage};
-pub use map::StorageMap;
+pub use map::{StorageMap, PrefixIterator, MapIterator};
 pub use double_map::StorageDoubleMap;
 pub use value::StorageValue;
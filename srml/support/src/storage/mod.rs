@@ -27,6 +27,21 @@ pub mod hashed;
 pub mod child;
 pub mod generator;
 
+/// Execute `f` in a new storage transaction.
+///
+/// All storage changes made by `f` are kept if it returns `Ok` and reverted if it returns `Err`.
+/// Transactions can be nested, rolling back an inner transaction only reverts the changes made by
+/// the inner `f`.
+pub fn with_transaction<R, E>(f: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+	runtime_io::storage_start_transaction();
+	let result = f();
+	match result {
+		Ok(_) => runtime_io::storage_commit_transaction(),
+		Err(_) => runtime_io::storage_rollback_transaction(),
+	}
+	result
+}
+
 /// A trait for working with macro-generated storage values under the substrate storage API.
 pub trait StorageValue<T: FullCodec> {
 	/// The type that get/take return.
@@ -155,6 +170,17 @@ pub trait StorageMap<K: FullEncode, V: FullCodec> {
 		where V: codec::DecodeLength + Len;
 }
 
+/// A strongly-typed map in storage whose entries can be iterated.
+///
+/// Implemented by the maps declared with `hasher(blake2_128_concat)`, which store all their
+/// entries under a common prefix, with their keys appended to their final keys.
+pub trait IterableStorageMap<K: FullCodec, V: FullCodec>: StorageMap<K, V> {
+	/// Iterate over all entries of the map, in the order of their final keys.
+	///
+	/// The items are the keys of the entries and their values.
+	fn iter() -> generator::MapIterator<K, V>;
+}
+
 /// A strongly-typed linked map in storage.
 ///
 /// Similar to `StorageMap` but allows to enumerate other elements and doesn't implement append.
@@ -239,6 +265,10 @@ pub trait StorageDoubleMap<K1: FullEncode, K2: FullEncode, V: FullCodec> {
 
 	fn remove_prefix<KArg1>(k1: KArg1) where KArg1: ?Sized + EncodeLike<K1>;
 
+	/// Iterate over all entries that have the given first key, in the order of their final keys.
+	fn iter_prefix<KArg1>(k1: KArg1) -> generator::PrefixIterator<V>
+		where KArg1: ?Sized + EncodeLike<K1>;
+
 	fn mutate<KArg1, KArg2, R, F>(k1: KArg1, k2: KArg2, f: F) -> R
 	where
 		KArg1: EncodeLike<K1>,
@@ -273,3 +303,74 @@ pub trait StorageDoubleMap<K1: FullEncode, K2: FullEncode, V: FullCodec> {
 		Items: IntoIterator<Item=EncodeLikeItem> + Clone + EncodeLike<V>,
 		Items::IntoIter: ExactSizeIterator;
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use runtime_io::TestExternalities;
+
+	#[test]
+	fn with_transaction_works() {
+		TestExternalities::default().execute_with(|| {
+			unhashed::put(b"outer", &1u32);
+
+			let result: Result<(), ()> = with_transaction(|| {
+				unhashed::put(b"outer", &2u32);
+
+				let inner: Result<(), ()> = with_transaction(|| {
+					unhashed::put(b"inner", &3u32);
+					unhashed::kill(b"outer");
+					Err(())
+				});
+				assert_eq!(inner, Err(()));
+				assert_eq!(unhashed::get::<u32>(b"outer"), Some(2));
+				assert_eq!(unhashed::get::<u32>(b"inner"), None);
+
+				let inner: Result<(), ()> = with_transaction(|| {
+					unhashed::put(b"inner", &4u32);
+					Ok(())
+				});
+				assert_eq!(inner, Ok(()));
+				assert_eq!(unhashed::get::<u32>(b"inner"), Some(4));
+
+				Err(())
+			});
+
+			assert_eq!(result, Err(()));
+			assert_eq!(unhashed::get::<u32>(b"outer"), Some(1));
+			assert_eq!(unhashed::get::<u32>(b"inner"), None);
+
+			assert_eq!(with_transaction(|| -> Result<(), ()> {
+				unhashed::put(b"inner", &5u32);
+				Ok(())
+			}), Ok(()));
+			assert_eq!(unhashed::get::<u32>(b"inner"), Some(5));
+		});
+	}
+
+	#[test]
+	fn prefix_iterator_works() {
+		TestExternalities::default().execute_with(|| {
+			unhashed::put(b"abc", &1u32);
+			unhashed::put(b"abd", &2u32);
+			unhashed::put(b"abe", &3u32);
+			unhashed::put(b"ac", &4u32);
+			unhashed::put(b"a", &5u32);
+
+			let entries = generator::PrefixIterator::<u32>::new(b"ab".to_vec()).collect::<Vec<_>>();
+			assert_eq!(entries, vec![
+				(b"abc".to_vec(), 1),
+				(b"abd".to_vec(), 2),
+				(b"abe".to_vec(), 3),
+			]);
+
+			unhashed::kill(b"abd");
+			let values = generator::PrefixIterator::<u32>::new(b"ab".to_vec())
+				.map(|(_, value)| value)
+				.collect::<Vec<_>>();
+			assert_eq!(values, vec![1, 3]);
+
+			assert_eq!(generator::PrefixIterator::<u32>::new(b"b".to_vec()).count(), 0);
+		});
+	}
+}
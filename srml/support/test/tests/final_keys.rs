@@ -17,7 +17,7 @@
 use runtime_io::with_storage;
 use support::storage::unhashed;
 use codec::Encode;
-use support::{StorageDoubleMap, StorageLinkedMap, StorageMap, StorageValue};
+use support::{IterableStorageMap, StorageDoubleMap, StorageLinkedMap, StorageMap, StorageValue};
 
 mod no_instance {
 	use codec::{Encode, Decode, EncodeLike};
@@ -37,6 +37,7 @@ mod no_instance {
 
 			pub Map: map u32 => u32;
 			pub Map2: map hasher(twox_128) u32 => u32;
+			pub Map3: map hasher(blake2_128_concat) u32 => u32;
 
 			pub LinkedMap: linked_map u32 => u32;
 			pub LinkedMap2: linked_map hasher(twox_128) u32 => u32;
@@ -101,6 +102,12 @@ fn final_keys_no_instance() {
 		k.extend(1u32.encode());
 		assert_eq!(unhashed::get::<u32>(&runtime_io::twox_128(&k)), Some(2u32));
 
+		no_instance::Map3::insert(1, 2);
+		let mut k = runtime_io::twox_128(b"FinalKeysNone Map3").to_vec();
+		k.extend(&runtime_io::blake2_128(&1u32.encode()));
+		k.extend(1u32.encode());
+		assert_eq!(unhashed::get::<u32>(&k), Some(2u32));
+
 		let head = b"head of FinalKeysNone LinkedMap".to_vec();
 		assert_eq!(unhashed::get::<u32>(&runtime_io::blake2_256(&head)), None);
 
@@ -225,3 +232,19 @@ fn final_keys_instance_2() {
 		assert_eq!(unhashed::get::<u32>(&k), Some(3u32));
 	});
 }
+
+#[test]
+fn prefixed_maps_can_be_iterated() {
+	with_storage(&mut Default::default(), || {
+		no_instance::Map3::insert(1, 2);
+		no_instance::Map3::insert(3, 4);
+		no_instance::Map::insert(5, 6);
+
+		let mut entries = no_instance::Map3::iter().collect::<Vec<_>>();
+		entries.sort();
+		assert_eq!(entries, vec![(1, 2), (3, 4)]);
+
+		no_instance::Map3::remove(1);
+		assert_eq!(no_instance::Map3::iter().collect::<Vec<_>>(), vec![(3, 4)]);
+	});
+}
@@ -21,7 +21,7 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use rstd::prelude::*;
-use support::{decl_module, decl_event, Parameter};
+use support::{decl_module, decl_event, Parameter, storage::with_transaction};
 use system::ensure_root;
 use sr_primitives::{traits::Dispatchable, weights::SimpleDispatchInfo, DispatchError};
 
@@ -47,11 +47,14 @@ decl_module! {
 		fn deposit_event() = default;
 
 		/// Send a batch of dispatch calls (only root).
+		///
+		/// Every call is executed in its own storage transaction, so the storage changes of a
+		/// failing call are reverted without affecting the other calls.
 		#[weight = SimpleDispatchInfo::FreeOperational]
 		fn batch(origin, calls: Vec<<T as Trait>::Call>) {
 			ensure_root(origin)?;
 			let results = calls.into_iter()
-				.map(|call| call.dispatch(system::RawOrigin::Root.into()))
+				.map(|call| with_transaction(|| call.dispatch(system::RawOrigin::Root.into())))
 				.map(|res| res.map_err(Into::into))
 				.collect::<Vec<_>>();
 			Self::deposit_event(Event::BatchExecuted(results));